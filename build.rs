@@ -1,10 +1,21 @@
-extern crate capnpc;
+use std::process::Command;
 
 fn main() {
-    // Compile capnp schema source code
-    capnpc::CompilerCommand::new()
-        .file("src/spec/event.capnp")
-        .output_path("")
-        .run()
-        .expect("schema compilation to succeed");
+    // Embed the commit that the server was built from, if it was built from
+    // a git checkout, such that operators can tell which build is deployed
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GNOMEGG_GIT_COMMIT={}", commit);
+
+    // The commit changes whenever HEAD is moved, or the checked out branch
+    // advances
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
 }
@@ -1,16 +0,0 @@
--- Please Steven, don't nuke the WOOF posters
-DROP TABLE IF EXISTS users;
-
-DROP TABLE IF EXISTS ids;
-
-DROP TABLE IF EXISTS reddit_connected;
-
-DROP TABLE IF EXISTS twitch_connected;
-
-DROP TABLE IF EXISTS twitter_connected;
-
-DROP TABLE IF EXISTS google_connected;
-
-DROP TABLE IF EXISTS discord_connected;
-
-DROP TABLE IF EXISTS roles;
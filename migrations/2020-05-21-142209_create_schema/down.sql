@@ -0,0 +1,33 @@
+DROP TABLE IF EXISTS gifted_subscriptions;
+DROP TABLE IF EXISTS donations;
+DROP TABLE IF EXISTS bot_commands;
+DROP TABLE IF EXISTS chat_stats;
+DROP TABLE IF EXISTS announcements;
+DROP TABLE IF EXISTS pins;
+DROP TABLE IF EXISTS poll_results;
+DROP TABLE IF EXISTS link_whitelist;
+DROP TABLE IF EXISTS flair_grants;
+DROP TABLE IF EXISTS flairs;
+DROP TABLE IF EXISTS subscriptions;
+DROP TABLE IF EXISTS emotes;
+DROP TABLE IF EXISTS ignores;
+DROP TABLE IF EXISTS whisper_reads;
+DROP TABLE IF EXISTS whispers;
+DROP TABLE IF EXISTS chat_history;
+DROP TABLE IF EXISTS audit_log;
+DROP TABLE IF EXISTS recovery_codes;
+DROP TABLE IF EXISTS two_factor;
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS last_seen;
+DROP TABLE IF EXISTS settings;
+DROP TABLE IF EXISTS username_history;
+DROP TABLE IF EXISTS bans;
+DROP TABLE IF EXISTS mutes;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS discord_connected;
+DROP TABLE IF EXISTS google_connected;
+DROP TABLE IF EXISTS twitter_connected;
+DROP TABLE IF EXISTS twitch_connected;
+DROP TABLE IF EXISTS reddit_connected;
+DROP TABLE IF EXISTS ids;
+DROP TABLE IF EXISTS users;
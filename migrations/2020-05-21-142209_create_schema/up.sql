@@ -0,0 +1,343 @@
+-- SQLite only stores signed integers, so unsigned columns are declared as
+-- BIGINT and checked when they are read or written.
+
+-- Users who have signed up for gnome.gg
+CREATE TABLE users (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       username VARCHAR(20) UNIQUE,
+       verified BOOLEAN NOT NULL,
+       nationality TEXT,
+       accepts_gifts BOOLEAN,
+       minecraft_name VARCHAR(16) UNIQUE,
+       created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       avatar VARCHAR(255)
+);
+
+-- SQLite has no ON UPDATE clause, so the time at which a user's profile was
+-- last modified is kept up to date by a trigger, unless it was set explicitly
+CREATE TRIGGER users_updated_at AFTER UPDATE ON users
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+       UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- IDs pertaining to each username in the database
+CREATE TABLE ids (
+       -- Assigned by the trigger below, since only a primary key may be
+       -- incremented automatically
+       id BIGINT UNIQUE,
+       username VARCHAR(20) PRIMARY KEY,
+       user_id BIGINT NOT NULL UNIQUE
+);
+
+CREATE TRIGGER ids_id AFTER INSERT ON ids
+WHEN NEW.id IS NULL
+BEGIN
+       UPDATE ids SET id = NEW.rowid WHERE rowid = NEW.rowid;
+END;
+
+-- Users who have used reddit to connect to their accounts.
+CREATE TABLE reddit_connected (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       id_hash BLOB UNIQUE,
+       id_value TEXT
+);
+
+-- Users who have used twitch to connect their accounts.
+CREATE TABLE twitch_connected (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       id_hash BLOB UNIQUE,
+       id_value TEXT
+);
+
+-- Users who have used twitter to connect their accounts.
+CREATE TABLE twitter_connected (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       id_hash BLOB UNIQUE,
+       id_value TEXT
+);
+
+-- Users who have used a google account to connect their accounts.
+CREATE TABLE google_connected (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       id_hash BLOB UNIQUE,
+       id_value TEXT
+);
+
+-- Users who have used a discord account to connect their accounts.
+CREATE TABLE discord_connected (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       id_hash BLOB UNIQUE,
+       id_value TEXT
+);
+
+-- Permissions for each user registered for gnome.gg
+CREATE TABLE roles (
+       -- Assigned by the trigger below, since only a primary key may be
+       -- incremented automatically
+       id BIGINT UNIQUE,
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       administrator BOOLEAN,
+       moderator BOOLEAN,
+       vip BOOLEAN,
+       protected BOOLEAN,
+       subscriber BOOLEAN,
+       bot BOOLEAN
+);
+
+CREATE TRIGGER roles_id AFTER INSERT ON roles
+WHEN NEW.id IS NULL
+BEGIN
+       UPDATE roles SET id = NEW.rowid WHERE rowid = NEW.rowid;
+END;
+
+-- User who have been muted
+CREATE TABLE mutes (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       duration BIGINT NOT NULL,
+       initiated_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE bans (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       duration BIGINT,
+       initiated_at TIMESTAMP NOT NULL,
+       ip TEXT
+);
+
+-- Usernames previously held by each user
+CREATE TABLE username_history (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       user_id BIGINT NOT NULL,
+       username VARCHAR(20) NOT NULL,
+       changed_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX username_history_user_id ON username_history (user_id);
+
+-- The chat preferences of each user
+CREATE TABLE settings (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       preferences TEXT NOT NULL
+);
+
+-- The most recent activity of each user
+CREATE TABLE last_seen (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       connected_at TIMESTAMP NULL,
+       messaged_at TIMESTAMP NULL
+);
+
+-- Long-lived tokens with which users obtain new session tokens
+CREATE TABLE refresh_tokens (
+       token_hash BLOB PRIMARY KEY,
+       user_id BIGINT NOT NULL,
+       session_id VARCHAR(48) NOT NULL,
+       issued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       used BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX refresh_tokens_session_id ON refresh_tokens (session_id);
+CREATE INDEX refresh_tokens_user_id ON refresh_tokens (user_id);
+
+-- The TOTP secrets with which users holding privileged roles prove their
+-- identity when logging in
+CREATE TABLE two_factor (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       secret TEXT NOT NULL,
+       confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+       last_step BIGINT,
+       enrolled_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Single-use codes with which users may log in without their authenticator
+-- app
+CREATE TABLE recovery_codes (
+       code_hash BLOB PRIMARY KEY,
+       user_id BIGINT NOT NULL
+);
+
+CREATE INDEX recovery_codes_user_id ON recovery_codes (user_id);
+
+-- A record of sensitive actions, and of each action performed while
+-- impersonating another user
+CREATE TABLE audit_log (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       actor_id BIGINT,
+       impersonator_id BIGINT,
+       action VARCHAR(255) NOT NULL,
+       status SMALLINT,
+       detail TEXT,
+       performed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_actor_id ON audit_log (actor_id);
+CREATE INDEX audit_log_impersonator_id ON audit_log (impersonator_id);
+
+-- An archive of each message sent to the chat
+CREATE TABLE chat_history (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       sender_id BIGINT NOT NULL,
+       sender VARCHAR(20) NOT NULL,
+       contents TEXT NOT NULL,
+       sent_at TIMESTAMP NOT NULL,
+       deleted_at TIMESTAMP NULL,
+       edited_at TIMESTAMP NULL,
+       original_contents TEXT NULL
+);
+
+CREATE INDEX chat_history_sender_id_sent_at ON chat_history (sender_id, sent_at);
+CREATE INDEX chat_history_sent_at ON chat_history (sent_at);
+
+-- Private messages sent between users
+CREATE TABLE whispers (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       sender_id BIGINT NOT NULL,
+       sender VARCHAR(20) NOT NULL,
+       recipient_id BIGINT NOT NULL,
+       contents TEXT NOT NULL,
+       sent_at TIMESTAMP NOT NULL,
+       delivered_at TIMESTAMP NULL
+);
+
+CREATE INDEX whispers_sender_id_recipient_id ON whispers (sender_id, recipient_id);
+CREATE INDEX whispers_recipient_id_delivered_at ON whispers (recipient_id, delivered_at);
+
+-- The most recent whisper that each user has read in each of their
+-- conversations
+CREATE TABLE whisper_reads (
+       user_id BIGINT NOT NULL,
+       peer_id BIGINT NOT NULL,
+       last_read_id BIGINT NOT NULL,
+       PRIMARY KEY (user_id, peer_id)
+);
+
+-- The users whose messages each user has chosen not to receive
+CREATE TABLE ignores (
+       user_id BIGINT NOT NULL,
+       ignored_id BIGINT NOT NULL,
+       ignored_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       PRIMARY KEY (user_id, ignored_id)
+);
+
+-- Each of the emotes that may be used in the chat
+CREATE TABLE emotes (
+       code VARCHAR(32) NOT NULL PRIMARY KEY,
+       image VARCHAR(255) NOT NULL,
+       tier TINYINT NULL
+);
+
+-- The subscription held by each subscribed user
+CREATE TABLE subscriptions (
+       user_id BIGINT NOT NULL PRIMARY KEY,
+       tier TINYINT NOT NULL,
+       expires_at TIMESTAMP NULL
+);
+
+-- Each of the badges that may be displayed alongside a chatter's username
+CREATE TABLE flairs (
+       name VARCHAR(32) NOT NULL PRIMARY KEY,
+       label VARCHAR(64) NOT NULL,
+       image VARCHAR(255) NULL,
+       role VARCHAR(32) NULL,
+       priority SMALLINT NOT NULL DEFAULT 0
+);
+
+-- Each of the flairs granted to individual users, regardless of their roles
+CREATE TABLE flair_grants (
+       user_id BIGINT NOT NULL,
+       flair VARCHAR(32) NOT NULL,
+       PRIMARY KEY (user_id, flair)
+);
+
+-- Each of the domains that chatters may link to
+CREATE TABLE link_whitelist (
+       domain VARCHAR(255) NOT NULL PRIMARY KEY
+);
+
+-- Each of the polls that have ended, alongside their results
+CREATE TABLE poll_results (
+       id BIGINT NOT NULL PRIMARY KEY,
+       question VARCHAR(255) NOT NULL,
+       options TEXT NOT NULL,
+       totals TEXT NOT NULL,
+       weighted BOOLEAN NOT NULL DEFAULT FALSE,
+       created_by BIGINT NOT NULL,
+       started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       ended_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Messages pinned above the chat by moderators
+CREATE TABLE pins (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       pinned_by_id BIGINT NOT NULL,
+       pinned_by VARCHAR(20) NOT NULL,
+       contents TEXT NOT NULL,
+       pinned_at TIMESTAMP NOT NULL,
+       expires_at TIMESTAMP NULL,
+       unpinned_at TIMESTAMP NULL
+);
+
+CREATE INDEX pins_unpinned_at ON pins (unpinned_at);
+
+-- Messages sent to the entire chat by the server, once or on a schedule
+CREATE TABLE announcements (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       message TEXT NOT NULL,
+       schedule VARCHAR(255) NULL,
+       created_by BIGINT NULL,
+       next_run_at TIMESTAMP NOT NULL,
+       created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX announcements_next_run_at ON announcements (next_run_at);
+
+-- The number of public messages sent by each user on each day
+CREATE TABLE chat_stats (
+       user_id BIGINT NOT NULL,
+       day DATE NOT NULL,
+       lines BIGINT NOT NULL DEFAULT 0,
+       PRIMARY KEY (user_id, day)
+);
+
+CREATE INDEX chat_stats_day ON chat_stats (day);
+
+-- Each of the custom commands that chatters may invoke by sending !name
+CREATE TABLE bot_commands (
+       name VARCHAR(32) NOT NULL PRIMARY KEY,
+       handler VARCHAR(16) NOT NULL,
+       target VARCHAR(255) NOT NULL,
+       cooldown INT NOT NULL DEFAULT 0,
+       role VARCHAR(32) NULL
+);
+
+-- Each of the donations made to the streamer
+CREATE TABLE donations (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       donor_id BIGINT NULL,
+       donor VARCHAR(64) NOT NULL,
+       amount BIGINT NOT NULL,
+       currency VARCHAR(3) NOT NULL,
+       message TEXT NULL,
+       donated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX donations_donated_at ON donations (donated_at);
+
+-- Each of the subscriptions gifted from one chatter to another
+CREATE TABLE gifted_subscriptions (
+       id INTEGER PRIMARY KEY AUTOINCREMENT,
+       gifter_id BIGINT NULL,
+       gifter VARCHAR(64) NOT NULL,
+       recipient_id BIGINT NOT NULL,
+       recipient VARCHAR(64) NOT NULL,
+       tier TINYINT NOT NULL,
+       months TINYINT NOT NULL DEFAULT 1,
+       amount BIGINT NOT NULL,
+       currency VARCHAR(3) NOT NULL,
+       message TEXT NULL,
+       gifted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX gifted_subscriptions_gifted_at ON gifted_subscriptions (gifted_at);
@@ -1,9 +1,18 @@
+// Argument lists in doc comments continue each item on an unindented line
+#![allow(clippy::doc_lazy_continuation)]
+
 #[macro_use]
 extern crate diesel;
 
+#[macro_use]
+extern crate diesel_migrations;
+
 #[macro_use]
 extern crate actix_web;
 
+// The derives and table! macro of diesel 1.4 expand to impls nested in
+// functions and constants
+#[allow(non_local_definitions)]
 #[macro_use]
 pub mod spec;
 pub mod ws_http_server;
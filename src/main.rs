@@ -1,3 +1,166 @@
-fn main() {
-    println!("Hello, world!");
+use gnomegg::ws_http_server::{
+    config::{Config, ConfigError},
+    jwt::{self, KeySet},
+    migrations,
+    modules::{oauth, Cache},
+    outbox::{Outbox, RedisOutbox},
+    server::{self, State},
+    telemetry, warmup,
+};
+
+use std::{env, io, path::Path, sync::Arc};
+
+#[actix_web::main]
+async fn main() -> io::Result<()> {
+    dotenv::dotenv().ok();
+
+    // Settings are read from the file named by CONFIG_FILE (gnomegg.toml by
+    // default), and overridden by the matching environment variables (e.g.
+    // DATABASE_URL overrides [database] url)
+    let config = Config::load()?;
+
+    // Spans and events are logged in the configured format (text or JSON)
+    // according to RUST_LOG, and spans are
+    // exported to the configured OTLP/HTTP collector, if any, when built with
+    // the otlp feature
+    telemetry::init(config.log_format(), config.otlp_endpoint())?;
+
+    let redis = redis::Client::open(config.redis_url())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // Connections to redis and MySQL are pooled across workers
+    let cache_pool = config
+        .redis_pool()
+        .build_redis(redis)
+        .map_err(io::Error::other)?;
+    let persistent_pool = config
+        .database_pool()
+        .build(config.database_url())
+        .map_err(io::Error::other)?;
+
+    // Bans, roles and usernames are read from the replica, if any
+    let replica_pool = match config.replica_url() {
+        Some(url) => Some(config.replica_pool().build(url).map_err(io::Error::other)?),
+        None => None,
+    };
+
+    // Moderation writes are queued in redis while MySQL is down, and
+    // replayed once it returns
+    let outbox = if config.queue_failed_writes() {
+        Some(Arc::new(RedisOutbox::new(
+            cache_pool.clone(),
+            config.key_prefix().to_owned(),
+        )) as Arc<dyn Outbox>)
+    } else {
+        None
+    };
+
+    let mut state = State::new(cache_pool, persistent_pool)
+        .with_replica(replica_pool)
+        .with_admin_token(config.admin_token().to_owned())
+        .with_trusted_proxies(config.trusted_proxies().to_vec())
+        .with_stream_webhook_secret(config.stream_webhook_secret().map(str::to_owned))
+        .with_live_announcement(config.live_announcement().map(str::to_owned))
+        .with_default_roles(config.default_roles().to_vec())
+        .with_moderation_layers(config.moderation_layers())
+        .with_breaker(config.breaker())
+        .with_load_shedder(config.load_shedder())
+        .with_compression(config.compression())
+        .with_retry_policy(config.retry_policy())
+        .with_local_cache(config.local_cache())
+        .with_key_prefix(config.key_prefix().to_owned())
+        .with_dispatcher(config.dispatcher())
+        .with_outbox(outbox)
+        .with_avatar_dir(config.avatar_dir().to_owned())
+        .with_frontend_dir(config.frontend_dir().map(Path::to_owned));
+
+    // Pending migrations are run before the server starts, unless disabled
+    // (e.g. when deploys migrate the database in a separate step). Running
+    // `gnomegg --migrate` runs them without starting the server.
+    let migrate_only = env::args().nth(1).as_deref() == Some("--migrate");
+    if migrate_only || config.migrate_on_startup() {
+        let persistent_conn = state.persistent_connection().map_err(io::Error::other)?;
+
+        migrations::run_pending(&persistent_conn, &mut io::stdout()).map_err(io::Error::other)?;
+
+        // Bans and mutes cached as JSON strings by earlier releases are
+        // converted to hashes. Sanctions that can't be converted are read
+        // from MySQL instead until they are replaced.
+        let converted = state.cache_connection().and_then(|mut conn| {
+            migrations::convert_cached_sanctions(
+                &mut Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            )
+        });
+        match converted {
+            Ok(converted) => tracing::info!(converted, "converted the cached sanctions to hashes"),
+            Err(e) => tracing::warn!(error = %e, "unable to convert the cached sanctions"),
+        }
+    }
+
+    if migrate_only {
+        return Ok(());
+    }
+
+    // Session tokens are signed with the newest of the PEM-encoded RSA keys
+    // in the given directory. Without a configured directory, sessions are
+    // invalidated on restart.
+    let jwt_keys_dir = config.jwt_keys_dir();
+
+    // Running `gnomegg rotate-keys` generates a new signing key, keeping the
+    // previous key so that tokens it signed remain valid until they expire
+    if env::args().nth(1).as_deref() == Some("rotate-keys") {
+        let dir = jwt_keys_dir.ok_or(ConfigError::Missing {
+            setting: "server.jwt_keys_dir",
+            variable: "JWT_KEYS_DIR",
+        })?;
+
+        println!("generated signing key {}", jwt::rotate_keys(dir, 2)?);
+
+        return Ok(());
+    }
+
+    if let Some(dir) = jwt_keys_dir {
+        state = state.with_signing_keys(KeySet::load(dir)?);
+    }
+
+    // The first of the encryption keys is used to encrypt new values, and
+    // the rest are kept to decrypt older values
+    let keyring = config.keyring();
+
+    // Running `gnomegg reencrypt` encrypts each stored value with the current
+    // key, rather than starting the server
+    if env::args().nth(1).as_deref() == Some("reencrypt") {
+        let keyring = keyring.ok_or(ConfigError::Missing {
+            setting: "server.encryption_keys",
+            variable: "ENCRYPTION_KEYS",
+        })?;
+        let persistent_conn = state.persistent_connection().map_err(io::Error::other)?;
+
+        let reencrypted =
+            oauth::reencrypt_connections(&persistent_conn, keyring).map_err(io::Error::other)?;
+        println!("re-encrypted {} linked account IDs", reencrypted);
+
+        return Ok(());
+    }
+
+    if let Some(keyring) = keyring {
+        state = state.with_keyring(keyring.clone());
+    }
+
+    // Oauth providers are enabled by providing each of their credentials
+    for (provider, credentials) in config.oauth_providers() {
+        state = state.with_oauth_provider(provider, credentials);
+    }
+
+    // Active bans, mutes and roles are preloaded into redis, such that a cold
+    // cache (e.g. after redis restarts) doesn't send the first wave of
+    // connections to MySQL. The server starts regardless of whether or not
+    // the cache could be warmed.
+    if config.warm_cache_on_startup() {
+        if let Err(e) = warmup::warm_state(&state) {
+            tracing::warn!(error = %e, "unable to warm the caching layer");
+        }
+    }
+
+    server::serve(config.bind_address(), config.tls(), state).await
 }
@@ -0,0 +1,247 @@
+use super::{backend::Unsigned, schema::announcements};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+/// The maximum length of an announcement's message.
+pub const MAX_ANNOUNCEMENT_LENGTH: usize = 512;
+
+/// Determines whether or not the given expression is a valid cron schedule.
+/// Schedules are made up of seconds, minutes, hours, days of the month,
+/// months, days of the week, and optionally years (e.g. "0 0 17 * * Fri").
+///
+/// # Arguments
+///
+/// * `schedule` - The cron expression that should be checked
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::announcement::is_valid_schedule;
+///
+/// assert!(is_valid_schedule("0 */30 * * * *"));
+/// assert!(!is_valid_schedule("every thirty minutes"));
+/// ```
+pub fn is_valid_schedule(schedule: &str) -> bool {
+    Schedule::from_str(schedule).is_ok()
+}
+
+/// Determines the first time after the given time at which the given cron
+/// schedule fires, if the schedule is valid and fires again.
+///
+/// # Arguments
+///
+/// * `schedule` - The cron expression describing the schedule
+/// * `after` - The time after which the next occurrence should be found
+///
+/// # Example
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use gnomegg::spec::announcement::next_occurrence;
+///
+/// let after = Utc.ymd(2020, 5, 17).and_hms(12, 10, 0);
+/// assert_eq!(next_occurrence("0 */30 * * * *", after), Some(Utc.ymd(2020, 5, 17).and_hms(12, 30, 0)));
+/// ```
+pub fn next_occurrence(schedule: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    Schedule::from_str(schedule).ok()?.after(&after).next()
+}
+
+/// Announcement represents a message sent to the entire chat by the server,
+/// either once or repeatedly according to a cron schedule.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Announcement {
+    /// A unique identifier assigned to the announcement
+    id: u64,
+
+    /// The message sent to the chat
+    message: String,
+
+    /// The cron expression describing when the announcement repeats, if it
+    /// does
+    schedule: Option<String>,
+
+    /// The ID of the user who registered the announcement, if it wasn't
+    /// registered with the administrative token
+    created_by: Option<u64>,
+
+    /// The time at which the announcement is next sent
+    next_run_at: NaiveDateTime,
+
+    /// The time at which the announcement was registered
+    created_at: NaiveDateTime,
+}
+
+impl Announcement {
+    /// Retreives the ID of the announcement.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the message sent to the chat.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Retreives the cron expression describing when the announcement
+    /// repeats, if it does.
+    pub fn schedule(&self) -> Option<&str> {
+        self.schedule.as_deref()
+    }
+
+    /// Retreives the ID of the user who registered the announcement, if it
+    /// wasn't registered with the administrative token.
+    pub fn created_by(&self) -> Option<u64> {
+        self.created_by
+    }
+
+    /// Retreives the time at which the announcement is next sent.
+    pub fn next_run_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.next_run_at, Utc)
+    }
+
+    /// Retreives the time at which the announcement was registered.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.created_at, Utc)
+    }
+
+    /// Determines whether or not the announcement should be sent at the
+    /// given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_run_at <= now.naive_utc()
+    }
+
+    /// Schedules the next run of a repeating announcement, returning None if
+    /// the announcement was sent for the last time. Runs missed while the
+    /// server was down are skipped, rather than being sent all at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The time at which the announcement was sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use gnomegg::spec::announcement::NewAnnouncement;
+    ///
+    /// let now = Utc.ymd(2020, 5, 17).and_hms(12, 0, 0);
+    /// let once = NewAnnouncement::new("Debate at 5", now, now).with_id(1);
+    /// assert_eq!(once.rescheduled(now), None);
+    ///
+    /// let repeating = NewAnnouncement::new("Debate at 5", now, now)
+    ///     .with_schedule(Some("0 0 * * * *"))
+    ///     .with_id(2);
+    /// assert_eq!(repeating.rescheduled(now).unwrap().next_run_at(), Utc.ymd(2020, 5, 17).and_hms(13, 0, 0));
+    /// ```
+    pub fn rescheduled(&self, now: DateTime<Utc>) -> Option<Self> {
+        let next = next_occurrence(self.schedule.as_deref()?, now)?;
+
+        Some(Self {
+            next_run_at: next.naive_utc().trunc_subsecs(0),
+            ..self.clone()
+        })
+    }
+}
+
+/// NewAnnouncement represents a request to register an announcement.
+/// Announcements are assigned an ID once registered.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "announcements"]
+pub struct NewAnnouncement<'a> {
+    /// The message that should be sent to the chat
+    message: &'a str,
+
+    /// The cron expression describing when the announcement should repeat,
+    /// if it should
+    schedule: Option<&'a str>,
+
+    /// The ID of the user registering the announcement, if any
+    created_by: Option<Unsigned<u64>>,
+
+    /// The time at which the announcement should first be sent
+    next_run_at: NaiveDateTime,
+
+    /// The time at which the announcement was registered
+    created_at: NaiveDateTime,
+}
+
+impl<'a> NewAnnouncement<'a> {
+    /// Creates a new request to register an announcement sent once.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be sent to the chat
+    /// * `first_run_at` - The time at which the announcement should first be
+    /// sent
+    /// * `created_at` - The time at which the announcement was registered
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::announcement::NewAnnouncement;
+    ///
+    /// let announcement = NewAnnouncement::new("Debate at 5", Utc::now(), Utc::now()).with_id(1);
+    /// assert_eq!(announcement.message(), "Debate at 5");
+    /// ```
+    pub fn new(message: &'a str, first_run_at: DateTime<Utc>, created_at: DateTime<Utc>) -> Self {
+        Self {
+            message,
+            schedule: None,
+            created_by: None,
+            // Announcements are only tracked to the second, as MySQL
+            // timestamps are
+            next_run_at: first_run_at.naive_utc().trunc_subsecs(0),
+            created_at: created_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Consumes the request, and modifies it such that the announcement
+    /// repeats according to the given cron schedule.
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` - (optional) The cron expression describing when the
+    /// announcement should repeat
+    pub fn with_schedule(mut self, schedule: Option<&'a str>) -> Self {
+        self.schedule = schedule;
+
+        self
+    }
+
+    /// Consumes the request, and modifies it according to the user
+    /// registering the announcement.
+    ///
+    /// # Arguments
+    ///
+    /// * `created_by` - (optional) The ID of the user registering the
+    /// announcement
+    pub fn with_created_by(mut self, created_by: Option<u64>) -> Self {
+        self.created_by = created_by.map(Unsigned);
+
+        self
+    }
+
+    /// Converts the request into a registered announcement with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the announcement
+    pub fn with_id(&self, id: u64) -> Announcement {
+        Announcement {
+            id,
+            message: self.message.to_owned(),
+            schedule: self.schedule.map(str::to_owned),
+            created_by: self.created_by.map(|created| created.0),
+            next_run_at: self.next_run_at,
+            created_at: self.created_at,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// HeldMessage represents a chatter's first message, held back from the chat
+/// until a moderator approves or rejects it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct HeldMessage {
+    /// A unique identifier assigned to the held message
+    id: u64,
+
+    /// The ID of the user who sent the message
+    sender_id: u64,
+
+    /// The username held by the user who sent the message
+    sender: String,
+
+    /// The contents of the message
+    contents: String,
+
+    /// The time at which the message was sent
+    held_at: DateTime<Utc>,
+}
+
+impl HeldMessage {
+    /// Retreives the ID of the held message.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who sent the message.
+    pub fn sender_id(&self) -> u64 {
+        self.sender_id
+    }
+
+    /// Retreives the username held by the user who sent the message.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Retreives the contents of the message.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Retreives the time at which the message was sent.
+    pub fn held_at(&self) -> DateTime<Utc> {
+        self.held_at
+    }
+}
+
+/// NewHeldMessage represents a message that should be held for approval.
+#[derive(Clone, Copy, Debug)]
+pub struct NewHeldMessage<'a> {
+    /// The ID of the user who sent the message
+    sender_id: u64,
+
+    /// The username held by the user who sent the message
+    sender: &'a str,
+
+    /// The contents of the message
+    contents: &'a str,
+
+    /// The time at which the message was sent
+    held_at: DateTime<Utc>,
+}
+
+impl<'a> NewHeldMessage<'a> {
+    /// Creates a new message that should be held for approval.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `sender` - The username held by the user who sent the message
+    /// * `contents` - The contents of the message
+    /// * `held_at` - The time at which the message was sent
+    pub fn new(sender_id: u64, sender: &'a str, contents: &'a str, held_at: DateTime<Utc>) -> Self {
+        Self {
+            sender_id,
+            sender,
+            contents,
+            held_at,
+        }
+    }
+
+    /// Retreives the ID of the user who sent the message.
+    pub fn sender_id(&self) -> u64 {
+        self.sender_id
+    }
+
+    /// Retreives the contents of the message.
+    pub fn contents(&self) -> &str {
+        self.contents
+    }
+
+    /// Consumes the new held message, and assigns it the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the held message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::approval::NewHeldMessage;
+    ///
+    /// let held = NewHeldMessage::new(69420, "essaywriter", "first time chatter", Utc::now()).with_id(1);
+    /// assert_eq!(held.sender(), "essaywriter");
+    /// ```
+    pub fn with_id(self, id: u64) -> HeldMessage {
+        HeldMessage {
+            id,
+            sender_id: self.sender_id,
+            sender: self.sender.to_owned(),
+            contents: self.contents.to_owned(),
+            held_at: self.held_at,
+        }
+    }
+}
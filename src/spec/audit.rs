@@ -0,0 +1,151 @@
+use super::{backend::Unsigned, schema::audit_log};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// AuditEntry represents a recorded sensitive action, or an action performed
+/// by an administrator while impersonating another user.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct AuditEntry {
+    /// A unique identifier assigned to the entry
+    id: u64,
+
+    /// The ID of the user on whose behalf the action was performed, if the
+    /// action wasn't performed with the administrative token
+    actor_id: Option<u64>,
+
+    /// The ID of the administrator impersonating the actor, if any
+    impersonator_id: Option<u64>,
+
+    /// A short description of the action
+    action: String,
+
+    /// The HTTP status with which the request was answered, if the action
+    /// was a request
+    status: Option<u16>,
+
+    /// Free-form context provided for the action
+    detail: Option<String>,
+
+    /// The time at which the action was performed
+    performed_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+    /// Retreives the ID of the entry.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user on whose behalf the action was performed.
+    pub fn actor_id(&self) -> Option<u64> {
+        self.actor_id
+    }
+
+    /// Retreives the ID of the administrator impersonating the actor, if any.
+    pub fn impersonator_id(&self) -> Option<u64> {
+        self.impersonator_id
+    }
+
+    /// Retreives the description of the action.
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// Retreives the HTTP status with which the request was answered, if any.
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// Retreives the context provided for the action, if any.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+
+    /// Retreives the time at which the action was performed.
+    pub fn performed_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.performed_at, Utc)
+    }
+}
+
+/// NewAuditEntry represents a request to record an action in the audit log.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "audit_log"]
+pub struct NewAuditEntry<'a> {
+    /// The ID of the user on whose behalf the action was performed
+    actor_id: Option<Unsigned<u64>>,
+
+    /// The ID of the administrator impersonating the actor, if any
+    impersonator_id: Option<Unsigned<u64>>,
+
+    /// A short description of the action
+    action: &'a str,
+
+    /// The HTTP status with which the request was answered, if any
+    status: Option<Unsigned<u16>>,
+
+    /// Free-form context provided for the action
+    detail: Option<&'a str>,
+}
+
+impl<'a> NewAuditEntry<'a> {
+    /// Creates a new audit log entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `actor_id` - The ID of the user on whose behalf the action was
+    /// performed, or None if the administrative token was used
+    /// * `impersonator_id` - The ID of the administrator impersonating the
+    /// actor, if any
+    /// * `action` - A short description of the action
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::audit::NewAuditEntry;
+    ///
+    /// let entry = NewAuditEntry::new(Some(1), Some(2), "GET /sessions").with_status(200);
+    /// assert_eq!(entry.impersonator_id(), Some(2));
+    /// ```
+    pub fn new(actor_id: Option<u64>, impersonator_id: Option<u64>, action: &'a str) -> Self {
+        Self {
+            actor_id: actor_id.map(Unsigned),
+            impersonator_id: impersonator_id.map(Unsigned),
+            action,
+            status: None,
+            detail: None,
+        }
+    }
+
+    /// Consumes the entry, attaching the HTTP status with which the request
+    /// was answered.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status of the response
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(Unsigned(status));
+
+        self
+    }
+
+    /// Consumes the entry, attaching context for the action.
+    ///
+    /// # Arguments
+    ///
+    /// * `detail` - Free-form context provided for the action
+    pub fn with_detail(mut self, detail: &'a str) -> Self {
+        self.detail = Some(detail);
+
+        self
+    }
+
+    /// Retreives the ID of the user on whose behalf the action was performed.
+    pub fn actor_id(&self) -> Option<u64> {
+        self.actor_id.map(|actor| actor.0)
+    }
+
+    /// Retreives the ID of the administrator impersonating the actor, if any.
+    pub fn impersonator_id(&self) -> Option<u64> {
+        self.impersonator_id.map(|impersonator| impersonator.0)
+    }
+}
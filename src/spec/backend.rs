@@ -0,0 +1,185 @@
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow, Queryable},
+    dsl::sql,
+    expression::{bound::Bound, AsExpression},
+    row::Row,
+    serialize::{self, Output, ToSql},
+    sql_types::{BigInt, Nullable, SqlOrd},
+    sqlite::{Sqlite, SqliteConnection},
+    QueryResult, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    io::Write,
+    str::FromStr,
+};
+
+/// PersistentBackend is the database backend of the persistence layer.
+pub type PersistentBackend = Sqlite;
+
+/// PersistentConnection is a connection to the persistence layer.
+pub type PersistentConnection = SqliteConnection;
+
+/// Unsigned wraps an unsigned integer bound to, or loaded from, a column of
+/// one of the unsigned SQL types below. Diesel implements AsExpression for
+/// every expression type, which keeps bare integers from being bound to a
+/// locally declared SQL type; values passed to queries and insertable structs
+/// are wrapped instead.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(transparent)]
+pub struct Unsigned<T>(pub T);
+
+impl<T: Display> Display for Unsigned<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: FromStr> FromStr for Unsigned<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Unsigned)
+    }
+}
+
+impl<T, ST, DB> FromSqlRow<ST, DB> for Unsigned<T>
+where
+    DB: Backend,
+    Unsigned<T>: FromSql<ST, DB>,
+{
+    fn build_from_row<R: Row<DB>>(row: &mut R) -> deserialize::Result<Self> {
+        FromSql::<ST, DB>::from_sql(row.take())
+    }
+}
+
+impl<T, ST, DB> Queryable<ST, DB> for Unsigned<T>
+where
+    DB: Backend,
+    Self: FromSqlRow<ST, DB>,
+{
+    type Row = Self;
+
+    fn build(row: Self::Row) -> Self {
+        row
+    }
+}
+
+/// Declares an SQL type holding an unsigned integer in an SQLite BIGINT
+/// column, which only holds signed integers. Values are converted to and from
+/// i64 with a check, such that a value out of range is reported as an error
+/// rather than wrapping around.
+macro_rules! unsigned_sql_type {
+    ($(#[$attr:meta])* $name:ident => $rust_type:ty) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, QueryId, SqlType)]
+        #[sqlite_type = "Long"]
+        pub struct $name;
+
+        impl SqlOrd for $name {}
+
+        impl FromSql<$name, Sqlite> for $rust_type {
+            fn from_sql(value: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+                let signed = <i64 as FromSql<BigInt, Sqlite>>::from_sql(value)?;
+
+                Ok(<$rust_type>::try_from(signed)?)
+            }
+        }
+
+        impl ToSql<$name, Sqlite> for $rust_type {
+            fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+                let signed = i64::try_from(*self)?;
+
+                <i64 as ToSql<BigInt, Sqlite>>::to_sql(&signed, out)
+            }
+        }
+
+        impl FromSql<$name, Sqlite> for Unsigned<$rust_type> {
+            fn from_sql(value: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+                <$rust_type as FromSql<$name, Sqlite>>::from_sql(value).map(Unsigned)
+            }
+        }
+
+        impl ToSql<$name, Sqlite> for Unsigned<$rust_type> {
+            fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+                <$rust_type as ToSql<$name, Sqlite>>::to_sql(&self.0, out)
+            }
+        }
+
+        impl ToSql<Nullable<$name>, Sqlite> for Unsigned<$rust_type> {
+            fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+                <Self as ToSql<$name, Sqlite>>::to_sql(self, out)
+            }
+        }
+
+        impl AsExpression<$name> for Unsigned<$rust_type> {
+            type Expression = Bound<$name, Self>;
+
+            fn as_expression(self) -> Self::Expression {
+                Bound::new(self)
+            }
+        }
+
+        impl<'a> AsExpression<$name> for &'a Unsigned<$rust_type> {
+            type Expression = Bound<$name, Self>;
+
+            fn as_expression(self) -> Self::Expression {
+                Bound::new(self)
+            }
+        }
+
+        impl AsExpression<Nullable<$name>> for Unsigned<$rust_type> {
+            type Expression = Bound<Nullable<$name>, Self>;
+
+            fn as_expression(self) -> Self::Expression {
+                Bound::new(self)
+            }
+        }
+
+        impl<'a> AsExpression<Nullable<$name>> for &'a Unsigned<$rust_type> {
+            type Expression = Bound<Nullable<$name>, Self>;
+
+            fn as_expression(self) -> Self::Expression {
+                Bound::new(self)
+            }
+        }
+    };
+}
+
+unsigned_sql_type!(
+    /// UnsignedBigint is the SQL type of the columns holding a u64.
+    UnsignedBigint => u64
+);
+
+unsigned_sql_type!(
+    /// UnsignedInteger is the SQL type of the columns holding a u32.
+    UnsignedInteger => u32
+);
+
+unsigned_sql_type!(
+    /// UnsignedSmallint is the SQL type of the columns holding a u16.
+    UnsignedSmallint => u16
+);
+
+unsigned_sql_type!(
+    /// UnsignedTinyint is the SQL type of the columns holding a u8.
+    UnsignedTinyint => u8
+);
+
+/// Gets the ID assigned to the row most recently inserted over the given
+/// connection. SQLite doesn't support RETURNING clauses through diesel, so
+/// IDs assigned on insertion must be fetched separately, in the same
+/// transaction.
+///
+/// # Arguments
+///
+/// * `conn` - The connection over which the row was inserted
+pub fn last_insert_id(conn: &PersistentConnection) -> QueryResult<u64> {
+    diesel::select(sql::<UnsignedBigint>("last_insert_rowid()")).get_result(conn)
+}
@@ -1,21 +1,37 @@
-use super::{schema::bans, user::User};
+use super::{
+    backend::Unsigned,
+    fields::{self, TIMESTAMP_FORMAT},
+    schema::bans,
+    user::User,
+};
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::Associations;
+use redis::RedisError;
 use serde::{Deserialize, Serialize};
 
+use std::collections::HashMap;
+
 /// Ban represents a ban entry in the SQL database.
 #[derive(
-    Identifiable, Insertable, Queryable, Associations, Serialize, Deserialize, PartialEq, Debug,
+    Identifiable,
+    Insertable,
+    Queryable,
+    Associations,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Clone,
+    Debug,
 )]
 #[belongs_to(User)]
 #[table_name = "bans"]
 #[primary_key(user_id)]
 pub struct Ban {
     /// The ID of the user corresponding to this ban
-    user_id: u64,
+    user_id: Unsigned<u64>,
 
     /// The (optional) number of nanoseconds that this ban will be in effect for
-    duration: Option<u64>,
+    duration: Option<Unsigned<u64>>,
 
     /// The time at which the ban was issued
     initiated_at: NaiveDateTime,
@@ -27,7 +43,7 @@ pub struct Ban {
 impl Default for Ban {
     fn default() -> Self {
         Self {
-            user_id: 0,
+            user_id: Unsigned(0),
             duration: None,
             initiated_at: Utc::now().naive_utc(),
             ip: None,
@@ -43,7 +59,7 @@ impl Ban {
     /// * `user_id` - The ID of the user who will be banned
     pub fn new(user_id: u64) -> Self {
         Self {
-            user_id,
+            user_id: Unsigned(user_id),
             duration: None,
             initiated_at: Utc::now().naive_utc(),
             ip: None,
@@ -57,7 +73,7 @@ impl Ban {
     ///
     /// * `user_id` - The user ID that should be associated with the ban
     pub fn with_user_id(mut self, user_id: u64) -> Self {
-        self.user_id = user_id;
+        self.user_id = Unsigned(user_id);
 
         self
     }
@@ -70,7 +86,7 @@ impl Ban {
     /// * `duration` - The number of nanoseconds that the ban should be active
     /// for
     pub fn with_duration(mut self, duration: u64) -> Self {
-        self.duration = Some(duration);
+        self.duration = Some(Unsigned(duration));
 
         self
     }
@@ -102,24 +118,105 @@ impl Ban {
     /// Determines whether or not the ban is active.
     pub fn active(&self) -> bool {
         self.active_for()
-            .map_or(true, |d| Utc::now().naive_utc() < self.initiated_at + d)
+            .is_none_or(|d| Utc::now().naive_utc() < self.initiated_at + d)
     }
 
     /// Retreieves the ID pertaining to the use who will be band.
     pub fn concerns(&self) -> u64 {
-        self.user_id
+        self.user_id.0
     }
 
     /// Constructs a duration representing the timeframe that the ban will be
     /// active for.
     pub fn active_for(&self) -> Option<Duration> {
-        self.duration.map(|d| Duration::nanoseconds(d as i64))
+        self.duration.map(|d| Duration::nanoseconds(d.0 as i64))
+    }
+
+    /// Determines the time at which the ban lapses, if it isn't permanent.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.active_for()
+            .map(|d| DateTime::from_naive_utc_and_offset(self.initiated_at + d, Utc))
     }
 
     /// Obtains the IP adddress of the user being banned.
     pub fn address(&self) -> Option<&str> {
         self.ip.as_deref()
     }
+
+    /// Encodes the ban as the fields of a redis hash, such that each of its
+    /// fields may be read or updated individually. The duration of a
+    /// permanent ban, and the address of a ban lacking one, are left out.
+    pub fn to_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("user_id", self.user_id.to_string()),
+            (
+                "initiated_at",
+                self.initiated_at.format(TIMESTAMP_FORMAT).to_string(),
+            ),
+        ];
+
+        if let Some(duration) = self.duration.map(|duration| duration.0) {
+            fields.push(("duration", duration.to_string()));
+        }
+
+        if let Some(ip) = &self.ip {
+            fields.push(("ip", ip.clone()));
+        }
+
+        fields
+    }
+
+    /// Decodes a ban from the fields of a redis hash. Redis reads missing
+    /// hashes as empty, so an empty hash is decoded as no ban at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The fields of the hash
+    pub fn from_fields(fields: &HashMap<String, String>) -> Result<Option<Self>, RedisError> {
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            user_id: fields::required(fields, "user_id")?,
+            duration: fields::optional(fields, "duration")?,
+            initiated_at: fields::required(fields, "initiated_at")?,
+            ip: fields::optional(fields, "ip")?,
+        }))
+    }
+}
+
+impl<'a> From<&NewBan<'a>> for Ban {
+    /// Converts the given request to add a ban entry into the ban it adds.
+    ///
+    /// # Arguments
+    ///
+    /// * `ban` - The request to add a ban entry
+    fn from(ban: &NewBan<'a>) -> Self {
+        Self {
+            user_id: ban.user_id,
+            duration: ban.duration,
+            initiated_at: ban.initiated_at,
+            ip: ban.ip.map(str::to_owned),
+        }
+    }
+}
+
+impl<'a> From<&'a Ban> for NewBan<'a> {
+    /// Converts the given ban entry into a request to add it again (e.g. in
+    /// order to restore it).
+    ///
+    /// # Arguments
+    ///
+    /// * `ban` - The ban entry that should be added
+    fn from(ban: &'a Ban) -> Self {
+        Self {
+            user_id: ban.user_id,
+            duration: ban.duration,
+            initiated_at: ban.initiated_at,
+            ip: ban.ip.as_deref(),
+        }
+    }
 }
 
 /// NewBan represents a request to add a ban entry in the database.
@@ -127,10 +224,10 @@ impl Ban {
 #[table_name = "bans"]
 pub struct NewBan<'a> {
     /// The ID of the user corresponding to this ban
-    user_id: u64,
+    user_id: Unsigned<u64>,
 
     /// The (optional) number of nanoseconds that this ban will be in effect for
-    duration: Option<u64>,
+    duration: Option<Unsigned<u64>>,
 
     /// The time at which the ban was issued
     initiated_at: NaiveDateTime,
@@ -155,8 +252,8 @@ impl<'a> NewBan<'a> {
         ip: Option<&'a str>,
     ) -> Self {
         Self {
-            user_id,
-            duration,
+            user_id: Unsigned(user_id),
+            duration: duration.map(Unsigned),
             initiated_at: initiated_at.naive_utc(),
             ip,
         }
@@ -165,18 +262,29 @@ impl<'a> NewBan<'a> {
     /// Determines whether or not the ban is active.
     pub fn active(&self) -> bool {
         self.active_for()
-            .map_or(true, |d| Utc::now().naive_utc() < self.initiated_at + d)
+            .is_none_or(|d| Utc::now().naive_utc() < self.initiated_at + d)
     }
 
     /// Retreieves the ID pertaining to the use who will be band.
     pub fn concerns(&self) -> u64 {
-        self.user_id
+        self.user_id.0
     }
 
     /// Constructs a duration representing the timeframe that the ban will be
     /// active for.
     pub fn active_for(&self) -> Option<Duration> {
-        self.duration.map(|d| Duration::nanoseconds(d as i64))
+        self.duration.map(|d| Duration::nanoseconds(d.0 as i64))
+    }
+
+    /// Retreives the number of nanoseconds that the ban will be in effect
+    /// for, if it expires.
+    pub fn duration(&self) -> Option<u64> {
+        self.duration.map(|duration| duration.0)
+    }
+
+    /// Retreives the time at which the ban was issued.
+    pub fn initiated_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.initiated_at, Utc)
     }
 
     /// Obtains the IP adddress of the user being banned.
@@ -0,0 +1,297 @@
+use super::{backend::Unsigned, schema::bot_commands, user::Role};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The username under which replies to bot commands are sent to the chat.
+pub const BOT_USERNAME: &str = "gnomebot";
+
+/// The maximum length of the URL of a webhook answering a command.
+pub const MAX_TARGET_LENGTH: usize = 255;
+
+/// CommandHandler represents the handler that answers a bot command.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CommandHandler {
+    /// The command is answered by the handler registered in the dispatcher
+    /// under the given name
+    Builtin(String),
+
+    /// The command is answered by POSTing its invocation to the given URL
+    Webhook(String),
+}
+
+impl CommandHandler {
+    /// Gets the name of the kind of handler, as it is stored (e.g.
+    /// "webhook").
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Builtin(_) => "builtin",
+            Self::Webhook(_) => "webhook",
+        }
+    }
+
+    /// Retreives the name of the builtin handler, or the URL of the webhook
+    /// answering the command.
+    pub fn target(&self) -> &str {
+        match self {
+            Self::Builtin(target) | Self::Webhook(target) => target,
+        }
+    }
+}
+
+/// BotCommand represents a custom command that chatters may invoke by
+/// sending a message starting with an exclamation mark followed by its name
+/// (e.g. "!logs").
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "bot_commands"]
+pub struct BotCommand {
+    /// The name typed after an exclamation mark in order to invoke the
+    /// command
+    name: String,
+
+    /// The kind of handler that answers the command
+    handler: String,
+
+    /// The name of the builtin handler, or the URL of the webhook that
+    /// answers the command
+    target: String,
+
+    /// The number of seconds after the command is invoked during which it
+    /// may not be invoked again
+    cooldown: Unsigned<u32>,
+
+    /// The role required in order to invoke the command, if any
+    role: Option<String>,
+}
+
+impl BotCommand {
+    /// Creates a new command that every chatter may invoke at any time.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name typed after an exclamation mark in order to invoke
+    /// the command
+    /// * `handler` - The handler that answers the command
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::{bot_command::{BotCommand, CommandHandler}, user::Role};
+    ///
+    /// let command = BotCommand::new("song", CommandHandler::Webhook("https://example.com/song".to_owned()))
+    ///     .with_cooldown(30)
+    ///     .with_role(Some(Role::Subscriber));
+    /// assert!(command.is_valid());
+    /// assert!(!command.may_invoke(&[]));
+    /// ```
+    pub fn new(name: &str, handler: CommandHandler) -> Self {
+        Self {
+            name: name.to_owned(),
+            handler: handler.kind().to_owned(),
+            target: handler.target().to_owned(),
+            cooldown: Unsigned(0),
+            role: None,
+        }
+    }
+
+    /// Consumes the command, and modifies it according to the provided
+    /// cooldown.
+    ///
+    /// # Arguments
+    ///
+    /// * `cooldown` - The number of seconds after the command is invoked
+    /// during which it may not be invoked again
+    pub fn with_cooldown(mut self, cooldown: u32) -> Self {
+        self.cooldown = Unsigned(cooldown);
+
+        self
+    }
+
+    /// Consumes the command, and restricts it to holders of the provided
+    /// role, or of any role ranked above it.
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - (optional) The role required in order to invoke the command
+    pub fn with_role(mut self, role: Option<Role>) -> Self {
+        self.role = role.map(|role| role.to_str().to_owned());
+
+        self
+    }
+
+    /// Retreives the name of the command.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retreives the handler that answers the command, if its kind is known.
+    pub fn handler(&self) -> Option<CommandHandler> {
+        match self.handler.as_str() {
+            "builtin" => Some(CommandHandler::Builtin(self.target.clone())),
+            "webhook" => Some(CommandHandler::Webhook(self.target.clone())),
+            _ => None,
+        }
+    }
+
+    /// Retreives the number of seconds after the command is invoked during
+    /// which it may not be invoked again.
+    pub fn cooldown(&self) -> u32 {
+        self.cooldown.0
+    }
+
+    /// Retreives the role required in order to invoke the command, if any.
+    pub fn role(&self) -> Option<Role> {
+        self.role.as_ref().and_then(|role| role.parse().ok())
+    }
+
+    /// Determines whether or not a chatter holding the given roles may invoke
+    /// the command.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles held by the chatter
+    pub fn may_invoke(&self, roles: &[Role]) -> bool {
+        self.role()
+            .is_none_or(|required| roles.iter().any(|role| role.rank() >= required.rank()))
+    }
+
+    /// Determines whether or not the command may be stored. Command names
+    /// must be lowercase, as invocations are matched case-insensitively.
+    pub fn is_valid(&self) -> bool {
+        is_valid_name(&self.name)
+            && self.name == self.name.to_lowercase()
+            && match self.handler() {
+                Some(CommandHandler::Builtin(target)) => is_valid_name(&target),
+                Some(CommandHandler::Webhook(target)) => {
+                    target.len() <= MAX_TARGET_LENGTH
+                        && (target.starts_with("https://") || target.starts_with("http://"))
+                }
+                None => false,
+            }
+            && self.role.as_ref().is_none_or(|_| self.role().is_some())
+    }
+}
+
+/// BotInvocation represents a chatter invoking a bot command, as it is sent
+/// to the handler answering the command.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct BotInvocation {
+    /// The name of the invoked command
+    command: String,
+
+    /// The text following the command's name in the message
+    args: String,
+
+    /// The ID of the chatter who invoked the command
+    sender_id: u64,
+
+    /// The username of the chatter who invoked the command
+    sender: String,
+
+    /// The time at which the command was invoked
+    sent_at: DateTime<Utc>,
+}
+
+impl BotInvocation {
+    /// Parses the invocation of a bot command from the given message, if it
+    /// invokes one. Command names are case-insensitive.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the chatter who sent the message
+    /// * `sender` - The username of the chatter who sent the message
+    /// * `msg` - The contents of the message
+    /// * `sent_at` - The time at which the message was sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::bot_command::BotInvocation;
+    ///
+    /// let invocation = BotInvocation::parse(69420, "MrMouton", "!Logs  Destiny", Utc::now()).unwrap();
+    /// assert_eq!(invocation.command(), "logs");
+    /// assert_eq!(invocation.args(), "Destiny");
+    /// assert!(BotInvocation::parse(69420, "MrMouton", "! logs", Utc::now()).is_none());
+    /// ```
+    pub fn parse(sender_id: u64, sender: &str, msg: &str, sent_at: DateTime<Utc>) -> Option<Self> {
+        let msg = msg.trim().strip_prefix('!')?;
+        let (command, args) = match msg.find(char::is_whitespace) {
+            Some(i) => (&msg[..i], msg[i..].trim_start()),
+            None => (msg, ""),
+        };
+
+        if !is_valid_name(command) {
+            return None;
+        }
+
+        Some(Self {
+            command: command.to_lowercase(),
+            args: args.to_owned(),
+            sender_id,
+            sender: sender.to_owned(),
+            sent_at,
+        })
+    }
+
+    /// Retreives the name of the invoked command.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Retreives the text following the command's name in the message.
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    /// Retreives the ID of the chatter who invoked the command.
+    pub fn sender_id(&self) -> u64 {
+        self.sender_id
+    }
+
+    /// Retreives the username of the chatter who invoked the command.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Retreives the time at which the command was invoked.
+    pub fn sent_at(&self) -> DateTime<Utc> {
+        self.sent_at
+    }
+}
+
+/// WebhookReply represents the response of a webhook answering a bot
+/// command.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct WebhookReply {
+    /// The message that should be sent to the chat in reply, if any
+    #[serde(default)]
+    reply: Option<String>,
+}
+
+impl WebhookReply {
+    /// Retreives the message that should be sent to the chat in reply, if
+    /// any.
+    pub fn reply(&self) -> Option<&str> {
+        self.reply.as_deref()
+    }
+}
+
+/// Determines whether or not the given string is a valid command name.
+/// Command names must be between 1 and 32 characters long, and may only
+/// contain alphanumeric characters and underscores.
+///
+/// # Arguments
+///
+/// * `name` - The command name that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::bot_command::is_valid_name;
+///
+/// assert!(is_valid_name("logs"));
+/// assert!(!is_valid_name("!logs"));
+/// ```
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,571 @@
+use super::{
+    backend::Unsigned,
+    backend::UnsignedBigint,
+    schema::{donations, gifted_subscriptions},
+    subscription::{Subscription, MAX_TIER},
+};
+use chrono::{DateTime, Duration, NaiveDateTime, SubsecRound, Utc};
+use diesel::sql_types::{Nullable, Varchar};
+use serde::{Deserialize, Serialize};
+
+/// The currency in which gifts are assumed to be paid for if none is
+/// specified.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// The maximum length of the name displayed for a donor or gifter.
+pub const MAX_DONOR_LENGTH: usize = 64;
+
+/// The maximum length of a message sent alongside a donation or gift.
+pub const MAX_DONATION_MESSAGE_LENGTH: usize = 255;
+
+/// The number of days added to a subscription for each month gifted.
+pub const DAYS_PER_MONTH: i64 = 30;
+
+/// Donation represents a donation made to the streamer, as it is recorded.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Donation {
+    /// A unique identifier assigned to the donation
+    id: u64,
+
+    /// The ID of the user who donated, if the donor has an account
+    donor_id: Option<u64>,
+
+    /// The name displayed for the donor
+    donor: String,
+
+    /// The amount donated, in the smallest unit of its currency
+    amount: u64,
+
+    /// The ISO 4217 code of the currency in which the amount was donated
+    currency: String,
+
+    /// The message sent alongside the donation, if any
+    message: Option<String>,
+
+    /// The time at which the donation was made
+    donated_at: NaiveDateTime,
+}
+
+impl Donation {
+    /// Retreives the ID of the donation.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who donated, if the donor has an account.
+    pub fn donor_id(&self) -> Option<u64> {
+        self.donor_id
+    }
+
+    /// Retreives the name displayed for the donor.
+    pub fn donor(&self) -> &str {
+        &self.donor
+    }
+
+    /// Retreives the amount donated, in the smallest unit of its currency.
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Retreives the ISO 4217 code of the currency in which the amount was
+    /// donated.
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    /// Retreives the message sent alongside the donation, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Retreives the time at which the donation was made.
+    pub fn donated_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.donated_at, Utc)
+    }
+}
+
+/// NewDonation represents a donation that should be recorded.
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "donations"]
+pub struct NewDonation<'a> {
+    /// The ID of the user who donated, if the donor has an account
+    donor_id: Option<Unsigned<u64>>,
+
+    /// The name displayed for the donor
+    donor: &'a str,
+
+    /// The amount donated, in the smallest unit of its currency
+    amount: Unsigned<u64>,
+
+    /// The ISO 4217 code of the currency in which the amount was donated
+    currency: &'a str,
+
+    /// The message sent alongside the donation, if any
+    message: Option<&'a str>,
+
+    /// The time at which the donation was made
+    donated_at: NaiveDateTime,
+}
+
+impl<'a> NewDonation<'a> {
+    /// Creates a new donation made by a donor without an account.
+    ///
+    /// # Arguments
+    ///
+    /// * `donor` - The name displayed for the donor
+    /// * `amount` - The amount donated, in the smallest unit of its currency
+    /// * `currency` - The ISO 4217 code of the currency in which the amount
+    /// was donated
+    /// * `donated_at` - The time at which the donation was made
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::donation::NewDonation;
+    ///
+    /// let donation = NewDonation::new("MrMouton", 500, "USD", Utc::now())
+    ///     .with_donor_id(Some(69420))
+    ///     .with_message(Some("for the gnomes"));
+    /// assert!(donation.is_valid());
+    /// assert_eq!(donation.with_id(1).amount(), 500);
+    /// ```
+    pub fn new(donor: &'a str, amount: u64, currency: &'a str, donated_at: DateTime<Utc>) -> Self {
+        Self {
+            donor_id: None,
+            donor,
+            amount: Unsigned(amount),
+            currency,
+            message: None,
+            // Donations are only tracked to the second, as MySQL timestamps
+            // are
+            donated_at: donated_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Consumes the donation, and attributes it to the user with the given
+    /// ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `donor_id` - (optional) The ID of the user who donated
+    pub fn with_donor_id(mut self, donor_id: Option<u64>) -> Self {
+        self.donor_id = donor_id.map(Unsigned);
+
+        self
+    }
+
+    /// Consumes the donation, and attaches the given message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - (optional) The message sent alongside the donation
+    pub fn with_message(mut self, message: Option<&'a str>) -> Self {
+        self.message = message;
+
+        self
+    }
+
+    /// Determines whether or not the donation may be recorded.
+    pub fn is_valid(&self) -> bool {
+        is_valid_donor(self.donor)
+            && self.amount.0 > 0
+            && is_valid_currency(self.currency)
+            && self.message.is_none_or(is_valid_message)
+    }
+
+    /// Converts the donation into a recorded donation with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the donation
+    pub fn with_id(&self, id: u64) -> Donation {
+        Donation {
+            id,
+            donor_id: self.donor_id.map(|donor| donor.0),
+            donor: self.donor.to_owned(),
+            amount: self.amount.0,
+            currency: self.currency.to_owned(),
+            message: self.message.map(str::to_owned),
+            donated_at: self.donated_at,
+        }
+    }
+}
+
+/// GiftedSubscription represents a subscription gifted from one chatter to
+/// another, as it is recorded.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct GiftedSubscription {
+    /// A unique identifier assigned to the gift
+    id: u64,
+
+    /// The ID of the user who gifted the subscription, if the gifter has an
+    /// account
+    gifter_id: Option<u64>,
+
+    /// The name displayed for the gifter
+    gifter: String,
+
+    /// The ID of the user to whom the subscription was gifted
+    recipient_id: u64,
+
+    /// The username held by the recipient when the subscription was gifted
+    recipient: String,
+
+    /// The tier of the gifted subscription
+    tier: u8,
+
+    /// The number of months for which the subscription was gifted
+    months: u8,
+
+    /// The amount paid for the gift, in the smallest unit of its currency
+    amount: u64,
+
+    /// The ISO 4217 code of the currency in which the gift was paid for
+    currency: String,
+
+    /// The message sent alongside the gift, if any
+    message: Option<String>,
+
+    /// The time at which the subscription was gifted
+    gifted_at: NaiveDateTime,
+}
+
+impl GiftedSubscription {
+    /// Retreives the ID of the gift.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who gifted the subscription, if the
+    /// gifter has an account.
+    pub fn gifter_id(&self) -> Option<u64> {
+        self.gifter_id
+    }
+
+    /// Retreives the name displayed for the gifter.
+    pub fn gifter(&self) -> &str {
+        &self.gifter
+    }
+
+    /// Retreives the ID of the user to whom the subscription was gifted.
+    pub fn recipient_id(&self) -> u64 {
+        self.recipient_id
+    }
+
+    /// Retreives the username held by the recipient when the subscription
+    /// was gifted.
+    pub fn recipient(&self) -> &str {
+        &self.recipient
+    }
+
+    /// Retreives the tier of the gifted subscription.
+    pub fn tier(&self) -> u8 {
+        self.tier
+    }
+
+    /// Retreives the number of months for which the subscription was gifted.
+    pub fn months(&self) -> u8 {
+        self.months
+    }
+
+    /// Retreives the amount paid for the gift, in the smallest unit of its
+    /// currency.
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Retreives the ISO 4217 code of the currency in which the gift was
+    /// paid for.
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    /// Retreives the message sent alongside the gift, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Retreives the time at which the subscription was gifted.
+    pub fn gifted_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.gifted_at, Utc)
+    }
+
+    /// Gets the subscription held by the recipient once the gift is applied
+    /// to the subscription they already hold, if any. Gifted months are added
+    /// on to any time remaining on the recipient's subscription, and the
+    /// higher of the two tiers is kept. Subscriptions that never lapse are
+    /// only upgraded.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - (optional) The subscription already held by the
+    /// recipient
+    /// * `now` - The current time
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use gnomegg::spec::donation::NewGiftedSubscription;
+    ///
+    /// let now = Utc::now();
+    /// let gift = NewGiftedSubscription::new("MrMouton", 69420, "essaywriter", 2, now)
+    ///     .with_months(3)
+    ///     .with_id(1);
+    /// let sub = gift.apply_to(None, now);
+    /// assert_eq!(sub.tier(), 2);
+    /// assert!(sub.expires_at().unwrap() > now + Duration::days(89));
+    /// ```
+    pub fn apply_to(&self, existing: Option<&Subscription>, now: DateTime<Utc>) -> Subscription {
+        let existing = existing.filter(|sub| sub.active_tier().is_some());
+        let tier = existing.map_or(self.tier, |sub| sub.tier().max(self.tier));
+
+        let expires_at = match existing.map(Subscription::expires_at) {
+            Some(None) => None,
+            Some(Some(expires_at)) if expires_at > now => {
+                Some(expires_at + Duration::days(self.months as i64 * DAYS_PER_MONTH))
+            }
+            _ => Some(now + Duration::days(self.months as i64 * DAYS_PER_MONTH)),
+        };
+
+        Subscription::new(self.recipient_id, tier).with_expires_at(expires_at)
+    }
+}
+
+/// NewGiftedSubscription represents a gifted subscription that should be
+/// recorded.
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "gifted_subscriptions"]
+pub struct NewGiftedSubscription<'a> {
+    /// The ID of the user who gifted the subscription, if the gifter has an
+    /// account
+    gifter_id: Option<Unsigned<u64>>,
+
+    /// The name displayed for the gifter
+    gifter: &'a str,
+
+    /// The ID of the user to whom the subscription was gifted
+    recipient_id: Unsigned<u64>,
+
+    /// The username held by the recipient
+    recipient: &'a str,
+
+    /// The tier of the gifted subscription
+    tier: Unsigned<u8>,
+
+    /// The number of months for which the subscription was gifted
+    months: Unsigned<u8>,
+
+    /// The amount paid for the gift, in the smallest unit of its currency
+    amount: Unsigned<u64>,
+
+    /// The ISO 4217 code of the currency in which the gift was paid for
+    currency: &'a str,
+
+    /// The message sent alongside the gift, if any
+    message: Option<&'a str>,
+
+    /// The time at which the subscription was gifted
+    gifted_at: NaiveDateTime,
+}
+
+impl<'a> NewGiftedSubscription<'a> {
+    /// Creates a new one-month gift, made by a gifter without an account at
+    /// no recorded cost.
+    ///
+    /// # Arguments
+    ///
+    /// * `gifter` - The name displayed for the gifter
+    /// * `recipient_id` - The ID of the user to whom the subscription was
+    /// gifted
+    /// * `recipient` - The username held by the recipient
+    /// * `tier` - The tier of the gifted subscription
+    /// * `gifted_at` - The time at which the subscription was gifted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::donation::NewGiftedSubscription;
+    ///
+    /// let gift = NewGiftedSubscription::new("MrMouton", 69420, "essaywriter", 1, Utc::now())
+    ///     .with_gifter_id(Some(42069))
+    ///     .with_price(499, "USD");
+    /// assert!(gift.is_valid());
+    /// ```
+    pub fn new(
+        gifter: &'a str,
+        recipient_id: u64,
+        recipient: &'a str,
+        tier: u8,
+        gifted_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            gifter_id: None,
+            gifter,
+            recipient_id: Unsigned(recipient_id),
+            recipient,
+            tier: Unsigned(tier),
+            months: Unsigned(1),
+            amount: Unsigned(0),
+            currency: DEFAULT_CURRENCY,
+            message: None,
+            gifted_at: gifted_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Consumes the gift, and attributes it to the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `gifter_id` - (optional) The ID of the user who gifted the
+    /// subscription
+    pub fn with_gifter_id(mut self, gifter_id: Option<u64>) -> Self {
+        self.gifter_id = gifter_id.map(Unsigned);
+
+        self
+    }
+
+    /// Consumes the gift, and modifies it according to the provided number
+    /// of months.
+    ///
+    /// # Arguments
+    ///
+    /// * `months` - The number of months for which the subscription was
+    /// gifted
+    pub fn with_months(mut self, months: u8) -> Self {
+        self.months = Unsigned(months);
+
+        self
+    }
+
+    /// Consumes the gift, and records the amount paid for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount paid, in the smallest unit of its currency
+    /// * `currency` - The ISO 4217 code of the currency in which the gift was
+    /// paid for
+    pub fn with_price(mut self, amount: u64, currency: &'a str) -> Self {
+        self.amount = Unsigned(amount);
+        self.currency = currency;
+
+        self
+    }
+
+    /// Consumes the gift, and attaches the given message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - (optional) The message sent alongside the gift
+    pub fn with_message(mut self, message: Option<&'a str>) -> Self {
+        self.message = message;
+
+        self
+    }
+
+    /// Determines whether or not the gift may be recorded.
+    pub fn is_valid(&self) -> bool {
+        is_valid_donor(self.gifter)
+            && (1..=MAX_TIER).contains(&self.tier.0)
+            && self.months.0 > 0
+            && is_valid_currency(self.currency)
+            && self.message.is_none_or(is_valid_message)
+    }
+
+    /// Converts the gift into a recorded gift with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the gift
+    pub fn with_id(&self, id: u64) -> GiftedSubscription {
+        GiftedSubscription {
+            id,
+            gifter_id: self.gifter_id.map(|gifter| gifter.0),
+            gifter: self.gifter.to_owned(),
+            recipient_id: self.recipient_id.0,
+            recipient: self.recipient.to_owned(),
+            tier: self.tier.0,
+            months: self.months.0,
+            amount: self.amount.0,
+            currency: self.currency.to_owned(),
+            message: self.message.map(str::to_owned),
+            gifted_at: self.gifted_at,
+        }
+    }
+}
+
+/// TopDonor represents a donor's position on the ranking of the donors who
+/// donated the most in a currency.
+#[derive(QueryableByName, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TopDonor {
+    /// The ID of the user who donated, if the donor has an account
+    #[sql_type = "Nullable<UnsignedBigint>"]
+    donor_id: Option<u64>,
+
+    /// The name displayed for the donor
+    #[sql_type = "Varchar"]
+    donor: String,
+
+    /// The total amount donated, in the smallest unit of the currency
+    #[sql_type = "UnsignedBigint"]
+    total: u64,
+}
+
+impl TopDonor {
+    /// Retreives the ID of the user who donated, if the donor has an account.
+    pub fn donor_id(&self) -> Option<u64> {
+        self.donor_id
+    }
+
+    /// Retreives the name displayed for the donor.
+    pub fn donor(&self) -> &str {
+        &self.donor
+    }
+
+    /// Retreives the total amount donated, in the smallest unit of the
+    /// currency.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+/// Determines whether or not the given string is a valid ISO 4217 currency
+/// code.
+///
+/// # Arguments
+///
+/// * `currency` - The currency code that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::donation::is_valid_currency;
+///
+/// assert!(is_valid_currency("EUR"));
+/// assert!(!is_valid_currency("eur"));
+/// ```
+pub fn is_valid_currency(currency: &str) -> bool {
+    currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Determines whether or not the given string may be displayed for a donor
+/// or gifter.
+///
+/// # Arguments
+///
+/// * `donor` - The name that should be validated
+fn is_valid_donor(donor: &str) -> bool {
+    !donor.trim().is_empty() && donor.len() <= MAX_DONOR_LENGTH
+}
+
+/// Determines whether or not the given string may be sent alongside a
+/// donation or gift.
+///
+/// # Arguments
+///
+/// * `message` - The message that should be validated
+fn is_valid_message(message: &str) -> bool {
+    message.len() <= MAX_DONATION_MESSAGE_LENGTH
+}
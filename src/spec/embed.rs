@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+
+/// The longest username that Twitch permits.
+const MAX_TWITCH_CHANNEL_LENGTH: usize = 25;
+
+/// The length of every YouTube video ID.
+const YOUTUBE_ID_LENGTH: usize = 11;
+
+/// Platform represents a service hosting streams that may be embedded
+/// alongside the chat.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Twitch,
+    Youtube,
+}
+
+impl Platform {
+    /// Gets the name of the platform, as it appears in embed keys (e.g.
+    /// "twitch").
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Twitch => "twitch",
+            Self::Youtube => "youtube",
+        }
+    }
+
+    /// Determines whether or not the given string identifies a stream on the
+    /// platform.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The string that should be checked
+    fn is_valid_channel(&self, channel: &str) -> bool {
+        match self {
+            Self::Twitch => {
+                !channel.is_empty()
+                    && channel.len() <= MAX_TWITCH_CHANNEL_LENGTH
+                    && channel
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            Self::Youtube => {
+                channel.len() == YOUTUBE_ID_LENGTH
+                    && channel
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            }
+        }
+    }
+}
+
+/// Embed represents a stream that chatters may watch alongside the chat.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Embed {
+    /// The platform hosting the stream
+    platform: Platform,
+
+    /// The Twitch channel or YouTube video ID of the stream
+    channel: String,
+}
+
+impl Embed {
+    /// Parses the embed linked by the given word, if any. Embeds may be
+    /// linked with a URL (e.g. "https://twitch.tv/destiny") or with an embed
+    /// key prefixed with a hash (e.g. "#twitch/destiny").
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - The word that should be parsed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::embed::Embed;
+    ///
+    /// assert_eq!(Embed::parse("#twitch/Destiny").map(|embed| embed.key()), Some("twitch/destiny".to_owned()));
+    /// assert_eq!(
+    ///     Embed::parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1").map(|embed| embed.key()),
+    ///     Some("youtube/dQw4w9WgXcQ".to_owned())
+    /// );
+    /// assert_eq!(Embed::parse("https://example.com/destiny"), None);
+    /// ```
+    pub fn parse(word: &str) -> Option<Self> {
+        if let Some(key) = word.strip_prefix('#') {
+            return Self::from_key(key);
+        }
+
+        let rest = word
+            .strip_prefix("https://")
+            .or_else(|| word.strip_prefix("http://"))
+            .unwrap_or(word);
+        let rest = rest
+            .strip_prefix("www.")
+            .or_else(|| rest.strip_prefix("m."))
+            .unwrap_or(rest);
+        let (host, path) = rest.split_once('/')?;
+
+        match host.to_lowercase().as_str() {
+            "twitch.tv" => Self::new(Platform::Twitch, path.split(&['/', '?'][..]).next()?),
+            "youtu.be" => Self::new(Platform::Youtube, path.split(&['/', '?'][..]).next()?),
+            "youtube.com" => {
+                let query = path.strip_prefix("watch?")?;
+
+                query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("v="))
+                    .and_then(|id| Self::new(Platform::Youtube, id))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses the given embed key (e.g. "twitch/destiny").
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key that should be parsed
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (platform, channel) = key.split_once('/')?;
+
+        match platform.to_lowercase().as_str() {
+            "twitch" => Self::new(Platform::Twitch, channel),
+            "youtube" => Self::new(Platform::Youtube, channel),
+            _ => None,
+        }
+    }
+
+    /// Creates a new embed, if the given channel identifies a stream on the
+    /// platform. Twitch channels are case-insensitive, and are lowercased.
+    ///
+    /// # Arguments
+    ///
+    /// * `platform` - The platform hosting the stream
+    /// * `channel` - The Twitch channel or YouTube video ID of the stream
+    fn new(platform: Platform, channel: &str) -> Option<Self> {
+        if !platform.is_valid_channel(channel) {
+            return None;
+        }
+
+        Some(Self {
+            platform,
+            channel: match platform {
+                Platform::Twitch => channel.to_lowercase(),
+                Platform::Youtube => channel.to_owned(),
+            },
+        })
+    }
+
+    /// Retreives the platform hosting the stream.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Retreives the Twitch channel or YouTube video ID of the stream.
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Gets the key identifying the embed (e.g. "twitch/destiny").
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.platform.name(), self.channel)
+    }
+}
+
+/// Gets each of the embeds linked in the given message, in the order that
+/// they appear, without duplicates.
+///
+/// # Arguments
+///
+/// * `msg` - The message whose embeds should be found
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::embed::linked_embeds;
+///
+/// assert_eq!(linked_embeds("#twitch/destiny or twitch.tv/Destiny or youtu.be/dQw4w9WgXcQ").len(), 2);
+/// ```
+pub fn linked_embeds(msg: &str) -> Vec<Embed> {
+    let mut embeds: Vec<Embed> = Vec::new();
+
+    for embed in msg.split_whitespace().filter_map(Embed::parse) {
+        if !embeds.contains(&embed) {
+            embeds.push(embed);
+        }
+    }
+
+    embeds
+}
+
+/// EmbedRank represents an embed's position on the ranking of the embeds
+/// being watched by the most chatters.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct EmbedRank {
+    /// The embed being watched
+    embed: Embed,
+
+    /// The number of chatters who recently linked the embed
+    chatters: u64,
+}
+
+impl EmbedRank {
+    /// Creates a new ranking entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `embed` - The embed being watched
+    /// * `chatters` - The number of chatters who recently linked the embed
+    pub fn new(embed: Embed, chatters: u64) -> Self {
+        Self { embed, chatters }
+    }
+
+    /// Retreives the embed being watched.
+    pub fn embed(&self) -> &Embed {
+        &self.embed
+    }
+
+    /// Retreives the number of chatters who recently linked the embed.
+    pub fn chatters(&self) -> u64 {
+        self.chatters
+    }
+}
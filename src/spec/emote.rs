@@ -0,0 +1,105 @@
+use super::{backend::Unsigned, schema::emotes, subscription::MAX_TIER};
+use serde::{Deserialize, Serialize};
+
+/// The maximum length of a reference to an emote's image.
+pub const MAX_IMAGE_LENGTH: usize = 255;
+
+/// Emote represents an image that chatters may embed in their messages by
+/// typing its code.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "emotes"]
+pub struct Emote {
+    /// The code typed by chatters in order to use the emote
+    code: String,
+
+    /// A reference to the image rendered in place of the code
+    image: String,
+
+    /// (optional) The minimum subscription tier required in order to use the
+    /// emote
+    tier: Option<Unsigned<u8>>,
+}
+
+impl Emote {
+    /// Creates a new emote available to every chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code typed by chatters in order to use the emote
+    /// * `image` - A reference to the image rendered in place of the code
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::emote::Emote;
+    ///
+    /// let emote = Emote::new("PepeLaugh", "PepeLaugh.png").with_tier(Some(2));
+    /// assert!(emote.is_valid());
+    /// ```
+    pub fn new(code: &str, image: &str) -> Self {
+        Self {
+            code: code.to_owned(),
+            image: image.to_owned(),
+            tier: None,
+        }
+    }
+
+    /// Consumes the emote, and restricts it to subscribers of the given tier
+    /// or above.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - (optional) The minimum subscription tier required in order
+    /// to use the emote
+    pub fn with_tier(mut self, tier: Option<u8>) -> Self {
+        self.tier = tier.map(Unsigned);
+
+        self
+    }
+
+    /// Retreives the code typed by chatters in order to use the emote.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Retreives the reference to the emote's image.
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// Retreives the minimum subscription tier required in order to use the
+    /// emote, if it is restricted.
+    pub fn tier(&self) -> Option<u8> {
+        self.tier.map(|tier| tier.0)
+    }
+
+    /// Determines whether or not the emote may be stored.
+    pub fn is_valid(&self) -> bool {
+        is_valid_code(&self.code)
+            && !self.image.is_empty()
+            && self.image.len() <= MAX_IMAGE_LENGTH
+            && self
+                .tier
+                .is_none_or(|tier| (1..=MAX_TIER).contains(&tier.0))
+    }
+}
+
+/// Determines whether or not the given string is a valid emote code. Emote
+/// codes must be between 2 and 32 characters long, and may only contain
+/// alphanumeric characters.
+///
+/// # Arguments
+///
+/// * `code` - The emote code that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::emote::is_valid_code;
+///
+/// assert!(is_valid_code("OverRustle"));
+/// assert!(!is_valid_code("Over Rustle"));
+/// ```
+pub fn is_valid_code(code: &str) -> bool {
+    (2..=32).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphanumeric())
+}
@@ -1,11 +1,26 @@
 use chrono::{naive::NaiveDateTime, DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::{
+    announcement::Announcement,
+    approval::HeldMessage,
+    donation::{Donation, GiftedSubscription},
+    embed::EmbedRank,
+    pin::PinnedMessage,
+    poll::{Poll, PollResult},
+    profile::SenderProfile,
+    user::Role,
+    whisper::UnreadCount,
+};
+
+use std::borrow::Cow;
+
 /// Message is a message sent as text, rendered on the client.
 #[derive(Serialize, Deserialize)]
 pub struct Message<'a> {
     /// The contents of the message
-    contents: &'a str,
+    #[serde(borrow)]
+    contents: Cow<'a, str>,
 }
 
 impl<'a> Message<'a> {
@@ -23,7 +38,9 @@ impl<'a> Message<'a> {
     /// let msg = Message::new("Mitta mitt mooowooo mitty mitta mitt mwoomooo");
     /// ```
     pub fn new(contents: &'a str) -> Self {
-        Self { contents }
+        Self {
+            contents: Cow::Borrowed(contents),
+        }
     }
 
     /// Returns the contents of the message.
@@ -39,6 +56,25 @@ impl<'a> Message<'a> {
     pub fn msg(&self) -> &str {
         &self.contents
     }
+
+    /// Replaces the contents of the message (e.g. with a sanitized copy).
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The new contents of the message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Message;
+    ///
+    /// let mut msg = Message::new("  hello   world ");
+    /// msg.set_msg("hello world".to_owned());
+    /// msg.msg(); // => "hello world"
+    /// ```
+    pub fn set_msg(&mut self, contents: String) {
+        self.contents = Cow::Owned(contents);
+    }
 }
 
 /// PrivMessage is a message sent as text, rendered on the client corresponding
@@ -86,7 +122,7 @@ impl<'a> PrivMessage<'a> {
     /// msg.to(); // => "essaywriter"
     /// ```
     pub fn to(&self) -> &str {
-        &self.concerns
+        self.concerns
     }
 
     /// Retrieves the contents of the private message.
@@ -148,7 +184,7 @@ impl<'a> Mute<'a> {
     /// mute.user(); // => "essaywriter"
     /// ```
     pub fn user(&self) -> &str {
-        &self.concerns
+        self.concerns
     }
 
     /// Retreives the number of nanoseconds that the aforementioned user should
@@ -203,7 +239,7 @@ impl<'a> Unmute<'a> {
     /// unmute.user(); // => "essaywriter"
     /// ```
     pub fn user(&self) -> &str {
-        &self.concerns
+        self.concerns
     }
 }
 
@@ -256,7 +292,7 @@ impl<'a> Ban<'a> {
     /// ban.user(); // => "RightToBearArmsLOL"
     /// ```
     pub fn user(&self) -> &str {
-        &self.concerns
+        self.concerns
     }
 
     /// Retreives the reason this user was banned.
@@ -270,7 +306,7 @@ impl<'a> Ban<'a> {
     /// ban.reason(); // => "failing to falsify the Christian god"
     /// ```
     pub fn reason(&self) -> &str {
-        &self.reasoning
+        self.reasoning
     }
 
     /// Retreieves the number of nanoseconds the user will be banned for.
@@ -326,13 +362,14 @@ impl<'a> Unban<'a> {
     /// unban.user(); // => "essaywriter"
     /// ```
     pub fn user(&self) -> &str {
-        &self.concerns
+        self.concerns
     }
 }
 
 /// Subonly is a command used to set whether or not the chat is open only to
-/// subscribers or not.
-#[derive(Serialize, Deserialize)]
+/// subscribers or not. Subonly events notify chatters of the new state once
+/// the command has been executed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 pub struct Subonly {
     /// Whether or not the chat should be in subonly mode
     on: bool,
@@ -373,293 +410,1473 @@ impl Subonly {
     }
 }
 
-/// Ping is a command used to initiate a client-server ping-pong loop.
-#[derive(Serialize, Deserialize)]
-pub struct Ping {
-    /// The time at which the ping request was initiated by the user
-    initiation_timestamp: NaiveDateTime,
+/// Slowmode is a command used to set whether or not chatters must wait
+/// between sending messages. Slowmode events notify chatters of the new
+/// state once the command has been executed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Slowmode {
+    /// Whether or not the chat should be in slowmode
+    on: bool,
+
+    /// The number of seconds that chatters must wait between messages, if
+    /// not the server's default
+    #[serde(default)]
+    interval: Option<u64>,
 }
 
-impl Default for Ping {
-    /// Generates a ping command at the current timestamp.
+impl Slowmode {
+    /// Creates a new Slowmode command, using the server's default interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Whether or not the chat should be in slowmode
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Ping;
-    /// use std::default::Default;
+    /// use gnomegg::spec::event::Slowmode;
     ///
-    /// let current_time_ping = Ping::default();
+    /// let slowmode = Slowmode::new(true).with_interval(Some(10));
     /// ```
-    fn default() -> Self {
-        Self::new()
+    pub fn new(on: bool) -> Self {
+        Self { on, interval: None }
     }
-}
 
-impl Ping {
-    /// Creates a new ping command at the current time.
+    /// Consumes the command, and modifies it according to the provided
+    /// interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - (optional) The number of seconds that chatters must
+    /// wait between messages
+    pub fn with_interval(mut self, interval: Option<u64>) -> Self {
+        self.interval = interval;
+
+        self
+    }
+
+    /// Determines whether or not slowmode will be active once this command
+    /// is executed.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Ping;
+    /// use gnomegg::spec::event::Slowmode;
     ///
-    /// let ping_request = Ping::new();
+    /// let slowmode = Slowmode::new(true);
+    /// slowmode.active(); // => true
     /// ```
-    pub fn new() -> Self {
-        Self {
-            initiation_timestamp: Utc::now().naive_utc(),
-        }
+    pub fn active(&self) -> bool {
+        self.on
     }
 
-    /// Creates a new ping command at the provided time.
+    /// Retreives the number of seconds that chatters must wait between
+    /// messages, if not the server's default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Slowmode;
+    ///
+    /// let slowmode = Slowmode::new(true).with_interval(Some(10));
+    /// slowmode.interval(); // => Some(10)
+    /// ```
+    pub fn interval(&self) -> Option<u64> {
+        self.interval
+    }
+}
+
+/// Handshake is an event sent to each chatter upon connecting, describing the
+/// modes that the chat is in, so that clients can render their input
+/// accordingly.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Handshake {
+    /// Whether or not the chat is in subonly mode
+    subonly: bool,
+
+    /// The number of seconds that chatters must wait between messages, if
+    /// the chat is in slowmode
+    slowmode: Option<u64>,
+
+    /// Whether or not the chatter may send messages in the chat's current
+    /// modes
+    can_chat: bool,
+
+    /// The message pinned above the chat, if there is one
+    pinned: Option<PinnedMessage>,
+}
+
+impl Handshake {
+    /// Creates a new handshake for a chat in no particular mode.
     ///
     /// # Arguments
     ///
-    /// * `initiation_timestamp` - The timestamp that the server will assume the
-    /// ping request was made at
+    /// * `can_chat` - Whether or not the chatter may send messages
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Ping;
-    /// use chrono::{Utc, Duration};
+    /// use gnomegg::spec::event::Handshake;
     ///
-    /// // Spectrum internet REE
-    /// let ping_request = Ping::new_with_initiation_timestamp(Utc::now() - Duration::seconds(420));
+    /// let handshake = Handshake::new(false).with_subonly(true).with_slowmode(Some(5));
     /// ```
-    pub fn new_with_initiation_timestamp(initiation_timestamp: DateTime<Utc>) -> Self {
+    pub fn new(can_chat: bool) -> Self {
         Self {
-            initiation_timestamp: initiation_timestamp.naive_utc(),
+            subonly: false,
+            slowmode: None,
+            can_chat,
+            pinned: None,
         }
     }
 
-    /// Retreieves the time at which this ping request was initiated.
+    /// Consumes the handshake, and modifies it according to whether or not
+    /// the chat is in subonly mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `subonly` - Whether or not the chat is in subonly mode
+    pub fn with_subonly(mut self, subonly: bool) -> Self {
+        self.subonly = subonly;
+
+        self
+    }
+
+    /// Consumes the handshake, and modifies it according to the provided
+    /// slowmode interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `slowmode` - (optional) The number of seconds that chatters must wait
+    /// between messages
+    pub fn with_slowmode(mut self, slowmode: Option<u64>) -> Self {
+        self.slowmode = slowmode;
+
+        self
+    }
+
+    /// Consumes the handshake, and modifies it such that it includes the
+    /// provided pinned message.
+    ///
+    /// # Arguments
+    ///
+    /// * `pinned` - (optional) The message pinned above the chat
+    pub fn with_pinned(mut self, pinned: Option<PinnedMessage>) -> Self {
+        self.pinned = pinned;
+
+        self
+    }
+
+    /// Determines whether or not the chat is in subonly mode.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Ping;
+    /// use gnomegg::spec::event::Handshake;
     ///
-    /// let ping_request = Ping::new();
-    /// ping_request.started_at(); // => Utc::now()
+    /// let handshake = Handshake::new(false).with_subonly(true);
+    /// handshake.subonly(); // => true
     /// ```
-    pub fn started_at(&self) -> NaiveDateTime {
-        self.initiation_timestamp
+    pub fn subonly(&self) -> bool {
+        self.subonly
     }
-}
-
-/// Pong is an event representing a response to a ping request from the server.
-#[derive(Serialize, Deserialize)]
-pub struct Pong {
-    /// The time at which the server responded to the user request for a ping
-    response_timestamp: DateTime<Utc>,
-}
 
-impl Default for Pong {
-    /// Generates a new pong response, assuming the server responded at the
-    /// current UTC time.
+    /// Retreives the number of seconds that chatters must wait between
+    /// messages, if the chat is in slowmode.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Pong;
-    /// use std::default::Default;
+    /// use gnomegg::spec::event::Handshake;
     ///
-    /// let ping_response = Pong::default();
+    /// let handshake = Handshake::new(true).with_slowmode(Some(5));
+    /// handshake.slowmode(); // => Some(5)
     /// ```
-    fn default() -> Self {
-        Self::new()
+    pub fn slowmode(&self) -> Option<u64> {
+        self.slowmode
     }
-}
 
-impl Pong {
-    /// Creates a new pong response.
+    /// Determines whether or not the chatter may send messages in the chat's
+    /// current modes.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Pong;
+    /// use gnomegg::spec::event::Handshake;
     ///
-    /// let ping_response = Pong::new();
+    /// let handshake = Handshake::new(false).with_subonly(true);
+    /// handshake.can_chat(); // => false
     /// ```
-    pub fn new() -> Self {
-        Self {
-            response_timestamp: Utc::now(),
-        }
+    pub fn can_chat(&self) -> bool {
+        self.can_chat
     }
 
-    /// Retreieves the time at which the server responded to the request for
-    /// ping.
+    /// Retreives the message pinned above the chat, if there is one.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Pong;
+    /// use gnomegg::spec::event::Handshake;
     ///
-    /// let ping_request = Pong::new();
-    /// ping_request.responded_at(); // => Utc::now()
+    /// let handshake = Handshake::new(true);
+    /// handshake.pinned(); // => None
     /// ```
-    pub fn responded_at(&self) -> DateTime<Utc> {
-        self.response_timestamp
+    pub fn pinned(&self) -> Option<&PinnedMessage> {
+        self.pinned.as_ref()
     }
 }
 
-/// Broadcast is an event representing an incoming message, intended for the
-/// entire server.
-#[derive(Serialize, Deserialize)]
-pub struct Broadcast<'a> {
-    /// The sender of the message
-    sender: &'a str,
-
-    /// The message sent in the broadcast event
-    message: Message<'a>,
+/// PinMessage is a command used to pin a message above the chat, replacing
+/// any message that is already pinned.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct PinMessage<'a> {
+    /// The contents of the message that should be pinned
+    message: &'a str,
+
+    /// The number of seconds for which the message should remain pinned. If
+    /// no TTL is provided, the server's default is used.
+    #[serde(default)]
+    ttl: Option<u64>,
 }
 
-impl<'a> Broadcast<'a> {
-    /// Creates a new broadcast event with the given user and message.
+impl<'a> PinMessage<'a> {
+    /// Creates a new PinMessage command for the given message.
     ///
     /// # Arguments
     ///
-    /// * `sender` - The username of the sender of the message
-    /// * `message` - The contents of the message to be broadcasted
+    /// * `message` - The contents of the message that should be pinned
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Broadcast;
+    /// use gnomegg::spec::event::PinMessage;
     ///
-    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
+    /// let pin = PinMessage::new("Debate at 5").with_ttl(Some(3600));
     /// ```
-    pub fn new(sender: &'a str, message: &'a str) -> Self {
-        Self {
-            sender,
-            message: Message::new(message),
-        }
+    pub fn new(message: &'a str) -> Self {
+        Self { message, ttl: None }
     }
 
-    /// Gets the username of the chatter that sent the message.
+    /// Consumes the command, and modifies it such that the message is
+    /// automatically unpinned after the given number of seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - (optional) The number of seconds for which the message
+    /// should remain pinned
+    pub fn with_ttl(mut self, ttl: Option<u64>) -> Self {
+        self.ttl = ttl;
+
+        self
+    }
+
+    /// Retreives the contents of the message that should be pinned.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Broadcast;
+    /// use gnomegg::spec::event::PinMessage;
     ///
-    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
-    /// broadcasted_msg.sent_by(); // => "MrMouton"
+    /// let pin = PinMessage::new("Debate at 5");
+    /// pin.message(); // => "Debate at 5"
     /// ```
-    pub fn sent_by(&self) -> &str {
-        &self.sender
+    pub fn message(&self) -> &'a str {
+        self.message
     }
 
-    /// Gets the contents of the broadcasted message.
+    /// Retreives the number of seconds for which the message should remain
+    /// pinned, if a TTL was provided.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::Broadcast;
+    /// use gnomegg::spec::event::PinMessage;
     ///
-    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
-    /// broadcasted_msg.msg(); // => "I am a living meme holy shit. Hacked by a 7 year old."
+    /// let pin = PinMessage::new("Debate at 5").with_ttl(Some(3600));
+    /// pin.ttl(); // => Some(3600)
     /// ```
-    pub fn msg(&self) -> &str {
-        self.message.msg()
+    pub fn ttl(&self) -> Option<u64> {
+        self.ttl
     }
 }
 
-/// Error is an event representing a failure response from the server to a set
-/// of clients.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Error<'a> {
-    /// The users that this error will be communicated to
-    concerns: EventTarget<'a>,
-
-    /// The error that will be sent to each user
-    error: &'a str,
+/// UnpinMessage is a command used to unpin the pinned message. It is also
+/// emitted as an event notifying chatters that the message was unpinned.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct UnpinMessage;
+
+/// DeleteMessage is a command used to delete a message sent to the chat. It
+/// is also emitted as an event notifying chatters that the message was
+/// deleted.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct DeleteMessage {
+    /// The ID of the message in the chat history
+    id: u64,
 }
 
-impl<'a> Error<'a> {
-    /// Creates a new error with the given target and error message.
+impl DeleteMessage {
+    /// Creates a new DeleteMessage command.
     ///
     /// # Arguments
     ///
-    /// * `target` - The users the error will be sent to
-    /// * `error` - The error message that will be sent to the aforementioned users
+    /// * `id` - The ID of the message that should be deleted
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::{Error, EventTarget};
+    /// use gnomegg::spec::event::DeleteMessage;
     ///
-    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
+    /// let delete = DeleteMessage::new(1);
+    /// delete.id(); // => 1
     /// ```
-    pub fn new(target: EventTarget<'a>, error: &'a str) -> Self {
-        Self {
-            concerns: target,
-            error,
-        }
+    pub fn new(id: u64) -> Self {
+        Self { id }
     }
 
-    /// Determines the users that will be affected by this error.
+    /// Retreives the ID of the message in the chat history.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// EditMessage is a command used by the sender of a message to change its
+/// contents shortly after sending it. It is also emitted as an event
+/// notifying chatters of the message's new contents.
+#[derive(Serialize, Deserialize)]
+pub struct EditMessage<'a> {
+    /// The ID of the message in the chat history
+    id: u64,
+
+    /// The new contents of the message
+    #[serde(borrow)]
+    message: Message<'a>,
+
+    /// Each of the emotes used in the new contents of the message
+    #[serde(borrow, default)]
+    emotes: Vec<EmoteSpan<'a>>,
+}
+
+impl<'a> EditMessage<'a> {
+    /// Creates a new EditMessage command.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be edited
+    /// * `contents` - The new contents of the message
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::{Error, EventTarget};
+    /// use gnomegg::spec::event::EditMessage;
     ///
-    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
-    /// err.targets(); // => EventTarget::All
+    /// let edit = EditMessage::new(1, "Pog");
+    /// edit.contents(); // => "Pog"
     /// ```
-    pub fn targets(&self) -> &EventTarget {
-        &self.concerns
+    pub fn new(id: u64, contents: &'a str) -> Self {
+        Self {
+            id,
+            message: Message::new(contents),
+            emotes: Vec::new(),
+        }
     }
 
-    /// Retreieves the message corresponding to this error.
+    /// Consumes the edit, and attaches the location of each of the emotes
+    /// used in the new contents of the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `emotes` - The emotes used in the new contents of the message
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::event::{Error, EventTarget};
+    /// use gnomegg::spec::event::{EditMessage, EmoteSpan};
     ///
-    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
-    /// err.err_message(); // => "mister mouton got evicted Slumlord"
+    /// let edit = EditMessage::new(1, "Pog").with_emotes(vec![EmoteSpan::new("Pog", 0)]);
+    /// edit.emotes()[0].end(); // => 3
     /// ```
-    pub fn err_message(&self) -> &str {
-        &self.error
-    }
-}
-
-/// CommandKind represents any one of the possible commands.
-#[derive(Serialize, Deserialize)]
-pub enum CommandKind<'a> {
-    /// This command sends a message
-    #[serde(borrow)]
-    Message(Message<'a>),
+    pub fn with_emotes(mut self, emotes: Vec<EmoteSpan<'a>>) -> Self {
+        self.emotes = emotes;
 
-    /// This command sends a message to one user
-    PrivMessage(PrivMessage<'a>),
+        self
+    }
 
-    /// This command mutes a user
-    Mute(Mute<'a>),
+    /// Retreives the ID of the message in the chat history.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-    /// This command unmutes a user
-    Unmute(Unmute<'a>),
+    /// Retreives the new contents of the message.
+    pub fn contents(&self) -> &str {
+        self.message.msg()
+    }
 
-    /// This command bans a user
-    Ban(Ban<'a>),
+    /// Gets the location of each of the emotes used in the new contents of
+    /// the message.
+    pub fn emotes(&self) -> &[EmoteSpan<'a>] {
+        &self.emotes
+    }
+}
 
-    /// This command unbans a user
-    Unban(Unban<'a>),
+/// StartPoll is a command used to put a question to the chat.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct StartPoll<'a> {
+    /// The question asked by the poll
+    question: &'a str,
 
-    /// This command makes the chat sub-only mode
-    Subonly(Subonly),
+    /// The answers that chatters may vote for
+    #[serde(borrow)]
+    options: Vec<&'a str>,
 
-    /// This command pings a user
-    Ping(Ping),
+    /// Whether or not votes should be weighted by the voter's subscription
+    #[serde(default)]
+    weighted: bool,
 }
 
-/// Command represents any valid command, alongside the user issuing the
-/// command.
+impl<'a> StartPoll<'a> {
+    /// Creates a new StartPoll command for an unweighted poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `question` - The question asked by the poll
+    /// * `options` - The answers that chatters may vote for
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::StartPoll;
+    ///
+    /// let start = StartPoll::new("Is Destiny based?", vec!["yes", "no"]).with_weighted(true);
+    /// ```
+    pub fn new(question: &'a str, options: Vec<&'a str>) -> Self {
+        Self {
+            question,
+            options,
+            weighted: false,
+        }
+    }
+
+    /// Consumes the command, and modifies it according to whether or not
+    /// votes should be weighted by the voter's subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `weighted` - Whether or not votes should be weighted
+    pub fn with_weighted(mut self, weighted: bool) -> Self {
+        self.weighted = weighted;
+
+        self
+    }
+
+    /// Retreives the question asked by the poll.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::StartPoll;
+    ///
+    /// let start = StartPoll::new("Is Destiny based?", vec!["yes", "no"]);
+    /// start.question(); // => "Is Destiny based?"
+    /// ```
+    pub fn question(&self) -> &'a str {
+        self.question
+    }
+
+    /// Retreives the answers that chatters may vote for.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::StartPoll;
+    ///
+    /// let start = StartPoll::new("Is Destiny based?", vec!["yes", "no"]);
+    /// start.options(); // => ["yes", "no"]
+    /// ```
+    pub fn options(&self) -> &[&'a str] {
+        &self.options
+    }
+
+    /// Determines whether or not votes should be weighted by the voter's
+    /// subscription.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::StartPoll;
+    ///
+    /// let start = StartPoll::new("Is Destiny based?", vec!["yes", "no"]);
+    /// start.weighted(); // => false
+    /// ```
+    pub fn weighted(&self) -> bool {
+        self.weighted
+    }
+}
+
+/// Vote is a command used to vote for one of the options of the active poll.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Vote {
+    /// The ID of the poll in which the vote is cast
+    poll: u64,
+
+    /// The index of the option being voted for
+    option: usize,
+}
+
+impl Vote {
+    /// Creates a new Vote command.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The ID of the poll in which the vote is cast
+    /// * `option` - The index of the option being voted for
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Vote;
+    ///
+    /// let vote = Vote::new(1, 0);
+    /// vote.option(); // => 0
+    /// ```
+    pub fn new(poll: u64, option: usize) -> Self {
+        Self { poll, option }
+    }
+
+    /// Retreives the ID of the poll in which the vote is cast.
+    pub fn poll(&self) -> u64 {
+        self.poll
+    }
+
+    /// Retreives the index of the option being voted for.
+    pub fn option(&self) -> usize {
+        self.option
+    }
+}
+
+/// EndPoll is a command used to end the active poll, announcing its results.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct EndPoll;
+
+/// PollVotes is an event notifying chatters of the number of votes cast for
+/// each of the active poll's options.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PollVotes {
+    /// The ID of the poll
+    poll: u64,
+
+    /// The number of votes cast for each option, in the order that the
+    /// options were given
+    totals: Vec<u64>,
+}
+
+impl PollVotes {
+    /// Creates a new PollVotes event.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The ID of the poll
+    /// * `totals` - The number of votes cast for each option
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::PollVotes;
+    ///
+    /// let votes = PollVotes::new(1, vec![3, 1]);
+    /// votes.totals(); // => [3, 1]
+    /// ```
+    pub fn new(poll: u64, totals: Vec<u64>) -> Self {
+        Self { poll, totals }
+    }
+
+    /// Retreives the ID of the poll.
+    pub fn poll(&self) -> u64 {
+        self.poll
+    }
+
+    /// Retreives the number of votes cast for each option.
+    pub fn totals(&self) -> &[u64] {
+        &self.totals
+    }
+}
+
+/// Ping is a command used to initiate a client-server ping-pong loop.
+#[derive(Serialize, Deserialize)]
+pub struct Ping {
+    /// The time at which the ping request was initiated by the user
+    initiation_timestamp: NaiveDateTime,
+}
+
+impl Default for Ping {
+    /// Generates a ping command at the current timestamp.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Ping;
+    /// use std::default::Default;
+    ///
+    /// let current_time_ping = Ping::default();
+    /// ```
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ping {
+    /// Creates a new ping command at the current time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Ping;
+    ///
+    /// let ping_request = Ping::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            initiation_timestamp: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Creates a new ping command at the provided time.
+    ///
+    /// # Arguments
+    ///
+    /// * `initiation_timestamp` - The timestamp that the server will assume the
+    /// ping request was made at
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Ping;
+    /// use chrono::{Utc, Duration};
+    ///
+    /// // Spectrum internet REE
+    /// let ping_request = Ping::new_with_initiation_timestamp(Utc::now() - Duration::seconds(420));
+    /// ```
+    pub fn new_with_initiation_timestamp(initiation_timestamp: DateTime<Utc>) -> Self {
+        Self {
+            initiation_timestamp: initiation_timestamp.naive_utc(),
+        }
+    }
+
+    /// Retreieves the time at which this ping request was initiated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Ping;
+    ///
+    /// let ping_request = Ping::new();
+    /// ping_request.started_at(); // => Utc::now()
+    /// ```
+    pub fn started_at(&self) -> NaiveDateTime {
+        self.initiation_timestamp
+    }
+}
+
+/// Pong is an event representing a response to a ping request from the server.
+#[derive(Serialize, Deserialize)]
+pub struct Pong {
+    /// The time at which the server responded to the user request for a ping
+    response_timestamp: DateTime<Utc>,
+}
+
+impl Default for Pong {
+    /// Generates a new pong response, assuming the server responded at the
+    /// current UTC time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Pong;
+    /// use std::default::Default;
+    ///
+    /// let ping_response = Pong::default();
+    /// ```
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pong {
+    /// Creates a new pong response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Pong;
+    ///
+    /// let ping_response = Pong::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            response_timestamp: Utc::now(),
+        }
+    }
+
+    /// Retreieves the time at which the server responded to the request for
+    /// ping.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Pong;
+    ///
+    /// let ping_request = Pong::new();
+    /// ping_request.responded_at(); // => Utc::now()
+    /// ```
+    pub fn responded_at(&self) -> DateTime<Utc> {
+        self.response_timestamp
+    }
+}
+
+/// Broadcast is an event representing an incoming message, intended for the
+/// entire server.
+#[derive(Serialize, Deserialize)]
+pub struct Broadcast<'a> {
+    /// The sender of the message
+    sender: &'a str,
+
+    /// The message sent in the broadcast event
+    message: Message<'a>,
+
+    /// Whether or not the sender's account was created recently
+    #[serde(default)]
+    new_account: bool,
+
+    /// The usernames of each of the chatters mentioned in the message
+    #[serde(borrow, default)]
+    mentions: Vec<&'a str>,
+
+    /// Each of the emotes used in the message
+    #[serde(borrow, default)]
+    emotes: Vec<EmoteSpan<'a>>,
+
+    /// The names of each of the flairs held by the sender, in the order that
+    /// they are displayed
+    #[serde(default)]
+    flairs: Vec<String>,
+
+    /// Whether or not this is the first message sent by the sender
+    #[serde(default)]
+    first_message: bool,
+
+    /// The ID assigned to the message in the chat history, if it was
+    /// recorded there
+    #[serde(default)]
+    id: Option<u64>,
+
+    /// The ID of the sender
+    #[serde(default)]
+    sender_id: Option<u64>,
+
+    /// The roles held by the sender
+    #[serde(default)]
+    roles: Vec<Role>,
+
+    /// The tier of the subscription held by the sender, if they hold one
+    #[serde(default)]
+    tier: Option<u8>,
+}
+
+impl<'a> Broadcast<'a> {
+    /// Creates a new broadcast event with the given user and message.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - The username of the sender of the message
+    /// * `message` - The contents of the message to be broadcasted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
+    /// ```
+    pub fn new(sender: &'a str, message: &'a str) -> Self {
+        Self {
+            sender,
+            message: Message::new(message),
+            new_account: false,
+            mentions: Vec::new(),
+            emotes: Vec::new(),
+            flairs: Vec::new(),
+            first_message: false,
+            id: None,
+            sender_id: None,
+            roles: Vec::new(),
+            tier: None,
+        }
+    }
+
+    /// Creates a new broadcast event with the given user and an owned
+    /// message, such as one produced by the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - The username of the sender of the message
+    /// * `message` - The contents of the message to be broadcasted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new_owned("gnomebot", format!("!{} !{}", "logs", "song"));
+    /// broadcasted_msg.msg(); // => "!logs !song"
+    /// ```
+    pub fn new_owned(sender: &'a str, message: String) -> Self {
+        let mut broadcast = Self::new(sender, "");
+        broadcast.message.set_msg(message);
+
+        broadcast
+    }
+
+    /// Consumes the broadcast, and marks it according to whether or not the
+    /// sender's account was created recently.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_account` - Whether or not the sender's account is new
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("essaywriter", "first time chatter, long time listener").with_new_account(true);
+    /// ```
+    pub fn with_new_account(mut self, new_account: bool) -> Self {
+        self.new_account = new_account;
+
+        self
+    }
+
+    /// Gets the username of the chatter that sent the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
+    /// broadcasted_msg.sent_by(); // => "MrMouton"
+    /// ```
+    pub fn sent_by(&self) -> &str {
+        self.sender
+    }
+
+    /// Gets the contents of the broadcasted message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "I am a living meme holy shit. Hacked by a 7 year old.");
+    /// broadcasted_msg.msg(); // => "I am a living meme holy shit. Hacked by a 7 year old."
+    /// ```
+    pub fn msg(&self) -> &str {
+        self.message.msg()
+    }
+
+    /// Determines whether or not the sender's account was created recently.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("essaywriter", "first time chatter, long time listener").with_new_account(true);
+    /// broadcasted_msg.from_new_account(); // => true
+    /// ```
+    pub fn from_new_account(&self) -> bool {
+        self.new_account
+    }
+
+    /// Consumes the broadcast, and attaches the usernames of each of the
+    /// chatters mentioned in the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `mentions` - The usernames of the mentioned chatters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "@Destiny what's your take").with_mentions(vec!["Destiny"]);
+    /// ```
+    pub fn with_mentions(mut self, mentions: Vec<&'a str>) -> Self {
+        self.mentions = mentions;
+
+        self
+    }
+
+    /// Gets the usernames of each of the chatters mentioned in the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "@Destiny what's your take").with_mentions(vec!["Destiny"]);
+    /// broadcasted_msg.mentions(); // => ["Destiny"]
+    /// ```
+    pub fn mentions(&self) -> &[&'a str] {
+        &self.mentions
+    }
+
+    /// Consumes the broadcast, and attaches the location of each of the
+    /// emotes used in the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `emotes` - The emotes used in the message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Broadcast, EmoteSpan};
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "OverRustle").with_emotes(vec![EmoteSpan::new("OverRustle", 0)]);
+    /// ```
+    pub fn with_emotes(mut self, emotes: Vec<EmoteSpan<'a>>) -> Self {
+        self.emotes = emotes;
+
+        self
+    }
+
+    /// Gets the location of each of the emotes used in the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Broadcast, EmoteSpan};
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "OverRustle").with_emotes(vec![EmoteSpan::new("OverRustle", 0)]);
+    /// broadcasted_msg.emotes()[0].end(); // => 10
+    /// ```
+    pub fn emotes(&self) -> &[EmoteSpan<'a>] {
+        &self.emotes
+    }
+
+    /// Consumes the broadcast, and attaches the names of each of the flairs
+    /// held by the sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `flairs` - The names of the sender's flairs, in the order that they
+    /// are displayed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "hello").with_flairs(vec!["moderator".to_owned()]);
+    /// ```
+    pub fn with_flairs(mut self, flairs: Vec<String>) -> Self {
+        self.flairs = flairs;
+
+        self
+    }
+
+    /// Gets the names of each of the flairs held by the sender, in the order
+    /// that they are displayed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "hello").with_flairs(vec!["moderator".to_owned()]);
+    /// broadcasted_msg.flairs(); // => ["moderator"]
+    /// ```
+    pub fn flairs(&self) -> &[String] {
+        &self.flairs
+    }
+
+    /// Consumes the broadcast, and modifies it according to whether or not
+    /// it is the first message sent by the sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_message` - Whether or not this is the sender's first message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("essaywriter", "first time chatter, long time listener").with_first_message(true);
+    /// ```
+    pub fn with_first_message(mut self, first_message: bool) -> Self {
+        self.first_message = first_message;
+
+        self
+    }
+
+    /// Determines whether or not this is the first message sent by the
+    /// sender.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("essaywriter", "first time chatter, long time listener").with_first_message(true);
+    /// broadcasted_msg.is_first_message(); // => true
+    /// ```
+    pub fn is_first_message(&self) -> bool {
+        self.first_message
+    }
+
+    /// Consumes the broadcast, and attaches the ID assigned to the message in
+    /// the chat history, so that it may be referred to later (e.g. when it is
+    /// deleted).
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - (optional) The ID assigned to the message in the chat history
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Broadcast;
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "Pog").with_id(Some(1));
+    /// broadcasted_msg.id(); // => Some(1)
+    /// ```
+    pub fn with_id(mut self, id: Option<u64>) -> Self {
+        self.id = id;
+
+        self
+    }
+
+    /// Gets the ID assigned to the message in the chat history, if it was
+    /// recorded there.
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    /// Consumes the broadcast, and attaches the sender's ID, roles, flairs
+    /// and subscription tier, so that clients needn't look them up for each
+    /// message.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - The profile of the sender
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::{event::Broadcast, profile::SenderProfile, user::Role};
+    ///
+    /// let broadcasted_msg = Broadcast::new("MrMouton", "hello")
+    ///     .with_profile(SenderProfile::new(69420, vec![Role::Subscriber]).with_tier(Some(1)));
+    /// broadcasted_msg.tier(); // => Some(1)
+    /// ```
+    pub fn with_profile(mut self, profile: SenderProfile) -> Self {
+        self.sender_id = Some(profile.user_id());
+        self.roles = profile.roles().to_vec();
+        self.flairs = profile.flairs().to_vec();
+        self.tier = profile.tier();
+
+        self
+    }
+
+    /// Gets the ID of the sender, if it was attached.
+    pub fn sender_id(&self) -> Option<u64> {
+        self.sender_id
+    }
+
+    /// Gets the roles held by the sender.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Gets the tier of the subscription held by the sender, if they hold
+    /// one.
+    pub fn tier(&self) -> Option<u8> {
+        self.tier
+    }
+}
+
+/// EmoteSpan represents the location of an emote used in a message.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct EmoteSpan<'a> {
+    /// The code of the emote
+    code: &'a str,
+
+    /// The byte offset at which the emote's code begins
+    start: usize,
+
+    /// The byte offset at which the emote's code ends, exclusive
+    end: usize,
+}
+
+impl<'a> EmoteSpan<'a> {
+    /// Creates a new emote span for the given code, beginning at the given
+    /// offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote
+    /// * `start` - The byte offset at which the code begins in the message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::EmoteSpan;
+    ///
+    /// let span = EmoteSpan::new("PepeLaugh", 4);
+    /// ```
+    pub fn new(code: &'a str, start: usize) -> Self {
+        Self {
+            code,
+            start,
+            end: start + code.len(),
+        }
+    }
+
+    /// Gets the code of the emote.
+    pub fn code(&self) -> &str {
+        self.code
+    }
+
+    /// Gets the byte offset at which the emote's code begins.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Gets the byte offset at which the emote's code ends, exclusive.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+/// Combo is an event notifying chatters that consecutive messages have
+/// consisted only of the same emote, so that each client displays the same
+/// running count.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Combo<'a> {
+    /// The code of the emote being comboed
+    emote: &'a str,
+
+    /// The number of consecutive messages consisting only of the emote
+    count: u64,
+}
+
+impl<'a> Combo<'a> {
+    /// Creates a new combo event.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The code of the emote being comboed
+    /// * `count` - The number of consecutive messages consisting only of the
+    /// emote
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Combo;
+    ///
+    /// let combo = Combo::new("PEPE", 3);
+    /// combo.count(); // => 3
+    /// ```
+    pub fn new(emote: &'a str, count: u64) -> Self {
+        Self { emote, count }
+    }
+
+    /// Retreives the code of the emote being comboed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Combo;
+    ///
+    /// let combo = Combo::new("PEPE", 3);
+    /// combo.emote(); // => "PEPE"
+    /// ```
+    pub fn emote(&self) -> &str {
+        self.emote
+    }
+
+    /// Retreives the number of consecutive messages consisting only of the
+    /// emote.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// StreamStatus is an event notifying chatters that the stream went live, or
+/// went offline.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct StreamStatus {
+    /// Whether or not the stream is live
+    live: bool,
+
+    /// The time at which the stream went live or offline
+    since: DateTime<Utc>,
+}
+
+impl StreamStatus {
+    /// Creates a new stream status event.
+    ///
+    /// # Arguments
+    ///
+    /// * `live` - Whether or not the stream is live
+    /// * `since` - The time at which the stream went live or offline
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::event::StreamStatus;
+    ///
+    /// let status = StreamStatus::new(true, Utc::now());
+    /// assert!(status.live());
+    /// ```
+    pub fn new(live: bool, since: DateTime<Utc>) -> Self {
+        Self { live, since }
+    }
+
+    /// Determines whether or not the stream is live.
+    pub fn live(&self) -> bool {
+        self.live
+    }
+
+    /// Retreives the time at which the stream went live or offline.
+    pub fn since(&self) -> DateTime<Utc> {
+        self.since
+    }
+}
+
+/// Highlight is an event notifying a chatter that they were mentioned in a
+/// message.
+#[derive(Serialize, Deserialize)]
+pub struct Highlight<'a> {
+    /// The sender of the message mentioning the chatter
+    sender: &'a str,
+
+    /// The message mentioning the chatter
+    message: Message<'a>,
+}
+
+impl<'a> Highlight<'a> {
+    /// Creates a new highlight event for a message sent by the given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - The username of the sender of the message
+    /// * `message` - The contents of the message mentioning the chatter
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Highlight;
+    ///
+    /// let highlight = Highlight::new("MrMouton", "Destiny when's the next debate");
+    /// ```
+    pub fn new(sender: &'a str, message: &'a str) -> Self {
+        Self {
+            sender,
+            message: Message::new(message),
+        }
+    }
+
+    /// Gets the username of the chatter that sent the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Highlight;
+    ///
+    /// let highlight = Highlight::new("MrMouton", "Destiny when's the next debate");
+    /// highlight.sent_by(); // => "MrMouton"
+    /// ```
+    pub fn sent_by(&self) -> &str {
+        self.sender
+    }
+
+    /// Gets the contents of the message mentioning the chatter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::Highlight;
+    ///
+    /// let highlight = Highlight::new("MrMouton", "Destiny when's the next debate");
+    /// highlight.msg(); // => "Destiny when's the next debate"
+    /// ```
+    pub fn msg(&self) -> &str {
+        self.message.msg()
+    }
+}
+
+/// Error is an event representing a failure response from the server to a set
+/// of clients.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Error<'a> {
+    /// The users that this error will be communicated to
+    concerns: EventTarget<'a>,
+
+    /// The error that will be sent to each user
+    error: &'a str,
+
+    /// The number of seconds after which the request may be retried, if it
+    /// was rejected for being too early
+    #[serde(default)]
+    retry_after: Option<u64>,
+}
+
+impl<'a> Error<'a> {
+    /// Creates a new error with the given target and error message.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The users the error will be sent to
+    /// * `error` - The error message that will be sent to the aforementioned users
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Error, EventTarget};
+    ///
+    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
+    /// ```
+    pub fn new(target: EventTarget<'a>, error: &'a str) -> Self {
+        Self {
+            concerns: target,
+            error,
+            retry_after: None,
+        }
+    }
+
+    /// Consumes the error, and modifies it according to the provided
+    /// countdown.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_after` - (optional) The number of seconds after which the
+    /// request may be retried
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Error, EventTarget};
+    ///
+    /// let err = Error::new(EventTarget::User("MrMouton"), "slowmode").with_retry_after(Some(3));
+    /// ```
+    pub fn with_retry_after(mut self, retry_after: Option<u64>) -> Self {
+        self.retry_after = retry_after;
+
+        self
+    }
+
+    /// Determines the users that will be affected by this error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Error, EventTarget};
+    ///
+    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
+    /// err.targets(); // => EventTarget::All
+    /// ```
+    pub fn targets(&self) -> &EventTarget<'_> {
+        &self.concerns
+    }
+
+    /// Retreieves the message corresponding to this error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Error, EventTarget};
+    ///
+    /// let err = Error::new(EventTarget::All, "mister mouton got evicted Slumlord");
+    /// err.err_message(); // => "mister mouton got evicted Slumlord"
+    /// ```
+    pub fn err_message(&self) -> &str {
+        self.error
+    }
+
+    /// Retreives the number of seconds after which the request may be
+    /// retried, if it was rejected for being too early.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{Error, EventTarget};
+    ///
+    /// let err = Error::new(EventTarget::User("MrMouton"), "slowmode").with_retry_after(Some(3));
+    /// err.retry_after(); // => Some(3)
+    /// ```
+    pub fn retry_after(&self) -> Option<u64> {
+        self.retry_after
+    }
+}
+
+/// CommandKind represents any one of the possible commands.
+#[derive(Serialize, Deserialize)]
+pub enum CommandKind<'a> {
+    /// This command sends a message
+    #[serde(borrow)]
+    Message(Message<'a>),
+
+    /// This command sends a message to one user
+    PrivMessage(PrivMessage<'a>),
+
+    /// This command mutes a user
+    Mute(Mute<'a>),
+
+    /// This command unmutes a user
+    Unmute(Unmute<'a>),
+
+    /// This command bans a user
+    Ban(Ban<'a>),
+
+    /// This command unbans a user
+    Unban(Unban<'a>),
+
+    /// This command makes the chat sub-only mode
+    Subonly(Subonly),
+
+    /// This command sets whether or not chatters must wait between messages
+    Slowmode(Slowmode),
+
+    /// This command starts a poll
+    StartPoll(StartPoll<'a>),
+
+    /// This command votes in the active poll
+    Vote(Vote),
+
+    /// This command ends the active poll
+    EndPoll(EndPoll),
+
+    /// This command pins a message above the chat
+    PinMessage(PinMessage<'a>),
+
+    /// This command unpins the pinned message
+    UnpinMessage(UnpinMessage),
+
+    /// This command deletes a message sent to the chat
+    DeleteMessage(DeleteMessage),
+
+    /// This command edits a message recently sent to the chat
+    EditMessage(EditMessage<'a>),
+
+    /// This command pings a user
+    Ping(Ping),
+}
+
+/// Command represents any valid command, alongside the user issuing the
+/// command.
 #[derive(Serialize, Deserialize)]
 pub struct Command<'a> {
     /// The issuer of the command
@@ -704,7 +1921,7 @@ impl<'a> Command<'a> {
     ///
     /// cmd.command_type(); // => CommandKind::Message
     /// ```
-    pub fn command_type(&self) -> &CommandKind {
+    pub fn command_type(&self) -> &CommandKind<'_> {
         &self.kind
     }
 
@@ -722,7 +1939,31 @@ impl<'a> Command<'a> {
     /// cmd.command_type(); // => CommandKind::Message
     /// ```
     pub fn sent_by(&self) -> &str {
-        &self.issuer
+        self.issuer
+    }
+
+    /// Retreives a mutable reference to the message carried by the command,
+    /// if it sends a public or private message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::event::{CommandKind, Command, Message};
+    ///
+    /// let msg = Message::new("Hi nathanPepe dadd");
+    /// let mut cmd = Command::new("MrMouton", CommandKind::Message(msg));
+    ///
+    /// if let Some(msg) = cmd.message_mut() {
+    ///     msg.set_msg("Hi dadd".to_owned());
+    /// }
+    /// ```
+    pub fn message_mut(&mut self) -> Option<&mut Message<'a>> {
+        match &mut self.kind {
+            CommandKind::Message(msg) => Some(msg),
+            CommandKind::PrivMessage(msg) => Some(&mut msg.message),
+            CommandKind::EditMessage(edit) => Some(&mut edit.message),
+            _ => None,
+        }
     }
 }
 
@@ -736,6 +1977,9 @@ pub enum EventTarget<'a> {
     /// This event targets a specific user
     User(&'a str),
 
+    /// This event targets each active chatter holding the given role
+    Role(Role),
+
     /// This event is hidden, and will only be seen by the server
     Server,
 }
@@ -748,13 +1992,91 @@ pub enum EventKind<'a> {
     IssueCommand(Command<'a>),
 
     /// This event represents a response to a ping request from the server
-    Pong,
+    Pong(Pong),
 
     /// This event represents a new message being broadcasted
-    Broadcast,
+    Broadcast(Broadcast<'a>),
 
     /// This event represents a response to a client request with an error
-    Error,
+    Error(Error<'a>),
+
+    /// This event represents a chatter being mentioned in a message
+    Highlight(Highlight<'a>),
+
+    /// This event represents a change in whether or not the chat is in
+    /// slowmode
+    Slowmode(Slowmode),
+
+    /// This event represents a change in whether or not the chat is in
+    /// subonly mode
+    Subonly(Subonly),
+
+    /// This event represents the state of the chat sent to a connecting
+    /// chatter
+    Handshake(Handshake),
+
+    /// This event represents a poll being started
+    PollStarted(Poll),
+
+    /// This event represents a change in the number of votes cast in the
+    /// active poll
+    PollVotes(PollVotes),
+
+    /// This event represents a poll ending, alongside its results
+    PollEnded(PollResult),
+
+    /// This event represents a combo of an emote being extended
+    Combo(Combo<'a>),
+
+    /// This event represents a message being pinned above the chat
+    Pinned(PinnedMessage),
+
+    /// This event represents the pinned message being unpinned
+    Unpinned(UnpinMessage),
+
+    /// This event represents an announcement sent by the server
+    Announcement(Announcement),
+
+    /// This event represents the issuer's first message being held until a
+    /// moderator approves it
+    HeldForApproval(HeldMessage),
+
+    /// This event represents a message being deleted by a moderator
+    Deleted(DeleteMessage),
+
+    /// This event represents a message being edited by its sender
+    Edited(EditMessage<'a>),
+
+    /// This event represents the ranking of the embeds being watched by the
+    /// most chatters
+    Embeds(Vec<EmbedRank>),
+
+    /// This event represents a donation made to the streamer
+    Donation(Donation),
+
+    /// This event represents a subscription gifted from one chatter to
+    /// another
+    GiftedSubscription(GiftedSubscription),
+
+    /// This event represents a chatter being banned by a moderator. The
+    /// timeframe of a permanent ban is zero.
+    Banned(Ban<'a>),
+
+    /// This event represents a chatter's ban being lifted by a moderator
+    Unbanned(Unban<'a>),
+
+    /// This event represents a chatter being muted by a moderator
+    Muted(Mute<'a>),
+
+    /// This event represents a chatter's mute being lifted by a moderator
+    Unmuted(Unmute<'a>),
+
+    /// This event represents the stream going live, or going offline
+    StreamStatus(StreamStatus),
+
+    /// This event represents a change in the number of whispers that the
+    /// chatter has yet to read in one of their conversations
+    WhisperUnread(UnreadCount),
 }
 
 /// Event represents any action on gnomegg that might require a change in state.
@@ -807,7 +2129,7 @@ impl<'a> Event<'a> {
     /// let event = Event::new(EventTarget::User("Destiny"), EventKind::IssueCommand(cmd));
     /// event.targets(); // => EventTarget::User("Destiny")
     /// ```
-    pub fn targets(&self) -> &EventTarget {
+    pub fn targets(&self) -> &EventTarget<'_> {
         &self.concerns
     }
 
@@ -824,7 +2146,7 @@ impl<'a> Event<'a> {
     /// let event = Event::new(EventTarget::User("Destiny"), EventKind::IssueCommand(cmd));
     /// event.targets(); // => EventTarget::User("Destiny")
     /// ```
-    pub fn event_kind(&self) -> &EventKind {
+    pub fn event_kind(&self) -> &EventKind<'_> {
         &self.kind
     }
 }
@@ -0,0 +1,60 @@
+use redis::RedisError;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{Error as IoError, ErrorKind},
+    str::FromStr,
+};
+
+/// The format in which timestamps are stored in the fields of redis hashes,
+/// matching the format in which they are serialized.
+pub(crate) const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Parses the field of a redis hash with the given name, if it is set.
+///
+/// # Arguments
+///
+/// * `fields` - The fields of the hash
+/// * `name` - The name of the field that should be parsed
+pub(crate) fn optional<T>(
+    fields: &HashMap<String, String>,
+    name: &'static str,
+) -> Result<Option<T>, RedisError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fields
+        .get(name)
+        .map(|value| {
+            value.parse().map_err(|e| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid field {}: {}", name, e),
+                )
+                .into()
+            })
+        })
+        .transpose()
+}
+
+/// Parses the field of a redis hash with the given name, failing if it isn't
+/// set.
+///
+/// # Arguments
+///
+/// * `fields` - The fields of the hash
+/// * `name` - The name of the field that should be parsed
+pub(crate) fn required<T>(
+    fields: &HashMap<String, String>,
+    name: &'static str,
+) -> Result<T, RedisError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    optional(fields, name)?.ok_or_else(|| {
+        IoError::new(ErrorKind::InvalidData, format!("missing field {}", name)).into()
+    })
+}
@@ -0,0 +1,191 @@
+use super::{
+    backend::Unsigned,
+    schema::{flair_grants, flairs},
+    user::Role,
+};
+use serde::{Deserialize, Serialize};
+
+/// The maximum length of a flair's label.
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// The maximum length of a reference to a flair's image.
+pub const MAX_IMAGE_LENGTH: usize = 255;
+
+/// Flair represents a badge displayed alongside the usernames of the chatters
+/// holding it. Flairs are held by each user with the flair's role, and by
+/// each user granted the flair individually.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "flairs"]
+pub struct Flair {
+    /// The name identifying the flair
+    name: String,
+
+    /// The human-readable name of the flair
+    label: String,
+
+    /// A reference to the image rendered as the badge, if it has one
+    image: Option<String>,
+
+    /// The role whose holders are given the flair, if any
+    role: Option<String>,
+
+    /// The position of the flair among a chatter's flairs, lowest first
+    priority: Unsigned<u16>,
+}
+
+impl Flair {
+    /// Creates a new flair that must be granted to each user individually.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name identifying the flair
+    /// * `label` - The human-readable name of the flair
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::{flair::Flair, user::Role};
+    ///
+    /// let flair = Flair::new("moderator", "Moderator").with_role(Some(Role::Moderator));
+    /// assert!(flair.is_valid());
+    /// assert_eq!(flair.role(), Some(Role::Moderator));
+    /// ```
+    pub fn new(name: &str, label: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            label: label.to_owned(),
+            image: None,
+            role: None,
+            priority: Unsigned(0),
+        }
+    }
+
+    /// Consumes the flair, and modifies it according to the provided image.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - (optional) A reference to the image rendered as the badge
+    pub fn with_image(mut self, image: Option<String>) -> Self {
+        self.image = image;
+
+        self
+    }
+
+    /// Consumes the flair, and gives it to each holder of the provided role.
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - (optional) The role whose holders should be given the flair
+    pub fn with_role(mut self, role: Option<Role>) -> Self {
+        self.role = role.map(|role| role.to_str().to_owned());
+
+        self
+    }
+
+    /// Consumes the flair, and modifies it according to the provided
+    /// priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The position of the flair among a chatter's flairs
+    pub fn with_priority(mut self, priority: u16) -> Self {
+        self.priority = Unsigned(priority);
+
+        self
+    }
+
+    /// Retreives the name identifying the flair.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retreives the human-readable name of the flair.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Retreives the reference to the flair's image, if it has one.
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    /// Retreives the role whose holders are given the flair, if any.
+    pub fn role(&self) -> Option<Role> {
+        self.role.as_ref().and_then(|role| role.parse().ok())
+    }
+
+    /// Retreives the position of the flair among a chatter's flairs.
+    pub fn priority(&self) -> u16 {
+        self.priority.0
+    }
+
+    /// Determines whether or not the flair may be stored.
+    pub fn is_valid(&self) -> bool {
+        is_valid_name(&self.name)
+            && !self.label.is_empty()
+            && self.label.len() <= MAX_LABEL_LENGTH
+            && self
+                .image
+                .as_ref()
+                .is_none_or(|image| !image.is_empty() && image.len() <= MAX_IMAGE_LENGTH)
+    }
+}
+
+/// FlairGrant represents a flair granted to an individual user.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "flair_grants"]
+pub struct FlairGrant {
+    /// The ID of the user granted the flair
+    user_id: Unsigned<u64>,
+
+    /// The name of the flair granted to the user
+    flair: String,
+}
+
+impl FlairGrant {
+    /// Creates a new flair grant.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user granted the flair
+    /// * `flair` - The name of the flair granted to the user
+    pub fn new(user_id: u64, flair: &str) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            flair: flair.to_owned(),
+        }
+    }
+
+    /// Retreives the ID of the user granted the flair.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the name of the flair granted to the user.
+    pub fn flair(&self) -> &str {
+        &self.flair
+    }
+}
+
+/// Determines whether or not the given string is a valid flair name. Flair
+/// names must be between 2 and 32 characters long, and may only contain
+/// alphanumeric characters, underscores, and hyphens.
+///
+/// # Arguments
+///
+/// * `name` - The flair name that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::flair::is_valid_name;
+///
+/// assert!(is_valid_name("micro-sub"));
+/// assert!(!is_valid_name("micro sub"));
+/// ```
+pub fn is_valid_name(name: &str) -> bool {
+    (2..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
@@ -0,0 +1,189 @@
+use super::{backend::Unsigned, schema::chat_history};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::mem;
+
+/// ChatMessage represents a message sent to the chat, as recorded in the
+/// chat history.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ChatMessage {
+    /// A unique identifier assigned to the message, increasing with each
+    /// message sent
+    id: u64,
+
+    /// The ID of the user who sent the message
+    sender_id: u64,
+
+    /// The username held by the sender when the message was sent
+    sender: String,
+
+    /// The contents of the message
+    contents: String,
+
+    /// The time at which the message was sent
+    sent_at: NaiveDateTime,
+
+    /// The time at which the message was deleted by a moderator, if it was
+    deleted_at: Option<NaiveDateTime>,
+
+    /// The time at which the message was last edited by its sender, if it
+    /// was
+    edited_at: Option<NaiveDateTime>,
+
+    /// The contents of the message before it was first edited, if it was
+    original_contents: Option<String>,
+}
+
+impl ChatMessage {
+    /// Retreives the ID of the message.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who sent the message.
+    pub fn sender_id(&self) -> u64 {
+        self.sender_id
+    }
+
+    /// Retreives the username held by the sender when the message was sent.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Retreives the contents of the message.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Retreives the time at which the message was sent.
+    pub fn sent_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.sent_at, Utc)
+    }
+
+    /// Retreives the time at which the message was deleted, if it was.
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Determines whether or not the message was deleted by a moderator.
+    pub fn deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Retreives the time at which the message was last edited, if it was.
+    pub fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Retreives the contents of the message before it was first edited, if
+    /// it was.
+    pub fn original_contents(&self) -> Option<&str> {
+        self.original_contents.as_deref()
+    }
+
+    /// Consumes the message, and replaces its contents with the given edited
+    /// contents. The contents sent originally are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - The new contents of the message
+    /// * `at` - The time at which the message was edited
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::history::NewChatMessage;
+    ///
+    /// let msg = NewChatMessage::new(69420, "MrMouton", "Pgo", Utc::now()).with_id(1);
+    /// let edited = msg.with_edit("Pog", Utc::now()).with_edit("PogU", Utc::now());
+    /// assert_eq!(edited.contents(), "PogU");
+    /// assert_eq!(edited.original_contents(), Some("Pgo"));
+    /// ```
+    pub fn with_edit(mut self, contents: &str, at: DateTime<Utc>) -> Self {
+        let original = mem::replace(&mut self.contents, contents.to_owned());
+        self.original_contents.get_or_insert(original);
+        self.edited_at = Some(at.naive_utc().trunc_subsecs(0));
+
+        self
+    }
+
+    /// Consumes the message, and discards its original contents, so that it
+    /// may be shown to chatters who aren't moderators.
+    pub fn redacted(mut self) -> Self {
+        self.original_contents = None;
+
+        self
+    }
+}
+
+/// NewChatMessage represents a request to record a message in the chat
+/// history. Messages are assigned an ID once archived.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "chat_history"]
+pub struct NewChatMessage<'a> {
+    /// The ID of the user who sent the message
+    sender_id: Unsigned<u64>,
+
+    /// The username of the sender
+    sender: &'a str,
+
+    /// The contents of the message
+    contents: &'a str,
+
+    /// The time at which the message was sent
+    sent_at: NaiveDateTime,
+}
+
+impl<'a> NewChatMessage<'a> {
+    /// Creates a new history entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `sender` - The username of the sender
+    /// * `contents` - The contents of the message
+    /// * `sent_at` - The time at which the message was sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::history::NewChatMessage;
+    ///
+    /// let msg = NewChatMessage::new(69420, "MrMouton", "Pog", Utc::now());
+    /// let archived = msg.with_id(1);
+    /// assert_eq!(archived.id(), 1);
+    /// assert_eq!(archived.sender(), "MrMouton");
+    /// ```
+    pub fn new(sender_id: u64, sender: &'a str, contents: &'a str, sent_at: DateTime<Utc>) -> Self {
+        Self {
+            sender_id: Unsigned(sender_id),
+            sender,
+            contents,
+            // History is only tracked to the second, as MySQL timestamps are
+            sent_at: sent_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Converts the entry into a recorded message with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the message
+    pub fn with_id(&self, id: u64) -> ChatMessage {
+        ChatMessage {
+            id,
+            sender_id: self.sender_id.0,
+            sender: self.sender.to_owned(),
+            contents: self.contents.to_owned(),
+            sent_at: self.sent_at,
+            deleted_at: None,
+            edited_at: None,
+            original_contents: None,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use super::{backend::Unsigned, schema::ignores};
+use serde::{Deserialize, Serialize};
+
+/// IgnoreEntry represents a user's choice not to receive the messages of
+/// another user.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "ignores"]
+pub struct IgnoreEntry {
+    /// The ID of the user who is ignoring another user
+    user_id: Unsigned<u64>,
+
+    /// The ID of the user being ignored
+    ignored_id: Unsigned<u64>,
+}
+
+impl IgnoreEntry {
+    /// Creates a new ignore list entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user being ignored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::ignore::IgnoreEntry;
+    ///
+    /// let entry = IgnoreEntry::new(42069, 69420);
+    /// assert_eq!(entry.ignored_id(), 69420);
+    /// ```
+    pub fn new(user_id: u64, ignored_id: u64) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            ignored_id: Unsigned(ignored_id),
+        }
+    }
+
+    /// Retreives the ID of the user who is ignoring another user.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the ID of the user being ignored.
+    pub fn ignored_id(&self) -> u64 {
+        self.ignored_id.0
+    }
+}
@@ -0,0 +1,72 @@
+use super::{backend::Unsigned, schema::last_seen};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+/// LastSeen represents the most recent activity of a gnome.gg user.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "last_seen"]
+pub struct LastSeen {
+    /// The ID of the user whose activity is described
+    user_id: Unsigned<u64>,
+
+    /// The last time at which the user connected to the chat
+    connected_at: Option<NaiveDateTime>,
+
+    /// The last time at which the user sent a message
+    messaged_at: Option<NaiveDateTime>,
+}
+
+impl LastSeen {
+    /// Creates a new activity record for the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity is described
+    /// * `connected_at` - (optional) The last time at which the user connected
+    /// * `messaged_at` - (optional) The last time at which the user sent a
+    /// message
+    pub fn new(
+        user_id: u64,
+        connected_at: Option<DateTime<Utc>>,
+        messaged_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        // Activity is only tracked to the second, as MySQL timestamps are
+        let truncate = |time: DateTime<Utc>| time.naive_utc().trunc_subsecs(0);
+
+        Self {
+            user_id: Unsigned(user_id),
+            connected_at: connected_at.map(truncate),
+            messaged_at: messaged_at.map(truncate),
+        }
+    }
+
+    /// Retreives the ID of the user whose activity is described.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the last time at which the user connected to the chat.
+    pub fn connected_at(&self) -> Option<DateTime<Utc>> {
+        self.connected_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Retreives the last time at which the user sent a message.
+    pub fn messaged_at(&self) -> Option<DateTime<Utc>> {
+        self.messaged_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Combines two activity records for the same user, keeping the most
+    /// recent of each timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The record that should be merged into this one
+    pub fn merge(mut self, other: &Self) -> Self {
+        self.connected_at = self.connected_at.max(other.connected_at);
+        self.messaged_at = self.messaged_at.max(other.messaged_at);
+
+        self
+    }
+}
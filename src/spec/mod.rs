@@ -1,6 +1,28 @@
+pub mod announcement;
+pub mod approval;
+pub mod audit;
+pub mod backend;
 pub mod ban;
+pub mod bot_command;
+pub mod donation;
+pub mod embed;
+pub mod emote;
 pub mod event;
+mod fields;
+pub mod flair;
+pub mod history;
+pub mod ignore;
+pub mod last_seen;
 pub mod mute;
+pub mod pin;
+pub mod poll;
+pub mod profile;
+pub mod refresh_token;
 pub mod schema;
+pub mod settings;
+pub mod stats;
+pub mod subscription;
+pub mod two_factor;
 #[macro_use]
 pub mod user;
+pub mod whisper;
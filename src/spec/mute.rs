@@ -1,24 +1,39 @@
-use super::{schema::mutes, user::User};
+use super::{
+    backend::Unsigned,
+    fields::{self, TIMESTAMP_FORMAT},
+    schema::mutes,
+    user::User,
+};
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use redis::{FromRedisValue, RedisError, Value};
 use serde::{Deserialize, Serialize};
-use serde_json::Error as SerdeError;
 
-use std::io::{Error as IoError, ErrorKind};
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind},
+};
 
 /// Mute represents a mute entry in the SQL database.
 #[derive(
-    Identifiable, Insertable, Queryable, Associations, Serialize, Deserialize, PartialEq, Debug,
+    Identifiable,
+    Insertable,
+    Queryable,
+    Associations,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Clone,
+    Debug,
 )]
 #[belongs_to(User)]
 #[table_name = "mutes"]
 #[primary_key(user_id)]
 pub struct Mute {
     /// The ID of the user corresponding to this mute
-    user_id: u64,
+    user_id: Unsigned<u64>,
 
     /// The number of nanoseconds that this mute will be in effect for
-    duration: u64,
+    duration: Unsigned<u64>,
 
     /// The time at which this mute was issued
     initiated_at: NaiveDateTime,
@@ -27,8 +42,8 @@ pub struct Mute {
 impl Default for Mute {
     fn default() -> Self {
         Self {
-            user_id: 0,
-            duration: 0,
+            user_id: Unsigned(0),
+            duration: Unsigned(0),
             initiated_at: Utc::now().naive_utc(),
         }
     }
@@ -39,8 +54,8 @@ impl Mute {
     /// initiation timestamp.
     pub fn new(user_id: u64, duration: u64) -> Self {
         Self {
-            user_id,
-            duration,
+            user_id: Unsigned(user_id),
+            duration: Unsigned(duration),
             initiated_at: Utc::now().naive_utc(),
         }
     }
@@ -52,7 +67,7 @@ impl Mute {
     ///
     /// * `user_id` - The user ID that should be associated with this mute
     pub fn with_user_id(mut self, user_id: u64) -> Self {
-        self.user_id = user_id;
+        self.user_id = Unsigned(user_id);
 
         self
     }
@@ -65,7 +80,7 @@ impl Mute {
     /// * `duration` - The number of nanoseconds that the mute should be active
     /// for
     pub fn with_duration(mut self, duration: u64) -> Self {
-        self.duration = duration;
+        self.duration = Unsigned(duration);
 
         self
     }
@@ -84,27 +99,60 @@ impl Mute {
 
     /// Determines whether or not the mute is active.
     pub fn active(&self) -> bool {
-        Utc::now().naive_utc() < self.initiated_at + Duration::nanoseconds(self.duration as i64)
+        Utc::now().naive_utc() < self.initiated_at + Duration::nanoseconds(self.duration.0 as i64)
     }
 
     /// Retreieves the ID pertaining to the use who will be muted.
     pub fn concerns(&self) -> u64 {
-        self.user_id
+        self.user_id.0
     }
 
     /// Constructs a duration representing the timeframe that the mute will be
     /// active for.
     pub fn active_for(&self) -> Duration {
-        Duration::nanoseconds(self.duration as i64)
+        Duration::nanoseconds(self.duration.0 as i64)
+    }
+
+    /// Determines the time at which the mute lapses.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.initiated_at + self.active_for(), Utc)
+    }
+
+    /// Encodes the mute as the fields of a redis hash, such that each of its
+    /// fields may be read or updated individually.
+    pub fn to_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("user_id", self.user_id.to_string()),
+            ("duration", self.duration.to_string()),
+            (
+                "initiated_at",
+                self.initiated_at.format(TIMESTAMP_FORMAT).to_string(),
+            ),
+        ]
+    }
+
+    /// Decodes a mute from the fields of a redis hash. Redis reads missing
+    /// hashes as empty, so an empty hash is decoded as no mute at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The fields of the hash
+    pub fn from_fields(fields: &HashMap<String, String>) -> Result<Option<Self>, RedisError> {
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            user_id: fields::required(fields, "user_id")?,
+            duration: fields::required(fields, "duration")?,
+            initiated_at: fields::required(fields, "initiated_at")?,
+        }))
     }
 }
 
 impl FromRedisValue for Mute {
     fn from_redis_value(v: &Value) -> Result<Self, RedisError> {
-        match v {
-            Value::Data(d) => serde_json::from_slice(&d)
-                .map_err(|e| <SerdeError as Into<IoError>>::into(e).into()),
-            _ => Err(IoError::new(ErrorKind::Other, "unexpected response type").into()),
-        }
+        Self::from_fields(&HashMap::from_redis_value(v)?)?
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidData, "empty mute hash").into())
     }
 }
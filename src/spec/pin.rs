@@ -0,0 +1,179 @@
+use super::{backend::Unsigned, schema::pins};
+use chrono::{DateTime, Duration, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+/// PinnedMessage represents a message pinned above the chat by a moderator,
+/// shown to each chatter until it expires or is unpinned.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct PinnedMessage {
+    /// A unique identifier assigned to the pin
+    id: u64,
+
+    /// The ID of the user who pinned the message
+    pinned_by_id: u64,
+
+    /// The username held by the user who pinned the message
+    pinned_by: String,
+
+    /// The contents of the message
+    contents: String,
+
+    /// The time at which the message was pinned
+    pinned_at: NaiveDateTime,
+
+    /// The time at which the message is automatically unpinned, if it is
+    expires_at: Option<NaiveDateTime>,
+
+    /// The time at which the message was unpinned by a moderator, if it was
+    unpinned_at: Option<NaiveDateTime>,
+}
+
+impl PinnedMessage {
+    /// Retreives the ID of the pin.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who pinned the message.
+    pub fn pinned_by_id(&self) -> u64 {
+        self.pinned_by_id
+    }
+
+    /// Retreives the username held by the user who pinned the message.
+    pub fn pinned_by(&self) -> &str {
+        &self.pinned_by
+    }
+
+    /// Retreives the contents of the message.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Retreives the time at which the message was pinned.
+    pub fn pinned_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.pinned_at, Utc)
+    }
+
+    /// Retreives the time at which the message is automatically unpinned, if
+    /// it is.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Determines whether or not the message is still pinned at the given
+    /// time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The time at which the pin should be checked
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use gnomegg::spec::pin::NewPinnedMessage;
+    ///
+    /// let now = Utc::now();
+    /// let pin = NewPinnedMessage::new(69420, "MrMouton", "Debate at 5", now)
+    ///     .with_ttl(Some(Duration::minutes(5)))
+    ///     .with_id(1);
+    ///
+    /// assert!(pin.is_active(now));
+    /// assert!(!pin.is_active(now + Duration::minutes(10)));
+    /// ```
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.unpinned_at.is_none()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > now.naive_utc())
+    }
+}
+
+/// NewPinnedMessage represents a request to pin a message. Pins are assigned
+/// an ID once recorded.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "pins"]
+pub struct NewPinnedMessage<'a> {
+    /// The ID of the user pinning the message
+    pinned_by_id: Unsigned<u64>,
+
+    /// The username of the user pinning the message
+    pinned_by: &'a str,
+
+    /// The contents of the message
+    contents: &'a str,
+
+    /// The time at which the message was pinned
+    pinned_at: NaiveDateTime,
+
+    /// The time at which the message should be automatically unpinned, if it
+    /// should be
+    expires_at: Option<NaiveDateTime>,
+}
+
+impl<'a> NewPinnedMessage<'a> {
+    /// Creates a new request to pin a message indefinitely.
+    ///
+    /// # Arguments
+    ///
+    /// * `pinned_by_id` - The ID of the user pinning the message
+    /// * `pinned_by` - The username of the user pinning the message
+    /// * `contents` - The contents of the message
+    /// * `pinned_at` - The time at which the message was pinned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::pin::NewPinnedMessage;
+    ///
+    /// let pin = NewPinnedMessage::new(69420, "MrMouton", "Debate at 5", Utc::now()).with_id(1);
+    /// assert_eq!(pin.contents(), "Debate at 5");
+    /// ```
+    pub fn new(
+        pinned_by_id: u64,
+        pinned_by: &'a str,
+        contents: &'a str,
+        pinned_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            pinned_by_id: Unsigned(pinned_by_id),
+            pinned_by,
+            contents,
+            // Pins are only tracked to the second, as MySQL timestamps are
+            pinned_at: pinned_at.naive_utc().trunc_subsecs(0),
+            expires_at: None,
+        }
+    }
+
+    /// Consumes the request, and modifies it such that the message is
+    /// automatically unpinned after the given amount of time.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - (optional) The amount of time for which the message should
+    /// remain pinned
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.expires_at = ttl.map(|ttl| self.pinned_at + ttl);
+
+        self
+    }
+
+    /// Converts the request into a recorded pin with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the pin
+    pub fn with_id(&self, id: u64) -> PinnedMessage {
+        PinnedMessage {
+            id,
+            pinned_by_id: self.pinned_by_id.0,
+            pinned_by: self.pinned_by.to_owned(),
+            contents: self.contents.to_owned(),
+            pinned_at: self.pinned_at,
+            expires_at: self.expires_at,
+            unpinned_at: None,
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use super::{backend::Unsigned, schema::poll_results};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of options that a poll may have.
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+/// The maximum length of a poll's question.
+pub const MAX_QUESTION_LENGTH: usize = 255;
+
+/// The maximum length of each of a poll's options.
+pub const MAX_OPTION_LENGTH: usize = 64;
+
+/// Determines whether or not a poll with the given question and options may
+/// be started. Polls must have a question, and between two and
+/// MAX_POLL_OPTIONS non-empty options.
+///
+/// # Arguments
+///
+/// * `question` - The question asked by the poll
+/// * `options` - The answers that chatters may vote for
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::poll::is_valid_poll;
+///
+/// assert!(is_valid_poll("Is Destiny based?", &["yes", "no"]));
+/// assert!(!is_valid_poll("Is Destiny based?", &["yes"]));
+/// ```
+pub fn is_valid_poll(question: &str, options: &[&str]) -> bool {
+    !question.trim().is_empty()
+        && question.len() <= MAX_QUESTION_LENGTH
+        && options.len() >= 2
+        && options.len() <= MAX_POLL_OPTIONS
+        && options
+            .iter()
+            .all(|option| !option.trim().is_empty() && option.len() <= MAX_OPTION_LENGTH)
+}
+
+/// Determines how many votes a single ballot is worth. Ballots in weighted
+/// polls are worth one vote, plus one for each tier of the voter's
+/// subscription, while ballots in other polls are always worth one vote.
+///
+/// # Arguments
+///
+/// * `weighted` - Whether or not the poll weighs votes by subscription
+/// * `tier` - (optional) The tier of the voter's active subscription
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::poll::vote_weight;
+///
+/// assert_eq!(vote_weight(true, Some(2)), 3);
+/// assert_eq!(vote_weight(false, Some(2)), 1);
+/// ```
+pub fn vote_weight(weighted: bool, tier: Option<u8>) -> u64 {
+    if weighted {
+        1 + tier.unwrap_or(0) as u64
+    } else {
+        1
+    }
+}
+
+/// Poll represents a question put to the chat, which chatters may answer by
+/// voting for one of its options.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Poll {
+    /// A unique identifier assigned to the poll
+    id: u64,
+
+    /// The question asked by the poll
+    question: String,
+
+    /// The answers that chatters may vote for
+    options: Vec<String>,
+
+    /// Whether or not votes are weighted by the voter's subscription
+    weighted: bool,
+
+    /// The ID of the user who started the poll
+    created_by: u64,
+
+    /// The time at which the poll was started
+    started_at: NaiveDateTime,
+}
+
+impl Poll {
+    /// Retreives the ID of the poll.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the question asked by the poll.
+    pub fn question(&self) -> &str {
+        &self.question
+    }
+
+    /// Retreives the answers that chatters may vote for.
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    /// Determines whether or not votes are weighted by the voter's
+    /// subscription.
+    pub fn weighted(&self) -> bool {
+        self.weighted
+    }
+
+    /// Retreives the ID of the user who started the poll.
+    pub fn created_by(&self) -> u64 {
+        self.created_by
+    }
+
+    /// Retreives the time at which the poll was started.
+    pub fn started_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.started_at, Utc)
+    }
+
+    /// Concludes the poll with the given vote totals.
+    ///
+    /// # Arguments
+    ///
+    /// * `totals` - The number of votes cast for each option, in the order
+    /// that the options were given
+    /// * `ended_at` - The time at which the poll ended
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::poll::NewPoll;
+    ///
+    /// let poll = NewPoll::new(69420, "Is Destiny based?", vec!["yes", "no"], false, Utc::now()).with_id(1);
+    /// let result = poll.finish(vec![3, 1], Utc::now());
+    /// assert_eq!(result.winner(), Some(0));
+    /// ```
+    pub fn finish(&self, totals: Vec<u64>, ended_at: DateTime<Utc>) -> PollResult {
+        PollResult {
+            poll: self.clone(),
+            totals,
+            ended_at: ended_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+}
+
+/// NewPoll represents a request to start a poll. Polls are assigned an ID
+/// once started.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct NewPoll<'a> {
+    /// The ID of the user starting the poll
+    created_by: u64,
+
+    /// The question asked by the poll
+    question: &'a str,
+
+    /// The answers that chatters may vote for
+    #[serde(borrow)]
+    options: Vec<&'a str>,
+
+    /// Whether or not votes should be weighted by the voter's subscription
+    weighted: bool,
+
+    /// The time at which the poll was started
+    started_at: NaiveDateTime,
+}
+
+impl<'a> NewPoll<'a> {
+    /// Creates a new poll request.
+    ///
+    /// # Arguments
+    ///
+    /// * `created_by` - The ID of the user starting the poll
+    /// * `question` - The question asked by the poll
+    /// * `options` - The answers that chatters may vote for
+    /// * `weighted` - Whether or not votes should be weighted by the voter's
+    /// subscription
+    /// * `started_at` - The time at which the poll was started
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::poll::NewPoll;
+    ///
+    /// let poll = NewPoll::new(69420, "Is Destiny based?", vec!["yes", "no"], true, Utc::now()).with_id(1);
+    /// assert_eq!(poll.id(), 1);
+    /// assert_eq!(poll.options().len(), 2);
+    /// ```
+    pub fn new(
+        created_by: u64,
+        question: &'a str,
+        options: Vec<&'a str>,
+        weighted: bool,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            created_by,
+            question,
+            options,
+            weighted,
+            // Polls are only tracked to the second, as MySQL timestamps are
+            started_at: started_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Converts the request into a started poll with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the poll
+    pub fn with_id(&self, id: u64) -> Poll {
+        Poll {
+            id,
+            question: self.question.to_owned(),
+            options: self
+                .options
+                .iter()
+                .map(|option| (*option).to_owned())
+                .collect(),
+            weighted: self.weighted,
+            created_by: self.created_by,
+            started_at: self.started_at,
+        }
+    }
+}
+
+/// PollResult represents a poll that has ended, alongside the number of
+/// votes cast for each of its options.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct PollResult {
+    /// The poll that ended
+    poll: Poll,
+
+    /// The number of votes cast for each option, in the order that the
+    /// options were given
+    totals: Vec<u64>,
+
+    /// The time at which the poll ended
+    ended_at: NaiveDateTime,
+}
+
+impl PollResult {
+    /// Retreives the poll that ended.
+    pub fn poll(&self) -> &Poll {
+        &self.poll
+    }
+
+    /// Retreives the number of votes cast for each option.
+    pub fn totals(&self) -> &[u64] {
+        &self.totals
+    }
+
+    /// Retreives the time at which the poll ended.
+    pub fn ended_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.ended_at, Utc)
+    }
+
+    /// Determines the index of the option with the most votes, if any votes
+    /// were cast and no other option received as many.
+    pub fn winner(&self) -> Option<usize> {
+        let max = self.totals.iter().copied().max().filter(|max| *max > 0)?;
+
+        let mut leaders = self
+            .totals
+            .iter()
+            .enumerate()
+            .filter(|(_, total)| **total == max);
+
+        leaders
+            .next()
+            .filter(|_| leaders.next().is_none())
+            .map(|(i, _)| i)
+    }
+}
+
+/// PollResultEntry represents the result of a poll as it is stored in the
+/// SQL database.
+#[derive(Queryable, Insertable)]
+#[table_name = "poll_results"]
+pub(crate) struct PollResultEntry {
+    /// The ID of the poll
+    id: Unsigned<u64>,
+
+    /// The question asked by the poll
+    question: String,
+
+    /// The JSON-encoded options of the poll
+    options: String,
+
+    /// The JSON-encoded number of votes cast for each option
+    totals: String,
+
+    /// Whether or not votes were weighted by the voter's subscription
+    weighted: bool,
+
+    /// The ID of the user who started the poll
+    created_by: Unsigned<u64>,
+
+    /// The time at which the poll was started
+    started_at: NaiveDateTime,
+
+    /// The time at which the poll ended
+    ended_at: NaiveDateTime,
+}
+
+impl PollResultEntry {
+    /// Creates a new row for the given poll result.
+    pub(crate) fn new(result: &PollResult) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            id: Unsigned(result.poll.id),
+            question: result.poll.question.clone(),
+            options: serde_json::to_string(&result.poll.options)?,
+            totals: serde_json::to_string(&result.totals)?,
+            weighted: result.poll.weighted,
+            created_by: Unsigned(result.poll.created_by),
+            started_at: result.poll.started_at,
+            ended_at: result.ended_at,
+        })
+    }
+
+    /// Decodes the poll result stored in the row.
+    pub(crate) fn result(&self) -> Result<PollResult, serde_json::Error> {
+        Ok(PollResult {
+            poll: Poll {
+                id: self.id.0,
+                question: self.question.clone(),
+                options: serde_json::from_str(&self.options)?,
+                weighted: self.weighted,
+                created_by: self.created_by.0,
+                started_at: self.started_at,
+            },
+            totals: serde_json::from_str(&self.totals)?,
+            ended_at: self.ended_at,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use super::user::Role;
+use serde::{Deserialize, Serialize};
+
+/// SenderProfile represents the details of a chatter that are attached to
+/// each message they send, so that clients needn't look them up separately.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SenderProfile {
+    /// The ID of the chatter
+    user_id: u64,
+
+    /// The roles held by the chatter
+    roles: Vec<Role>,
+
+    /// The names of each of the flairs held by the chatter, in the order that
+    /// they are displayed
+    flairs: Vec<String>,
+
+    /// The tier of the subscription held by the chatter, if they hold one
+    /// that has not lapsed
+    tier: Option<u8>,
+}
+
+impl SenderProfile {
+    /// Creates a new profile for the chatter with the given ID, holding no
+    /// flairs or subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter
+    /// * `roles` - The roles held by the chatter
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::{profile::SenderProfile, user::Role};
+    ///
+    /// let profile = SenderProfile::new(69420, vec![Role::Subscriber])
+    ///     .with_flairs(vec!["tier1".to_owned()])
+    ///     .with_tier(Some(1));
+    /// assert_eq!(profile.tier(), Some(1));
+    /// ```
+    pub fn new(user_id: u64, roles: Vec<Role>) -> Self {
+        Self {
+            user_id,
+            roles,
+            flairs: Vec::new(),
+            tier: None,
+        }
+    }
+
+    /// Consumes the profile, and attaches the names of each of the flairs
+    /// held by the chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `flairs` - The names of the flairs held by the chatter, in the order
+    /// that they are displayed
+    pub fn with_flairs(mut self, flairs: Vec<String>) -> Self {
+        self.flairs = flairs;
+
+        self
+    }
+
+    /// Consumes the profile, and attaches the tier of the chatter's
+    /// subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `tier` - (optional) The tier of the subscription held by the chatter
+    pub fn with_tier(mut self, tier: Option<u8>) -> Self {
+        self.tier = tier;
+
+        self
+    }
+
+    /// Retreives the ID of the chatter.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Retreives the roles held by the chatter.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Retreives the names of each of the flairs held by the chatter, in the
+    /// order that they are displayed.
+    pub fn flairs(&self) -> &[String] {
+        &self.flairs
+    }
+
+    /// Retreives the tier of the subscription held by the chatter, if they
+    /// hold one that has not lapsed.
+    pub fn tier(&self) -> Option<u8> {
+        self.tier
+    }
+}
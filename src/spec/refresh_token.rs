@@ -0,0 +1,94 @@
+use super::{backend::Unsigned, schema::refresh_tokens};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+
+/// RefreshToken represents a long-lived token with which a user may obtain a
+/// new session token. Only a hash of the token itself is stored.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "refresh_tokens"]
+pub struct RefreshToken {
+    /// The BLAKE3 hash of the token
+    token_hash: Vec<u8>,
+
+    /// The ID of the user to whom the token was issued
+    user_id: Unsigned<u64>,
+
+    /// The ID of the session that the token belongs to
+    session_id: String,
+
+    /// The time at which the token was issued
+    issued_at: NaiveDateTime,
+
+    /// Whether or not the token has already been exchanged
+    used: bool,
+}
+
+impl RefreshToken {
+    /// Creates a new, unused refresh token record.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The raw token issued to the user
+    /// * `user_id` - The ID of the user to whom the token was issued
+    /// * `session_id` - The ID of the session that the token belongs to
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::refresh_token::RefreshToken;
+    ///
+    /// let token = RefreshToken::new("token", 1, "session".to_owned());
+    /// assert_eq!(token.token_hash(), blake3::hash(b"token").as_bytes());
+    /// assert!(!token.used());
+    /// ```
+    pub fn new(token: &str, user_id: u64, session_id: String) -> Self {
+        Self {
+            token_hash: Self::hash(token).to_vec(),
+            user_id: Unsigned(user_id),
+            session_id,
+            issued_at: Utc::now().naive_utc().trunc_subsecs(0),
+            used: false,
+        }
+    }
+
+    /// Computes the hash under which the given raw token is stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The raw token issued to the user
+    pub fn hash(token: &str) -> [u8; 32] {
+        *blake3::hash(token.as_bytes()).as_bytes()
+    }
+
+    /// Retreives the hash of the token.
+    pub fn token_hash(&self) -> &[u8] {
+        &self.token_hash
+    }
+
+    /// Retreives the ID of the user to whom the token was issued.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the ID of the session that the token belongs to.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Retreives the time at which the token was issued.
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.issued_at, Utc)
+    }
+
+    /// Determines whether or not the token has already been exchanged.
+    pub fn used(&self) -> bool {
+        self.used
+    }
+
+    /// Marks the token as having been exchanged.
+    pub fn mark_used(mut self) -> Self {
+        self.used = true;
+
+        self
+    }
+}
@@ -1,56 +1,298 @@
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    announcements (id) {
+        id -> UnsignedBigint,
+        message -> Text,
+        schedule -> Nullable<Varchar>,
+        created_by -> Nullable<UnsignedBigint>,
+        next_run_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::{UnsignedBigint, UnsignedSmallint};
+
+    audit_log (id) {
+        id -> UnsignedBigint,
+        actor_id -> Nullable<UnsignedBigint>,
+        impersonator_id -> Nullable<UnsignedBigint>,
+        action -> Varchar,
+        status -> Nullable<UnsignedSmallint>,
+        detail -> Nullable<Text>,
+        performed_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     bans (user_id) {
-        user_id -> Unsigned<Bigint>,
-        duration -> Nullable<Unsigned<Bigint>>,
+        user_id -> UnsignedBigint,
+        duration -> Nullable<UnsignedBigint>,
         initiated_at -> Timestamp,
         ip -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedInteger;
+
+    bot_commands (name) {
+        name -> Varchar,
+        handler -> Varchar,
+        target -> Varchar,
+        cooldown -> UnsignedInteger,
+        role -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    chat_history (id) {
+        id -> UnsignedBigint,
+        sender_id -> UnsignedBigint,
+        sender -> Varchar,
+        contents -> Text,
+        sent_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+        edited_at -> Nullable<Timestamp>,
+        original_contents -> Nullable<Text>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    chat_stats (user_id, day) {
+        user_id -> UnsignedBigint,
+        day -> Date,
+        lines -> UnsignedBigint,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     discord_connected (user_id) {
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
         id_hash -> Nullable<Binary>,
         id_value -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    donations (id) {
+        id -> UnsignedBigint,
+        donor_id -> Nullable<UnsignedBigint>,
+        donor -> Varchar,
+        amount -> UnsignedBigint,
+        currency -> Varchar,
+        message -> Nullable<Text>,
+        donated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedTinyint;
+
+    emotes (code) {
+        code -> Varchar,
+        image -> Varchar,
+        tier -> Nullable<UnsignedTinyint>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    flair_grants (user_id, flair) {
+        user_id -> UnsignedBigint,
+        flair -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedSmallint;
+
+    flairs (name) {
+        name -> Varchar,
+        label -> Varchar,
+        image -> Nullable<Varchar>,
+        role -> Nullable<Varchar>,
+        priority -> UnsignedSmallint,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::{UnsignedBigint, UnsignedTinyint};
+
+    gifted_subscriptions (id) {
+        id -> UnsignedBigint,
+        gifter_id -> Nullable<UnsignedBigint>,
+        gifter -> Varchar,
+        recipient_id -> UnsignedBigint,
+        recipient -> Varchar,
+        tier -> UnsignedTinyint,
+        months -> UnsignedTinyint,
+        amount -> UnsignedBigint,
+        currency -> Varchar,
+        message -> Nullable<Text>,
+        gifted_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     google_connected (user_id) {
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
         id_hash -> Nullable<Binary>,
         id_value -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     ids (username) {
-        id -> Unsigned<Bigint>,
+        id -> UnsignedBigint,
         username -> Varchar,
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    ignores (user_id, ignored_id) {
+        user_id -> UnsignedBigint,
+        ignored_id -> UnsignedBigint,
+        ignored_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    last_seen (user_id) {
+        user_id -> UnsignedBigint,
+        connected_at -> Nullable<Timestamp>,
+        messaged_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    link_whitelist (domain) {
+        domain -> Varchar,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     mutes (user_id) {
-        user_id -> Unsigned<Bigint>,
-        duration -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
+        duration -> UnsignedBigint,
         initiated_at -> Timestamp,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    pins (id) {
+        id -> UnsignedBigint,
+        pinned_by_id -> UnsignedBigint,
+        pinned_by -> Varchar,
+        contents -> Text,
+        pinned_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        unpinned_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    poll_results (id) {
+        id -> UnsignedBigint,
+        question -> Varchar,
+        options -> Text,
+        totals -> Text,
+        weighted -> Bool,
+        created_by -> UnsignedBigint,
+        started_at -> Timestamp,
+        ended_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    recovery_codes (code_hash) {
+        code_hash -> Binary,
+        user_id -> UnsignedBigint,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     reddit_connected (user_id) {
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
         id_hash -> Nullable<Binary>,
         id_value -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    refresh_tokens (token_hash) {
+        token_hash -> Binary,
+        user_id -> UnsignedBigint,
+        session_id -> Varchar,
+        issued_at -> Timestamp,
+        used -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     roles (user_id) {
-        id -> Unsigned<Bigint>,
-        user_id -> Unsigned<Bigint>,
+        id -> UnsignedBigint,
+        user_id -> UnsignedBigint,
         administrator -> Nullable<Bool>,
         moderator -> Nullable<Bool>,
         vip -> Nullable<Bool>,
@@ -61,41 +303,147 @@ table! {
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    settings (user_id) {
+        user_id -> UnsignedBigint,
+        preferences -> Text,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::{UnsignedBigint, UnsignedTinyint};
+
+    subscriptions (user_id) {
+        user_id -> UnsignedBigint,
+        tier -> UnsignedTinyint,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     twitch_connected (user_id) {
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
         id_hash -> Nullable<Binary>,
         id_value -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     twitter_connected (user_id) {
-        user_id -> Unsigned<Bigint>,
+        user_id -> UnsignedBigint,
         id_hash -> Nullable<Binary>,
         id_value -> Nullable<Text>,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    two_factor (user_id) {
+        user_id -> UnsignedBigint,
+        secret -> Text,
+        confirmed -> Bool,
+        last_step -> Nullable<Bigint>,
+        enrolled_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    username_history (id) {
+        id -> UnsignedBigint,
+        user_id -> UnsignedBigint,
+        username -> Varchar,
+        changed_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
     users (id) {
-        id -> Unsigned<Bigint>,
+        id -> UnsignedBigint,
         username -> Nullable<Varchar>,
         verified -> Bool,
         nationality -> Nullable<Text>,
         accepts_gifts -> Nullable<Bool>,
         minecraft_name -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        avatar -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use crate::spec::backend::UnsignedBigint;
+
+    whisper_reads (user_id, peer_id) {
+        user_id -> UnsignedBigint,
+        peer_id -> UnsignedBigint,
+        last_read_id -> UnsignedBigint,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::spec::backend::UnsignedBigint;
+
+    whispers (id) {
+        id -> UnsignedBigint,
+        sender_id -> UnsignedBigint,
+        sender -> Varchar,
+        recipient_id -> UnsignedBigint,
+        contents -> Text,
+        sent_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
+    announcements,
+    audit_log,
     bans,
+    bot_commands,
+    chat_history,
+    chat_stats,
     discord_connected,
+    donations,
+    emotes,
+    flair_grants,
+    flairs,
+    gifted_subscriptions,
     google_connected,
     ids,
+    ignores,
+    last_seen,
+    link_whitelist,
     mutes,
+    pins,
+    poll_results,
+    recovery_codes,
     reddit_connected,
+    refresh_tokens,
     roles,
+    settings,
+    subscriptions,
     twitch_connected,
     twitter_connected,
+    two_factor,
+    username_history,
     users,
+    whisper_reads,
+    whispers,
 );
@@ -0,0 +1,140 @@
+use super::{backend::Unsigned, schema::settings};
+use serde::{Deserialize, Serialize};
+
+use std::default::Default;
+
+/// The maximum number of flairs that a user may hide.
+pub const MAX_HIDDEN_FLAIRS: usize = 64;
+
+/// Settings represents the chat preferences of a gnome.gg user.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Whether or not the user should be notified when they are mentioned
+    notify_on_mention: bool,
+
+    /// Whether or not the user should be notified when they are whispered
+    notify_on_whisper: bool,
+
+    /// The names of the flairs that should not be displayed to the user
+    hidden_flairs: Vec<String>,
+
+    /// The IANA name of the timezone in which timestamps should be displayed
+    /// to the user (e.g. America/Chicago)
+    timezone: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            notify_on_mention: true,
+            notify_on_whisper: true,
+            hidden_flairs: Vec::new(),
+            timezone: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Determines whether or not the user should be notified when they are
+    /// mentioned.
+    pub fn notify_on_mention(&self) -> bool {
+        self.notify_on_mention
+    }
+
+    /// Determines whether or not the user should be notified when they are
+    /// whispered.
+    pub fn notify_on_whisper(&self) -> bool {
+        self.notify_on_whisper
+    }
+
+    /// Retreives the names of the flairs hidden by the user.
+    pub fn hidden_flairs(&self) -> &[String] {
+        &self.hidden_flairs
+    }
+
+    /// Retreives the timezone in which timestamps should be displayed to the
+    /// user, if they have chosen one.
+    pub fn timezone(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
+    /// Consumes the settings, and modifies them according to the provided
+    /// mention notification preference.
+    pub fn with_notify_on_mention(mut self, notify_on_mention: bool) -> Self {
+        self.notify_on_mention = notify_on_mention;
+
+        self
+    }
+
+    /// Consumes the settings, and modifies them according to the provided
+    /// whisper notification preference.
+    pub fn with_notify_on_whisper(mut self, notify_on_whisper: bool) -> Self {
+        self.notify_on_whisper = notify_on_whisper;
+
+        self
+    }
+
+    /// Consumes the settings, and modifies them according to the provided
+    /// hidden flairs.
+    pub fn with_hidden_flairs(mut self, hidden_flairs: Vec<String>) -> Self {
+        self.hidden_flairs = hidden_flairs;
+
+        self
+    }
+
+    /// Consumes the settings, and modifies them according to the provided
+    /// timezone.
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+
+        self
+    }
+
+    /// Determines whether or not the settings are fit to be stored.
+    pub fn is_valid(&self) -> bool {
+        self.hidden_flairs.len() <= MAX_HIDDEN_FLAIRS
+            && self.timezone.as_deref().is_none_or(is_valid_timezone)
+    }
+}
+
+/// Determines whether or not the given string is shaped like an IANA timezone
+/// name.
+///
+/// # Arguments
+///
+/// * `timezone` - The timezone name that should be checked
+pub fn is_valid_timezone(timezone: &str) -> bool {
+    !timezone.is_empty()
+        && timezone.len() <= 64
+        && timezone
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+}
+
+/// SettingsEntry represents a user's settings as they are stored in the SQL
+/// database.
+#[derive(Queryable, Insertable)]
+#[table_name = "settings"]
+pub(crate) struct SettingsEntry {
+    /// The ID of the user to whom the settings belong
+    user_id: Unsigned<u64>,
+
+    /// The JSON-encoded settings
+    preferences: String,
+}
+
+impl SettingsEntry {
+    /// Creates a new settings row for the user with the given ID.
+    pub(crate) fn new(user_id: u64, settings: &Settings) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            user_id: Unsigned(user_id),
+            preferences: serde_json::to_string(settings)?,
+        })
+    }
+
+    /// Decodes the settings stored in the row.
+    pub(crate) fn settings(&self) -> Result<Settings, serde_json::Error> {
+        serde_json::from_str(&self.preferences)
+    }
+}
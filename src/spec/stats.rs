@@ -0,0 +1,205 @@
+use super::{backend::Unsigned, schema::chat_stats};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The longest period over which chatters may be ranked, in hours.
+pub const MAX_LEADERBOARD_HOURS: u64 = 7 * 24;
+
+/// Parses the period over which chatters should be ranked, given as a number
+/// of hours or days (e.g. "24h" or "7d"), into a number of hours. Periods
+/// longer than MAX_LEADERBOARD_HOURS are rejected.
+///
+/// # Arguments
+///
+/// * `period` - The period that should be parsed
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::stats::parse_leaderboard_period;
+///
+/// assert_eq!(parse_leaderboard_period("24h"), Some(24));
+/// assert_eq!(parse_leaderboard_period("7d"), Some(168));
+/// assert_eq!(parse_leaderboard_period("1y"), None);
+/// ```
+pub fn parse_leaderboard_period(period: &str) -> Option<u64> {
+    let (count, hours) = if let Some(count) = period.strip_suffix('h') {
+        (count, 1)
+    } else {
+        (period.strip_suffix('d')?, 24)
+    };
+
+    count
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(hours)
+        .filter(|hours| *hours > 0 && *hours <= MAX_LEADERBOARD_HOURS)
+}
+
+/// Window represents a period of time over which messages are counted.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Window {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Window {
+    /// Each of the windows over which messages are counted.
+    pub const ALL: [Self; 3] = [Self::Minute, Self::Hour, Self::Day];
+
+    /// Gets the length of the window in seconds.
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// Gets the number of seconds for which the message count of a single
+    /// window is kept, which is long enough to compare it against the
+    /// windows preceding it.
+    pub fn retention(&self) -> i64 {
+        match self {
+            Self::Minute => 2 * 60 * 60,
+            Self::Hour => 2 * 24 * 60 * 60,
+            Self::Day => 31 * 24 * 60 * 60,
+        }
+    }
+
+    /// Gets the index of the window containing the given time, counted from
+    /// the unix epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - The time whose window should be found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use gnomegg::spec::stats::Window;
+    ///
+    /// let at = Utc.ymd(2020, 5, 18).and_hms(12, 30, 0);
+    /// assert_eq!(Window::Hour.bucket(at), Window::Hour.bucket(Utc.ymd(2020, 5, 18).and_hms(12, 59, 59)));
+    /// assert_ne!(Window::Minute.bucket(at), Window::Minute.bucket(Utc.ymd(2020, 5, 18).and_hms(12, 31, 0)));
+    /// ```
+    pub fn bucket(&self, at: DateTime<Utc>) -> i64 {
+        at.timestamp().div_euclid(self.seconds())
+    }
+
+    /// Gets the name of the window, as it appears in redis keys and routes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+}
+
+/// DailyStats represents the number of public messages that a user sent on
+/// a given day, as it is stored in the SQL database.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "chat_stats"]
+pub struct DailyStats {
+    /// The ID of the user who sent the messages
+    user_id: Unsigned<u64>,
+
+    /// The day, in UTC, on which the messages were sent
+    day: NaiveDate,
+
+    /// The number of public messages sent by the user that day
+    lines: Unsigned<u64>,
+}
+
+impl DailyStats {
+    /// Creates a new daily message count.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the messages
+    /// * `day` - The day on which the messages were sent
+    /// * `lines` - The number of public messages sent by the user that day
+    pub fn new(user_id: u64, day: NaiveDate, lines: u64) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            day,
+            lines: Unsigned(lines),
+        }
+    }
+
+    /// Retreives the ID of the user who sent the messages.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the day on which the messages were sent.
+    pub fn day(&self) -> NaiveDate {
+        self.day
+    }
+
+    /// Retreives the number of public messages sent by the user that day.
+    pub fn lines(&self) -> u64 {
+        self.lines.0
+    }
+}
+
+/// TopChatter represents a chatter's position on the leaderboard of users
+/// who sent the most public messages over a period.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TopChatter {
+    /// The chatter's position on the leaderboard, starting at one
+    rank: usize,
+
+    /// The ID of the chatter
+    user_id: u64,
+
+    /// The username currently held by the chatter, if they still have one
+    username: Option<String>,
+
+    /// The number of public messages sent by the chatter over the period
+    lines: u64,
+}
+
+impl TopChatter {
+    /// Creates a new leaderboard entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `rank` - The chatter's position on the leaderboard
+    /// * `user_id` - The ID of the chatter
+    /// * `username` - (optional) The username currently held by the chatter
+    /// * `lines` - The number of public messages sent by the chatter
+    pub fn new(rank: usize, user_id: u64, username: Option<String>, lines: u64) -> Self {
+        Self {
+            rank,
+            user_id,
+            username,
+            lines,
+        }
+    }
+
+    /// Retreives the chatter's position on the leaderboard.
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Retreives the ID of the chatter.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Retreives the username currently held by the chatter, if any.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Retreives the number of public messages sent by the chatter over the
+    /// period.
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+}
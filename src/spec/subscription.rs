@@ -0,0 +1,89 @@
+use super::{backend::Unsigned, schema::subscriptions};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The highest subscription tier that a user may hold.
+pub const MAX_TIER: u8 = 4;
+
+/// Subscription represents the subscription held by a user, granting them
+/// access to the emotes of its tier and below.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "subscriptions"]
+pub struct Subscription {
+    /// The ID of the user holding the subscription
+    user_id: Unsigned<u64>,
+
+    /// The tier of the subscription, starting at 1
+    tier: Unsigned<u8>,
+
+    /// The time at which the subscription lapses, if it does
+    expires_at: Option<NaiveDateTime>,
+}
+
+impl Subscription {
+    /// Creates a new subscription that never lapses.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user holding the subscription
+    /// * `tier` - The tier of the subscription
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use gnomegg::spec::subscription::Subscription;
+    ///
+    /// let sub = Subscription::new(69420, 2).with_expires_at(Some(Utc::now() + Duration::days(30)));
+    /// assert!(sub.is_valid());
+    /// assert_eq!(sub.active_tier(), Some(2));
+    /// ```
+    pub fn new(user_id: u64, tier: u8) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            tier: Unsigned(tier),
+            expires_at: None,
+        }
+    }
+
+    /// Consumes the subscription, and modifies it according to the provided
+    /// expiry time.
+    ///
+    /// # Arguments
+    ///
+    /// * `expires_at` - (optional) The time at which the subscription lapses
+    pub fn with_expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at.map(|time| time.naive_utc());
+
+        self
+    }
+
+    /// Retreives the ID of the user holding the subscription.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the tier of the subscription.
+    pub fn tier(&self) -> u8 {
+        self.tier.0
+    }
+
+    /// Retreives the time at which the subscription lapses, if it does.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+
+    /// Retreives the tier of the subscription if it has not yet lapsed.
+    pub fn active_tier(&self) -> Option<u8> {
+        match self.expires_at() {
+            Some(expires_at) if expires_at <= Utc::now() => None,
+            _ => Some(self.tier.0),
+        }
+    }
+
+    /// Determines whether or not the subscription may be stored.
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_TIER).contains(&self.tier.0)
+    }
+}
@@ -0,0 +1,101 @@
+use super::{backend::Unsigned, schema::two_factor};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+
+/// TwoFactor represents a user's enrollment in TOTP two-factor
+/// authentication.
+#[derive(Queryable, Insertable, PartialEq, Clone, Debug)]
+#[table_name = "two_factor"]
+pub struct TwoFactor {
+    /// The ID of the user to whom the secret belongs
+    user_id: Unsigned<u64>,
+
+    /// The base32-encoded secret, encrypted if a keyring is configured
+    secret: String,
+
+    /// Whether or not the user has proven that their authenticator app holds
+    /// the secret
+    confirmed: bool,
+
+    /// The time step of the most recently accepted code
+    last_step: Option<i64>,
+
+    /// The time at which the user began enrolling
+    enrolled_at: NaiveDateTime,
+}
+
+impl TwoFactor {
+    /// Creates a new, unconfirmed enrollment.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the secret belongs
+    /// * `secret` - The base32-encoded secret
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::two_factor::TwoFactor;
+    ///
+    /// let enrollment = TwoFactor::new(1, "GEZDGNBVGY3TQOJQ".to_owned());
+    /// assert!(!enrollment.confirmed());
+    /// assert!(enrollment.confirm(2).confirmed());
+    /// ```
+    pub fn new(user_id: u64, secret: String) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            secret,
+            confirmed: false,
+            last_step: None,
+            enrolled_at: Utc::now().naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Retreives the ID of the user to whom the secret belongs.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the stored secret.
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// Determines whether or not the user has confirmed their enrollment.
+    pub fn confirmed(&self) -> bool {
+        self.confirmed
+    }
+
+    /// Retreives the time step of the most recently accepted code, if any.
+    pub fn last_step(&self) -> Option<i64> {
+        self.last_step
+    }
+
+    /// Retreives the time at which the user began enrolling.
+    pub fn enrolled_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.enrolled_at, Utc)
+    }
+
+    /// Consumes the enrollment, replacing its stored secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The secret that should be stored
+    pub fn with_secret(mut self, secret: String) -> Self {
+        self.secret = secret;
+
+        self
+    }
+
+    /// Marks the enrollment as confirmed, and records that the code for the
+    /// given time step has been used.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The time step of the accepted code
+    pub fn confirm(mut self, step: i64) -> Self {
+        self.confirmed = true;
+        self.last_step = Some(step);
+
+        self
+    }
+}
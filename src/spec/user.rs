@@ -1,8 +1,11 @@
-use super::schema::{ids, roles, users};
+use super::{
+    backend::PersistentBackend,
+    backend::Unsigned,
+    schema::{ids, roles, username_history, users},
+};
+use chrono::{DateTime, Duration, NaiveDateTime, SubsecRound, Utc};
 use diesel::{
     expression::BoxableExpression,
-    mysql::Mysql,
-    query_builder::SqlQuery,
     sql_types::{Bool, Nullable},
     Insertable,
 };
@@ -11,26 +14,189 @@ use serde::{Deserialize, Serialize};
 use std::{convert::Into, default::Default, error::Error, fmt, str::FromStr};
 
 /// User represents a generic gnome.gg user.
-#[derive(Identifiable, Queryable, Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
 #[table_name = "users"]
 pub struct User {
     /// The user's unique identifier
-    id: u64,
+    id: Unsigned<u64>,
 
     /// The username of the user
-    username: String,
+    username: Option<String>,
 
     /// Whether or not the user has a verified email
     verified: bool,
 
     /// The country that the user most identifies with
-    nationality: String,
+    nationality: Option<String>,
 
     /// Whether or not the user accepts gifts
-    accepts_gifts: bool,
+    accepts_gifts: Option<bool>,
 
     /// The user's minecraft username
-    minecraft_name: String,
+    minecraft_name: Option<String>,
+
+    /// The time at which the user signed up
+    created_at: NaiveDateTime,
+
+    /// The time at which the user's profile was last modified
+    updated_at: NaiveDateTime,
+
+    /// The key of the image that the user has chosen as their avatar
+    avatar: Option<String>,
+}
+
+/// The age below which an account is considered new, in hours.
+pub const NEW_ACCOUNT_HOURS: i64 = 24;
+
+impl User {
+    /// Retreives the user's unique identifier.
+    pub fn id(&self) -> u64 {
+        self.id.0
+    }
+
+    /// Retreives the username of the user, if they have one.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Determines whether or not the user has a verified email.
+    pub fn verified(&self) -> bool {
+        self.verified
+    }
+
+    /// Retreives the country that the user most identifies with.
+    pub fn nationality(&self) -> Option<&str> {
+        self.nationality.as_deref()
+    }
+
+    /// Determines whether or not the user accepts gifts.
+    pub fn accepts_gifts(&self) -> bool {
+        self.accepts_gifts.unwrap_or(false)
+    }
+
+    /// Retreives the user's minecraft username.
+    pub fn minecraft_name(&self) -> Option<&str> {
+        self.minecraft_name.as_deref()
+    }
+
+    /// Retreives the key of the user's avatar, if they have uploaded one.
+    pub fn avatar(&self) -> Option<&str> {
+        self.avatar.as_deref()
+    }
+
+    /// Retreives the time at which the user signed up.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.created_at, Utc)
+    }
+
+    /// Retreives the time at which the user's profile was last modified.
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.updated_at, Utc)
+    }
+
+    /// Determines whether or not the user signed up within the given amount
+    /// of time.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The age below which an account is considered new
+    pub fn is_new_account(&self, threshold: Duration) -> bool {
+        Utc::now().signed_duration_since(self.created_at()) < threshold
+    }
+
+    /// Consumes the user, and marks it as having been modified at the current
+    /// time.
+    pub(crate) fn touch(mut self) -> Self {
+        // MySQL timestamps only have a precision of one second
+        self.updated_at = Utc::now().naive_utc().trunc_subsecs(0);
+
+        self
+    }
+
+    /// Consumes the user, and modifies it according to the provided
+    /// nationality.
+    ///
+    /// # Arguments
+    ///
+    /// * `nationality` - The country that the user most identifies with
+    pub fn with_nationality(mut self, nationality: Option<String>) -> Self {
+        self.nationality = nationality;
+
+        self
+    }
+
+    /// Consumes the user, and modifies it according to the provided "accepts
+    /// gifts" status.
+    ///
+    /// # Arguments
+    ///
+    /// * `accepts_gifts` - Whether or not the user accepts gifts
+    pub fn with_accepts_gifts(mut self, accepts_gifts: bool) -> Self {
+        self.accepts_gifts = Some(accepts_gifts);
+
+        self
+    }
+
+    /// Consumes the user, and modifies it according to the provided avatar.
+    ///
+    /// # Arguments
+    ///
+    /// * `avatar` - The key of the image that the user has chosen as their
+    /// avatar
+    pub fn with_avatar(mut self, avatar: Option<String>) -> Self {
+        self.avatar = avatar;
+
+        self
+    }
+
+    /// Consumes the user, and modifies it according to the provided minecraft
+    /// username.
+    ///
+    /// # Arguments
+    ///
+    /// * `minecraft_name` - The user's minecraft username
+    pub fn with_minecraft_name(mut self, minecraft_name: Option<String>) -> Self {
+        self.minecraft_name = minecraft_name;
+
+        self
+    }
+}
+
+/// Determines whether or not the given string may be used as a gnomegg
+/// username. Usernames must be between 3 and 20 characters long, and may only
+/// contain alphanumeric characters and underscores.
+///
+/// # Arguments
+///
+/// * `username` - The username that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::spec::user::is_valid_username;
+///
+/// assert!(is_valid_username("MrMouton"));
+/// assert!(!is_valid_username("Mr Mouton"));
+/// ```
+pub fn is_valid_username(username: &str) -> bool {
+    (3..=20).contains(&username.len())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Determines whether or not the given string is a valid minecraft username.
+/// Minecraft usernames must be between 3 and 16 characters long, and may only
+/// contain alphanumeric characters and underscores.
+///
+/// # Arguments
+///
+/// * `minecraft_name` - The minecraft username that should be validated
+pub fn is_valid_minecraft_name(minecraft_name: &str) -> bool {
+    (3..=16).contains(&minecraft_name.len())
+        && minecraft_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 /// NewUser represents a request to create a new user.
@@ -44,13 +210,13 @@ pub struct NewUser<'a> {
     verified: bool,
 
     /// The country that the user most identifies with
-    nationality: &'a str,
+    nationality: Option<&'a str>,
 
     /// Whether or not the user accepts gifts
     accepts_gifts: bool,
 
     /// The user's minecraft username
-    minecraft_name: &'a str,
+    minecraft_name: Option<&'a str>,
 }
 
 impl<'a> NewUser<'a> {
@@ -77,12 +243,17 @@ impl<'a> NewUser<'a> {
         Self {
             username,
             verified,
-            nationality,
+            nationality: Some(nationality),
             accepts_gifts,
-            minecraft_name,
+            minecraft_name: Some(minecraft_name),
         }
     }
 
+    /// Retreives the username that will be assigned to the new user.
+    pub fn username(&self) -> &str {
+        self.username
+    }
+
     /// Consumes an existing instance of the NewUser, and modifies it according to
     /// the provided username.
     ///
@@ -115,7 +286,7 @@ impl<'a> NewUser<'a> {
     ///
     /// * `nationality` - The country that the user most identifies with
     pub fn with_nationality(mut self, nationality: &'a str) -> Self {
-        self.nationality = nationality;
+        self.nationality = Some(nationality);
 
         self
     }
@@ -139,7 +310,7 @@ impl<'a> NewUser<'a> {
     ///
     /// * `accepts_gifts` - Whther or not the user accepts gifts
     pub fn with_minecraft_name(mut self, minecraft_name: &'a str) -> Self {
-        self.minecraft_name = minecraft_name;
+        self.minecraft_name = Some(minecraft_name);
 
         self
     }
@@ -168,7 +339,7 @@ pub(crate) struct NewIdMapping<'a> {
     username: &'a str,
 
     /// The user ID of the user
-    user_id: u64,
+    user_id: Unsigned<u64>,
 }
 
 impl<'a> NewIdMapping<'a> {
@@ -179,7 +350,42 @@ impl<'a> NewIdMapping<'a> {
     /// * `username` - The username that should be mapped to the ID
     /// * `user_id` - The ID to which the username should be mapped
     pub fn new(username: &'a str, user_id: u64) -> Self {
-        Self { username, user_id }
+        Self {
+            username,
+            user_id: Unsigned(user_id),
+        }
+    }
+}
+
+/// NewUsernameChange represents a request to record a username previously
+/// held by a user.
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "username_history"]
+pub(crate) struct NewUsernameChange<'a> {
+    /// The ID of the user who changed their name
+    user_id: Unsigned<u64>,
+
+    /// The username held by the user prior to the change
+    username: &'a str,
+
+    /// The time at which the username was changed
+    changed_at: NaiveDateTime,
+}
+
+impl<'a> NewUsernameChange<'a> {
+    /// Creates a new username history entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who changed their name
+    /// * `username` - The username held by the user prior to the change
+    /// * `changed_at` - The time at which the username was changed
+    pub fn new(user_id: u64, username: &'a str, changed_at: DateTime<Utc>) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            username,
+            changed_at: changed_at.naive_utc(),
+        }
     }
 }
 
@@ -194,9 +400,9 @@ pub trait OauthConnection {
     fn id_hash(&self) -> &[u8];
 }
 
-/// RedditConnection represents an oauth connection to Reddit for a gnomegg
-/// user.
-pub struct RedditConnection<'a> {
+/// ConnectionId represents the identifier assigned to a gnomegg user by an
+/// oauth provider.
+pub struct ConnectionId<'a> {
     /// The ID assigned to the user
     value: &'a str,
 
@@ -204,30 +410,39 @@ pub struct RedditConnection<'a> {
     hash: blake3::Hash,
 }
 
-impl<'a> RedditConnection<'a> {
-    /// Creates a new instance of the reddit connection primitive.
+impl<'a> ConnectionId<'a> {
+    /// Creates a new instance of the connection ID primitive. Identifiers
+    /// that are encrypted at rest must be hashed with a secret key, as
+    /// provider account IDs are easily guessed, and could otherwise be
+    /// recovered by hashing candidate IDs.
     ///
     /// # Arguments
     ///
-    /// * `reddit_id` - The unique identified assigned by reddit to this user
-    pub fn new(reddit_id: &'a str) -> Self {
+    /// * `external_id` - The unique identifier assigned by the oauth provider
+    /// to this user
+    /// * `key` - The secret key with which the identifier should be hashed,
+    /// if it is encrypted at rest
+    pub fn new(external_id: &'a str, key: Option<&[u8; 32]>) -> Self {
         Self {
-            value: reddit_id,
-            hash: blake3::hash(reddit_id.as_bytes()),
+            value: external_id,
+            hash: match key {
+                Some(key) => blake3::keyed_hash(key, external_id.as_bytes()),
+                None => blake3::hash(external_id.as_bytes()),
+            },
         }
     }
 }
 
-impl<'a> OauthConnection for RedditConnection<'a> {
+impl<'a> OauthConnection for ConnectionId<'a> {
     /// Retreives the identifier assigned to the gnomegg user by the oauth
     /// provider.
     ///
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::user::{RedditConnection, OauthConnection};
+    /// use gnomegg::spec::user::{ConnectionId, OauthConnection};
     ///
-    /// let reddit_conn = RedditConnection::new("123456");
+    /// let reddit_conn = ConnectionId::new("123456", Some(&[7; 32]));
     /// assert_eq!(reddit_conn.id(), "123456")
     /// ```
     fn id(&self) -> &str {
@@ -239,9 +454,10 @@ impl<'a> OauthConnection for RedditConnection<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::spec::user::{RedditConnection, OauthConnection};
+    /// use gnomegg::spec::user::{ConnectionId, OauthConnection};
     ///
-    /// let reddit_conn = RedditConnection::new("123456");
+    /// let reddit_conn = ConnectionId::new("123456", Some(&[7; 32]));
+    /// assert_eq!(reddit_conn.id_hash().len(), 32)
     /// ```
     fn id_hash(&self) -> &[u8] {
         self.hash.as_bytes()
@@ -249,7 +465,8 @@ impl<'a> OauthConnection for RedditConnection<'a> {
 }
 
 /// Role represents an exclusive, individual role.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
     Administrator,
     Moderator,
@@ -277,26 +494,32 @@ impl Role {
         }
     }
 
-    /// Constructs a raw SQL query for the Role with the given role status.
+    /// Gets the standing of the role relative to the other roles, where more
+    /// privileged roles rank higher. Bots rank lowest, as they are trusted
+    /// with nothing beyond chatting.
     ///
-    /// # Arguments
+    /// # Example
     ///
-    /// * `has_role` - Whether or not the user has the rol
-    pub(crate) fn construct_give_role_statement(&self, user_id: u64, has_role: bool) -> SqlQuery {
-        diesel::sql_query(format!(
-            "IF EXISTS (SELECT * FROM roles WHERE user_id = {}) UPDATE roles SET {} = {} WHERE user_id = {} ELSE INSERT INTO roles(user_id, {}) VALUES({}, {}) END",
-            user_id,
-            self.to_str(),
-            has_role,
-            user_id,
-            self.to_str(),
-            user_id,
-            has_role
-        ))
+    /// ```
+    /// use gnomegg::spec::user::Role;
+    ///
+    /// assert!(Role::Moderator.rank() > Role::Subscriber.rank());
+    /// ```
+    pub fn rank(&self) -> u8 {
+        match self {
+            Self::Administrator => 5,
+            Self::Moderator => 4,
+            Self::VIP => 3,
+            Self::Protected => 2,
+            Self::Subscriber => 1,
+            Self::Bot => 0,
+        }
     }
 }
 
-impl From<&Role> for Box<dyn BoxableExpression<roles::table, Mysql, SqlType = Nullable<Bool>>> {
+impl From<&Role>
+    for Box<dyn BoxableExpression<roles::table, PersistentBackend, SqlType = Nullable<Bool>>>
+{
     fn from(r: &Role) -> Self {
         match r {
             Role::Administrator => Box::new(roles::dsl::administrator),
@@ -448,7 +671,7 @@ impl From<&RoleEntry> for Vec<Role> {
 #[table_name = "roles"]
 pub struct NewRoleEntry {
     /// The ID of the user associated with the role
-    user_id: u64,
+    user_id: Unsigned<u64>,
 
     /// Whether or not this user is an administrator
     administrator: Option<bool>,
@@ -479,7 +702,7 @@ impl NewRoleEntry {
     /// * `roles` - The roles that should be assigned to the user
     pub fn new(user_id: u64, roles: &[Role]) -> Self {
         Self {
-            user_id,
+            user_id: Unsigned(user_id),
             administrator: Some(roles.contains(&Role::Administrator)),
             moderator: Some(roles.contains(&Role::Moderator)),
             vip: Some(roles.contains(&Role::VIP)),
@@ -505,3 +728,63 @@ impl NewRoleEntry {
         }
     }
 }
+
+/// RoleChanges represents a change to some of the roles held by a user. Roles
+/// left unset are not changed.
+#[derive(AsChangeset, PartialEq, Debug, Default)]
+#[table_name = "roles"]
+pub struct RoleChanges {
+    /// Whether or not this user is an administrator
+    administrator: Option<bool>,
+
+    /// Whether or not this user is a moderator
+    moderator: Option<bool>,
+
+    /// Whether or not this user is a VIP
+    vip: Option<bool>,
+
+    /// Whether or not this user is protected
+    protected: Option<bool>,
+
+    /// Whether or not this user is a subscriber
+    subscriber: Option<bool>,
+
+    /// Whether or not this user is a bot
+    bot: Option<bool>,
+}
+
+impl RoleChanges {
+    /// Creates a new change, giving or taking each of the given roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles that should be given or taken
+    /// * `has_role` - Whether or not the user should hold the roles
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::user::{Role, RoleChanges};
+    ///
+    /// let changes = RoleChanges::new(&[Role::Moderator], true);
+    /// assert!(!changes.is_empty());
+    /// assert!(RoleChanges::new(&[], true).is_empty());
+    /// ```
+    pub fn new(roles: &[Role], has_role: bool) -> Self {
+        let change = |role| Some(has_role).filter(|_| roles.contains(&role));
+
+        Self {
+            administrator: change(Role::Administrator),
+            moderator: change(Role::Moderator),
+            vip: change(Role::VIP),
+            protected: change(Role::Protected),
+            subscriber: change(Role::Subscriber),
+            bot: change(Role::Bot),
+        }
+    }
+
+    /// Determines whether or not the change leaves every role as it is.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    backend::Unsigned,
+    backend::UnsignedBigint,
+    schema::{whisper_reads, whispers},
+};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use diesel::sql_types::Bigint;
+use serde::{Deserialize, Serialize};
+
+/// Whisper represents a private message sent from one user to another.
+#[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Whisper {
+    /// A unique identifier assigned to the whisper, increasing with each
+    /// whisper sent
+    id: u64,
+
+    /// The ID of the user who sent the whisper
+    sender_id: u64,
+
+    /// The username held by the sender when the whisper was sent
+    sender: String,
+
+    /// The ID of the user to whom the whisper was sent
+    recipient_id: u64,
+
+    /// The contents of the whisper
+    contents: String,
+
+    /// The time at which the whisper was sent
+    sent_at: NaiveDateTime,
+
+    /// The time at which the whisper was delivered to the recipient, if it
+    /// has been
+    delivered_at: Option<NaiveDateTime>,
+}
+
+impl Whisper {
+    /// Retreives the ID of the whisper.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the ID of the user who sent the whisper.
+    pub fn sender_id(&self) -> u64 {
+        self.sender_id
+    }
+
+    /// Retreives the username held by the sender when the whisper was sent.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Retreives the ID of the user to whom the whisper was sent.
+    pub fn recipient_id(&self) -> u64 {
+        self.recipient_id
+    }
+
+    /// Retreives the contents of the whisper.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Retreives the time at which the whisper was sent.
+    pub fn sent_at(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.sent_at, Utc)
+    }
+
+    /// Retreives the time at which the whisper was delivered, if it has been.
+    pub fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        self.delivered_at
+            .map(|time| DateTime::from_naive_utc_and_offset(time, Utc))
+    }
+}
+
+/// NewWhisper represents a request to record a whisper. Whispers are
+/// assigned an ID once archived.
+#[derive(Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "whispers"]
+pub struct NewWhisper<'a> {
+    /// The ID of the user who sent the whisper
+    sender_id: Unsigned<u64>,
+
+    /// The username of the sender
+    sender: &'a str,
+
+    /// The ID of the user to whom the whisper was sent
+    recipient_id: Unsigned<u64>,
+
+    /// The contents of the whisper
+    contents: &'a str,
+
+    /// The time at which the whisper was sent
+    sent_at: NaiveDateTime,
+}
+
+impl<'a> NewWhisper<'a> {
+    /// Creates a new whisper.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the user who sent the whisper
+    /// * `sender` - The username of the sender
+    /// * `recipient_id` - The ID of the user to whom the whisper was sent
+    /// * `contents` - The contents of the whisper
+    /// * `sent_at` - The time at which the whisper was sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::spec::whisper::NewWhisper;
+    ///
+    /// let whisper = NewWhisper::new(69420, "MrMouton", 42069, "hello", Utc::now()).with_id(1);
+    /// assert_eq!(whisper.recipient_id(), 42069);
+    /// assert!(whisper.delivered_at().is_none());
+    /// ```
+    pub fn new(
+        sender_id: u64,
+        sender: &'a str,
+        recipient_id: u64,
+        contents: &'a str,
+        sent_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            sender_id: Unsigned(sender_id),
+            sender,
+            recipient_id: Unsigned(recipient_id),
+            contents,
+            // Whispers are only tracked to the second, as MySQL timestamps are
+            sent_at: sent_at.naive_utc().trunc_subsecs(0),
+        }
+    }
+
+    /// Converts the whisper into a recorded, undelivered whisper with the
+    /// given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID assigned to the whisper
+    pub fn with_id(&self, id: u64) -> Whisper {
+        Whisper {
+            id,
+            sender_id: self.sender_id.0,
+            sender: self.sender.to_owned(),
+            recipient_id: self.recipient_id.0,
+            contents: self.contents.to_owned(),
+            sent_at: self.sent_at,
+            delivered_at: None,
+        }
+    }
+}
+
+/// ReadMarker represents the most recent whisper that a user has read in a
+/// conversation.
+#[derive(Queryable, Insertable, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[table_name = "whisper_reads"]
+pub struct ReadMarker {
+    /// The ID of the user who read the whispers
+    user_id: Unsigned<u64>,
+
+    /// The ID of the user who sent the whispers
+    peer_id: Unsigned<u64>,
+
+    /// The ID of the most recent whisper read by the user
+    last_read_id: Unsigned<u64>,
+}
+
+impl ReadMarker {
+    /// Creates a new read marker.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who read the whispers
+    /// * `peer_id` - The ID of the user who sent the whispers
+    /// * `last_read_id` - The ID of the most recent whisper read by the user
+    pub fn new(user_id: u64, peer_id: u64, last_read_id: u64) -> Self {
+        Self {
+            user_id: Unsigned(user_id),
+            peer_id: Unsigned(peer_id),
+            last_read_id: Unsigned(last_read_id),
+        }
+    }
+
+    /// Retreives the ID of the user who read the whispers.
+    pub fn user_id(&self) -> u64 {
+        self.user_id.0
+    }
+
+    /// Retreives the ID of the user who sent the whispers.
+    pub fn peer_id(&self) -> u64 {
+        self.peer_id.0
+    }
+
+    /// Retreives the ID of the most recent whisper read by the user.
+    pub fn last_read_id(&self) -> u64 {
+        self.last_read_id.0
+    }
+}
+
+/// UnreadCount represents the number of whispers that a user has yet to read
+/// in a conversation.
+#[derive(QueryableByName, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct UnreadCount {
+    /// The ID of the user to whom the whispers were sent
+    #[sql_type = "UnsignedBigint"]
+    user_id: u64,
+
+    /// The ID of the user who sent the whispers
+    #[sql_type = "UnsignedBigint"]
+    peer_id: u64,
+
+    /// The ID of the most recent whisper read by the user, or zero if none
+    /// have been read
+    #[sql_type = "UnsignedBigint"]
+    last_read_id: u64,
+
+    /// The number of whispers sent after the most recently read whisper
+    #[sql_type = "Bigint"]
+    unread: i64,
+}
+
+impl UnreadCount {
+    /// Creates a new unread count.
+    ///
+    /// # Arguments
+    ///
+    /// * `marker` - The user's read marker in the conversation
+    /// * `unread` - The number of whispers sent after the marker
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::spec::whisper::{ReadMarker, UnreadCount};
+    ///
+    /// let count = UnreadCount::new(&ReadMarker::new(42069, 69420, 7), 3);
+    /// assert_eq!(count.peer_id(), 69420);
+    /// assert_eq!(count.unread(), 3);
+    /// ```
+    pub fn new(marker: &ReadMarker, unread: u64) -> Self {
+        Self {
+            user_id: marker.user_id.0,
+            peer_id: marker.peer_id.0,
+            last_read_id: marker.last_read_id.0,
+            unread: unread as i64,
+        }
+    }
+
+    /// Retreives the ID of the user to whom the whispers were sent.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Retreives the ID of the user who sent the whispers.
+    pub fn peer_id(&self) -> u64 {
+        self.peer_id
+    }
+
+    /// Retreives the ID of the most recent whisper read by the user.
+    pub fn last_read_id(&self) -> u64 {
+        self.last_read_id
+    }
+
+    /// Retreives the number of unread whispers in the conversation.
+    pub fn unread(&self) -> u64 {
+        self.unread as u64
+    }
+}
@@ -0,0 +1,664 @@
+use actix_web::{
+    dev::Payload, http::StatusCode, web::Data, FromRequest, HttpRequest, ResponseError,
+};
+use futures::future::{ready, Ready};
+use openssl::memcmp;
+
+use super::{
+    super::spec::user::Role,
+    modules::{
+        roles::Provider as RolesProvider, sessions::Provider as SessionsProvider, Cache, Hybrid,
+        Persistent, ProviderError,
+    },
+    problem::ErrorCode,
+    server::State,
+};
+
+use std::{error::Error, fmt, marker::PhantomData};
+
+/// AuthError represents any error encountered while authenticating a request.
+#[derive(Debug)]
+pub enum AuthError {
+    MissingCredentials,
+    InvalidCredentials,
+    InsufficientPermissions,
+    Unavailable,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCredentials => write!(f, "the request carried no credentials"),
+            Self::InvalidCredentials => write!(f, "the provided credentials are invalid"),
+            Self::InsufficientPermissions => {
+                write!(f, "the requester is not permitted to perform this action")
+            }
+            Self::Unavailable => write!(f, "the credentials could not be verified"),
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::MissingCredentials => StatusCode::UNAUTHORIZED,
+            Self::InvalidCredentials | Self::InsufficientPermissions => StatusCode::FORBIDDEN,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl ErrorCode for AuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::MissingCredentials => "missing_credentials",
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::InsufficientPermissions => "insufficient_permissions",
+            Self::Unavailable => "credentials_unverifiable",
+        }
+    }
+}
+
+/// Gets the bearer token attached to the request's Authorization header, if
+/// any.
+///
+/// # Arguments
+///
+/// * `req` - The request whose bearer token should be obtained
+pub(crate) fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+}
+
+/// AdminToken is an extractor guaranteeing that the request was made by a
+/// holder of the server's administrative token.
+pub struct AdminToken;
+
+impl FromRequest for AdminToken {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = match bearer_token(req) {
+            Some(token) => token,
+            None => return ready(Err(AuthError::MissingCredentials)),
+        };
+
+        ready(match req.app_data::<Data<State>>() {
+            // An unset administrative token disables administrative
+            // routes entirely. Tokens are compared in constant time, so that
+            // the token can't be learned from the time taken to reject it.
+            Some(state)
+                if !state.admin_token().is_empty()
+                    && state.admin_token().len() == token.len()
+                    && memcmp::eq(state.admin_token().as_bytes(), token.as_bytes()) =>
+            {
+                Ok(Self)
+            }
+            _ => Err(AuthError::InvalidCredentials),
+        })
+    }
+}
+
+/// AuthedUser is an extractor guaranteeing that the request carried a valid
+/// session token, describing the user to whom the token was issued.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthedUser {
+    /// The ID of the user to whom the session token was issued
+    id: u64,
+
+    /// The roles held by the user when the session token was issued
+    roles: Vec<Role>,
+
+    /// The ID of the administrator acting as the user, if the session token
+    /// was issued for impersonation
+    impersonator: Option<u64>,
+
+    /// The ID of the session to which the session token belongs
+    session_id: String,
+}
+
+impl AuthedUser {
+    /// Retreives the ID of the authenticated user.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retreives the roles held by the authenticated user.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Retreives the ID of the administrator acting as the authenticated
+    /// user, if the user is being impersonated.
+    pub fn impersonator(&self) -> Option<u64> {
+        self.impersonator
+    }
+
+    /// Retreives the ID of the session to which the user's session token
+    /// belongs.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Determines whether or not the authenticated user holds the given role.
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - The role that the user should hold
+    pub fn has_role(&self, role: &Role) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+impl FromRequest for AuthedUser {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let (token, state) = match (bearer_token(req), req.app_data::<Data<State>>()) {
+            (Some(token), Some(state)) => (token, state),
+            (None, _) => return ready(Err(AuthError::MissingCredentials)),
+            _ => return ready(Err(AuthError::InvalidCredentials)),
+        };
+
+        let claims = match state.signing_keys().verify(token) {
+            Ok(claims) => claims,
+            Err(_) => return ready(Err(AuthError::InvalidCredentials)),
+        };
+
+        // Tokens remain valid only as long as the sessions they belong to,
+        // so that sessions may be revoked before their tokens expire
+        ready(
+            match state.retry_policy().run(|| {
+                state.cache_connection().and_then(|mut conn| {
+                    Cache::new(&mut conn)
+                        .with_prefix(state.key_prefix())
+                        .session_user(claims.session_id())
+                })
+            }) {
+                Ok(Some(user_id)) if user_id == claims.user_id() => Ok(Self {
+                    id: user_id,
+                    roles: claims.roles().to_vec(),
+                    impersonator: claims.impersonator(),
+                    session_id: claims.session_id().to_owned(),
+                }),
+                Ok(_) => Err(AuthError::InvalidCredentials),
+                Err(_) => Err(AuthError::Unavailable),
+            },
+        )
+    }
+}
+
+/// Gets the ID of the user whose session token was presented with the given
+/// request, if any. The token's signature is verified, but its session isn't
+/// looked up, so the ID may only be used for informational purposes (e.g.
+/// logging).
+///
+/// # Arguments
+///
+/// * `req` - The request carrying the token
+pub(crate) fn claimed_user_id(req: &HttpRequest) -> Option<u64> {
+    let state = req.app_data::<Data<State>>()?;
+
+    bearer_token(req)
+        .and_then(|token| state.signing_keys().verify(token).ok())
+        .map(|claims| claims.user_id())
+}
+
+/// Principal represents the party on whose behalf a request was made.
+#[derive(Debug, PartialEq)]
+pub enum Principal {
+    /// The request was made by a holder of the server's administrative token
+    Administrator,
+
+    /// The request was made by the given authenticated user
+    User(AuthedUser),
+}
+
+impl Principal {
+    /// Determines whether or not the principal may perform moderation
+    /// actions. Moderation may be performed by moderators and administrators.
+    pub fn is_moderator(&self) -> bool {
+        match self {
+            Self::Administrator => true,
+            Self::User(user) => {
+                user.has_role(&Role::Moderator) || user.has_role(&Role::Administrator)
+            }
+        }
+    }
+
+    /// Determines whether or not the principal holds administrative
+    /// privileges.
+    pub fn is_administrator(&self) -> bool {
+        match self {
+            Self::Administrator => true,
+            Self::User(user) => user.has_role(&Role::Administrator),
+        }
+    }
+
+    /// Determines whether or not the principal may perform the action
+    /// described by the given capability, according to the roles it held
+    /// when it was authorized.
+    pub fn permits<C: Capability>(&self) -> bool {
+        match self {
+            Self::Administrator => true,
+            Self::User(user) => C::granted(user.roles()),
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        match AdminToken::from_request(req, payload).into_inner() {
+            Ok(_) => return ready(Ok(Self::Administrator)),
+            Err(AuthError::MissingCredentials) => return ready(Err(AuthError::MissingCredentials)),
+            _ => (),
+        }
+
+        // Any other bearer token must be a user's session token
+        ready(
+            AuthedUser::from_request(req, payload)
+                .into_inner()
+                .map(Self::User),
+        )
+    }
+}
+
+/// RoleMarker represents a type standing in for a role, so that the role
+/// required by a RequireRole extractor may be designated by its type.
+pub trait RoleMarker {
+    /// The role that the marker stands in for
+    const ROLE: Role;
+}
+
+/// Markers for each of the roles that may be required by a RequireRole
+/// extractor.
+pub mod role {
+    use super::{Role, RoleMarker};
+
+    /// Stands in for the administrator role.
+    pub struct Administrator;
+
+    impl RoleMarker for Administrator {
+        const ROLE: Role = Role::Administrator;
+    }
+
+    /// Stands in for the moderator role.
+    pub struct Moderator;
+
+    impl RoleMarker for Moderator {
+        const ROLE: Role = Role::Moderator;
+    }
+
+    /// Stands in for the VIP role.
+    pub struct Vip;
+
+    impl RoleMarker for Vip {
+        const ROLE: Role = Role::VIP;
+    }
+
+    /// Stands in for the protected role.
+    pub struct Protected;
+
+    impl RoleMarker for Protected {
+        const ROLE: Role = Role::Protected;
+    }
+
+    /// Stands in for the subscriber role.
+    pub struct Subscriber;
+
+    impl RoleMarker for Subscriber {
+        const ROLE: Role = Role::Subscriber;
+    }
+
+    /// Stands in for the bot role.
+    pub struct Bot;
+
+    impl RoleMarker for Bot {
+        const ROLE: Role = Role::Bot;
+    }
+}
+
+/// Capability represents an action that may only be performed by users
+/// holding certain roles.
+pub trait Capability {
+    /// Determines whether or not a user holding the given roles may perform
+    /// the action.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles held by the user
+    fn granted(roles: &[Role]) -> bool;
+}
+
+/// Each of the capabilities that may be required by a RequireCapability
+/// extractor.
+pub mod capability {
+    use super::{Capability, Role};
+
+    /// Permits banning users from the chat.
+    pub struct CanBan;
+
+    impl Capability for CanBan {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits muting users in the chat.
+    pub struct CanMute;
+
+    impl Capability for CanMute {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits toggling chat-wide modes, like slowmode.
+    pub struct CanSetChatModes;
+
+    impl Capability for CanSetChatModes {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits viewing the activity of other users.
+    pub struct CanViewActivity;
+
+    impl Capability for CanViewActivity {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits granting and revoking the roles of other users.
+    pub struct CanManageRoles;
+
+    impl Capability for CanManageRoles {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits creating, replacing, and deleting emotes.
+    pub struct CanManageEmotes;
+
+    impl Capability for CanManageEmotes {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits creating and deleting flairs, and granting them to users.
+    pub struct CanManageFlairs;
+
+    impl Capability for CanManageFlairs {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits editing the domains that chatters may link to.
+    pub struct CanManageLinks;
+
+    impl Capability for CanManageLinks {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits starting and ending polls.
+    pub struct CanManagePolls;
+
+    impl Capability for CanManagePolls {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits registering and cancelling scheduled announcements.
+    pub struct CanManageAnnouncements;
+
+    impl Capability for CanManageAnnouncements {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits pinning and unpinning messages.
+    pub struct CanPinMessages;
+
+    impl Capability for CanPinMessages {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits deleting messages sent to the chat.
+    pub struct CanDeleteMessages;
+
+    impl Capability for CanDeleteMessages {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits approving and rejecting messages held while the chat is in
+    /// safe mode.
+    pub struct CanApproveMessages;
+
+    impl Capability for CanApproveMessages {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Moderator) || roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits creating, replacing, and deleting bot commands.
+    pub struct CanManageBotCommands;
+
+    impl Capability for CanManageBotCommands {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+
+    /// Permits recording donations and gifted subscriptions.
+    pub struct CanRecordDonations;
+
+    impl Capability for CanRecordDonations {
+        fn granted(roles: &[Role]) -> bool {
+            roles.contains(&Role::Administrator)
+        }
+    }
+}
+
+/// Ensures that the given principal is permitted to perform an action,
+/// according to the roles it currently holds. Roles are read from the roles
+/// provider rather than the principal's session token, so that revoked roles
+/// take effect immediately. Holders of the administrative token are permitted
+/// to perform any action.
+///
+/// # Arguments
+///
+/// * `req` - The request made by the principal
+/// * `principal` - The party on whose behalf the request was made
+/// * `permitted` - Determines whether or not the given roles permit the action
+fn authorize<F: FnOnce(&[Role]) -> bool>(
+    req: &HttpRequest,
+    principal: Principal,
+    permitted: F,
+) -> Result<Principal, AuthError> {
+    let user = match principal {
+        Principal::Administrator => return Ok(principal),
+        Principal::User(user) => user,
+    };
+
+    let state = req
+        .app_data::<Data<State>>()
+        .ok_or(AuthError::InvalidCredentials)?;
+    let roles = current_roles(state, user.id).map_err(|_| AuthError::Unavailable)?;
+
+    if permitted(&roles) {
+        Ok(Principal::User(AuthedUser { roles, ..user }))
+    } else {
+        Err(AuthError::InsufficientPermissions)
+    }
+}
+
+/// Gets the roles currently held by the user with the given ID, preferring
+/// this node's local copy, which is discarded once the roles change.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `user_id` - The ID of the user whose roles should be obtained
+fn current_roles(state: &State, user_id: u64) -> Result<Vec<Role>, ProviderError> {
+    let local = state.local_cache();
+    if let Some(roles) = local.roles(user_id) {
+        return Ok(roles);
+    }
+
+    let generation = local.generation();
+    let roles = state.retry_policy().run(|| {
+        let mut conn = state.cache_connection()?;
+        let persistent_conn = state.persistent_connection()?;
+        let replica_conn = state.replica_connection()?;
+
+        Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+        )
+        .with_breaker(Some(state.breaker().clone()))
+        .with_lookups(Some(state.lookups().clone()))
+        .roles_for_user(user_id)
+    })?;
+    local.store_roles(generation, user_id, &roles);
+
+    Ok(roles)
+}
+
+/// RequireRole is an extractor guaranteeing that the request was made by a
+/// user currently holding the role designated by R, or by an administrator
+/// (e.g. `RequireRole<role::Moderator>`).
+pub struct RequireRole<R: RoleMarker> {
+    /// The party on whose behalf the request was made
+    principal: Principal,
+
+    role: PhantomData<R>,
+}
+
+impl<R: RoleMarker> RequireRole<R> {
+    /// Retreives the party on whose behalf the request was made.
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+}
+
+impl<R: RoleMarker> FromRequest for RequireRole<R> {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        ready(
+            Principal::from_request(req, payload)
+                .into_inner()
+                .and_then(|principal| {
+                    authorize(req, principal, |roles| {
+                        roles.contains(&R::ROLE) || roles.contains(&Role::Administrator)
+                    })
+                })
+                .map(|principal| Self {
+                    principal,
+                    role: PhantomData,
+                }),
+        )
+    }
+}
+
+/// RequireCapability is an extractor guaranteeing that the request was made
+/// by a party permitted to perform the action designated by C (e.g.
+/// `RequireCapability<capability::CanBan>`).
+pub struct RequireCapability<C: Capability> {
+    /// The party on whose behalf the request was made
+    principal: Principal,
+
+    capability: PhantomData<C>,
+}
+
+impl<C: Capability> RequireCapability<C> {
+    /// Retreives the party on whose behalf the request was made.
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+}
+
+impl<C: Capability> FromRequest for RequireCapability<C> {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        ready(
+            Principal::from_request(req, payload)
+                .into_inner()
+                .and_then(|principal| authorize(req, principal, C::granted))
+                .map(|principal| Self {
+                    principal,
+                    capability: PhantomData,
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capability::*, *};
+
+    #[test]
+    fn test_capabilities() {
+        let moderator = [Role::Moderator];
+        let administrator = [Role::Administrator];
+        let subscriber = [Role::Subscriber];
+
+        assert!(CanBan::granted(&moderator));
+        assert!(CanMute::granted(&administrator));
+        assert!(!CanBan::granted(&subscriber));
+        assert!(CanSetChatModes::granted(&moderator));
+        assert!(!CanSetChatModes::granted(&subscriber));
+        assert!(!CanManageRoles::granted(&moderator));
+        assert!(CanManageRoles::granted(&administrator));
+        assert!(!CanManageEmotes::granted(&moderator));
+        assert!(CanManageEmotes::granted(&administrator));
+        assert!(!CanManageFlairs::granted(&moderator));
+        assert!(CanManageFlairs::granted(&administrator));
+        assert!(!CanManageLinks::granted(&moderator));
+        assert!(CanManageLinks::granted(&administrator));
+        assert!(CanManagePolls::granted(&moderator));
+        assert!(!CanManagePolls::granted(&subscriber));
+        assert!(CanPinMessages::granted(&moderator));
+        assert!(!CanPinMessages::granted(&subscriber));
+        assert!(!CanManageAnnouncements::granted(&moderator));
+        assert!(CanManageAnnouncements::granted(&administrator));
+        assert!(CanDeleteMessages::granted(&moderator));
+        assert!(!CanDeleteMessages::granted(&subscriber));
+        assert!(CanApproveMessages::granted(&moderator));
+        assert!(!CanApproveMessages::granted(&subscriber));
+        assert!(!CanManageBotCommands::granted(&moderator));
+        assert!(CanManageBotCommands::granted(&administrator));
+        assert!(!CanRecordDonations::granted(&moderator));
+        assert!(CanRecordDonations::granted(&administrator));
+    }
+}
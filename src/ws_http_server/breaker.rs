@@ -0,0 +1,319 @@
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Serialize;
+
+use super::modules::ProviderError;
+
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// The number of consecutive failures of the persistent layer after which
+/// the breaker opens if no threshold is specified.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// The number of seconds during which an open breaker keeps requests from
+/// reaching the persistent layer if no cool-down is specified.
+pub const DEFAULT_COOL_DOWN_SECONDS: u64 = 30;
+
+/// BreakerState represents whether or not requests may currently reach the
+/// persistent layer.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests reach the persistent layer
+    Closed,
+
+    /// The persistent layer is failing, so requests are served by the
+    /// caching layer alone until the cool-down elapses
+    Open,
+
+    /// The cool-down has elapsed, and a single probing request has been
+    /// allowed to reach the persistent layer
+    HalfOpen,
+}
+
+/// Circuit represents the mutable state of a circuit breaker.
+#[derive(Debug)]
+struct Circuit {
+    /// Whether or not requests may currently reach the persistent layer
+    state: BreakerState,
+
+    /// The number of requests to the persistent layer that have failed in a
+    /// row
+    consecutive_failures: u32,
+
+    /// The time at which the breaker last opened or let a probe through, if
+    /// it isn't closed
+    opened_at: Option<Instant>,
+
+    /// The number of times that the breaker has opened
+    trips: u64,
+}
+
+/// CircuitBreaker keeps hybrid providers from sending requests to the
+/// persistent layer while it is failing. Once the persistent layer fails a
+/// number of times in a row, the breaker opens, and reads are served by the
+/// caching layer alone for a cool-down period. Afterwards, a single probing
+/// request is let through: if it succeeds, the breaker closes, and otherwise
+/// it opens for another cool-down period.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// The number of consecutive failures after which the breaker opens
+    failure_threshold: u32,
+
+    /// The time during which an open breaker keeps requests from reaching
+    /// the persistent layer
+    cool_down: Duration,
+
+    /// The state of the breaker, shared by every worker
+    circuit: Mutex<Circuit>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cool_down: Duration::from_secs(DEFAULT_COOL_DOWN_SECONDS),
+            circuit: Mutex::new(Circuit {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trips: 0,
+            }),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Consumes the breaker, and modifies it according to the provided
+    /// failure threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `failure_threshold` - The number of consecutive failures of the
+    /// persistent layer after which the breaker should open
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+
+        self
+    }
+
+    /// Consumes the breaker, and modifies it according to the provided
+    /// cool-down.
+    ///
+    /// # Arguments
+    ///
+    /// * `cool_down` - The time during which an open breaker should keep
+    /// requests from reaching the persistent layer
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+
+        self
+    }
+
+    /// Determines whether or not a request may be sent to the persistent
+    /// layer. Once an open breaker's cool-down elapses, a single probing
+    /// request is allowed, whose result should be recorded. If it isn't,
+    /// another probe is allowed once the cool-down elapses again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diesel::result::ConnectionError;
+    /// use gnomegg::ws_http_server::{breaker::{BreakerState, CircuitBreaker}, modules::ProviderError};
+    ///
+    /// let breaker = CircuitBreaker::default().with_failure_threshold(1);
+    /// assert!(breaker.allow());
+    ///
+    /// breaker.record::<()>(&Err(ProviderError::ConnectionError(
+    ///     ConnectionError::BadConnection("connection refused".to_owned()),
+    /// )));
+    /// assert_eq!(breaker.state(), BreakerState::Open);
+    /// assert!(!breaker.allow());
+    /// ```
+    pub fn allow(&self) -> bool {
+        let mut circuit = self.circuit();
+
+        match circuit.state {
+            BreakerState::Closed => true,
+            BreakerState::Open | BreakerState::HalfOpen => {
+                if circuit
+                    .opened_at
+                    .is_none_or(|opened_at| opened_at.elapsed() >= self.cool_down)
+                {
+                    circuit.state = BreakerState::HalfOpen;
+                    circuit.opened_at = Some(Instant::now());
+
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Records the result of a request sent to the persistent layer. Errors
+    /// caused by the request itself (e.g. a missing argument, or a
+    /// conflicting row) aren't considered failures of the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result of the request
+    pub fn record<T>(&self, result: &Result<T, ProviderError>) {
+        let mut circuit = self.circuit();
+
+        match result {
+            Err(e) if indicates_outage(e) => {
+                circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+
+                if circuit.state == BreakerState::HalfOpen
+                    || circuit.consecutive_failures >= self.failure_threshold
+                {
+                    if circuit.state != BreakerState::Open {
+                        circuit.trips += 1;
+                    }
+
+                    circuit.state = BreakerState::Open;
+                    circuit.opened_at = Some(Instant::now());
+                }
+            }
+            _ => {
+                circuit.state = BreakerState::Closed;
+                circuit.consecutive_failures = 0;
+                circuit.opened_at = None;
+            }
+        }
+    }
+
+    /// Retreives whether or not requests may currently reach the persistent
+    /// layer.
+    pub fn state(&self) -> BreakerState {
+        self.circuit().state
+    }
+
+    /// Takes a snapshot of the state of the breaker.
+    pub fn metrics(&self) -> BreakerMetrics {
+        let circuit = self.circuit();
+
+        BreakerMetrics {
+            state: circuit.state,
+            consecutive_failures: circuit.consecutive_failures,
+            trips: circuit.trips,
+        }
+    }
+
+    /// Locks the state of the breaker. The state remains consistent even if
+    /// a thread panicked while holding the lock, so poisoning is ignored.
+    fn circuit(&self) -> MutexGuard<'_, Circuit> {
+        self.circuit
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// BreakerMetrics represents a snapshot of the state of a circuit breaker.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct BreakerMetrics {
+    /// Whether or not requests may currently reach the persistent layer
+    state: BreakerState,
+
+    /// The number of requests to the persistent layer that have failed in a
+    /// row
+    consecutive_failures: u32,
+
+    /// The number of times that the breaker has opened
+    trips: u64,
+}
+
+impl BreakerMetrics {
+    /// Retreives whether or not requests may currently reach the persistent
+    /// layer.
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    /// Retreives the number of requests to the persistent layer that have
+    /// failed in a row.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Retreives the number of times that the breaker has opened.
+    pub fn trips(&self) -> u64 {
+        self.trips
+    }
+}
+
+/// Determines whether or not the given error indicates that the persistent
+/// layer is unavailable, rather than that the request was rejected.
+///
+/// # Arguments
+///
+/// * `e` - The error returned by the persistent layer
+pub(crate) fn indicates_outage(e: &ProviderError) -> bool {
+    match e {
+        ProviderError::ConnectionError(_)
+        | ProviderError::PoolError(_)
+        | ProviderError::Unavailable => true,
+        ProviderError::DieselError(DieselError::NotFound)
+        | ProviderError::DieselError(DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            _,
+        ))
+        | ProviderError::DieselError(DieselError::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        )) => false,
+        ProviderError::DieselError(_) => true,
+        ProviderError::Composite { persistent, .. } => indicates_outage(persistent),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use diesel::result::ConnectionError;
+
+    use std::thread;
+
+    /// Gets an error indicating that the persistent layer is unavailable.
+    fn outage() -> Result<(), ProviderError> {
+        Err(ProviderError::ConnectionError(
+            ConnectionError::BadConnection("connection refused".to_owned()),
+        ))
+    }
+
+    #[test]
+    fn test_breaker() {
+        let breaker = CircuitBreaker::default()
+            .with_failure_threshold(2)
+            .with_cool_down(Duration::from_millis(50));
+
+        // Rejected requests don't count as failures
+        breaker.record::<()>(&Err(ProviderError::InvalidArgument { arg: "user_id" }));
+        breaker.record(&outage());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record(&outage());
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+
+        // A single probe is let through once the cool-down elapses, and a
+        // failing probe opens the breaker again
+        thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        breaker.record(&outage());
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.metrics().trips(), 2);
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow());
+        breaker.record(&Ok(()));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.metrics().consecutive_failures(), 0);
+    }
+}
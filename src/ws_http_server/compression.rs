@@ -0,0 +1,139 @@
+use actix_web::{
+    dev::{BodyEncoding, BodySize, MessageBody, ServiceResponse},
+    http::{header::CONTENT_TYPE, ContentEncoding},
+};
+
+/// The number of bytes that a response body must hold before it is
+/// compressed, if no threshold is specified.
+pub const DEFAULT_MIN_COMPRESSED_BYTES: u64 = 1024;
+
+/// The media types of the response bodies that may be compressed. Bodies of
+/// other types (e.g. avatars) are usually compressed already.
+const COMPRESSIBLE_TYPES: [&str; 5] = [
+    "application/json",
+    "application/problem+json",
+    "application/javascript",
+    "image/svg+xml",
+    "text/",
+];
+
+/// The media type of event streams, which are never compressed, as encoders
+/// hold frames back until enough of them are buffered.
+const EVENT_STREAM_TYPE: &str = "text/event-stream";
+
+/// CompressionPolicy determines which responses are compressed according to
+/// the encodings accepted by the client. Only large bodies of compressible
+/// types are compressed, as compressing small ones costs more than it saves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressionPolicy {
+    /// Whether or not any response is compressed
+    enabled: bool,
+
+    /// The number of bytes that a body must hold before it is compressed
+    min_bytes: u64,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_bytes: DEFAULT_MIN_COMPRESSED_BYTES,
+        }
+    }
+}
+
+impl CompressionPolicy {
+    /// Consumes the policy, and modifies it according to whether or not
+    /// responses should be compressed at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether or not any response should be compressed
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+
+        self
+    }
+
+    /// Consumes the policy, and modifies it according to the provided size
+    /// threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_bytes` - The number of bytes that a body must hold before it
+    /// should be compressed
+    pub fn with_min_bytes(mut self, min_bytes: u64) -> Self {
+        self.min_bytes = min_bytes;
+
+        self
+    }
+
+    /// Determines whether or not a body of the given type and size should be
+    /// compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The media type of the body, if known
+    /// * `size` - The size of the body
+    pub fn should_compress(&self, content_type: Option<&str>, size: BodySize) -> bool {
+        let compressible = content_type.is_some_and(|content_type| {
+            !content_type.starts_with(EVENT_STREAM_TYPE)
+                && COMPRESSIBLE_TYPES
+                    .iter()
+                    .any(|compressible| content_type.starts_with(compressible))
+        });
+
+        // Streamed bodies of compressible types are assumed to be large
+        let large = match size {
+            BodySize::Sized(len) => len >= self.min_bytes,
+            BodySize::Stream => true,
+            BodySize::None | BodySize::Empty => false,
+        };
+
+        self.enabled && compressible && large
+    }
+
+    /// Keeps the given response from being compressed, unless the policy
+    /// calls for it to be. The response is otherwise compressed according to
+    /// the encodings accepted by the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `res` - The response that may be compressed
+    pub fn apply<B: MessageBody>(&self, res: &mut ServiceResponse<B>) {
+        let compress = self.should_compress(
+            res.headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok()),
+            res.response().body().size(),
+        );
+
+        if !compress {
+            res.response_mut().encoding(ContentEncoding::Identity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_compress() {
+        let policy = CompressionPolicy::default();
+        assert!(policy.should_compress(Some("application/json"), BodySize::Sized(4096)));
+        assert!(policy.should_compress(Some("text/html; charset=utf-8"), BodySize::Sized(4096)));
+
+        // Small bodies, images, and event streams aren't compressed
+        assert!(!policy.should_compress(Some("application/json"), BodySize::Sized(64)));
+        assert!(!policy.should_compress(Some("image/png"), BodySize::Sized(4096)));
+        assert!(!policy.should_compress(Some("text/event-stream"), BodySize::Stream));
+        assert!(!policy.should_compress(None, BodySize::Sized(4096)));
+
+        let policy = policy.with_min_bytes(0);
+        assert!(policy.should_compress(Some("application/json"), BodySize::Sized(64)));
+
+        let policy = policy.with_enabled(false);
+        assert!(!policy.should_compress(Some("application/json"), BodySize::Sized(4096)));
+    }
+}
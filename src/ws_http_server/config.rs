@@ -0,0 +1,1476 @@
+use chrono::Duration;
+use serde::{de, Deserialize, Deserializer};
+
+use super::{
+    super::spec::{announcement::MAX_ANNOUNCEMENT_LENGTH, stats::Window, user::Role},
+    breaker::{CircuitBreaker, DEFAULT_COOL_DOWN_SECONDS, DEFAULT_FAILURE_THRESHOLD},
+    compression::{CompressionPolicy, DEFAULT_MIN_COMPRESSED_BYTES},
+    dispatcher::{
+        ChatGates, Dispatcher, EscalationPolicy, LinkFilter, SlowmodePolicy,
+        DEFAULT_EDIT_WINDOW_SECONDS, DEFAULT_SLOWMODE_INTERVAL,
+    },
+    invalidation::{LocalCache, DEFAULT_LOCAL_CACHE_CAPACITY, DEFAULT_LOCAL_CACHE_TTL_SECONDS},
+    keyring::Keyring,
+    modules::{
+        oauth::{OauthCredentials, OauthProvider},
+        Layers,
+    },
+    pool::{PoolConfig, DEFAULT_CONNECTION_TIMEOUT_SECONDS, DEFAULT_MAX_SIZE},
+    retry::{RetryPolicy, DEFAULT_BASE_DELAY_MS, DEFAULT_MAX_ATTEMPTS, DEFAULT_MAX_DELAY_MS},
+    sanitizer::{
+        Sanitizer, DEFAULT_MAX_COMBINING_MARKS, DEFAULT_MAX_MESSAGE_BYTES,
+        DEFAULT_MAX_MESSAGE_CHARS,
+    },
+    shedding::{LoadShedder, DEFAULT_MAX_LOOP_LAG_MS, DEFAULT_MAX_PROVIDER_LATENCY_MS},
+    telemetry::LogFormat,
+};
+
+use std::{
+    env,
+    error::Error,
+    fmt, fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration as StdDuration,
+};
+
+/// The environment variable naming the configuration file that should be
+/// read.
+pub const CONFIG_FILE_VARIABLE: &str = "CONFIG_FILE";
+
+/// The configuration file read if CONFIG_FILE isn't set, provided that it
+/// exists.
+pub const DEFAULT_CONFIG_FILE: &str = "gnomegg.toml";
+
+/// The address that the server listens on if none is specified.
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8080";
+
+/// The address of the redis caching layer if none is specified.
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1/";
+
+/// The directory that avatars are stored in if none is specified.
+pub const DEFAULT_AVATAR_DIR: &str = "avatars";
+
+/// ConfigError represents any error encountered while loading or validating
+/// the server's configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file couldn't be read
+    Io { path: PathBuf, error: io::Error },
+
+    /// The configuration file isn't valid TOML, or holds an unknown or
+    /// mistyped setting
+    Malformed {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+
+    /// An environment variable holds a value that can't be parsed
+    InvalidVariable { name: String, reason: String },
+
+    /// A required setting was provided by neither the configuration file nor
+    /// the environment
+    Missing {
+        setting: &'static str,
+        variable: &'static str,
+    },
+
+    /// A setting holds a value that can't be used
+    Invalid {
+        setting: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(
+                f,
+                "the configuration file {} could not be read: {}",
+                path.display(),
+                error
+            ),
+            Self::Malformed { path, error } => write!(
+                f,
+                "the configuration file {} is invalid: {}",
+                path.display(),
+                error
+            ),
+            Self::InvalidVariable { name, reason } => write!(
+                f,
+                "the environment variable {} is invalid: {}",
+                name, reason
+            ),
+            Self::Missing { setting, variable } => write!(
+                f,
+                "{} must be set, either in the configuration file or through {}",
+                setting, variable
+            ),
+            Self::Invalid { setting, reason } => write!(f, "{} is invalid: {}", setting, reason),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Malformed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for io::Error {
+    /// Constructs an IO error from the given configuration error, such that
+    /// it may be returned from the server's entry point.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The configuration error that should be wrapped in the IO error
+    fn from(e: ConfigError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// Config represents every setting of the server. Settings are read from a
+/// TOML file, if any, and then overridden by the environment variables
+/// naming them (e.g. `[redis] url` is overridden by REDIS_URL), before being
+/// validated as a whole.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The settings of the HTTP server itself
+    server: ServerConfig,
+
+    /// The settings of the redis caching layer
+    redis: RedisConfig,
+
+    /// The settings of the MySQL persistence layer
+    database: DatabaseConfig,
+
+    /// The settings governing which chatters may chat, and what they may send
+    chat: ChatConfig,
+
+    /// The limits on how often chatters may send messages
+    rate_limits: RateLimitConfig,
+
+    /// The optional behaviors of the server
+    features: FeatureConfig,
+
+    /// The settings governing how the server copes with failing backends
+    resilience: ResilienceConfig,
+
+    /// The credentials of each of the enabled oauth providers
+    oauth: OauthConfig,
+}
+
+/// ServerConfig represents the settings of the HTTP server itself.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ServerConfig {
+    /// The address that the server listens on
+    bind_address: String,
+
+    /// The certificate and key used to serve requests over TLS
+    tls: TlsConfig,
+
+    /// The token granting access to the administrative routes
+    admin_token: String,
+
+    /// The addresses of the reverse proxies whose forwarded headers are
+    /// trusted to name the client making a request
+    trusted_proxies: Vec<IpAddr>,
+
+    /// The directory that avatars are stored in
+    avatar_dir: PathBuf,
+
+    /// The directory holding the keys that session tokens are signed with
+    jwt_keys_dir: Option<PathBuf>,
+
+    /// The keys that sensitive values are encrypted with at rest
+    #[serde(deserialize_with = "deserialize_parsed")]
+    encryption_keys: Option<Keyring>,
+
+    /// The format in which spans and events are logged
+    log_format: LogFormat,
+
+    /// The URL of the OTLP/HTTP collector that spans are exported to
+    otlp_endpoint: Option<String>,
+
+    /// The secret that requests to the stream status webhook are signed with
+    stream_webhook_secret: Option<String>,
+
+    /// Whether or not pending migrations are run before the server starts
+    migrate_on_startup: bool,
+
+    /// Whether or not the cache is preloaded from MySQL before the server
+    /// starts
+    warm_cache_on_startup: bool,
+
+    /// Whether or not large responses are compressed for clients accepting
+    /// gzip, deflate or brotli
+    compress_responses: bool,
+
+    /// The number of bytes that a response body must hold before it is
+    /// compressed
+    compression_min_bytes: u64,
+
+    /// The directory holding the chat frontend served alongside the API,
+    /// which requires the frontend feature
+    frontend_dir: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: DEFAULT_BIND_ADDRESS.to_owned(),
+            tls: TlsConfig::default(),
+            admin_token: String::new(),
+            trusted_proxies: Vec::new(),
+            avatar_dir: DEFAULT_AVATAR_DIR.into(),
+            jwt_keys_dir: None,
+            encryption_keys: None,
+            log_format: LogFormat::default(),
+            otlp_endpoint: None,
+            stream_webhook_secret: None,
+            migrate_on_startup: true,
+            warm_cache_on_startup: true,
+            compress_responses: true,
+            compression_min_bytes: DEFAULT_MIN_COMPRESSED_BYTES,
+            frontend_dir: None,
+        }
+    }
+}
+
+/// TlsConfig represents the PEM-encoded certificate chain and private key
+/// with which the server serves requests over TLS.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct TlsConfig {
+    /// The path of the certificate chain
+    cert_path: Option<PathBuf>,
+
+    /// The path of the private key
+    key_path: Option<PathBuf>,
+}
+
+/// PoolSettings represents the sizing of a pool of connections, any part of
+/// which may be left to its default.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct PoolSettings {
+    /// The maximum number of connections held by the pool
+    size: Option<u32>,
+
+    /// The number of idle connections that the pool tries to keep open
+    min_idle: Option<u32>,
+
+    /// The number of seconds that a handler waits for a connection
+    timeout_seconds: Option<u64>,
+}
+
+impl PoolSettings {
+    /// Overrides the sizing of the pool with the environment variables
+    /// starting with the given prefix (e.g. DATABASE_POOL_SIZE).
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment variables overriding the sizing
+    /// * `prefix` - The prefix of each of the pool's environment variables
+    fn apply_env(&mut self, env: &Env, prefix: &str) -> Result<(), ConfigError> {
+        env.parse_some(&format!("{}_POOL_SIZE", prefix), &mut self.size)?;
+        env.parse_some(&format!("{}_POOL_MIN_IDLE", prefix), &mut self.min_idle)?;
+        env.parse_some(
+            &format!("{}_POOL_TIMEOUT_SECONDS", prefix),
+            &mut self.timeout_seconds,
+        )
+    }
+
+    /// Ensures that the pool may hold at least one connection, and keeps no
+    /// more idle connections than it may hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `setting` - The name of the pool's settings
+    fn validate(&self, setting: &'static str) -> Result<(), ConfigError> {
+        let size = self.size.unwrap_or(DEFAULT_MAX_SIZE);
+
+        if size == 0 {
+            return Err(ConfigError::Invalid {
+                setting,
+                reason: "the pool must hold at least one connection".to_owned(),
+            });
+        }
+
+        match self.min_idle {
+            Some(min_idle) if min_idle > size => Err(ConfigError::Invalid {
+                setting,
+                reason: format!(
+                    "the pool can't keep {} idle connections, since it holds at most {}",
+                    min_idle, size
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Builds the pool configuration described by the settings.
+    fn pool_config(&self) -> PoolConfig {
+        PoolConfig::default()
+            .with_max_size(self.size.unwrap_or(DEFAULT_MAX_SIZE))
+            .with_min_idle(self.min_idle)
+            .with_connection_timeout(StdDuration::from_secs(
+                self.timeout_seconds
+                    .unwrap_or(DEFAULT_CONNECTION_TIMEOUT_SECONDS),
+            ))
+    }
+}
+
+/// RedisConfig represents the settings of the redis caching layer.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct RedisConfig {
+    /// The address of the redis server
+    url: String,
+
+    /// The prefix of each key written by the server
+    key_prefix: String,
+
+    /// The sizing of the pool of connections to redis
+    pool: PoolSettings,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_REDIS_URL.to_owned(),
+            key_prefix: String::new(),
+            pool: PoolSettings::default(),
+        }
+    }
+}
+
+/// DatabaseConfig represents the settings of the MySQL persistence layer.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseConfig {
+    /// The address of the primary MySQL database, or the path to the SQLite
+    /// database if the crate is built with the `sqlite` feature
+    url: Option<String>,
+
+    /// The sizing of the pool of connections to the primary database
+    pool: PoolSettings,
+
+    /// The read replica that hot reads are served from, if any
+    replica: ReplicaConfig,
+}
+
+/// ReplicaConfig represents the settings of a MySQL read replica.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ReplicaConfig {
+    /// The address of the replica
+    url: Option<String>,
+
+    /// The sizing of the pool of connections to the replica
+    pool: PoolSettings,
+}
+
+/// ChatConfig represents the settings governing which chatters may chat,
+/// and what they may send.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ChatConfig {
+    /// The roles given to each new user
+    default_roles: Vec<Role>,
+
+    /// The number of hours that an account must have existed for before its
+    /// holder may chat
+    min_account_age_hours: Option<i64>,
+
+    /// The role from which chatters may link to any domain
+    link_bypass_role: Option<Role>,
+
+    /// The number of minutes that chatters linking to a forbidden domain are
+    /// muted for
+    link_mute_minutes: Option<i64>,
+
+    /// The number of minutes that pinned messages remain pinned for
+    pin_ttl_minutes: Option<i64>,
+
+    /// The window in which a chatter must not have sent any other message
+    /// for their message to be flagged as their first, or "ever"
+    #[serde(deserialize_with = "deserialize_window")]
+    first_message_window: Option<Window>,
+
+    /// The number of seconds that chatters may edit their messages for
+    edit_window_seconds: i64,
+
+    /// The maximum number of combining marks per character
+    max_combining_marks: usize,
+
+    /// The maximum number of characters per message, or 0 for no limit
+    max_message_chars: usize,
+
+    /// The maximum number of bytes per message, or 0 for no limit
+    max_message_bytes: usize,
+
+    /// The announcement sent to the chat when the stream goes live
+    live_announcement: Option<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            default_roles: Vec::new(),
+            min_account_age_hours: None,
+            link_bypass_role: None,
+            link_mute_minutes: None,
+            pin_ttl_minutes: None,
+            first_message_window: None,
+            edit_window_seconds: DEFAULT_EDIT_WINDOW_SECONDS,
+            max_combining_marks: DEFAULT_MAX_COMBINING_MARKS,
+            max_message_chars: DEFAULT_MAX_MESSAGE_CHARS,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            live_announcement: None,
+        }
+    }
+}
+
+/// RateLimitConfig represents the limits on how often chatters may send
+/// messages.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct RateLimitConfig {
+    /// The number of seconds between messages in slowmode, unless a
+    /// moderator specifies otherwise
+    slowmode_interval_seconds: u64,
+
+    /// The role from which chatters may chat freely in slowmode, or "none"
+    #[serde(deserialize_with = "deserialize_exempt_role")]
+    slowmode_exempt_role: Option<Role>,
+
+    /// The mutes issued against chatters repeatedly sending spam
+    spam_escalation: Vec<EscalationStep>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            slowmode_interval_seconds: DEFAULT_SLOWMODE_INTERVAL,
+            slowmode_exempt_role: Some(Role::Moderator),
+            spam_escalation: Vec::new(),
+        }
+    }
+}
+
+/// EscalationStep represents the mute issued against a chatter sending spam
+/// for the given number of times.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct EscalationStep {
+    /// The number of offenses from which the step applies
+    offenses: u64,
+
+    /// The number of minutes that the chatter is muted for
+    minutes: i64,
+}
+
+impl FromStr for EscalationStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (offenses, minutes) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| "escalation steps must be formatted as offenses:minutes".to_owned())?;
+
+        Ok(Self {
+            offenses: offenses.parse().map_err(|e| format!("{}", e))?,
+            minutes: minutes.parse().map_err(|e| format!("{}", e))?,
+        })
+    }
+}
+
+/// FeatureConfig represents the optional behaviors of the server.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct FeatureConfig {
+    /// Whether or not chatters must verify their email before chatting
+    require_verified_email: bool,
+
+    /// Whether or not links to domains that aren't whitelisted are filtered
+    filter_links: bool,
+
+    /// Whether or not first messages are held until a moderator approves them
+    safe_mode: bool,
+
+    /// Whether or not control characters are stripped from messages
+    strip_control_chars: bool,
+
+    /// Whether or not runs of whitespace in messages are collapsed
+    collapse_whitespace: bool,
+
+    /// Whether or not messages are normalized to NFC
+    normalize_unicode: bool,
+
+    /// Whether or not confusable characters are folded to their Latin
+    /// lookalikes
+    fold_confusables: bool,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            require_verified_email: false,
+            filter_links: false,
+            safe_mode: false,
+            strip_control_chars: true,
+            collapse_whitespace: true,
+            normalize_unicode: true,
+            fold_confusables: false,
+        }
+    }
+}
+
+/// ResilienceConfig represents the settings governing how the server copes
+/// with failing backends.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ResilienceConfig {
+    /// The layers that bans and mutes are stored in
+    #[serde(deserialize_with = "deserialize_layers")]
+    moderation_layers: Layers,
+
+    /// The number of consecutive failures after which MySQL stops being read
+    breaker_failure_threshold: u32,
+
+    /// The number of seconds that MySQL stops being read for
+    breaker_cool_down_seconds: u64,
+
+    /// The number of chatters whose roles and mutes are kept in memory, or 0
+    /// to disable the local cache
+    local_cache_size: usize,
+
+    /// The number of seconds that roles and mutes are kept in memory for
+    local_cache_ttl_seconds: u64,
+
+    /// The number of times that idempotent operations are attempted
+    retry_max_attempts: u32,
+
+    /// The number of milliseconds waited before the first retry
+    retry_base_delay_ms: u64,
+
+    /// The maximum number of milliseconds waited before any retry
+    retry_max_delay_ms: u64,
+
+    /// Whether or not moderation writes are queued in redis while MySQL is
+    /// down, rather than failing
+    queue_failed_writes: bool,
+
+    /// The number of milliseconds that event loops may lag by before
+    /// low-priority requests are shed, or 0 to ignore lag
+    shed_max_loop_lag_ms: u64,
+
+    /// The number of milliseconds that checking out a connection may take
+    /// before low-priority requests are shed, or 0 to ignore latency
+    shed_max_provider_latency_ms: u64,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            moderation_layers: Layers::default(),
+            breaker_failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            breaker_cool_down_seconds: DEFAULT_COOL_DOWN_SECONDS,
+            local_cache_size: DEFAULT_LOCAL_CACHE_CAPACITY,
+            local_cache_ttl_seconds: DEFAULT_LOCAL_CACHE_TTL_SECONDS,
+            retry_max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_base_delay_ms: DEFAULT_BASE_DELAY_MS,
+            retry_max_delay_ms: DEFAULT_MAX_DELAY_MS,
+            queue_failed_writes: true,
+            shed_max_loop_lag_ms: DEFAULT_MAX_LOOP_LAG_MS,
+            shed_max_provider_latency_ms: DEFAULT_MAX_PROVIDER_LATENCY_MS,
+        }
+    }
+}
+
+/// OauthConfig represents the credentials of each of the oauth providers.
+/// A provider is enabled by providing each of its credentials.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct OauthConfig {
+    /// The credentials of the Twitch application
+    twitch: OauthSettings,
+
+    /// The credentials of the Reddit application
+    reddit: OauthSettings,
+
+    /// The credentials of the Twitter application
+    twitter: OauthSettings,
+}
+
+impl OauthConfig {
+    /// Retreives the settings of the given provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider whose settings should be obtained
+    fn settings_mut(&mut self, provider: OauthProvider) -> &mut OauthSettings {
+        match provider {
+            OauthProvider::Twitch => &mut self.twitch,
+            OauthProvider::Reddit => &mut self.reddit,
+            OauthProvider::Twitter => &mut self.twitter,
+        }
+    }
+
+    /// Retreives the settings of each of the providers.
+    fn settings(&self) -> [(OauthProvider, &OauthSettings); 3] {
+        [
+            (OauthProvider::Twitch, &self.twitch),
+            (OauthProvider::Reddit, &self.reddit),
+            (OauthProvider::Twitter, &self.twitter),
+        ]
+    }
+}
+
+/// OauthSettings represents the credentials of the application registered
+/// with an oauth provider.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct OauthSettings {
+    /// The ID of the application
+    client_id: Option<String>,
+
+    /// The secret of the application
+    client_secret: Option<String>,
+
+    /// The URL that the provider redirects users to once they authorize the
+    /// application
+    redirect_url: Option<String>,
+}
+
+impl OauthSettings {
+    /// Builds the credentials described by the settings, if each of them was
+    /// provided.
+    fn credentials(&self) -> Option<OauthCredentials> {
+        match (&self.client_id, &self.client_secret, &self.redirect_url) {
+            (Some(client_id), Some(client_secret), Some(redirect_url)) => {
+                Some(OauthCredentials::new(
+                    client_id.clone(),
+                    client_secret.clone(),
+                    redirect_url.clone(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Determines whether or not only some of the credentials were provided.
+    fn partial(&self) -> bool {
+        let provided = [&self.client_id, &self.client_secret, &self.redirect_url]
+            .iter()
+            .filter(|setting| setting.is_some())
+            .count();
+
+        provided > 0 && provided < 3
+    }
+}
+
+/// Env represents the environment variables overriding the settings of a
+/// configuration.
+struct Env<'a> {
+    /// Obtains the value of the environment variable with the given name
+    vars: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl<'a> Env<'a> {
+    /// Overrides the given setting with the value of the given environment
+    /// variable, if it is set, as parsed by the given function.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    /// * `setting` - The setting that should be overridden
+    /// * `parse` - The function parsing the value of the variable
+    fn with<T, E: fmt::Display>(
+        &self,
+        name: &str,
+        setting: &mut T,
+        parse: impl FnOnce(&str) -> Result<T, E>,
+    ) -> Result<(), ConfigError> {
+        if let Some(value) = (self.vars)(name) {
+            *setting = parse(value.trim()).map_err(|e| ConfigError::InvalidVariable {
+                name: name.to_owned(),
+                reason: e.to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Overrides the given setting with the parsed value of the given
+    /// environment variable, if it is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    /// * `setting` - The setting that should be overridden
+    fn parse<T: FromStr>(&self, name: &str, setting: &mut T) -> Result<(), ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.with(name, setting, str::parse)
+    }
+
+    /// Overrides the given optional setting with the parsed value of the
+    /// given environment variable, if it is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    /// * `setting` - The setting that should be overridden
+    fn parse_some<T: FromStr>(&self, name: &str, setting: &mut Option<T>) -> Result<(), ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.with(name, setting, |value| value.parse().map(Some))
+    }
+
+    /// Overrides the given toggle with the value of the given environment
+    /// variable, if it is set, which must be either "true" or "false".
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    /// * `setting` - The toggle that should be overridden
+    fn flag(&self, name: &str, setting: &mut bool) -> Result<(), ConfigError> {
+        self.with(name, setting, |value| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err("expected either true or false"),
+        })
+    }
+
+    /// Overrides the given list with the comma-separated items of the given
+    /// environment variable, if it is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable
+    /// * `setting` - The list that should be overridden
+    fn list<T: FromStr>(&self, name: &str, setting: &mut Vec<T>) -> Result<(), ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.with(name, setting, |value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::parse)
+                .collect()
+        })
+    }
+}
+
+impl Config {
+    /// Loads the configuration of the server: the file named by CONFIG_FILE
+    /// (or gnomegg.toml, if it exists) is read, each of its settings is
+    /// overridden by the matching environment variable, if any, and the
+    /// resulting configuration is validated.
+    pub fn load() -> Result<Self, ConfigError> {
+        let config = match env::var(CONFIG_FILE_VARIABLE) {
+            Ok(path) => Self::from_file(path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            Err(_) => Self::default(),
+        }
+        .with_env(|name| env::var(name).ok())?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Reads the configuration from the TOML file at the given path. Any
+    /// setting missing from the file is left to its default.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the configuration file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+
+        toml::from_str(&contents).map_err(|error| ConfigError::Malformed {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    /// Consumes the configuration, and overrides each of its settings with
+    /// the matching environment variable, if it is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `vars` - Obtains the value of the environment variable with the
+    /// given name, if it is set
+    pub fn with_env(mut self, vars: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let env = Env { vars: &vars };
+
+        let server = &mut self.server;
+        env.parse("BIND_ADDRESS", &mut server.bind_address)?;
+        env.parse_some("TLS_CERT_PATH", &mut server.tls.cert_path)?;
+        env.parse_some("TLS_KEY_PATH", &mut server.tls.key_path)?;
+        env.parse("ADMIN_TOKEN", &mut server.admin_token)?;
+        env.list("TRUSTED_PROXIES", &mut server.trusted_proxies)?;
+        env.parse("AVATAR_DIR", &mut server.avatar_dir)?;
+        env.parse_some("JWT_KEYS_DIR", &mut server.jwt_keys_dir)?;
+        env.parse_some("ENCRYPTION_KEYS", &mut server.encryption_keys)?;
+        env.parse("LOG_FORMAT", &mut server.log_format)?;
+        env.parse_some("OTLP_ENDPOINT", &mut server.otlp_endpoint)?;
+        env.parse_some("STREAM_WEBHOOK_SECRET", &mut server.stream_webhook_secret)?;
+        env.flag("MIGRATE_ON_STARTUP", &mut server.migrate_on_startup)?;
+        env.flag("WARM_CACHE_ON_STARTUP", &mut server.warm_cache_on_startup)?;
+        env.flag("COMPRESS_RESPONSES", &mut server.compress_responses)?;
+        env.parse("COMPRESSION_MIN_BYTES", &mut server.compression_min_bytes)?;
+        env.parse_some("FRONTEND_DIR", &mut server.frontend_dir)?;
+
+        env.parse("REDIS_URL", &mut self.redis.url)?;
+        env.parse("REDIS_KEY_PREFIX", &mut self.redis.key_prefix)?;
+        self.redis.pool.apply_env(&env, "REDIS")?;
+
+        let database = &mut self.database;
+        env.parse_some("DATABASE_URL", &mut database.url)?;
+        database.pool.apply_env(&env, "DATABASE")?;
+        env.parse_some("DATABASE_REPLICA_URL", &mut database.replica.url)?;
+        database.replica.pool.apply_env(&env, "DATABASE_REPLICA")?;
+
+        let chat = &mut self.chat;
+        env.list("DEFAULT_ROLES", &mut chat.default_roles)?;
+        env.parse_some("MIN_ACCOUNT_AGE_HOURS", &mut chat.min_account_age_hours)?;
+        env.parse_some("LINK_BYPASS_ROLE", &mut chat.link_bypass_role)?;
+        env.parse_some("LINK_MUTE_MINUTES", &mut chat.link_mute_minutes)?;
+        env.parse_some("PIN_TTL_MINUTES", &mut chat.pin_ttl_minutes)?;
+        env.with(
+            "FIRST_MESSAGE_WINDOW",
+            &mut chat.first_message_window,
+            parse_window,
+        )?;
+        env.parse("EDIT_WINDOW_SECONDS", &mut chat.edit_window_seconds)?;
+        env.parse("MAX_COMBINING_MARKS", &mut chat.max_combining_marks)?;
+        env.parse("MAX_MESSAGE_CHARS", &mut chat.max_message_chars)?;
+        env.parse("MAX_MESSAGE_BYTES", &mut chat.max_message_bytes)?;
+        env.parse_some("LIVE_ANNOUNCEMENT", &mut chat.live_announcement)?;
+
+        let rate_limits = &mut self.rate_limits;
+        env.parse(
+            "SLOWMODE_INTERVAL_SECONDS",
+            &mut rate_limits.slowmode_interval_seconds,
+        )?;
+        env.with(
+            "SLOWMODE_EXEMPT_ROLE",
+            &mut rate_limits.slowmode_exempt_role,
+            parse_exempt_role,
+        )?;
+        env.list("SPAM_ESCALATION", &mut rate_limits.spam_escalation)?;
+
+        let features = &mut self.features;
+        env.flag(
+            "REQUIRE_VERIFIED_EMAIL",
+            &mut features.require_verified_email,
+        )?;
+        env.flag("FILTER_LINKS", &mut features.filter_links)?;
+        env.flag("SAFE_MODE", &mut features.safe_mode)?;
+        env.flag("STRIP_CONTROL_CHARS", &mut features.strip_control_chars)?;
+        env.flag("COLLAPSE_WHITESPACE", &mut features.collapse_whitespace)?;
+        env.flag("NORMALIZE_UNICODE", &mut features.normalize_unicode)?;
+        env.flag("FOLD_CONFUSABLES", &mut features.fold_confusables)?;
+
+        let resilience = &mut self.resilience;
+        env.parse("MODERATION_LAYERS", &mut resilience.moderation_layers)?;
+        env.parse(
+            "BREAKER_FAILURE_THRESHOLD",
+            &mut resilience.breaker_failure_threshold,
+        )?;
+        env.parse(
+            "BREAKER_COOL_DOWN_SECONDS",
+            &mut resilience.breaker_cool_down_seconds,
+        )?;
+        env.parse("LOCAL_CACHE_SIZE", &mut resilience.local_cache_size)?;
+        env.parse(
+            "LOCAL_CACHE_TTL_SECONDS",
+            &mut resilience.local_cache_ttl_seconds,
+        )?;
+        env.parse("RETRY_MAX_ATTEMPTS", &mut resilience.retry_max_attempts)?;
+        env.parse("RETRY_BASE_DELAY_MS", &mut resilience.retry_base_delay_ms)?;
+        env.parse("RETRY_MAX_DELAY_MS", &mut resilience.retry_max_delay_ms)?;
+        env.flag("QUEUE_FAILED_WRITES", &mut resilience.queue_failed_writes)?;
+        env.parse("SHED_MAX_LOOP_LAG_MS", &mut resilience.shed_max_loop_lag_ms)?;
+        env.parse(
+            "SHED_MAX_PROVIDER_LATENCY_MS",
+            &mut resilience.shed_max_provider_latency_ms,
+        )?;
+
+        // Each provider's credentials are overridden by the variables
+        // starting with its name (e.g. TWITCH_CLIENT_ID)
+        for provider in &[
+            OauthProvider::Twitch,
+            OauthProvider::Reddit,
+            OauthProvider::Twitter,
+        ] {
+            let prefix = provider.to_str().to_uppercase();
+            let settings = self.oauth.settings_mut(*provider);
+
+            env.parse_some(&format!("{}_CLIENT_ID", prefix), &mut settings.client_id)?;
+            env.parse_some(
+                &format!("{}_CLIENT_SECRET", prefix),
+                &mut settings.client_secret,
+            )?;
+            env.parse_some(
+                &format!("{}_REDIRECT_URL", prefix),
+                &mut settings.redirect_url,
+            )?;
+        }
+
+        Ok(self)
+    }
+
+    /// Ensures that the configuration may be used to start the server,
+    /// describing the first problem found otherwise.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.database.url.is_none() {
+            return Err(ConfigError::Missing {
+                setting: "database.url",
+                variable: "DATABASE_URL",
+            });
+        }
+
+        match self.server.bind_address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => (),
+            _ => {
+                return Err(ConfigError::Invalid {
+                    setting: "server.bind_address",
+                    reason: format!(
+                        "expected a host and port (e.g. {}), got {:?}",
+                        DEFAULT_BIND_ADDRESS, self.server.bind_address
+                    ),
+                })
+            }
+        }
+
+        match (&self.server.tls.cert_path, &self.server.tls.key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                for path in &[cert_path, key_path] {
+                    if !path.is_file() {
+                        return Err(ConfigError::Invalid {
+                            setting: "server.tls",
+                            reason: format!("{} does not exist", path.display()),
+                        });
+                    }
+                }
+            }
+            (None, None) => (),
+            _ => {
+                return Err(ConfigError::Invalid {
+                    setting: "server.tls",
+                    reason: "both cert_path and key_path must be set to serve over TLS".to_owned(),
+                })
+            }
+        }
+
+        if let Some(announcement) = &self.chat.live_announcement {
+            if announcement.trim().is_empty() || announcement.len() > MAX_ANNOUNCEMENT_LENGTH {
+                return Err(ConfigError::Invalid {
+                    setting: "chat.live_announcement",
+                    reason: format!(
+                        "expected between 1 and {} bytes, got {}",
+                        MAX_ANNOUNCEMENT_LENGTH,
+                        announcement.len()
+                    ),
+                });
+            }
+        }
+
+        self.redis.pool.validate("redis.pool")?;
+        self.database.pool.validate("database.pool")?;
+        self.database
+            .replica
+            .pool
+            .validate("database.replica.pool")?;
+
+        let resilience = &self.resilience;
+        if resilience.retry_base_delay_ms > resilience.retry_max_delay_ms {
+            return Err(ConfigError::Invalid {
+                setting: "resilience.retry_base_delay_ms",
+                reason: format!(
+                    "the base delay ({}ms) exceeds the maximum delay ({}ms)",
+                    resilience.retry_base_delay_ms, resilience.retry_max_delay_ms
+                ),
+            });
+        }
+
+        for (provider, settings) in self.oauth.settings().iter() {
+            if settings.partial() {
+                return Err(ConfigError::Invalid {
+                    setting: "oauth",
+                    reason: format!(
+                        "{} requires client_id, client_secret and redirect_url to be set",
+                        provider.to_str()
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retreives the address that the server should listen on.
+    pub fn bind_address(&self) -> &str {
+        &self.server.bind_address
+    }
+
+    /// Retreives the paths of the certificate chain and private key with
+    /// which the server should serve requests over TLS, if any.
+    pub fn tls(&self) -> Option<(&Path, &Path)> {
+        match (&self.server.tls.cert_path, &self.server.tls.key_path) {
+            (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+            _ => None,
+        }
+    }
+
+    /// Retreives the token granting access to the administrative routes.
+    pub fn admin_token(&self) -> &str {
+        &self.server.admin_token
+    }
+
+    /// Retreives the addresses of the reverse proxies whose forwarded headers
+    /// should be trusted to name the client making a request.
+    pub fn trusted_proxies(&self) -> &[IpAddr] {
+        &self.server.trusted_proxies
+    }
+
+    /// Retreives the directory that avatars should be stored in.
+    pub fn avatar_dir(&self) -> &Path {
+        &self.server.avatar_dir
+    }
+
+    /// Retreives the directory holding the keys that session tokens should
+    /// be signed with, if any.
+    pub fn jwt_keys_dir(&self) -> Option<&Path> {
+        self.server.jwt_keys_dir.as_deref()
+    }
+
+    /// Retreives the keys that sensitive values should be encrypted with at
+    /// rest, if any.
+    pub fn keyring(&self) -> Option<&Keyring> {
+        self.server.encryption_keys.as_ref()
+    }
+
+    /// Retreives the format in which spans and events should be logged.
+    pub fn log_format(&self) -> LogFormat {
+        self.server.log_format
+    }
+
+    /// Retreives the URL of the OTLP/HTTP collector that spans should be
+    /// exported to, if any.
+    pub fn otlp_endpoint(&self) -> Option<&str> {
+        self.server.otlp_endpoint.as_deref()
+    }
+
+    /// Retreives the secret that requests to the stream status webhook must
+    /// be signed with, if the webhook is enabled.
+    pub fn stream_webhook_secret(&self) -> Option<&str> {
+        self.server.stream_webhook_secret.as_deref()
+    }
+
+    /// Retreives the directory holding the chat frontend that should be
+    /// served alongside the API, if any.
+    pub fn frontend_dir(&self) -> Option<&Path> {
+        self.server.frontend_dir.as_deref()
+    }
+
+    /// Retreives the announcement that should be sent to the chat when the
+    /// stream goes live, if any.
+    pub fn live_announcement(&self) -> Option<&str> {
+        self.chat.live_announcement.as_deref()
+    }
+
+    /// Determines whether or not pending migrations should be run before the
+    /// server starts.
+    pub fn migrate_on_startup(&self) -> bool {
+        self.server.migrate_on_startup
+    }
+
+    /// Determines whether or not the active bans, mutes and roles should be
+    /// preloaded into redis before the server starts.
+    pub fn warm_cache_on_startup(&self) -> bool {
+        self.server.warm_cache_on_startup
+    }
+
+    /// Builds the policy determining which responses are compressed.
+    pub fn compression(&self) -> CompressionPolicy {
+        CompressionPolicy::default()
+            .with_enabled(self.server.compress_responses)
+            .with_min_bytes(self.server.compression_min_bytes)
+    }
+
+    /// Retreives the address of the redis caching layer.
+    pub fn redis_url(&self) -> &str {
+        &self.redis.url
+    }
+
+    /// Retreives the prefix of each key written to the redis caching layer.
+    pub fn key_prefix(&self) -> &str {
+        &self.redis.key_prefix
+    }
+
+    /// Builds the configuration of the pool of connections to redis.
+    pub fn redis_pool(&self) -> PoolConfig {
+        self.redis.pool.pool_config()
+    }
+
+    /// Retreives the address of the primary MySQL database. Validated
+    /// configurations always hold one.
+    pub fn database_url(&self) -> &str {
+        self.database.url.as_deref().unwrap_or_default()
+    }
+
+    /// Builds the configuration of the pool of connections to the primary
+    /// MySQL database.
+    pub fn database_pool(&self) -> PoolConfig {
+        self.database.pool.pool_config()
+    }
+
+    /// Retreives the address of the MySQL read replica, if any.
+    pub fn replica_url(&self) -> Option<&str> {
+        self.database.replica.url.as_deref()
+    }
+
+    /// Builds the configuration of the pool of connections to the MySQL read
+    /// replica.
+    pub fn replica_pool(&self) -> PoolConfig {
+        self.database.replica.pool.pool_config()
+    }
+
+    /// Retreives the roles given to each new user.
+    pub fn default_roles(&self) -> &[Role] {
+        &self.chat.default_roles
+    }
+
+    /// Retreives the layers that bans and mutes should be stored in.
+    pub fn moderation_layers(&self) -> Layers {
+        self.resilience.moderation_layers
+    }
+
+    /// Determines whether or not moderation writes should be queued in redis
+    /// while MySQL is down.
+    pub fn queue_failed_writes(&self) -> bool {
+        self.resilience.queue_failed_writes
+    }
+
+    /// Builds the circuit breaker guarding reads from MySQL.
+    pub fn breaker(&self) -> CircuitBreaker {
+        CircuitBreaker::default()
+            .with_failure_threshold(self.resilience.breaker_failure_threshold)
+            .with_cool_down(StdDuration::from_secs(
+                self.resilience.breaker_cool_down_seconds,
+            ))
+    }
+
+    /// Builds the shedder rejecting low-priority requests while the node is
+    /// overloaded.
+    pub fn load_shedder(&self) -> LoadShedder {
+        LoadShedder::default()
+            .with_max_loop_lag(StdDuration::from_millis(
+                self.resilience.shed_max_loop_lag_ms,
+            ))
+            .with_max_provider_latency(StdDuration::from_millis(
+                self.resilience.shed_max_provider_latency_ms,
+            ))
+    }
+
+    /// Builds the policy according to which idempotent operations are
+    /// retried while redis can't be reached.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+            .with_max_attempts(self.resilience.retry_max_attempts)
+            .with_base_delay(StdDuration::from_millis(
+                self.resilience.retry_base_delay_ms,
+            ))
+            .with_max_delay(StdDuration::from_millis(self.resilience.retry_max_delay_ms))
+    }
+
+    /// Builds the in-process cache of active chatters' roles and mutes.
+    pub fn local_cache(&self) -> LocalCache {
+        LocalCache::new(
+            self.resilience.local_cache_size,
+            StdDuration::from_secs(self.resilience.local_cache_ttl_seconds),
+        )
+    }
+
+    /// Builds the dispatcher handling chat commands according to the chat,
+    /// rate limit and feature settings.
+    pub fn dispatcher(&self) -> Dispatcher {
+        let (chat, rate_limits, features) = (&self.chat, &self.rate_limits, &self.features);
+
+        let gates = ChatGates::default()
+            .with_min_account_age(chat.min_account_age_hours.map(Duration::hours))
+            .with_require_verified(features.require_verified_email);
+
+        let link_filter = if features.filter_links {
+            Some(
+                LinkFilter::default()
+                    .with_bypass_role(chat.link_bypass_role)
+                    .with_mute_duration(chat.link_mute_minutes.map(Duration::minutes)),
+            )
+        } else {
+            None
+        };
+
+        let escalation = rate_limits
+            .spam_escalation
+            .iter()
+            .fold(EscalationPolicy::default(), |policy, step| {
+                policy.with_step(step.offenses, Duration::minutes(step.minutes))
+            });
+
+        let slowmode = SlowmodePolicy::default()
+            .with_default_interval(rate_limits.slowmode_interval_seconds)
+            .with_exempt_role(rate_limits.slowmode_exempt_role);
+
+        // A maximum length of 0 lifts the corresponding limit
+        let sanitizer = Sanitizer::default()
+            .with_strip_control(features.strip_control_chars)
+            .with_collapse_whitespace(features.collapse_whitespace)
+            .with_normalize(features.normalize_unicode)
+            .with_fold_confusables(features.fold_confusables)
+            .with_max_combining_marks(Some(chat.max_combining_marks))
+            .with_max_chars(Some(chat.max_message_chars).filter(|chars| *chars > 0))
+            .with_max_bytes(Some(chat.max_message_bytes).filter(|bytes| *bytes > 0));
+
+        Dispatcher::new(gates)
+            .with_link_filter(link_filter)
+            .with_escalation_policy(escalation)
+            .with_slowmode_policy(slowmode)
+            .with_sanitizer(sanitizer)
+            .with_pin_ttl(chat.pin_ttl_minutes.map(Duration::minutes))
+            .with_first_message_window(chat.first_message_window)
+            .with_safe_mode(features.safe_mode)
+            .with_edit_window(Duration::seconds(chat.edit_window_seconds))
+    }
+
+    /// Builds the credentials of each of the enabled oauth providers.
+    pub fn oauth_providers(&self) -> Vec<(OauthProvider, OauthCredentials)> {
+        self.oauth
+            .settings()
+            .iter()
+            .filter_map(|(provider, settings)| {
+                settings
+                    .credentials()
+                    .map(|credentials| (*provider, credentials))
+            })
+            .collect()
+    }
+}
+
+/// Parses a first message window: the name of a window (e.g. "hour"), or
+/// "ever".
+///
+/// # Arguments
+///
+/// * `value` - The value that should be parsed
+fn parse_window(value: &str) -> Result<Option<Window>, String> {
+    if value == "ever" {
+        return Ok(None);
+    }
+
+    Window::ALL
+        .iter()
+        .copied()
+        .find(|window| window.name() == value)
+        .map(Some)
+        .ok_or_else(|| format!("expected minute, hour, day or ever, got {:?}", value))
+}
+
+/// Parses the role exempt from slowmode: the name of a role, or either
+/// "none" or an empty value if no role is exempt.
+///
+/// # Arguments
+///
+/// * `value` - The value that should be parsed
+fn parse_exempt_role(value: &str) -> Result<Option<Role>, String> {
+    match value {
+        "" | "none" => Ok(None),
+        role => role.parse().map(Some).map_err(|e| format!("{}", e)),
+    }
+}
+
+/// Deserializes an optional setting from a string, using the setting's
+/// FromStr implementation.
+fn deserialize_parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.trim().parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Deserializes a first message window from its name, or "ever".
+fn deserialize_window<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Window>, D::Error> {
+    parse_window(String::deserialize(deserializer)?.trim()).map_err(de::Error::custom)
+}
+
+/// Deserializes the role exempt from slowmode from its name, or "none".
+fn deserialize_exempt_role<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Role>, D::Error> {
+    parse_exempt_role(String::deserialize(deserializer)?.trim()).map_err(de::Error::custom)
+}
+
+/// Deserializes the layers backing a provider from their name.
+fn deserialize_layers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Layers, D::Error> {
+    String::deserialize(deserializer)?
+        .trim()
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Builds a lookup of the given environment variables.
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_layering() -> Result<(), ConfigError> {
+        let config = toml::from_str::<Config>(
+            r#"
+            [server]
+            bind_address = "0.0.0.0:80"
+
+            [database]
+            url = "mysql://file"
+
+            [database.pool]
+            size = 20
+
+            [rate_limits]
+            slowmode_exempt_role = "none"
+
+            [[rate_limits.spam_escalation]]
+            offenses = 3
+            minutes = 5
+            "#,
+        )
+        .unwrap()
+        .with_env(vars(&[
+            ("DATABASE_URL", "mysql://env"),
+            ("DATABASE_POOL_MIN_IDLE", "4"),
+            ("DEFAULT_ROLES", "subscriber, vip"),
+            ("FILTER_LINKS", "true"),
+        ]))?;
+        config.validate()?;
+
+        // Settings missing from both sources are left to their defaults
+        assert_eq!(config.redis_url(), DEFAULT_REDIS_URL);
+        assert!(config.migrate_on_startup());
+
+        // Settings only found in the file are kept, and settings found in the
+        // environment override the file
+        assert_eq!(config.bind_address(), "0.0.0.0:80");
+        assert_eq!(config.database_url(), "mysql://env");
+        assert_eq!(config.database.pool.size, Some(20));
+        assert_eq!(config.database.pool.min_idle, Some(4));
+        assert_eq!(config.default_roles(), &[Role::Subscriber, Role::VIP]);
+        assert_eq!(config.rate_limits.slowmode_exempt_role, None);
+        assert_eq!(
+            config.rate_limits.spam_escalation,
+            vec![EscalationStep {
+                offenses: 3,
+                minutes: 5
+            }]
+        );
+        assert!(config.features.filter_links);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_example() -> Result<(), ConfigError> {
+        let config = toml::from_str::<Config>(include_str!("../../gnomegg.example.toml")).map_err(
+            |error| ConfigError::Malformed {
+                path: "gnomegg.example.toml".into(),
+                error,
+            },
+        )?;
+        config.validate()?;
+
+        assert_eq!(config.bind_address(), DEFAULT_BIND_ADDRESS);
+        assert!(config.oauth_providers().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = |pairs: &[(&str, &str)]| {
+            Config::default()
+                .with_env(vars(pairs))
+                .and_then(|config| config.validate())
+                .unwrap_err()
+        };
+
+        match invalid(&[]) {
+            ConfigError::Missing { variable, .. } => assert_eq!(variable, "DATABASE_URL"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        match invalid(&[("DATABASE_URL", "mysql://"), ("SAFE_MODE", "yes")]) {
+            ConfigError::InvalidVariable { name, .. } => assert_eq!(name, "SAFE_MODE"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        match invalid(&[("DATABASE_URL", "mysql://"), ("LOG_FORMAT", "xml")]) {
+            ConfigError::InvalidVariable { name, .. } => assert_eq!(name, "LOG_FORMAT"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        for pairs in &[
+            &[("BIND_ADDRESS", "localhost")][..],
+            &[("TLS_CERT_PATH", "cert.pem")][..],
+            &[("REDIS_POOL_SIZE", "2"), ("REDIS_POOL_MIN_IDLE", "3")][..],
+            &[("TWITCH_CLIENT_ID", "id")][..],
+        ] {
+            let pairs = [&[("DATABASE_URL", "mysql://")][..], pairs].concat();
+
+            match invalid(&pairs) {
+                ConfigError::Invalid { .. } => (),
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,2231 @@
+use chrono::{DateTime, Duration, Utc};
+use redis::Connection as RedisConnection;
+use tracing::instrument;
+
+use super::{
+    super::spec::{
+        approval::{HeldMessage, NewHeldMessage},
+        backend::PersistentConnection,
+        bot_command::{BotInvocation, CommandHandler, BOT_USERNAME},
+        embed::linked_embeds,
+        emote::Emote,
+        event::{
+            Broadcast, Combo, Command, CommandKind, DeleteMessage, EditMessage, EmoteSpan, Error,
+            Event, EventKind, EventTarget, Handshake, Highlight, PinMessage, PollVotes, Pong,
+            Slowmode, StartPoll, Subonly, UnpinMessage, Vote,
+        },
+        history::NewChatMessage,
+        pin::NewPinnedMessage,
+        poll::{is_valid_poll, vote_weight, NewPoll},
+        stats::Window,
+        user::{is_valid_username, Role, User, NEW_ACCOUNT_HOURS},
+        whisper::NewWhisper,
+    },
+    auth::{
+        capability::{CanDeleteMessages, CanManagePolls, CanPinMessages, CanSetChatModes},
+        Capability,
+    },
+    breaker::CircuitBreaker,
+    invalidation::LocalCache,
+    lookups::CacheLookups,
+    modules::{
+        approvals::Provider as ApprovalsProvider,
+        bot_commands::{self, BuiltinHandler, Provider as BotCommandsProvider},
+        chat_modes::Provider as ChatModesProvider,
+        combos::Provider as CombosProvider,
+        embeds::Provider as EmbedsProvider,
+        emotes::Provider as EmotesProvider,
+        history::Provider as HistoryProvider,
+        ignores::Provider as IgnoresProvider,
+        last_seen::Provider as LastSeenProvider,
+        links::{is_valid_domain, Provider as LinksProvider},
+        mutes::Provider as MutesProvider,
+        name_resolver::Provider as NameResolver,
+        pins::Provider as PinsProvider,
+        polls::Provider as PollsProvider,
+        profiles,
+        roles::Provider as RolesProvider,
+        snapshot::Provider as SnapshotProvider,
+        spam::{Fingerprint, Provider as SpamProvider},
+        stats::Provider as StatsProvider,
+        subscriptions::Provider as SubscriptionsProvider,
+        users::Provider as UsersProvider,
+        whispers::Provider as WhispersProvider,
+        Cache, Hybrid, Persistent, ProviderError,
+    },
+    outbox::Outbox,
+    sanitizer::{SanitizeError, Sanitizer},
+};
+
+use std::{borrow::Cow, collections::HashMap, error, fmt, iter, sync::Arc};
+
+/// The number of seconds that chatters must wait between messages in
+/// slowmode, unless the Slowmode command specifies otherwise.
+pub const DEFAULT_SLOWMODE_INTERVAL: u64 = 5;
+
+/// The maximum number of words in a message that are looked up as possible
+/// mentions.
+pub const MAX_MENTION_CANDIDATES: usize = 32;
+
+/// The number of consecutive messages consisting only of the same emote
+/// after which combo events are sent.
+pub const MIN_COMBO_LENGTH: u64 = 2;
+
+/// The number of seconds after sending a message within which its sender
+/// may edit it, unless the dispatcher specifies otherwise.
+pub const DEFAULT_EDIT_WINDOW_SECONDS: i64 = 60;
+
+/// DispatchError represents any reason for which a command issued by a
+/// chatter could not be carried out.
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The issuer of the command is not a registered user
+    UnknownIssuer,
+
+    /// The recipient of a whisper is not a registered user
+    UnknownRecipient,
+
+    /// The issuer's account is younger than the minimum account age
+    AccountTooNew,
+
+    /// The issuer's account does not have a verified email
+    UnverifiedAccount,
+
+    /// The message has no visible contents once sanitized
+    EmptyMessage,
+
+    /// The message exceeds the maximum length once sanitized
+    MessageTooLong,
+
+    /// The issuer is muted
+    Muted,
+
+    /// The message links to a domain that isn't whitelisted
+    LinkNotAllowed,
+
+    /// The message duplicates, or is highly similar to, one of the issuer's
+    /// recent messages
+    Duplicate,
+
+    /// The issuer sent a message too recently while the chat is in slowmode
+    Slowmode {
+        /// The number of seconds until the issuer may send another message
+        retry_after: u64,
+    },
+
+    /// The issuer doesn't hold a role permitting them to issue the command
+    Forbidden,
+
+    /// The issuer isn't a subscriber, and the chat is in subonly mode
+    Subonly,
+
+    /// A poll is already active
+    PollActive,
+
+    /// No poll is active, or the vote was cast in a poll that has ended
+    NoActivePoll,
+
+    /// The poll's question or options are empty or too long, or there are
+    /// too few or too many options
+    InvalidPoll,
+
+    /// The vote is for an option that the poll doesn't have
+    InvalidVote,
+
+    /// The issuer already voted in the active poll
+    AlreadyVoted,
+
+    /// No message is pinned
+    NoPinnedMessage,
+
+    /// The message doesn't exist, or was already deleted
+    UnknownMessage,
+
+    /// The message was sent too long ago to be edited
+    EditWindowElapsed,
+
+    /// The message uses an emote restricted to a subscription tier that the
+    /// issuer doesn't hold
+    RestrictedEmote,
+
+    /// The command is not yet supported by the dispatcher
+    UnsupportedCommand,
+
+    /// A backend failed while handling the command
+    ProviderError(ProviderError),
+}
+
+impl DispatchError {
+    /// Gets the message that should be sent to the issuer of the command in
+    /// an error event.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::UnknownIssuer => "unknownissuer",
+            Self::UnknownRecipient => "unknownrecipient",
+            Self::AccountTooNew => "accounttoonew",
+            Self::UnverifiedAccount => "unverifiedaccount",
+            Self::EmptyMessage => "emptymessage",
+            Self::MessageTooLong => "messagetoolong",
+            Self::Muted => "muted",
+            Self::LinkNotAllowed => "linknotallowed",
+            Self::Duplicate => "duplicate",
+            Self::Slowmode { .. } => "slowmode",
+            Self::Forbidden => "forbidden",
+            Self::Subonly => "subonly",
+            Self::PollActive => "pollactive",
+            Self::NoActivePoll => "nopoll",
+            Self::InvalidPoll => "invalidpoll",
+            Self::InvalidVote => "invalidvote",
+            Self::AlreadyVoted => "alreadyvoted",
+            Self::NoPinnedMessage => "nopin",
+            Self::UnknownMessage => "unknownmessage",
+            Self::EditWindowElapsed => "editwindowelapsed",
+            Self::RestrictedEmote => "restrictedemote",
+            Self::UnsupportedCommand => "unsupportedcommand",
+            Self::ProviderError(_) => "internalerror",
+        }
+    }
+
+    /// Gets the number of seconds after which the command may be retried, if
+    /// it was rejected for being too early.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::Slowmode { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownIssuer => write!(f, "the issuer of the command is not registered"),
+            Self::UnknownRecipient => write!(f, "the recipient of the whisper is not registered"),
+            Self::AccountTooNew => write!(f, "the issuer's account is too new to chat"),
+            Self::UnverifiedAccount => write!(f, "the issuer's account has no verified email"),
+            Self::EmptyMessage => write!(f, "the message has no visible contents"),
+            Self::MessageTooLong => write!(f, "the message exceeds the maximum length"),
+            Self::Muted => write!(f, "the issuer is muted"),
+            Self::LinkNotAllowed => {
+                write!(f, "the message links to a domain that isn't whitelisted")
+            }
+            Self::Duplicate => write!(f, "the message repeats one of the issuer's recent messages"),
+            Self::Slowmode { retry_after } => write!(
+                f,
+                "the issuer may not send another message for {} seconds",
+                retry_after
+            ),
+            Self::Forbidden => write!(f, "the issuer may not issue the command"),
+            Self::Subonly => write!(f, "the chat is open only to subscribers"),
+            Self::PollActive => write!(f, "a poll is already active"),
+            Self::NoActivePoll => write!(f, "no poll is active"),
+            Self::InvalidPoll => write!(f, "the poll is malformed"),
+            Self::InvalidVote => write!(f, "the poll has no such option"),
+            Self::AlreadyVoted => write!(f, "the issuer already voted in the poll"),
+            Self::NoPinnedMessage => write!(f, "no message is pinned"),
+            Self::UnknownMessage => write!(f, "the message doesn't exist or was deleted"),
+            Self::EditWindowElapsed => write!(f, "the message was sent too long ago to edit"),
+            Self::RestrictedEmote => write!(f, "the issuer may not use an emote in the message"),
+            Self::UnsupportedCommand => write!(f, "the command is not supported"),
+            Self::ProviderError(e) => write!(f, "the command could not be handled: {}", e),
+        }
+    }
+}
+
+impl error::Error for DispatchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::ProviderError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SanitizeError> for DispatchError {
+    /// Constructs a dispatch error from the given sanitization error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The reason for which the message could not be sanitized
+    fn from(e: SanitizeError) -> Self {
+        match e {
+            SanitizeError::EmptyMessage => Self::EmptyMessage,
+            SanitizeError::MessageTooLong => Self::MessageTooLong,
+        }
+    }
+}
+
+impl From<ProviderError> for DispatchError {
+    /// Constructs a dispatch error from the given provider error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The provider error that should be wrapped in the DispatchError
+    fn from(e: ProviderError) -> Self {
+        Self::ProviderError(e)
+    }
+}
+
+/// ChatGates represents the requirements that an account must satisfy before
+/// it may send messages. By default, every account may chat.
+#[derive(Clone, Debug, Default)]
+pub struct ChatGates {
+    /// (optional) The minimum age of an account that may chat
+    min_account_age: Option<Duration>,
+
+    /// Whether or not accounts must have a verified email in order to chat
+    require_verified: bool,
+}
+
+impl ChatGates {
+    /// Consumes the gates, and modifies them according to the provided
+    /// minimum account age.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_account_age` - (optional) The minimum age of an account that may
+    /// chat
+    pub fn with_min_account_age(mut self, min_account_age: Option<Duration>) -> Self {
+        self.min_account_age = min_account_age;
+
+        self
+    }
+
+    /// Consumes the gates, and modifies them according to the provided
+    /// verification requirement.
+    ///
+    /// # Arguments
+    ///
+    /// * `require_verified` - Whether or not accounts must have a verified
+    /// email in order to chat
+    pub fn with_require_verified(mut self, require_verified: bool) -> Self {
+        self.require_verified = require_verified;
+
+        self
+    }
+
+    /// Ensures that the given user is permitted to send messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user attempting to send a message
+    pub fn check(&self, user: &User) -> Result<(), DispatchError> {
+        if self.require_verified && !user.verified() {
+            return Err(DispatchError::UnverifiedAccount);
+        }
+
+        match self.min_account_age {
+            Some(min_age) if user.is_new_account(min_age) => Err(DispatchError::AccountTooNew),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// LinkFilter represents the restrictions placed on links to domains that
+/// aren't whitelisted. By default, no chatter may post such links, and the
+/// offending messages are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct LinkFilter {
+    /// (optional) The lowest role whose holders may link to any domain
+    bypass_role: Option<Role>,
+
+    /// (optional) The duration for which chatters linking to a domain that
+    /// isn't whitelisted are muted
+    mute_duration: Option<Duration>,
+}
+
+impl LinkFilter {
+    /// Consumes the filter, and modifies it according to the provided bypass
+    /// role.
+    ///
+    /// # Arguments
+    ///
+    /// * `bypass_role` - (optional) The lowest role whose holders may link to
+    /// any domain
+    pub fn with_bypass_role(mut self, bypass_role: Option<Role>) -> Self {
+        self.bypass_role = bypass_role;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it according to the provided mute
+    /// duration.
+    ///
+    /// # Arguments
+    ///
+    /// * `mute_duration` - (optional) The duration for which offending
+    /// chatters should be muted, rather than only having their message dropped
+    pub fn with_mute_duration(mut self, mute_duration: Option<Duration>) -> Self {
+        self.mute_duration = mute_duration;
+
+        self
+    }
+
+    /// Determines whether or not a holder of the given roles may link to any
+    /// domain.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles held by the chatter
+    pub fn bypassed_by(&self, roles: &[Role]) -> bool {
+        self.bypass_role
+            .is_some_and(|bypass_role| roles.iter().any(|role| role.rank() >= bypass_role.rank()))
+    }
+}
+
+/// SlowmodePolicy represents the server's slowmode settings. By default,
+/// chatters must wait DEFAULT_SLOWMODE_INTERVAL seconds between messages,
+/// and moderators are exempt.
+#[derive(Clone, Debug)]
+pub struct SlowmodePolicy {
+    /// The number of seconds that chatters must wait between messages, unless
+    /// the Slowmode command specifies otherwise
+    default_interval: u64,
+
+    /// (optional) The lowest role whose holders are exempt from slowmode
+    exempt_role: Option<Role>,
+}
+
+impl Default for SlowmodePolicy {
+    fn default() -> Self {
+        Self {
+            default_interval: DEFAULT_SLOWMODE_INTERVAL,
+            exempt_role: Some(Role::Moderator),
+        }
+    }
+}
+
+impl SlowmodePolicy {
+    /// Consumes the policy, and modifies it according to the provided
+    /// default interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `default_interval` - The number of seconds that chatters must wait
+    /// between messages, unless the Slowmode command specifies otherwise
+    pub fn with_default_interval(mut self, default_interval: u64) -> Self {
+        self.default_interval = default_interval;
+
+        self
+    }
+
+    /// Consumes the policy, and modifies it according to the provided exempt
+    /// role.
+    ///
+    /// # Arguments
+    ///
+    /// * `exempt_role` - (optional) The lowest role whose holders are exempt
+    /// from slowmode
+    pub fn with_exempt_role(mut self, exempt_role: Option<Role>) -> Self {
+        self.exempt_role = exempt_role;
+
+        self
+    }
+
+    /// Determines whether or not a holder of the given roles is exempt from
+    /// slowmode.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles held by the chatter
+    pub fn exempts(&self, roles: &[Role]) -> bool {
+        self.exempt_role
+            .is_some_and(|exempt_role| roles.iter().any(|role| role.rank() >= exempt_role.rank()))
+    }
+}
+
+/// EscalationPolicy represents the mutes issued to chatters who repeatedly
+/// send spam. By default, offenders only have their messages rejected.
+#[derive(Clone, Debug, Default)]
+pub struct EscalationPolicy {
+    /// The number of offenses after which offenders are muted, alongside the
+    /// duration of the mute, in ascending order of offenses
+    steps: Vec<(u64, Duration)>,
+}
+
+impl EscalationPolicy {
+    /// Consumes the policy, and mutes chatters for the given duration once
+    /// they have committed the given number of offenses, until they reach
+    /// the next step.
+    ///
+    /// # Arguments
+    ///
+    /// * `offenses` - The number of offenses after which offenders should be
+    /// muted
+    /// * `mute_duration` - The duration for which offenders should be muted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Duration;
+    /// use gnomegg::ws_http_server::dispatcher::EscalationPolicy;
+    ///
+    /// let policy = EscalationPolicy::default()
+    ///     .with_step(3, Duration::minutes(5))
+    ///     .with_step(5, Duration::hours(1));
+    /// assert_eq!(policy.mute_duration(4), Some(Duration::minutes(5)));
+    /// ```
+    pub fn with_step(mut self, offenses: u64, mute_duration: Duration) -> Self {
+        self.steps.push((offenses, mute_duration));
+        self.steps.sort_by_key(|(offenses, _)| *offenses);
+
+        self
+    }
+
+    /// Gets the duration for which a chatter who has committed the given
+    /// number of offenses should be muted, if they should be muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `offenses` - The number of offenses committed by the chatter
+    pub fn mute_duration(&self, offenses: u64) -> Option<Duration> {
+        self.steps
+            .iter()
+            .rev()
+            .find(|(threshold, _)| offenses >= *threshold)
+            .map(|(_, mute_duration)| *mute_duration)
+    }
+}
+
+/// Dispatcher turns commands issued by chatters into the events that should
+/// be delivered to each client.
+#[derive(Clone, Debug, Default)]
+pub struct Dispatcher {
+    /// The requirements that an account must satisfy in order to chat
+    gates: ChatGates,
+
+    /// (optional) The restrictions placed on links in messages
+    links: Option<LinkFilter>,
+
+    /// The mutes issued to chatters who repeatedly send spam
+    escalation: EscalationPolicy,
+
+    /// The server's slowmode settings
+    slowmode: SlowmodePolicy,
+
+    /// The stage through which each message passes before it is sent
+    sanitizer: Sanitizer,
+
+    /// (optional) The amount of time for which messages remain pinned, unless
+    /// the PinMessage command specifies otherwise
+    pin_ttl: Option<Duration>,
+
+    /// (optional) The window within which a chatter must not have sent any
+    /// public messages for their message to be considered their first. By
+    /// default, only a chatter's first message ever is.
+    first_message_window: Option<Window>,
+
+    /// Whether or not first messages are held until a moderator approves
+    /// them
+    safe_mode: bool,
+
+    /// The amount of time after sending a message within which its sender
+    /// may edit it
+    edit_window: Duration,
+
+    /// The handlers implemented by the server that may answer bot commands,
+    /// keyed by the names under which they are registered
+    builtins: HashMap<String, BuiltinHandler>,
+
+    /// (optional) The breaker keeping reads from reaching the persistent
+    /// layer while it is failing
+    breaker: Option<Arc<CircuitBreaker>>,
+
+    /// The prefix prepended to each redis key and channel used by the
+    /// deployment
+    key_prefix: String,
+
+    /// (optional) The copies of frequently read values kept by this node
+    local_cache: Option<Arc<LocalCache>>,
+
+    /// (optional) The outbox holding the moderation writes that couldn't be
+    /// persisted while the persistent layer was down
+    outbox: Option<Arc<dyn Outbox>>,
+
+    /// (optional) The counts of the hits, misses, and errors of the reads
+    /// sent to the caching layer
+    lookups: Option<Arc<CacheLookups>>,
+}
+
+impl Dispatcher {
+    /// Creates a new dispatcher enforcing the given chat gates.
+    ///
+    /// # Arguments
+    ///
+    /// * `gates` - The requirements that an account must satisfy in order to
+    /// chat
+    pub fn new(gates: ChatGates) -> Self {
+        Self {
+            gates,
+            links: None,
+            escalation: EscalationPolicy::default(),
+            slowmode: SlowmodePolicy::default(),
+            sanitizer: Sanitizer::default(),
+            pin_ttl: None,
+            first_message_window: None,
+            safe_mode: false,
+            edit_window: Duration::seconds(DEFAULT_EDIT_WINDOW_SECONDS),
+            builtins: bot_commands::default_builtins()
+                .into_iter()
+                .map(|(name, handler)| (name.to_owned(), handler))
+                .collect(),
+            breaker: None,
+            key_prefix: String::new(),
+            local_cache: None,
+            outbox: None,
+            lookups: None,
+        }
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// link filter. Without a link filter, messages may link to any domain.
+    ///
+    /// # Arguments
+    ///
+    /// * `links` - (optional) The restrictions placed on links in messages
+    pub fn with_link_filter(mut self, links: Option<LinkFilter>) -> Self {
+        self.links = links;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// escalation policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `escalation` - The mutes issued to chatters who repeatedly send spam
+    pub fn with_escalation_policy(mut self, escalation: EscalationPolicy) -> Self {
+        self.escalation = escalation;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// slowmode policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `slowmode` - The server's slowmode settings
+    pub fn with_slowmode_policy(mut self, slowmode: SlowmodePolicy) -> Self {
+        self.slowmode = slowmode;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// sanitizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `sanitizer` - The stage through which each message passes before it
+    /// is sent
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = sanitizer;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// default pin TTL. Without a default TTL, messages remain pinned until
+    /// they are unpinned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin_ttl` - (optional) The amount of time for which messages remain
+    /// pinned
+    pub fn with_pin_ttl(mut self, pin_ttl: Option<Duration>) -> Self {
+        self.pin_ttl = pin_ttl;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// first message window. Without a window, only a chatter's first
+    /// message ever is flagged as their first.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_message_window` - (optional) The window within which a
+    /// chatter must not have sent any public messages for their message to be
+    /// flagged as their first
+    pub fn with_first_message_window(mut self, first_message_window: Option<Window>) -> Self {
+        self.first_message_window = first_message_window;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to whether or not
+    /// the chat is in safe mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `safe_mode` - Whether or not first messages should be held until a
+    /// moderator approves them
+    pub fn with_safe_mode(mut self, safe_mode: bool) -> Self {
+        self.safe_mode = safe_mode;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// edit window.
+    ///
+    /// # Arguments
+    ///
+    /// * `edit_window` - The amount of time after sending a message within
+    /// which its sender may edit it
+    pub fn with_edit_window(mut self, edit_window: Duration) -> Self {
+        self.edit_window = edit_window;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// circuit breaker.
+    ///
+    /// # Arguments
+    ///
+    /// * `breaker` - (optional) The breaker that should keep reads from
+    /// reaching the persistent layer while it is failing
+    pub fn with_breaker(mut self, breaker: Option<Arc<CircuitBreaker>>) -> Self {
+        self.breaker = breaker;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// redis key prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_prefix` - The prefix that should be prepended to each redis key
+    /// and channel used by the deployment
+    pub fn with_key_prefix(mut self, key_prefix: String) -> Self {
+        self.key_prefix = key_prefix;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// local cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `local_cache` - (optional) The copies of frequently read values
+    /// that should be kept by this node. Without a local cache, each value
+    /// is read from redis.
+    pub fn with_local_cache(mut self, local_cache: Option<Arc<LocalCache>>) -> Self {
+        self.local_cache = local_cache;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// outbox.
+    ///
+    /// # Arguments
+    ///
+    /// * `outbox` - (optional) The outbox that moderation writes which can't
+    /// be persisted should be queued in. Without an outbox, moderation
+    /// commands fail while the persistent layer is down.
+    pub fn with_outbox(mut self, outbox: Option<Arc<dyn Outbox>>) -> Self {
+        self.outbox = outbox;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and modifies it according to the provided
+    /// lookup counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookups` - (optional) The counts that the hits, misses, and errors
+    /// of reads sent to the caching layer should be recorded in
+    pub fn with_lookups(mut self, lookups: Option<Arc<CacheLookups>>) -> Self {
+        self.lookups = lookups;
+
+        self
+    }
+
+    /// Consumes the dispatcher, and registers the provided handler under the
+    /// given name, so that bot commands may be answered by it. Any handler
+    /// already registered under the name is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name under which the handler should be registered
+    /// * `handler` - The handler answering each invocation of a command
+    /// registered with its name
+    pub fn with_builtin(mut self, name: &str, handler: BuiltinHandler) -> Self {
+        self.builtins.insert(name.to_owned(), handler);
+
+        self
+    }
+
+    /// Determines whether or not a handler is registered under the given
+    /// name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the handler
+    pub fn has_builtin(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    /// Produces the event sending the given reply to a bot command to the
+    /// entire chat. Replies are sanitized like any other message, and are
+    /// dropped if nothing remains of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` - The message that should be sent in reply
+    pub fn bot_reply(&self, reply: &str) -> Option<Event<'static>> {
+        let reply = self.sanitizer.sanitize(reply).ok()?.into_owned();
+
+        Some(Event::new(
+            EventTarget::All,
+            EventKind::Broadcast(Broadcast::new_owned(BOT_USERNAME, reply)),
+        ))
+    }
+
+    /// Handles the given command, producing each of the events that should be
+    /// delivered as a result. Messages carried by the command are sanitized
+    /// in place before anything else is done with them. Commands that cannot
+    /// be carried out produce an error event addressed to the issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `replica_conn` - (optional) A connection to a read replica of the
+    /// MySQL persistence layer, to which bans, roles and usernames are routed
+    /// * `cmd` - The command that should be handled
+    #[instrument(skip_all, fields(sent_by = cmd.sent_by()))]
+    pub fn dispatch<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        replica_conn: Option<&PersistentConnection>,
+        cmd: &'a mut Command<'a>,
+    ) -> Vec<Event<'a>> {
+        let sanitized = self.sanitize(cmd);
+        let cmd: &'a Command<'a> = cmd;
+
+        sanitized
+            .and_then(|_| self.handle(conn, persistent_conn, replica_conn, cmd))
+            .unwrap_or_else(|e| {
+                let target = EventTarget::User(cmd.sent_by());
+
+                vec![Event::new(
+                    EventTarget::User(cmd.sent_by()),
+                    EventKind::Error(
+                        Error::new(target, e.message()).with_retry_after(e.retry_after()),
+                    ),
+                )]
+            })
+    }
+
+    /// Sanitizes the message carried by the given command, if any, replacing
+    /// its contents if they were modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command whose message should be sanitized
+    fn sanitize(&self, cmd: &mut Command) -> Result<(), DispatchError> {
+        if let Some(msg) = cmd.message_mut() {
+            if let Cow::Owned(sanitized) = self.sanitizer.sanitize(msg.msg())? {
+                msg.set_msg(sanitized);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles the given command, producing each of the events that should
+    /// be delivered as a result.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `replica_conn` - (optional) A connection to a read replica of the
+    /// MySQL persistence layer
+    /// * `cmd` - The command that should be handled
+    fn handle<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        replica_conn: Option<&PersistentConnection>,
+        cmd: &'a Command<'a>,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let (target, contents) = match cmd.command_type() {
+            CommandKind::Message(msg) => (EventTarget::All, msg.msg()),
+            CommandKind::PrivMessage(msg) => (EventTarget::User(msg.to()), msg.contents()),
+            CommandKind::Ping(_) => {
+                return Ok(vec![Event::new(
+                    EventTarget::User(cmd.sent_by()),
+                    EventKind::Pong(Pong::new()),
+                )])
+            }
+            CommandKind::Slowmode(slowmode) => {
+                return self.set_slowmode(conn, persistent_conn, cmd.sent_by(), *slowmode)
+            }
+            CommandKind::Subonly(subonly) => {
+                return self.set_subonly(conn, persistent_conn, cmd.sent_by(), *subonly)
+            }
+            CommandKind::StartPoll(start) => {
+                return self.start_poll(conn, persistent_conn, cmd.sent_by(), start)
+            }
+            CommandKind::Vote(vote) => {
+                return self.vote(conn, persistent_conn, cmd.sent_by(), *vote)
+            }
+            CommandKind::EndPoll(_) => return self.end_poll(conn, persistent_conn, cmd.sent_by()),
+            CommandKind::PinMessage(pin) => {
+                return self.pin_message(conn, persistent_conn, cmd.sent_by(), *pin)
+            }
+            CommandKind::UnpinMessage(_) => {
+                return self.unpin_message(conn, persistent_conn, cmd.sent_by())
+            }
+            CommandKind::DeleteMessage(delete) => {
+                return self.delete_message(conn, persistent_conn, cmd.sent_by(), *delete)
+            }
+            CommandKind::EditMessage(edit) => {
+                return self.edit_message(conn, persistent_conn, cmd.sent_by(), edit)
+            }
+            _ => return Err(DispatchError::UnsupportedCommand),
+        };
+
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn).with_replica(replica_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+
+        let issuer = hybrid
+            .user_id_for(cmd.sent_by())?
+            .map_or(Ok(None), |id| hybrid.get_user(id))?
+            .ok_or(DispatchError::UnknownIssuer)?;
+
+        let roles = self.check_issuer(&mut hybrid, &issuer)?;
+
+        if matches!(target, EventTarget::All)
+            && !may_chat_in_subonly(&roles)
+            && self.subonly(&mut hybrid)?
+        {
+            return Err(DispatchError::Subonly);
+        }
+
+        let spans = self.check_contents(&mut hybrid, issuer.id(), &roles, contents)?;
+
+        // Repeats are checked last, so that only messages which are actually
+        // sent enter the issuer's window of recent messages
+        let fingerprint = Fingerprint::new(contents);
+        self.check_repeat(&mut hybrid, issuer.id(), &fingerprint)?;
+
+        // Slowmode only applies to public messages, and is checked once the
+        // message is otherwise acceptable, so that rejected messages don't
+        // count against the issuer
+        if matches!(target, EventTarget::All) && !self.slowmode.exempts(&roles) {
+            if let Some(interval) = hybrid.slowmode_interval()? {
+                if let Some(retry_after) = hybrid.claim_message_slot(issuer.id(), interval)? {
+                    return Err(DispatchError::Slowmode { retry_after });
+                }
+            }
+        }
+
+        hybrid.record_fingerprint(issuer.id(), &fingerprint)?;
+
+        let now = Utc::now();
+        hybrid.record_message(issuer.id(), now)?;
+
+        // Whispers are archived and queued in case their recipient is
+        // offline. Whispers to a recipient ignoring the issuer are dropped
+        // without being archived, queued, or delivered, and the issuer isn't
+        // told, so that they can't learn that they are being ignored.
+        if let EventTarget::User(recipient) = target {
+            let recipient_id = hybrid
+                .user_id_for(recipient)?
+                .ok_or(DispatchError::UnknownRecipient)?;
+
+            if hybrid.is_ignoring(recipient_id, issuer.id())? {
+                return Ok(Vec::new());
+            }
+
+            hybrid.record_whisper(&NewWhisper::new(
+                issuer.id(),
+                cmd.sent_by(),
+                recipient_id,
+                contents,
+                now,
+            ))?;
+
+            let profile = profiles::profile_for(&mut hybrid, issuer.id())?;
+
+            return Ok(vec![Event::new(
+                target,
+                EventKind::Broadcast(
+                    Broadcast::new(cmd.sent_by(), contents)
+                        .with_new_account(issuer.is_new_account(Duration::hours(NEW_ACCOUNT_HOURS)))
+                        .with_emotes(spans)
+                        .with_profile(profile),
+                ),
+            )]);
+        }
+
+        // In safe mode, first messages are held back until a moderator
+        // approves them, and only the issuer is told
+        if self.safe_mode
+            && !may_skip_approval(&roles)
+            && self.is_first_message(&mut hybrid, issuer.id(), now)?
+        {
+            let held = hybrid.hold_message(&NewHeldMessage::new(
+                issuer.id(),
+                cmd.sent_by(),
+                contents,
+                now,
+            ))?;
+
+            return Ok(vec![Event::new(
+                EventTarget::User(cmd.sent_by()),
+                EventKind::HeldForApproval(held),
+            )]);
+        }
+
+        let mut events = self.broadcast(&mut hybrid, &issuer, cmd.sent_by(), contents, spans)?;
+
+        // Bot commands are answered once the invoking message is broadcast,
+        // so that the reply follows it
+        events.extend(self.invoke_bot_command(
+            &mut hybrid,
+            issuer.id(),
+            cmd.sent_by(),
+            &roles,
+            contents,
+            now,
+        )?);
+
+        Ok(events)
+    }
+
+    /// Answers the bot command invoked by the given message, if any. Commands
+    /// that the sender may not invoke, or that are cooling down, are ignored.
+    /// Commands answered by webhooks are queued, and their replies are
+    /// published once the webhook answers.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers from which the command should be resolved
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `sender` - The username of the user who sent the message
+    /// * `roles` - The roles held by the user who sent the message
+    /// * `contents` - The contents of the message
+    /// * `now` - The time at which the message was sent
+    fn invoke_bot_command<'a>(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        sender_id: u64,
+        sender: &str,
+        roles: &[Role],
+        contents: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Event<'a>>, DispatchError> {
+        let invocation = match BotInvocation::parse(sender_id, sender, contents, now) {
+            Some(invocation) => invocation,
+            None => return Ok(None),
+        };
+
+        let command = match hybrid.bot_command(invocation.command())? {
+            Some(command) if command.may_invoke(roles) => command,
+            _ => return Ok(None),
+        };
+
+        if !hybrid.claim_command_cooldown(&command)? {
+            return Ok(None);
+        }
+
+        match command.handler() {
+            Some(CommandHandler::Builtin(name)) => match self.builtins.get(&name) {
+                Some(handler) => {
+                    Ok(handler(hybrid, &invocation)?.and_then(|reply| self.bot_reply(&reply)))
+                }
+                None => Ok(None),
+            },
+            Some(CommandHandler::Webhook(_)) => {
+                hybrid.queue_invocation(&invocation)?;
+
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Ensures that the given user may currently send messages, according to
+    /// the chat's gates and their mute, returning the roles that they hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to look up the user's mute and roles
+    /// * `issuer` - The user sending a message
+    fn check_issuer(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        issuer: &User,
+    ) -> Result<Vec<Role>, DispatchError> {
+        self.gates.check(issuer)?;
+
+        if self.is_muted(hybrid, issuer.id())? {
+            return Err(DispatchError::Muted);
+        }
+
+        Ok(self.roles_for_user(hybrid, issuer.id())?)
+    }
+
+    /// Ensures that the given message may be sent by its sender, according
+    /// to the link filter and the emotes that the sender is entitled to,
+    /// returning the location of each of the emotes used in the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to check the message
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `roles` - The roles held by the user who sent the message
+    /// * `contents` - The contents of the message
+    fn check_contents<'a>(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        sender_id: u64,
+        roles: &[Role],
+        contents: &'a str,
+    ) -> Result<Vec<EmoteSpan<'a>>, DispatchError> {
+        self.check_links(hybrid, sender_id, roles, contents)?;
+
+        // Tier-gated emotes are rejected rather than stripped, so that the
+        // sender knows that their message wasn't sent as written
+        let emotes = hybrid
+            .emotes()?
+            .into_iter()
+            .map(|emote| (emote.code().to_owned(), emote))
+            .collect::<HashMap<String, Emote>>();
+        let spans = emote_spans(contents, &emotes);
+
+        if let Some(required) = spans
+            .iter()
+            .filter_map(|span| emotes.get(span.code()).and_then(|emote| emote.tier()))
+            .max()
+        {
+            if hybrid
+                .active_tier(sender_id)?
+                .is_none_or(|tier| tier < required)
+            {
+                return Err(DispatchError::RestrictedEmote);
+            }
+        }
+
+        Ok(spans)
+    }
+
+    /// Rejects the message with the given fingerprint if it repeats one of
+    /// its sender's recent messages, muting the sender if they have repeated
+    /// themselves often enough to be escalated.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to check and mute the sender
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `fingerprint` - The fingerprint of the message
+    fn check_repeat(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        sender_id: u64,
+        fingerprint: &Fingerprint,
+    ) -> Result<(), DispatchError> {
+        if !hybrid.is_repeat(sender_id, fingerprint)? {
+            return Ok(());
+        }
+
+        let offenses = hybrid.record_offense(sender_id)?;
+
+        if let Some(duration) = self.escalation.mute_duration(offenses) {
+            hybrid.set_muted(
+                sender_id,
+                true,
+                duration.num_nanoseconds().map(|nanos| nanos as u64),
+            )?;
+        }
+
+        Err(DispatchError::Duplicate)
+    }
+
+    /// Gets the roles held by the user with the given ID, preferring this
+    /// node's local copy.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to look up the roles
+    /// * `user_id` - The ID of the user whose roles should be obtained
+    fn roles_for_user(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        user_id: u64,
+    ) -> Result<Vec<Role>, ProviderError> {
+        let local = match &self.local_cache {
+            Some(local) => local,
+            None => return hybrid.roles_for_user(user_id),
+        };
+
+        if let Some(roles) = local.roles(user_id) {
+            return Ok(roles);
+        }
+
+        let generation = local.generation();
+        let roles = hybrid.roles_for_user(user_id)?;
+        local.store_roles(generation, user_id, &roles);
+
+        Ok(roles)
+    }
+
+    /// Determines whether or not the user with the given ID is currently
+    /// muted, preferring this node's local copy of their most recent mute.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to look up the mute
+    /// * `user_id` - The ID of the user whose mute should be checked
+    fn is_muted(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        user_id: u64,
+    ) -> Result<bool, ProviderError> {
+        let local = match &self.local_cache {
+            Some(local) => local,
+            None => return hybrid.is_muted(user_id),
+        };
+
+        // Mutes are copied rather than their state, as they expire on their
+        // own
+        let mute = match local.mute(user_id) {
+            Some(mute) => mute,
+            None => {
+                let generation = local.generation();
+                let mute = hybrid.get_mute(user_id)?;
+                local.store_mute(generation, user_id, mute.as_ref());
+
+                mute
+            }
+        };
+
+        Ok(mute.is_some_and(|mute| mute.active()))
+    }
+
+    /// Determines whether or not the chat is in subonly mode, preferring
+    /// this node's local copy.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to look up the chat modes
+    fn subonly(&self, hybrid: &mut Hybrid<Cache, Persistent>) -> Result<bool, ProviderError> {
+        let local = match &self.local_cache {
+            Some(local) => local,
+            None => return hybrid.subonly(),
+        };
+
+        if let Some(subonly) = local.subonly() {
+            return Ok(subonly);
+        }
+
+        let generation = local.generation();
+        let subonly = hybrid.subonly()?;
+        local.store_subonly(generation, subonly);
+
+        Ok(subonly)
+    }
+
+    /// Rejects the given message if it links to a domain that isn't
+    /// whitelisted, muting its sender if the link filter says to. Holders of
+    /// the link filter's bypass role may link to any domain.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to check and mute the sender
+    /// * `sender_id` - The ID of the user who sent the message
+    /// * `roles` - The roles held by the user who sent the message
+    /// * `contents` - The contents of the message
+    fn check_links(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        sender_id: u64,
+        roles: &[Role],
+        contents: &str,
+    ) -> Result<(), DispatchError> {
+        let links = match self
+            .links
+            .as_ref()
+            .filter(|links| !links.bypassed_by(roles))
+        {
+            Some(links) => links,
+            None => return Ok(()),
+        };
+
+        for host in link_hosts(contents) {
+            if hybrid.is_whitelisted(&host)? {
+                continue;
+            }
+
+            if let Some(duration) = links.mute_duration {
+                hybrid.set_muted(
+                    sender_id,
+                    true,
+                    duration.num_nanoseconds().map(|nanos| nanos as u64),
+                )?;
+            }
+
+            return Err(DispatchError::LinkNotAllowed);
+        }
+
+        Ok(())
+    }
+
+    /// Broadcasts a message held for approval to the entire chat, as though
+    /// its sender had just sent it. Held messages are released once a
+    /// moderator approves them.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `held` - The message that should be broadcasted
+    pub fn release<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        held: &'a HeldMessage,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+
+        let issuer = hybrid
+            .get_user(held.sender_id())?
+            .ok_or(DispatchError::UnknownIssuer)?;
+
+        let emotes = hybrid
+            .emotes()?
+            .into_iter()
+            .map(|emote| (emote.code().to_owned(), emote))
+            .collect::<HashMap<String, Emote>>();
+        let spans = emote_spans(held.contents(), &emotes);
+
+        self.broadcast(&mut hybrid, &issuer, held.sender(), held.contents(), spans)
+    }
+
+    /// Determines whether or not a public message sent by the user with the
+    /// given ID at the given time would be their first, according to the
+    /// first message window.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers used to count the user's messages
+    /// * `user_id` - The ID of the user sending the message
+    /// * `now` - The time at which the message is sent
+    fn is_first_message(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        user_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<bool, ProviderError> {
+        let count = match self.first_message_window {
+            Some(window) => hybrid.message_count(window, Some(user_id), now)?,
+            None => hybrid.lines_typed(user_id)?,
+        };
+
+        Ok(count == 0)
+    }
+
+    /// Records a public message in the chat history and statistics, and
+    /// produces each of the events that should be delivered as a result of
+    /// it being sent to the entire chat.
+    ///
+    /// # Arguments
+    ///
+    /// * `hybrid` - The providers in which the message should be recorded
+    /// * `issuer` - The user who sent the message
+    /// * `sender` - The username of the user who sent the message
+    /// * `contents` - The contents of the message
+    /// * `spans` - Each of the emotes used in the message
+    fn broadcast<'a>(
+        &self,
+        hybrid: &mut Hybrid<Cache, Persistent>,
+        issuer: &User,
+        sender: &'a str,
+        contents: &'a str,
+        spans: Vec<EmoteSpan<'a>>,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let now = Utc::now();
+
+        // Messages are checked against the issuer's message counts before
+        // they are counted themselves
+        let first_message = self.is_first_message(hybrid, issuer.id(), now)?;
+
+        hybrid.record_line(issuer.id(), now)?;
+
+        // A chatter is considered to be watching the last embed they linked
+        if let Some(embed) = linked_embeds(contents).pop() {
+            hybrid.record_embed(issuer.id(), &embed, now)?;
+        }
+
+        let recorded =
+            hybrid.record_chat_message(&NewChatMessage::new(issuer.id(), sender, contents, now))?;
+
+        let mentions = detect_mentions(hybrid, sender, contents)?;
+
+        // Combos are counted by the server, so that each client displays the
+        // same count. Any other public message breaks the running combo.
+        let mut combo = None;
+        match combo_emote(contents, &spans) {
+            Some(emote) => {
+                let count = hybrid.extend_combo(emote)?;
+
+                if count >= MIN_COMBO_LENGTH {
+                    combo = Some(Event::new(
+                        EventTarget::All,
+                        EventKind::Combo(Combo::new(emote, count)),
+                    ));
+                }
+            }
+            None => hybrid.break_combo()?,
+        }
+
+        // The issuer's roles, flairs and subscription are attached here, so
+        // that clients needn't look them up for each message
+        let profile = profiles::profile_for(hybrid, issuer.id())?;
+
+        // Each mentioned chatter is notified separately, so that their
+        // clients needn't scan every message for their username
+        let highlights = mentions.iter().map(|mention| {
+            Event::new(
+                EventTarget::User(mention),
+                EventKind::Highlight(Highlight::new(sender, contents)),
+            )
+        });
+
+        Ok(iter::once(Event::new(
+            EventTarget::All,
+            EventKind::Broadcast(
+                Broadcast::new(sender, contents)
+                    .with_new_account(issuer.is_new_account(Duration::hours(NEW_ACCOUNT_HOURS)))
+                    .with_mentions(mentions.clone())
+                    .with_emotes(spans)
+                    .with_profile(profile)
+                    .with_first_message(first_message)
+                    .with_id(Some(recorded.id())),
+            ),
+        ))
+        .chain(combo)
+        .chain(highlights)
+        .collect())
+    }
+
+    /// Puts the chat in slowmode, or takes it out of slowmode, announcing the
+    /// interval in effect to each chatter. Only moderators may toggle
+    /// slowmode.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `slowmode` - The requested slowmode state
+    fn set_slowmode<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        slowmode: Slowmode,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        authorize::<CanSetChatModes>(&mut hybrid, issuer)?;
+
+        let interval = if slowmode.active() {
+            Some(
+                slowmode
+                    .interval()
+                    .filter(|interval| *interval > 0)
+                    .unwrap_or(self.slowmode.default_interval),
+            )
+        } else {
+            None
+        };
+        hybrid.set_slowmode_interval(interval)?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Slowmode(Slowmode::new(slowmode.active()).with_interval(interval)),
+        )])
+    }
+
+    /// Opens the chat only to subscribers, or to everyone, announcing the
+    /// new state to each chatter. Only moderators may toggle subonly mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `subonly` - The requested subonly state
+    fn set_subonly<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        subonly: Subonly,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        authorize::<CanSetChatModes>(&mut hybrid, issuer)?;
+
+        hybrid.set_subonly(subonly.active())?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Subonly(Subonly::new(subonly.active())),
+        )])
+    }
+
+    /// Starts a poll, unless one is already active, announcing it to each
+    /// chatter. Only moderators may start polls.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `start` - The poll that should be started
+    fn start_poll<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        start: &StartPoll,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        let issuer_id = authorize::<CanManagePolls>(&mut hybrid, issuer)?;
+
+        if !is_valid_poll(start.question(), start.options()) {
+            return Err(DispatchError::InvalidPoll);
+        }
+
+        let poll = hybrid
+            .start_poll(&NewPoll::new(
+                issuer_id,
+                start.question(),
+                start.options().to_vec(),
+                start.weighted(),
+                Utc::now(),
+            ))?
+            .ok_or(DispatchError::PollActive)?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::PollStarted(poll),
+        )])
+    }
+
+    /// Casts the issuer's ballot in the active poll, announcing the updated
+    /// totals to each chatter. Ballots in weighted polls are worth more for
+    /// subscribers of higher tiers.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `vote` - The option that the issuer is voting for
+    fn vote<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        vote: Vote,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+
+        let issuer_id = hybrid
+            .user_id_for(issuer)?
+            .ok_or(DispatchError::UnknownIssuer)?;
+        if self.is_muted(&mut hybrid, issuer_id)? {
+            return Err(DispatchError::Muted);
+        }
+
+        let poll = hybrid
+            .active_poll()?
+            .filter(|poll| poll.id() == vote.poll())
+            .ok_or(DispatchError::NoActivePoll)?;
+        if vote.option() >= poll.options().len() {
+            return Err(DispatchError::InvalidVote);
+        }
+
+        let weight = vote_weight(
+            poll.weighted(),
+            if poll.weighted() {
+                hybrid.active_tier(issuer_id)?
+            } else {
+                None
+            },
+        );
+        if !hybrid.vote(poll.id(), issuer_id, vote.option(), weight)? {
+            return Err(DispatchError::AlreadyVoted);
+        }
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::PollVotes(PollVotes::new(poll.id(), hybrid.totals(&poll)?)),
+        )])
+    }
+
+    /// Ends the active poll, announcing its results to each chatter. Only
+    /// moderators may end polls.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    fn end_poll<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        authorize::<CanManagePolls>(&mut hybrid, issuer)?;
+
+        let result = hybrid.end_poll()?.ok_or(DispatchError::NoActivePoll)?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::PollEnded(result),
+        )])
+    }
+
+    /// Pins a message above the chat, replacing any message that is already
+    /// pinned, and announces it to each chatter. Pinned messages pass through
+    /// the same sanitizer as regular messages. Only moderators may pin
+    /// messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `pin` - The message that should be pinned
+    fn pin_message<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        pin: PinMessage,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        let issuer_id = authorize::<CanPinMessages>(&mut hybrid, issuer)?;
+
+        let contents = self.sanitizer.sanitize(pin.message())?;
+
+        // A TTL of zero seconds stands in for the server's default
+        let ttl = pin
+            .ttl()
+            .filter(|ttl| *ttl > 0)
+            .map(|ttl| Duration::seconds(ttl as i64))
+            .or(self.pin_ttl);
+
+        let pinned = hybrid.pin_message(
+            &NewPinnedMessage::new(issuer_id, issuer, &contents, Utc::now()).with_ttl(ttl),
+        )?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Pinned(pinned),
+        )])
+    }
+
+    /// Unpins the pinned message, announcing its removal to each chatter.
+    /// Only moderators may unpin messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    fn unpin_message<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        authorize::<CanPinMessages>(&mut hybrid, issuer)?;
+
+        if hybrid.pinned_message()?.is_none() {
+            return Err(DispatchError::NoPinnedMessage);
+        }
+        hybrid.unpin_message()?;
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Unpinned(UnpinMessage),
+        )])
+    }
+
+    /// Replaces the contents of a message recently sent to the chat,
+    /// announcing its new contents to each chatter. Only the sender of a
+    /// message may edit it, and only within the edit window. The message's
+    /// original contents are kept in the chat history.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `edit` - The message that should be edited, and its new contents
+    fn edit_message<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        edit: &'a EditMessage<'a>,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+
+        let issuer = hybrid
+            .user_id_for(issuer)?
+            .map_or(Ok(None), |id| hybrid.get_user(id))?
+            .ok_or(DispatchError::UnknownIssuer)?;
+
+        let roles = self.check_issuer(&mut hybrid, &issuer)?;
+
+        let message = hybrid
+            .chat_message(edit.id())?
+            .filter(|message| !message.deleted())
+            .ok_or(DispatchError::UnknownMessage)?;
+
+        // Chatters can't learn whether a message they didn't send exists
+        if message.sender_id() != issuer.id() {
+            return Err(DispatchError::UnknownMessage);
+        }
+
+        let now = Utc::now();
+        if now - message.sent_at() > self.edit_window {
+            return Err(DispatchError::EditWindowElapsed);
+        }
+
+        // Edits are subject to the same checks as new messages, so that they
+        // can't be sidestepped by editing a message after sending it
+        if !may_chat_in_subonly(&roles) && self.subonly(&mut hybrid)? {
+            return Err(DispatchError::Subonly);
+        }
+
+        let spans = self.check_contents(&mut hybrid, issuer.id(), &roles, edit.contents())?;
+
+        // Corrections to the message itself aren't repeats, though edits
+        // repeating any other recent message are
+        let fingerprint = Fingerprint::new(edit.contents());
+        if !fingerprint.is_similar_to(&Fingerprint::new(message.contents())) {
+            self.check_repeat(&mut hybrid, issuer.id(), &fingerprint)?;
+            hybrid.record_fingerprint(issuer.id(), &fingerprint)?;
+        }
+
+        if !hybrid.edit_chat_message(edit.id(), edit.contents(), now)? {
+            return Err(DispatchError::UnknownMessage);
+        }
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Edited(EditMessage::new(edit.id(), edit.contents()).with_emotes(spans)),
+        )])
+    }
+
+    /// Deletes a message sent to the chat, announcing its deletion to each
+    /// chatter. The message is kept in the chat history, where only
+    /// moderators may view it. Only moderators may delete messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `issuer` - The username of the issuer of the command
+    /// * `delete` - The message that should be deleted
+    fn delete_message<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        issuer: &'a str,
+        delete: DeleteMessage,
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+        authorize::<CanDeleteMessages>(&mut hybrid, issuer)?;
+
+        if !hybrid.delete_chat_message(delete.id(), Utc::now())? {
+            return Err(DispatchError::UnknownMessage);
+        }
+
+        Ok(vec![Event::new(
+            EventTarget::All,
+            EventKind::Deleted(delete),
+        )])
+    }
+
+    /// Builds the handshake that should be sent to each of the given chatters
+    /// upon connecting, describing the chat's current modes, the pinned
+    /// message, and whether or not each chatter may send messages in the
+    /// chat's modes. Banned and muted chatters may not send messages. The
+    /// moderation state of every chatter is looked up at once, so that a
+    /// burst of connections costs a single round trip.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection to the redis caching layer
+    /// * `persistent_conn` - A connection to the MySQL persistence layer
+    /// * `usernames` - The usernames of the connecting chatters
+    #[instrument(skip_all, fields(chatters = usernames.len()))]
+    pub fn handshakes<'a>(
+        &self,
+        conn: &mut RedisConnection,
+        persistent_conn: &PersistentConnection,
+        usernames: &[&'a str],
+    ) -> Result<Vec<Event<'a>>, DispatchError> {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(&self.key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .with_breaker(self.breaker.clone())
+        .with_lookups(self.lookups.clone())
+        .with_outbox(self.outbox.clone());
+
+        let subonly = hybrid.subonly()?;
+        let slowmode = hybrid.slowmode_interval()?;
+        let pinned = hybrid.pinned_message()?;
+
+        // Unregistered chatters hold no roles, and may never chat
+        let user_ids = hybrid.user_ids_for(usernames)?;
+        let mut states = hybrid
+            .moderation_states(&user_ids.iter().filter_map(|id| *id).collect::<Vec<u64>>())?
+            .into_iter();
+
+        Ok(usernames
+            .iter()
+            .zip(user_ids.iter())
+            .map(|(username, user_id)| {
+                let can_chat = user_id.is_some()
+                    && states.next().is_some_and(|state| {
+                        !state.is_banned()
+                            && !state.is_muted()
+                            && (!subonly || may_chat_in_subonly(state.roles()))
+                    });
+
+                Event::new(
+                    EventTarget::User(username),
+                    EventKind::Handshake(
+                        Handshake::new(can_chat)
+                            .with_subonly(subonly)
+                            .with_slowmode(slowmode)
+                            .with_pinned(pinned.clone()),
+                    ),
+                )
+            })
+            .collect())
+    }
+}
+
+/// Ensures that the issuer of a command holds the given capability,
+/// returning the issuer's user ID.
+///
+/// # Arguments
+///
+/// * `hybrid` - The provider with which the issuer's roles should be looked up
+/// * `issuer` - The username of the issuer of the command
+fn authorize<C: Capability>(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    issuer: &str,
+) -> Result<u64, DispatchError> {
+    let issuer_id = hybrid
+        .user_id_for(issuer)?
+        .ok_or(DispatchError::UnknownIssuer)?;
+
+    if C::granted(&hybrid.roles_for_user(issuer_id)?) {
+        Ok(issuer_id)
+    } else {
+        Err(DispatchError::Forbidden)
+    }
+}
+
+/// Determines whether or not a chatter holding the given roles may send
+/// messages while the chat is in subonly mode, which is the case for
+/// subscribers and moderators.
+///
+/// # Arguments
+///
+/// * `roles` - The roles held by the chatter
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::{spec::user::Role, ws_http_server::dispatcher::may_chat_in_subonly};
+///
+/// assert!(may_chat_in_subonly(&[Role::Subscriber]));
+/// assert!(!may_chat_in_subonly(&[Role::VIP]));
+/// ```
+pub fn may_chat_in_subonly(roles: &[Role]) -> bool {
+    roles
+        .iter()
+        .any(|role| *role == Role::Subscriber || role.rank() >= Role::Moderator.rank())
+}
+
+/// Determines whether or not a chatter holding the given roles may send
+/// their first message in safe mode without it being held for approval. Only
+/// moderators and administrators may.
+///
+/// # Arguments
+///
+/// * `roles` - The roles held by the chatter
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::{spec::user::Role, ws_http_server::dispatcher::may_skip_approval};
+///
+/// assert!(may_skip_approval(&[Role::Moderator]));
+/// assert!(!may_skip_approval(&[Role::Subscriber]));
+/// ```
+pub fn may_skip_approval(roles: &[Role]) -> bool {
+    roles
+        .iter()
+        .any(|role| role.rank() >= Role::Moderator.rank())
+}
+
+/// Detects each of the registered chatters mentioned in the given message,
+/// either as @username or as a bare username. Chatters aren't considered to
+/// have mentioned themselves.
+///
+/// # Arguments
+///
+/// * `resolver` - The name resolver with which mentioned usernames should be
+/// looked up
+/// * `sender` - The username of the sender of the message
+/// * `msg` - The contents of the message
+pub fn detect_mentions<'a, R: NameResolver>(
+    resolver: &mut R,
+    sender: &str,
+    msg: &'a str,
+) -> Result<Vec<&'a str>, ProviderError> {
+    let candidates = mention_candidates(msg)
+        .into_iter()
+        .filter(|candidate| *candidate != sender)
+        .collect::<Vec<&str>>();
+
+    Ok(candidates
+        .iter()
+        .zip(resolver.user_ids_for(&candidates)?)
+        .filter(|(_, user_id)| user_id.is_some())
+        .map(|(candidate, _)| *candidate)
+        .collect())
+}
+
+/// Locates each of the known emotes used in the given message. Emote codes
+/// must be surrounded by characters that can't appear in a code.
+///
+/// # Arguments
+///
+/// * `msg` - The contents of the message
+/// * `emotes` - Each of the known emotes, keyed by their codes
+fn emote_spans<'a>(msg: &'a str, emotes: &HashMap<String, Emote>) -> Vec<EmoteSpan<'a>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    // A trailing separator ensures that a code ending the message is found
+    for (i, c) in msg.char_indices().chain(iter::once((msg.len(), ' '))) {
+        match (c.is_ascii_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(begin)) => {
+                if emotes.contains_key(&msg[begin..i]) {
+                    spans.push(EmoteSpan::new(&msg[begin..i], begin));
+                }
+
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    spans
+}
+
+/// Gets the code of the emote that the given message consists of, if it
+/// consists only of a single emote.
+///
+/// # Arguments
+///
+/// * `msg` - The contents of the message
+/// * `spans` - Each of the emotes used in the message
+fn combo_emote<'a>(msg: &'a str, spans: &[EmoteSpan]) -> Option<&'a str> {
+    match spans {
+        [span] if msg[..span.start()].trim().is_empty() && msg[span.end()..].trim().is_empty() => {
+            Some(&msg[span.start()..span.end()])
+        }
+        _ => None,
+    }
+}
+
+/// Extracts the lowercase host of each of the links in the given message.
+/// Links may be written with or without a scheme, though words without a
+/// scheme are only considered links if they begin with a valid domain.
+///
+/// # Arguments
+///
+/// * `msg` - The contents of the message
+fn link_hosts(msg: &str) -> Vec<String> {
+    msg.split_whitespace()
+        .filter_map(|word| {
+            let word = word
+                .trim_start_matches(|c: char| !c.is_ascii_alphanumeric())
+                .to_lowercase();
+            let (has_scheme, rest) = match word.split_once("://") {
+                Some((scheme, rest)) if scheme == "http" || scheme == "https" => (true, rest),
+                _ => (false, word.as_str()),
+            };
+
+            // Hosts end at their path, and may be preceded by credentials
+            // or followed by a port
+            let authority = rest.split(&['/', '?', '#'][..]).next()?;
+            let host = authority
+                .rsplit('@')
+                .next()?
+                .split(':')
+                .next()?
+                .trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+
+            // Hosts that can't be whitelisted, like IP addresses, are still
+            // links if they follow a scheme
+            if is_valid_domain(host) || (has_scheme && !host.is_empty()) {
+                Some(host.to_owned())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Gets each of the distinct words in the given message that could be a
+/// username, in the order that they first appear. Only the first
+/// MAX_MENTION_CANDIDATES words are considered, so that long messages can't
+/// force a lookup of every word.
+///
+/// # Arguments
+///
+/// * `msg` - The contents of the message
+fn mention_candidates(msg: &str) -> Vec<&str> {
+    let mut candidates: Vec<&str> = Vec::new();
+
+    // Splitting on anything that can't appear in a username leaves both
+    // @username and bare username mentions as words
+    for word in msg.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+        if candidates.len() == MAX_MENTION_CANDIDATES {
+            break;
+        }
+
+        if is_valid_username(word) && !candidates.contains(&word) {
+            candidates.push(word);
+        }
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_gates() -> Result<(), Box<dyn Error>> {
+        let created_at = (Utc::now() - Duration::hours(2))
+            .naive_utc()
+            .format("%Y-%m-%dT%H:%M:%S");
+        let user: User = serde_json::from_str(&format!(
+            r#"{{"id":69420,"username":"MrMouton","verified":false,"nationality":null,"accepts_gifts":null,"minecraft_name":null,"created_at":"{0}","updated_at":"{0}"}}"#,
+            created_at
+        ))?;
+
+        assert!(ChatGates::default().check(&user).is_ok());
+        assert!(ChatGates::default()
+            .with_min_account_age(Some(Duration::hours(1)))
+            .check(&user)
+            .is_ok());
+        assert!(matches!(
+            ChatGates::default()
+                .with_min_account_age(Some(Duration::hours(24)))
+                .check(&user),
+            Err(DispatchError::AccountTooNew)
+        ));
+        assert!(matches!(
+            ChatGates::default()
+                .with_require_verified(true)
+                .check(&user),
+            Err(DispatchError::UnverifiedAccount)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_emote_spans() {
+        let emotes = vec![
+            Emote::new("OverRustle", "OverRustle.png"),
+            Emote::new("PepeLaugh", "PepeLaugh.png").with_tier(Some(2)),
+        ]
+        .into_iter()
+        .map(|emote| (emote.code().to_owned(), emote))
+        .collect::<HashMap<String, Emote>>();
+
+        assert_eq!(
+            emote_spans("OverRustle héhé PepeLaugh, OverRustled PepeLaugh", &emotes),
+            vec![
+                EmoteSpan::new("OverRustle", 0),
+                EmoteSpan::new("PepeLaugh", 18),
+                EmoteSpan::new("PepeLaugh", 41),
+            ]
+        );
+        assert!(emote_spans("", &emotes).is_empty());
+    }
+
+    #[test]
+    fn test_combo_emote() {
+        let spans = [EmoteSpan::new("PEPE", 1)];
+        assert_eq!(combo_emote(" PEPE ", &spans), Some("PEPE"));
+        assert_eq!(combo_emote(" PEPE!", &spans), None);
+        assert_eq!(
+            combo_emote(
+                "PEPE PEPE",
+                &[EmoteSpan::new("PEPE", 0), EmoteSpan::new("PEPE", 5)]
+            ),
+            None
+        );
+        assert_eq!(combo_emote("hi", &[]), None);
+    }
+
+    #[test]
+    fn test_link_hosts() {
+        assert_eq!(
+            link_hosts("watch (https://www.YouTube.com/watch?v=1) or twitch.tv/destiny, e.g. http://127.0.0.1:8080"),
+            vec!["www.youtube.com", "twitch.tv", "127.0.0.1"]
+        );
+        assert!(link_hosts("Mr. Mouton said 3.14... ftp is dead").is_empty());
+    }
+
+    #[test]
+    fn test_link_filter() {
+        let filter = LinkFilter::default().with_bypass_role(Some(Role::VIP));
+
+        assert!(filter.bypassed_by(&[Role::Subscriber, Role::Moderator]));
+        assert!(!filter.bypassed_by(&[Role::Subscriber]));
+        assert!(!LinkFilter::default().bypassed_by(&[Role::Administrator]));
+    }
+
+    #[test]
+    fn test_slowmode_policy() {
+        let policy = SlowmodePolicy::default();
+
+        assert!(policy.exempts(&[Role::Administrator]));
+        assert!(!policy.exempts(&[Role::VIP, Role::Subscriber]));
+        assert!(!policy.with_exempt_role(None).exempts(&[Role::Moderator]));
+    }
+
+    #[test]
+    fn test_may_chat_in_subonly() {
+        assert!(may_chat_in_subonly(&[Role::Subscriber]));
+        assert!(may_chat_in_subonly(&[Role::Moderator]));
+        assert!(!may_chat_in_subonly(&[Role::VIP, Role::Bot]));
+        assert!(!may_chat_in_subonly(&[]));
+    }
+
+    #[test]
+    fn test_escalation_policy() {
+        let policy = EscalationPolicy::default()
+            .with_step(5, Duration::hours(1))
+            .with_step(3, Duration::minutes(5));
+
+        assert_eq!(policy.mute_duration(2), None);
+        assert_eq!(policy.mute_duration(3), Some(Duration::minutes(5)));
+        assert_eq!(policy.mute_duration(7), Some(Duration::hours(1)));
+        assert_eq!(EscalationPolicy::default().mute_duration(100), None);
+    }
+
+    #[test]
+    fn test_mention_candidates() {
+        assert_eq!(
+            mention_candidates("@Destiny MrMouton, did you see destiny's take? @Destiny"),
+            vec!["Destiny", "MrMouton", "did", "you", "see", "destiny", "take"]
+        );
+        assert!(mention_candidates("a b c ?!").is_empty());
+        assert_eq!(mention_candidates(&"word ".repeat(100)).len(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+use actix_web::{
+    http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    HttpRequest, HttpResponse,
+};
+use openssl::sha;
+use serde::Serialize;
+
+use super::modules::ProviderError;
+
+/// The number of bytes of each representation's digest included in its
+/// entity tag.
+const TAG_LENGTH: usize = 16;
+
+/// Computes the entity tag of the given representation of a resource. Tags
+/// are derived from the representation itself, so every node tags an
+/// unchanged resource identically.
+///
+/// # Arguments
+///
+/// * `body` - The serialized representation of the resource
+pub fn entity_tag(body: &[u8]) -> String {
+    let digest = sha::sha256(body);
+
+    format!(
+        "\"{}\"",
+        digest[..TAG_LENGTH]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    )
+}
+
+/// Determines whether or not the given If-None-Match header lists the given
+/// entity tag, such that the client's copy of the resource is current. Tags
+/// are compared weakly, as allowed for If-None-Match.
+///
+/// # Arguments
+///
+/// * `if_none_match` - The value of the If-None-Match header sent by the
+/// client
+/// * `tag` - The entity tag of the current representation of the resource
+pub fn matches(if_none_match: &str, tag: &str) -> bool {
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .map(|candidate| candidate.trim())
+            .any(|candidate| candidate.trim_start_matches("W/") == tag)
+}
+
+/// Responds to the given request with the given value serialized as JSON,
+/// tagged with its entity tag. Clients whose copy of the value is current, as
+/// indicated by their If-None-Match header, are answered with 304 Not
+/// Modified instead, without a body.
+///
+/// # Arguments
+///
+/// * `req` - The request being answered
+/// * `value` - The current representation of the requested resource
+pub fn respond<T: Serialize>(req: &HttpRequest, value: &T) -> Result<HttpResponse, ProviderError> {
+    let body = serde_json::to_vec(value)?;
+    let tag = entity_tag(&body);
+
+    let current = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|header| header.to_str().ok())
+        .is_some_and(|header| matches(header, &tag));
+
+    // Clients may keep their copy, but must revalidate it before each use
+    Ok(if current {
+        HttpResponse::NotModified()
+            .header(ETAG, tag)
+            .header(CACHE_CONTROL, "no-cache")
+            .finish()
+    } else {
+        HttpResponse::Ok()
+            .header(ETAG, tag)
+            .header(CACHE_CONTROL, "no-cache")
+            .content_type("application/json")
+            .body(body)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_tag() {
+        let tag = entity_tag(b"[]");
+        assert_eq!(tag, entity_tag(b"[]"));
+        assert_ne!(tag, entity_tag(b"[1]"));
+        assert_eq!(tag.len(), TAG_LENGTH * 2 + 2);
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+    }
+
+    #[test]
+    fn test_matches() {
+        let tag = entity_tag(b"[]");
+
+        assert!(matches(&tag, &tag));
+        assert!(matches(&format!("W/{}", tag), &tag));
+        assert!(matches(&format!("\"stale\", {}", tag), &tag));
+        assert!(matches("*", &tag));
+        assert!(!matches("\"stale\"", &tag));
+        assert!(!matches("", &tag));
+    }
+}
@@ -0,0 +1,512 @@
+use actix_web::web::Data;
+use redis::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::spec::{mute::Mute, user::Role},
+    modules::ProviderError,
+    server::State,
+};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The redis channel on which invalidations are published, before it is
+/// namespaced according to the deployment's key prefix.
+pub const INVALIDATION_CHANNEL: &str = "cache_invalidations";
+
+/// The number of users whose roles and mutes are held by a node's local
+/// cache if no capacity is specified.
+pub const DEFAULT_LOCAL_CACHE_CAPACITY: usize = 10_000;
+
+/// The number of seconds that a local copy is kept for if no TTL is
+/// specified, bounding how stale it may become if an invalidation is lost
+/// in transit.
+pub const DEFAULT_LOCAL_CACHE_TTL_SECONDS: u64 = 60;
+
+/// The time that a node waits before subscribing to the invalidation channel
+/// again once its subscription is lost.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Invalidation represents a change to a value that nodes may hold a local
+/// copy of. Invalidations are published by the caching layer once the value
+/// is modified, such that each node discards its copy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Invalidation {
+    /// The roles held by the user with the given ID changed
+    Roles { user_id: u64 },
+
+    /// The user with the given ID was muted or unmuted
+    Mute { user_id: u64 },
+
+    /// The chat modes changed
+    ChatModes,
+}
+
+/// Lru is a map from user IDs to values that holds a bounded number of
+/// entries for a bounded amount of time, evicting the least recently used
+/// entry once full.
+#[derive(Debug)]
+struct Lru<V> {
+    /// The maximum number of entries held by the map. No entries are held if
+    /// the capacity is zero.
+    capacity: usize,
+
+    /// The amount of time after which an entry expires
+    ttl: Duration,
+
+    /// The number of lookups and insertions made so far, used to order the
+    /// entries by their last use
+    clock: u64,
+
+    /// Each entry, along with the time at which it was stored and the tick
+    /// of the clock at which it was last used, keyed by user ID
+    entries: HashMap<u64, (V, Instant, u64)>,
+
+    /// The user ID of each entry, keyed by the tick at which it was last used
+    recency: BTreeMap<u64, u64>,
+}
+
+impl<V: Clone> Lru<V> {
+    /// Creates a new, empty map.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of entries held by the map
+    /// * `ttl` - The amount of time after which an entry expires
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            clock: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    /// Gets the value stored for the given user, if it hasn't expired,
+    /// marking it as the most recently used entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose value should be obtained
+    fn get(&mut self, user_id: u64) -> Option<V> {
+        let (stored_at, used_at) = match self.entries.get(&user_id) {
+            Some((_, stored_at, used_at)) => (*stored_at, *used_at),
+            None => return None,
+        };
+
+        if stored_at.elapsed() >= self.ttl {
+            self.remove(user_id);
+
+            return None;
+        }
+
+        self.clock += 1;
+        self.recency.remove(&used_at);
+        self.recency.insert(self.clock, user_id);
+
+        let clock = self.clock;
+        self.entries.get_mut(&user_id).map(|(value, _, used_at)| {
+            *used_at = clock;
+            value.clone()
+        })
+    }
+
+    /// Stores the given value for the given user, evicting the least recently
+    /// used entry if the map is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose value should be stored
+    /// * `value` - The value that should be stored
+    fn insert(&mut self, user_id: u64, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(user_id);
+        while self.entries.len() >= self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(used_at) => *used_at,
+                None => break,
+            };
+
+            if let Some(evicted) = self.recency.remove(&oldest) {
+                self.entries.remove(&evicted);
+            }
+        }
+
+        self.clock += 1;
+        self.recency.insert(self.clock, user_id);
+        self.entries
+            .insert(user_id, (value, Instant::now(), self.clock));
+    }
+
+    /// Discards the value stored for the given user, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose value should be discarded
+    fn remove(&mut self, user_id: u64) {
+        if let Some((_, _, used_at)) = self.entries.remove(&user_id) {
+            self.recency.remove(&used_at);
+        }
+    }
+
+    /// Discards every entry.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+/// LocalCache holds the copies of frequently read values kept by a single
+/// node, such that they needn't be fetched from redis for each message.
+/// Copies are discarded once an invalidation concerning them is received, or
+/// once they expire, and are only kept while the node is subscribed to the
+/// invalidation channel.
+#[derive(Debug)]
+pub struct LocalCache {
+    /// Whether or not the node is subscribed to the invalidation channel
+    subscribed: AtomicBool,
+
+    /// The number of invalidations received so far, used to keep values
+    /// looked up before an invalidation from being stored after it
+    generation: AtomicU64,
+
+    /// The roles held by each user that were recently looked up
+    roles: Mutex<Lru<Vec<Role>>>,
+
+    /// The most recent mute issued against each user that was recently
+    /// looked up, if any
+    mutes: Mutex<Lru<Option<Mute>>>,
+
+    /// Whether or not the chat is in subonly mode, if known
+    subonly: Mutex<Option<bool>>,
+}
+
+impl Default for LocalCache {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_LOCAL_CACHE_CAPACITY,
+            Duration::from_secs(DEFAULT_LOCAL_CACHE_TTL_SECONDS),
+        )
+    }
+}
+
+impl LocalCache {
+    /// Creates a new, empty local cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of users whose roles and mutes may be held
+    /// at once, or zero if they shouldn't be held locally at all
+    /// * `ttl` - The amount of time for which each copy is kept
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::invalidation::LocalCache;
+    /// use std::time::Duration;
+    ///
+    /// // Nothing is held by a local cache without any capacity
+    /// let local = LocalCache::new(0, Duration::from_secs(60));
+    /// assert_eq!(local.roles(1), None);
+    /// ```
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            subscribed: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+            roles: Mutex::new(Lru::new(capacity, ttl)),
+            mutes: Mutex::new(Lru::new(capacity, ttl)),
+            subonly: Mutex::new(None),
+        }
+    }
+
+    /// Gets the number of invalidations received so far, which should be
+    /// obtained before looking up a value that will be stored locally.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Gets the local copy of the roles held by the user with the given ID,
+    /// if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be obtained
+    pub fn roles(&self, user_id: u64) -> Option<Vec<Role>> {
+        lock(&self.roles).get(user_id)
+    }
+
+    /// Stores a local copy of the roles held by the user with the given ID,
+    /// unless an invalidation was received since they were looked up.
+    ///
+    /// # Arguments
+    ///
+    /// * `generation` - The generation of the cache prior to the lookup
+    /// * `user_id` - The ID of the user whose roles were looked up
+    /// * `roles` - The roles held by the user
+    pub fn store_roles(&self, generation: u64, user_id: u64, roles: &[Role]) {
+        let mut cached = lock(&self.roles);
+        if self.is_current(generation) {
+            cached.insert(user_id, roles.to_vec());
+        }
+    }
+
+    /// Gets the local copy of the most recent mute issued against the user
+    /// with the given ID, if any. The outer option is empty if no copy is
+    /// held.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose mute should be obtained
+    pub fn mute(&self, user_id: u64) -> Option<Option<Mute>> {
+        lock(&self.mutes).get(user_id)
+    }
+
+    /// Stores a local copy of the most recent mute issued against the user
+    /// with the given ID, unless an invalidation was received since it was
+    /// looked up.
+    ///
+    /// # Arguments
+    ///
+    /// * `generation` - The generation of the cache prior to the lookup
+    /// * `user_id` - The ID of the user whose mute was looked up
+    /// * `mute` - The most recent mute issued against the user, if any
+    pub fn store_mute(&self, generation: u64, user_id: u64, mute: Option<&Mute>) {
+        let mut cached = lock(&self.mutes);
+        if self.is_current(generation) {
+            cached.insert(user_id, mute.cloned());
+        }
+    }
+
+    /// Gets the local copy of whether or not the chat is in subonly mode, if
+    /// any.
+    pub fn subonly(&self) -> Option<bool> {
+        *lock(&self.subonly)
+    }
+
+    /// Stores a local copy of whether or not the chat is in subonly mode,
+    /// unless an invalidation was received since it was looked up.
+    ///
+    /// # Arguments
+    ///
+    /// * `generation` - The generation of the cache prior to the lookup
+    /// * `subonly` - Whether or not the chat is in subonly mode
+    pub fn store_subonly(&self, generation: u64, subonly: bool) {
+        let mut cached = lock(&self.subonly);
+        if self.is_current(generation) {
+            *cached = Some(subonly);
+        }
+    }
+
+    /// Discards the local copies concerned by the given invalidation.
+    ///
+    /// # Arguments
+    ///
+    /// * `invalidation` - The change that was made to the values
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::invalidation::{Invalidation, LocalCache};
+    ///
+    /// let local = LocalCache::default();
+    /// local.invalidate(&Invalidation::Roles { user_id: 1 });
+    /// assert_eq!(local.roles(1), None);
+    /// ```
+    pub fn invalidate(&self, invalidation: &Invalidation) {
+        // The generation is advanced before the copies are discarded, such
+        // that lookups racing with the invalidation can't store their values
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        match invalidation {
+            Invalidation::Roles { user_id } => lock(&self.roles).remove(*user_id),
+            Invalidation::Mute { user_id } => lock(&self.mutes).remove(*user_id),
+            Invalidation::ChatModes => *lock(&self.subonly) = None,
+        }
+    }
+
+    /// Discards every local copy. Called whenever invalidations may have
+    /// been missed.
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        lock(&self.roles).clear();
+        lock(&self.mutes).clear();
+        *lock(&self.subonly) = None;
+    }
+
+    /// Determines whether or not a value looked up at the given generation
+    /// may be stored locally, as no invalidation could have been missed
+    /// since.
+    ///
+    /// # Arguments
+    ///
+    /// * `generation` - The generation of the cache prior to the lookup
+    fn is_current(&self, generation: u64) -> bool {
+        self.subscribed.load(Ordering::SeqCst) && self.generation() == generation
+    }
+
+    /// Records whether or not the node is subscribed to the invalidation
+    /// channel, discarding every local copy, as invalidations published
+    /// while the node wasn't subscribed were missed.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscribed` - Whether or not the node is subscribed
+    fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::SeqCst);
+        self.clear();
+    }
+}
+
+/// Locks one of the local cache's values. Copies remain consistent even if a
+/// thread panicked while holding the lock, so poisoning is ignored.
+///
+/// # Arguments
+///
+/// * `value` - The value that should be locked
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Subscribes to the given invalidation channel, discarding the local copies
+/// concerned by each invalidation received until the subscription is lost.
+/// Malformed invalidations are ignored.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer, which is dedicated to
+/// the subscription until it is lost
+/// * `channel` - The namespaced channel on which invalidations are published
+/// * `local` - The node's local cache
+pub fn listen(
+    conn: &mut Connection,
+    channel: &str,
+    local: &LocalCache,
+) -> Result<(), ProviderError> {
+    let mut pubsub = conn.as_pubsub();
+    pubsub.subscribe(channel)?;
+    local.set_subscribed(true);
+
+    let lost = loop {
+        match pubsub
+            .get_message()
+            .and_then(|msg| msg.get_payload::<String>())
+        {
+            Ok(payload) => {
+                if let Ok(invalidation) = serde_json::from_str::<Invalidation>(&payload) {
+                    local.invalidate(&invalidation);
+                }
+            }
+            Err(e) => break e,
+        }
+    };
+
+    local.set_subscribed(false);
+
+    Err(lost.into())
+}
+
+/// Keeps the node's local cache subscribed to the deployment's invalidation
+/// channel for as long as the server is running. The subscription holds a
+/// pooled connection on a dedicated thread, and is made again whenever it is
+/// lost.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the node's local cache
+pub(crate) fn spawn_invalidation_task(state: Data<State>) {
+    thread::spawn(move || {
+        let channel = format!("{}{}", state.key_prefix(), INVALIDATION_CHANNEL);
+
+        loop {
+            let _ = state
+                .cache_connection()
+                .and_then(|mut conn| listen(&mut conn, &channel, state.local_cache()));
+
+            thread::sleep(RESUBSCRIBE_DELAY);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalidate() {
+        let local = LocalCache::default();
+
+        // Nothing is stored until the node is subscribed
+        local.store_subonly(local.generation(), true);
+        assert_eq!(local.subonly(), None);
+
+        local.set_subscribed(true);
+        let generation = local.generation();
+        local.store_roles(generation, 1, &[Role::Moderator]);
+        local.store_roles(generation, 2, &[Role::Subscriber]);
+        local.store_subonly(generation, true);
+
+        // Only the copies concerned by an invalidation are discarded
+        local.invalidate(&Invalidation::Roles { user_id: 1 });
+        assert_eq!(local.roles(1), None);
+        assert_eq!(local.roles(2), Some(vec![Role::Subscriber]));
+        assert_eq!(local.subonly(), Some(true));
+
+        local.invalidate(&Invalidation::ChatModes);
+        assert_eq!(local.subonly(), None);
+
+        // Values looked up before an invalidation aren't stored
+        local.store_subonly(generation, true);
+        assert_eq!(local.subonly(), None);
+
+        // Invalidations are published as JSON
+        let encoded = serde_json::to_string(&Invalidation::Roles { user_id: 2 }).unwrap();
+        assert_eq!(encoded, r#"{"kind":"roles","user_id":2}"#);
+        local.invalidate(&serde_json::from_str(&encoded).unwrap());
+        assert_eq!(local.roles(2), None);
+    }
+
+    #[test]
+    fn test_lru() {
+        let local = LocalCache::new(2, Duration::from_secs(60));
+        local.set_subscribed(true);
+
+        let generation = local.generation();
+        local.store_roles(generation, 1, &[Role::Moderator]);
+        local.store_roles(generation, 2, &[Role::VIP]);
+
+        // The least recently used user is evicted once the cache is full
+        assert!(local.roles(1).is_some());
+        local.store_roles(generation, 3, &[Role::Subscriber]);
+        assert_eq!(local.roles(2), None);
+        assert_eq!(local.roles(1), Some(vec![Role::Moderator]));
+        assert_eq!(local.roles(3), Some(vec![Role::Subscriber]));
+
+        // Users who aren't muted are remembered as such
+        local.store_mute(generation, 1, None);
+        assert_eq!(local.mute(1), Some(None));
+        local.invalidate(&Invalidation::Mute { user_id: 1 });
+        assert_eq!(local.mute(1), None);
+
+        // Expired copies are discarded
+        let expiring = LocalCache::new(2, Duration::from_secs(0));
+        expiring.set_subscribed(true);
+        expiring.store_roles(expiring.generation(), 1, &[Role::Moderator]);
+        assert_eq!(expiring.roles(1), None);
+    }
+}
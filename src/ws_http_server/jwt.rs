@@ -0,0 +1,721 @@
+use actix_web::{http::StatusCode, ResponseError};
+use chrono::Utc;
+use openssl::{
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    sign::{Signer, Verifier},
+};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerdeError;
+
+use super::{super::spec::user::Role, problem::ErrorCode};
+
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+/// The number of seconds that a session token remains valid for after being
+/// issued. Roles are embedded in tokens, so changes to a user's roles take
+/// effect once their token is refreshed.
+pub const TOKEN_TTL: i64 = 15 * 60;
+
+/// The number of seconds that a token issued to an administrator
+/// impersonating another user remains valid for. Such tokens can't be
+/// refreshed, so impersonation ends once the token expires.
+pub const IMPERSONATION_TTL: i64 = 10 * 60;
+
+/// The algorithm with which session tokens are signed.
+const ALGORITHM: &str = "RS256";
+
+/// The number of bits in a generated RSA signing key.
+const KEY_BITS: u32 = 2048;
+
+/// The number of random bytes in the ID of an ephemeral signing key.
+const KEY_ID_LENGTH: usize = 8;
+
+/// JwtError represents any error encountered while issuing or validating a
+/// session token.
+#[derive(Debug)]
+pub enum JwtError {
+    /// The token isn't a well-formed JWT
+    Malformed,
+
+    /// The token was signed with an unsupported algorithm
+    UnsupportedAlgorithm,
+
+    /// The token's signature doesn't match its contents
+    InvalidSignature,
+
+    /// The token is past its expiry
+    Expired,
+
+    /// The token's claims couldn't be serialized or deserialized
+    SerdeError(SerdeError),
+
+    /// The token couldn't be signed
+    CryptoError(ErrorStack),
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "the token is malformed"),
+            Self::UnsupportedAlgorithm => write!(f, "the token's signing algorithm is unsupported"),
+            Self::InvalidSignature => write!(f, "the token's signature is invalid"),
+            Self::Expired => write!(f, "the token has expired"),
+            Self::SerdeError(e) => write!(f, "the token's claims are invalid: {}", e),
+            Self::CryptoError(e) => write!(f, "the token could not be signed: {}", e),
+        }
+    }
+}
+
+impl Error for JwtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::SerdeError(e) => Some(e),
+            Self::CryptoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ResponseError for JwtError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::SerdeError(_) | Self::CryptoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl ErrorCode for JwtError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Malformed => "malformed_token",
+            Self::UnsupportedAlgorithm => "unsupported_token_algorithm",
+            Self::InvalidSignature => "invalid_token_signature",
+            Self::Expired => "token_expired",
+            Self::SerdeError(_) | Self::CryptoError(_) => "token_error",
+        }
+    }
+}
+
+impl From<SerdeError> for JwtError {
+    /// Constructs a JWT error from the given serde error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The serde error that should be wrapped in the JwtError
+    fn from(e: SerdeError) -> Self {
+        Self::SerdeError(e)
+    }
+}
+
+impl From<ErrorStack> for JwtError {
+    /// Constructs a JWT error from the given openssl error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The openssl error that should be wrapped in the JwtError
+    fn from(e: ErrorStack) -> Self {
+        Self::CryptoError(e)
+    }
+}
+
+/// Header represents the JOSE header of a session token.
+#[derive(Serialize, Deserialize)]
+struct Header<'a> {
+    /// The algorithm with which the token was signed
+    alg: &'a str,
+
+    /// The type of the token
+    typ: &'a str,
+
+    /// The ID of the key with which the token was signed
+    kid: &'a str,
+}
+
+/// Claims represents the contents of a session token.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Claims {
+    /// The ID of the user to whom the token was issued
+    sub: u64,
+
+    /// The ID of the session that the token belongs to
+    sid: String,
+
+    /// The roles held by the user when the token was issued
+    roles: Vec<Role>,
+
+    /// The UNIX timestamp at which the token was issued
+    iat: i64,
+
+    /// The UNIX timestamp at which the token expires
+    exp: i64,
+
+    /// The administrator acting on behalf of the user, if the token was
+    /// issued for impersonation (see RFC 8693)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+}
+
+/// Actor represents the party acting on behalf of the subject of a token.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Actor {
+    /// The ID of the acting user
+    sub: u64,
+}
+
+impl Claims {
+    /// Creates a new set of claims for a token expiring after the standard
+    /// token lifetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the token is issued
+    /// * `session_id` - The ID of the session that the token belongs to
+    /// * `roles` - The roles held by the user
+    pub fn new(user_id: u64, session_id: String, roles: Vec<Role>) -> Self {
+        let now = Utc::now().timestamp();
+
+        Self {
+            sub: user_id,
+            sid: session_id,
+            roles,
+            iat: now,
+            exp: now + TOKEN_TTL,
+            act: None,
+        }
+    }
+
+    /// Creates a new set of claims for a token permitting an administrator to
+    /// act as another user. The token expires after the impersonation
+    /// lifetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user being impersonated
+    /// * `session_id` - The ID of the session that the token belongs to
+    /// * `roles` - The roles held by the impersonated user
+    /// * `impersonator_id` - The ID of the administrator acting as the user
+    pub fn impersonating(
+        user_id: u64,
+        session_id: String,
+        roles: Vec<Role>,
+        impersonator_id: u64,
+    ) -> Self {
+        let claims = Self::new(user_id, session_id, roles);
+
+        Self {
+            exp: claims.iat + IMPERSONATION_TTL,
+            act: Some(Actor {
+                sub: impersonator_id,
+            }),
+            ..claims
+        }
+    }
+
+    /// Retreives the ID of the user to whom the token was issued.
+    pub fn user_id(&self) -> u64 {
+        self.sub
+    }
+
+    /// Retreives the ID of the session that the token belongs to.
+    pub fn session_id(&self) -> &str {
+        &self.sid
+    }
+
+    /// Retreives the roles held by the user when the token was issued.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Retreives the ID of the administrator acting as the user, if the token
+    /// was issued for impersonation.
+    pub fn impersonator(&self) -> Option<u64> {
+        self.act.map(|actor| actor.sub)
+    }
+}
+
+/// SigningKey represents an RSA key with which the server signs session
+/// tokens. Each key is identified by an ID embedded in the tokens it signs,
+/// so that tokens may be verified after the key has been rotated.
+#[derive(Clone)]
+pub struct SigningKey {
+    /// The ID of the key
+    id: String,
+
+    /// The RSA key pair
+    key: PKey<Private>,
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey({})", self.id)
+    }
+}
+
+impl SigningKey {
+    /// Generates a new RSA signing key with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the key
+    pub fn generate(id: String) -> Result<Self, JwtError> {
+        Ok(Self {
+            id,
+            key: PKey::from_rsa(Rsa::generate(KEY_BITS)?)?,
+        })
+    }
+
+    /// Loads the signing key with the given ID from a PEM-encoded private
+    /// key.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the key
+    /// * `pem` - The PEM-encoded RSA private key
+    pub fn from_pem(id: String, pem: &[u8]) -> Result<Self, JwtError> {
+        Ok(Self {
+            id,
+            key: PKey::private_key_from_pem(pem)?,
+        })
+    }
+
+    /// Encodes the key's private key as PEM.
+    pub fn to_pem(&self) -> Result<Vec<u8>, JwtError> {
+        self.key.private_key_to_pem_pkcs8().map_err(|e| e.into())
+    }
+
+    /// Retreives the ID of the key.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Describes the key's public key as a JSON web key, so that other
+    /// services may verify the tokens it signs.
+    pub fn jwk(&self) -> Result<Jwk, JwtError> {
+        let rsa = self.key.rsa()?;
+
+        Ok(Jwk {
+            kty: "RSA",
+            key_use: "sig",
+            alg: ALGORITHM,
+            kid: self.id.clone(),
+            n: base64::encode_config(rsa.n().to_vec(), base64::URL_SAFE_NO_PAD),
+            e: base64::encode_config(rsa.e().to_vec(), base64::URL_SAFE_NO_PAD),
+        })
+    }
+
+    /// Signs the given message with RSASSA-PKCS1-v1_5 using SHA-256.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be signed
+    fn sign_message(&self, message: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(message)?;
+
+        signer.sign_to_vec()
+    }
+
+    /// Determines whether or not the given signature was produced by the key
+    /// over the given message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that was signed
+    /// * `signature` - The signature that should be checked
+    fn verify_message(&self, message: &[u8], signature: &[u8]) -> Result<bool, ErrorStack> {
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &self.key)?;
+        verifier.update(message)?;
+
+        verifier.verify(signature)
+    }
+}
+
+/// Jwk represents the public half of a signing key, formatted as a JSON web
+/// key.
+#[derive(Serialize, Debug)]
+pub struct Jwk {
+    /// The type of the key
+    kty: &'static str,
+
+    /// The purpose of the key
+    #[serde(rename = "use")]
+    key_use: &'static str,
+
+    /// The algorithm with which the key signs tokens
+    alg: &'static str,
+
+    /// The ID of the key
+    kid: String,
+
+    /// The base64url-encoded RSA modulus
+    n: String,
+
+    /// The base64url-encoded RSA public exponent
+    e: String,
+}
+
+/// Jwks represents each of the public keys with which session tokens may be
+/// verified, formatted as a JSON web key set.
+#[derive(Serialize, Debug)]
+pub struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+/// KeySet represents each of the keys with which session tokens may be
+/// signed or verified. Tokens are signed with the current key, and retired
+/// keys are kept so that tokens signed before a rotation remain valid until
+/// they expire.
+#[derive(Clone, Debug)]
+pub struct KeySet {
+    /// The key with which new tokens are signed
+    current: SigningKey,
+
+    /// The keys with which tokens may still be verified, but not signed
+    retired: Vec<SigningKey>,
+}
+
+impl KeySet {
+    /// Creates a new key set signing tokens with the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The key with which new tokens should be signed
+    pub fn new(current: SigningKey) -> Self {
+        Self {
+            current,
+            retired: Vec::new(),
+        }
+    }
+
+    /// Consumes the key set, and adds a retired key with which tokens may be
+    /// verified, but not signed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The retired key
+    pub fn with_retired_key(mut self, key: SigningKey) -> Self {
+        self.retired.push(key);
+
+        self
+    }
+
+    /// Generates a key set holding a single random key. Tokens signed by a
+    /// random key are invalidated once the server restarts.
+    pub fn random() -> Result<Self, JwtError> {
+        let mut id = [0; KEY_ID_LENGTH];
+        thread_rng().fill_bytes(&mut id);
+
+        SigningKey::generate(format!(
+            "ephemeral-{}",
+            id.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        ))
+        .map(Self::new)
+    }
+
+    /// Loads each of the PEM-encoded keys in the given directory, using the
+    /// name of each file (minus its .pem extension) as the ID of its key. Key
+    /// IDs are ordered such that the greatest ID is used to sign new tokens,
+    /// as is the case for the timestamped IDs assigned by rotate_keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the keys
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let mut keys = key_ids(dir)?
+            .into_iter()
+            .map(|id| {
+                let pem = fs::read(dir.join(format!("{}.pem", id)))?;
+
+                SigningKey::from_pem(id, &pem)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let current = keys
+            .pop()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no signing keys were found"))?;
+
+        Ok(keys
+            .into_iter()
+            .rev()
+            .fold(Self::new(current), |set, key| set.with_retired_key(key)))
+    }
+
+    /// Retreives the key with which new tokens are signed.
+    pub fn current(&self) -> &SigningKey {
+        &self.current
+    }
+
+    /// Gets the key with the given ID, if it is held by the key set.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the key
+    fn key(&self, id: &str) -> Option<&SigningKey> {
+        std::iter::once(&self.current)
+            .chain(self.retired.iter())
+            .find(|key| key.id == id)
+    }
+
+    /// Describes each of the keys in the set as a JSON web key set.
+    pub fn jwks(&self) -> Result<Jwks, JwtError> {
+        std::iter::once(&self.current)
+            .chain(self.retired.iter())
+            .map(SigningKey::jwk)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|keys| Jwks { keys })
+    }
+
+    /// Signs the given claims with the current key, producing a compact JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - The claims that should be contained in the token
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::jwt::{Claims, KeySet};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let keys = KeySet::random()?;
+    /// let token = keys.sign(&Claims::new(1, "session".to_owned(), Vec::new()))?;
+    ///
+    /// assert_eq!(keys.verify(&token)?.user_id(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sign(&self, claims: &Claims) -> Result<String, JwtError> {
+        let header = serde_json::to_vec(&Header {
+            alg: ALGORITHM,
+            typ: "JWT",
+            kid: &self.current.id,
+        })?;
+        let message = format!(
+            "{}.{}",
+            base64::encode_config(header, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(serde_json::to_vec(claims)?, base64::URL_SAFE_NO_PAD)
+        );
+        let signature = self.current.sign_message(message.as_bytes())?;
+
+        Ok(format!(
+            "{}.{}",
+            message,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    /// Verifies the signature and expiry of the given token, returning its
+    /// claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The compact JWT that should be verified
+    pub fn verify(&self, token: &str) -> Result<Claims, JwtError> {
+        let mut parts = token.rsplitn(2, '.');
+        let (signature, message) = match (parts.next(), parts.next()) {
+            (Some(signature), Some(message)) => (signature, message),
+            _ => return Err(JwtError::Malformed),
+        };
+        let (header, claims) = match message.split('.').collect::<Vec<_>>().as_slice() {
+            [header, claims] => (*header, *claims),
+            _ => return Err(JwtError::Malformed),
+        };
+
+        let decode = |part: &str| {
+            base64::decode_config(part, base64::URL_SAFE_NO_PAD).map_err(|_| JwtError::Malformed)
+        };
+
+        // The algorithm must be checked before the signature, so that tokens
+        // claiming to be unsigned are never accepted
+        let header = decode(header)?;
+        let header = serde_json::from_slice::<Header>(&header).map_err(|_| JwtError::Malformed)?;
+        if header.alg != ALGORITHM {
+            return Err(JwtError::UnsupportedAlgorithm);
+        }
+
+        let key = self.key(header.kid).ok_or(JwtError::InvalidSignature)?;
+        if !key.verify_message(message.as_bytes(), &decode(signature)?)? {
+            return Err(JwtError::InvalidSignature);
+        }
+
+        let claims = serde_json::from_slice::<Claims>(&decode(claims)?)?;
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(JwtError::Expired);
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Gets the IDs of each of the keys stored in the given directory, in
+/// ascending order.
+///
+/// # Arguments
+///
+/// * `dir` - The directory holding the keys
+fn key_ids(dir: &Path) -> io::Result<Vec<String>> {
+    let mut ids = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            entry
+                .map(|entry| {
+                    let path = entry.path();
+
+                    match path.extension() {
+                        Some(ext) if ext == "pem" => path
+                            .file_stem()
+                            .and_then(|stem| stem.to_str())
+                            .map(|stem| stem.to_owned()),
+                        _ => None,
+                    }
+                })
+                .transpose()
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    ids.sort();
+
+    Ok(ids)
+}
+
+/// Generates a new signing key in the given directory, which will be used to
+/// sign tokens once the server is restarted. Only the given number of the
+/// most recent keys are kept; older keys are deleted, invalidating any tokens
+/// that they signed. Returns the ID of the new key.
+///
+/// # Arguments
+///
+/// * `dir` - The directory holding the keys
+/// * `retain` - The number of keys that should be kept, including the new key
+pub fn rotate_keys(dir: &Path, retain: usize) -> io::Result<String> {
+    fs::create_dir_all(dir)?;
+
+    let key = SigningKey::generate(Utc::now().format("%Y%m%d%H%M%S").to_string())
+        .map_err(io::Error::other)?;
+    let pem = key.to_pem().map_err(io::Error::other)?;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(dir.join(format!("{}.pem", key.id())))?;
+    file.write_all(&pem)?;
+
+    let ids = key_ids(dir)?;
+    for id in ids.iter().take(ids.len().saturating_sub(retain.max(1))) {
+        fs::remove_file(dir.join(format!("{}.pem", id)))?;
+    }
+
+    Ok(key.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_verify() -> Result<(), Box<dyn Error>> {
+        let keys = KeySet::random()?;
+        let claims = Claims::new(42069, "session".to_owned(), vec![Role::Moderator]);
+
+        let token = keys.sign(&claims)?;
+        assert_eq!(keys.verify(&token)?, claims);
+
+        // Tokens signed by another key must be rejected
+        assert!(matches!(
+            KeySet::random()?.verify(&token),
+            Err(JwtError::InvalidSignature)
+        ));
+
+        // As must tokens whose claims have been tampered with
+        let mut parts = token.split('.');
+        let forged = format!(
+            "{}.{}.{}",
+            parts.next().unwrap_or_default(),
+            base64::encode_config(
+                serde_json::to_vec(&Claims::new(
+                    1,
+                    "session".to_owned(),
+                    vec![Role::Administrator]
+                ))?,
+                base64::URL_SAFE_NO_PAD
+            ),
+            parts.nth(1).unwrap_or_default()
+        );
+        assert!(matches!(
+            keys.verify(&forged),
+            Err(JwtError::InvalidSignature)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_impersonation() -> Result<(), Box<dyn Error>> {
+        let keys = KeySet::random()?;
+        let claims = Claims::impersonating(42069, "session".to_owned(), Vec::new(), 1);
+
+        let verified = keys.verify(&keys.sign(&claims)?)?;
+        assert_eq!(verified.impersonator(), Some(1));
+        assert_eq!(verified.exp - verified.iat, IMPERSONATION_TTL);
+        assert_eq!(
+            keys.verify(&keys.sign(&Claims::new(42069, "session".to_owned(), Vec::new()))?)?
+                .impersonator(),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired() -> Result<(), Box<dyn Error>> {
+        let keys = KeySet::random()?;
+        let mut claims = Claims::new(42069, "session".to_owned(), Vec::new());
+        claims.exp = claims.iat - 1;
+
+        assert!(matches!(
+            keys.verify(&keys.sign(&claims)?),
+            Err(JwtError::Expired)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("gnomegg-keys-{}", std::process::id()));
+        let claims = Claims::new(42069, "session".to_owned(), Vec::new());
+
+        let first = rotate_keys(&dir, 2)?;
+        let token = KeySet::load(&dir)?.sign(&claims)?;
+
+        // Tokens signed before a rotation remain valid until their key is
+        // pruned
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let second = rotate_keys(&dir, 2)?;
+        let keys = KeySet::load(&dir)?;
+        assert_eq!(keys.current().id(), second);
+        assert_eq!(keys.verify(&token)?, claims);
+        assert_eq!(keys.jwks()?.keys.len(), 2);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        rotate_keys(&dir, 2)?;
+        assert!(KeySet::load(&dir)?.key(&first).is_none());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
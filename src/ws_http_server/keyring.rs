@@ -0,0 +1,413 @@
+use openssl::{
+    error::ErrorStack,
+    rand::rand_bytes,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+
+use std::{collections::HashMap, error::Error, fmt, iter, str::FromStr};
+
+/// The version prefix of each value sealed by a keyring.
+const SEALED_PREFIX: &str = "v1";
+
+/// The number of bytes in each key, and each data key.
+pub const KEY_LENGTH: usize = 32;
+
+/// The context from which the keys of blind indexes are derived, such that
+/// they differ from the keys that they are derived from.
+const INDEX_KEY_CONTEXT: &str = "gnomegg 2020-05 blind index key";
+
+/// The number of bytes in each AES-GCM nonce.
+const NONCE_LENGTH: usize = 12;
+
+/// The number of bytes in each AES-GCM authentication tag.
+const TAG_LENGTH: usize = 16;
+
+/// KeyringError represents any error encountered while sealing or opening a
+/// value, or while parsing a keyring.
+#[derive(Debug)]
+pub enum KeyringError {
+    /// The sealed value is malformed
+    Malformed,
+
+    /// The value was sealed with a key that the keyring doesn't hold
+    UnknownKey,
+
+    /// A key is not a base64-encoded 32-byte key, or has an invalid ID
+    InvalidKey,
+
+    /// The value couldn't be sealed, or failed authentication when opened
+    CryptoError(ErrorStack),
+}
+
+impl fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "the sealed value is malformed"),
+            Self::UnknownKey => write!(f, "the value was sealed with an unknown key"),
+            Self::InvalidKey => write!(f, "the key is invalid"),
+            Self::CryptoError(e) => write!(f, "the value could not be sealed or opened: {}", e),
+        }
+    }
+}
+
+impl Error for KeyringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CryptoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorStack> for KeyringError {
+    /// Constructs a keyring error from the given openssl error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The openssl error that should be wrapped in the KeyringError
+    fn from(e: ErrorStack) -> Self {
+        Self::CryptoError(e)
+    }
+}
+
+/// Keyring holds the keys with which sensitive values are encrypted at rest.
+/// Values are sealed with envelope encryption: each value is encrypted with
+/// its own random data key, which is in turn encrypted with the keyring's
+/// current key. Retired keys are kept so that values sealed with them may
+/// still be opened until they are resealed.
+#[derive(Clone)]
+pub struct Keyring {
+    /// The ID of the key with which new values are sealed
+    current: String,
+
+    /// Each key held by the keyring, by ID
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Keyring({})", self.current)
+    }
+}
+
+impl Keyring {
+    /// Creates a new keyring sealing values with the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the key, which is stored alongside sealed values
+    /// * `key` - The 32-byte key
+    pub fn new(id: &str, key: Vec<u8>) -> Result<Self, KeyringError> {
+        Self::validate(id, &key)?;
+
+        let mut keys = HashMap::new();
+        keys.insert(id.to_owned(), key);
+
+        Ok(Self {
+            current: id.to_owned(),
+            keys,
+        })
+    }
+
+    /// Consumes the keyring, and adds a retired key with which values may be
+    /// opened, but not sealed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the retired key
+    /// * `key` - The 32-byte key
+    pub fn with_retired_key(mut self, id: &str, key: Vec<u8>) -> Result<Self, KeyringError> {
+        Self::validate(id, &key)?;
+
+        if id != self.current {
+            self.keys.insert(id.to_owned(), key);
+        }
+
+        Ok(self)
+    }
+
+    /// Ensures that the given key may be held by a keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the key
+    /// * `key` - The key
+    fn validate(id: &str, key: &[u8]) -> Result<(), KeyringError> {
+        if id.is_empty() || id.contains('.') || key.len() != KEY_LENGTH {
+            Err(KeyringError::InvalidKey)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gets the key with which blind indexes of sensitive values are
+    /// computed, which is derived from the keyring's current key. Blind
+    /// indexes allow sealed values to be looked up without being opened,
+    /// without allowing them to be recovered by hashing candidate values.
+    pub fn index_key(&self) -> [u8; KEY_LENGTH] {
+        derive_index_key(&self.keys[&self.current])
+    }
+
+    /// Gets the keys with which blind indexes may have been computed, which
+    /// are derived from each key held by the keyring, current key first.
+    pub fn index_keys(&self) -> Vec<[u8; KEY_LENGTH]> {
+        iter::once(&self.current)
+            .chain(self.keys.keys().filter(|id| **id != self.current))
+            .map(|id| derive_index_key(&self.keys[id]))
+            .collect()
+    }
+
+    /// Determines whether or not the given stored value was sealed by a
+    /// keyring, rather than being stored in plain text.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The stored value
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(&format!("{}.", SEALED_PREFIX))
+    }
+
+    /// Determines whether or not the given stored value should be resealed,
+    /// either because it is stored in plain text, or because it was sealed
+    /// with a retired key.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The stored value
+    pub fn needs_reseal(&self, value: &str) -> bool {
+        !Self::is_sealed(value) || value.split('.').nth(1) != Some(self.current.as_str())
+    }
+
+    /// Seals the given plain text with the keyring's current key.
+    ///
+    /// # Arguments
+    ///
+    /// * `plaintext` - The value that should be sealed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::keyring::Keyring;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let keyring = Keyring::new("primary", vec![7; 32])?;
+    /// let sealed = keyring.seal("8675309")?;
+    ///
+    /// assert_ne!(sealed, "8675309");
+    /// assert_eq!(keyring.open(&sealed)?, "8675309");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn seal(&self, plaintext: &str) -> Result<String, KeyringError> {
+        let mut data_key = vec![0; KEY_LENGTH];
+        rand_bytes(&mut data_key)?;
+
+        let wrapped_key = encrypt(
+            &self.keys[&self.current],
+            self.current.as_bytes(),
+            &data_key,
+        )?;
+        let ciphertext = encrypt(&data_key, &[], plaintext.as_bytes())?;
+
+        Ok(format!(
+            "{}.{}.{}.{}",
+            SEALED_PREFIX,
+            self.current,
+            base64::encode_config(wrapped_key, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(ciphertext, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    /// Opens the given stored value. Values stored in plain text are returned
+    /// as-is, so that values stored before encryption was enabled remain
+    /// readable.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The stored value
+    pub fn open(&self, value: &str) -> Result<String, KeyringError> {
+        if !Self::is_sealed(value) {
+            return Ok(value.to_owned());
+        }
+
+        let (key_id, wrapped_key, ciphertext) =
+            match value.split('.').collect::<Vec<_>>().as_slice() {
+                [_, key_id, wrapped_key, ciphertext] => (*key_id, *wrapped_key, *ciphertext),
+                _ => return Err(KeyringError::Malformed),
+            };
+        let key = self.keys.get(key_id).ok_or(KeyringError::UnknownKey)?;
+
+        let decode = |part: &str| {
+            base64::decode_config(part, base64::URL_SAFE_NO_PAD)
+                .map_err(|_| KeyringError::Malformed)
+        };
+
+        let data_key = decrypt(key, key_id.as_bytes(), &decode(wrapped_key)?)?;
+        let plaintext = decrypt(&data_key, &[], &decode(ciphertext)?)?;
+
+        String::from_utf8(plaintext).map_err(|_| KeyringError::Malformed)
+    }
+}
+
+impl FromStr for Keyring {
+    type Err = KeyringError;
+
+    /// Parses a keyring from a comma-separated list of keys formatted as
+    /// id:base64key (e.g. "2020-05:...,2020-04:..."). The first key is used to
+    /// seal new values, and each subsequent key is retired.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = s.split(',').map(|entry| {
+            let mut parts = entry.trim().splitn(2, ':');
+
+            match (parts.next(), parts.next()) {
+                (Some(id), Some(key)) => base64::decode(key)
+                    .map(|key| (id, key))
+                    .map_err(|_| KeyringError::InvalidKey),
+                _ => Err(KeyringError::InvalidKey),
+            }
+        });
+
+        let (id, key) = keys.next().ok_or(KeyringError::InvalidKey)??;
+
+        keys.try_fold(Self::new(id, key)?, |keyring, entry| {
+            entry.and_then(|(id, key)| keyring.with_retired_key(id, key))
+        })
+    }
+}
+
+/// Derives the key with which blind indexes are computed from the given key.
+///
+/// # Arguments
+///
+/// * `key` - The key held by the keyring
+fn derive_index_key(key: &[u8]) -> [u8; KEY_LENGTH] {
+    *blake3::Hasher::new_derive_key(INDEX_KEY_CONTEXT)
+        .update(key)
+        .finalize()
+        .as_bytes()
+}
+
+/// Encrypts the given data with AES-256-GCM, producing the nonce, ciphertext,
+/// and authentication tag concatenated.
+///
+/// # Arguments
+///
+/// * `key` - The key with which the data should be encrypted
+/// * `aad` - Additional data authenticated alongside the ciphertext
+/// * `data` - The data that should be encrypted
+fn encrypt(key: &[u8], aad: &[u8], data: &[u8]) -> Result<Vec<u8>, KeyringError> {
+    let mut nonce = vec![0; NONCE_LENGTH];
+    rand_bytes(&mut nonce)?;
+
+    let mut tag = vec![0; TAG_LENGTH];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        aad,
+        data,
+        &mut tag,
+    )?;
+
+    Ok([nonce, ciphertext, tag].concat())
+}
+
+/// Decrypts data produced by encrypt.
+///
+/// # Arguments
+///
+/// * `key` - The key with which the data was encrypted
+/// * `aad` - Additional data authenticated alongside the ciphertext
+/// * `sealed` - The nonce, ciphertext, and authentication tag concatenated
+fn decrypt(key: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, KeyringError> {
+    if sealed.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(KeyringError::Malformed);
+    }
+
+    let (nonce, rest) = sealed.split_at(NONCE_LENGTH);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LENGTH);
+
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        aad,
+        ciphertext,
+        tag,
+    )
+    .map_err(|e| e.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() -> Result<(), Box<dyn Error>> {
+        let old = Keyring::new("old", vec![1; KEY_LENGTH])?;
+        let sealed = old.seal("8675309")?;
+        assert!(!old.needs_reseal(&sealed));
+
+        // Values sealed with a retired key remain readable, but must be
+        // resealed
+        let rotated = Keyring::new("new", vec![2; KEY_LENGTH])?
+            .with_retired_key("old", vec![1; KEY_LENGTH])?;
+        assert!(rotated.needs_reseal(&sealed));
+        assert_eq!(rotated.open(&sealed)?, "8675309");
+        assert!(!rotated.needs_reseal(&rotated.seal("8675309")?));
+
+        // Once the retired key is dropped, its values can't be opened
+        let dropped = Keyring::new("new", vec![2; KEY_LENGTH])?;
+        assert!(matches!(
+            dropped.open(&sealed),
+            Err(KeyringError::UnknownKey)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_keys() -> Result<(), Box<dyn Error>> {
+        let old = Keyring::new("old", vec![1; KEY_LENGTH])?;
+        let rotated = Keyring::new("new", vec![2; KEY_LENGTH])?
+            .with_retired_key("old", vec![1; KEY_LENGTH])?;
+
+        // Index keys differ from the keys that they are derived from, and
+        // indexes computed with a retired key may still be found
+        assert_ne!(old.index_key().to_vec(), vec![1; KEY_LENGTH]);
+        assert_ne!(rotated.index_key(), old.index_key());
+        assert_eq!(
+            rotated.index_keys(),
+            vec![rotated.index_key(), old.index_key()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plaintext() -> Result<(), Box<dyn Error>> {
+        let keyring = Keyring::new("primary", vec![1; KEY_LENGTH])?;
+
+        assert!(keyring.needs_reseal("8675309"));
+        assert_eq!(keyring.open("8675309")?, "8675309");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), Box<dyn Error>> {
+        let keyring = format!(
+            "new:{},old:{}",
+            base64::encode([2; KEY_LENGTH]),
+            base64::encode([1; KEY_LENGTH])
+        )
+        .parse::<Keyring>()?;
+
+        assert_eq!(keyring.current, "new");
+        assert_eq!(keyring.keys.len(), 2);
+        assert!("new:c2hvcnQ=".parse::<Keyring>().is_err());
+
+        Ok(())
+    }
+}
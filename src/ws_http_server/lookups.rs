@@ -0,0 +1,169 @@
+use serde::Serialize;
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+};
+
+/// LookupCounts represents the outcomes of the reads sent to the caching
+/// layer by a single module.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LookupCounts {
+    /// The number of values that the caching layer held
+    hits: u64,
+
+    /// The number of values that the caching layer didn't hold
+    misses: u64,
+
+    /// The number of reads that the caching layer failed to answer, and
+    /// that fell back to the persistent layer
+    errors: u64,
+}
+
+impl LookupCounts {
+    /// Retreives the number of values that the caching layer held.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Retreives the number of values that the caching layer didn't hold.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Retreives the number of reads that the caching layer failed to
+    /// answer.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+}
+
+/// LookupMetrics represents a snapshot of the outcomes of the reads sent to
+/// the caching layer, keyed by the module that sent them (e.g. "bans").
+pub type LookupMetrics = BTreeMap<&'static str, LookupCounts>;
+
+/// CacheLookups counts the outcomes of the reads sent to the caching layer
+/// by hybrid providers, per module. Misses (i.e. values that simply aren't
+/// cached) are counted separately from errors (i.e. reads that redis failed
+/// to answer), such that a redis outage, which sends every read to the
+/// persistent layer, can be told apart from a cold cache.
+#[derive(Debug, Default)]
+pub struct CacheLookups {
+    /// The outcomes of the reads sent by each module, shared by every worker
+    modules: Mutex<LookupMetrics>,
+}
+
+impl CacheLookups {
+    /// Records the outcome of a read answered by the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module that sent the read (e.g. "bans")
+    /// * `lookup` - The value read from the caching layer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::lookups::CacheLookups;
+    ///
+    /// let lookups = CacheLookups::default();
+    /// lookups.record_hit("mutes", &vec![Some(1), None]);
+    ///
+    /// let mutes = lookups.metrics()["mutes"];
+    /// assert_eq!((mutes.hits(), mutes.misses()), (1, 1));
+    /// ```
+    pub fn record_hit(&self, module: &'static str, lookup: &impl Lookup) {
+        let (hits, misses) = lookup.outcomes();
+        let mut modules = self.modules();
+        let counts = modules.entry(module).or_default();
+
+        counts.hits = counts.hits.saturating_add(hits);
+        counts.misses = counts.misses.saturating_add(misses);
+    }
+
+    /// Records a read that the caching layer failed to answer.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module that sent the read (e.g. "bans")
+    pub fn record_error(&self, module: &'static str) {
+        let mut modules = self.modules();
+        let counts = modules.entry(module).or_default();
+
+        counts.errors = counts.errors.saturating_add(1);
+    }
+
+    /// Takes a snapshot of the outcomes of the reads sent by each module.
+    pub fn metrics(&self) -> LookupMetrics {
+        self.modules().clone()
+    }
+
+    /// Locks the counts of each module. The counts remain consistent even if
+    /// a thread panicked while holding the lock, so poisoning is ignored.
+    fn modules(&self) -> MutexGuard<'_, LookupMetrics> {
+        self.modules
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Lookup is implemented by each of the values read from the caching layer
+/// by hybrid providers, such that the values that the cache held may be
+/// told apart from those that it didn't.
+pub trait Lookup {
+    /// Determines the number of values read that the caching layer held,
+    /// and the number that it didn't, respectively.
+    fn outcomes(&self) -> (u64, u64);
+}
+
+impl<T> Lookup for Option<T> {
+    fn outcomes(&self) -> (u64, u64) {
+        match self {
+            Some(_) => (1, 0),
+            None => (0, 1),
+        }
+    }
+}
+
+impl<T> Lookup for Vec<Option<T>> {
+    fn outcomes(&self) -> (u64, u64) {
+        self.iter()
+            .map(Lookup::outcomes)
+            .fold((0, 0), |(hits, misses), (hit, miss)| {
+                (hits + hit, misses + miss)
+            })
+    }
+}
+
+impl Lookup for bool {
+    /// Flags read from the caching layer are always answered, as an absent
+    /// flag can't be told apart from an unset one.
+    fn outcomes(&self) -> (u64, u64) {
+        (1, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups() {
+        let lookups = CacheLookups::default();
+        lookups.record_hit("bans", &Some(1));
+        lookups.record_hit("bans", &None::<u64>);
+        lookups.record_hit("bans", &false);
+        lookups.record_error("bans");
+        lookups.record_error("roles");
+
+        let metrics = lookups.metrics();
+        assert_eq!(metrics["bans"].hits(), 2);
+        assert_eq!(metrics["bans"].misses(), 1);
+        assert_eq!(metrics["bans"].errors(), 1);
+
+        // Modules are counted separately
+        assert_eq!(metrics["roles"].hits(), 0);
+        assert_eq!(metrics["roles"].errors(), 1);
+        assert!(!metrics.contains_key("mutes"));
+    }
+}
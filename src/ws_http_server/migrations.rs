@@ -0,0 +1,179 @@
+use diesel_migrations::RunMigrationsError;
+use serde::de::DeserializeOwned;
+
+use super::{
+    super::spec::{backend::PersistentConnection, ban::Ban, mute::Mute},
+    modules::{Cache, ProviderError},
+};
+
+use std::io::Write;
+
+// Each of the migrations in the crate's migrations directory is compiled into
+// the binary, such that a build always carries the schema that it expects
+embed_migrations!("migrations");
+
+/// Reads a sanction cached as a JSON string by an earlier release, unless the
+/// key already holds a hash.
+///
+/// KEYS: the sanction.
+const READ_LEGACY_SANCTION_SCRIPT: &str = r"
+if redis.call('TYPE', KEYS[1]).ok == 'string' then
+    return redis.call('GET', KEYS[1])
+end
+
+return false
+";
+
+/// Replaces a sanction cached as a JSON string with a hash holding its
+/// fields, keeping its expiry. The key is left untouched if it no longer
+/// holds the string that was read (e.g. because it was replaced meanwhile).
+/// Returns whether or not the sanction was replaced.
+///
+/// KEYS: the sanction.
+/// ARGV: the string that was read, followed by the fields of the hash.
+const CONVERT_SANCTION_SCRIPT: &str = r"
+if redis.call('TYPE', KEYS[1]).ok ~= 'string' or redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+
+local ttl = redis.call('PTTL', KEYS[1])
+redis.call('DEL', KEYS[1])
+redis.call('HSET', KEYS[1], unpack(ARGV, 2))
+
+if ttl > 0 then
+    redis.call('PEXPIRE', KEYS[1], ttl)
+end
+
+return 1
+";
+
+/// Brings the database's schema up to date by running each of the
+/// embedded migrations that hasn't been run yet, in order. Migrations that
+/// were already run are skipped, so running them again is a no-op.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the persistence layer
+/// * `out` - The writer to which the name of each migration is logged as it
+/// is run
+pub fn run_pending<W: Write>(
+    conn: &PersistentConnection,
+    out: &mut W,
+) -> Result<(), RunMigrationsError> {
+    embedded_migrations::run_with_output(conn, out)
+}
+
+/// Converts each of the bans and mutes cached as JSON strings by earlier
+/// releases into the hashes that they are now cached as, returning the
+/// number of sanctions converted. Sanctions that are already cached as
+/// hashes are skipped, so converting them again is a no-op.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer holding the sanctions
+pub fn convert_cached_sanctions(cache: &mut Cache) -> Result<usize, ProviderError> {
+    let mut converted = 0;
+
+    for key in cache
+        .scan("banned::*")?
+        .into_iter()
+        .chain(cache.scan("banned_addr::*")?)
+    {
+        converted += convert_sanction(cache, &key, Ban::to_fields)? as usize;
+    }
+
+    for key in cache.scan("muted::*")? {
+        converted += convert_sanction(cache, &key, Mute::to_fields)? as usize;
+    }
+
+    Ok(converted)
+}
+
+/// Converts the sanction cached as a JSON string under the given key into a
+/// hash, returning whether or not it was converted.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer holding the sanction
+/// * `key` - The (namespaced) key of the sanction
+/// * `to_fields` - Encodes the sanction as the fields of a hash
+fn convert_sanction<T: DeserializeOwned>(
+    cache: &mut Cache,
+    key: &str,
+    to_fields: impl Fn(&T) -> Vec<(&'static str, String)>,
+) -> Result<bool, ProviderError> {
+    let raw: Option<String> = cache.eval(READ_LEGACY_SANCTION_SCRIPT, |script| {
+        script.key(key);
+    })?;
+    let raw = match raw {
+        Some(raw) => raw,
+        None => return Ok(false),
+    };
+    let fields = to_fields(&serde_json::from_str(&raw)?);
+
+    cache.eval(CONVERT_SANCTION_SCRIPT, |script| {
+        script.key(key).arg(&raw).arg(fields.as_slice());
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::modules::{
+            bans::{BanQuery, Provider as BansProvider},
+            mutes::Provider as MutesProvider,
+        },
+        *,
+    };
+    use diesel::Connection;
+
+    use std::{env, error::Error, io};
+
+    #[test]
+    fn test_run_pending() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        run_pending(&persistent_conn, &mut io::sink())?;
+
+        // Migrations that were already run should be skipped
+        run_pending(&persistent_conn, &mut io::sink())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_cached_sanctions() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        // Sanctions were once cached as JSON strings
+        let ban = Ban::new(1).with_duration(60_000_000_000);
+        let mute = Mute::new(1, 60_000_000_000);
+        redis::pipe()
+            .cmd("DEL")
+            .arg(&["legacy::banned::1", "legacy::muted::1"])
+            .cmd("SET")
+            .arg("legacy::banned::1")
+            .arg(serde_json::to_string(&ban)?)
+            .cmd("SET")
+            .arg("legacy::muted::1")
+            .arg(serde_json::to_string(&mute)?)
+            .arg("EX")
+            .arg(60)
+            .query::<()>(&mut conn)?;
+
+        let mut cache = Cache::new(&mut conn).with_prefix("legacy::");
+        assert_eq!(convert_cached_sanctions(&mut cache)?, 2);
+        assert_eq!(cache.get_ban(&BanQuery::Id(1))?, Some(ban));
+        assert_eq!(cache.get_mute(1)?, Some(mute));
+
+        // Sanctions that were already converted are skipped
+        assert_eq!(convert_cached_sanctions(&mut cache)?, 0);
+
+        Ok(())
+    }
+}
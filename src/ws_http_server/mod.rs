@@ -1,2 +1,23 @@
+pub mod auth;
+pub mod breaker;
+pub mod compression;
+pub mod config;
+pub mod dispatcher;
+pub mod etag;
+pub mod invalidation;
+pub mod jwt;
+pub mod keyring;
+pub mod lookups;
+pub mod migrations;
 pub mod modules;
+pub mod outbox;
+pub mod pagination;
+pub mod pool;
+pub mod problem;
+pub mod retry;
+pub mod sanitizer;
 pub mod server;
+pub mod shedding;
+pub mod telemetry;
+pub mod totp;
+pub mod warmup;
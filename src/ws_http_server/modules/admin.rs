@@ -0,0 +1,584 @@
+use actix_web::{
+    http::{header::RETRY_AFTER, StatusCode},
+    web::{Data, Json},
+    Error as HttpError, HttpResponse, Scope,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{
+        super::spec::{
+            announcement::{NewAnnouncement, MAX_ANNOUNCEMENT_LENGTH},
+            event::EventTarget,
+            user::Role,
+        },
+        auth::{
+            capability::{CanBan, CanManageAnnouncements, CanManageRoles, CanMute},
+            role::Administrator,
+            AuthError, Principal, RequireCapability, RequireRole,
+        },
+        problem::Problem,
+        server::State,
+    },
+    audit,
+    bans::{self, username_for},
+    connections::{Connection, Provider as ConnectionsProvider},
+    mutes,
+    name_resolver::Provider as NameResolver,
+    roles, Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The number of seconds over which the broadcasts sent by each party are
+/// counted.
+pub const BROADCAST_WINDOW: u64 = 60;
+
+/// The number of broadcasts that a single party may send within the
+/// broadcast window.
+const MAX_BROADCASTS: u64 = 5;
+
+/// The maximum number of actions that may be submitted in a single bulk
+/// moderation request.
+pub const MAX_BULK_ACTIONS: usize = 100;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the admin module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/admin")
+        .service(list_sessions)
+        .service(broadcast)
+        .service(bulk_moderation)
+}
+
+/// BroadcastRequest represents a request to send a system message to the
+/// chat.
+#[derive(Deserialize)]
+pub struct BroadcastRequest {
+    /// The message that should be sent to the chat
+    message: String,
+
+    /// (optional) The role that chatters must hold in order to receive the
+    /// message. Every chatter receives the message by default.
+    role: Option<Role>,
+}
+
+/// BulkAction represents a single moderation action submitted as part of a
+/// bulk moderation request.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Bans the user, and their address if one is provided
+    Ban {
+        user_id: u64,
+        ip: Option<String>,
+        duration: Option<u64>,
+        reason: Option<String>,
+    },
+
+    /// Lifts the ban issued against the user
+    Unban { user_id: u64 },
+
+    /// Mutes the user for the given number of nanoseconds
+    Mute {
+        user_id: u64,
+        duration: u64,
+        reason: Option<String>,
+    },
+
+    /// Lifts the mute issued against the user
+    Unmute { user_id: u64 },
+
+    /// Grants the role to the user
+    GrantRole { user_id: u64, role: Role },
+
+    /// Revokes the role from the user
+    RevokeRole { user_id: u64, role: Role },
+}
+
+impl BulkAction {
+    /// Retreives the ID of the user that the action concerns.
+    pub fn user_id(&self) -> u64 {
+        match self {
+            Self::Ban { user_id, .. }
+            | Self::Unban { user_id }
+            | Self::Mute { user_id, .. }
+            | Self::Unmute { user_id }
+            | Self::GrantRole { user_id, .. }
+            | Self::RevokeRole { user_id, .. } => *user_id,
+        }
+    }
+
+    /// Ensures that the given principal may perform the action, and that
+    /// its parameters are valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - The party performing the action
+    pub fn validate(&self, principal: &Principal) -> Result<(), HttpError> {
+        let permitted = match self {
+            Self::Ban { .. } | Self::Unban { .. } => principal.permits::<CanBan>(),
+            Self::Mute { .. } | Self::Unmute { .. } => principal.permits::<CanMute>(),
+            Self::GrantRole { .. } | Self::RevokeRole { .. } => {
+                principal.permits::<CanManageRoles>()
+            }
+        };
+        if !permitted {
+            return Err(AuthError::InsufficientPermissions.into());
+        }
+
+        match self {
+            Self::Ban { duration, ip, .. } => {
+                bans::validate_ban(*duration, ip.as_deref()).map_err(|e| e.into())
+            }
+            Self::Mute { duration: 0, .. } => {
+                Err(ProviderError::InvalidArgument { arg: "duration" }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Performs the action on behalf of the given principal, returning
+    /// whether or not it had any effect. Lifting a ban or mute that doesn't
+    /// exist has no effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The shared server state used to open connections to each
+    /// layer
+    /// * `principal` - The party performing the action
+    /// * `username` - The username of the user that the action concerns
+    pub fn apply(
+        &self,
+        state: &State,
+        principal: &Principal,
+        username: &str,
+    ) -> Result<bool, ProviderError> {
+        match self {
+            Self::Ban {
+                user_id,
+                ip,
+                duration,
+                reason,
+            } => bans::ban_user(
+                state,
+                principal,
+                *user_id,
+                username,
+                *duration,
+                ip.as_deref(),
+                reason.as_deref().unwrap_or_default(),
+            )
+            .map(|_| true),
+            Self::Unban { user_id } => bans::lift_user_ban(state, principal, *user_id, username),
+            Self::Mute {
+                user_id,
+                duration,
+                reason,
+            } => mutes::mute_user(
+                state,
+                principal,
+                *user_id,
+                username,
+                *duration,
+                reason.as_deref().unwrap_or_default(),
+            )
+            .map(|_| true),
+            Self::Unmute { user_id } => mutes::lift_user_mute(state, principal, *user_id, username),
+            Self::GrantRole { user_id, role } => {
+                roles::write_roles(state, |roles| roles.give_role(*user_id, role)).map(|_| true)
+            }
+            Self::RevokeRole { user_id, role } => {
+                roles::write_roles(state, |roles| roles.remove_role(*user_id, role)).map(|_| true)
+            }
+        }
+    }
+}
+
+/// BulkResult represents the outcome of a single action submitted as part of
+/// a bulk moderation request.
+#[derive(Serialize)]
+pub struct BulkResult {
+    /// The HTTP status with which the action would have been answered had it
+    /// been submitted on its own
+    status: u16,
+
+    /// (optional) A description of why the action failed, if it did
+    error: Option<Problem>,
+}
+
+impl BulkResult {
+    /// Creates a new result from the given outcome of an action.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - The status with which the action succeeded, or the
+    /// error with which it failed
+    fn of(outcome: Result<StatusCode, HttpError>) -> Self {
+        match outcome {
+            Ok(status) => Self {
+                status: status.as_u16(),
+                error: None,
+            },
+            Err(e) => {
+                let problem = Problem::of(&e);
+
+                Self {
+                    status: problem.status(),
+                    error: Some(problem),
+                }
+            }
+        }
+    }
+}
+
+/// BulkReport represents the response to a bulk moderation request.
+#[derive(Serialize)]
+pub struct BulkReport {
+    /// Whether or not the actions were applied. Actions are only applied if
+    /// each of them passes validation.
+    applied: bool,
+
+    /// The outcome of each action, in the order in which they were submitted
+    results: Vec<BulkResult>,
+}
+
+/// ConnectedSession represents a chat connection held open by any node,
+/// alongside the username of the user who opened it.
+#[derive(Serialize)]
+pub struct ConnectedSession {
+    /// The connection itself
+    #[serde(flatten)]
+    connection: Connection,
+
+    /// The username of the user who opened the connection, if they
+    /// authenticated and have a username
+    username: Option<String>,
+}
+
+/// Gets each of the chat connections held open by any node, oldest first.
+/// Only administrators may view connected sessions.
+#[get("/sessions")]
+pub async fn list_sessions(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+) -> Result<Json<Vec<ConnectedSession>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let connections = Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .connections(Utc::now())?;
+
+    // Anonymous connections are resolved to no username
+    let usernames = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()))
+    .usernames_for(
+        &connections
+            .iter()
+            .filter_map(Connection::user_id)
+            .collect::<Vec<u64>>(),
+    )?;
+    let mut usernames = usernames.into_iter();
+
+    Ok(Json(
+        connections
+            .into_iter()
+            .map(|connection| ConnectedSession {
+                username: connection
+                    .user_id()
+                    .and_then(|_| usernames.next().flatten()),
+                connection,
+            })
+            .collect(),
+    ))
+}
+
+/// Immediately sends the given system message to every connected chatter, or
+/// only to those holding the given role. Unlike announcements, broadcasts
+/// aren't registered, and therefore aren't repeated. Only administrators may
+/// send broadcasts, and each party may only send a handful per minute.
+#[post("/broadcast")]
+pub async fn broadcast(
+    state: Data<State>,
+    auth: RequireCapability<CanManageAnnouncements>,
+    body: Json<BroadcastRequest>,
+) -> Result<HttpResponse, HttpError> {
+    if body.message.trim().is_empty() || body.message.len() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err(ProviderError::InvalidArgument { arg: "message" }.into());
+    }
+
+    let created_by = match auth.principal() {
+        Principal::Administrator => None,
+        Principal::User(user) => Some(user.id()),
+    };
+
+    let mut conn = state.cache_connection()?;
+    let mut cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+
+    if let Some(retry_after) = cache.record_broadcast(created_by)? {
+        return Ok(HttpResponse::TooManyRequests()
+            .header(RETRY_AFTER, retry_after.to_string())
+            .finish());
+    }
+
+    // Broadcasts are never registered, so they don't hold an ID of their own
+    let now = Utc::now();
+    let announcement = NewAnnouncement::new(&body.message, now, now)
+        .with_created_by(created_by)
+        .with_id(0);
+
+    cache.publish_announcement(
+        body.role.map_or(EventTarget::All, EventTarget::Role),
+        announcement,
+    )?;
+
+    audit::record_moderation_action(
+        &state,
+        auth.principal(),
+        "broadcast",
+        &match body.role {
+            Some(role) => format!("to {}: {}", role.to_str(), body.message),
+            None => body.message.clone(),
+        },
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Applies each of the given moderation actions, so that moderator tooling
+/// may clean up after a raid in a single request. Every action is validated
+/// before any is applied: if any action is malformed, concerns a user who
+/// doesn't exist, or isn't permitted, none are applied, and the request is
+/// answered with 422 Unprocessable Entity. Otherwise, the actions are applied
+/// in order, and the outcome of each is reported. Actions can't be rolled
+/// back, so an action failing to apply doesn't undo those before it.
+#[post("/moderation/bulk")]
+pub async fn bulk_moderation(
+    state: Data<State>,
+    auth: RequireCapability<CanBan>,
+    body: Json<Vec<BulkAction>>,
+) -> Result<HttpResponse, HttpError> {
+    if body.is_empty() || body.len() > MAX_BULK_ACTIONS {
+        return Err(ProviderError::InvalidArgument { arg: "actions" }.into());
+    }
+
+    let validated = body
+        .iter()
+        .map(|action| {
+            action.validate(auth.principal())?;
+
+            username_for(&state, action.user_id())?.ok_or_else(|| {
+                Problem::new(
+                    StatusCode::NOT_FOUND,
+                    "user_not_found",
+                    "the user doesn't exist",
+                )
+                .into()
+            })
+        })
+        .collect::<Vec<Result<String, HttpError>>>();
+
+    // Actions that passed validation are reported as failing on account of
+    // the actions that didn't
+    if validated.iter().any(Result::is_err) {
+        return Ok(HttpResponse::UnprocessableEntity().json(BulkReport {
+            applied: false,
+            results: validated
+                .into_iter()
+                .map(|username| BulkResult::of(username.map(|_| StatusCode::FAILED_DEPENDENCY)))
+                .collect(),
+        }));
+    }
+
+    let results = body
+        .iter()
+        .zip(validated.into_iter().filter_map(Result::ok))
+        .map(|(action, username)| {
+            BulkResult::of(
+                action
+                    .apply(&state, auth.principal(), &username)
+                    .map(|applied| {
+                        if applied {
+                            StatusCode::NO_CONTENT
+                        } else {
+                            StatusCode::NOT_FOUND
+                        }
+                    })
+                    .map_err(HttpError::from),
+            )
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(BulkReport {
+        applied: true,
+        results,
+    }))
+}
+
+/// Provider represents an arbitrary backend for the broadcast rate limit,
+/// which counts the broadcasts sent by each party. Counters are ephemeral,
+/// and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Records a broadcast by the given party, returning the number of
+    /// seconds until the party may broadcast again if it has exceeded its
+    /// limit, in which case the broadcast should not be sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending the broadcast, or None if it
+    /// is sent with the administrative token
+    fn record_broadcast(&mut self, user_id: Option<u64>) -> Result<Option<u64>, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records a broadcast by the given party in the redis caching layer.
+    /// The counter expires at the end of the broadcast window.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending the broadcast, or None if it
+    /// is sent with the administrative token
+    fn record_broadcast(&mut self, user_id: Option<u64>) -> Result<Option<u64>, ProviderError> {
+        let key = self.key(match user_id {
+            Some(user_id) => format!("broadcasts::{}", user_id),
+            None => "broadcasts::admin".to_owned(),
+        });
+
+        let (broadcasts, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("INCR")
+            .arg(&key)
+            .cmd("TTL")
+            .arg(&key)
+            .query(self.connection)?;
+
+        // The window starts with the first broadcast sent within it
+        if ttl < 0 {
+            redis::cmd("EXPIRE")
+                .arg(&key)
+                .arg(BROADCAST_WINDOW)
+                .query::<()>(self.connection)?;
+        }
+
+        Ok(if broadcasts > MAX_BROADCASTS {
+            Some(if ttl < 0 {
+                BROADCAST_WINDOW
+            } else {
+                ttl.max(1) as u64
+            })
+        } else {
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            announcements::ANNOUNCEMENT_CHANNEL,
+            events::{self, EventRelay, Subscriber},
+        },
+        *,
+    };
+    use futures::channel::mpsc::TryRecvError;
+
+    use std::{error::Error, time::Duration};
+
+    #[test]
+    fn test_record_broadcast() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut cache = Cache::new(&mut conn).with_prefix("test_broadcasts::");
+        redis::cmd("DEL")
+            .arg(cache.key("broadcasts::69420"))
+            .query::<()>(cache.connection)?;
+
+        for _ in 0..MAX_BROADCASTS {
+            assert_eq!(cache.record_broadcast(Some(69420))?, None);
+        }
+
+        // Exceeding the limit is rejected until the window lapses
+        let retry_after = cache.record_broadcast(Some(69420))?;
+        assert!(retry_after.is_some_and(|secs| secs <= BROADCAST_WINDOW));
+
+        // Each party is limited separately
+        redis::cmd("DEL")
+            .arg(cache.key("broadcasts::admin"))
+            .query::<()>(cache.connection)?;
+        assert_eq!(cache.record_broadcast(None)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_broadcast() -> Result<(), Box<dyn Error>> {
+        let client = redis::Client::open("redis://127.0.0.1/")?;
+        let mut conn = client.get_connection()?;
+        let mut sub_conn = client.get_connection()?;
+
+        let mut pubsub = sub_conn.as_pubsub();
+        pubsub.set_read_timeout(Some(Duration::from_secs(5)))?;
+        pubsub.subscribe(format!("test_role_broadcasts::{}", ANNOUNCEMENT_CHANNEL))?;
+
+        // Broadcasts are published as the broadcast route publishes them
+        let now = Utc::now();
+        Cache::new(&mut conn)
+            .with_prefix("test_role_broadcasts::")
+            .publish_announcement(
+                EventTarget::Role(Role::Moderator),
+                NewAnnouncement::new("Hi mods", now, now).with_id(0),
+            )?;
+        let event = events::parse(&pubsub.get_message()?.get_payload::<String>()?)
+            .expect("role broadcasts should be relayed");
+
+        // Only the chatters holding the role are sent the broadcast
+        let relay = EventRelay::default();
+        let mut moderator = relay.subscribe(
+            Subscriber::new("Destiny".to_owned()).with_roles(vec![Role::Moderator]),
+            None,
+        );
+        let mut chatter = relay.subscribe(Subscriber::new("MrMouton".to_owned()), None);
+        assert!(moderator.try_recv().unwrap().starts_with(b"retry: "));
+        assert!(chatter.try_recv().unwrap().starts_with(b"retry: "));
+
+        relay.relay(event);
+        assert!(moderator.try_recv().is_ok_and(|frame| frame
+            .windows(b"Hi mods".len())
+            .any(|window| window == b"Hi mods")));
+        assert_eq!(chatter.try_recv(), Err(TryRecvError::Empty));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_action() -> Result<(), Box<dyn Error>> {
+        let actions: Vec<BulkAction> = serde_json::from_str(
+            r#"[
+                {"action": "ban", "user_id": 1, "ip": "127.0.0.1"},
+                {"action": "mute", "user_id": 2, "duration": 0},
+                {"action": "grant_role", "user_id": 3, "role": "vip"}
+            ]"#,
+        )?;
+        assert_eq!(
+            actions
+                .iter()
+                .map(BulkAction::user_id)
+                .collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+
+        // Zero-length mutes are rejected before any action is applied
+        let principal = Principal::Administrator;
+        assert!(actions[0].validate(&principal).is_ok());
+        assert!(actions[1].validate(&principal).is_err());
+        assert!(actions[2].validate(&principal).is_ok());
+
+        Ok(())
+    }
+}
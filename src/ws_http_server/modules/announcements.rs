@@ -0,0 +1,649 @@
+use actix_web::{
+    rt,
+    web::{self, Data, Json, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            announcement::{
+                is_valid_schedule, next_occurrence, Announcement, NewAnnouncement,
+                MAX_ANNOUNCEMENT_LENGTH,
+            },
+            backend::{last_insert_id, Unsigned},
+            event::{Event, EventKind, EventTarget},
+            schema::announcements,
+        },
+        auth::{capability::CanManageAnnouncements, Principal, RequireCapability},
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{slice, time::Duration};
+
+/// The redis channel on which each announcement is published when it is
+/// sent, so that it may be pushed to every connected chatter.
+pub const ANNOUNCEMENT_CHANNEL: &str = "announcements";
+
+/// The interval at which the schedule is checked for announcements that are
+/// due.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The redis hash holding each of the registered announcements, keyed by
+/// their IDs.
+const ANNOUNCEMENTS_KEY: &str = "announcements";
+
+/// The redis key holding the ID of the most recently registered
+/// announcement.
+const LAST_ID_KEY: &str = "last_announcement_id";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the announcements module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/announcements")
+        .service(list_announcements)
+        .service(get_announcement)
+        .service(create_announcement)
+        .service(delete_announcement)
+}
+
+/// Periodically sends each announcement that is due for as long as the
+/// server is running, publishing it on the announcement channel.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_announcement_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(TICK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // Announcements that fail to be taken from the schedule remain
+            // due, and are retried on the next tick
+            let _ = web::block(move || -> Result<(), ProviderError> {
+                let mut conn = state.cache_connection()?;
+                let persistent_conn = state.persistent_connection()?;
+
+                let due = Hybrid::new(
+                    Cache::new(&mut conn).with_prefix(state.key_prefix()),
+                    Persistent::new(&persistent_conn),
+                )
+                .take_due_announcements(Utc::now())?;
+
+                due.into_iter().try_for_each(|announcement| {
+                    Cache::new(&mut conn)
+                        .with_prefix(state.key_prefix())
+                        .publish_announcement(EventTarget::All, announcement)
+                })
+            })
+            .await;
+        }
+    });
+}
+
+/// AnnouncementRequest represents a request to register an announcement.
+#[derive(Deserialize)]
+pub struct AnnouncementRequest {
+    /// The message that should be sent to the chat
+    message: String,
+
+    /// (optional) The time at which the announcement should first be sent.
+    /// Repeating announcements are first sent at the next occurrence of
+    /// their schedule by default.
+    at: Option<DateTime<Utc>>,
+
+    /// (optional) The cron expression describing when the announcement
+    /// should repeat (e.g. "0 0 17 * * Fri")
+    schedule: Option<String>,
+}
+
+/// Gets each of the registered announcements. Only administrators may
+/// manage announcements.
+#[get("")]
+pub async fn list_announcements(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageAnnouncements>,
+) -> Result<Json<Vec<Announcement>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .announcements()
+    .map(Json)
+}
+
+/// Gets the announcement with the given ID. Only administrators may manage
+/// announcements.
+#[get("/{announcement_id}")]
+pub async fn get_announcement(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageAnnouncements>,
+    announcement_id: Path<u64>,
+) -> Result<Option<Json<Announcement>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .announcement(*announcement_id)
+    .map(|announcement| announcement.map(Json))
+}
+
+/// Registers an announcement, sent either once at the given time, or
+/// repeatedly according to the given cron schedule. Only administrators may
+/// manage announcements.
+#[post("")]
+pub async fn create_announcement(
+    state: Data<State>,
+    auth: RequireCapability<CanManageAnnouncements>,
+    body: Json<AnnouncementRequest>,
+) -> Result<Json<Announcement>, HttpError> {
+    if body.message.trim().is_empty() || body.message.len() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err(ProviderError::InvalidArgument { arg: "message" }.into());
+    }
+    if !body.schedule.as_deref().is_none_or(is_valid_schedule) {
+        return Err(ProviderError::InvalidArgument { arg: "schedule" }.into());
+    }
+
+    let now = Utc::now();
+    let first_run_at = body
+        .at
+        .or_else(|| {
+            body.schedule
+                .as_deref()
+                .and_then(|schedule| next_occurrence(schedule, now))
+        })
+        .ok_or(ProviderError::MissingArgument { arg: "at" })?;
+
+    let created_by = match auth.principal() {
+        Principal::Administrator => None,
+        Principal::User(user) => Some(user.id()),
+    };
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .schedule_announcement(
+        &NewAnnouncement::new(&body.message, first_run_at, now)
+            .with_schedule(body.schedule.as_deref())
+            .with_created_by(created_by),
+    )
+    .map(Json)
+    .map_err(|e| e.into())
+}
+
+/// Cancels the announcement with the given ID. Only administrators may
+/// manage announcements.
+#[delete("/{announcement_id}")]
+pub async fn delete_announcement(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageAnnouncements>,
+    announcement_id: Path<u64>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .cancel_announcement(*announcement_id)
+    .map(|cancelled| Some(HttpResponse::NoContent().finish()).filter(|_| cancelled))
+}
+
+/// Provider represents an arbitrary backend for the announcements service,
+/// which holds the schedule of announcements sent by the server.
+pub trait Provider {
+    /// Gets each of the registered announcements, ordered by their IDs.
+    fn announcements(&mut self) -> Result<Vec<Announcement>, ProviderError>;
+
+    /// Retreives the announcement with the given ID, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be obtained
+    fn announcement(&mut self, id: u64) -> Result<Option<Announcement>, ProviderError>;
+
+    /// Registers the given announcement.
+    ///
+    /// # Arguments
+    ///
+    /// * `announcement` - The announcement that should be registered
+    fn schedule_announcement(
+        &mut self,
+        announcement: &NewAnnouncement,
+    ) -> Result<Announcement, ProviderError>;
+
+    /// Cancels the announcement with the given ID, returning whether or not
+    /// it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be cancelled
+    fn cancel_announcement(&mut self, id: u64) -> Result<bool, ProviderError>;
+
+    /// Takes each of the announcements that are due at the given time from
+    /// the schedule, rescheduling those that repeat and removing the rest.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    fn take_due_announcements(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Announcement>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Stores each of the given announcements in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `announcements` - The announcements that should be stored
+    fn set_announcements(&mut self, announcements: &[Announcement]) -> Result<(), ProviderError> {
+        if announcements.is_empty() {
+            return Ok(());
+        }
+
+        let mut cmd = redis::cmd("HSET");
+        cmd.arg(self.key(ANNOUNCEMENTS_KEY));
+
+        for announcement in announcements {
+            cmd.arg(announcement.id())
+                .arg(serde_json::to_string(announcement)?);
+        }
+
+        cmd.query(self.connection).map_err(|e| e.into())
+    }
+
+    /// Publishes the given announcement as an event, so that it may be
+    /// pushed to each of the connected chatters it targets.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The chatters who should receive the announcement
+    /// * `announcement` - The announcement being sent
+    pub(crate) fn publish_announcement(
+        &mut self,
+        target: EventTarget,
+        announcement: Announcement,
+    ) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(ANNOUNCEMENT_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                target,
+                EventKind::Announcement(announcement),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets each of the announcements stored in the redis caching layer,
+    /// ordered by their IDs.
+    fn announcements(&mut self) -> Result<Vec<Announcement>, ProviderError> {
+        let mut announcements = redis::cmd("HVALS")
+            .arg(self.key(ANNOUNCEMENTS_KEY))
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|announcement| serde_json::from_str(announcement))
+            .collect::<Result<Vec<Announcement>, _>>()?;
+        announcements.sort_by_key(Announcement::id);
+
+        Ok(announcements)
+    }
+
+    /// Retreives the announcement with the given ID from the redis caching
+    /// layer, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be obtained
+    fn announcement(&mut self, id: u64) -> Result<Option<Announcement>, ProviderError> {
+        redis::cmd("HGET")
+            .arg(self.key(ANNOUNCEMENTS_KEY))
+            .arg(id)
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |announcement| {
+                serde_json::from_str(&announcement)
+                    .map(Some)
+                    .map_err(|e| e.into())
+            })
+    }
+
+    /// Registers the given announcement in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `announcement` - The announcement that should be registered
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::announcement::NewAnnouncement, ws_http_server::modules::{Cache, announcements::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut announcements = Cache::new(&mut conn);
+    /// let announcement = announcements.schedule_announcement(
+    ///     &NewAnnouncement::new("Debate at 5", Utc::now(), Utc::now()).with_schedule(Some("0 0 17 * * Fri")),
+    /// )?;
+    ///
+    /// assert_eq!(announcements.announcement(announcement.id())?, Some(announcement));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn schedule_announcement(
+        &mut self,
+        announcement: &NewAnnouncement,
+    ) -> Result<Announcement, ProviderError> {
+        let id: u64 = redis::cmd("INCR")
+            .arg(self.key(LAST_ID_KEY))
+            .query(self.connection)?;
+        let announcement = announcement.with_id(id);
+
+        self.set_announcements(slice::from_ref(&announcement))
+            .map(|_| announcement)
+    }
+
+    /// Cancels the announcement with the given ID in the redis caching
+    /// layer, returning whether or not it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be cancelled
+    fn cancel_announcement(&mut self, id: u64) -> Result<bool, ProviderError> {
+        redis::cmd("HDEL")
+            .arg(self.key(ANNOUNCEMENTS_KEY))
+            .arg(id)
+            .query::<u64>(self.connection)
+            .map(|removed| removed > 0)
+            .map_err(|e| e.into())
+    }
+
+    /// Takes each of the announcements that are due at the given time from
+    /// the redis caching layer, rescheduling those that repeat and removing
+    /// the rest.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    fn take_due_announcements(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Announcement>, ProviderError> {
+        let due = self
+            .announcements()?
+            .into_iter()
+            .filter(|announcement| announcement.is_due(now))
+            .collect::<Vec<Announcement>>();
+
+        for announcement in &due {
+            match announcement.rescheduled(now) {
+                Some(next) => self.set_announcements(slice::from_ref(&next))?,
+                None => {
+                    self.cancel_announcement(announcement.id())?;
+                }
+            }
+        }
+
+        Ok(due)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets each of the announcements stored in the MySQL database, ordered
+    /// by their IDs.
+    fn announcements(&mut self) -> Result<Vec<Announcement>, ProviderError> {
+        announcements::table
+            .order(announcements::dsl::id.asc())
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the announcement with the given ID from the MySQL database,
+    /// if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be obtained
+    fn announcement(&mut self, id: u64) -> Result<Option<Announcement>, ProviderError> {
+        announcements::table
+            .find(Unsigned(id))
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Records the given announcement in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `announcement` - The announcement that should be registered
+    fn schedule_announcement(
+        &mut self,
+        announcement: &NewAnnouncement,
+    ) -> Result<Announcement, ProviderError> {
+        self.connection.transaction(|| {
+            diesel::insert_into(announcements::table)
+                .values(announcement)
+                .execute(self.connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched separately
+            last_insert_id(self.connection)
+                .map(|id| announcement.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Deletes the announcement with the given ID from the MySQL database,
+    /// returning whether or not it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be cancelled
+    fn cancel_announcement(&mut self, id: u64) -> Result<bool, ProviderError> {
+        diesel::delete(announcements::table.find(Unsigned(id)))
+            .execute(self.connection)
+            .map(|removed| removed > 0)
+            .map_err(|e| e.into())
+    }
+
+    /// Takes each of the announcements that are due at the given time from
+    /// the MySQL database. Due announcements are locked while they are taken,
+    /// so that each announcement is only sent once when several servers share
+    /// the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    fn take_due_announcements(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Announcement>, ProviderError> {
+        self.connection.transaction(|| {
+            let due: Vec<Announcement> = announcements::table
+                .filter(announcements::dsl::next_run_at.le(now.naive_utc()))
+                .load(self.connection)?;
+
+            for announcement in &due {
+                match announcement.rescheduled(now) {
+                    Some(next) => diesel::update(announcements::table.find(Unsigned(next.id())))
+                        .set(announcements::dsl::next_run_at.eq(next.next_run_at().naive_utc()))
+                        .execute(self.connection)?,
+                    None => diesel::delete(announcements::table.find(Unsigned(announcement.id())))
+                        .execute(self.connection)?,
+                };
+            }
+
+            Ok(due)
+        })
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets each of the registered announcements, populating the cache from
+    /// the persistent layer if it holds none.
+    fn announcements(&mut self) -> Result<Vec<Announcement>, ProviderError> {
+        match self.cache.announcements() {
+            Ok(announcements) if !announcements.is_empty() => Ok(announcements),
+            _ => self.persistent.announcements().and_then(|announcements| {
+                self.cache
+                    .set_announcements(&announcements)
+                    .map(|_| announcements)
+            }),
+        }
+    }
+
+    /// Retreives the announcement with the given ID, populating the cache
+    /// from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be obtained
+    fn announcement(&mut self, id: u64) -> Result<Option<Announcement>, ProviderError> {
+        match self.cache.announcement(id) {
+            Ok(Some(announcement)) => Ok(Some(announcement)),
+            _ => self.persistent.announcement(id).and_then(|announcement| {
+                announcement.map_or(Ok(None), |announcement| {
+                    self.cache
+                        .set_announcements(slice::from_ref(&announcement))
+                        .map(|_| Some(announcement))
+                })
+            }),
+        }
+    }
+
+    /// Registers the given announcement in the persistent layer, and caches
+    /// it under the ID that it was assigned.
+    ///
+    /// # Arguments
+    ///
+    /// * `announcement` - The announcement that should be registered
+    fn schedule_announcement(
+        &mut self,
+        announcement: &NewAnnouncement,
+    ) -> Result<Announcement, ProviderError> {
+        let announcement = self.persistent.schedule_announcement(announcement)?;
+
+        self.cache
+            .set_announcements(slice::from_ref(&announcement))
+            .map(|_| announcement)
+    }
+
+    /// Cancels the announcement with the given ID in the active provider,
+    /// returning whether or not it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the announcement that should be cancelled
+    fn cancel_announcement(&mut self, id: u64) -> Result<bool, ProviderError> {
+        let cancelled = self.persistent.cancel_announcement(id)?;
+        self.cache.cancel_announcement(id)?;
+
+        Ok(cancelled)
+    }
+
+    /// Takes each of the announcements that are due at the given time from
+    /// the persistent layer, which is the source of truth for the schedule,
+    /// and mirrors the resulting changes in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    fn take_due_announcements(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Announcement>, ProviderError> {
+        let due = self.persistent.take_due_announcements(now)?;
+
+        for announcement in &due {
+            match announcement.rescheduled(now) {
+                Some(next) => self.cache.set_announcements(slice::from_ref(&next))?,
+                None => {
+                    self.cache.cancel_announcement(announcement.id())?;
+                }
+            }
+        }
+
+        Ok(due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use chrono::Duration;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut announcements =
+            Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+
+        let now = Utc::now();
+        let once =
+            announcements.schedule_announcement(&NewAnnouncement::new("Debate at 5", now, now))?;
+        let repeating = announcements.schedule_announcement(
+            &NewAnnouncement::new("Stream starting", now, now).with_schedule(Some("0 0 * * * *")),
+        )?;
+        let later = announcements.schedule_announcement(&NewAnnouncement::new(
+            "Later",
+            now + Duration::hours(1),
+            now,
+        ))?;
+        assert_eq!(
+            announcements.announcement(repeating.id())?,
+            Some(repeating.clone())
+        );
+
+        // Only announcements that are due are taken, and repeating
+        // announcements remain scheduled
+        let due = announcements.take_due_announcements(now)?;
+        assert!(due.contains(&once) && due.contains(&repeating) && !due.contains(&later));
+        assert_eq!(announcements.announcement(once.id())?, None);
+        assert!(announcements
+            .announcement(repeating.id())?
+            .is_some_and(|announcement| !announcement.is_due(now)));
+
+        assert!(announcements.cancel_announcement(repeating.id())?);
+        assert!(announcements.cancel_announcement(later.id())?);
+        assert!(!announcements.cancel_announcement(later.id())?);
+
+        Ok(())
+    }
+}
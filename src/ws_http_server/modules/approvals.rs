@@ -0,0 +1,308 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    HttpResponse, Scope,
+};
+
+use super::{
+    super::{
+        super::spec::{
+            approval::{HeldMessage, NewHeldMessage},
+            event::Event,
+        },
+        auth::{capability::CanApproveMessages, RequireCapability},
+        dispatcher::DispatchError,
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The redis channel on which the events produced by each approved message
+/// are published, so that they may be pushed to the chatters they concern.
+pub const APPROVAL_CHANNEL: &str = "approved_messages";
+
+/// The redis hash holding each of the messages awaiting approval, keyed by
+/// their IDs.
+const HELD_MESSAGES_KEY: &str = "held_messages";
+
+/// The redis key holding the ID of the most recently held message.
+const LAST_ID_KEY: &str = "last_held_message_id";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the approvals module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/approvals")
+        .service(list_held_messages)
+        .service(approve_message)
+        .service(reject_message)
+}
+
+/// Gets each of the messages awaiting approval. Only moderators may approve
+/// or reject held messages.
+#[get("")]
+pub async fn list_held_messages(
+    state: Data<State>,
+    _auth: RequireCapability<CanApproveMessages>,
+) -> Result<Json<Vec<HeldMessage>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .held_messages()
+    .map(Json)
+}
+
+/// Approves the held message with the given ID, broadcasting it to the
+/// entire chat. Only moderators may approve or reject held messages.
+#[post("/{message_id}")]
+pub async fn approve_message(
+    state: Data<State>,
+    _auth: RequireCapability<CanApproveMessages>,
+    message_id: Path<u64>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let held = match Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .take_held_message(*message_id)?
+    {
+        Some(held) => held,
+        None => return Ok(None),
+    };
+
+    let events = match state
+        .dispatcher()
+        .release(&mut conn, &persistent_conn, &held)
+    {
+        Ok(events) => events,
+        Err(DispatchError::ProviderError(e)) => return Err(e),
+
+        // The sender's account no longer exists, so there is no one to
+        // attribute the message to
+        Err(_) => return Ok(None),
+    };
+
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .publish_approved(&events)
+        .map(|_| Some(HttpResponse::NoContent().finish()))
+}
+
+/// Rejects the held message with the given ID, discarding it without it
+/// ever being seen by the chat. Only moderators may approve or reject held
+/// messages.
+#[delete("/{message_id}")]
+pub async fn reject_message(
+    state: Data<State>,
+    _auth: RequireCapability<CanApproveMessages>,
+    message_id: Path<u64>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .take_held_message(*message_id)
+    .map(|held| held.map(|_| HttpResponse::NoContent().finish()))
+}
+
+/// Provider represents an arbitrary backend for the approvals service, which
+/// holds the first messages sent by chatters while the chat is in safe mode
+/// until a moderator approves or rejects them. Held messages are ephemeral,
+/// and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Holds the given message until it is approved or rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message that should be held
+    fn hold_message(&mut self, msg: &NewHeldMessage) -> Result<HeldMessage, ProviderError>;
+
+    /// Gets each of the messages awaiting approval, ordered by their IDs.
+    fn held_messages(&mut self) -> Result<Vec<HeldMessage>, ProviderError>;
+
+    /// Removes the held message with the given ID, returning it if it
+    /// existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the held message that should be taken
+    fn take_held_message(&mut self, id: u64) -> Result<Option<HeldMessage>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes each of the events produced by an approved message, so that
+    /// they may be pushed to the chatters they concern.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events produced by the approved message
+    fn publish_approved(&mut self, events: &[Event]) -> Result<(), ProviderError> {
+        let mut pipe = redis::pipe();
+
+        for event in events {
+            pipe.cmd("PUBLISH")
+                .arg(self.key(APPROVAL_CHANNEL))
+                .arg(serde_json::to_string(event)?)
+                .ignore();
+        }
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Holds the given message in the redis caching layer until it is
+    /// approved or rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message that should be held
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::approval::NewHeldMessage, ws_http_server::modules::{Cache, approvals::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut approvals = Cache::new(&mut conn);
+    /// let held = approvals.hold_message(&NewHeldMessage::new(69420, "essaywriter", "first time chatter", Utc::now()))?;
+    ///
+    /// assert_eq!(approvals.take_held_message(held.id())?, Some(held));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn hold_message(&mut self, msg: &NewHeldMessage) -> Result<HeldMessage, ProviderError> {
+        let id: u64 = redis::cmd("INCR")
+            .arg(self.key(LAST_ID_KEY))
+            .query(self.connection)?;
+        let held = msg.with_id(id);
+
+        redis::cmd("HSET")
+            .arg(self.key(HELD_MESSAGES_KEY))
+            .arg(id)
+            .arg(serde_json::to_string(&held)?)
+            .query::<()>(self.connection)?;
+
+        Ok(held)
+    }
+
+    /// Gets each of the messages awaiting approval in the redis caching
+    /// layer, ordered by their IDs.
+    fn held_messages(&mut self) -> Result<Vec<HeldMessage>, ProviderError> {
+        let mut held = redis::cmd("HVALS")
+            .arg(self.key(HELD_MESSAGES_KEY))
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|msg| serde_json::from_str(msg))
+            .collect::<Result<Vec<HeldMessage>, _>>()?;
+        held.sort_by_key(HeldMessage::id);
+
+        Ok(held)
+    }
+
+    /// Removes the held message with the given ID from the redis caching
+    /// layer, returning it if it existed. The message is read and removed
+    /// atomically, so that it is only ever approved or rejected once.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the held message that should be taken
+    fn take_held_message(&mut self, id: u64) -> Result<Option<HeldMessage>, ProviderError> {
+        let (held, removed): (Option<String>, u64) = redis::pipe()
+            .atomic()
+            .cmd("HGET")
+            .arg(self.key(HELD_MESSAGES_KEY))
+            .arg(id)
+            .cmd("HDEL")
+            .arg(self.key(HELD_MESSAGES_KEY))
+            .arg(id)
+            .query(self.connection)?;
+
+        held.filter(|_| removed > 0).map_or(Ok(None), |held| {
+            serde_json::from_str(&held).map(Some).map_err(|e| e.into())
+        })
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Holds the given message in the caching layer until it is approved or
+    /// rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message that should be held
+    fn hold_message(&mut self, msg: &NewHeldMessage) -> Result<HeldMessage, ProviderError> {
+        self.cache.hold_message(msg)
+    }
+
+    /// Gets each of the messages awaiting approval in the caching layer,
+    /// ordered by their IDs.
+    fn held_messages(&mut self) -> Result<Vec<HeldMessage>, ProviderError> {
+        self.cache.held_messages()
+    }
+
+    /// Removes the held message with the given ID from the caching layer,
+    /// returning it if it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the held message that should be taken
+    fn take_held_message(&mut self, id: u64) -> Result<Option<HeldMessage>, ProviderError> {
+        self.cache.take_held_message(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_approvals() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut approvals = Cache::new(&mut conn);
+        let first = approvals.hold_message(&NewHeldMessage::new(
+            69420,
+            "essaywriter",
+            "first time chatter",
+            Utc::now(),
+        ))?;
+        let second = approvals.hold_message(&NewHeldMessage::new(
+            42069,
+            "MrMouton",
+            "long time listener",
+            Utc::now(),
+        ))?;
+
+        let held = approvals.held_messages()?;
+        assert!(held.contains(&first) && held.contains(&second));
+
+        // A held message may only be taken once
+        assert_eq!(
+            approvals.take_held_message(first.id())?,
+            Some(first.clone())
+        );
+        assert_eq!(approvals.take_held_message(first.id())?, None);
+        assert!(!approvals.held_messages()?.contains(&first));
+
+        approvals.take_held_message(second.id())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use actix_web::{
+    http::StatusCode,
+    web::{Data, Json, Query},
+    HttpRequest, Scope,
+};
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            audit::{AuditEntry, NewAuditEntry},
+            backend::Unsigned,
+            schema::audit_log,
+        },
+        auth::{self, role::Administrator, Principal, RequireRole},
+        pagination::{Page, Pagination, Sort},
+        server::State,
+    },
+    Persistent, ProviderError,
+};
+
+/// The number of entries returned by the audit log route if no limit is
+/// specified.
+const DEFAULT_LIMIT: usize = 50;
+
+/// The maximum number of entries that may be returned by the audit log route.
+const MAX_LIMIT: usize = 500;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the audit module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/audit").service(list_entries)
+}
+
+/// AuditQuery represents the filters accepted by the audit log route,
+/// alongside its pagination parameters.
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    /// (optional) The ID of a user whose actions, or whose impersonations of
+    /// other users, should be returned
+    user_id: Option<u64>,
+}
+
+/// Gets a page of entries in the audit log, most recent first unless sorted
+/// in ascending order. Entries are paged by their IDs. Only administrators
+/// may view the audit log.
+#[get("")]
+pub async fn list_entries(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+    pagination: Pagination,
+    query: Query<AuditQuery>,
+) -> Result<Json<Page<AuditEntry>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+    let limit = pagination.limit(DEFAULT_LIMIT, MAX_LIMIT);
+
+    Persistent::new(&persistent_conn)
+        .audit_entries(
+            query.user_id,
+            pagination.cursor()?,
+            pagination.sort(Sort::Desc),
+            limit,
+        )
+        .map(|entries| Json(Page::new(entries, limit, AuditEntry::id)))
+}
+
+/// Records the given request in the audit log if it was made with a token
+/// issued for impersonation, attributing it to both the impersonated user and
+/// the administrator acting as them.
+///
+/// # Arguments
+///
+/// * `req` - The request that was answered
+/// * `status` - The status with which the request was answered
+pub(crate) fn record_impersonated_request(
+    req: &HttpRequest,
+    status: StatusCode,
+) -> Result<(), ProviderError> {
+    let state = match req.app_data::<Data<State>>() {
+        Some(state) => state,
+        None => return Ok(()),
+    };
+
+    // Requests carrying invalid tokens weren't performed on anyone's behalf
+    let claims =
+        match auth::bearer_token(req).and_then(|token| state.signing_keys().verify(token).ok()) {
+            Some(claims) => claims,
+            None => return Ok(()),
+        };
+
+    let impersonator = match claims.impersonator() {
+        Some(impersonator) => impersonator,
+        None => return Ok(()),
+    };
+
+    let action = format!("{} {}", req.method(), req.path());
+    let persistent_conn = state.persistent_connection()?;
+
+    Persistent::new(&persistent_conn).record_action(
+        &NewAuditEntry::new(Some(claims.user_id()), Some(impersonator), &action)
+            .with_status(status.as_u16()),
+    )
+}
+
+/// Records the given moderation action in the audit log, attributing it to
+/// the principal who performed it, and to the administrator impersonating
+/// them, if any. Moderation actions are applied before they are recorded, so
+/// failing to record one is logged rather than reported.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open a connection to the
+/// persistent layer
+/// * `principal` - The party who performed the action
+/// * `action` - A short description of the action (e.g. "ban.create")
+/// * `detail` - Context provided for the action (e.g. its reason)
+pub(crate) fn record_moderation_action(
+    state: &State,
+    principal: &Principal,
+    action: &str,
+    detail: &str,
+) {
+    let (actor_id, impersonator_id) = match principal {
+        Principal::Administrator => (None, None),
+        Principal::User(user) => (Some(user.id()), user.impersonator()),
+    };
+
+    if let Err(e) = state.persistent_connection().and_then(|persistent_conn| {
+        Persistent::new(&persistent_conn).record_action(
+            &NewAuditEntry::new(actor_id, impersonator_id, action).with_detail(detail),
+        )
+    }) {
+        tracing::warn!(error = %e, action, "unable to record the moderation action");
+    }
+}
+
+/// Provider represents an arbitrary backend for the audit log. Entries must
+/// never be lost, and are therefore only stored in the persistent layer.
+pub trait Provider {
+    /// Records the given action in the audit log.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The action that should be recorded
+    fn record_action(&mut self, entry: &NewAuditEntry) -> Result<(), ProviderError>;
+
+    /// Gets up to `limit` entries in the audit log following the given
+    /// cursor, in the given order.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - (optional) The ID of a user who must have either
+    /// performed each action, or impersonated the user who performed it
+    /// * `cursor` - (optional) The ID of the entry that each returned entry
+    /// must follow
+    /// * `sort` - The order in which entries should be returned
+    /// * `limit` - The maximum number of entries that should be returned
+    fn audit_entries(
+        &mut self,
+        user_id: Option<u64>,
+        cursor: Option<u64>,
+        sort: Sort,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, ProviderError>;
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Records the given action in the MySQL audit log.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The action that should be recorded
+    fn record_action(&mut self, entry: &NewAuditEntry) -> Result<(), ProviderError> {
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Gets up to `limit` entries in the MySQL audit log following the given
+    /// cursor, in the given order.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - (optional) The ID of a user who must have either
+    /// performed each action, or impersonated the user who performed it
+    /// * `cursor` - (optional) The ID of the entry that each returned entry
+    /// must follow
+    /// * `sort` - The order in which entries should be returned
+    /// * `limit` - The maximum number of entries that should be returned
+    fn audit_entries(
+        &mut self,
+        user_id: Option<u64>,
+        cursor: Option<u64>,
+        sort: Sort,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, ProviderError> {
+        let mut query = audit_log::dsl::audit_log.limit(limit as i64).into_boxed();
+
+        query = match (sort, cursor) {
+            (Sort::Asc, Some(cursor)) => query
+                .filter(audit_log::dsl::id.gt(Unsigned(cursor)))
+                .order(audit_log::dsl::id.asc()),
+            (Sort::Asc, None) => query.order(audit_log::dsl::id.asc()),
+            (Sort::Desc, Some(cursor)) => query
+                .filter(audit_log::dsl::id.lt(Unsigned(cursor)))
+                .order(audit_log::dsl::id.desc()),
+            (Sort::Desc, None) => query.order(audit_log::dsl::id.desc()),
+        };
+
+        if let Some(user_id) = user_id {
+            query = query.filter(
+                audit_log::dsl::actor_id
+                    .eq(Some(user_id))
+                    .or(audit_log::dsl::impersonator_id.eq(Some(user_id))),
+            );
+        }
+
+        query.load(self.connection).map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_persistent() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+        let mut audit = Persistent::new(&conn);
+
+        audit.record_action(
+            &NewAuditEntry::new(Some(42069), Some(69420), "GET /sessions").with_status(200),
+        )?;
+
+        let entries = audit.audit_entries(Some(69420), None, Sort::Desc, 1)?;
+        assert_eq!(entries[0].actor_id(), Some(42069));
+        assert_eq!(entries[0].impersonator_id(), Some(69420));
+        assert_eq!(entries[0].action(), "GET /sessions");
+        assert_eq!(entries[0].status(), Some(200));
+
+        // Nothing follows the most recent entry
+        assert!(audit
+            .audit_entries(Some(69420), Some(entries[0].id()), Sort::Asc, 1)?
+            .is_empty());
+
+        Ok(())
+    }
+}
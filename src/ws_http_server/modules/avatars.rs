@@ -0,0 +1,183 @@
+use actix_web::{
+    error::BlockingError,
+    http::header::CONTENT_TYPE,
+    web::{self, Bytes, Data, Json, Path},
+    Error as HttpError, HttpRequest, HttpResponse, Scope,
+};
+
+use super::{
+    super::{super::spec::user::User, auth::Principal, server::State},
+    users::{self, Provider as UsersProvider},
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{fs, io::ErrorKind};
+
+/// The maximum size of an uploaded avatar, in bytes.
+pub const MAX_AVATAR_SIZE: usize = 256 * 1024;
+
+/// Each of the image formats accepted as avatars, alongside their extension
+/// and the leading bytes of a file in the format.
+const AVATAR_FORMATS: &[(&str, &str, &[u8])] = &[
+    ("image/png", "png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", "jpg", b"\xff\xd8\xff"),
+    ("image/gif", "gif", b"GIF8"),
+    ("image/webp", "webp", b"RIFF"),
+];
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the avatars module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/avatars")
+        .service(upload_avatar)
+        .service(download_avatar)
+}
+
+/// Replaces the avatar of the specified user with the image provided in the
+/// request body, responding with the updated profile. Users may only change
+/// their own avatars, unless they are an administrator.
+#[put("/{user_id}")]
+pub async fn upload_avatar(
+    state: Data<State>,
+    principal: Principal,
+    req: HttpRequest,
+    user_id: Path<u64>,
+    body: Bytes,
+) -> Result<Option<Json<User>>, HttpError> {
+    let user_id = *user_id;
+
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|header| header.to_str().ok())
+        .unwrap_or_default();
+    let ext = validate_avatar(content_type, &body)?;
+    users::authorize_edit(&principal, user_id)?;
+
+    let user = {
+        let mut conn = state.cache_connection()?;
+        let persistent_conn = state.persistent_connection()?;
+
+        match Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        )
+        .get_user(user_id)?
+        {
+            Some(user) => user,
+            None => return Ok(None),
+        }
+    };
+
+    // Avatars are keyed by their owner and contents, so that they may be
+    // cached indefinitely by clients
+    let key = format!("{}-{}.{}", user_id, blake3::hash(&body).to_hex(), ext);
+
+    let dir = state.avatar_dir().clone();
+    let path = dir.join(&key);
+    web::block(move || fs::create_dir_all(&dir).and_then(|_| fs::write(path, body))).await?;
+
+    let previous = user.avatar().map(|avatar| avatar.to_owned());
+    let user = user.with_avatar(Some(key.clone()));
+
+    {
+        let mut conn = state.cache_connection()?;
+        let persistent_conn = state.persistent_connection()?;
+
+        Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        )
+        .set_user(&user)?;
+    }
+
+    if let Some(previous) = previous.filter(|previous| *previous != key) {
+        remove_avatar(&state, previous).await?;
+    }
+
+    Ok(Some(Json(user)))
+}
+
+/// Gets the avatar image with the given key.
+#[get("/{key}")]
+pub async fn download_avatar(
+    state: Data<State>,
+    key: Path<String>,
+) -> Result<HttpResponse, HttpError> {
+    let key = key.into_inner();
+
+    // Keys are only ever generated by the server, so anything else can't be
+    // an avatar (and might escape the avatar directory)
+    let content_type = match key.rsplit('.').next().and_then(|ext| {
+        AVATAR_FORMATS
+            .iter()
+            .find(|(_, format_ext, _)| *format_ext == ext)
+    }) {
+        Some((content_type, _, _)) if is_valid_key(&key) => *content_type,
+        _ => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let path = state.avatar_dir().join(key);
+
+    match web::block(move || fs::read(path)).await {
+        Ok(image) => Ok(HttpResponse::Ok().content_type(content_type).body(image)),
+        Err(BlockingError::Error(e)) if e.kind() == ErrorKind::NotFound => {
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Deletes the avatar image with the given key from the avatar directory, if
+/// it exists.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `key` - The key of the avatar that should be deleted
+pub(crate) async fn remove_avatar(state: &Data<State>, key: String) -> Result<(), HttpError> {
+    if !is_valid_key(&key) {
+        return Ok(());
+    }
+
+    let path = state.avatar_dir().join(key);
+
+    match web::block(move || fs::remove_file(path)).await {
+        Err(BlockingError::Error(e)) if e.kind() != ErrorKind::NotFound => {
+            Err(BlockingError::Error(e).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Ensures that the given image is an acceptable avatar, returning the
+/// extension that the image should be stored with.
+///
+/// # Arguments
+///
+/// * `content_type` - The content type declared by the uploader
+/// * `image` - The contents of the image
+pub fn validate_avatar(content_type: &str, image: &[u8]) -> Result<&'static str, ProviderError> {
+    if image.is_empty() || image.len() > MAX_AVATAR_SIZE {
+        return Err(ProviderError::InvalidArgument { arg: "avatar" });
+    }
+
+    AVATAR_FORMATS
+        .iter()
+        .find(|(format, _, magic)| *format == content_type && image.starts_with(magic))
+        .map(|(_, ext, _)| *ext)
+        .ok_or(ProviderError::InvalidArgument { arg: "avatar" })
+}
+
+/// Determines whether or not the given string could be a key generated for an
+/// uploaded avatar.
+///
+/// # Arguments
+///
+/// * `key` - The key that should be checked
+fn is_valid_key(key: &str) -> bool {
+    !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
@@ -1,34 +1,356 @@
 use actix_web::{
-    web::{Data, HttpRequest, Json, Path},
-    Scope,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Scope,
 };
 use chrono::Utc;
-use diesel::{result::Error as DieselError, ExpressionMethods, QueryDsl, RunQueryDsl};
-use redis::RedisError;
+use diesel::{
+    dsl::sql, result::Error as DieselError, sql_types::Bool, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
+use serde::Deserialize;
+use tracing::instrument;
 
 use super::{
-    super::super::spec::{
-        ban::{Ban, NewBan},
-        schema::bans,
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            ban::{Ban, NewBan},
+            event::{Ban as BanEvent, Event, EventKind, EventTarget, Unban},
+            schema::bans,
+        },
+        auth::{capability::CanBan, Principal, RequireCapability},
+        breaker,
+        outbox::{PendingWrite, Unavailable},
+        pagination::{Page, Pagination, Sort},
+        server::State,
     },
-    Cache, Persistent, ProviderError, Hybrid
+    audit,
+    name_resolver::Provider as NameResolver,
+    write_both, Cache, Hybrid, Layers, Memory, Persistent, ProviderError,
 };
 
+use std::{collections::HashMap, net::IpAddr};
+
+/// The number of bans returned by the ban list route if no limit is
+/// specified.
+const DEFAULT_PAGE_LENGTH: usize = 50;
+
+/// The maximum number of bans that may be returned by the ban list route.
+const MAX_PAGE_LENGTH: usize = 200;
+
+/// The condition satisfied by the bans that haven't lapsed. Durations are
+/// stored in nanoseconds, and converted to the fractional days counted by
+/// julianday.
+const UNLAPSED: &str =
+    "(duration IS NULL OR julianday(initiated_at) + duration / 86400000000000.0 > julianday('now'))";
+
+/// The redis channel on which the events produced by moderation actions
+/// taken over HTTP are published, so that they may be pushed to connected
+/// chatters.
+pub const MODERATION_CHANNEL: &str = "moderation";
+
 /// Builds an actix service group encompassing each of the HTTP routes
 /// designated by the bans module.
 pub(crate) fn build_service_group() -> Scope {
     Scope::new("/bans")
+        .service(list_bans)
+        .service(user_ban)
+        .service(create_ban)
+        .service(lift_ban)
+}
+
+/// BanRequest represents a request to ban a user.
+#[derive(Deserialize)]
+pub struct BanRequest {
+    /// The ID of the user who should be banned
+    user_id: u64,
+
+    /// (optional) The IP address that should be banned alongside the user
+    ip: Option<String>,
+
+    /// (optional) The number of nanoseconds that the ban should be active
+    /// for. Bans without a duration are permanent.
+    duration: Option<u64>,
+
+    /// (optional) Why the user is being banned
+    reason: Option<String>,
+}
+
+/// BansQuery represents the filters accepted by the ban list route, alongside
+/// its pagination parameters.
+#[derive(Deserialize)]
+pub struct BansQuery {
+    /// (optional) Whether or not bans that have lapsed, but haven't been
+    /// lifted, should be returned
+    #[serde(default)]
+    include_lapsed: bool,
+}
+
+/// Gets a page of bans, ordered by the IDs of the banned users, which are
+/// used as cursors. Only users permitted to ban may view bans.
+#[get("")]
+pub async fn list_bans(
+    state: Data<State>,
+    _auth: RequireCapability<CanBan>,
+    pagination: Pagination,
+    query: Query<BansQuery>,
+) -> Result<Json<Page<Ban>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+    let limit = pagination.limit(DEFAULT_PAGE_LENGTH, MAX_PAGE_LENGTH);
+
+    Persistent::new(&persistent_conn)
+        .with_replica(replica_conn.as_deref())
+        .bans(
+            pagination.cursor()?,
+            pagination.sort(Sort::Asc),
+            query.include_lapsed,
+            limit,
+        )
+        .map(|bans| Json(Page::new(bans, limit, Ban::concerns)))
+}
+
+/// Gets the active ban issued against the specified user. Responds with 404
+/// Not Found if the user isn't banned. Only users permitted to ban may view
+/// bans.
+#[get("/{user_id}")]
+pub async fn user_ban(
+    state: Data<State>,
+    _auth: RequireCapability<CanBan>,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let ban = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()))
+    .get_ban(&BanQuery::Id(*user_id))?;
+
+    // Lapsed bans remain persisted until they are lifted
+    Ok(match ban.filter(Ban::active) {
+        Some(ban) => HttpResponse::Ok().json(ban),
+        None => HttpResponse::NotFound().finish(),
+    })
 }
 
-/// Gets a list of bans corresponding to the specified user.
-/*#[get("/{user_id}")]
-pub async fn user_bans<'a>(
-    bans: Data<Hybrid<'a>>,
-    req: HttpRequest,
-    user_id: Path<u32>,
-) -> Result<Json<Vec<Ban>>, ProviderError> {
+/// Bans the specified user, and their address if one is provided, notifying
+/// every connected chatter. Banning a user who is already banned replaces
+/// their ban. Responds with 404 Not Found if the user doesn't exist. Only
+/// users permitted to ban may ban other users.
+#[post("")]
+pub async fn create_ban(
+    state: Data<State>,
+    auth: RequireCapability<CanBan>,
+    body: Json<BanRequest>,
+) -> Result<HttpResponse, ProviderError> {
+    validate_ban(body.duration, body.ip.as_deref())?;
+
+    let username = match username_for(&state, body.user_id)? {
+        Some(username) => username,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
 
-}*/
+    ban_user(
+        &state,
+        auth.principal(),
+        body.user_id,
+        &username,
+        body.duration,
+        body.ip.as_deref(),
+        body.reason.as_deref().unwrap_or_default(),
+    )
+    .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Lifts the ban issued against the specified user, notifying every
+/// connected chatter. Responds with 404 Not Found if the user isn't banned.
+/// Only users permitted to ban may lift bans.
+#[delete("/{user_id}")]
+pub async fn lift_ban(
+    state: Data<State>,
+    auth: RequireCapability<CanBan>,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let username = match username_for(&state, *user_id)? {
+        Some(username) => username,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    Ok(
+        if lift_user_ban(&state, auth.principal(), *user_id, &username)? {
+            HttpResponse::NoContent().finish()
+        } else {
+            HttpResponse::NotFound().finish()
+        },
+    )
+}
+
+/// Ensures that a ban with the given parameters may be issued.
+///
+/// # Arguments
+///
+/// * `duration` - (optional) The number of nanoseconds that the ban should
+/// be active for
+/// * `ip` - (optional) The IP address that should be banned alongside the
+/// user
+pub(crate) fn validate_ban(duration: Option<u64>, ip: Option<&str>) -> Result<(), ProviderError> {
+    if duration == Some(0) {
+        return Err(ProviderError::InvalidArgument { arg: "duration" });
+    }
+    if ip.is_some_and(|ip| ip.parse::<IpAddr>().is_err()) {
+        return Err(ProviderError::InvalidArgument { arg: "ip" });
+    }
+
+    Ok(())
+}
+
+/// Bans the given user, notifying every connected chatter, and records the
+/// ban in the audit log. The ban's parameters must already have been
+/// validated.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `principal` - The party issuing the ban
+/// * `user_id` - The ID of the user who should be banned
+/// * `username` - The username of the user who should be banned
+/// * `duration` - (optional) The number of nanoseconds that the ban should
+/// be active for
+/// * `ip` - (optional) The IP address that should be banned alongside the
+/// user
+/// * `reason` - Why the user is being banned
+pub(crate) fn ban_user(
+    state: &State,
+    principal: &Principal,
+    user_id: u64,
+    username: &str,
+    duration: Option<u64>,
+    ip: Option<&str>,
+    reason: &str,
+) -> Result<(), ProviderError> {
+    write_bans(state, |bans| bans.set_banned(user_id, true, duration, ip))?;
+
+    publish_moderation(
+        state,
+        &Event::new(
+            EventTarget::All,
+            EventKind::Banned(BanEvent::new(
+                username,
+                reason,
+                duration.unwrap_or_default(),
+            )),
+        ),
+    )?;
+    audit::record_moderation_action(
+        state,
+        principal,
+        "ban.create",
+        &format!("user {}: {}", user_id, reason),
+    );
+
+    Ok(())
+}
+
+/// Lifts the ban issued against the given user, notifying every connected
+/// chatter, and records the lifted ban in the audit log. Returns whether or
+/// not the user was banned.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `principal` - The party lifting the ban
+/// * `user_id` - The ID of the user whose ban should be lifted
+/// * `username` - The username of the user whose ban should be lifted
+pub(crate) fn lift_user_ban(
+    state: &State,
+    principal: &Principal,
+    user_id: u64,
+    username: &str,
+) -> Result<bool, ProviderError> {
+    if !write_bans(state, |bans| bans.set_banned(user_id, false, None, None))? {
+        return Ok(false);
+    }
+
+    publish_moderation(
+        state,
+        &Event::new(EventTarget::All, EventKind::Unbanned(Unban::new(username))),
+    )?;
+    audit::record_moderation_action(state, principal, "ban.lift", &format!("user {}", user_id));
+
+    Ok(true)
+}
+
+/// Retreives the username of the user with the given ID, if they exist.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `user_id` - The ID of the user whose username should be retreived
+pub(crate) fn username_for(state: &State, user_id: u64) -> Result<Option<String>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()))
+    .username_for(user_id)
+}
+
+/// Publishes the given event produced by a moderation action, so that it
+/// may be pushed to the chatters it concerns.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open a connection to redis
+/// * `event` - The event produced by the moderation action
+pub(crate) fn publish_moderation(state: &State, event: &Event) -> Result<(), ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+
+    redis::cmd("PUBLISH")
+        .arg(cache.key(MODERATION_CHANNEL))
+        .arg(serde_json::to_string(event)?)
+        .query(cache.connection)
+        .map_err(|e| e.into())
+}
+
+/// Applies the given write to the bans. If the persistent layer can't be
+/// reached and the server has an outbox, the write is applied to the caching
+/// layer and queued until the persistent layer returns.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `write` - The write that should be applied
+fn write_bans<T>(
+    state: &State,
+    write: impl FnOnce(&mut dyn Provider) -> Result<T, ProviderError>,
+) -> Result<T, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+    let outbox = state.outbox().cloned();
+
+    match state.persistent_connection() {
+        Ok(persistent_conn) => {
+            write(&mut Hybrid::new(cache, Persistent::new(&persistent_conn)).with_outbox(outbox))
+        }
+        Err(e) if outbox.is_some() && breaker::indicates_outage(&e) => {
+            tracing::warn!(error = %e, "persistent layer is down, queueing the write");
+
+            write(&mut Hybrid::new(cache, Unavailable).with_outbox(outbox))
+        }
+        Err(e) => Err(e),
+    }
+}
 
 /// BanQuery represents a query for a ban based on its IP or corresponding user
 /// ID.
@@ -57,7 +379,7 @@ pub trait Provider {
     /// ```
     /// # #[macro_use]
     /// # extern crate tokio;
-    /// use gnomegg::ws_http_server::modules::bans::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, bans::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -89,7 +411,7 @@ pub trait Provider {
     /// ```
     /// # #[macro_use]
     /// # extern crate tokio;
-    /// use gnomegg::{ws_http_server::modules::bans::{Cache, Provider}, spec::ban::NewBan};
+    /// use gnomegg::{ws_http_server::modules::{Cache, bans::Provider}, spec::ban::NewBan};
     /// use chrono::offset::Utc;
     /// # use std::error::Error;
     ///
@@ -114,7 +436,7 @@ pub trait Provider {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::bans::{Cache, Provider, BanQuery};
+    /// use gnomegg::ws_http_server::modules::{Cache, bans::{Provider, BanQuery}};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(),Box<dyn Error>> {
@@ -142,7 +464,7 @@ pub trait Provider {
     /// ```
     /// # #[macro_use]
     /// # extern crate tokio;
-    /// use gnomegg::ws_http_server::modules::bans::{Cache, Provider, BanQuery};
+    /// use gnomegg::ws_http_server::modules::{Cache, bans::{Provider, BanQuery}};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -158,6 +480,30 @@ pub trait Provider {
     fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError>;
 }
 
+/// Removes a user's ban from the cache, along with the ban stored under the
+/// address that the user was banned with, if it still concerns the user.
+/// Returns whether or not the user's ban was removed.
+///
+/// KEYS: the user's ban.
+/// ARGV: the namespace of address bans, and (optionally) an address whose
+/// ban should be removed regardless of whom it concerns.
+const UNBAN_SCRIPT: &str = r"
+local ip = redis.call('HGET', KEYS[1], 'ip')
+if ip then
+    local addr_key = ARGV[1] .. ip
+
+    if redis.call('HGET', addr_key, 'user_id') == redis.call('HGET', KEYS[1], 'user_id') then
+        redis.call('DEL', addr_key)
+    end
+end
+
+if ARGV[2] ~= '' then
+    redis.call('DEL', ARGV[1] .. ARGV[2])
+end
+
+return redis.call('DEL', KEYS[1])
+";
+
 impl<'a> Provider for Cache<'a> {
     /// Sets a user's banned status in the redis caching layer.
     ///
@@ -168,6 +514,7 @@ impl<'a> Provider for Cache<'a> {
     /// * `duration` - (optional) The number of nanoseconds that the ban
     /// should be active for (this does not apply for unmuting a user)
     /// * `ip` - (optional) The IP of the user that should be banned
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn set_banned(
         &mut self,
         user_id: u64,
@@ -175,25 +522,27 @@ impl<'a> Provider for Cache<'a> {
         duration: Option<u64>,
         ip: Option<&str>,
     ) -> Result<bool, ProviderError> {
-        // If we're unmuting a user, we simply need to remove the redis entry
+        // If we're unbanning a user, we simply need to remove the redis
+        // entries, including the one stored under the address that the user
+        // was banned with
         if !banned {
-            if let Some(addr) = ip {
-                redis::cmd("DEL")
-                    .arg(format!("banned_addr::{}", addr))
-                    .query(self.connection)
-                    .map_err(<RedisError as Into<ProviderError>>::into)?;
-            }
+            let (key, addr_namespace) = (
+                self.key(format_args!("banned::{}", user_id)),
+                self.key("banned_addr::"),
+            );
 
-            return redis::cmd("DEL")
-                .arg(format!("banned::{}", user_id))
-                .query(self.connection)
-                .map_err(|e| e.into());
+            return self.eval(UNBAN_SCRIPT, |script| {
+                script
+                    .key(key)
+                    .arg(addr_namespace)
+                    .arg(ip.unwrap_or_default());
+            });
         }
 
         // Otherwise, insert a new ban into the redis database, and return any old entries
         Ok(self
             .register_ban(&NewBan::new(user_id, duration, Utc::now(), ip))?
-            .map_or(false, |ban| ban.active()))
+            .is_some_and(|ban| ban.active()))
     }
 
     /// Registers a gnomegg ban primitive in the cache backend.
@@ -202,23 +551,40 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `ban` - The ban primitive that should be used to modify the bans
     /// state
+    #[instrument(skip_all, fields(layer = "cache"), err)]
     fn register_ban(&mut self, ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
-        if let Some(addr) = ban.address() {
-            redis::cmd("SET")
-                .arg(format!("banned_addr::{}", addr))
-                .arg(serde_json::to_vec(ban)?)
-                .query::<()>(self.connection)?;
-        }
+        let fields = Ban::from(ban).to_fields();
+        let addr_key = ban
+            .address()
+            .map(|addr| self.key(format_args!("banned_addr::{}", addr)));
+        let key = self.key(format_args!("banned::{}", ban.concerns()));
 
-        redis::cmd("GETSET")
-            .arg(format!("banned::{}", ban.concerns()))
-            .arg(serde_json::to_vec(ban)?)
-            .query::<Option<String>>(self.connection)
-            .map_err(|e| e.into())
-            .map(|raw| {
-                raw.map(|str_data| serde_json::from_str::<Ban>(&str_data).map(Some))?
-                    .unwrap_or(None)
-            })
+        // The ban is registered under the user's address and ID at once.
+        // Each hash is replaced rather than updated, such that fields of the
+        // previous ban (e.g. its duration) aren't carried over.
+        let (old,): (HashMap<String, String>,) = self.transaction(|pipe| {
+            if let Some(addr_key) = addr_key {
+                pipe.cmd("DEL")
+                    .arg(&addr_key)
+                    .ignore()
+                    .cmd("HSET")
+                    .arg(&addr_key)
+                    .arg(fields.as_slice())
+                    .ignore();
+            }
+
+            pipe.cmd("HGETALL")
+                .arg(&key)
+                .cmd("DEL")
+                .arg(&key)
+                .ignore()
+                .cmd("HSET")
+                .arg(&key)
+                .arg(fields.as_slice())
+                .ignore();
+        })?;
+
+        Ban::from_fields(&old).map_err(|e| e.into())
     }
 
     /// Gets the ban primitive corresponding to the given user ID.
@@ -227,18 +593,16 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
+    #[instrument(skip_all, fields(layer = "cache"), err)]
     fn get_ban(&mut self, query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
-        redis::cmd("GET")
+        let fields = redis::cmd("HGETALL")
             .arg(match query {
-                BanQuery::Address(s) => format!("banned_addr::{}", s),
-                BanQuery::Id(id) => format!("banned::{}", id),
-            })
-            .query::<Option<String>>(self.connection)
-            .map_err(|e| e.into())
-            .map(|raw| {
-                raw.map(|str_data| serde_json::from_str::<Ban>(&str_data).map(Some))?
-                    .unwrap_or(None)
+                BanQuery::Address(s) => self.key(format_args!("banned_addr::{}", s)),
+                BanQuery::Id(id) => self.key(format_args!("banned::{}", id)),
             })
+            .query::<HashMap<String, String>>(self.connection)?;
+
+        Ban::from_fields(&fields).map_err(|e| e.into())
     }
 
     /// Checks whether or not a user with the given username has been banned
@@ -247,8 +611,9 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
+    #[instrument(skip_all, fields(layer = "cache"), err)]
     fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError> {
-        Ok(self.get_ban(query)?.map_or(false, |ban| ban.active()))
+        Ok(self.get_ban(query)?.is_some_and(|ban| ban.active()))
     }
 }
 
@@ -262,6 +627,7 @@ impl<'a> Provider for Persistent<'a> {
     /// * `duration` - (optional) The number of nanoseconds that the ban
     /// should be active for (this does not apply for unmuting a user)
     /// * `ip` - (optional) The IP of the user that should be banned
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn set_banned(
         &mut self,
         user_id: u64,
@@ -274,16 +640,16 @@ impl<'a> Provider for Persistent<'a> {
         // If the user is being unbanned, we simply need to delete the row
         // corresponding to the user's ban in the database
         if !banned {
-            return diesel::delete(bans::dsl::bans.find(user_id))
+            return diesel::delete(bans::dsl::bans.find(Unsigned(user_id)))
                 .execute(self.connection)
-                .map(|_| old.map_or(false, |ban| ban.active()))
+                .map(|_| old.is_some_and(|ban| ban.active()))
                 .map_err(|e| e.into());
         }
 
         // Otherwise, insert a new ban entry
         Ok(self
             .register_ban(&NewBan::new(user_id, duration, Utc::now(), ip))?
-            .map_or(false, |ban| ban.active()))
+            .is_some_and(|ban| ban.active()))
     }
 
     /// Registers a gnomegg ban primitive in the cache backend.
@@ -292,6 +658,7 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `ban` - The ban primitive that should be used to modify the bans
     /// state
+    #[instrument(skip_all, fields(layer = "persistent"), err)]
     fn register_ban(&mut self, ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
         let old = self.get_ban(&BanQuery::Id(ban.concerns()))?;
 
@@ -308,12 +675,15 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
+    #[instrument(skip_all, fields(layer = "persistent"), err)]
     fn get_ban(&mut self, query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
         let ban = match query {
-            BanQuery::Id(id) => bans::dsl::bans.find(id).first::<Ban>(self.connection),
+            BanQuery::Id(id) => bans::dsl::bans
+                .find(Unsigned(*id))
+                .first::<Ban>(self.reader()),
             BanQuery::Address(address) => bans::dsl::bans
                 .filter(bans::dsl::ip.eq(address))
-                .first::<Ban>(self.connection),
+                .first::<Ban>(self.reader()),
         };
 
         ban.map(Some).or_else(|e| {
@@ -331,12 +701,137 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
+    #[instrument(skip_all, fields(layer = "persistent"), err)]
     fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError> {
-        Ok(self.get_ban(query)?.map_or(false, |ban| ban.active()))
+        Ok(self.get_ban(query)?.is_some_and(|ban| ban.active()))
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Gets up to `limit` bans following the given cursor, ordered by the IDs
+    /// of the banned users. Bans are only listed by the persistent layer,
+    /// which holds every ban.
+    ///
+    /// # Arguments
+    ///
+    /// * `cursor` - (optional) The ID of the banned user that each returned
+    /// ban must follow
+    /// * `sort` - The order in which bans should be returned
+    /// * `include_lapsed` - Whether or not bans that have lapsed, but
+    /// haven't been lifted, should be returned
+    /// * `limit` - The maximum number of bans that should be returned
+    pub fn bans(
+        &mut self,
+        cursor: Option<u64>,
+        sort: Sort,
+        include_lapsed: bool,
+        limit: usize,
+    ) -> Result<Vec<Ban>, ProviderError> {
+        let mut query = bans::dsl::bans.limit(limit as i64).into_boxed();
+
+        query = match (sort, cursor) {
+            (Sort::Asc, Some(cursor)) => query
+                .filter(bans::dsl::user_id.gt(Unsigned(cursor)))
+                .order(bans::dsl::user_id.asc()),
+            (Sort::Asc, None) => query.order(bans::dsl::user_id.asc()),
+            (Sort::Desc, Some(cursor)) => query
+                .filter(bans::dsl::user_id.lt(Unsigned(cursor)))
+                .order(bans::dsl::user_id.desc()),
+            (Sort::Desc, None) => query.order(bans::dsl::user_id.desc()),
+        };
+
+        if !include_lapsed {
+            query = query.filter(sql::<Bool>(UNLAPSED));
+        }
+
+        query.load(self.reader()).map_err(|e| e.into())
     }
 }
 
-impl<'a> Provider for Hybrid<'a> {
+impl Provider for Memory {
+    /// Sets a user's banned status in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter who will be banned by this command
+    /// * `banned` - Whether or not this user should be banned
+    /// * `duration` - (optional) The number of nanoseconds that the ban
+    /// should be active for (this does not apply for unmuting a user)
+    /// * `ip` - (optional) The IP of the user that should be registered as
+    /// banned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{bans::{BanQuery, Provider}, Memory};
+    ///
+    /// let mut bans = Memory::new();
+    /// bans.set_banned(1, true, None, Some("127.0.0.1")).unwrap();
+    /// assert!(bans.is_banned(&BanQuery::Address("127.0.0.1")).unwrap());
+    /// ```
+    fn set_banned(
+        &mut self,
+        user_id: u64,
+        banned: bool,
+        duration: Option<u64>,
+        ip: Option<&str>,
+    ) -> Result<bool, ProviderError> {
+        if !banned {
+            if let Some(addr) = ip {
+                self.banned_addrs.remove(addr);
+            }
+
+            return Ok(self.bans.remove(&user_id).is_some_and(|ban| ban.active()));
+        }
+
+        Ok(self
+            .register_ban(&NewBan::new(user_id, duration, Utc::now(), ip))?
+            .is_some_and(|ban| ban.active()))
+    }
+
+    /// Registers a gnomegg ban primitive in memory, returning the ban it
+    /// replaced, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `ban` - The ban primitive that should be used to modify the bans
+    /// state
+    fn register_ban(&mut self, ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
+        if let Some(addr) = ban.address() {
+            self.banned_addrs.insert(addr.to_owned(), Ban::from(ban));
+        }
+
+        Ok(self.bans.insert(ban.concerns(), Ban::from(ban)))
+    }
+
+    /// Gets the ban primitive corresponding to the given user ID or IP
+    /// address from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A query containing an IP address or a user ID that should be
+    /// searched for
+    fn get_ban(&mut self, query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
+        Ok(match query {
+            BanQuery::Address(addr) => self.banned_addrs.get(*addr),
+            BanQuery::Id(id) => self.bans.get(id),
+        }
+        .cloned())
+    }
+
+    /// Checks whether or not a user with the given username or address has
+    /// been banned.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A query containing an IP address or a user ID that should be
+    /// searched for
+    fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError> {
+        Ok(self.get_ban(query)?.is_some_and(|ban| ban.active()))
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
     /// Sets a user's banned status in the active provider.
     ///
     /// # Arguments
@@ -354,9 +849,23 @@ impl<'a> Provider for Hybrid<'a> {
         duration: Option<u64>,
         ip: Option<&str>,
     ) -> Result<bool, ProviderError> {
-        self.cache
-            .set_banned(user_id, banned, duration, ip)
-            .and(self.persistent.set_banned(user_id, banned, duration, ip))
+        let pending = if banned {
+            PendingWrite::Ban {
+                user_id,
+                duration,
+                initiated_at: Utc::now(),
+                ip: ip.map(str::to_owned),
+            }
+        } else {
+            PendingWrite::Unban {
+                user_id,
+                ip: ip.map(str::to_owned),
+            }
+        };
+
+        self.write_ban(user_id, ip, pending, |bans| {
+            bans.set_banned(user_id, banned, duration, ip)
+        })
     }
 
     /// Registers a gnomegg ban primitive in the active provider.
@@ -366,9 +875,16 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `ban` - The ban primitive that should be used to modify the bans
     /// state
     fn register_ban(&mut self, ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
-        self.cache
-            .register_ban(ban)
-            .and(self.persistent.register_ban(ban))
+        let pending = PendingWrite::Ban {
+            user_id: ban.concerns(),
+            duration: ban.duration(),
+            initiated_at: ban.initiated_at(),
+            ip: ban.address().map(str::to_owned),
+        };
+
+        self.write_ban(ban.concerns(), ban.address(), pending, |bans| {
+            bans.register_ban(ban)
+        })
     }
 
     /// Gets the ban primitive corresponding to the given user ID.
@@ -378,9 +894,8 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
     fn get_ban(&mut self, query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
-        self.cache
-            .get_ban(query)
-            .or_else(|_| self.persistent.get_ban(query))
+        let cached = self.cache.get_ban(query);
+        self.fall_back("bans", cached, |hybrid| hybrid.persistent.get_ban(query))
     }
 
     /// Checks whether or not a user with the given username has been banned
@@ -390,19 +905,179 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `query` - A query containing an IP address or a user ID that should be
     /// searched for in the database
     fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError> {
-        self.cache
-            .is_banned(query)
-            .or_else(|_| self.persistent.is_banned(query))
+        let cached = self.cache.is_banned(query);
+        self.fall_back("bans", cached, |hybrid| hybrid.persistent.is_banned(query))
+    }
+}
+
+impl<C: Provider, P: Provider> Hybrid<C, P> {
+    /// Applies the given write to the caching layer, and then to the
+    /// persistent layer. If the persistent layer rejects the write, the
+    /// cached entries for the user and address are restored to their state
+    /// before the write, such that the cache never reports a ban that wasn't
+    /// persisted. Restoration is best-effort: if the cache fails in the
+    /// meantime, the persistent layer's error is still returned. Writes that
+    /// can't be persisted because the persistent layer is down are queued in
+    /// the provider's outbox instead, if it has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ban is being written
+    /// * `ip` - (optional) The IP address whose ban is being written
+    /// * `pending` - The write that should be queued if it can't be persisted
+    /// * `write` - The write that should be applied to each layer
+    fn write_ban<T>(
+        &mut self,
+        user_id: u64,
+        ip: Option<&str>,
+        pending: PendingWrite,
+        mut write: impl FnMut(&mut dyn Provider) -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let previous = self.cache.get_ban(&BanQuery::Id(user_id));
+        let previous_addr = ip.map(|addr| (addr, self.cache.get_ban(&BanQuery::Address(addr))));
+
+        let written = write(&mut self.cache);
+        let pending = Some(pending).filter(|_| written.is_ok());
+        let persisted = self.persist(pending, |persistent| write(persistent));
+
+        match (written, persisted) {
+            (written, Ok(None)) => written,
+            (Ok(_), Err(e)) => {
+                // Restoring the address' ban may overwrite the user's entry,
+                // so it is restored first
+                if let Some((addr, Ok(previous_addr))) = previous_addr {
+                    let _ = match previous_addr {
+                        Some(ban) => self.cache.register_ban(&NewBan::from(&ban)).map(|_| ()),
+                        None => self
+                            .cache
+                            .set_banned(user_id, false, None, Some(addr))
+                            .map(|_| ()),
+                    };
+                }
+
+                if let Ok(previous) = previous {
+                    let _ = match previous {
+                        Some(ban) => self.cache.register_ban(&NewBan::from(&ban)).map(|_| ()),
+                        None => self
+                            .cache
+                            .set_banned(user_id, false, None, None)
+                            .map(|_| ()),
+                    };
+                }
+
+                Err(e)
+            }
+            (written, Ok(Some(persisted))) => write_both(written, Ok(persisted)),
+            (written, Err(e)) => write_both(written, Err(e)),
+        }
+    }
+}
+
+impl Provider for Unavailable {
+    fn set_banned(
+        &mut self,
+        _user_id: u64,
+        _banned: bool,
+        _duration: Option<u64>,
+        _ip: Option<&str>,
+    ) -> Result<bool, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn register_ban(&mut self, _ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn get_ban(&mut self, _query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn is_banned(&mut self, _query: &BanQuery) -> Result<bool, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+}
+
+impl<T: Provider + ?Sized> Provider for Box<T> {
+    fn set_banned(
+        &mut self,
+        user_id: u64,
+        banned: bool,
+        duration: Option<u64>,
+        ip: Option<&str>,
+    ) -> Result<bool, ProviderError> {
+        (**self).set_banned(user_id, banned, duration, ip)
+    }
+
+    fn register_ban(&mut self, ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
+        (**self).register_ban(ban)
+    }
+
+    fn get_ban(&mut self, query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
+        (**self).get_ban(query)
+    }
+
+    fn is_banned(&mut self, query: &BanQuery) -> Result<bool, ProviderError> {
+        (**self).is_banned(query)
+    }
+}
+
+/// Selects the bans provider backed by the given layers. Layers left unused
+/// by the selection are dropped.
+///
+/// # Arguments
+///
+/// * `layers` - The layers that should back the provider
+/// * `cache` - The redis caching layer
+/// * `persistent` - The MySQL persistence layer
+///
+/// # Example
+///
+/// ```
+/// use diesel::Connection;
+/// use gnomegg::{
+///     spec::backend::PersistentConnection,
+///     ws_http_server::modules::{
+///         bans::{self, BanQuery, Provider},
+///         Cache, Layers, Persistent,
+///     },
+/// };
+/// # use std::{env, error::Error};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// dotenv::dotenv()?;
+///
+/// let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+/// let persistent_conn = PersistentConnection::establish(&env::var("DATABASE_URL")?)?;
+///
+/// let mut bans = bans::provider(
+///     "cache".parse()?,
+///     Cache::new(&mut conn),
+///     Persistent::new(&persistent_conn),
+/// );
+/// bans.set_banned(1, true, None, None)?;
+/// assert!(bans.is_banned(&BanQuery::Id(1))?);
+/// Ok(())
+/// # }
+/// ```
+pub fn provider<'a>(
+    layers: Layers,
+    cache: Cache<'a>,
+    persistent: Persistent<'a>,
+) -> Box<dyn Provider + 'a> {
+    match layers {
+        Layers::Cache => Box::new(cache),
+        Layers::Persistent => Box::new(persistent),
+        Layers::Hybrid => Box::new(Hybrid::new(cache, persistent)),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use diesel::{mysql::MysqlConnection, Connection};
     use super::{
-        super::super::super::spec::{schema::users, user::NewUser},
+        super::super::super::spec::{backend::PersistentConnection, schema::users, user::NewUser},
         *,
     };
+    use diesel::Connection;
     use dotenv;
 
     use std::{default::Default, env, error::Error};
@@ -413,7 +1088,7 @@ mod tests {
 
         let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -435,7 +1110,7 @@ mod tests {
         let mut bans = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
         bans.set_banned(id, true, None, None)?;
 
-        assert_eq!(bans.is_banned(&BanQuery::Id(id))?, true);
+        assert!(bans.is_banned(&BanQuery::Id(id))?);
 
         Ok(())
     }
@@ -450,7 +1125,7 @@ mod tests {
         let mut bans = Cache::new(&mut conn);
         bans.set_banned(42069, true, None, None)?;
 
-        assert_eq!(bans.is_banned(&BanQuery::Id(42069))?, true);
+        assert!(bans.is_banned(&BanQuery::Id(42069))?);
 
         Ok(())
     }
@@ -460,7 +1135,7 @@ mod tests {
         dotenv::dotenv()?;
 
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -482,7 +1157,123 @@ mod tests {
         let mut bans = Persistent::new(&persistent_conn);
         bans.set_banned(id, true, None, None)?;
 
-        assert_eq!(bans.is_banned(&BanQuery::Id(id))?, true);
+        assert!(bans.is_banned(&BanQuery::Id(id))?);
+        assert!(bans
+            .bans(Some(id - 1), Sort::Asc, false, 1)?
+            .iter()
+            .any(|ban| ban.concerns() == id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory() -> Result<(), Box<dyn Error>> {
+        let mut bans = Hybrid::new(Memory::new(), Memory::new());
+
+        bans.set_banned(1, true, None, Some("127.0.0.1"))?;
+        assert!(bans.is_banned(&BanQuery::Id(1))?);
+        assert!(bans.is_banned(&BanQuery::Address("127.0.0.1"))?);
+
+        // Bans lapse once their duration elapses
+        bans.set_banned(2, true, Some(0), None)?;
+        assert!(!bans.is_banned(&BanQuery::Id(2))?);
+
+        bans.set_banned(1, false, None, Some("127.0.0.1"))?;
+        assert!(!bans.is_banned(&BanQuery::Id(1))?);
+        assert!(!bans.is_banned(&BanQuery::Address("127.0.0.1"))?);
+
+        Ok(())
+    }
+
+    /// Failing is a layer rejecting every operation, used to verify the
+    /// behavior of hybrid providers when a layer is unavailable.
+    struct Failing;
+
+    impl Provider for Failing {
+        fn set_banned(
+            &mut self,
+            _user_id: u64,
+            _banned: bool,
+            _duration: Option<u64>,
+            _ip: Option<&str>,
+        ) -> Result<bool, ProviderError> {
+            Err(ProviderError::InvalidArgument { arg: "user_id" })
+        }
+
+        fn register_ban(&mut self, _ban: &NewBan) -> Result<Option<Ban>, ProviderError> {
+            Err(ProviderError::InvalidArgument { arg: "ban" })
+        }
+
+        fn get_ban(&mut self, _query: &BanQuery) -> Result<Option<Ban>, ProviderError> {
+            Err(ProviderError::InvalidArgument { arg: "query" })
+        }
+
+        fn is_banned(&mut self, _query: &BanQuery) -> Result<bool, ProviderError> {
+            Err(ProviderError::InvalidArgument { arg: "query" })
+        }
+    }
+
+    #[test]
+    fn test_write_through() -> Result<(), Box<dyn Error>> {
+        let mut cache = Memory::new();
+        cache.set_banned(2, true, Some(60_000_000_000), None)?;
+
+        let mut bans = Hybrid::new(cache, Failing);
+
+        // Bans rejected by the persistent layer aren't left in the cache
+        assert!(bans.set_banned(1, true, None, Some("127.0.0.1")).is_err());
+        assert!(!bans.cache.is_banned(&BanQuery::Id(1))?);
+        assert!(!bans.cache.is_banned(&BanQuery::Address("127.0.0.1"))?);
+
+        // Bans that were already cached are restored
+        assert!(bans.set_banned(2, false, None, None).is_err());
+        assert!(bans.cache.is_banned(&BanQuery::Id(2))?);
+        assert!(bans
+            .register_ban(&NewBan::new(2, None, Utc::now(), None))
+            .is_err());
+        assert_eq!(
+            bans.cache
+                .get_ban(&BanQuery::Id(2))?
+                .and_then(|ban| ban.active_for()),
+            Some(chrono::Duration::seconds(60))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite_error() {
+        let mut bans = Hybrid::new(Failing, Failing);
+
+        // Errors from both layers are reported when both layers fail
+        assert!(matches!(
+            bans.get_ban(&BanQuery::Id(1)),
+            Err(ProviderError::Composite { .. })
+        ));
+        assert!(matches!(
+            bans.set_banned(1, true, None, None),
+            Err(ProviderError::Composite { .. })
+        ));
+
+        // A single failing layer is reported as is
+        let mut bans = Hybrid::new(Memory::new(), Failing);
+        assert!(matches!(
+            bans.set_banned(1, true, None, None),
+            Err(ProviderError::InvalidArgument { arg: "user_id" })
+        ));
+    }
+
+    #[test]
+    fn test_boxed() -> Result<(), Box<dyn Error>> {
+        assert_eq!("persistent".parse::<Layers>()?, Layers::Persistent);
+        assert!("redis".parse::<Layers>().is_err());
+
+        // Providers selected at runtime may still be composed
+        let cache: Box<dyn Provider> = Box::new(Memory::new());
+        let mut bans = Hybrid::new(cache, Box::new(Memory::new()) as Box<dyn Provider>);
+
+        bans.set_banned(1, true, None, None)?;
+        assert!(bans.is_banned(&BanQuery::Id(1))?);
 
         Ok(())
     }
@@ -0,0 +1,734 @@
+use actix_web::{
+    rt,
+    web::{self, Data, Json, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use futures::future;
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            bot_command::{BotCommand, BotInvocation, CommandHandler, WebhookReply},
+            event::Event,
+            schema::bot_commands,
+            user::Role,
+        },
+        auth::{capability::CanManageBotCommands, RequireCapability},
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{slice, time::Duration};
+
+/// The redis channel on which replies to bot commands answered by webhooks
+/// are published, so that they may be pushed to every connected chatter.
+pub const BOT_REPLY_CHANNEL: &str = "bot_replies";
+
+/// The interval at which queued invocations are sent to the webhooks
+/// answering them.
+const WEBHOOK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The amount of time that a webhook may take to answer an invocation before
+/// it is abandoned.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum number of queued invocations sent to webhooks on each tick.
+const WEBHOOK_BATCH_SIZE: usize = 50;
+
+/// The redis hash holding each of the bot commands, keyed by their names.
+const BOT_COMMANDS_KEY: &str = "bot_commands";
+
+/// The redis list holding each of the invocations awaiting an answer from a
+/// webhook, oldest first.
+const INVOCATIONS_KEY: &str = "bot_command_invocations";
+
+/// BuiltinHandler represents a handler implemented by the server, which may
+/// answer any bot command registered with its name. Handlers produce the
+/// message that should be sent to the chat in reply, if any.
+pub type BuiltinHandler =
+    fn(&mut Hybrid<Cache, Persistent>, &BotInvocation) -> Result<Option<String>, ProviderError>;
+
+/// Gets the handlers implemented by the server that are available to every
+/// dispatcher, alongside the names under which they are registered.
+pub fn default_builtins() -> Vec<(&'static str, BuiltinHandler)> {
+    vec![("commands", list_commands)]
+}
+
+/// Answers an invocation with the names of each of the bot commands that
+/// may be invoked.
+///
+/// # Arguments
+///
+/// * `hybrid` - The providers from which the commands should be obtained
+/// * `_invocation` - The invocation being answered
+fn list_commands(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    _invocation: &BotInvocation,
+) -> Result<Option<String>, ProviderError> {
+    let names = hybrid
+        .bot_commands()?
+        .iter()
+        .map(|command| format!("!{}", command.name()))
+        .collect::<Vec<String>>();
+
+    Ok(Some(names.join(" ")).filter(|names| !names.is_empty()))
+}
+
+/// Gets the redis key of the value marking the given command as cooling
+/// down.
+///
+/// # Arguments
+///
+/// * `name` - The name of the command whose cooldown should be located
+fn cooldown_key(name: &str) -> String {
+    format!("bot_command_cooldown::{}", name)
+}
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the bot commands module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/bot_commands")
+        .service(list_bot_commands)
+        .service(get_bot_command)
+        .service(put_bot_command)
+        .service(delete_bot_command)
+}
+
+/// Periodically sends each queued invocation to the webhook answering it for
+/// as long as the server is running, publishing the replies on the bot reply
+/// channel.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_webhook_task(state: Data<State>) {
+    rt::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        let mut interval = rt::time::interval(WEBHOOK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let pending_state = state.clone();
+            let pending = match web::block(
+                move || -> Result<Vec<(String, BotInvocation)>, ProviderError> {
+                    let mut conn = pending_state.cache_connection()?;
+                    let persistent_conn = pending_state.persistent_connection()?;
+
+                    Hybrid::new(
+                        Cache::new(&mut conn).with_prefix(pending_state.key_prefix()),
+                        Persistent::new(&persistent_conn),
+                    )
+                    .take_webhook_invocations(WEBHOOK_BATCH_SIZE)
+                },
+            )
+            .await
+            {
+                Ok(pending) => pending,
+                Err(_) => continue,
+            };
+
+            // Webhooks that fail or time out are simply left unanswered
+            let replies = future::join_all(
+                pending
+                    .iter()
+                    .map(|(url, invocation)| call_webhook(&client, url, invocation)),
+            )
+            .await
+            .into_iter()
+            .filter_map(|reply| reply.ok().flatten())
+            .collect::<Vec<String>>();
+
+            if replies.is_empty() {
+                continue;
+            }
+
+            let state = state.clone();
+            let _ = web::block(move || -> Result<(), ProviderError> {
+                let mut conn = state.cache_connection()?;
+
+                let events = replies
+                    .iter()
+                    .filter_map(|reply| state.dispatcher().bot_reply(reply))
+                    .collect::<Vec<Event>>();
+
+                Cache::new(&mut conn)
+                    .with_prefix(state.key_prefix())
+                    .publish_replies(&events)
+            })
+            .await;
+        }
+    });
+}
+
+/// Sends the given invocation to the webhook at the given URL, returning the
+/// webhook's reply, if any.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client with which the webhook should be called
+/// * `url` - The URL of the webhook answering the invocation
+/// * `invocation` - The invocation that should be answered
+async fn call_webhook(
+    client: &reqwest::Client,
+    url: &str,
+    invocation: &BotInvocation,
+) -> Result<Option<String>, reqwest::Error> {
+    client
+        .post(url)
+        .json(invocation)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<WebhookReply>()
+        .await
+        .map(|reply| reply.reply().map(str::to_owned))
+}
+
+/// BotCommandRequest represents a request to create or replace a bot
+/// command.
+#[derive(Deserialize)]
+pub struct BotCommandRequest {
+    /// The kind of handler that should answer the command (builtin or
+    /// webhook)
+    handler: String,
+
+    /// The name of the builtin handler, or the URL of the webhook that should
+    /// answer the command
+    target: String,
+
+    /// (optional) The number of seconds after the command is invoked during
+    /// which it may not be invoked again
+    cooldown: Option<u32>,
+
+    /// (optional) The role required in order to invoke the command
+    role: Option<Role>,
+}
+
+/// Gets each of the bot commands that may be invoked, ordered by their
+/// names.
+#[get("")]
+pub async fn list_bot_commands(state: Data<State>) -> Result<Json<Vec<BotCommand>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .bot_commands()
+    .map(Json)
+}
+
+/// Gets the bot command with the given name.
+#[get("/{name}")]
+pub async fn get_bot_command(
+    state: Data<State>,
+    name: Path<String>,
+) -> Result<Option<Json<BotCommand>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .bot_command(&name.to_lowercase())
+    .map(|command| command.map(Json))
+}
+
+/// Creates or replaces the bot command with the given name. Builtin handlers
+/// must be registered in the dispatcher. Only administrators may manage bot
+/// commands.
+#[put("/{name}")]
+pub async fn put_bot_command(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageBotCommands>,
+    name: Path<String>,
+    body: Json<BotCommandRequest>,
+) -> Result<Json<BotCommand>, HttpError> {
+    let handler = match body.handler.as_str() {
+        "builtin" if state.dispatcher().has_builtin(&body.target) => {
+            CommandHandler::Builtin(body.target.clone())
+        }
+        "webhook" => CommandHandler::Webhook(body.target.clone()),
+        _ => return Err(ProviderError::InvalidArgument { arg: "handler" }.into()),
+    };
+
+    let command = BotCommand::new(&name.to_lowercase(), handler)
+        .with_cooldown(body.cooldown.unwrap_or_default())
+        .with_role(body.role);
+    if !command.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "command" }.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .set_bot_command(&command)?;
+
+    Ok(Json(command))
+}
+
+/// Deletes the bot command with the given name. Only administrators may
+/// manage bot commands.
+#[delete("/{name}")]
+pub async fn delete_bot_command(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageBotCommands>,
+    name: Path<String>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .remove_bot_command(&name.to_lowercase())
+    .map(|removed| removed.map(|_| HttpResponse::NoContent().finish()))
+}
+
+/// Provider represents an arbitrary backend for the bot commands service,
+/// which is the source of truth for the set of custom commands that chatters
+/// may invoke.
+pub trait Provider {
+    /// Gets each of the bot commands that may be invoked, ordered by their
+    /// names.
+    fn bot_commands(&mut self) -> Result<Vec<BotCommand>, ProviderError>;
+
+    /// Retreives the bot command with the given name, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be obtained
+    fn bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError>;
+
+    /// Stores the given bot command, replacing any existing command with the
+    /// same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that should be stored
+    fn set_bot_command(&mut self, command: &BotCommand) -> Result<(), ProviderError>;
+
+    /// Removes the bot command with the given name, returning the removed
+    /// command if it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be removed
+    fn remove_bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Stores each of the given bot commands in the redis caching layer at
+    /// once.
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The commands that should be stored
+    fn set_bot_commands(&mut self, commands: &[BotCommand]) -> Result<(), ProviderError> {
+        // HSET requires at least one field
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        let mut fields = Vec::with_capacity(commands.len());
+        for command in commands {
+            fields.push((command.name(), serde_json::to_string(command)?));
+        }
+
+        redis::cmd("HSET")
+            .arg(self.key(BOT_COMMANDS_KEY))
+            .arg(fields)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Starts the cooldown of the command with the given name, unless it is
+    /// already cooling down. Returns whether or not the cooldown was started.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the invoked command
+    /// * `cooldown` - The number of seconds for which the command should cool
+    /// down
+    fn claim_command_cooldown(&mut self, name: &str, cooldown: u32) -> Result<bool, ProviderError> {
+        if cooldown == 0 {
+            return Ok(true);
+        }
+
+        redis::cmd("SET")
+            .arg(self.key(cooldown_key(name)))
+            .arg(1)
+            .arg("EX")
+            .arg(cooldown)
+            .arg("NX")
+            .query::<Option<String>>(self.connection)
+            .map(|set| set.is_some())
+            .map_err(|e| e.into())
+    }
+
+    /// Queues the given invocation until it is sent to the webhook answering
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `invocation` - The invocation that should be queued
+    fn queue_invocation(&mut self, invocation: &BotInvocation) -> Result<(), ProviderError> {
+        redis::cmd("RPUSH")
+            .arg(self.key(INVOCATIONS_KEY))
+            .arg(serde_json::to_string(invocation)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Removes up to the given number of queued invocations, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of invocations that should be taken
+    fn take_invocations(&mut self, limit: usize) -> Result<Vec<BotInvocation>, ProviderError> {
+        let (invocations, _): (Vec<String>, ()) = redis::pipe()
+            .atomic()
+            .cmd("LRANGE")
+            .arg(self.key(INVOCATIONS_KEY))
+            .arg(0)
+            .arg(limit as isize - 1)
+            .cmd("LTRIM")
+            .arg(self.key(INVOCATIONS_KEY))
+            .arg(limit)
+            .arg(-1)
+            .query(self.connection)?;
+
+        invocations
+            .iter()
+            .map(|invocation| serde_json::from_str(invocation).map_err(|e| e.into()))
+            .collect()
+    }
+
+    /// Publishes each of the given replies to bot commands, so that they may
+    /// be pushed to every connected chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events carrying the replies
+    fn publish_replies(&mut self, events: &[Event]) -> Result<(), ProviderError> {
+        let mut pipe = redis::pipe();
+
+        for event in events {
+            pipe.cmd("PUBLISH")
+                .arg(self.key(BOT_REPLY_CHANNEL))
+                .arg(serde_json::to_string(event)?)
+                .ignore();
+        }
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets each of the bot commands held by the redis caching layer, ordered
+    /// by their names.
+    fn bot_commands(&mut self) -> Result<Vec<BotCommand>, ProviderError> {
+        let mut commands = redis::cmd("HVALS")
+            .arg(self.key(BOT_COMMANDS_KEY))
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|command| serde_json::from_str(command))
+            .collect::<Result<Vec<BotCommand>, _>>()?;
+        commands.sort_by(|a, b| a.name().cmp(b.name()));
+
+        Ok(commands)
+    }
+
+    /// Retreives the bot command with the given name from the redis caching
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be obtained
+    fn bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        redis::cmd("HGET")
+            .arg(self.key(BOT_COMMANDS_KEY))
+            .arg(name)
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given bot command in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, bot_commands::Provider}, spec::bot_command::{BotCommand, CommandHandler}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut commands = Cache::new(&mut conn);
+    /// let command = BotCommand::new("help", CommandHandler::Builtin("commands".to_owned()));
+    ///
+    /// commands.set_bot_command(&command)?;
+    /// assert_eq!(commands.bot_command("help")?, Some(command));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_bot_command(&mut self, command: &BotCommand) -> Result<(), ProviderError> {
+        self.set_bot_commands(slice::from_ref(command))
+    }
+
+    /// Removes the bot command with the given name from the redis caching
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be removed
+    fn remove_bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        let command = self.bot_command(name)?;
+
+        redis::cmd("HDEL")
+            .arg(self.key(BOT_COMMANDS_KEY))
+            .arg(name)
+            .query::<()>(self.connection)?;
+
+        Ok(command)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets each of the bot commands stored in the MySQL database, ordered by
+    /// their names.
+    fn bot_commands(&mut self) -> Result<Vec<BotCommand>, ProviderError> {
+        bot_commands::table
+            .order(bot_commands::dsl::name.asc())
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the bot command with the given name from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be obtained
+    fn bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        bot_commands::table
+            .find(name)
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Stores the given bot command in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that should be stored
+    fn set_bot_command(&mut self, command: &BotCommand) -> Result<(), ProviderError> {
+        diesel::replace_into(bot_commands::table)
+            .values(command)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the bot command with the given name from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be removed
+    fn remove_bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        let command = self.bot_command(name)?;
+
+        diesel::delete(bot_commands::table.find(name)).execute(self.connection)?;
+
+        Ok(command)
+    }
+}
+
+impl<'a, 'b> Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Starts the cooldown of the command with the given name, unless it is
+    /// already cooling down. Returns whether or not the command may be
+    /// invoked. Cooldowns are only kept in the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The invoked command
+    pub fn claim_command_cooldown(&mut self, command: &BotCommand) -> Result<bool, ProviderError> {
+        self.cache
+            .claim_command_cooldown(command.name(), command.cooldown())
+    }
+
+    /// Queues the given invocation until it is sent to the webhook answering
+    /// it. Invocations are only queued in the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `invocation` - The invocation that should be queued
+    pub fn queue_invocation(&mut self, invocation: &BotInvocation) -> Result<(), ProviderError> {
+        self.cache.queue_invocation(invocation)
+    }
+
+    /// Removes up to the given number of queued invocations, alongside the
+    /// URLs of the webhooks answering them. Invocations of commands that
+    /// have since been removed, or are no longer answered by a webhook, are
+    /// dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of invocations that should be taken
+    fn take_webhook_invocations(
+        &mut self,
+        limit: usize,
+    ) -> Result<Vec<(String, BotInvocation)>, ProviderError> {
+        let mut pending = Vec::new();
+
+        for invocation in self.cache.take_invocations(limit)? {
+            if let Some(CommandHandler::Webhook(url)) = self
+                .bot_command(invocation.command())?
+                .and_then(|command| command.handler())
+            {
+                pending.push((url, invocation));
+            }
+        }
+
+        Ok(pending)
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets each of the bot commands that may be invoked, populating the
+    /// cache from the persistent layer if it holds none.
+    fn bot_commands(&mut self) -> Result<Vec<BotCommand>, ProviderError> {
+        match self.cache.bot_commands() {
+            Ok(commands) if !commands.is_empty() => Ok(commands),
+            _ => self
+                .persistent
+                .bot_commands()
+                .and_then(|commands| self.cache.set_bot_commands(&commands).map(|_| commands)),
+        }
+    }
+
+    /// Retreives the bot command with the given name, populating the cache
+    /// from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be obtained
+    fn bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        match self.cache.bot_command(name) {
+            Ok(Some(command)) => Ok(Some(command)),
+            _ => self.persistent.bot_command(name).and_then(|command| {
+                command.map_or(Ok(None), |command| {
+                    self.cache.set_bot_command(&command).map(|_| Some(command))
+                })
+            }),
+        }
+    }
+
+    /// Stores the given bot command in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that should be stored
+    fn set_bot_command(&mut self, command: &BotCommand) -> Result<(), ProviderError> {
+        self.persistent
+            .set_bot_command(command)
+            .and_then(|_| self.cache.set_bot_command(command))
+    }
+
+    /// Removes the bot command with the given name from the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command that should be removed
+    fn remove_bot_command(&mut self, name: &str) -> Result<Option<BotCommand>, ProviderError> {
+        let command = self.persistent.remove_bot_command(name)?;
+        self.cache.remove_bot_command(name)?;
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use chrono::Utc;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let command = BotCommand::new(
+            "test_song",
+            CommandHandler::Webhook("https://example.com/song".to_owned()),
+        )
+        .with_cooldown(30);
+
+        let mut commands = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        commands.set_bot_command(&command)?;
+
+        assert_eq!(commands.bot_command("test_song")?, Some(command.clone()));
+        assert!(commands.bot_commands()?.contains(&command));
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(BOT_COMMANDS_KEY)
+            .query::<()>(&mut conn)?;
+        let mut commands = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert!(commands.bot_commands()?.contains(&command));
+
+        // A command may not be invoked again until its cooldown elapses
+        redis::cmd("DEL")
+            .arg(cooldown_key("test_song"))
+            .query::<()>(commands.cache.connection)?;
+        assert!(commands.claim_command_cooldown(&command)?);
+        assert!(!commands.claim_command_cooldown(&command)?);
+
+        // Invocations of webhook commands are taken alongside their URLs
+        let invocation =
+            BotInvocation::parse(69420, "MrMouton", "!test_song sandstorm", Utc::now()).unwrap();
+        commands.queue_invocation(&invocation)?;
+        assert!(commands
+            .take_webhook_invocations(WEBHOOK_BATCH_SIZE)?
+            .contains(&("https://example.com/song".to_owned(), invocation)));
+
+        assert_eq!(commands.remove_bot_command("test_song")?, Some(command));
+        assert_eq!(commands.bot_command("test_song")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use actix_web::{
+    web::{Bytes, Data},
+    HttpResponse, Scope,
+};
+
+use super::{
+    super::{
+        super::spec::event::{Command, CommandKind, Event},
+        auth::AuthedUser,
+        server::State,
+    },
+    bans, Cache, ProviderError,
+};
+
+/// The redis channel on which the events produced by each command issued by
+/// a chatter are published, so that they may be pushed to the chatters they
+/// concern.
+pub const CHAT_CHANNEL: &str = "chat";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the chat module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/chat").service(issue_command)
+}
+
+/// Issues the command in the request body on behalf of the authenticated
+/// chatter, for clients that don't hold a WebSocket open. The events produced
+/// by the command, including any error addressed to the chatter, are pushed
+/// to the chatters they concern over the event relay, rather than returned.
+#[post("")]
+pub async fn issue_command(
+    state: Data<State>,
+    user: AuthedUser,
+    body: Bytes,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let kind = serde_json::from_slice::<CommandKind>(&body)
+        .map_err(|_| ProviderError::InvalidArgument { arg: "command" })?;
+
+    // Commands are always issued under the chatter's own username, whatever
+    // the client claims
+    let username = match bans::username_for(&state, user.id())? {
+        Some(username) => username,
+        None => return Ok(None),
+    };
+    let mut cmd = Command::new(&username, kind);
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let events = state.dispatcher().dispatch(
+        &mut conn,
+        &persistent_conn,
+        replica_conn.as_deref(),
+        &mut cmd,
+    );
+
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .publish_chat(&events)
+        .map(|_| Some(HttpResponse::NoContent().finish()))
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes each of the events produced by a command issued by a
+    /// chatter, so that they may be pushed to the chatters they concern.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events produced by the command
+    fn publish_chat(&mut self, events: &[Event]) -> Result<(), ProviderError> {
+        let mut pipe = redis::pipe();
+
+        for event in events {
+            pipe.cmd("PUBLISH")
+                .arg(self.key(CHAT_CHANNEL))
+                .arg(serde_json::to_string(event)?)
+                .ignore();
+        }
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::Utc;
+
+use super::{super::invalidation::Invalidation, Cache, Hybrid, ProviderError};
+
+/// The redis key holding the number of seconds that chatters must wait
+/// between messages while the chat is in slowmode.
+const SLOWMODE_KEY: &str = "slowmode";
+
+/// The redis key that exists while the chat is in subonly mode.
+const SUBONLY_KEY: &str = "subonly";
+
+/// Gets the redis key of the value holding the time, in milliseconds since
+/// the unix epoch, at which the given user last sent a message in slowmode.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose last message should be located
+fn last_message_key(user_id: u64) -> String {
+    format!("slowmode_last_message::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the chat modes service,
+/// which stores the modes restricting who may chat, and how often. Modes are
+/// ephemeral, and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Gets the number of seconds that chatters must wait between messages,
+    /// if the chat is in slowmode.
+    fn slowmode_interval(&mut self) -> Result<Option<u64>, ProviderError>;
+
+    /// Puts the chat in slowmode with the given interval, or takes the chat
+    /// out of slowmode.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - (optional) The number of seconds that chatters must
+    /// wait between messages
+    fn set_slowmode_interval(&mut self, interval: Option<u64>) -> Result<(), ProviderError>;
+
+    /// Records a message sent by the given user, unless they sent a message
+    /// within the given interval, in which case the number of seconds until
+    /// they may send another is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending a message
+    /// * `interval` - The number of seconds that chatters must wait between
+    /// messages
+    fn claim_message_slot(
+        &mut self,
+        user_id: u64,
+        interval: u64,
+    ) -> Result<Option<u64>, ProviderError>;
+
+    /// Determines whether or not the chat is in subonly mode.
+    fn subonly(&mut self) -> Result<bool, ProviderError>;
+
+    /// Puts the chat in subonly mode, or takes it out of subonly mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Whether or not the chat should be in subonly mode
+    fn set_subonly(&mut self, on: bool) -> Result<(), ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the number of seconds that chatters must wait between messages
+    /// from the redis caching layer, if the chat is in slowmode.
+    fn slowmode_interval(&mut self) -> Result<Option<u64>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(SLOWMODE_KEY))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Puts the chat in slowmode with the given interval in the redis caching
+    /// layer, or takes the chat out of slowmode.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - (optional) The number of seconds that chatters must
+    /// wait between messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, chat_modes::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut modes = Cache::new(&mut conn);
+    /// modes.set_slowmode_interval(Some(5))?;
+    ///
+    /// assert_eq!(modes.slowmode_interval()?, Some(5));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_slowmode_interval(&mut self, interval: Option<u64>) -> Result<(), ProviderError> {
+        match interval {
+            Some(interval) => redis::cmd("SET")
+                .arg(self.key(SLOWMODE_KEY))
+                .arg(interval)
+                .query(self.connection),
+            None => redis::cmd("DEL")
+                .arg(self.key(SLOWMODE_KEY))
+                .query::<()>(self.connection),
+        }?;
+
+        self.publish_invalidation(&Invalidation::ChatModes)
+    }
+
+    /// Records a message sent by the given user in the redis caching layer,
+    /// unless they sent a message within the given interval. Users' last
+    /// messages are forgotten once the interval has passed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending a message
+    /// * `interval` - The number of seconds that chatters must wait between
+    /// messages
+    fn claim_message_slot(
+        &mut self,
+        user_id: u64,
+        interval: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        let now = Utc::now().timestamp_millis();
+        let last_message: Option<i64> = redis::cmd("GET")
+            .arg(self.key(last_message_key(user_id)))
+            .query(self.connection)?;
+
+        // The remaining time is rounded up, so that chatters retrying after
+        // the countdown aren't rejected again
+        let interval_ms = (interval * 1000) as i64;
+        if let Some(elapsed) = last_message.map(|last| now - last) {
+            if elapsed < interval_ms {
+                return Ok(Some(((interval_ms - elapsed + 999) / 1000) as u64));
+            }
+        }
+
+        redis::cmd("SET")
+            .arg(self.key(last_message_key(user_id)))
+            .arg(now)
+            .arg("EX")
+            .arg(interval.max(1))
+            .query::<()>(self.connection)?;
+
+        Ok(None)
+    }
+
+    /// Determines whether or not the chat is in subonly mode according to
+    /// the redis caching layer.
+    fn subonly(&mut self) -> Result<bool, ProviderError> {
+        redis::cmd("EXISTS")
+            .arg(self.key(SUBONLY_KEY))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Puts the chat in subonly mode in the redis caching layer, or takes it
+    /// out of subonly mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Whether or not the chat should be in subonly mode
+    fn set_subonly(&mut self, on: bool) -> Result<(), ProviderError> {
+        if on {
+            redis::cmd("SET")
+                .arg(self.key(SUBONLY_KEY))
+                .arg(true)
+                .query(self.connection)
+        } else {
+            redis::cmd("DEL")
+                .arg(self.key(SUBONLY_KEY))
+                .query::<()>(self.connection)
+        }?;
+
+        self.publish_invalidation(&Invalidation::ChatModes)
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Gets the number of seconds that chatters must wait between messages
+    /// from the caching layer, if the chat is in slowmode.
+    fn slowmode_interval(&mut self) -> Result<Option<u64>, ProviderError> {
+        self.cache.slowmode_interval()
+    }
+
+    /// Puts the chat in slowmode with the given interval in the caching
+    /// layer, or takes the chat out of slowmode.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - (optional) The number of seconds that chatters must
+    /// wait between messages
+    fn set_slowmode_interval(&mut self, interval: Option<u64>) -> Result<(), ProviderError> {
+        self.cache.set_slowmode_interval(interval)
+    }
+
+    /// Records a message sent by the given user in the caching layer, unless
+    /// they sent a message within the given interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending a message
+    /// * `interval` - The number of seconds that chatters must wait between
+    /// messages
+    fn claim_message_slot(
+        &mut self,
+        user_id: u64,
+        interval: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        self.cache.claim_message_slot(user_id, interval)
+    }
+
+    /// Determines whether or not the chat is in subonly mode according to the
+    /// caching layer.
+    fn subonly(&mut self) -> Result<bool, ProviderError> {
+        self.cache.subonly()
+    }
+
+    /// Puts the chat in subonly mode in the caching layer, or takes it out of
+    /// subonly mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Whether or not the chat should be in subonly mode
+    fn set_subonly(&mut self, on: bool) -> Result<(), ProviderError> {
+        self.cache.set_subonly(on)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_slowmode() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        redis::cmd("DEL")
+            .arg(last_message_key(42069))
+            .query::<()>(&mut conn)?;
+
+        let mut modes = Cache::new(&mut conn);
+        modes.set_slowmode_interval(Some(30))?;
+        assert_eq!(modes.slowmode_interval()?, Some(30));
+
+        // The second message should be rejected with a countdown
+        assert_eq!(modes.claim_message_slot(42069, 30)?, None);
+        assert!(modes
+            .claim_message_slot(42069, 30)?
+            .is_some_and(|retry_after| retry_after > 0 && retry_after <= 30));
+
+        modes.set_slowmode_interval(None)?;
+        assert_eq!(modes.slowmode_interval()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subonly() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut modes = Cache::new(&mut conn);
+        modes.set_subonly(true)?;
+        assert!(modes.subonly()?);
+
+        modes.set_subonly(false)?;
+        assert!(!modes.subonly()?);
+
+        Ok(())
+    }
+}
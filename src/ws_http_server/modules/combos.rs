@@ -0,0 +1,126 @@
+use super::{Cache, Hybrid, ProviderError};
+
+/// The redis key holding the code of the emote being comboed.
+const EMOTE_KEY: &str = "combo_emote";
+
+/// The redis key holding the number of consecutive messages consisting only
+/// of the emote being comboed.
+const COUNT_KEY: &str = "combo_count";
+
+/// Provider represents an arbitrary backend for the combos service, which
+/// tracks runs of consecutive public messages consisting only of the same
+/// emote. Combos are ephemeral, and are therefore only stored in the caching
+/// layer.
+pub trait Provider {
+    /// Extends the running combo if it is of the given emote, or starts a new
+    /// combo of the emote otherwise, returning the length of the combo.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The code of the emote that was sent
+    fn extend_combo(&mut self, emote: &str) -> Result<u64, ProviderError>;
+
+    /// Ends the running combo, if there is one.
+    fn break_combo(&mut self) -> Result<(), ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Extends the running combo in the redis caching layer if it is of the
+    /// given emote, or starts a new combo of the emote otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The code of the emote that was sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, combos::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut combos = Cache::new(&mut conn);
+    /// combos.break_combo()?;
+    ///
+    /// combos.extend_combo("PEPE")?;
+    /// assert_eq!(combos.extend_combo("PEPE")?, 2);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn extend_combo(&mut self, emote: &str) -> Result<u64, ProviderError> {
+        let running: Option<String> = redis::cmd("GET")
+            .arg(self.key(EMOTE_KEY))
+            .query(self.connection)?;
+
+        if running.as_deref() == Some(emote) {
+            return redis::cmd("INCR")
+                .arg(self.key(COUNT_KEY))
+                .query(self.connection)
+                .map_err(|e| e.into());
+        }
+
+        redis::cmd("MSET")
+            .arg(self.key(EMOTE_KEY))
+            .arg(emote)
+            .arg(self.key(COUNT_KEY))
+            .arg(1)
+            .query::<()>(self.connection)?;
+
+        Ok(1)
+    }
+
+    /// Ends the running combo in the redis caching layer, if there is one.
+    fn break_combo(&mut self) -> Result<(), ProviderError> {
+        redis::cmd("DEL")
+            .arg(self.key(EMOTE_KEY))
+            .arg(self.key(COUNT_KEY))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Extends the running combo in the caching layer if it is of the given
+    /// emote, or starts a new combo of the emote otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The code of the emote that was sent
+    fn extend_combo(&mut self, emote: &str) -> Result<u64, ProviderError> {
+        self.cache.extend_combo(emote)
+    }
+
+    /// Ends the running combo in the caching layer, if there is one.
+    fn break_combo(&mut self) -> Result<(), ProviderError> {
+        self.cache.break_combo()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_combos() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut combos = Cache::new(&mut conn);
+        combos.break_combo()?;
+
+        assert_eq!(combos.extend_combo("PEPE")?, 1);
+        assert_eq!(combos.extend_combo("PEPE")?, 2);
+
+        // A different emote starts a new combo
+        assert_eq!(combos.extend_combo("OMEGALUL")?, 1);
+
+        combos.break_combo()?;
+        assert_eq!(combos.extend_combo("OMEGALUL")?, 1);
+
+        Ok(())
+    }
+}
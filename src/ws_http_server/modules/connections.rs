@@ -0,0 +1,283 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{Cache, ProviderError};
+
+use std::collections::{HashMap, HashSet};
+
+/// The number of seconds that a connection remains registered for after its
+/// last heartbeat. Connections held by a node that stops sending heartbeats
+/// (e.g. because it crashed) are dropped from the registry once they lapse.
+pub const CONNECTION_TTL: i64 = 90;
+
+/// The versions of the chat protocol that clients may negotiate, oldest
+/// first.
+pub const PROTOCOL_VERSIONS: [u32; 1] = [1];
+
+/// The redis sorted set holding the ID of each registered connection, scored
+/// by the time at which its registration lapses.
+const EXPIRIES_KEY: &str = "connections";
+
+/// The redis hash holding each registered connection, keyed by its ID.
+const CONNECTIONS_KEY: &str = "connections::info";
+
+/// Connection represents a chat connection held open by any node.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Connection {
+    /// The unique ID of the connection
+    id: String,
+
+    /// The node holding the connection
+    node: String,
+
+    /// The ID of the user who opened the connection, if they authenticated
+    user_id: Option<u64>,
+
+    /// The IP address from which the connection was opened
+    ip: String,
+
+    /// The time at which the connection was opened
+    connected_at: DateTime<Utc>,
+
+    /// The version of the chat protocol negotiated by the client
+    protocol_version: u32,
+}
+
+impl Connection {
+    /// Creates a new connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique ID of the connection
+    /// * `node` - The node holding the connection
+    /// * `user_id` - (optional) The ID of the user who opened the connection
+    /// * `ip` - The IP address from which the connection was opened
+    /// * `connected_at` - The time at which the connection was opened
+    /// * `protocol_version` - The version of the chat protocol negotiated by
+    /// the client
+    pub fn new(
+        id: &str,
+        node: &str,
+        user_id: Option<u64>,
+        ip: &str,
+        connected_at: DateTime<Utc>,
+        protocol_version: u32,
+    ) -> Self {
+        Self {
+            id: id.to_owned(),
+            node: node.to_owned(),
+            user_id,
+            ip: ip.to_owned(),
+            connected_at,
+            protocol_version,
+        }
+    }
+
+    /// Retreives the unique ID of the connection.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Retreives the node holding the connection.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Retreives the ID of the user who opened the connection, if they
+    /// authenticated.
+    pub fn user_id(&self) -> Option<u64> {
+        self.user_id
+    }
+
+    /// Retreives the IP address from which the connection was opened.
+    pub fn ip(&self) -> &str {
+        &self.ip
+    }
+
+    /// Retreives the time at which the connection was opened.
+    pub fn connected_at(&self) -> DateTime<Utc> {
+        self.connected_at
+    }
+
+    /// Retreives the version of the chat protocol negotiated by the client.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+}
+
+/// Provider represents an arbitrary backend for the connection registry,
+/// which keeps track of the chat connections held open by every node.
+/// Connections are ephemeral, and are therefore only stored in the caching
+/// layer, which is shared by each node.
+pub trait Provider {
+    /// Registers the given connection, or renews its registration if it is
+    /// already registered. Connections must be renewed at least once every
+    /// `CONNECTION_TTL` seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection that should be registered
+    /// * `at` - The current time
+    fn register_connection(
+        &mut self,
+        connection: &Connection,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError>;
+
+    /// Removes the connection with the given ID from the registry, returning
+    /// whether or not it was registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the connection that was closed
+    fn deregister_connection(&mut self, id: &str) -> Result<bool, ProviderError>;
+
+    /// Gets each of the connections registered by any node whose
+    /// registration hasn't lapsed, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - The current time
+    fn connections(&mut self, at: DateTime<Utc>) -> Result<Vec<Connection>, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Registers the given connection in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection that should be registered
+    /// * `at` - The current time
+    fn register_connection(
+        &mut self,
+        connection: &Connection,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError> {
+        let expires_at = (at + Duration::seconds(CONNECTION_TTL)).timestamp();
+
+        redis::pipe()
+            .atomic()
+            .cmd("ZADD")
+            .arg(self.key(EXPIRIES_KEY))
+            .arg(expires_at)
+            .arg(connection.id())
+            .ignore()
+            .cmd("HSET")
+            .arg(self.key(CONNECTIONS_KEY))
+            .arg(connection.id())
+            .arg(serde_json::to_string(connection)?)
+            .ignore()
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the connection with the given ID from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the connection that was closed
+    fn deregister_connection(&mut self, id: &str) -> Result<bool, ProviderError> {
+        let (removed,): (u64,) = redis::pipe()
+            .atomic()
+            .cmd("ZREM")
+            .arg(self.key(EXPIRIES_KEY))
+            .arg(id)
+            .cmd("HDEL")
+            .arg(self.key(CONNECTIONS_KEY))
+            .arg(id)
+            .ignore()
+            .query(self.connection)?;
+
+        Ok(removed > 0)
+    }
+
+    /// Gets each of the live connections registered in the redis caching
+    /// layer, dropping those whose registration has lapsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - The current time
+    fn connections(&mut self, at: DateTime<Utc>) -> Result<Vec<Connection>, ProviderError> {
+        let (live, registered): (Vec<String>, HashMap<String, String>) = redis::pipe()
+            .atomic()
+            .cmd("ZRANGEBYSCORE")
+            .arg(self.key(EXPIRIES_KEY))
+            .arg(at.timestamp())
+            .arg("+inf")
+            .cmd("HGETALL")
+            .arg(self.key(CONNECTIONS_KEY))
+            .query(self.connection)?;
+
+        let live: HashSet<String> = live.into_iter().collect();
+        let stale: Vec<&String> = registered.keys().filter(|id| !live.contains(*id)).collect();
+
+        // Connections held by nodes that stopped renewing them are pruned, so
+        // that the registry doesn't grow unbounded
+        if !stale.is_empty() {
+            redis::pipe()
+                .cmd("ZREMRANGEBYSCORE")
+                .arg(self.key(EXPIRIES_KEY))
+                .arg("-inf")
+                .arg(format!("({}", at.timestamp()))
+                .ignore()
+                .cmd("HDEL")
+                .arg(self.key(CONNECTIONS_KEY))
+                .arg(stale)
+                .ignore()
+                .query::<()>(self.connection)?;
+        }
+
+        let mut connections = registered
+            .iter()
+            .filter(|(id, _)| live.contains(*id))
+            .map(|(_, raw)| serde_json::from_str(raw))
+            .collect::<Result<Vec<Connection>, _>>()?;
+        connections.sort_by_key(|connection| connection.connected_at());
+
+        Ok(connections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_cache() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut registry = Cache::new(&mut conn).with_prefix("test_connections::");
+        let now = Utc::now();
+
+        let first = Connection::new("a", "node-1", Some(69420), "127.0.0.1", now, 1);
+        let second = Connection::new(
+            "b",
+            "node-2",
+            None,
+            "127.0.0.2",
+            now + Duration::seconds(1),
+            2,
+        );
+        registry.register_connection(&first, now)?;
+        registry.register_connection(&second, now)?;
+
+        // Connections registered by any node are listed, oldest first
+        assert_eq!(
+            registry.connections(now)?,
+            vec![first.clone(), second.clone()]
+        );
+
+        // Connections that aren't renewed lapse
+        let later = now + Duration::seconds(CONNECTION_TTL + 1);
+        registry.register_connection(&second, later)?;
+        assert_eq!(registry.connections(later)?, vec![second.clone()]);
+        assert!(!registry.deregister_connection(first.id())?);
+
+        assert!(registry.deregister_connection(second.id())?);
+        assert_eq!(registry.connections(later)?, vec![]);
+
+        Ok(())
+    }
+}
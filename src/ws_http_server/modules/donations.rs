@@ -0,0 +1,484 @@
+use actix_web::{
+    web::{Data, Json, Query},
+    Error as HttpError, Scope,
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    sql_types::{Timestamp, Varchar},
+    Connection, ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{last_insert_id, UnsignedBigint},
+            donation::{
+                Donation, GiftedSubscription, NewDonation, NewGiftedSubscription, TopDonor,
+                DEFAULT_CURRENCY,
+            },
+            event::{Event, EventKind, EventTarget},
+            schema::{donations, gifted_subscriptions},
+            user::Role,
+        },
+        auth::{capability::CanRecordDonations, RequireCapability},
+        server::State,
+    },
+    name_resolver::Provider as NameResolverProvider,
+    roles::Provider as RolesProvider,
+    subscriptions::Provider as SubscriptionsProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The redis channel on which each recorded donation and gifted subscription
+/// is published, so that it may be pushed to every connected chatter.
+pub const DONATION_CHANNEL: &str = "donations";
+
+/// The number of entries returned by the aggregate routes if no limit is
+/// specified.
+const DEFAULT_LIMIT: usize = 10;
+
+/// The maximum number of entries that may be returned by the aggregate
+/// routes.
+const MAX_LIMIT: usize = 100;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the donations module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/donations")
+        .service(recent_donations)
+        .service(top_donors)
+        .service(recent_gifts)
+        .service(record_donation)
+        .service(record_gift)
+}
+
+/// DonationRequest represents a request to record a donation.
+#[derive(Deserialize)]
+pub struct DonationRequest {
+    /// (optional) The ID of the user who donated, if the donor has an account
+    donor_id: Option<u64>,
+
+    /// The name displayed for the donor
+    donor: String,
+
+    /// The amount donated, in the smallest unit of its currency
+    amount: u64,
+
+    /// (optional) The ISO 4217 code of the currency in which the amount was
+    /// donated
+    currency: Option<String>,
+
+    /// (optional) The message sent alongside the donation
+    message: Option<String>,
+}
+
+/// GiftRequest represents a request to record a gifted subscription.
+#[derive(Deserialize)]
+pub struct GiftRequest {
+    /// (optional) The ID of the user who gifted the subscription, if the
+    /// gifter has an account
+    gifter_id: Option<u64>,
+
+    /// The name displayed for the gifter
+    gifter: String,
+
+    /// The username of the user to whom the subscription was gifted
+    recipient: String,
+
+    /// The tier of the gifted subscription
+    tier: u8,
+
+    /// (optional) The number of months for which the subscription was gifted
+    months: Option<u8>,
+
+    /// (optional) The amount paid for the gift, in the smallest unit of its
+    /// currency
+    amount: Option<u64>,
+
+    /// (optional) The ISO 4217 code of the currency in which the gift was
+    /// paid for
+    currency: Option<String>,
+
+    /// (optional) The message sent alongside the gift
+    message: Option<String>,
+}
+
+/// RecentQuery represents the query parameters accepted by the routes
+/// listing recent donations and gifts.
+#[derive(Deserialize)]
+pub struct RecentQuery {
+    /// (optional) The maximum number of entries that should be returned
+    limit: Option<usize>,
+}
+
+/// TopDonorsQuery represents the query parameters accepted by the top
+/// donors route.
+#[derive(Deserialize)]
+pub struct TopDonorsQuery {
+    /// (optional) The ISO 4217 code of the currency whose donations should be
+    /// totalled
+    currency: Option<String>,
+
+    /// (optional) The number of days of donations that should be totalled
+    days: Option<u32>,
+
+    /// (optional) The maximum number of donors that should be returned
+    limit: Option<usize>,
+}
+
+/// Records a donation made to the streamer, and notifies every connected
+/// chatter of it.
+#[post("")]
+pub async fn record_donation(
+    state: Data<State>,
+    _auth: RequireCapability<CanRecordDonations>,
+    body: Json<DonationRequest>,
+) -> Result<Json<Donation>, ProviderError> {
+    let donation = NewDonation::new(
+        &body.donor,
+        body.amount,
+        body.currency.as_deref().unwrap_or(DEFAULT_CURRENCY),
+        Utc::now(),
+    )
+    .with_donor_id(body.donor_id)
+    .with_message(body.message.as_deref());
+    if !donation.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "donation" });
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let donation = Persistent::new(&persistent_conn).record_donation(&donation)?;
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .publish_donation(donation.clone())?;
+
+    Ok(Json(donation))
+}
+
+/// Records a subscription gifted to the chatter with the given username,
+/// adding the gifted months to their subscription, and notifies every
+/// connected chatter of it.
+#[post("/gifts")]
+pub async fn record_gift(
+    state: Data<State>,
+    _auth: RequireCapability<CanRecordDonations>,
+    body: Json<GiftRequest>,
+) -> Result<Json<GiftedSubscription>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    let recipient_id = hybrid
+        .user_id_for(&body.recipient)?
+        .ok_or(ProviderError::InvalidArgument { arg: "recipient" })?;
+
+    let now = Utc::now();
+    let gift =
+        NewGiftedSubscription::new(&body.gifter, recipient_id, &body.recipient, body.tier, now)
+            .with_gifter_id(body.gifter_id)
+            .with_months(body.months.unwrap_or(1))
+            .with_price(
+                body.amount.unwrap_or(0),
+                body.currency.as_deref().unwrap_or(DEFAULT_CURRENCY),
+            )
+            .with_message(body.message.as_deref());
+    if !gift.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "gift" }.into());
+    }
+
+    let gift = Persistent::new(&persistent_conn).record_gift(&gift)?;
+
+    let sub = gift.apply_to(hybrid.get_subscription(recipient_id)?.as_ref(), now);
+    hybrid.set_subscription(&sub)?;
+    hybrid.give_role(recipient_id, &Role::Subscriber)?;
+
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .publish_gift(gift.clone())?;
+
+    Ok(Json(gift))
+}
+
+/// Gets the most recent donations, most recent first.
+#[get("/recent")]
+pub async fn recent_donations(
+    state: Data<State>,
+    query: Query<RecentQuery>,
+) -> Result<Json<Vec<Donation>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+
+    Persistent::new(&persistent_conn)
+        .recent_donations(query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .map(Json)
+}
+
+/// Gets the donors who donated the most in a currency, optionally over the
+/// last few days only, most generous first.
+#[get("/top")]
+pub async fn top_donors(
+    state: Data<State>,
+    query: Query<TopDonorsQuery>,
+) -> Result<Json<Vec<TopDonor>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+
+    Persistent::new(&persistent_conn)
+        .top_donors(
+            query.currency.as_deref().unwrap_or(DEFAULT_CURRENCY),
+            query
+                .days
+                .map(|days| Utc::now() - Duration::days(days as i64)),
+            query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        )
+        .map(Json)
+}
+
+/// Gets the most recently gifted subscriptions, most recent first.
+#[get("/gifts/recent")]
+pub async fn recent_gifts(
+    state: Data<State>,
+    query: Query<RecentQuery>,
+) -> Result<Json<Vec<GiftedSubscription>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+
+    Persistent::new(&persistent_conn)
+        .recent_gifts(query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .map(Json)
+}
+
+/// Provider represents an arbitrary backend for the donations service.
+/// Donations and gifts are financial records that must never be lost, and
+/// are therefore only stored in the persistent layer.
+pub trait Provider {
+    /// Records the given donation, returning it alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `donation` - The donation that should be recorded
+    fn record_donation(&mut self, donation: &NewDonation) -> Result<Donation, ProviderError>;
+
+    /// Records the given gifted subscription, returning it alongside its
+    /// assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `gift` - The gift that should be recorded
+    fn record_gift(
+        &mut self,
+        gift: &NewGiftedSubscription,
+    ) -> Result<GiftedSubscription, ProviderError>;
+
+    /// Gets up to `limit` of the most recent donations, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of donations that should be returned
+    fn recent_donations(&mut self, limit: usize) -> Result<Vec<Donation>, ProviderError>;
+
+    /// Gets up to `limit` of the most recently gifted subscriptions, most
+    /// recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of gifts that should be returned
+    fn recent_gifts(&mut self, limit: usize) -> Result<Vec<GiftedSubscription>, ProviderError>;
+
+    /// Gets up to `limit` of the donors who donated the most in the given
+    /// currency, most generous first. Donors without accounts are told apart
+    /// by their displayed names.
+    ///
+    /// # Arguments
+    ///
+    /// * `currency` - The ISO 4217 code of the currency whose donations
+    /// should be totalled
+    /// * `since` - (optional) The time before which donations are ignored
+    /// * `limit` - The maximum number of donors that should be returned
+    fn top_donors(
+        &mut self,
+        currency: &str,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<TopDonor>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes the given donation as an event, so that it may be pushed to
+    /// every connected chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `donation` - The donation that was recorded
+    fn publish_donation(&mut self, donation: Donation) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(DONATION_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                EventTarget::All,
+                EventKind::Donation(donation),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Publishes the given gifted subscription as an event, so that it may
+    /// be pushed to every connected chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `gift` - The gift that was recorded
+    fn publish_gift(&mut self, gift: GiftedSubscription) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(DONATION_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                EventTarget::All,
+                EventKind::GiftedSubscription(gift),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Records the given donation in the MySQL database, returning it
+    /// alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `donation` - The donation that should be recorded
+    fn record_donation(&mut self, donation: &NewDonation) -> Result<Donation, ProviderError> {
+        self.connection.transaction(|| {
+            diesel::insert_into(donations::table)
+                .values(donation)
+                .execute(self.connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched separately
+            last_insert_id(self.connection)
+                .map(|id| donation.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Records the given gifted subscription in the MySQL database,
+    /// returning it alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `gift` - The gift that should be recorded
+    fn record_gift(
+        &mut self,
+        gift: &NewGiftedSubscription,
+    ) -> Result<GiftedSubscription, ProviderError> {
+        self.connection.transaction(|| {
+            diesel::insert_into(gifted_subscriptions::table)
+                .values(gift)
+                .execute(self.connection)?;
+
+            last_insert_id(self.connection)
+                .map(|id| gift.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Gets up to `limit` of the most recent donations from the MySQL
+    /// database, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of donations that should be returned
+    fn recent_donations(&mut self, limit: usize) -> Result<Vec<Donation>, ProviderError> {
+        donations::dsl::donations
+            .order(donations::dsl::id.desc())
+            .limit(limit as i64)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets up to `limit` of the most recently gifted subscriptions from the
+    /// MySQL database, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of gifts that should be returned
+    fn recent_gifts(&mut self, limit: usize) -> Result<Vec<GiftedSubscription>, ProviderError> {
+        gifted_subscriptions::dsl::gifted_subscriptions
+            .order(gifted_subscriptions::dsl::id.desc())
+            .limit(limit as i64)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets up to `limit` of the donors who donated the most in the given
+    /// currency from the MySQL database, most generous first.
+    ///
+    /// # Arguments
+    ///
+    /// * `currency` - The ISO 4217 code of the currency whose donations
+    /// should be totalled
+    /// * `since` - (optional) The time before which donations are ignored
+    /// * `limit` - The maximum number of donors that should be returned
+    fn top_donors(
+        &mut self,
+        currency: &str,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<TopDonor>, ProviderError> {
+        let since = since.unwrap_or(DateTime::UNIX_EPOCH).naive_utc();
+
+        diesel::sql_query(
+            "SELECT donor_id, donor, CAST(SUM(amount) AS UNSIGNED) AS total \
+             FROM donations WHERE currency = ? AND donated_at >= ? \
+             GROUP BY donor_id, donor ORDER BY total DESC, donor LIMIT ?",
+        )
+        .bind::<Varchar, _>(currency)
+        .bind::<Timestamp, _>(since)
+        .bind::<UnsignedBigint, _>(limit as u64)
+        .load(self.connection)
+        .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_persistent() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+        let mut donations = Persistent::new(&conn);
+        let now = Utc::now();
+
+        // XTS is reserved by ISO 4217 for testing, and never donated in
+        let donation = donations.record_donation(
+            &NewDonation::new("test_donations_a", 700, "XTS", now).with_message(Some("gnomes")),
+        )?;
+        donations.record_donation(&NewDonation::new("test_donations_b", 300, "XTS", now))?;
+        donations.record_donation(&NewDonation::new("test_donations_b", 300, "XTS", now))?;
+
+        assert_eq!(donations.recent_donations(3)?.last(), Some(&donation));
+
+        let ranking = donations.top_donors("XTS", Some(now - Duration::seconds(1)), 2)?;
+        assert_eq!(ranking[0].donor(), "test_donations_a");
+        assert_eq!(ranking[0].total(), 700);
+        assert_eq!(ranking[1].total(), 600);
+
+        let gift = donations.record_gift(
+            &NewGiftedSubscription::new("test_donations_a", 69420, "essaywriter", 2, now)
+                .with_months(3),
+        )?;
+        assert_eq!(donations.recent_gifts(1)?, vec![gift]);
+
+        Ok(())
+    }
+}
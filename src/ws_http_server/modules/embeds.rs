@@ -0,0 +1,401 @@
+use actix_web::{
+    rt,
+    web::{self, Data, Json, Query},
+    Scope,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            embed::{Embed, EmbedRank},
+            event::{Event, EventKind, EventTarget},
+        },
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::time::Duration;
+
+/// The redis channel on which the ranking of the most-watched embeds is
+/// periodically published, so that it may be pushed to every connected
+/// chatter.
+pub const EMBED_CHANNEL: &str = "embeds";
+
+/// The number of seconds for which a chatter is considered to be watching
+/// the embed they most recently linked.
+pub const EMBED_TTL: i64 = 1800;
+
+/// The interval at which the ranking of the most-watched embeds is
+/// published.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The redis set holding the key of every embed with recent watchers.
+const EMBEDS_KEY: &str = "embeds";
+
+/// The redis hash holding the key of the embed most recently linked by each
+/// chatter, keyed by their IDs.
+const WATCHERS_KEY: &str = "embed_watchers";
+
+/// The number of embeds returned by the ranking route if no limit is
+/// specified, and published on the embed channel.
+const DEFAULT_RANKING_LENGTH: usize = 5;
+
+/// The maximum number of embeds that may be returned by the ranking route.
+const MAX_RANKING_LENGTH: usize = 25;
+
+/// Gets the redis key of the sorted set holding the chatters watching the
+/// given embed, scored by the time at which they last linked it.
+///
+/// # Arguments
+///
+/// * `embed_key` - The key of the embed whose viewers should be located
+fn viewers_key(embed_key: &str) -> String {
+    format!("embed_viewers::{}", embed_key)
+}
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the embeds module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/embeds").service(top_embeds)
+}
+
+/// Periodically publishes the ranking of the most-watched embeds on the
+/// embed channel for as long as the server is running.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_embed_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(PUBLISH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // A ranking that fails to be published is simply recomputed on
+            // the next tick
+            let _ = web::block(move || -> Result<(), ProviderError> {
+                let mut conn = state.cache_connection()?;
+                let persistent_conn = state.persistent_connection()?;
+
+                let ranking = Hybrid::new(
+                    Cache::new(&mut conn).with_prefix(state.key_prefix()),
+                    Persistent::new(&persistent_conn),
+                )
+                .top_embeds(DEFAULT_RANKING_LENGTH, Utc::now())?;
+
+                Cache::new(&mut conn)
+                    .with_prefix(state.key_prefix())
+                    .publish_ranking(ranking)
+            })
+            .await;
+        }
+    });
+}
+
+/// RankingQuery represents the query parameters accepted by the ranking
+/// route.
+#[derive(Deserialize)]
+pub struct RankingQuery {
+    /// (optional) The maximum number of embeds that should be returned
+    limit: Option<usize>,
+}
+
+/// Gets the embeds being watched by the most chatters, most-watched first.
+/// A chatter is considered to be watching the embed they most recently
+/// linked, for EMBED_TTL seconds after linking it.
+#[get("/top")]
+pub async fn top_embeds(
+    state: Data<State>,
+    query: Query<RankingQuery>,
+) -> Result<Json<Vec<EmbedRank>>, ProviderError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RANKING_LENGTH)
+        .min(MAX_RANKING_LENGTH);
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .top_embeds(limit, Utc::now())
+    .map(Json)
+}
+
+/// Provider represents an arbitrary backend for the embeds service, which
+/// tracks the streams that chatters are watching alongside the chat. Watched
+/// embeds are ephemeral, and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Records that the given chatter is watching the given embed, replacing
+    /// the embed they were previously watching.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter who linked the embed
+    /// * `embed` - The embed that the chatter linked
+    /// * `at` - The time at which the chatter linked the embed
+    fn record_embed(
+        &mut self,
+        user_id: u64,
+        embed: &Embed,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError>;
+
+    /// Gets the embeds being watched by the most chatters, most-watched
+    /// first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of embeds that should be returned
+    /// * `now` - The current time, before which watchers expire
+    fn top_embeds(
+        &mut self,
+        limit: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<EmbedRank>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes the given ranking of the most-watched embeds, so that it may
+    /// be pushed to every connected chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranking` - The embeds being watched by the most chatters
+    fn publish_ranking(&mut self, ranking: Vec<EmbedRank>) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(EMBED_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                EventTarget::All,
+                EventKind::Embeds(ranking),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records that the given chatter is watching the given embed in the
+    /// redis caching layer, replacing the embed they were previously
+    /// watching.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter who linked the embed
+    /// * `embed` - The embed that the chatter linked
+    /// * `at` - The time at which the chatter linked the embed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::embed::Embed, ws_http_server::modules::{Cache, embeds::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut embeds = Cache::new(&mut conn);
+    /// let embed = Embed::parse("twitch.tv/destiny").unwrap();
+    /// embeds.record_embed(69420, &embed, Utc::now())?;
+    ///
+    /// assert!(embeds.top_embeds(25, Utc::now())?.iter().any(|rank| rank.embed() == &embed));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn record_embed(
+        &mut self,
+        user_id: u64,
+        embed: &Embed,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError> {
+        let key = embed.key();
+        let previous: Option<String> = redis::cmd("HGET")
+            .arg(self.key(WATCHERS_KEY))
+            .arg(user_id)
+            .query(self.connection)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        // A chatter only watches one embed at a time
+        if let Some(previous) = previous.filter(|previous| previous != &key) {
+            pipe.cmd("ZREM")
+                .arg(self.key(viewers_key(&previous)))
+                .arg(user_id)
+                .ignore();
+        }
+
+        pipe.cmd("HSET")
+            .arg(self.key(WATCHERS_KEY))
+            .arg(user_id)
+            .arg(&key)
+            .ignore()
+            .cmd("ZADD")
+            .arg(self.key(viewers_key(&key)))
+            .arg(at.timestamp())
+            .arg(user_id)
+            .ignore()
+            .cmd("SADD")
+            .arg(self.key(EMBEDS_KEY))
+            .arg(&key)
+            .ignore()
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the embeds being watched by the most chatters from the redis
+    /// caching layer, most-watched first. Expired watchers are pruned, and
+    /// embeds left without watchers are forgotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of embeds that should be returned
+    /// * `now` - The current time, before which watchers expire
+    fn top_embeds(
+        &mut self,
+        limit: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<EmbedRank>, ProviderError> {
+        let keys: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(self.key(EMBEDS_KEY))
+            .query(self.connection)?;
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.cmd("ZREMRANGEBYSCORE")
+                .arg(self.key(viewers_key(key)))
+                .arg("-inf")
+                .arg(format!("({}", now.timestamp() - EMBED_TTL))
+                .ignore()
+                .cmd("ZCARD")
+                .arg(self.key(viewers_key(key)));
+        }
+        let counts: Vec<u64> = pipe.query(self.connection)?;
+
+        let (live, expired): (Vec<_>, Vec<_>) = keys
+            .into_iter()
+            .zip(counts)
+            .partition(|(_, chatters)| *chatters > 0);
+
+        if !expired.is_empty() {
+            redis::cmd("SREM")
+                .arg(self.key(EMBEDS_KEY))
+                .arg(
+                    expired
+                        .into_iter()
+                        .map(|(key, _)| key)
+                        .collect::<Vec<String>>(),
+                )
+                .query::<()>(self.connection)?;
+        }
+
+        let mut ranking = live
+            .into_iter()
+            .filter_map(|(key, chatters)| {
+                Embed::from_key(&key).map(|embed| EmbedRank::new(embed, chatters))
+            })
+            .collect::<Vec<EmbedRank>>();
+        ranking.sort_by(|a, b| {
+            b.chatters()
+                .cmp(&a.chatters())
+                .then_with(|| a.embed().key().cmp(&b.embed().key()))
+        });
+        ranking.truncate(limit);
+
+        Ok(ranking)
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Records that the given chatter is watching the given embed in the
+    /// caching layer, replacing the embed they were previously watching.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter who linked the embed
+    /// * `embed` - The embed that the chatter linked
+    /// * `at` - The time at which the chatter linked the embed
+    fn record_embed(
+        &mut self,
+        user_id: u64,
+        embed: &Embed,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError> {
+        self.cache.record_embed(user_id, embed, at)
+    }
+
+    /// Gets the embeds being watched by the most chatters from the caching
+    /// layer, most-watched first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of embeds that should be returned
+    /// * `now` - The current time, before which watchers expire
+    fn top_embeds(
+        &mut self,
+        limit: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<EmbedRank>, ProviderError> {
+        self.cache.top_embeds(limit, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_embeds() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut embeds = Cache::new(&mut conn);
+        let now = Utc::now();
+        let first = Embed::parse("#twitch/test_embeds_a").unwrap();
+        let second = Embed::parse("#twitch/test_embeds_b").unwrap();
+
+        embeds.record_embed(1, &first, now)?;
+        embeds.record_embed(2, &first, now)?;
+        embeds.record_embed(3, &second, now)?;
+
+        let chatters_for = |ranking: &[EmbedRank], embed: &Embed| {
+            ranking
+                .iter()
+                .find(|rank| rank.embed() == embed)
+                .map(EmbedRank::chatters)
+        };
+
+        let ranking = embeds.top_embeds(MAX_RANKING_LENGTH, now)?;
+        assert_eq!(chatters_for(&ranking, &first), Some(2));
+        assert_eq!(chatters_for(&ranking, &second), Some(1));
+
+        // Linking another embed stops the chatter from watching the first
+        embeds.record_embed(2, &second, now)?;
+        let ranking = embeds.top_embeds(MAX_RANKING_LENGTH, now)?;
+        assert_eq!(chatters_for(&ranking, &first), Some(1));
+        assert_eq!(chatters_for(&ranking, &second), Some(2));
+
+        // Watchers expire once EMBED_TTL seconds have elapsed
+        let ranking =
+            embeds.top_embeds(MAX_RANKING_LENGTH, now + Duration::seconds(EMBED_TTL + 1))?;
+        assert_eq!(chatters_for(&ranking, &first), None);
+        assert_eq!(chatters_for(&ranking, &second), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpRequest, HttpResponse, Scope,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{emote::Emote, schema::emotes},
+        auth::{capability::CanManageEmotes, RequireCapability},
+        etag,
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::slice;
+
+/// The redis hash holding each of the emotes, keyed by their codes.
+const EMOTES_KEY: &str = "emotes";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the emotes module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/emotes")
+        .service(list_emotes)
+        .service(get_emote)
+        .service(put_emote)
+        .service(delete_emote)
+}
+
+/// EmoteRequest represents a request to create or replace an emote.
+#[derive(Deserialize)]
+pub struct EmoteRequest {
+    /// A reference to the image rendered in place of the emote's code
+    image: String,
+
+    /// (optional) The minimum subscription tier required in order to use the
+    /// emote
+    tier: Option<u8>,
+}
+
+/// Gets each of the emotes that may be used in the chat, ordered by their
+/// codes. Responds with 304 Not Modified if the client's copy of the emote
+/// list is current.
+#[get("")]
+pub async fn list_emotes(
+    state: Data<State>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let emotes = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .emotes()?;
+
+    etag::respond(&req, &emotes)
+}
+
+/// Gets the emote with the given code.
+#[get("/{code}")]
+pub async fn get_emote(
+    state: Data<State>,
+    code: Path<String>,
+) -> Result<Option<Json<Emote>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .get_emote(&code)
+    .map(|emote| emote.map(Json))
+}
+
+/// Creates or replaces the emote with the given code. Only administrators
+/// may manage emotes.
+#[put("/{code}")]
+pub async fn put_emote(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageEmotes>,
+    code: Path<String>,
+    body: Json<EmoteRequest>,
+) -> Result<Json<Emote>, HttpError> {
+    let emote = Emote::new(&code, &body.image).with_tier(body.tier);
+    if !emote.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "emote" }.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .set_emote(&emote)?;
+
+    Ok(Json(emote))
+}
+
+/// Deletes the emote with the given code. Only administrators may manage
+/// emotes.
+#[delete("/{code}")]
+pub async fn delete_emote(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageEmotes>,
+    code: Path<String>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .remove_emote(&code)
+    .map(|removed| removed.map(|_| HttpResponse::NoContent().finish()))
+}
+
+/// Provider represents an arbitrary backend for the emotes service, which is
+/// the source of truth for the set of emotes that may be used in the chat.
+pub trait Provider {
+    /// Gets each of the emotes that may be used in the chat, ordered by their
+    /// codes.
+    fn emotes(&mut self) -> Result<Vec<Emote>, ProviderError>;
+
+    /// Retreives the emote with the given code, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be obtained
+    fn get_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError>;
+
+    /// Stores the given emote, replacing any existing emote with the same
+    /// code.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The emote that should be stored
+    fn set_emote(&mut self, emote: &Emote) -> Result<(), ProviderError>;
+
+    /// Removes the emote with the given code, returning the removed emote if
+    /// it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be removed
+    fn remove_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Stores each of the given emotes in the redis caching layer at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `emotes` - The emotes that should be stored
+    fn set_emotes(&mut self, emotes: &[Emote]) -> Result<(), ProviderError> {
+        // HSET requires at least one field
+        if emotes.is_empty() {
+            return Ok(());
+        }
+
+        let mut fields = Vec::with_capacity(emotes.len());
+        for emote in emotes {
+            fields.push((emote.code(), serde_json::to_string(emote)?));
+        }
+
+        redis::cmd("HSET")
+            .arg(self.key(EMOTES_KEY))
+            .arg(fields)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets each of the emotes held by the redis caching layer, ordered by
+    /// their codes.
+    fn emotes(&mut self) -> Result<Vec<Emote>, ProviderError> {
+        let mut emotes = redis::cmd("HVALS")
+            .arg(self.key(EMOTES_KEY))
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|emote| serde_json::from_str(emote))
+            .collect::<Result<Vec<Emote>, _>>()?;
+        emotes.sort_by(|a, b| a.code().cmp(b.code()));
+
+        Ok(emotes)
+    }
+
+    /// Retreives the emote with the given code from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be obtained
+    fn get_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        redis::cmd("HGET")
+            .arg(self.key(EMOTES_KEY))
+            .arg(code)
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given emote in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The emote that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, emotes::Provider}, spec::emote::Emote};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut emotes = Cache::new(&mut conn);
+    /// let emote = Emote::new("OverRustle", "OverRustle.png");
+    ///
+    /// emotes.set_emote(&emote)?;
+    /// assert_eq!(emotes.get_emote("OverRustle")?, Some(emote));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_emote(&mut self, emote: &Emote) -> Result<(), ProviderError> {
+        self.set_emotes(slice::from_ref(emote))
+    }
+
+    /// Removes the emote with the given code from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be removed
+    fn remove_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        let emote = self.get_emote(code)?;
+
+        redis::cmd("HDEL")
+            .arg(self.key(EMOTES_KEY))
+            .arg(code)
+            .query::<()>(self.connection)?;
+
+        Ok(emote)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets each of the emotes stored in the MySQL database, ordered by their
+    /// codes.
+    fn emotes(&mut self) -> Result<Vec<Emote>, ProviderError> {
+        emotes::table
+            .order(emotes::dsl::code.asc())
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the emote with the given code from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be obtained
+    fn get_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        emotes::table
+            .find(code)
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Stores the given emote in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The emote that should be stored
+    fn set_emote(&mut self, emote: &Emote) -> Result<(), ProviderError> {
+        diesel::replace_into(emotes::table)
+            .values(emote)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the emote with the given code from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be removed
+    fn remove_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        let emote = self.get_emote(code)?;
+
+        diesel::delete(emotes::table.find(code)).execute(self.connection)?;
+
+        Ok(emote)
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets each of the emotes that may be used in the chat, populating the
+    /// cache from the persistent layer if it holds none.
+    fn emotes(&mut self) -> Result<Vec<Emote>, ProviderError> {
+        match self.cache.emotes() {
+            Ok(emotes) if !emotes.is_empty() => Ok(emotes),
+            _ => self
+                .persistent
+                .emotes()
+                .and_then(|emotes| self.cache.set_emotes(&emotes).map(|_| emotes)),
+        }
+    }
+
+    /// Retreives the emote with the given code, populating the cache from
+    /// the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be obtained
+    fn get_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        match self.cache.get_emote(code) {
+            Ok(Some(emote)) => Ok(Some(emote)),
+            _ => self.persistent.get_emote(code).and_then(|emote| {
+                emote.map_or(Ok(None), |emote| {
+                    self.cache.set_emote(&emote).map(|_| Some(emote))
+                })
+            }),
+        }
+    }
+
+    /// Stores the given emote in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `emote` - The emote that should be stored
+    fn set_emote(&mut self, emote: &Emote) -> Result<(), ProviderError> {
+        self.persistent
+            .set_emote(emote)
+            .and_then(|_| self.cache.set_emote(emote))
+    }
+
+    /// Removes the emote with the given code from the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the emote that should be removed
+    fn remove_emote(&mut self, code: &str) -> Result<Option<Emote>, ProviderError> {
+        let emote = self.persistent.remove_emote(code)?;
+        self.cache.remove_emote(code)?;
+
+        Ok(emote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let emote = Emote::new("PepoG", "PepoG.png").with_tier(Some(1));
+
+        let mut emotes = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        emotes.set_emote(&emote)?;
+
+        assert_eq!(emotes.get_emote("PepoG")?, Some(emote.clone()));
+        assert!(emotes.emotes()?.contains(&emote));
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL").arg(EMOTES_KEY).query::<()>(&mut conn)?;
+        let mut emotes = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert!(emotes.emotes()?.contains(&emote));
+
+        assert_eq!(emotes.remove_emote("PepoG")?, Some(emote));
+        assert_eq!(emotes.get_emote("PepoG")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1399 @@
+use actix_web::{
+    http::header::CACHE_CONTROL,
+    rt,
+    web::{self, Bytes, Data},
+    Error as HttpError, HttpRequest, HttpResponse, Scope,
+};
+use chrono::Utc;
+use futures::{
+    channel::{
+        mpsc::{self, Receiver, Sender},
+        oneshot,
+    },
+    stream, StreamExt,
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::Connection as RedisConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    super::{
+        super::spec::{
+            event::{Event, EventKind, Handshake},
+            history::ChatMessage,
+            user::Role,
+            whisper::Whisper,
+        },
+        auth::AuthedUser,
+        dispatcher::DispatchError,
+        server::State,
+    },
+    announcements::ANNOUNCEMENT_CHANNEL,
+    approvals::APPROVAL_CHANNEL,
+    bans::MODERATION_CHANNEL,
+    bot_commands::BOT_REPLY_CHANNEL,
+    chat::CHAT_CHANNEL,
+    connections::{Connection, Provider as ConnectionsProvider, PROTOCOL_VERSIONS},
+    donations::DONATION_CHANNEL,
+    embeds::EMBED_CHANNEL,
+    history, ignores,
+    last_seen::Provider as LastSeenProvider,
+    name_resolver::Provider as NameResolver,
+    roles::Provider as RolesProvider,
+    sessions::REVOCATION_CHANNEL,
+    stream::STREAM_CHANNEL,
+    throttle,
+    whispers::{self, UNREAD_CHANNEL},
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+/// The redis channels carrying the events pushed to connected chatters, before
+/// they are namespaced according to the deployment's key prefix.
+pub const BROADCAST_CHANNELS: [&str; 9] = [
+    CHAT_CHANNEL,
+    MODERATION_CHANNEL,
+    ANNOUNCEMENT_CHANNEL,
+    APPROVAL_CHANNEL,
+    BOT_REPLY_CHANNEL,
+    DONATION_CHANNEL,
+    EMBED_CHANNEL,
+    STREAM_CHANNEL,
+    UNREAD_CHANNEL,
+];
+
+/// The interval at which a comment is sent to each event stream while no
+/// events are, so that idle streams aren't closed by proxies.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The interval at which the registration of each event stream held open by
+/// the node is renewed, well before registrations lapse.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The number of characters in the ID of each event stream's registered
+/// connection.
+const CONNECTION_ID_LENGTH: usize = 16;
+
+/// The number of frames that may be queued for a single event stream. Clients
+/// that fall further behind are disconnected, rather than buffered for.
+const CLIENT_BUFFER: usize = 64;
+
+/// The number of recent events retained by each node, such that clients
+/// reconnecting or polling again may catch up on the events that they missed.
+const BACKLOG_LENGTH: usize = 256;
+
+/// The header with which a reconnecting client names the last event that it
+/// received.
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// The number of milliseconds that clients are asked to wait before
+/// reconnecting to a dropped event stream.
+const RECONNECT_DELAY_MS: u64 = 3000;
+
+/// The time that a node waits before subscribing to the broadcast channels
+/// again once its subscription is lost.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the events module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/events").service(stream_events)
+}
+
+/// Streams each event pushed to the connecting chatter as server-sent events,
+/// for clients that can't hold a WebSocket open. The stream is read-only.
+/// Anonymous clients are only sent events targeting every chatter, while
+/// authenticated chatters are also sent the events targeting them, less
+/// those sent by users they ignore. Each client is first sent a greeting
+/// holding the state of the chat. Clients reconnecting with a Last-Event-ID
+/// header are then sent the events that they missed, so long as the node
+/// still retains them.
+#[get("")]
+pub async fn stream_events(
+    req: HttpRequest,
+    state: Data<State>,
+    user: Option<AuthedUser>,
+) -> Result<HttpResponse, HttpError> {
+    let connection_id = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CONNECTION_ID_LENGTH)
+        .collect::<String>();
+    let connection = connection_for(&state, &req, user.as_ref(), &connection_id);
+    let subscriber = subscriber_for(&state, user.as_ref(), connection)?;
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|id| id.parse().ok());
+
+    // The client is subscribed before the greeting is loaded, so that no
+    // event is missed in between
+    let relayed = state.event_relay().subscribe(subscriber, last_event_id);
+    let greeting = greeting_for(&state, user.as_ref())?;
+
+    // The stream holds the registration, such that the connection is
+    // deregistered as soon as the stream is dropped
+    let registration = Registration {
+        state: state.clone(),
+        id: connection_id,
+    };
+    let events = stream::iter(vec![greeting_frame(&greeting)?])
+        .chain(relayed)
+        .map(move |frame| {
+            let _ = &registration;
+
+            Ok::<Bytes, HttpError>(frame)
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .streaming(events))
+}
+
+/// Describes the connection through which a chatter makes a request to the
+/// relay, such that it may be registered among the connections held open by
+/// the deployment.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `req` - The request made to the relay
+/// * `user` - (optional) The authenticated chatter making the request
+/// * `id` - The ID under which the connection should be registered
+pub(crate) fn connection_for(
+    state: &State,
+    req: &HttpRequest,
+    user: Option<&AuthedUser>,
+    id: &str,
+) -> Connection {
+    Connection::new(
+        id,
+        state.node(),
+        user.map(AuthedUser::id),
+        &throttle::client_ip(req, state.trusted_proxies()),
+        Utc::now(),
+        PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1],
+    )
+}
+
+/// Registers the given connection, or renews its registration, such that it
+/// is listed among the connections held open by the deployment until the
+/// registration lapses. Authenticated chatters are recorded as having
+/// connected.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `connection` - The connection that should be registered
+pub(crate) fn register_connection(
+    state: &State,
+    connection: &Connection,
+) -> Result<(), ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let mut cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+    let now = Utc::now();
+
+    cache.register_connection(connection, now)?;
+
+    match connection.user_id() {
+        Some(user_id) => cache.record_connect(user_id, now),
+        None => Ok(()),
+    }
+}
+
+/// Gets the subscriber for the chatter making a request to the relay, and
+/// registers the connection through which the request is made. The username,
+/// roles and ignore list of authenticated chatters are loaded, such that they
+/// are sent the events targeting them or their roles, and none sent by the
+/// users they ignore.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `user` - (optional) The authenticated chatter making the request
+/// * `connection` - The connection through which the request is made
+pub(crate) fn subscriber_for(
+    state: &State,
+    user: Option<&AuthedUser>,
+    connection: Connection,
+) -> Result<Subscriber, ProviderError> {
+    register_connection(state, &connection)?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(Subscriber::default().with_connection(connection)),
+    };
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    let username = match hybrid.username_for(user.id())? {
+        Some(username) => username,
+        None => return Ok(Subscriber::default().with_connection(connection)),
+    };
+    let roles = hybrid.roles_for_user(user.id())?;
+
+    let ignored =
+        ignores::ignored_usernames(&mut conn, state.key_prefix(), &persistent_conn, user.id())?;
+
+    Ok(Subscriber::new(username)
+        .with_roles(roles)
+        .with_ignored(ignored)
+        .with_session_id(user.session_id().to_owned())
+        .with_connection(connection))
+}
+
+/// Gets the greeting that should be sent to the chatter connecting to the
+/// relay, including the handshake describing the chat's modes and pinned
+/// message. Authenticated chatters are sent the whispers queued for them while
+/// they were offline, which are then marked as delivered.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state
+/// * `user` - (optional) The authenticated chatter connecting to the relay
+pub(crate) fn greeting_for(
+    state: &State,
+    user: Option<&AuthedUser>,
+) -> Result<Greeting, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    // Anonymous chatters are handshaken under an empty username, which no
+    // registered user holds, such that they may never chat
+    let username = match user {
+        Some(user) => Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        )
+        .username_for(user.id())?,
+        None => None,
+    }
+    .unwrap_or_default();
+    let handshake = match state
+        .dispatcher()
+        .handshakes(&mut conn, &persistent_conn, &[&username])
+    {
+        Ok(events) => handshake_in(events),
+        Err(DispatchError::ProviderError(e)) => return Err(e),
+        Err(_) => None,
+    };
+
+    Ok(Greeting {
+        handshake,
+        backfill: history::backfill(&mut conn, state.key_prefix(), &persistent_conn)?,
+        whispers: match user {
+            Some(user) => {
+                whispers::deliver_inbox(&mut conn, state.key_prefix(), &persistent_conn, user.id())?
+            }
+            None => Vec::new(),
+        },
+    })
+}
+
+/// Greeting represents the state of the chat sent to a client as it
+/// connects, before any relayed event.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Greeting {
+    /// The most recent messages sent to the chat, oldest first, which
+    /// replace any history held by the client
+    backfill: Vec<ChatMessage>,
+
+    /// The whispers sent to the chatter while they were offline, oldest first
+    whispers: Vec<Whisper>,
+
+    /// (optional) The chat's current modes and pinned message, and whether
+    /// or not the chatter may send messages in them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handshake: Option<Handshake>,
+}
+
+impl Greeting {
+    /// Retreives the most recent messages sent to the chat, oldest first.
+    pub fn backfill(&self) -> &[ChatMessage] {
+        &self.backfill
+    }
+
+    /// Retreives the whispers sent to the chatter while they were offline,
+    /// oldest first.
+    pub fn whispers(&self) -> &[Whisper] {
+        &self.whispers
+    }
+
+    /// Retreives the chat's current modes and pinned message, and whether or
+    /// not the chatter may send messages in them.
+    pub fn handshake(&self) -> Option<&Handshake> {
+        self.handshake.as_ref()
+    }
+}
+
+/// Finds the handshake among the events built for a connecting chatter, if
+/// there is one.
+///
+/// # Arguments
+///
+/// * `events` - The events built for the connecting chatter
+fn handshake_in(events: Vec<Event>) -> Option<Handshake> {
+    events.iter().find_map(|event| match event.event_kind() {
+        EventKind::Handshake(handshake) => Some(handshake.clone()),
+        _ => None,
+    })
+}
+
+/// Subscriber represents the chatter holding an event stream open, or
+/// polling for events. Anonymous subscribers are only sent the events
+/// targeting every chatter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subscriber {
+    /// (optional) The username of the authenticated chatter
+    username: Option<String>,
+
+    /// The roles held by the authenticated chatter
+    roles: Vec<Role>,
+
+    /// The usernames of each of the users ignored by the chatter
+    ignored: HashSet<String>,
+
+    /// (optional) The ID of the session authenticating the chatter, upon
+    /// whose revocation the chatter's event streams are closed
+    session_id: Option<String>,
+
+    /// (optional) The registered connection through which the chatter
+    /// subscribed, which is renewed for as long as their event stream is open
+    connection: Option<Connection>,
+}
+
+impl Subscriber {
+    /// Creates a new subscriber for the authenticated chatter with the given
+    /// username, who ignores nobody.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username of the authenticated chatter
+    pub fn new(username: String) -> Self {
+        Self {
+            username: Some(username),
+            roles: Vec::new(),
+            ignored: HashSet::new(),
+            session_id: None,
+            connection: None,
+        }
+    }
+
+    /// Sets the roles held by the chatter, such that they are sent the events
+    /// targeting any of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - The roles held by the chatter
+    pub fn with_roles(mut self, roles: Vec<Role>) -> Self {
+        self.roles = roles;
+
+        self
+    }
+
+    /// Sets the users ignored by the chatter, whose messages aren't sent to
+    /// them.
+    ///
+    /// # Arguments
+    ///
+    /// * `ignored` - The usernames of each of the users ignored by the chatter
+    pub fn with_ignored(mut self, ignored: Vec<String>) -> Self {
+        self.ignored = ignored.into_iter().collect();
+
+        self
+    }
+
+    /// Sets the session authenticating the chatter, such that their event
+    /// streams are closed once it is revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the session authenticating the chatter
+    pub fn with_session_id(mut self, session_id: String) -> Self {
+        self.session_id = Some(session_id);
+
+        self
+    }
+
+    /// Sets the registered connection through which the chatter subscribed,
+    /// such that its registration is renewed while their event stream is
+    /// open.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - The connection through which the chatter subscribed
+    pub fn with_connection(mut self, connection: Connection) -> Self {
+        self.connection = Some(connection);
+
+        self
+    }
+
+    /// Determines whether or not the given event should be sent to the
+    /// chatter. Events must target every chatter, the chatter themselves, or
+    /// a role that the chatter holds, and mustn't have been sent by a user
+    /// that the chatter ignores. Events hidden from chatters are never sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event, as published on its broadcast channel
+    pub fn admits(&self, event: &Value) -> bool {
+        let concerned = match event.get("concerns") {
+            Some(Value::String(target)) => target == "All",
+            Some(Value::Object(target)) => {
+                match (target.get("User").and_then(Value::as_str), &self.username) {
+                    (Some(recipient), Some(username)) => recipient == username,
+                    _ => target
+                        .get("Role")
+                        .and_then(|role| Role::deserialize(role).ok())
+                        .is_some_and(|role| self.roles.contains(&role)),
+                }
+            }
+            _ => false,
+        };
+
+        concerned && sender(event).is_none_or(|sender| !self.ignored.contains(sender))
+    }
+}
+
+/// EventId identifies an event relayed by a node. Events are numbered in the
+/// order in which the node received them, under an epoch chosen when the node
+/// started, such that IDs assigned by another node, or before a restart, are
+/// never mistaken for the node's own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EventId {
+    /// The epoch of the node that assigned the ID
+    epoch: u32,
+
+    /// The position of the event in the sequence of events relayed by the
+    /// node, starting from 1
+    seq: u64,
+}
+
+impl fmt::Display for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}-{}", self.epoch, self.seq)
+    }
+}
+
+impl FromStr for EventId {
+    type Err = ProviderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+
+        match (parts.next(), parts.next()) {
+            (Some(epoch), Some(seq)) => Ok(Self {
+                epoch: u32::from_str_radix(epoch, 16)
+                    .map_err(|_| ProviderError::InvalidArgument { arg: "event_id" })?,
+                seq: seq
+                    .parse()
+                    .map_err(|_| ProviderError::InvalidArgument { arg: "event_id" })?,
+            }),
+            _ => Err(ProviderError::InvalidArgument { arg: "event_id" }),
+        }
+    }
+}
+
+/// RelayedEvent represents an event relayed by a node, alongside the ID that
+/// the node assigned to it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RelayedEvent {
+    /// The ID assigned to the event
+    id: String,
+
+    /// The event, as published on its broadcast channel
+    event: Value,
+}
+
+/// EventBatch represents the events relayed since a client last polled.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EventBatch {
+    /// The events relayed since the client last polled, oldest first
+    events: Vec<RelayedEvent>,
+
+    /// The ID of the last event relayed, with which the client should poll
+    /// next
+    cursor: String,
+
+    /// Whether or not events may have been relayed that the client will
+    /// never receive, as they are no longer retained, or were relayed by
+    /// another node
+    missed: bool,
+
+    /// (optional) The state of the chat, sent in response to a client's
+    /// first poll
+    #[serde(skip_serializing_if = "Option::is_none")]
+    greeting: Option<Box<Greeting>>,
+}
+
+impl EventBatch {
+    /// Creates a new batch holding no events, from which the client should
+    /// poll again with the same cursor.
+    ///
+    /// # Arguments
+    ///
+    /// * `cursor` - The ID of the last event received by the client
+    pub fn idle(cursor: EventId) -> Self {
+        Self {
+            events: Vec::new(),
+            cursor: cursor.to_string(),
+            missed: false,
+            greeting: None,
+        }
+    }
+
+    /// Attaches the state of the chat to the batch, which is sent in
+    /// response to a client's first poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `greeting` - The state of the chat
+    pub fn with_greeting(mut self, greeting: Greeting) -> Self {
+        self.greeting = Some(Box::new(greeting));
+
+        self
+    }
+
+    /// Retreives the events relayed since the client last polled.
+    pub fn events(&self) -> &[RelayedEvent] {
+        &self.events
+    }
+
+    /// Retreives the ID with which the client should poll next.
+    pub fn cursor(&self) -> &str {
+        &self.cursor
+    }
+
+    /// Determines whether or not the client may have missed any events.
+    pub fn missed(&self) -> bool {
+        self.missed
+    }
+
+    /// Retreives the state of the chat, if the batch answers a client's
+    /// first poll.
+    pub fn greeting(&self) -> Option<&Greeting> {
+        self.greeting.as_deref()
+    }
+}
+
+/// Watch represents the outcome of a client's poll for events.
+pub enum Watch {
+    /// Events were relayed since the client last polled
+    Ready(EventBatch),
+
+    /// No events were relayed since the client last polled. The receiver
+    /// resolves once one is.
+    Pending(oneshot::Receiver<()>),
+}
+
+/// Client represents an open event stream.
+struct Client {
+    /// The chatter holding the stream open
+    subscriber: Subscriber,
+
+    /// The sender feeding the stream
+    sender: Sender<Bytes>,
+}
+
+/// Relayed holds the clients of a relay, and the events that it retains.
+#[derive(Default)]
+struct Relayed {
+    /// Each open event stream
+    clients: Vec<Client>,
+
+    /// The senders notifying each client waiting for the next event
+    waiters: Vec<oneshot::Sender<()>>,
+
+    /// The most recent events relayed, oldest first, alongside their
+    /// positions in the sequence of events relayed
+    backlog: VecDeque<(u64, Value)>,
+
+    /// The position of the last event relayed, or 0 if none have been
+    last_seq: u64,
+}
+
+/// EventRelay fans out the events received by a node's subscription to the
+/// broadcast channels to each of the event streams held open by the node, and
+/// to each of the clients polling it. A backlog of recent events is retained,
+/// from which clients may catch up on the events they missed between
+/// requests.
+pub struct EventRelay {
+    /// The epoch under which the relay assigns event IDs
+    epoch: u32,
+
+    /// The clients of the relay, and the events that it retains
+    relayed: Mutex<Relayed>,
+}
+
+impl Default for EventRelay {
+    fn default() -> Self {
+        Self {
+            epoch: thread_rng().gen(),
+            relayed: Mutex::default(),
+        }
+    }
+}
+
+impl EventRelay {
+    /// Opens a new event stream, which receives every event relayed from now
+    /// on that the given subscriber admits.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscriber` - The chatter opening the stream
+    /// * `last_event_id` - (optional) The ID of the last event received by a
+    /// reconnecting client, after which each retained event is sent again
+    pub fn subscribe(
+        &self,
+        subscriber: Subscriber,
+        last_event_id: Option<EventId>,
+    ) -> Receiver<Bytes> {
+        // Replayed events don't count against the client's buffer
+        let (mut sender, receiver) = mpsc::channel(CLIENT_BUFFER + BACKLOG_LENGTH);
+        let mut relayed = self.relayed();
+
+        // The buffer can hold the delay and the whole backlog, so neither can
+        // be rejected
+        let _ = sender.try_send(Bytes::from(format!("retry: {}\n\n", RECONNECT_DELAY_MS)));
+        if let Some(batch) =
+            last_event_id.and_then(|id| self.events_after(&relayed, &subscriber, id))
+        {
+            for relayed_event in batch.events {
+                let _ = sender.try_send(frame(&relayed_event.id, &relayed_event.event));
+            }
+        }
+
+        relayed.clients.push(Client { subscriber, sender });
+
+        receiver
+    }
+
+    /// Assigns the given event an ID, and sends it to each open event stream
+    /// whose subscriber admits it, and wakes each waiting client. Streams that
+    /// were closed or have fallen behind are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event that should be sent
+    pub fn relay(&self, event: Value) {
+        let mut relayed = self.relayed();
+        relayed.last_seq += 1;
+
+        let id = self.id(relayed.last_seq);
+        let encoded = frame(&id.to_string(), &event);
+
+        Self::send(&mut relayed.clients, &encoded, |subscriber| {
+            subscriber.admits(&event)
+        });
+
+        relayed.backlog.push_back((id.seq, event));
+        if relayed.backlog.len() > BACKLOG_LENGTH {
+            relayed.backlog.pop_front();
+        }
+
+        for waiter in relayed.waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+
+    /// Sends a comment to each open event stream, which clients ignore, so
+    /// that idle streams aren't closed by proxies. Streams and waiting
+    /// clients that have gone away are dropped.
+    pub fn keepalive(&self) {
+        let mut relayed = self.relayed();
+
+        Self::send(
+            &mut relayed.clients,
+            &Bytes::from_static(b": keepalive\n\n"),
+            |_| true,
+        );
+        relayed.waiters.retain(|waiter| !waiter.is_canceled());
+    }
+
+    /// Gets the events admitted by the given subscriber that were relayed
+    /// after the given event, or a receiver that resolves once any event is.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscriber` - The chatter polling for events
+    /// * `since` - The ID of the last event received by the client
+    pub fn watch(&self, subscriber: &Subscriber, since: EventId) -> Watch {
+        let mut relayed = self.relayed();
+
+        match self.events_after(&relayed, subscriber, since) {
+            Some(batch) => Watch::Ready(batch),
+            None => {
+                let (sender, receiver) = oneshot::channel();
+                relayed.waiters.push(sender);
+
+                Watch::Pending(receiver)
+            }
+        }
+    }
+
+    /// Closes each event stream authenticated by the given session, which was
+    /// revoked. Polling clients need not be found, as each poll is
+    /// authenticated anew.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the revoked session
+    pub fn revoke(&self, session_id: &str) {
+        self.relayed()
+            .clients
+            .retain(|client| client.subscriber.session_id.as_deref() != Some(session_id));
+    }
+
+    /// Gets a batch holding no events, from which a client may start polling
+    /// for the events relayed from now on.
+    pub fn latest(&self) -> EventBatch {
+        EventBatch::idle(self.id(self.relayed().last_seq))
+    }
+
+    /// Gets the registered connection of each event stream held open by the
+    /// node. Streams that were closed are dropped.
+    pub fn connections(&self) -> Vec<Connection> {
+        let mut relayed = self.relayed();
+        relayed.clients.retain(|client| !client.sender.is_closed());
+
+        relayed
+            .clients
+            .iter()
+            .filter_map(|client| client.subscriber.connection.clone())
+            .collect()
+    }
+
+    /// Gets the number of event streams held open by the node.
+    pub fn len(&self) -> usize {
+        self.relayed().clients.len()
+    }
+
+    /// Determines whether or not the node holds no event streams open.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the retained events admitted by the given subscriber that were
+    /// relayed after the given event, if any events were relayed since.
+    /// Clients whose last event is no longer retained, or was relayed by
+    /// another node, are sent each retained event, and told that they may
+    /// have missed others.
+    ///
+    /// # Arguments
+    ///
+    /// * `relayed` - The clients of the relay, and the events that it retains
+    /// * `subscriber` - The chatter to whom the events would be sent
+    /// * `since` - The ID of the last event received by the client
+    fn events_after(
+        &self,
+        relayed: &Relayed,
+        subscriber: &Subscriber,
+        since: EventId,
+    ) -> Option<EventBatch> {
+        let oldest = relayed
+            .backlog
+            .front()
+            .map_or(relayed.last_seq + 1, |(seq, _)| *seq);
+        let missed =
+            since.epoch != self.epoch || since.seq > relayed.last_seq || since.seq + 1 < oldest;
+
+        if !missed && since.seq == relayed.last_seq {
+            return None;
+        }
+
+        // Clients are still given the latest cursor if none of the events
+        // relayed since were admitted, so that they aren't considered again
+        let events = relayed
+            .backlog
+            .iter()
+            .filter(|(seq, event)| (missed || *seq > since.seq) && subscriber.admits(event))
+            .map(|(seq, event)| RelayedEvent {
+                id: self.id(*seq).to_string(),
+                event: event.clone(),
+            })
+            .collect::<Vec<RelayedEvent>>();
+
+        Some(EventBatch {
+            events,
+            cursor: self.id(relayed.last_seq).to_string(),
+            missed,
+            greeting: None,
+        })
+    }
+
+    /// Sends the given frame to each of the given event streams whose
+    /// subscriber admits it, dropping those that were closed or have fallen
+    /// behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `clients` - Each open event stream
+    /// * `frame` - The frame that should be sent
+    /// * `admits` - Determines whether or not a subscriber should be sent the
+    /// frame
+    fn send(clients: &mut Vec<Client>, frame: &Bytes, admits: impl Fn(&Subscriber) -> bool) {
+        clients.retain(|client| !client.sender.is_closed());
+
+        *clients = clients
+            .drain(..)
+            .filter_map(|mut client| {
+                if !admits(&client.subscriber) {
+                    return Some(client);
+                }
+
+                client.sender.try_send(frame.clone()).ok().map(|_| client)
+            })
+            .collect();
+    }
+
+    /// Gets the ID of the event at the given position in the sequence of
+    /// events relayed by the node.
+    fn id(&self, seq: u64) -> EventId {
+        EventId {
+            epoch: self.epoch,
+            seq,
+        }
+    }
+
+    /// Locks the clients of the relay, recovering them if another thread
+    /// panicked while holding them.
+    fn relayed(&self) -> MutexGuard<'_, Relayed> {
+        self.relayed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Registration represents the registered connection of an open event
+/// stream, which is deregistered once the stream is dropped, whether the client
+/// went away or the relay closed the stream.
+struct Registration {
+    /// The shared server state
+    state: Data<State>,
+
+    /// The ID of the registered connection
+    id: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // Registrations that can't be removed lapse on their own
+        let _ = self.state.cache_connection().and_then(|mut conn| {
+            Cache::new(&mut conn)
+                .with_prefix(self.state.key_prefix())
+                .deregister_connection(&self.id)
+        });
+    }
+}
+
+/// Encodes the given event as a server-sent event.
+///
+/// # Arguments
+///
+/// * `id` - The ID assigned to the event
+/// * `event` - The event, as published on its broadcast channel
+fn frame(id: &str, event: &Value) -> Bytes {
+    // Events are published as single-line JSON, so they fit in one data
+    // field
+    Bytes::from(format!("id: {}\ndata: {}\n\n", id, event))
+}
+
+/// Encodes the given greeting as a server-sent event. Greetings carry no ID,
+/// so that they don't move a reconnecting client's position in the stream.
+///
+/// # Arguments
+///
+/// * `greeting` - The state of the chat sent to the connecting client
+fn greeting_frame(greeting: &Greeting) -> Result<Bytes, ProviderError> {
+    Ok(Bytes::from(format!(
+        "event: greeting\ndata: {}\n\n",
+        serde_json::to_string(greeting)?
+    )))
+}
+
+/// Gets the username of the user who sent the given event's message, if the
+/// event carries a message that may be ignored.
+///
+/// # Arguments
+///
+/// * `event` - The event, as published on its broadcast channel
+fn sender(event: &Value) -> Option<&str> {
+    let kind = event.get("kind")?;
+
+    kind.get("Broadcast")
+        .or_else(|| kind.get("Highlight"))?
+        .get("sender")?
+        .as_str()
+}
+
+/// Parses the given event, as published on a broadcast channel. Events that
+/// target neither every chatter, a single chatter, nor the holders of a role,
+/// or that are malformed, aren't relayed.
+///
+/// # Arguments
+///
+/// * `payload` - The event as published on a broadcast channel
+pub fn parse(payload: &str) -> Option<Value> {
+    let event = serde_json::from_str::<Value>(payload).ok()?;
+
+    match event.get("concerns") {
+        Some(Value::String(target)) if target == "All" => Some(event),
+        Some(Value::Object(target))
+            if target.contains_key("User") || target.contains_key("Role") =>
+        {
+            Some(event)
+        }
+        _ => None,
+    }
+}
+
+/// Subscribes to the given broadcast channels, relaying each event received
+/// to the node's event streams until the subscription is lost. The event
+/// streams authenticated by each session published on the revocation channel
+/// are closed.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer, which is dedicated to
+/// the subscription until it is lost
+/// * `channels` - The namespaced channels on which broadcast events are
+/// published
+/// * `revocation_channel` - The namespaced channel on which the ID of each
+/// revoked session is published
+/// * `relay` - The relay feeding the node's event streams
+pub fn listen(
+    conn: &mut RedisConnection,
+    channels: &[String],
+    revocation_channel: &str,
+    relay: &EventRelay,
+) -> Result<(), ProviderError> {
+    let mut pubsub = conn.as_pubsub();
+    pubsub.set_read_timeout(Some(KEEPALIVE_INTERVAL))?;
+    for channel in channels {
+        pubsub.subscribe(channel)?;
+    }
+    pubsub.subscribe(revocation_channel)?;
+
+    loop {
+        match pubsub.get_message().and_then(|msg| {
+            msg.get_payload::<String>()
+                .map(|payload| (msg.get_channel_name() == revocation_channel, payload))
+        }) {
+            Ok((true, session_id)) => relay.revoke(&session_id),
+            Ok((false, payload)) => {
+                if let Some(event) = parse(&payload) {
+                    relay.relay(event);
+                }
+            }
+
+            Err(e) if e.is_timeout() => relay.keepalive(),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Keeps the node subscribed to the deployment's broadcast channels for as
+/// long as the server is running, relaying their events to the node's event
+/// streams, and closing the streams of revoked sessions. The subscription holds a pooled connection on a dedicated thread,
+/// and is made again whenever it is lost.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the node's event relay
+pub(crate) fn spawn_relay_task(state: Data<State>) {
+    thread::spawn(move || {
+        let channels = BROADCAST_CHANNELS
+            .iter()
+            .map(|channel| format!("{}{}", state.key_prefix(), channel))
+            .collect::<Vec<String>>();
+        let revocation_channel = format!("{}{}", state.key_prefix(), REVOCATION_CHANNEL);
+
+        loop {
+            let _ = state.cache_connection().and_then(|mut conn| {
+                listen(
+                    &mut conn,
+                    &channels,
+                    &revocation_channel,
+                    state.event_relay(),
+                )
+            });
+
+            thread::sleep(RESUBSCRIBE_DELAY);
+        }
+    });
+}
+
+/// Periodically renews the registration of each event stream held open by
+/// the node for as long as the server is running, such that open streams
+/// remain listed, while those held by a node that stopped lapse.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the node's event relay
+pub(crate) fn spawn_heartbeat_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(HEARTBEAT_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // A failed heartbeat is retried on the next tick, before the
+            // registrations lapse
+            let _ = web::block(move || -> Result<(), ProviderError> {
+                let connections = state.event_relay().connections();
+                let mut conn = state.cache_connection()?;
+                let mut cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+                let now = Utc::now();
+
+                for connection in &connections {
+                    cache.register_connection(connection, now)?;
+                }
+
+                Ok(())
+            })
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::super::super::spec::event::{
+            Broadcast, Event, EventKind, EventTarget, StreamStatus,
+        },
+        *,
+    };
+    use chrono::Utc;
+    use futures::channel::mpsc::TryRecvError;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse() {
+        let event = serde_json::to_string(&Event::new(
+            EventTarget::All,
+            EventKind::StreamStatus(StreamStatus::new(true, Utc::now())),
+        ))
+        .unwrap();
+        assert!(parse(&event).is_some());
+
+        // Events targeting a single chatter are relayed, and filtered per
+        // subscriber
+        let event = serde_json::to_string(&Event::new(
+            EventTarget::User("MrMouton"),
+            EventKind::StreamStatus(StreamStatus::new(true, Utc::now())),
+        ))
+        .unwrap();
+        assert!(parse(&event).is_some());
+
+        // As are events targeting the holders of a role
+        let event = serde_json::to_string(&Event::new(
+            EventTarget::Role(Role::Moderator),
+            EventKind::StreamStatus(StreamStatus::new(true, Utc::now())),
+        ))
+        .unwrap();
+        assert!(parse(&event).is_some());
+
+        // Events hidden from chatters aren't relayed
+        let event = serde_json::to_string(&Event::new(
+            EventTarget::Server,
+            EventKind::StreamStatus(StreamStatus::new(false, Utc::now())),
+        ))
+        .unwrap();
+        assert_eq!(parse(&event), None);
+        assert_eq!(parse("garbage"), None);
+    }
+
+    #[test]
+    fn test_subscriber() {
+        let to_value = |event: Event| serde_json::to_value(&event).unwrap();
+
+        let broadcast = to_value(Event::new(
+            EventTarget::All,
+            EventKind::Broadcast(Broadcast::new("MrMouton", "Hi nathanPepe")),
+        ));
+        let whisper = to_value(Event::new(
+            EventTarget::User("Destiny"),
+            EventKind::Broadcast(Broadcast::new("MrMouton", "Hi nathanPepe")),
+        ));
+        let status = to_value(Event::new(
+            EventTarget::All,
+            EventKind::StreamStatus(StreamStatus::new(true, Utc::now())),
+        ));
+
+        // Anonymous subscribers are only sent events targeting every chatter
+        let anonymous = Subscriber::default();
+        assert!(anonymous.admits(&broadcast));
+        assert!(!anonymous.admits(&whisper));
+
+        let subscriber = Subscriber::new("Destiny".to_owned());
+        assert!(subscriber.admits(&whisper));
+        assert!(!Subscriber::new("RightToBearArmsLOL".to_owned()).admits(&whisper));
+
+        // Messages sent by ignored users aren't sent, but other events are
+        let subscriber = subscriber.with_ignored(vec!["MrMouton".to_owned()]);
+        assert!(!subscriber.admits(&broadcast));
+        assert!(!subscriber.admits(&whisper));
+        assert!(subscriber.admits(&status));
+
+        // Ignored messages are neither streamed nor polled for
+        let relay = EventRelay::default();
+        let since = relay.latest().cursor().parse::<EventId>().unwrap();
+        let mut stream = relay.subscribe(subscriber.clone(), None);
+        assert!(stream.try_recv().unwrap().starts_with(b"retry: "));
+
+        relay.relay(broadcast);
+        assert_eq!(stream.try_recv(), Err(TryRecvError::Empty));
+        match relay.watch(&subscriber, since) {
+            Watch::Ready(batch) => {
+                assert!(batch.events().is_empty());
+                assert_eq!(batch.cursor(), relay.id(1).to_string());
+            }
+            Watch::Pending(_) => panic!("an event was relayed"),
+        }
+
+        relay.relay(status);
+        assert_eq!(
+            stream.try_recv().ok(),
+            Some(frame(
+                &relay.id(2).to_string(),
+                &relay.relayed().backlog[1].1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_role_target() {
+        let event = parse(
+            &serde_json::to_string(&Event::new(
+                EventTarget::Role(Role::Moderator),
+                EventKind::Broadcast(Broadcast::new("MrMouton", "Hi mods")),
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let holder = Subscriber::new("Destiny".to_owned())
+            .with_roles(vec![Role::Subscriber, Role::Moderator]);
+        let non_holder =
+            Subscriber::new("RightToBearArmsLOL".to_owned()).with_roles(vec![Role::Subscriber]);
+        assert!(holder.admits(&event));
+        assert!(!non_holder.admits(&event));
+        assert!(!Subscriber::default().admits(&event));
+
+        // Only the streams of role holders are sent the event
+        let relay = EventRelay::default();
+        let mut holder_stream = relay.subscribe(holder, None);
+        let mut non_holder_stream = relay.subscribe(non_holder, None);
+        assert!(holder_stream.try_recv().unwrap().starts_with(b"retry: "));
+        assert!(non_holder_stream
+            .try_recv()
+            .unwrap()
+            .starts_with(b"retry: "));
+
+        relay.relay(event.clone());
+        assert_eq!(
+            holder_stream.try_recv().ok(),
+            Some(frame(&relay.id(1).to_string(), &event))
+        );
+        assert_eq!(non_holder_stream.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_greeting_frame() -> Result<(), ProviderError> {
+        let greeting = Greeting::default();
+        assert!(greeting.backfill().is_empty());
+        assert!(greeting.whispers().is_empty());
+        assert_eq!(
+            greeting_frame(&greeting)?,
+            Bytes::from_static(b"event: greeting\ndata: {\"backfill\":[],\"whispers\":[]}\n\n")
+        );
+
+        // Greetings are only serialized with the batches answering a
+        // client's first poll
+        let batch = EventRelay::default().latest();
+        assert!(!serde_json::to_string(&batch)?.contains("greeting"));
+        assert!(serde_json::to_string(&batch.with_greeting(greeting))?.contains("greeting"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_greeting_handshake() -> Result<(), ProviderError> {
+        let handshake = Handshake::new(false)
+            .with_subonly(true)
+            .with_slowmode(Some(5));
+
+        // The handshake is picked out of the events built for the chatter
+        assert_eq!(
+            handshake_in(vec![
+                Event::new(
+                    EventTarget::All,
+                    EventKind::Broadcast(Broadcast::new("MrMouton", "abc"))
+                ),
+                Event::new(
+                    EventTarget::User("MrMouton"),
+                    EventKind::Handshake(handshake.clone())
+                ),
+            ]),
+            Some(handshake.clone())
+        );
+        assert_eq!(handshake_in(Vec::new()), None);
+
+        let greeting = Greeting {
+            handshake: Some(handshake.clone()),
+            ..Greeting::default()
+        };
+        assert_eq!(greeting.handshake(), Some(&handshake));
+        assert_eq!(
+            serde_json::to_value(&greeting)?["handshake"],
+            serde_json::to_value(&handshake)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_id() -> Result<(), ProviderError> {
+        let id = EventId {
+            epoch: 0xdead_beef,
+            seq: 42,
+        };
+        assert_eq!(id.to_string(), "deadbeef-42");
+        assert_eq!(id.to_string().parse::<EventId>()?, id);
+
+        assert!("deadbeef".parse::<EventId>().is_err());
+        assert!("zzz-42".parse::<EventId>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay() {
+        let relay = EventRelay::default();
+        let mut first = relay.subscribe(Subscriber::default(), None);
+        let second = relay.subscribe(Subscriber::default(), None);
+        assert_eq!(relay.len(), 2);
+
+        // Closed streams are dropped once an event is relayed
+        drop(second);
+        relay.relay(json!({ "concerns": "All" }));
+        assert_eq!(relay.len(), 1);
+
+        let id = relay.id(1);
+        assert!(first.try_recv().unwrap().starts_with(b"retry: "));
+        assert_eq!(
+            first.try_recv().ok(),
+            Some(Bytes::from(format!(
+                "id: {}\ndata: {{\"concerns\":\"All\"}}\n\n",
+                id
+            )))
+        );
+
+        // Reconnecting clients are sent the events that they missed
+        relay.relay(json!({ "concerns": "All", "kind": true }));
+        let mut third = relay.subscribe(Subscriber::default(), Some(id));
+        assert!(third.try_recv().unwrap().starts_with(b"retry: "));
+        assert_eq!(
+            third.try_recv().ok(),
+            Some(Bytes::from(format!(
+                "id: {}\ndata: {{\"concerns\":\"All\",\"kind\":true}}\n\n",
+                relay.id(2)
+            )))
+        );
+
+        // Streams authenticated by a revoked session are closed
+        let mut revoked = relay.subscribe(
+            Subscriber::new("MrMouton".to_owned()).with_session_id("abc".to_owned()),
+            None,
+        );
+        relay.revoke("abc");
+        assert_eq!(relay.len(), 2);
+        assert!(revoked.try_recv().unwrap().starts_with(b"retry: "));
+        assert_eq!(revoked.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn test_connections() {
+        let relay = EventRelay::default();
+        let connection = Connection::new("a", "node-1", Some(69420), "127.0.0.1", Utc::now(), 1);
+
+        // Only the streams of registered connections are renewed
+        let open = relay.subscribe(
+            Subscriber::new("MrMouton".to_owned()).with_connection(connection.clone()),
+            None,
+        );
+        let _anonymous = relay.subscribe(Subscriber::default(), None);
+        assert_eq!(relay.connections(), vec![connection]);
+
+        // Closed streams are no longer renewed
+        drop(open);
+        assert!(relay.connections().is_empty());
+        assert_eq!(relay.len(), 1);
+    }
+
+    #[test]
+    fn test_watch() {
+        let relay = EventRelay::default();
+        let start = relay.latest();
+        assert!(start.events().is_empty());
+
+        let since = start.cursor().parse::<EventId>().unwrap();
+        let mut waiter = match relay.watch(&Subscriber::default(), since) {
+            Watch::Pending(waiter) => waiter,
+            Watch::Ready(_) => panic!("no events were relayed"),
+        };
+
+        // Waiting clients are woken once an event is relayed
+        relay.relay(json!({ "concerns": "All" }));
+        assert_eq!(waiter.try_recv(), Ok(Some(())));
+
+        match relay.watch(&Subscriber::default(), since) {
+            Watch::Ready(batch) => {
+                assert_eq!(batch.events().len(), 1);
+                assert_eq!(batch.cursor(), relay.id(1).to_string());
+                assert!(!batch.missed());
+            }
+            Watch::Pending(_) => panic!("an event was relayed"),
+        }
+
+        // Clients polling another node may have missed events
+        let foreign = EventId {
+            epoch: relay.epoch.wrapping_add(1),
+            seq: 1,
+        };
+        match relay.watch(&Subscriber::default(), foreign) {
+            Watch::Ready(batch) => assert!(batch.missed()),
+            Watch::Pending(_) => panic!("the client's ID is foreign"),
+        }
+
+        // Clients that fell behind the backlog may have missed events
+        for _ in 0..BACKLOG_LENGTH {
+            relay.relay(json!({ "concerns": "All" }));
+        }
+        match relay.watch(&Subscriber::default(), since) {
+            Watch::Ready(batch) => {
+                assert_eq!(batch.events().len(), BACKLOG_LENGTH);
+                assert!(batch.missed());
+            }
+            Watch::Pending(_) => panic!("events were relayed"),
+        }
+    }
+}
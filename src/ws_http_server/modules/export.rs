@@ -0,0 +1,370 @@
+use actix_web::{
+    rt,
+    web::{self, Data, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use diesel::{BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::Connection as RedisConnection;
+use serde::Serialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{PersistentConnection, Unsigned},
+            ban::Ban,
+            donation::Donation,
+            history::ChatMessage,
+            last_seen::LastSeen,
+            mute::Mute,
+            schema::{
+                chat_history, discord_connected, donations, google_connected, reddit_connected,
+                twitch_connected, twitter_connected, whispers,
+            },
+            settings::Settings,
+            user::{Role, User},
+            whisper::Whisper,
+        },
+        auth::Principal,
+        server::State,
+    },
+    bans::{self, BanQuery},
+    flairs::Provider as FlairsProvider,
+    ignores,
+    last_seen::Provider as LastSeenProvider,
+    mutes,
+    name_resolver::Provider as NameResolver,
+    roles::Provider as RolesProvider,
+    sessions::{Provider as SessionsProvider, Session},
+    settings::Provider as SettingsProvider,
+    users::{self, Provider as UsersProvider},
+    Cache, Hybrid, Layers, Persistent, ProviderError,
+};
+
+/// The number of seconds that a finished export remains available for
+/// download.
+const EXPORT_TTL: u64 = 24 * 60 * 60;
+
+/// The value stored in place of an export that is still being assembled.
+const PENDING_EXPORT: &str = "pending";
+
+/// The value stored in place of an export that could not be assembled.
+const FAILED_EXPORT: &str = "failed";
+
+/// The number of characters in an export download token.
+const TOKEN_LENGTH: usize = 32;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the export module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/exports")
+        .service(request_export)
+        .service(download_export)
+}
+
+/// UserExport represents each piece of data stored about a user.
+#[derive(Serialize)]
+pub struct UserExport {
+    /// The user's profile
+    user: Option<User>,
+
+    /// The usernames previously held by the user
+    previous_usernames: Vec<String>,
+
+    /// The roles held by the user
+    roles: Vec<Role>,
+
+    /// The user's chat preferences, if they have saved any
+    settings: Option<Settings>,
+
+    /// The user's most recent activity
+    last_seen: Option<LastSeen>,
+
+    /// The names of each oauth provider linked to the user's account
+    connections: Vec<&'static str>,
+
+    /// The user's ban, if they have been banned
+    ban: Option<Ban>,
+
+    /// The user's mute, if they have been muted
+    mute: Option<Mute>,
+
+    /// Each of the messages sent to the chat by the user, oldest first
+    chat_history: Vec<ChatMessage>,
+
+    /// Each of the whispers sent or received by the user, oldest first
+    whispers: Vec<Whisper>,
+
+    /// The usernames of each of the users ignored by the user
+    ignores: Vec<String>,
+
+    /// The user's active sessions
+    sessions: Vec<Session>,
+
+    /// Each of the donations made by the user, oldest first
+    donations: Vec<Donation>,
+
+    /// The names of each of the flairs granted to the user
+    flairs: Vec<String>,
+}
+
+/// ExportToken represents a response to a request for a data export.
+#[derive(Serialize)]
+pub struct ExportToken {
+    /// The token with which the export may be downloaded once it is ready
+    token: String,
+}
+
+/// Begins assembling an export of the data stored about the user with the
+/// given ID, and responds with a token that may be used to download the
+/// export once it is finished. Users may only export their own data, unless
+/// they are an administrator.
+#[post("/users/{user_id}")]
+pub async fn request_export(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, HttpError> {
+    let user_id = *user_id;
+    users::authorize_edit(&principal, user_id)?;
+
+    let mut conn = state.cache_connection()?;
+
+    let token = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .collect::<String>();
+    store_export(
+        &mut Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        &token,
+        PENDING_EXPORT,
+    )?;
+
+    // Exports touch every backend, so they are assembled off of the request
+    // path
+    let job_token = token.clone();
+    rt::spawn(async move {
+        let _ = web::block(move || -> Result<(), ProviderError> {
+            let mut conn = state.cache_connection()?;
+            let layers = state.moderation_layers();
+
+            let export = state
+                .persistent_connection()
+                .and_then(|persistent_conn| {
+                    export_user(
+                        &mut conn,
+                        state.key_prefix(),
+                        &persistent_conn,
+                        layers,
+                        user_id,
+                    )
+                })
+                .and_then(|export| serde_json::to_string(&export).map_err(|e| e.into()));
+
+            let mut cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+            match export {
+                Ok(export) => store_export(&mut cache, &job_token, &export),
+                Err(e) => store_export(&mut cache, &job_token, FAILED_EXPORT).and(Err(e)),
+            }
+        })
+        .await;
+    });
+
+    Ok(HttpResponse::Accepted().json(ExportToken { token }))
+}
+
+/// Downloads the export corresponding to the given token. Responds with 202
+/// Accepted if the export is still being assembled.
+#[get("/{token}")]
+pub async fn download_export(
+    state: Data<State>,
+    token: Path<String>,
+) -> Result<HttpResponse, ProviderError> {
+    let export = state.retry_policy().run(|| {
+        let mut conn = state.cache_connection()?;
+        let cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+
+        redis::cmd("GET")
+            .arg(cache.key(format_args!("export::{}", token)))
+            .query::<Option<String>>(cache.connection)
+            .map_err(|e| e.into())
+    })?;
+
+    Ok(match export.as_deref() {
+        None => HttpResponse::NotFound().finish(),
+        Some(PENDING_EXPORT) => HttpResponse::Accepted().finish(),
+        Some(FAILED_EXPORT) => HttpResponse::InternalServerError().finish(),
+        Some(export) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(export.to_owned()),
+    })
+}
+
+/// Stores the given export contents under the given download token.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer in which the export should be stored
+/// * `token` - The token with which the export may be downloaded
+/// * `contents` - The contents of the export
+fn store_export(cache: &mut Cache, token: &str, contents: &str) -> Result<(), ProviderError> {
+    redis::cmd("SET")
+        .arg(cache.key(format_args!("export::{}", token)))
+        .arg(contents)
+        .arg("EX")
+        .arg(EXPORT_TTL)
+        .query(cache.connection)
+        .map_err(|e| e.into())
+}
+
+/// Gathers each piece of data stored about the user with the given ID.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `layers` - The layers backing the bans and mutes providers
+/// * `user_id` - The ID of the user whose data should be exported
+pub fn export_user(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    layers: Layers,
+    user_id: u64,
+) -> Result<UserExport, ProviderError> {
+    let ban = bans::provider(
+        layers,
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .get_ban(&BanQuery::Id(user_id))?;
+    let mute = mutes::provider(
+        layers,
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .get_mute(user_id)?;
+    let ignores = ignores::ignored_usernames(conn, key_prefix, persistent_conn, user_id)?;
+    let sessions = Cache::new(conn)
+        .with_prefix(key_prefix)
+        .user_sessions(user_id)?;
+
+    let chat_history = chat_history::table
+        .filter(chat_history::dsl::sender_id.eq(Unsigned(user_id)))
+        .order(chat_history::dsl::id.asc())
+        .load::<ChatMessage>(persistent_conn)?;
+    let whispers = whispers::table
+        .filter(
+            whispers::dsl::sender_id
+                .eq(Unsigned(user_id))
+                .or(whispers::dsl::recipient_id.eq(Unsigned(user_id))),
+        )
+        .order(whispers::dsl::id.asc())
+        .load::<Whisper>(persistent_conn)?;
+    let donations = donations::table
+        .filter(donations::dsl::donor_id.eq(Unsigned(user_id)))
+        .order(donations::dsl::id.asc())
+        .load::<Donation>(persistent_conn)?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    );
+
+    // Determines whether or not the user has linked their account through
+    // the given connection table
+    macro_rules! linked {
+        ($table:ident) => {
+            $table::table
+                .find(Unsigned(user_id))
+                .select($table::dsl::user_id)
+                .first::<u64>(persistent_conn)
+                .optional()?
+                .is_some()
+        };
+    }
+
+    let connections = vec![
+        ("reddit", linked!(reddit_connected)),
+        ("twitch", linked!(twitch_connected)),
+        ("twitter", linked!(twitter_connected)),
+        ("google", linked!(google_connected)),
+        ("discord", linked!(discord_connected)),
+    ]
+    .into_iter()
+    .filter(|(_, linked)| *linked)
+    .map(|(provider, _)| provider)
+    .collect();
+
+    Ok(UserExport {
+        user: hybrid.get_user(user_id)?,
+        previous_usernames: hybrid.previous_usernames(user_id)?,
+        roles: hybrid.roles_for_user(user_id)?,
+        settings: hybrid.get_settings(user_id)?,
+        last_seen: hybrid.get_last_seen(user_id)?,
+        connections,
+        ban,
+        mute,
+        chat_history,
+        whispers,
+        ignores,
+        sessions,
+        donations,
+        flairs: hybrid.granted_flairs(user_id)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::migrations, *};
+    use diesel::{connection::SimpleConnection, Connection};
+
+    use std::{error::Error, io};
+
+    #[test]
+    fn test_export_user() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn = PersistentConnection::establish(":memory:")?;
+        migrations::run_pending(&persistent_conn, &mut io::sink())?;
+
+        // Nothing is cached under a fresh prefix, so everything is read from
+        // the database
+        let key_prefix = format!("test_export_{}::", thread_rng().gen::<u32>());
+
+        persistent_conn.batch_execute(
+            "INSERT INTO users (id, username, verified) VALUES (1, 'MrMouton', 1), (2, 'Destiny', 1);
+             INSERT INTO chat_history (sender_id, sender, contents, sent_at)
+                 VALUES (1, 'MrMouton', 'Hi nathanPepe', '2020-05-09 11:03:42');
+             INSERT INTO whispers (sender_id, sender, recipient_id, contents, sent_at)
+                 VALUES (2, 'Destiny', 1, 'Hi', '2020-05-10 09:30:27');
+             INSERT INTO ignores (user_id, ignored_id) VALUES (1, 2);
+             INSERT INTO donations (donor_id, donor, amount, currency) VALUES (1, 'MrMouton', 500, 'USD');
+             INSERT INTO flairs (name, label) VALUES ('gnome', 'Gnome');
+             INSERT INTO flair_grants (user_id, flair) VALUES (1, 'gnome');",
+        )?;
+        let token = Cache::new(&mut conn)
+            .with_prefix(&key_prefix)
+            .issue_session(1, None, None)?;
+
+        let export = export_user(&mut conn, &key_prefix, &persistent_conn, Layers::Hybrid, 1)?;
+        assert_eq!(export.chat_history.len(), 1);
+        assert_eq!(export.chat_history[0].contents(), "Hi nathanPepe");
+        assert_eq!(export.whispers.len(), 1);
+        assert_eq!(export.whispers[0].sender(), "Destiny");
+        assert_eq!(export.ignores, vec!["Destiny".to_owned()]);
+        assert_eq!(
+            export
+                .sessions
+                .iter()
+                .map(Session::id)
+                .collect::<Vec<&str>>(),
+            vec![token.as_str()]
+        );
+        assert_eq!(export.donations.len(), 1);
+        assert_eq!(export.donations[0].amount(), 500);
+        assert_eq!(export.flairs, vec!["gnome".to_owned()]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,731 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpRequest, HttpResponse, Scope,
+};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            flair::{Flair, FlairGrant},
+            schema::{flair_grants, flairs},
+            user::Role,
+        },
+        auth::{capability::CanManageFlairs, RequireCapability},
+        etag,
+        server::State,
+    },
+    roles::Provider as RolesProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::slice;
+
+/// The redis hash holding each of the flairs, keyed by their names.
+const FLAIRS_KEY: &str = "flairs";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the flairs module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/flairs")
+        .service(list_flairs)
+        .service(put_flair)
+        .service(delete_flair)
+        .service(user_flairs)
+        .service(grant_flair)
+        .service(revoke_flair)
+}
+
+/// FlairRequest represents a request to create or replace a flair.
+#[derive(Deserialize)]
+pub struct FlairRequest {
+    /// The human-readable name of the flair
+    label: String,
+
+    /// (optional) A reference to the image rendered as the badge
+    image: Option<String>,
+
+    /// (optional) The role whose holders should be given the flair
+    role: Option<Role>,
+
+    /// (optional) The position of the flair among a chatter's flairs
+    #[serde(default)]
+    priority: u16,
+}
+
+/// Gets each of the flairs that may be displayed in the chat, in the order
+/// that they are displayed. Responds with 304 Not Modified if the client's
+/// copy of the flair list is current.
+#[get("")]
+pub async fn list_flairs(
+    state: Data<State>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let flairs = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .flairs()?;
+
+    etag::respond(&req, &flairs)
+}
+
+/// Creates or replaces the flair with the given name. Only administrators
+/// may manage flairs.
+#[put("/{name}")]
+pub async fn put_flair(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageFlairs>,
+    name: Path<String>,
+    body: Json<FlairRequest>,
+) -> Result<Json<Flair>, HttpError> {
+    let body = body.into_inner();
+
+    let flair = Flair::new(&name, &body.label)
+        .with_image(body.image)
+        .with_role(body.role)
+        .with_priority(body.priority);
+    if !flair.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "flair" }.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .set_flair(&flair)?;
+
+    Ok(Json(flair))
+}
+
+/// Deletes the flair with the given name, revoking it from each user granted
+/// it. Only administrators may manage flairs.
+#[delete("/{name}")]
+pub async fn delete_flair(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageFlairs>,
+    name: Path<String>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .remove_flair(&name)
+    .map(|removed| removed.map(|_| HttpResponse::NoContent().finish()))
+}
+
+/// Gets each of the flairs held by the specified user, whether through their
+/// roles or granted individually, in the order that they are displayed.
+#[get("/users/{user_id}")]
+pub async fn user_flairs(
+    state: Data<State>,
+    user_id: Path<u64>,
+) -> Result<Json<Vec<Flair>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    let roles = hybrid.roles_for_user(*user_id)?;
+
+    flairs_for(&mut hybrid, *user_id, &roles).map(Json)
+}
+
+/// Grants the flair with the given name to the specified user. Only
+/// administrators may manage flairs.
+#[put("/users/{user_id}/{name}")]
+pub async fn grant_flair(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageFlairs>,
+    path: Path<(u64, String)>,
+) -> Result<Option<HttpResponse>, ProviderError> {
+    let (user_id, name) = path.into_inner();
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    if hybrid.get_flair(&name)?.is_none() {
+        return Ok(None);
+    }
+
+    hybrid
+        .grant_flair(user_id, &name)
+        .map(|_| Some(HttpResponse::NoContent().finish()))
+}
+
+/// Revokes the flair with the given name from the specified user. Flairs
+/// held through the user's roles can't be revoked individually. Only
+/// administrators may manage flairs.
+#[delete("/users/{user_id}/{name}")]
+pub async fn revoke_flair(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageFlairs>,
+    path: Path<(u64, String)>,
+) -> Result<HttpResponse, ProviderError> {
+    let (user_id, name) = path.into_inner();
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .revoke_flair(user_id, &name)
+    .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Resolves each of the flairs held by the given user, in the order that
+/// they are displayed.
+///
+/// # Arguments
+///
+/// * `provider` - The provider from which flairs should be obtained
+/// * `user_id` - The ID of the user whose flairs should be resolved
+/// * `roles` - The roles held by the user
+pub fn flairs_for<P: Provider>(
+    provider: &mut P,
+    user_id: u64,
+    roles: &[Role],
+) -> Result<Vec<Flair>, ProviderError> {
+    let granted = provider.granted_flairs(user_id)?;
+
+    Ok(provider
+        .flairs()?
+        .into_iter()
+        .filter(|flair| {
+            flair.role().is_some_and(|role| roles.contains(&role))
+                || granted.iter().any(|name| name == flair.name())
+        })
+        .collect())
+}
+
+/// Sorts the given flairs into the order that they are displayed.
+///
+/// # Arguments
+///
+/// * `flairs` - The flairs that should be sorted
+fn sort_flairs(flairs: &mut [Flair]) {
+    flairs.sort_by(|a, b| {
+        a.priority()
+            .cmp(&b.priority())
+            .then_with(|| a.name().cmp(b.name()))
+    });
+}
+
+/// Gets the redis key of the value holding the flairs granted to the given
+/// user.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose grants should be located
+pub(crate) fn grants_key(user_id: u64) -> String {
+    format!("flair_grants::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the flairs service, which
+/// stores the badges displayed alongside chatters' usernames and the users
+/// granted them individually.
+pub trait Provider {
+    /// Gets each of the flairs, in the order that they are displayed.
+    fn flairs(&mut self) -> Result<Vec<Flair>, ProviderError>;
+
+    /// Retreives the flair with the given name, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be obtained
+    fn get_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError>;
+
+    /// Stores the given flair, replacing any existing flair with the same
+    /// name.
+    ///
+    /// # Arguments
+    ///
+    /// * `flair` - The flair that should be stored
+    fn set_flair(&mut self, flair: &Flair) -> Result<(), ProviderError>;
+
+    /// Removes the flair with the given name, returning the removed flair if
+    /// it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be removed
+    fn remove_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError>;
+
+    /// Gets the names of each of the flairs granted to the given user
+    /// individually.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be obtained
+    fn granted_flairs(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError>;
+
+    /// Grants the flair with the given name to the given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be granted the flair
+    /// * `name` - The name of the flair that should be granted
+    fn grant_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError>;
+
+    /// Revokes the flair with the given name from the given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose flair should be revoked
+    /// * `name` - The name of the flair that should be revoked
+    fn revoke_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Stores each of the given flairs in the redis caching layer at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `flairs` - The flairs that should be stored
+    fn set_flairs(&mut self, flairs: &[Flair]) -> Result<(), ProviderError> {
+        // HSET requires at least one field
+        if flairs.is_empty() {
+            return Ok(());
+        }
+
+        let mut fields = Vec::with_capacity(flairs.len());
+        for flair in flairs {
+            fields.push((flair.name(), serde_json::to_string(flair)?));
+        }
+
+        redis::cmd("HSET")
+            .arg(self.key(FLAIRS_KEY))
+            .arg(fields)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the names of the flairs granted to the given user from the
+    /// redis caching layer, if they have been cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be obtained
+    fn cached_grants(&mut self, user_id: u64) -> Result<Option<Vec<String>>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(grants_key(user_id)))
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the names of the flairs granted to the given user in the redis
+    /// caching layer. Users granted no flairs are cached as well, so that
+    /// they needn't be looked up for each of their messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be stored
+    /// * `granted` - The names of the flairs granted to the user
+    fn cache_grants(&mut self, user_id: u64, granted: &[String]) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(grants_key(user_id)))
+            .arg(serde_json::to_string(granted)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets each of the flairs held by the redis caching layer, in the order
+    /// that they are displayed.
+    fn flairs(&mut self) -> Result<Vec<Flair>, ProviderError> {
+        let mut flairs = redis::cmd("HVALS")
+            .arg(self.key(FLAIRS_KEY))
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|flair| serde_json::from_str(flair))
+            .collect::<Result<Vec<Flair>, _>>()?;
+        sort_flairs(&mut flairs);
+
+        Ok(flairs)
+    }
+
+    /// Retreives the flair with the given name from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be obtained
+    fn get_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        redis::cmd("HGET")
+            .arg(self.key(FLAIRS_KEY))
+            .arg(name)
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given flair in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `flair` - The flair that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, flairs::Provider}, spec::flair::Flair};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut flairs = Cache::new(&mut conn);
+    /// let flair = Flair::new("nsfw-streamer", "Certified Streamer");
+    ///
+    /// flairs.set_flair(&flair)?;
+    /// assert_eq!(flairs.get_flair("nsfw-streamer")?, Some(flair));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_flair(&mut self, flair: &Flair) -> Result<(), ProviderError> {
+        self.set_flairs(slice::from_ref(flair))
+    }
+
+    /// Removes the flair with the given name from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be removed
+    fn remove_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        let flair = self.get_flair(name)?;
+
+        redis::cmd("HDEL")
+            .arg(self.key(FLAIRS_KEY))
+            .arg(name)
+            .query::<()>(self.connection)?;
+
+        Ok(flair)
+    }
+
+    /// Gets the names of each of the flairs granted to the given user from
+    /// the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be obtained
+    fn granted_flairs(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        self.cached_grants(user_id)
+            .map(|granted| granted.unwrap_or_default())
+    }
+
+    /// Grants the flair with the given name to the given user in the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be granted the flair
+    /// * `name` - The name of the flair that should be granted
+    fn grant_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        let mut granted = self.granted_flairs(user_id)?;
+
+        if !granted.iter().any(|flair| flair == name) {
+            granted.push(name.to_owned());
+        }
+
+        self.cache_grants(user_id, &granted)
+    }
+
+    /// Revokes the flair with the given name from the given user in the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose flair should be revoked
+    /// * `name` - The name of the flair that should be revoked
+    fn revoke_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        let mut granted = self.granted_flairs(user_id)?;
+        granted.retain(|flair| flair != name);
+
+        self.cache_grants(user_id, &granted)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets each of the flairs stored in the MySQL database, in the order
+    /// that they are displayed.
+    fn flairs(&mut self) -> Result<Vec<Flair>, ProviderError> {
+        flairs::table
+            .order((flairs::dsl::priority.asc(), flairs::dsl::name.asc()))
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the flair with the given name from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be obtained
+    fn get_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        flairs::table
+            .find(name)
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Stores the given flair in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `flair` - The flair that should be stored
+    fn set_flair(&mut self, flair: &Flair) -> Result<(), ProviderError> {
+        diesel::replace_into(flairs::table)
+            .values(flair)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the flair with the given name from the MySQL database,
+    /// alongside each of its grants.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be removed
+    fn remove_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        self.connection.transaction(|| {
+            let flair = self.get_flair(name)?;
+
+            diesel::delete(flair_grants::table.filter(flair_grants::dsl::flair.eq(name)))
+                .execute(self.connection)?;
+            diesel::delete(flairs::table.find(name)).execute(self.connection)?;
+
+            Ok(flair)
+        })
+    }
+
+    /// Gets the names of each of the flairs granted to the given user from
+    /// the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be obtained
+    fn granted_flairs(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        flair_grants::table
+            .filter(flair_grants::dsl::user_id.eq(Unsigned(user_id)))
+            .select(flair_grants::dsl::flair)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Grants the flair with the given name to the given user in the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be granted the flair
+    /// * `name` - The name of the flair that should be granted
+    fn grant_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        diesel::replace_into(flair_grants::table)
+            .values(FlairGrant::new(user_id, name))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Revokes the flair with the given name from the given user in the
+    /// MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose flair should be revoked
+    /// * `name` - The name of the flair that should be revoked
+    fn revoke_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        diesel::delete(
+            flair_grants::table.filter(
+                flair_grants::dsl::user_id
+                    .eq(Unsigned(user_id))
+                    .and(flair_grants::dsl::flair.eq(name)),
+            ),
+        )
+        .execute(self.connection)
+        .map(|_| ())
+        .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets each of the flairs, populating the cache from the persistent
+    /// layer if it holds none.
+    fn flairs(&mut self) -> Result<Vec<Flair>, ProviderError> {
+        match self.cache.flairs() {
+            Ok(flairs) if !flairs.is_empty() => Ok(flairs),
+            _ => self
+                .persistent
+                .flairs()
+                .and_then(|flairs| self.cache.set_flairs(&flairs).map(|_| flairs)),
+        }
+    }
+
+    /// Retreives the flair with the given name, populating the cache from
+    /// the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be obtained
+    fn get_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        match self.cache.get_flair(name) {
+            Ok(Some(flair)) => Ok(Some(flair)),
+            _ => self.persistent.get_flair(name).and_then(|flair| {
+                flair.map_or(Ok(None), |flair| {
+                    self.cache.set_flair(&flair).map(|_| Some(flair))
+                })
+            }),
+        }
+    }
+
+    /// Stores the given flair in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `flair` - The flair that should be stored
+    fn set_flair(&mut self, flair: &Flair) -> Result<(), ProviderError> {
+        self.persistent
+            .set_flair(flair)
+            .and_then(|_| self.cache.set_flair(flair))
+    }
+
+    /// Removes the flair with the given name from the active provider.
+    /// Cached grants of the flair are left in place, as they no longer
+    /// resolve to a flair.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flair that should be removed
+    fn remove_flair(&mut self, name: &str) -> Result<Option<Flair>, ProviderError> {
+        let flair = self.persistent.remove_flair(name)?;
+        self.cache.remove_flair(name)?;
+
+        Ok(flair)
+    }
+
+    /// Gets the names of each of the flairs granted to the given user,
+    /// populating the cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose grants should be obtained
+    fn granted_flairs(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        match self.cache.cached_grants(user_id) {
+            Ok(Some(granted)) => Ok(granted),
+            _ => self
+                .persistent
+                .granted_flairs(user_id)
+                .and_then(|granted| self.cache.cache_grants(user_id, &granted).map(|_| granted)),
+        }
+    }
+
+    /// Grants the flair with the given name to the given user, refreshing
+    /// the cached grants from the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be granted the flair
+    /// * `name` - The name of the flair that should be granted
+    fn grant_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        self.persistent.grant_flair(user_id, name)?;
+
+        let granted = self.persistent.granted_flairs(user_id)?;
+        self.cache.cache_grants(user_id, &granted)
+    }
+
+    /// Revokes the flair with the given name from the given user, refreshing
+    /// the cached grants from the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose flair should be revoked
+    /// * `name` - The name of the flair that should be revoked
+    fn revoke_flair(&mut self, user_id: u64, name: &str) -> Result<(), ProviderError> {
+        self.persistent.revoke_flair(user_id, name)?;
+
+        let granted = self.persistent.granted_flairs(user_id)?;
+        self.cache.cache_grants(user_id, &granted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let moderator = Flair::new("test-moderator", "Moderator").with_role(Some(Role::Moderator));
+        let contributor = Flair::new("test-contributor", "Contributor").with_priority(1);
+
+        let mut flairs = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        flairs.set_flair(&moderator)?;
+        flairs.set_flair(&contributor)?;
+        flairs.grant_flair(42069, "test-contributor")?;
+
+        let held = flairs_for(&mut flairs, 42069, &[Role::Moderator])?;
+        assert!(held.contains(&moderator) && held.contains(&contributor));
+        assert!(!flairs_for(&mut flairs, 42069, &[])?.contains(&moderator));
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(grants_key(42069))
+            .query::<()>(&mut conn)?;
+        let mut flairs = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert!(flairs_for(&mut flairs, 42069, &[])?.contains(&contributor));
+
+        flairs.revoke_flair(42069, "test-contributor")?;
+        assert!(!flairs_for(&mut flairs, 42069, &[])?.contains(&contributor));
+
+        assert_eq!(flairs.remove_flair("test-moderator")?, Some(moderator));
+        flairs.remove_flair("test-contributor")?;
+
+        Ok(())
+    }
+}
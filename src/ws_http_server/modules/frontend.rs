@@ -0,0 +1,259 @@
+use actix_web::{
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        Method,
+    },
+    web::{self, Data},
+    Error as HttpError, HttpRequest, HttpResponse,
+};
+
+use super::super::{etag, server::State};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The page served for the frontend's root, and for any path that doesn't
+/// name an asset, such that the frontend may route the request itself.
+const INDEX_PAGE: &str = "index.html";
+
+/// The Cache-Control header attached to assets whose names carry a hash of
+/// their contents, which never change.
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// The Cache-Control header attached to every other asset, which clients
+/// must revalidate before each use.
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+
+/// The minimum length of the part of an asset's name holding a hash of its
+/// contents.
+const MIN_HASH_LENGTH: usize = 8;
+
+/// Each of the extensions of the assets served, alongside their media types.
+/// Assets with other extensions are served as opaque bytes.
+const ASSET_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("js", "application/javascript"),
+    ("mjs", "application/javascript"),
+    ("css", "text/css"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("wasm", "application/wasm"),
+];
+
+/// Serves the chat frontend from the configured directory, for requests that
+/// no other route matched. Paths that don't name an asset, and don't look
+/// like they name a file, are answered with the frontend's index page, such
+/// that client-side routes survive a reload. Responds with 404 Not Found if
+/// no frontend directory is configured.
+pub async fn serve_asset(state: Data<State>, req: HttpRequest) -> Result<HttpResponse, HttpError> {
+    let root = match state.frontend_dir() {
+        Some(root) if req.method() == Method::GET || req.method() == Method::HEAD => {
+            root.to_owned()
+        }
+        _ => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let path = match asset_path(req.path()) {
+        Some(path) => path,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let (name, contents) = match read(root.join(&path)).await? {
+        Some(contents) => (path, contents),
+
+        // Paths naming a missing file are genuinely missing, rather than
+        // routed by the frontend
+        None if names_file(req.path()) => return Ok(HttpResponse::NotFound().finish()),
+        None => match read(root.join(INDEX_PAGE)).await? {
+            Some(contents) => (PathBuf::from(INDEX_PAGE), contents),
+            None => return Ok(HttpResponse::NotFound().finish()),
+        },
+    };
+
+    let cache_control = if is_hashed(&name) {
+        IMMUTABLE_CACHE_CONTROL
+    } else {
+        REVALIDATE_CACHE_CONTROL
+    };
+
+    // Unchanged assets are revalidated without being sent again
+    let tag = etag::entity_tag(&contents);
+    if req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|header| header.to_str().ok())
+        .is_some_and(|header| etag::matches(header, &tag))
+    {
+        return Ok(HttpResponse::NotModified()
+            .header(ETAG, tag)
+            .header(CACHE_CONTROL, cache_control)
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .header(CONTENT_TYPE, content_type(&name))
+        .header(ETAG, tag)
+        .header(CACHE_CONTROL, cache_control)
+        .body(contents))
+}
+
+/// Converts the given request path into the path of an asset, relative to
+/// the frontend directory. Paths that could escape the directory, or that
+/// name hidden files, are rejected.
+///
+/// # Arguments
+///
+/// * `request_path` - The path of the request
+pub fn asset_path(request_path: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for segment in request_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+    {
+        if segment.starts_with('.') || segment.contains('\\') || segment.contains(':') {
+            return None;
+        }
+
+        path.push(segment);
+    }
+
+    // Directories are served their index page
+    if request_path.ends_with('/') || path.as_os_str().is_empty() {
+        path.push(INDEX_PAGE);
+    }
+
+    Some(path)
+}
+
+/// Determines whether or not the given request path looks like it names a
+/// file (e.g. /assets/app.js), rather than a page routed by the frontend.
+///
+/// # Arguments
+///
+/// * `request_path` - The path of the request
+pub fn names_file(request_path: &str) -> bool {
+    request_path
+        .rsplit('/')
+        .next()
+        .is_some_and(|segment| segment.contains('.'))
+}
+
+/// Determines whether or not the name of the given asset carries a hash of
+/// its contents (e.g. main.3f2a9c1b.js or index-BqXy12Zk.js), as generated by
+/// most bundlers, such that the asset may be cached indefinitely.
+///
+/// # Arguments
+///
+/// * `path` - The path of the asset
+pub fn is_hashed(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit(['.', '-']).next())
+        .is_some_and(|hash| {
+            hash.len() >= MIN_HASH_LENGTH
+                && hash.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && hash.chars().any(|c| c.is_ascii_digit())
+        })
+}
+
+/// Gets the media type of the given asset, according to its extension.
+///
+/// # Arguments
+///
+/// * `path` - The path of the asset
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    ASSET_TYPES
+        .iter()
+        .find(|(asset_extension, _)| extension.as_deref() == Some(*asset_extension))
+        .map_or("application/octet-stream", |(_, content_type)| {
+            *content_type
+        })
+}
+
+/// Reads the file at the given path on a blocking thread, if it exists.
+/// Directories are treated as missing files.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file
+async fn read(path: PathBuf) -> Result<Option<Vec<u8>>, HttpError> {
+    web::block(move || {
+        if path.is_file() {
+            fs::read(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+    .await
+    .map_err(|e| e.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_path() {
+        assert_eq!(asset_path("/"), Some(PathBuf::from("index.html")));
+        assert_eq!(
+            asset_path("/assets/app.js"),
+            Some(PathBuf::from("assets/app.js"))
+        );
+        assert_eq!(asset_path("/docs/"), Some(PathBuf::from("docs/index.html")));
+
+        assert_eq!(asset_path("/../etc/passwd"), None);
+        assert_eq!(asset_path("/.env"), None);
+        assert_eq!(asset_path("/assets/..%2f..\\secret"), None);
+    }
+
+    #[test]
+    fn test_names_file() {
+        assert!(names_file("/assets/app.js"));
+        assert!(names_file("/favicon.ico"));
+
+        assert!(!names_file("/"));
+        assert!(!names_file("/settings/profile"));
+        assert!(!names_file("/chat/"));
+    }
+
+    #[test]
+    fn test_is_hashed() {
+        assert!(is_hashed(Path::new("assets/main.3f2a9c1b.js")));
+        assert!(is_hashed(Path::new("assets/index-Bq4y12Zk.css")));
+
+        assert!(!is_hashed(Path::new("index.html")));
+        assert!(!is_hashed(Path::new("favicon.ico")));
+        assert!(!is_hashed(Path::new("assets/dashboard.js")));
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            content_type(Path::new("index.html")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(content_type(Path::new("logo.SVG")), "image/svg+xml");
+        assert_eq!(
+            content_type(Path::new("LICENSE")),
+            "application/octet-stream"
+        );
+    }
+}
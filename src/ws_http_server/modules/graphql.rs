@@ -0,0 +1,367 @@
+use actix_web::{
+    web::{Data, Json},
+    Scope,
+};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Object, Request, Response, Result as GraphQLResult,
+    Schema,
+};
+use chrono::{DateTime, Utc};
+
+use super::{
+    super::{
+        super::spec::{ban::Ban, history::ChatMessage, mute::Mute, user::User},
+        auth::{AuthError, Principal},
+        server::State,
+    },
+    bans::{BanQuery, Provider as BansProvider},
+    history::{HistoryFilter, Provider as HistoryProvider},
+    mutes::Provider as MutesProvider,
+    name_resolver::Provider as NameResolver,
+    roles::Provider as RolesProvider,
+    users::Provider as UsersProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The number of messages returned by a history query if no limit is
+/// specified.
+const DEFAULT_HISTORY_LIMIT: u64 = 50;
+
+/// The maximum number of messages that may be returned by a history query.
+const MAX_HISTORY_LIMIT: u64 = 500;
+
+/// The schema describing each of the types and queries exposed by the
+/// GraphQL API.
+pub type GraphQLSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds an actix service group encompassing the GraphQL endpoint.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/graphql")
+        .data(Schema::new(QueryRoot, EmptyMutation, EmptySubscription))
+        .service(execute)
+}
+
+/// Executes the given GraphQL query on behalf of the requesting party, if
+/// any. Fields restricted to moderators resolve to errors for other parties,
+/// without failing the rest of the query.
+#[post("")]
+pub async fn execute(
+    state: Data<State>,
+    schema: Data<GraphQLSchema>,
+    principal: Option<Principal>,
+    request: Json<Request>,
+) -> Json<Response> {
+    Json(
+        schema
+            .execute(request.into_inner().data(state).data(principal))
+            .await,
+    )
+}
+
+/// Runs the given request against the hybrid providers of the server
+/// executing the query.
+///
+/// # Arguments
+///
+/// * `ctx` - The context of the field being resolved
+/// * `request` - The request that should be run
+fn with_hybrid<T>(
+    ctx: &Context<'_>,
+    request: impl FnOnce(&mut Hybrid<Cache, Persistent>) -> Result<T, ProviderError>,
+) -> Result<T, ProviderError> {
+    let state = ctx.data_unchecked::<Data<State>>();
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    request(
+        &mut Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+        )
+        .with_breaker(Some(state.breaker().clone()))
+        .with_lookups(Some(state.lookups().clone())),
+    )
+}
+
+/// Determines whether or not the party executing the query may view fields
+/// restricted to moderators.
+///
+/// # Arguments
+///
+/// * `ctx` - The context of the field being resolved
+fn is_moderator(ctx: &Context<'_>) -> bool {
+    ctx.data_unchecked::<Option<Principal>>()
+        .as_ref()
+        .is_some_and(Principal::is_moderator)
+}
+
+/// Ensures that the party executing the query may view fields restricted to
+/// moderators.
+///
+/// # Arguments
+///
+/// * `ctx` - The context of the field being resolved
+fn require_moderator(ctx: &Context<'_>) -> GraphQLResult<()> {
+    if is_moderator(ctx) {
+        Ok(())
+    } else {
+        Err(AuthError::InsufficientPermissions.into())
+    }
+}
+
+/// Resolves the user with the given ID, if they exist.
+///
+/// # Arguments
+///
+/// * `ctx` - The context of the field being resolved
+/// * `user_id` - The ID of the user
+fn user_node(ctx: &Context<'_>, user_id: u64) -> GraphQLResult<Option<UserNode>> {
+    Ok(with_hybrid(ctx, |hybrid| hybrid.get_user(user_id))?.map(UserNode))
+}
+
+/// QueryRoot represents each of the queries accepted by the GraphQL API.
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Gets the user with the given ID or username.
+    async fn user(
+        &self,
+        ctx: &Context<'_>,
+        id: Option<u64>,
+        username: Option<String>,
+    ) -> GraphQLResult<Option<UserNode>> {
+        let id = match (id, username) {
+            (Some(id), _) => Some(id),
+            (None, Some(username)) => with_hybrid(ctx, |hybrid| hybrid.user_id_for(&username))?,
+            (None, None) => return Err(ProviderError::MissingArgument { arg: "id" }.into()),
+        };
+
+        match id {
+            Some(id) => user_node(ctx, id),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the ban currently in effect against the user with the given ID,
+    /// if any. Only moderators may view bans.
+    async fn ban(&self, ctx: &Context<'_>, user_id: u64) -> GraphQLResult<Option<BanNode>> {
+        require_moderator(ctx)?;
+
+        Ok(
+            with_hybrid(ctx, |hybrid| hybrid.get_ban(&BanQuery::Id(user_id)))?
+                .filter(Ban::active)
+                .map(BanNode),
+        )
+    }
+
+    /// Gets the mute currently in effect against the user with the given ID,
+    /// if any. Only moderators may view mutes.
+    async fn mute(&self, ctx: &Context<'_>, user_id: u64) -> GraphQLResult<Option<MuteNode>> {
+        require_moderator(ctx)?;
+
+        Ok(with_hybrid(ctx, |hybrid| hybrid.get_mute(user_id))?
+            .filter(Mute::active)
+            .map(MuteNode))
+    }
+
+    /// Gets up to `limit` of the most recent messages sent to the chat before
+    /// the message with the given ID, oldest first.
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<u64>,
+        before: Option<u64>,
+    ) -> GraphQLResult<Vec<MessageNode>> {
+        let limit = limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .min(MAX_HISTORY_LIMIT) as usize;
+
+        Ok(with_hybrid(ctx, |hybrid| {
+            hybrid.messages(&HistoryFilter::default().with_before(before), limit)
+        })?
+        .into_iter()
+        .map(MessageNode)
+        .collect())
+    }
+}
+
+/// UserNode represents a user exposed by the GraphQL API.
+pub struct UserNode(User);
+
+#[Object]
+impl UserNode {
+    /// The ID of the user
+    async fn id(&self) -> u64 {
+        self.0.id()
+    }
+
+    /// The username held by the user, if they have one
+    async fn username(&self) -> Option<&str> {
+        self.0.username()
+    }
+
+    /// Whether or not the user has been verified
+    async fn verified(&self) -> bool {
+        self.0.verified()
+    }
+
+    /// The country that the user most identifies with, if any
+    async fn nationality(&self) -> Option<&str> {
+        self.0.nationality()
+    }
+
+    /// Whether or not the user accepts gifts
+    async fn accepts_gifts(&self) -> bool {
+        self.0.accepts_gifts()
+    }
+
+    /// The user's minecraft username, if any
+    async fn minecraft_name(&self) -> Option<&str> {
+        self.0.minecraft_name()
+    }
+
+    /// The key of the user's avatar, if they uploaded one
+    async fn avatar(&self) -> Option<&str> {
+        self.0.avatar()
+    }
+
+    /// The time at which the user registered
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at()
+    }
+
+    /// The names of the roles held by the user
+    async fn roles(&self, ctx: &Context<'_>) -> GraphQLResult<Vec<&'static str>> {
+        Ok(
+            with_hybrid(ctx, |hybrid| hybrid.roles_for_user(self.0.id()))?
+                .iter()
+                .map(|role| role.to_str())
+                .collect(),
+        )
+    }
+
+    /// The ban currently in effect against the user, if any. Only moderators
+    /// may view bans.
+    async fn ban(&self, ctx: &Context<'_>) -> GraphQLResult<Option<BanNode>> {
+        require_moderator(ctx)?;
+
+        Ok(
+            with_hybrid(ctx, |hybrid| hybrid.get_ban(&BanQuery::Id(self.0.id())))?
+                .filter(Ban::active)
+                .map(BanNode),
+        )
+    }
+
+    /// The mute currently in effect against the user, if any. Only
+    /// moderators may view mutes.
+    async fn mute(&self, ctx: &Context<'_>) -> GraphQLResult<Option<MuteNode>> {
+        require_moderator(ctx)?;
+
+        Ok(with_hybrid(ctx, |hybrid| hybrid.get_mute(self.0.id()))?
+            .filter(Mute::active)
+            .map(MuteNode))
+    }
+
+    /// Up to `limit` of the most recent messages sent by the user, oldest
+    /// first
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<u64>,
+    ) -> GraphQLResult<Vec<MessageNode>> {
+        let limit = limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .min(MAX_HISTORY_LIMIT) as usize;
+
+        Ok(with_hybrid(ctx, |hybrid| {
+            hybrid.messages(
+                &HistoryFilter::default().with_sender(Some(self.0.id())),
+                limit,
+            )
+        })?
+        .into_iter()
+        .map(MessageNode)
+        .collect())
+    }
+}
+
+/// BanNode represents a ban exposed by the GraphQL API.
+pub struct BanNode(Ban);
+
+#[Object]
+impl BanNode {
+    /// The banned user
+    async fn user(&self, ctx: &Context<'_>) -> GraphQLResult<Option<UserNode>> {
+        user_node(ctx, self.0.concerns())
+    }
+
+    /// The time at which the ban lapses, if it isn't permanent
+    async fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.0.expires_at()
+    }
+
+    /// The banned IP address, if any
+    async fn address(&self) -> Option<&str> {
+        self.0.address()
+    }
+}
+
+/// MuteNode represents a mute exposed by the GraphQL API.
+pub struct MuteNode(Mute);
+
+#[Object]
+impl MuteNode {
+    /// The muted user
+    async fn user(&self, ctx: &Context<'_>) -> GraphQLResult<Option<UserNode>> {
+        user_node(ctx, self.0.concerns())
+    }
+
+    /// The time at which the mute lapses
+    async fn expires_at(&self) -> DateTime<Utc> {
+        self.0.expires_at()
+    }
+}
+
+/// MessageNode represents a message in the chat history exposed by the
+/// GraphQL API.
+pub struct MessageNode(ChatMessage);
+
+#[Object]
+impl MessageNode {
+    /// The ID of the message
+    async fn id(&self) -> u64 {
+        self.0.id()
+    }
+
+    /// The user who sent the message
+    async fn sender(&self, ctx: &Context<'_>) -> GraphQLResult<Option<UserNode>> {
+        user_node(ctx, self.0.sender_id())
+    }
+
+    /// The contents of the message
+    async fn contents(&self) -> &str {
+        self.0.contents()
+    }
+
+    /// The time at which the message was sent
+    async fn sent_at(&self) -> DateTime<Utc> {
+        self.0.sent_at()
+    }
+
+    /// The time at which the message was last edited by its sender, if it
+    /// was
+    async fn edited_at(&self) -> Option<DateTime<Utc>> {
+        self.0.edited_at()
+    }
+
+    /// The contents of the message before it was first edited, if it was.
+    /// Only moderators may view the original contents of edited messages.
+    async fn original_contents(&self, ctx: &Context<'_>) -> GraphQLResult<Option<&str>> {
+        require_moderator(ctx)?;
+
+        Ok(self.0.original_contents())
+    }
+}
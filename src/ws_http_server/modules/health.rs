@@ -0,0 +1,178 @@
+use actix_web::{web::Data, HttpResponse, Scope};
+use diesel::RunQueryDsl;
+use redis::Connection as RedisConnection;
+use serde::Serialize;
+
+use super::{
+    super::{super::spec::backend::PersistentConnection, server::State},
+    ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the health module. The routes are mounted at the root, so
+/// the group should be registered after every other group.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("").service(healthz).service(readyz)
+}
+
+/// DependencyStatus represents whether or not the server could reach one of
+/// the backends that it depends on.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct DependencyStatus {
+    /// Whether or not the backend could be reached
+    ok: bool,
+
+    /// The reason that the backend couldn't be reached, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    /// Describes the outcome of the given check of a backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - The result of checking the backend
+    fn of(check: Result<(), ProviderError>) -> Self {
+        match check {
+            Ok(_) => Self {
+                ok: true,
+                error: None,
+            },
+            Err(e) => Self {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Readiness represents whether or not the server is able to answer requests,
+/// along with the status of each of its backends.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Readiness {
+    /// Whether or not every backend could be reached
+    ready: bool,
+
+    /// The status of the redis caching layer
+    redis: DependencyStatus,
+
+    /// The status of the MySQL persistence layer
+    mysql: DependencyStatus,
+
+    /// The status of the MySQL read replica, if the deployment has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mysql_replica: Option<DependencyStatus>,
+}
+
+impl Readiness {
+    /// Creates a new readiness report from the status of each backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `redis` - The status of the redis caching layer
+    /// * `mysql` - The status of the MySQL persistence layer
+    /// * `mysql_replica` - The status of the MySQL read replica, if any
+    pub fn new(
+        redis: DependencyStatus,
+        mysql: DependencyStatus,
+        mysql_replica: Option<DependencyStatus>,
+    ) -> Self {
+        Self {
+            ready: redis.ok && mysql.ok && mysql_replica.as_ref().is_none_or(|status| status.ok),
+            redis,
+            mysql,
+            mysql_replica,
+        }
+    }
+
+    /// Determines whether or not every backend could be reached.
+    pub fn ready(&self) -> bool {
+        self.ready
+    }
+}
+
+/// Sends a PING to the redis caching layer.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+fn ping_redis(conn: &mut RedisConnection) -> Result<(), ProviderError> {
+    redis::cmd("PING")
+        .query::<String>(conn)
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Runs a trivial query against a MySQL database.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the MySQL database
+fn ping_mysql(conn: &PersistentConnection) -> Result<(), ProviderError> {
+    diesel::sql_query("SELECT 1")
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Reports that the server is running. Liveness doesn't depend on any
+/// backend, such that orchestrators don't restart the server while a backend
+/// is down.
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+/// Reports whether or not the server can reach each of its backends, such
+/// that load balancers stop routing requests to it while it can't. Answers
+/// with a 503 if any backend can't be reached.
+#[get("/readyz")]
+pub async fn readyz(state: Data<State>) -> HttpResponse {
+    let redis = DependencyStatus::of(
+        state
+            .cache_connection()
+            .and_then(|mut conn| ping_redis(&mut conn)),
+    );
+    let mysql = DependencyStatus::of(
+        state
+            .persistent_connection()
+            .and_then(|conn| ping_mysql(&conn)),
+    );
+    let mysql_replica = state.replica_connection().map_or_else(
+        |e| Some(DependencyStatus::of(Err(e))),
+        |conn| conn.map(|conn| DependencyStatus::of(ping_mysql(&conn))),
+    );
+
+    let readiness = Readiness::new(redis, mysql, mysql_replica);
+    if readiness.ready() {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness() {
+        let ok = || DependencyStatus::of(Ok(()));
+        let down = || DependencyStatus::of(Err(ProviderError::MissingArgument { arg: "conn" }));
+
+        assert!(Readiness::new(ok(), ok(), None).ready());
+        assert!(!Readiness::new(ok(), ok(), Some(down())).ready());
+        assert!(!Readiness::new(down(), ok(), None).ready());
+
+        // Unreachable backends are reported along with the reason
+        assert_eq!(
+            serde_json::to_value(Readiness::new(ok(), down(), None)).unwrap(),
+            serde_json::json!({
+                "ready": false,
+                "redis": { "ok": true },
+                "mysql": { "ok": false, "error": down().error },
+            })
+        );
+    }
+}
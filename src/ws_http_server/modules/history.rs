@@ -0,0 +1,925 @@
+use actix_web::{
+    web::{Data, Json, Query},
+    Error as HttpError, Scope,
+};
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use redis::Connection as RedisConnection;
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{last_insert_id, PersistentConnection, Unsigned},
+            history::{ChatMessage, NewChatMessage},
+            schema::chat_history,
+        },
+        auth::{role::Moderator, AuthError, RequireRole},
+        pagination::{Page, Pagination, Sort},
+        server::State,
+    },
+    name_resolver::Provider as NameResolver,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::slice;
+
+/// The number of recent messages kept in the caching layer.
+pub const BUFFER_LENGTH: usize = 200;
+
+/// The number of recent messages sent to each newly connected client.
+pub const BACKFILL_LENGTH: usize = 50;
+
+/// The number of messages returned by the history route if no limit is
+/// specified.
+const DEFAULT_PAGE_LENGTH: usize = 50;
+
+/// The maximum number of messages that may be returned by the history route.
+const MAX_PAGE_LENGTH: usize = 200;
+
+/// The redis list holding the most recent messages, newest first.
+const BUFFER_KEY: &str = "chat_history";
+
+/// The redis key holding the ID of the most recent message recorded by the
+/// caching layer alone.
+const LAST_ID_KEY: &str = "chat_history::last_id";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the history module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/chat").service(list_history)
+}
+
+/// HistoryQuery represents the filters accepted by the chat history route,
+/// alongside its pagination parameters.
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    /// (optional) The username of the user who must have sent each message
+    author: Option<String>,
+
+    /// (optional) The earliest time at which each message may have been sent
+    since: Option<DateTime<Utc>>,
+
+    /// (optional) The time before which each message must have been sent
+    until: Option<DateTime<Utc>>,
+
+    /// (optional) Whether or not messages deleted by moderators should be
+    /// returned. Only moderators may view deleted messages.
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+/// Gets a page of messages sent to the chat, most recent first unless
+/// sorted in ascending order. Messages are paged by their IDs.
+#[get("/history")]
+pub async fn list_history(
+    state: Data<State>,
+    moderator: Option<RequireRole<Moderator>>,
+    pagination: Pagination,
+    query: Query<HistoryQuery>,
+) -> Result<Json<Page<ChatMessage>>, HttpError> {
+    if query.include_deleted && moderator.is_none() {
+        return Err(AuthError::InsufficientPermissions.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let sender_id = match &query.author {
+        Some(author) => match hybrid.user_id_for(author)? {
+            Some(sender_id) => Some(sender_id),
+
+            // Unknown users haven't sent any messages
+            None => return Ok(Json(Page::empty())),
+        },
+        None => None,
+    };
+
+    let limit = pagination.limit(DEFAULT_PAGE_LENGTH, MAX_PAGE_LENGTH);
+    let cursor = pagination.cursor::<u64>()?;
+    let sort = pagination.sort(Sort::Desc);
+
+    // Pages sorted in ascending order start from the very first message
+    let filter = match sort {
+        Sort::Asc => HistoryFilter::default().with_after(Some(cursor.unwrap_or(0))),
+        Sort::Desc => HistoryFilter::default().with_before(cursor),
+    };
+    let mut messages = hybrid.messages(
+        &filter
+            .with_sender(sender_id)
+            .with_since(query.since)
+            .with_until(query.until)
+            .with_deleted(query.include_deleted),
+        limit,
+    )?;
+
+    if sort == Sort::Desc {
+        messages.reverse();
+    }
+
+    // The contents of edited messages as they were originally sent are only
+    // shown to moderators
+    if moderator.is_none() {
+        messages = messages.into_iter().map(ChatMessage::redacted).collect();
+    }
+
+    Ok(Json(Page::new(messages, limit, ChatMessage::id)))
+}
+
+/// Gets the most recent messages that should be sent to a newly connected
+/// client, oldest first. Edited messages are sent without their original
+/// contents.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+pub fn backfill(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+) -> Result<Vec<ChatMessage>, ProviderError> {
+    Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .recent_messages(BACKFILL_LENGTH)
+    .map(|messages| messages.into_iter().map(ChatMessage::redacted).collect())
+}
+
+/// HistoryFilter represents the criteria that each message returned by a
+/// history query must satisfy. By default, every message not deleted by a
+/// moderator is matched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HistoryFilter {
+    /// (optional) The ID of the user who must have sent each message
+    sender_id: Option<u64>,
+
+    /// (optional) The earliest time at which each message may have been sent
+    since: Option<DateTime<Utc>>,
+
+    /// (optional) The time before which each message must have been sent
+    until: Option<DateTime<Utc>>,
+
+    /// (optional) The ID before which each message must have been sent, used
+    /// as a pagination cursor
+    before: Option<u64>,
+
+    /// (optional) The ID after which each message must have been sent, used
+    /// as a pagination cursor when paging forward
+    after: Option<u64>,
+
+    /// Whether or not messages deleted by moderators are matched
+    include_deleted: bool,
+}
+
+impl HistoryFilter {
+    /// Consumes the filter, and modifies it such that only messages sent by
+    /// the given user are matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - (optional) The ID of the user who must have sent each
+    /// message
+    pub fn with_sender(mut self, sender_id: Option<u64>) -> Self {
+        self.sender_id = sender_id;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it such that only messages sent at
+    /// or after the given time are matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - (optional) The earliest time at which each message may have
+    /// been sent
+    pub fn with_since(mut self, since: Option<DateTime<Utc>>) -> Self {
+        self.since = since;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it such that only messages sent
+    /// before the given time are matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `until` - (optional) The time before which each message must have
+    /// been sent
+    pub fn with_until(mut self, until: Option<DateTime<Utc>>) -> Self {
+        self.until = until;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it such that only messages sent
+    /// before the message with the given ID are matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - (optional) The ID of the message before which each
+    /// message must have been sent
+    pub fn with_before(mut self, before: Option<u64>) -> Self {
+        self.before = before;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it such that only messages sent
+    /// after the message with the given ID are matched. Queries paging
+    /// forward return the earliest matching messages, rather than the most
+    /// recent ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - (optional) The ID of the message after which each message
+    /// must have been sent
+    pub fn with_after(mut self, after: Option<u64>) -> Self {
+        self.after = after;
+
+        self
+    }
+
+    /// Consumes the filter, and modifies it such that messages deleted by
+    /// moderators are matched as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `include_deleted` - Whether or not deleted messages should be
+    /// matched
+    pub fn with_deleted(mut self, include_deleted: bool) -> Self {
+        self.include_deleted = include_deleted;
+
+        self
+    }
+
+    /// Determines whether or not the given message satisfies the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be checked
+    pub fn matches(&self, message: &ChatMessage) -> bool {
+        self.sender_id.is_none_or(|id| message.sender_id() == id)
+            && self.since.is_none_or(|since| message.sent_at() >= since)
+            && self.until.is_none_or(|until| message.sent_at() < until)
+            && self.before.is_none_or(|before| message.id() < before)
+            && self.after.is_none_or(|after| message.id() > after)
+            && (self.include_deleted || !message.deleted())
+    }
+}
+
+/// Provider represents an arbitrary backend for the chat history service,
+/// which records each message sent to the chat.
+pub trait Provider {
+    /// Records the given message in the chat history, returning the message
+    /// alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be recorded
+    fn record_chat_message(
+        &mut self,
+        message: &NewChatMessage,
+    ) -> Result<ChatMessage, ProviderError>;
+
+    /// Gets up to `limit` of the most recently sent messages, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of messages that should be returned
+    fn recent_messages(&mut self, limit: usize) -> Result<Vec<ChatMessage>, ProviderError>;
+
+    /// Gets up to `limit` of the most recently sent messages satisfying the
+    /// given filter, oldest first. If the filter pages forward from a cursor,
+    /// the earliest matching messages are returned instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The criteria that each message must satisfy
+    /// * `limit` - The maximum number of messages that should be returned
+    fn messages(
+        &mut self,
+        filter: &HistoryFilter,
+        limit: usize,
+    ) -> Result<Vec<ChatMessage>, ProviderError>;
+
+    /// Marks the message with the given ID as deleted by a moderator,
+    /// returning whether or not it existed and hadn't already been deleted.
+    /// Deleted messages are kept, so that moderators may still view them.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be deleted
+    /// * `at` - The time at which the message was deleted
+    fn delete_chat_message(&mut self, id: u64, at: DateTime<Utc>) -> Result<bool, ProviderError>;
+
+    /// Retreives the message with the given ID, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be obtained
+    fn chat_message(&mut self, id: u64) -> Result<Option<ChatMessage>, ProviderError>;
+
+    /// Replaces the contents of the message with the given ID, keeping the
+    /// contents it was originally sent with, and returning whether or not it
+    /// existed and hadn't been deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be edited
+    /// * `contents` - The new contents of the message
+    /// * `at` - The time at which the message was edited
+    fn edit_chat_message(
+        &mut self,
+        id: u64,
+        contents: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Finds the message with the given ID in the redis history buffer,
+    /// returning it alongside its serialized form, if it is buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be found
+    fn buffered_message(
+        &mut self,
+        id: u64,
+    ) -> Result<Option<(ChatMessage, String)>, ProviderError> {
+        let serialized: Vec<String> = redis::cmd("LRANGE")
+            .arg(self.key(BUFFER_KEY))
+            .arg(0)
+            .arg(-1)
+            .query(self.connection)?;
+
+        for raw in serialized {
+            let message = serde_json::from_str::<ChatMessage>(&raw)?;
+
+            if message.id() == id {
+                return Ok(Some((message, raw)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Pushes the given messages onto the redis history buffer, discarding
+    /// the oldest messages once the buffer is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The messages that should be buffered, oldest first
+    fn buffer_messages(&mut self, messages: &[ChatMessage]) -> Result<(), ProviderError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let serialized = messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, _>>()?;
+
+        redis::pipe()
+            .atomic()
+            .cmd("LPUSH")
+            .arg(self.key(BUFFER_KEY))
+            .arg(serialized)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(self.key(BUFFER_KEY))
+            .arg(0)
+            .arg(BUFFER_LENGTH - 1)
+            .ignore()
+            .query::<()>(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records the given message in the redis history buffer, returning the
+    /// message alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be recorded
+    fn record_chat_message(
+        &mut self,
+        message: &NewChatMessage,
+    ) -> Result<ChatMessage, ProviderError> {
+        let id: u64 = redis::cmd("INCR")
+            .arg(self.key(LAST_ID_KEY))
+            .query(self.connection)?;
+        let message = message.with_id(id);
+
+        self.buffer_messages(slice::from_ref(&message))
+            .map(|_| message)
+    }
+
+    /// Gets up to `limit` of the most recently sent messages from the redis
+    /// history buffer, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of messages that should be returned
+    fn recent_messages(&mut self, limit: usize) -> Result<Vec<ChatMessage>, ProviderError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let serialized: Vec<String> = redis::cmd("LRANGE")
+            .arg(self.key(BUFFER_KEY))
+            .arg(0)
+            .arg(limit - 1)
+            .query(self.connection)?;
+
+        serialized
+            .iter()
+            .rev()
+            .map(|message| serde_json::from_str(message).map_err(|e| e.into()))
+            .collect()
+    }
+
+    /// Gets up to `limit` of the most recently sent messages in the redis
+    /// history buffer satisfying the given filter, oldest first. Messages
+    /// that have fallen out of the buffer are not considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The criteria that each message must satisfy
+    /// * `limit` - The maximum number of messages that should be returned
+    fn messages(
+        &mut self,
+        filter: &HistoryFilter,
+        limit: usize,
+    ) -> Result<Vec<ChatMessage>, ProviderError> {
+        let buffered = self.recent_messages(BUFFER_LENGTH)?.into_iter();
+
+        if filter.after.is_some() {
+            return Ok(buffered
+                .filter(|message| filter.matches(message))
+                .take(limit)
+                .collect());
+        }
+
+        let mut matched = buffered
+            .rev()
+            .filter(|message| filter.matches(message))
+            .take(limit)
+            .collect::<Vec<ChatMessage>>();
+        matched.reverse();
+
+        Ok(matched)
+    }
+
+    /// Removes the message with the given ID from the redis history buffer,
+    /// returning whether or not it was buffered. The buffer only serves
+    /// messages to newly connected clients, so deleted messages are dropped
+    /// from it rather than kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be deleted
+    /// * `at` - The time at which the message was deleted
+    fn delete_chat_message(&mut self, id: u64, _at: DateTime<Utc>) -> Result<bool, ProviderError> {
+        let raw = match self.buffered_message(id)? {
+            Some((_, raw)) => raw,
+            None => return Ok(false),
+        };
+
+        // Messages are removed by value, so that messages pushed onto the
+        // buffer in the meantime don't shift the message out from under its
+        // index
+        redis::cmd("LREM")
+            .arg(self.key(BUFFER_KEY))
+            .arg(1)
+            .arg(raw)
+            .query::<u64>(self.connection)
+            .map(|removed| removed > 0)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the message with the given ID from the redis history
+    /// buffer, if it is buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be obtained
+    fn chat_message(&mut self, id: u64) -> Result<Option<ChatMessage>, ProviderError> {
+        self.buffered_message(id)
+            .map(|buffered| buffered.map(|(message, _)| message))
+    }
+
+    /// Replaces the contents of the message with the given ID in the redis
+    /// history buffer, returning whether or not it was buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be edited
+    /// * `contents` - The new contents of the message
+    /// * `at` - The time at which the message was edited
+    fn edit_chat_message(
+        &mut self,
+        id: u64,
+        contents: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, ProviderError> {
+        let (message, raw) = match self.buffered_message(id)? {
+            Some(buffered) => buffered,
+            None => return Ok(false),
+        };
+
+        // The edited message is inserted next to the original by value
+        // before the original is removed, so that the message keeps its
+        // place in the buffer
+        let (inserted, _): (i64, u64) = redis::pipe()
+            .atomic()
+            .cmd("LINSERT")
+            .arg(self.key(BUFFER_KEY))
+            .arg("BEFORE")
+            .arg(&raw)
+            .arg(serde_json::to_string(&message.with_edit(contents, at))?)
+            .cmd("LREM")
+            .arg(self.key(BUFFER_KEY))
+            .arg(1)
+            .arg(&raw)
+            .query(self.connection)?;
+
+        Ok(inserted > 0)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Archives the given message in the MySQL database, returning the
+    /// message alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be recorded
+    fn record_chat_message(
+        &mut self,
+        message: &NewChatMessage,
+    ) -> Result<ChatMessage, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            diesel::insert_into(chat_history::table)
+                .values(message)
+                .execute(connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched separately
+            last_insert_id(connection)
+                .map(|id| message.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Gets up to `limit` of the most recently archived messages, oldest
+    /// first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of messages that should be returned
+    fn recent_messages(&mut self, limit: usize) -> Result<Vec<ChatMessage>, ProviderError> {
+        self.messages(&HistoryFilter::default(), limit)
+    }
+
+    /// Gets up to `limit` of the most recently archived messages satisfying
+    /// the given filter, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The criteria that each message must satisfy
+    /// * `limit` - The maximum number of messages that should be returned
+    fn messages(
+        &mut self,
+        filter: &HistoryFilter,
+        limit: usize,
+    ) -> Result<Vec<ChatMessage>, ProviderError> {
+        let mut query = chat_history::dsl::chat_history
+            .limit(limit as i64)
+            .into_boxed();
+
+        // Queries paging forward start from the earliest matching message
+        query = match filter.after {
+            Some(after) => query
+                .filter(chat_history::dsl::id.gt(Unsigned(after)))
+                .order(chat_history::dsl::id.asc()),
+            None => query.order(chat_history::dsl::id.desc()),
+        };
+
+        if let Some(sender_id) = filter.sender_id {
+            query = query.filter(chat_history::dsl::sender_id.eq(Unsigned(sender_id)));
+        }
+
+        if let Some(since) = filter.since {
+            query = query.filter(chat_history::dsl::sent_at.ge(since.naive_utc()));
+        }
+
+        if let Some(until) = filter.until {
+            query = query.filter(chat_history::dsl::sent_at.lt(until.naive_utc()));
+        }
+
+        if let Some(before) = filter.before {
+            query = query.filter(chat_history::dsl::id.lt(Unsigned(before)));
+        }
+
+        if !filter.include_deleted {
+            query = query.filter(chat_history::dsl::deleted_at.is_null());
+        }
+
+        let mut messages = query.load::<ChatMessage>(self.connection)?;
+        if filter.after.is_none() {
+            messages.reverse();
+        }
+
+        Ok(messages)
+    }
+
+    /// Marks the archived message with the given ID as deleted, returning
+    /// whether or not it existed and hadn't already been deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be deleted
+    /// * `at` - The time at which the message was deleted
+    fn delete_chat_message(&mut self, id: u64, at: DateTime<Utc>) -> Result<bool, ProviderError> {
+        diesel::update(
+            chat_history::table
+                .find(Unsigned(id))
+                .filter(chat_history::dsl::deleted_at.is_null()),
+        )
+        .set(chat_history::dsl::deleted_at.eq(at.naive_utc()))
+        .execute(self.connection)
+        .map(|updated| updated > 0)
+        .map_err(|e| e.into())
+    }
+
+    /// Retreives the archived message with the given ID, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be obtained
+    fn chat_message(&mut self, id: u64) -> Result<Option<ChatMessage>, ProviderError> {
+        chat_history::table
+            .find(Unsigned(id))
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Replaces the contents of the archived message with the given ID,
+    /// keeping the contents it was originally sent with, and returning
+    /// whether or not it existed and hadn't been deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be edited
+    /// * `contents` - The new contents of the message
+    /// * `at` - The time at which the message was edited
+    fn edit_chat_message(
+        &mut self,
+        id: u64,
+        contents: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            let message = match chat_history::table
+                .find(Unsigned(id))
+                .filter(chat_history::dsl::deleted_at.is_null())
+                .first::<ChatMessage>(connection)
+                .optional()?
+            {
+                Some(message) => message.with_edit(contents, at),
+                None => return Ok(false),
+            };
+
+            diesel::update(chat_history::table.find(Unsigned(id)))
+                .set((
+                    chat_history::dsl::contents.eq(message.contents()),
+                    chat_history::dsl::edited_at.eq(message.edited_at().map(|at| at.naive_utc())),
+                    chat_history::dsl::original_contents.eq(message.original_contents()),
+                ))
+                .execute(connection)
+                .map(|_| true)
+                .map_err(|e| e.into())
+        })
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Archives the given message in the persistent layer, and pushes it onto
+    /// the cached history buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message that should be recorded
+    fn record_chat_message(
+        &mut self,
+        message: &NewChatMessage,
+    ) -> Result<ChatMessage, ProviderError> {
+        let message = self.persistent.record_chat_message(message)?;
+
+        self.cache
+            .buffer_messages(slice::from_ref(&message))
+            .map(|_| message)
+    }
+
+    /// Gets up to `limit` of the most recently sent messages, oldest first.
+    /// If the cached buffer holds too few messages (e.g. after a restart of
+    /// the caching layer), the buffer is refilled from the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of messages that should be returned
+    fn recent_messages(&mut self, limit: usize) -> Result<Vec<ChatMessage>, ProviderError> {
+        let cached = self.cache.recent_messages(limit)?;
+        if cached.len() >= limit.min(BUFFER_LENGTH) {
+            return Ok(cached);
+        }
+
+        let archived = self.persistent.recent_messages(limit.max(BUFFER_LENGTH))?;
+
+        redis::cmd("DEL")
+            .arg(self.cache.key(BUFFER_KEY))
+            .query::<()>(self.cache.connection)?;
+        self.cache
+            .buffer_messages(&archived[archived.len().saturating_sub(BUFFER_LENGTH)..])?;
+
+        Ok(archived[archived.len().saturating_sub(limit)..].to_vec())
+    }
+
+    /// Gets up to `limit` of the most recently sent messages satisfying the
+    /// given filter, oldest first. Filtered queries may reach beyond the
+    /// cached buffer, and are therefore answered by the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The criteria that each message must satisfy
+    /// * `limit` - The maximum number of messages that should be returned
+    fn messages(
+        &mut self,
+        filter: &HistoryFilter,
+        limit: usize,
+    ) -> Result<Vec<ChatMessage>, ProviderError> {
+        self.persistent.messages(filter, limit)
+    }
+
+    /// Marks the archived message with the given ID as deleted, and removes
+    /// it from the cached history buffer, returning whether or not it existed
+    /// and hadn't already been deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be deleted
+    /// * `at` - The time at which the message was deleted
+    fn delete_chat_message(&mut self, id: u64, at: DateTime<Utc>) -> Result<bool, ProviderError> {
+        let deleted = self.persistent.delete_chat_message(id, at)?;
+
+        self.cache.delete_chat_message(id, at).map(|_| deleted)
+    }
+
+    /// Retreives the message with the given ID from the cached history
+    /// buffer, or from the persistent layer if it is no longer buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be obtained
+    fn chat_message(&mut self, id: u64) -> Result<Option<ChatMessage>, ProviderError> {
+        match self.cache.chat_message(id)? {
+            Some(message) => Ok(Some(message)),
+            None => self.persistent.chat_message(id),
+        }
+    }
+
+    /// Replaces the contents of the archived message with the given ID, and
+    /// of its copy in the cached history buffer, returning whether or not it
+    /// existed and hadn't been deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the message that should be edited
+    /// * `contents` - The new contents of the message
+    /// * `at` - The time at which the message was edited
+    fn edit_chat_message(
+        &mut self,
+        id: u64,
+        contents: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, ProviderError> {
+        let edited = self.persistent.edit_chat_message(id, contents, at)?;
+
+        self.cache
+            .edit_chat_message(id, contents, at)
+            .map(|_| edited)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_filter() {
+        let sent_at = Utc::now();
+        let msg = NewChatMessage::new(42069, "MrMouton", "Pog", sent_at).with_id(10);
+
+        assert!(HistoryFilter::default().matches(&msg));
+        assert!(HistoryFilter::default()
+            .with_sender(Some(42069))
+            .with_before(Some(11))
+            .matches(&msg));
+        assert!(!HistoryFilter::default().with_before(Some(10)).matches(&msg));
+        assert!(!HistoryFilter::default().with_after(Some(10)).matches(&msg));
+        assert!(!HistoryFilter::default()
+            .with_since(Some(sent_at + Duration::seconds(1)))
+            .matches(&msg));
+        assert!(!HistoryFilter::default().with_sender(Some(1)).matches(&msg));
+    }
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut history = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        let sent_at = Utc::now();
+
+        let first = history
+            .record_chat_message(&NewChatMessage::new(42069, "MrMouton", "first", sent_at))?;
+        let second = history.record_chat_message(&NewChatMessage::new(
+            69420,
+            "essaywriter",
+            "second",
+            sent_at,
+        ))?;
+        assert!(second.id() > first.id());
+
+        // Messages are returned oldest first
+        assert_eq!(
+            history.recent_messages(2)?,
+            vec![first.clone(), second.clone()]
+        );
+
+        let by_sender = history.messages(
+            &HistoryFilter::default()
+                .with_sender(Some(42069))
+                .with_since(Some(sent_at - Duration::seconds(1))),
+            1,
+        )?;
+        assert_eq!(by_sender, vec![first.clone()]);
+
+        // Paging forward returns the earliest messages after the cursor
+        let forward = history.messages(
+            &HistoryFilter::default().with_after(Some(first.id() - 1)),
+            1,
+        )?;
+        assert_eq!(forward, vec![first.clone()]);
+
+        // Deleted messages are hidden from everyone but moderators, and may
+        // only be deleted once
+        assert!(history.delete_chat_message(first.id(), Utc::now())?);
+        assert!(!history.delete_chat_message(first.id(), Utc::now())?);
+        assert!(!history.recent_messages(BUFFER_LENGTH)?.contains(&first));
+
+        let with_deleted = history.messages(
+            &HistoryFilter::default()
+                .with_sender(Some(42069))
+                .with_before(Some(first.id() + 1))
+                .with_deleted(true),
+            1,
+        )?;
+        assert!(with_deleted[0].deleted() && with_deleted[0].id() == first.id());
+
+        // Edits keep the contents that the message was originally sent with,
+        // and deleted messages can't be edited
+        assert!(history.edit_chat_message(second.id(), "edited", Utc::now())?);
+        let edited = history
+            .chat_message(second.id())?
+            .expect("the message must exist");
+        assert_eq!(edited.contents(), "edited");
+        assert_eq!(edited.original_contents(), Some("second"));
+        assert!(!history.edit_chat_message(first.id(), "edited", Utc::now())?);
+
+        Ok(())
+    }
+}
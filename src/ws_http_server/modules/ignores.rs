@@ -0,0 +1,505 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use diesel::{BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use redis::Connection as RedisConnection;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{PersistentConnection, Unsigned},
+            event::{Event, EventKind},
+            ignore::IgnoreEntry,
+            schema::ignores,
+        },
+        auth::AuthedUser,
+        server::State,
+    },
+    name_resolver::Provider as NameResolver,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the ignores module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/ignores")
+        .service(list_ignores)
+        .service(add_ignore)
+        .service(remove_ignore)
+}
+
+/// Gets the usernames of each of the users ignored by the authenticated user.
+#[get("")]
+pub async fn list_ignores(
+    state: Data<State>,
+    user: AuthedUser,
+) -> Result<Json<Vec<String>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    ignored_usernames(&mut conn, state.key_prefix(), &persistent_conn, user.id()).map(Json)
+}
+
+/// Adds the user with the given username to the authenticated user's ignore
+/// list, so that none of their messages or whispers are delivered to the
+/// authenticated user.
+#[put("/{username}")]
+pub async fn add_ignore(
+    state: Data<State>,
+    user: AuthedUser,
+    username: Path<String>,
+) -> Result<Option<HttpResponse>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let ignored_id = match hybrid.user_id_for(&username)? {
+        Some(ignored_id) => ignored_id,
+        None => return Ok(None),
+    };
+
+    if ignored_id == user.id() {
+        return Err(ProviderError::InvalidArgument { arg: "username" }.into());
+    }
+
+    hybrid.ignore(user.id(), ignored_id)?;
+
+    Ok(Some(HttpResponse::NoContent().finish()))
+}
+
+/// Removes the user with the given username from the authenticated user's
+/// ignore list.
+#[delete("/{username}")]
+pub async fn remove_ignore(
+    state: Data<State>,
+    user: AuthedUser,
+    username: Path<String>,
+) -> Result<Option<HttpResponse>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let ignored_id = match hybrid.user_id_for(&username)? {
+        Some(ignored_id) => ignored_id,
+        None => return Ok(None),
+    };
+
+    hybrid.unignore(user.id(), ignored_id)?;
+
+    Ok(Some(HttpResponse::NoContent().finish()))
+}
+
+/// Gets the usernames of each of the users ignored by the given user. Users
+/// that have since deleted their accounts have no username, and are left out.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `user_id` - The ID of the user whose ignore list should be retreived
+pub fn ignored_usernames(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    user_id: u64,
+) -> Result<Vec<String>, ProviderError> {
+    let mut hybrid = Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    );
+    let ignored = hybrid.ignored_by(user_id)?;
+
+    Ok(hybrid
+        .usernames_for(&ignored)?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Narrows the given recipients of an event down to those who should receive
+/// it, dropping each recipient ignoring the sender of a broadcast, whisper, or
+/// highlight. Called before an event is fanned out to each recipient's
+/// sessions.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `event` - The event that should be delivered
+/// * `recipients` - The IDs of each of the users to whom the event is
+/// addressed
+pub fn recipients_for(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    event: &Event,
+    recipients: &[u64],
+) -> Result<Vec<u64>, ProviderError> {
+    // Only messages sent by users may be ignored
+    let sender = match event.event_kind() {
+        EventKind::Broadcast(broadcast) => broadcast.sent_by(),
+        EventKind::Highlight(highlight) => highlight.sent_by(),
+        _ => return Ok(recipients.to_vec()),
+    };
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    );
+
+    let sender_id = match hybrid.user_id_for(sender)? {
+        Some(sender_id) => sender_id,
+        None => return Ok(recipients.to_vec()),
+    };
+
+    let mut deliverable = Vec::with_capacity(recipients.len());
+
+    for recipient in recipients {
+        if !hybrid.is_ignoring(*recipient, sender_id)? {
+            deliverable.push(*recipient);
+        }
+    }
+
+    Ok(deliverable)
+}
+
+/// Gets the redis key of the value holding the given user's ignore list.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose ignore list should be located
+pub(crate) fn ignores_key(user_id: u64) -> String {
+    format!("ignores::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the ignores service, which
+/// stores the users whose messages each user has chosen not to receive.
+pub trait Provider {
+    /// Gets the IDs of each of the users ignored by the given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be obtained
+    fn ignored_by(&mut self, user_id: u64) -> Result<Vec<u64>, ProviderError>;
+
+    /// Determines whether or not the given user is ignoring the given
+    /// sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be checked
+    /// * `sender_id` - The ID of the user who may be ignored
+    fn is_ignoring(&mut self, user_id: u64, sender_id: u64) -> Result<bool, ProviderError>;
+
+    /// Adds the given user to the ignore list of another user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should be ignored
+    fn ignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError>;
+
+    /// Removes the given user from the ignore list of another user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should no longer be ignored
+    fn unignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Retreives the given user's ignore list from the redis caching layer,
+    /// if it has been cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be obtained
+    fn cached_ignores(&mut self, user_id: u64) -> Result<Option<Vec<u64>>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(ignores_key(user_id)))
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given user's ignore list in the redis caching layer. Empty
+    /// lists are stored as well, so that users ignoring nobody needn't be
+    /// looked up in the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be stored
+    /// * `ignored` - The IDs of each of the users ignored by the user
+    fn cache_ignores(&mut self, user_id: u64, ignored: &[u64]) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(ignores_key(user_id)))
+            .arg(serde_json::to_string(ignored)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the IDs of each of the users ignored by the given user from the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be obtained
+    fn ignored_by(&mut self, user_id: u64) -> Result<Vec<u64>, ProviderError> {
+        self.cached_ignores(user_id)
+            .map(|ignored| ignored.unwrap_or_default())
+    }
+
+    /// Determines whether or not the given user is ignoring the given sender
+    /// according to the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be checked
+    /// * `sender_id` - The ID of the user who may be ignored
+    fn is_ignoring(&mut self, user_id: u64, sender_id: u64) -> Result<bool, ProviderError> {
+        self.ignored_by(user_id)
+            .map(|ignored| ignored.contains(&sender_id))
+    }
+
+    /// Adds the given user to the ignore list of another user in the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should be ignored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, ignores::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut ignores = Cache::new(&mut conn);
+    /// ignores.ignore(42069, 69420)?;
+    ///
+    /// assert!(ignores.is_ignoring(42069, 69420)?);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn ignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        let mut ignored = self.ignored_by(user_id)?;
+
+        if !ignored.contains(&ignored_id) {
+            ignored.push(ignored_id);
+        }
+
+        self.cache_ignores(user_id, &ignored)
+    }
+
+    /// Removes the given user from the ignore list of another user in the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should no longer be ignored
+    fn unignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        let mut ignored = self.ignored_by(user_id)?;
+        ignored.retain(|id| *id != ignored_id);
+
+        self.cache_ignores(user_id, &ignored)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets the IDs of each of the users ignored by the given user from the
+    /// MySQL database, in the order in which they were ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be obtained
+    fn ignored_by(&mut self, user_id: u64) -> Result<Vec<u64>, ProviderError> {
+        ignores::table
+            .filter(ignores::dsl::user_id.eq(Unsigned(user_id)))
+            .order(ignores::dsl::ignored_at.asc())
+            .select(ignores::dsl::ignored_id)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Determines whether or not the given user is ignoring the given sender
+    /// according to the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be checked
+    /// * `sender_id` - The ID of the user who may be ignored
+    fn is_ignoring(&mut self, user_id: u64, sender_id: u64) -> Result<bool, ProviderError> {
+        ignores::table
+            .filter(
+                ignores::dsl::user_id
+                    .eq(Unsigned(user_id))
+                    .and(ignores::dsl::ignored_id.eq(Unsigned(sender_id))),
+            )
+            .select(ignores::dsl::ignored_id)
+            .first::<u64>(self.connection)
+            .optional()
+            .map(|ignored| ignored.is_some())
+            .map_err(|e| e.into())
+    }
+
+    /// Adds the given user to the ignore list of another user in the MySQL
+    /// database. Users already being ignored keep their original position in
+    /// the list.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should be ignored
+    fn ignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        diesel::insert_or_ignore_into(ignores::table)
+            .values(IgnoreEntry::new(user_id, ignored_id))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the given user from the ignore list of another user in the
+    /// MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should no longer be ignored
+    fn unignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        diesel::delete(ignores::table.find((Unsigned(user_id), Unsigned(ignored_id))))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets the IDs of each of the users ignored by the given user,
+    /// populating the cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be obtained
+    fn ignored_by(&mut self, user_id: u64) -> Result<Vec<u64>, ProviderError> {
+        match self.cache.cached_ignores(user_id) {
+            Ok(Some(ignored)) => Ok(ignored),
+            _ => self
+                .persistent
+                .ignored_by(user_id)
+                .and_then(|ignored| self.cache.cache_ignores(user_id, &ignored).map(|_| ignored)),
+        }
+    }
+
+    /// Determines whether or not the given user is ignoring the given
+    /// sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose ignore list should be checked
+    /// * `sender_id` - The ID of the user who may be ignored
+    fn is_ignoring(&mut self, user_id: u64, sender_id: u64) -> Result<bool, ProviderError> {
+        self.ignored_by(user_id)
+            .map(|ignored| ignored.contains(&sender_id))
+    }
+
+    /// Adds the given user to the ignore list of another user, refreshing
+    /// the cached list from the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should be ignored
+    fn ignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        self.persistent.ignore(user_id, ignored_id)?;
+
+        let ignored = self.persistent.ignored_by(user_id)?;
+        self.cache.cache_ignores(user_id, &ignored)
+    }
+
+    /// Removes the given user from the ignore list of another user,
+    /// refreshing the cached list from the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is ignoring another user
+    /// * `ignored_id` - The ID of the user who should no longer be ignored
+    fn unignore(&mut self, user_id: u64, ignored_id: u64) -> Result<(), ProviderError> {
+        self.persistent.unignore(user_id, ignored_id)?;
+
+        let ignored = self.persistent.ignored_by(user_id)?;
+        self.cache.cache_ignores(user_id, &ignored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut ignores = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        ignores.ignore(42069, 69420)?;
+        ignores.ignore(42069, 69420)?;
+
+        assert!(ignores.is_ignoring(42069, 69420)?);
+        assert!(!ignores.is_ignoring(69420, 42069)?);
+        assert_eq!(
+            ignores
+                .ignored_by(42069)?
+                .iter()
+                .filter(|id| **id == 69420)
+                .count(),
+            1
+        );
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(ignores_key(42069))
+            .query::<()>(&mut conn)?;
+        let mut ignores = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert!(ignores.is_ignoring(42069, 69420)?);
+
+        ignores.unignore(42069, 69420)?;
+        assert!(!ignores.is_ignoring(42069, 69420)?);
+        assert!(!Persistent::new(&persistent_conn).is_ignoring(42069, 69420)?);
+
+        Ok(())
+    }
+}
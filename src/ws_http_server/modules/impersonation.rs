@@ -0,0 +1,124 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpRequest, Scope,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{
+        super::spec::{audit::NewAuditEntry, user::Role},
+        auth::{role::Administrator, AuthError, Principal, RequireRole},
+        jwt::{Claims, IMPERSONATION_TTL},
+        server::State,
+    },
+    audit::Provider as AuditProvider,
+    roles::Provider as RolesProvider,
+    sessions::Provider as SessionsProvider,
+    throttle,
+    users::Provider as UsersProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the impersonation module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/impersonate").service(impersonate)
+}
+
+/// ImpersonationRequest represents a request made by an administrator to act
+/// as another user.
+#[derive(Deserialize)]
+pub struct ImpersonationRequest {
+    /// Why the administrator needs to act as the user, recorded in the audit
+    /// log
+    reason: String,
+}
+
+/// Impersonation represents the credentials issued to an administrator acting
+/// as another user. No refresh token is issued, so the impersonation ends
+/// once the token expires.
+#[derive(Serialize, Deserialize)]
+pub struct Impersonation {
+    /// The session token identifying the administrator as the user
+    token: String,
+
+    /// The ID of the session issued for the impersonation, with which it may
+    /// be ended early
+    session_id: String,
+
+    /// The time at which the token expires
+    expires_at: DateTime<Utc>,
+}
+
+/// Issues a short-lived token permitting the authenticated administrator to
+/// act as the specified user, for debugging problems specific to the user.
+/// Each request made with the token is recorded in the audit log under both
+/// the user and the administrator. Administrators may not be impersonated,
+/// and the holder of the administrative token, who has no account to which
+/// actions may be attributed, may not impersonate anyone.
+#[post("/{user_id}")]
+pub async fn impersonate(
+    req: HttpRequest,
+    state: Data<State>,
+    auth: RequireRole<Administrator>,
+    user_id: Path<u64>,
+    body: Json<ImpersonationRequest>,
+) -> Result<Option<Json<Impersonation>>, HttpError> {
+    let admin_id = match auth.principal() {
+        Principal::User(user) if user.impersonator().is_none() => user.id(),
+        _ => return Err(AuthError::InsufficientPermissions.into()),
+    };
+
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(ProviderError::MissingArgument { arg: "reason" }.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let roles = {
+        let mut hybrid = Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        );
+
+        if hybrid.get_user(*user_id)?.is_none() {
+            return Ok(None);
+        }
+
+        // Impersonating another administrator would grant their privileges
+        // under a different name
+        let roles = hybrid.roles_for_user(*user_id)?;
+        if roles.contains(&Role::Administrator) {
+            return Err(AuthError::InsufficientPermissions.into());
+        }
+
+        roles
+    };
+
+    // The session is visible to the user alongside their own, so that they
+    // may see when, and by whom, they were impersonated
+    let session_id = Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .issue_session(
+            *user_id,
+            Some(&format!("impersonated by user {}", admin_id)),
+            Some(&throttle::client_ip(&req, state.trusted_proxies())),
+        )?;
+
+    let claims = Claims::impersonating(*user_id, session_id.clone(), roles, admin_id);
+    let token = state.signing_keys().sign(&claims)?;
+
+    Persistent::new(&persistent_conn).record_action(
+        &NewAuditEntry::new(Some(*user_id), Some(admin_id), "impersonation.start")
+            .with_detail(reason),
+    )?;
+
+    Ok(Some(Json(Impersonation {
+        token,
+        session_id,
+        expires_at: Utc::now() + Duration::seconds(IMPERSONATION_TTL),
+    })))
+}
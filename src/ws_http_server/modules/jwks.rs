@@ -0,0 +1,25 @@
+use actix_web::{http::header::CACHE_CONTROL, web::Data, Error as HttpError, HttpResponse, Scope};
+
+use super::super::server::State;
+
+/// The number of seconds for which other services may cache the key set.
+/// Keys are only rotated on restart, so this may be fairly long.
+const MAX_AGE: u64 = 5 * 60;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the JWKS module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/.well-known").service(jwks)
+}
+
+/// Publishes the public keys with which session tokens may be verified, so
+/// that other services may authenticate gnomegg users without sharing the
+/// server's secrets.
+#[get("/jwks.json")]
+pub async fn jwks(state: Data<State>) -> Result<HttpResponse, HttpError> {
+    let jwks = state.signing_keys().jwks()?;
+
+    Ok(HttpResponse::Ok()
+        .header(CACHE_CONTROL, format!("public, max-age={}", MAX_AGE))
+        .json(jwks))
+}
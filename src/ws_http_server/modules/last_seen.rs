@@ -0,0 +1,354 @@
+use actix_web::{
+    rt,
+    web::{self, Data},
+};
+use chrono::{DateTime, Utc};
+use diesel::{Connection, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use super::{
+    super::{
+        super::spec::{backend::Unsigned, last_seen::LastSeen, schema::last_seen},
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::time::Duration;
+
+/// The interval at which cached activity is flushed to the persistent layer.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum number of users whose activity is flushed in one batch.
+const FLUSH_BATCH_SIZE: usize = 100;
+
+/// The redis set holding the IDs of each user whose cached activity has not
+/// yet been flushed to the persistent layer.
+const DIRTY_SET: &str = "last_seen_dirty";
+
+/// Periodically flushes the activity recorded in the caching layer to the
+/// persistent layer for as long as the server is running.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_flush_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(FLUSH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // A failed flush leaves the affected users marked as dirty in the
+            // cache, so they will be retried on the next tick
+            let _ = web::block(move || -> Result<usize, ProviderError> {
+                let mut conn = state.cache_connection()?;
+                let persistent_conn = state.persistent_connection()?;
+
+                Hybrid::new(
+                    Cache::new(&mut conn).with_prefix(state.key_prefix()),
+                    Persistent::new(&persistent_conn),
+                )
+                .flush_last_seen()
+            })
+            .await;
+        }
+    });
+}
+
+/// Provider represents an arbitrary backend for the last-seen service, which
+/// tracks the most recent activity of each user.
+pub trait Provider {
+    /// Records that the user with the given ID connected to the chat at the
+    /// given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who connected
+    /// * `at` - The time at which the user connected
+    fn record_connect(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError>;
+
+    /// Records that the user with the given ID sent a message at the given
+    /// time.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent a message
+    /// * `at` - The time at which the message was sent
+    fn record_message(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError>;
+
+    /// Retreives the most recent activity of the user with the given ID, if
+    /// any has been recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity should be obtained
+    fn get_last_seen(&mut self, user_id: u64) -> Result<Option<LastSeen>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Stores the given timestamp under the given field of a user's activity
+    /// hash, and marks the user as needing to be flushed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity should be updated
+    /// * `field` - The activity field that should be updated
+    /// * `at` - The time at which the activity occurred
+    fn record_activity(
+        &mut self,
+        user_id: u64,
+        field: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError> {
+        redis::pipe()
+            .atomic()
+            .cmd("HSET")
+            .arg(self.key(format_args!("last_seen::{}", user_id)))
+            .arg(field)
+            .arg(at.timestamp())
+            .ignore()
+            .cmd("SADD")
+            .arg(self.key(DIRTY_SET))
+            .arg(user_id)
+            .ignore()
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records that the user with the given ID connected to the chat at the
+    /// given time in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who connected
+    /// * `at` - The time at which the user connected
+    fn record_connect(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.record_activity(user_id, "connected_at", at)
+    }
+
+    /// Records that the user with the given ID sent a message at the given
+    /// time in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent a message
+    /// * `at` - The time at which the message was sent
+    fn record_message(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.record_activity(user_id, "messaged_at", at)
+    }
+
+    /// Retreives the most recent activity of the user with the given ID from
+    /// the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity should be obtained
+    fn get_last_seen(&mut self, user_id: u64) -> Result<Option<LastSeen>, ProviderError> {
+        let (connected_at, messaged_at) = redis::cmd("HMGET")
+            .arg(self.key(format_args!("last_seen::{}", user_id)))
+            .arg("connected_at")
+            .arg("messaged_at")
+            .query::<(Option<i64>, Option<i64>)>(self.connection)?;
+
+        if connected_at.is_none() && messaged_at.is_none() {
+            return Ok(None);
+        }
+
+        let to_time = |timestamp: i64| DateTime::from_timestamp(timestamp, 0);
+
+        Ok(Some(LastSeen::new(
+            user_id,
+            connected_at.and_then(to_time),
+            messaged_at.and_then(to_time),
+        )))
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Merges the given activity into the record stored in the MySQL
+    /// database, keeping the most recent of each timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity` - The activity that should be stored
+    pub fn store_last_seen(&mut self, activity: &LastSeen) -> Result<(), ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            let merged = match last_seen::table
+                .find(Unsigned(activity.user_id()))
+                .first::<LastSeen>(connection)
+                .optional()?
+            {
+                Some(existing) => existing.merge(activity),
+                None => activity.clone(),
+            };
+
+            diesel::replace_into(last_seen::table)
+                .values(&merged)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|e| e.into())
+        })
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Records that the user with the given ID connected to the chat at the
+    /// given time in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who connected
+    /// * `at` - The time at which the user connected
+    fn record_connect(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.store_last_seen(&LastSeen::new(user_id, Some(at), None))
+    }
+
+    /// Records that the user with the given ID sent a message at the given
+    /// time in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent a message
+    /// * `at` - The time at which the message was sent
+    fn record_message(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.store_last_seen(&LastSeen::new(user_id, None, Some(at)))
+    }
+
+    /// Retreives the most recent activity of the user with the given ID from
+    /// the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity should be obtained
+    fn get_last_seen(&mut self, user_id: u64) -> Result<Option<LastSeen>, ProviderError> {
+        last_seen::table
+            .find(Unsigned(user_id))
+            .first::<LastSeen>(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Writes the cached activity of each user marked as dirty to the
+    /// persistent layer, returning the number of users whose activity was
+    /// flushed.
+    pub fn flush_last_seen(&mut self) -> Result<usize, ProviderError> {
+        let mut flushed = 0;
+
+        loop {
+            let user_ids = redis::cmd("SPOP")
+                .arg(self.cache.key(DIRTY_SET))
+                .arg(FLUSH_BATCH_SIZE)
+                .query::<Vec<u64>>(self.cache.connection)?;
+
+            if user_ids.is_empty() {
+                return Ok(flushed);
+            }
+
+            for (i, user_id) in user_ids.iter().enumerate() {
+                let result = self.cache.get_last_seen(*user_id).and_then(|activity| {
+                    activity.map_or(Ok(()), |activity| {
+                        self.persistent.store_last_seen(&activity)
+                    })
+                });
+
+                // Users that couldn't be flushed are marked as dirty once
+                // more, so that their activity isn't lost
+                if let Err(e) = result {
+                    redis::cmd("SADD")
+                        .arg(self.cache.key(DIRTY_SET))
+                        .arg(&user_ids[i..])
+                        .query::<()>(self.cache.connection)?;
+
+                    return Err(e);
+                }
+
+                flushed += 1;
+            }
+        }
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Records that the user with the given ID connected to the chat at the
+    /// given time. Activity is only written to the caching layer, and is
+    /// periodically flushed to the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who connected
+    /// * `at` - The time at which the user connected
+    fn record_connect(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.cache.record_connect(user_id, at)
+    }
+
+    /// Records that the user with the given ID sent a message at the given
+    /// time. Activity is only written to the caching layer, and is
+    /// periodically flushed to the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent a message
+    /// * `at` - The time at which the message was sent
+    fn record_message(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.cache.record_message(user_id, at)
+    }
+
+    /// Retreives the most recent activity of the user with the given ID,
+    /// combining any activity that has yet to be flushed with the activity
+    /// stored in the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose activity should be obtained
+    fn get_last_seen(&mut self, user_id: u64) -> Result<Option<LastSeen>, ProviderError> {
+        let cached = self.cache.get_last_seen(user_id).unwrap_or(None);
+        let persisted = self.persistent.get_last_seen(user_id)?;
+
+        Ok(match (cached, persisted) {
+            (Some(cached), Some(persisted)) => Some(cached.merge(&persisted)),
+            (cached, persisted) => cached.or(persisted),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_flush() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let now = Utc::now();
+
+        let mut activity = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        activity.record_connect(42069, now)?;
+        activity.record_message(42069, now)?;
+        activity.flush_last_seen()?;
+
+        let expected = LastSeen::new(42069, Some(now), Some(now));
+        assert_eq!(activity.get_last_seen(42069)?, Some(expected.clone()));
+        assert_eq!(
+            Persistent::new(&persistent_conn).get_last_seen(42069)?,
+            Some(expected)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    HttpResponse, Scope,
+};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use super::{
+    super::{
+        super::spec::schema::link_whitelist,
+        auth::{capability::CanManageLinks, RequireCapability},
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The redis set holding each of the whitelisted domains.
+const WHITELIST_KEY: &str = "link_whitelist";
+
+/// The maximum length of a whitelisted domain.
+pub const MAX_DOMAIN_LENGTH: usize = 255;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the links module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/links")
+        .service(list_whitelist)
+        .service(whitelist_domain)
+        .service(unwhitelist_domain)
+}
+
+/// Gets each of the domains that chatters may link to.
+#[get("/whitelist")]
+pub async fn list_whitelist(state: Data<State>) -> Result<Json<Vec<String>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .whitelisted_domains()
+    .map(Json)
+}
+
+/// Permits chatters to link to the given domain, and each of its subdomains.
+/// Only administrators may manage the whitelist.
+#[put("/whitelist/{domain}")]
+pub async fn whitelist_domain(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageLinks>,
+    domain: Path<String>,
+) -> Result<HttpResponse, ProviderError> {
+    let domain = domain.to_lowercase();
+    if !is_valid_domain(&domain) {
+        return Err(ProviderError::InvalidArgument { arg: "domain" });
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .whitelist_domain(&domain)
+    .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Removes the given domain from the whitelist. Only administrators may
+/// manage the whitelist.
+#[delete("/whitelist/{domain}")]
+pub async fn unwhitelist_domain(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageLinks>,
+    domain: Path<String>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .unwhitelist_domain(&domain.to_lowercase())
+    .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Determines whether or not the given string is a valid domain. Domains
+/// must consist of at least two dot-separated labels, each of which may only
+/// contain lowercase alphanumeric characters and hyphens, and must end with
+/// an alphabetic top-level label.
+///
+/// # Arguments
+///
+/// * `domain` - The domain that should be validated
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::ws_http_server::modules::links::is_valid_domain;
+///
+/// assert!(is_valid_domain("youtube.com"));
+/// assert!(!is_valid_domain("e.g"));
+/// assert!(!is_valid_domain("3.14"));
+/// ```
+pub fn is_valid_domain(domain: &str) -> bool {
+    let labels = domain.split('.').collect::<Vec<&str>>();
+
+    domain.len() <= MAX_DOMAIN_LENGTH
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+/// Provider represents an arbitrary backend for the links service, which
+/// stores the domains that chatters may link to.
+pub trait Provider {
+    /// Gets each of the whitelisted domains.
+    fn whitelisted_domains(&mut self) -> Result<Vec<String>, ProviderError>;
+
+    /// Adds the given domain to the whitelist.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should be whitelisted
+    fn whitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError>;
+
+    /// Removes the given domain from the whitelist.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should no longer be whitelisted
+    fn unwhitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError>;
+
+    /// Determines whether or not chatters may link to the given host, which
+    /// is the case if it or any of its parent domains are whitelisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The lowercase host that should be checked
+    fn is_whitelisted(&mut self, host: &str) -> Result<bool, ProviderError> {
+        Ok(self.whitelisted_domains()?.iter().any(|domain| {
+            host == domain
+                || (host.ends_with(domain.as_str())
+                    && host[..host.len() - domain.len()].ends_with('.'))
+        }))
+    }
+}
+
+impl<'a> Cache<'a> {
+    /// Adds each of the given domains to the whitelist in the redis caching
+    /// layer at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `domains` - The domains that should be whitelisted
+    fn whitelist_domains(&mut self, domains: &[String]) -> Result<(), ProviderError> {
+        // SADD requires at least one member
+        if domains.is_empty() {
+            return Ok(());
+        }
+
+        redis::cmd("SADD")
+            .arg(self.key(WHITELIST_KEY))
+            .arg(domains)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets each of the domains whitelisted in the redis caching layer.
+    fn whitelisted_domains(&mut self) -> Result<Vec<String>, ProviderError> {
+        redis::cmd("SMEMBERS")
+            .arg(self.key(WHITELIST_KEY))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Adds the given domain to the whitelist in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should be whitelisted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, links::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut links = Cache::new(&mut conn);
+    /// links.whitelist_domain("youtube.com")?;
+    ///
+    /// assert!(links.is_whitelisted("m.youtube.com")?);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn whitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        self.whitelist_domains(&[domain.to_owned()])
+    }
+
+    /// Removes the given domain from the whitelist in the redis caching
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should no longer be whitelisted
+    fn unwhitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        redis::cmd("SREM")
+            .arg(self.key(WHITELIST_KEY))
+            .arg(domain)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets each of the domains whitelisted in the MySQL database.
+    fn whitelisted_domains(&mut self) -> Result<Vec<String>, ProviderError> {
+        link_whitelist::table
+            .select(link_whitelist::dsl::domain)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Adds the given domain to the whitelist in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should be whitelisted
+    fn whitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        diesel::replace_into(link_whitelist::table)
+            .values(link_whitelist::dsl::domain.eq(domain))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the given domain from the whitelist in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should no longer be whitelisted
+    fn unwhitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        diesel::delete(link_whitelist::table.find(domain))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets each of the whitelisted domains, populating the cache from the
+    /// persistent layer if it holds none.
+    fn whitelisted_domains(&mut self) -> Result<Vec<String>, ProviderError> {
+        match self.cache.whitelisted_domains() {
+            Ok(domains) if !domains.is_empty() => Ok(domains),
+            _ => self
+                .persistent
+                .whitelisted_domains()
+                .and_then(|domains| self.cache.whitelist_domains(&domains).map(|_| domains)),
+        }
+    }
+
+    /// Adds the given domain to the whitelist in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should be whitelisted
+    fn whitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        self.persistent
+            .whitelist_domain(domain)
+            .and_then(|_| self.cache.whitelist_domain(domain))
+    }
+
+    /// Removes the given domain from the whitelist in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain` - The domain that should no longer be whitelisted
+    fn unwhitelist_domain(&mut self, domain: &str) -> Result<(), ProviderError> {
+        self.persistent
+            .unwhitelist_domain(domain)
+            .and_then(|_| self.cache.unwhitelist_domain(domain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut links = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        links.whitelist_domain("test-gnomegg.com")?;
+
+        assert!(links.is_whitelisted("test-gnomegg.com")?);
+        assert!(links.is_whitelisted("www.test-gnomegg.com")?);
+        assert!(!links.is_whitelisted("nottest-gnomegg.com")?);
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(WHITELIST_KEY)
+            .query::<()>(&mut conn)?;
+        let mut links = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert!(links.is_whitelisted("test-gnomegg.com")?);
+
+        links.unwhitelist_domain("test-gnomegg.com")?;
+        assert!(!links.is_whitelisted("test-gnomegg.com")?);
+
+        Ok(())
+    }
+}
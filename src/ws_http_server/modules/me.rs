@@ -0,0 +1,90 @@
+use actix_web::{
+    web::{Data, Json},
+    Scope,
+};
+use serde::Serialize;
+
+use super::{
+    super::{
+        super::spec::{
+            ban::Ban,
+            mute::Mute,
+            settings::Settings,
+            user::{Role, User},
+        },
+        auth::AuthedUser,
+        server::State,
+    },
+    settings::Provider as SettingsProvider,
+    snapshot::{Provider as SnapshotProvider, UserModerationState},
+    subscriptions::Provider as SubscriptionsProvider,
+    users::Provider as UsersProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the me module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/me").service(me)
+}
+
+/// Me represents everything that a client must know about the authenticated
+/// user once they log in.
+#[derive(Serialize)]
+pub struct Me {
+    /// The profile of the user
+    #[serde(flatten)]
+    user: User,
+
+    /// The roles held by the user
+    roles: Vec<Role>,
+
+    /// The tier of the user's active subscription, if any
+    tier: Option<u8>,
+
+    /// The ban currently in effect against the user, if any
+    ban: Option<Ban>,
+
+    /// The mute currently in effect against the user, if any
+    mute: Option<Mute>,
+
+    /// The chat preferences of the user
+    settings: Settings,
+}
+
+/// Gets the profile, roles, subscription tier, active sanctions and settings
+/// of the authenticated user in a single response. Responds with 404 Not
+/// Found if the user's account no longer exists.
+#[get("")]
+pub async fn me(state: Data<State>, user: AuthedUser) -> Result<Option<Json<Me>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()));
+
+    let profile = match hybrid.get_user(user.id())? {
+        Some(profile) => profile,
+        None => return Ok(None),
+    };
+
+    // The user's roles and sanctions are gathered in a single round trip
+    let moderation = hybrid
+        .moderation_states(&[user.id()])?
+        .pop()
+        .unwrap_or_else(|| UserModerationState::new(user.id()));
+
+    Ok(Some(Json(Me {
+        user: profile,
+        roles: moderation.roles().to_vec(),
+        tier: hybrid.active_tier(user.id())?,
+        ban: moderation.ban().filter(|ban| ban.active()).cloned(),
+        mute: moderation.mute().filter(|mute| mute.active()).cloned(),
+        settings: hybrid.get_settings(user.id())?.unwrap_or_default(),
+    })))
+}
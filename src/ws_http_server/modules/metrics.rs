@@ -0,0 +1,86 @@
+use actix_web::{
+    web::{Data, Json},
+    Scope,
+};
+use serde::Serialize;
+
+use super::super::{
+    auth::{role::Administrator, RequireRole},
+    breaker::BreakerMetrics,
+    lookups::LookupMetrics,
+    pool::PoolMetrics,
+    server::State,
+    shedding::SheddingMetrics,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the metrics module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/metrics")
+        .service(pool_metrics)
+        .service(breaker_metrics)
+        .service(cache_metrics)
+        .service(shedding_metrics)
+}
+
+/// PoolUsage represents a snapshot of the usage of each of the server's
+/// connection pools.
+#[derive(Serialize)]
+pub struct PoolUsage {
+    /// The usage of the pool of connections to the redis caching layer
+    cache: PoolMetrics,
+
+    /// The usage of the pool of connections to the MySQL persistence layer
+    persistent: PoolMetrics,
+
+    /// The usage of the pool of connections to the MySQL read replica, if
+    /// any
+    replica: Option<PoolMetrics>,
+}
+
+/// Gets a snapshot of the usage of the redis and MySQL connection pools, so
+/// that they may be sized appropriately. Only administrators may view pool
+/// metrics.
+#[get("/pool")]
+pub async fn pool_metrics(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+) -> Json<PoolUsage> {
+    Json(PoolUsage {
+        cache: state.cache_pool_metrics(),
+        persistent: state.persistent_pool_metrics(),
+        replica: state.replica_pool_metrics(),
+    })
+}
+
+/// Gets a snapshot of the state of the circuit breaker guarding the MySQL
+/// persistence layer. Only administrators may view breaker metrics.
+#[get("/breaker")]
+pub async fn breaker_metrics(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+) -> Json<BreakerMetrics> {
+    Json(state.breaker_metrics())
+}
+
+/// Gets the number of hits, misses, and errors of the reads sent to the
+/// redis caching layer by each module, such that a failing cache can be told
+/// apart from a cold one. Only administrators may view cache metrics.
+#[get("/cache")]
+pub async fn cache_metrics(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+) -> Json<LookupMetrics> {
+    Json(state.lookup_metrics())
+}
+
+/// Gets a snapshot of the state of the node's load shedder, including the
+/// event loop lag and backend latency that it acts upon. Only administrators
+/// may view shedding metrics.
+#[get("/shedding")]
+pub async fn shedding_metrics(
+    state: Data<State>,
+    _auth: RequireRole<Administrator>,
+) -> Json<SheddingMetrics> {
+    Json(state.shedding_metrics())
+}
@@ -1,14 +1,75 @@
-use diesel::{mysql::MysqlConnection, result::Error as DieselError};
-use redis::{Connection, RedisError};
+use actix_web::{http::StatusCode, ResponseError};
+use diesel::{r2d2::PoolError, result::Error as DieselError, ConnectionError};
+use redis::{Connection, FromRedisValue, Pipeline, RedisError, Script, ScriptInvocation};
 use serde_json::Error as SerdeError;
 
-use std::{error::Error, fmt};
+use super::{
+    super::spec::{backend::PersistentConnection, ban::Ban, mute::Mute, user::Role},
+    breaker::{self, CircuitBreaker},
+    invalidation::{Invalidation, INVALIDATION_CHANNEL},
+    keyring::{Keyring, KeyringError},
+    lookups::{CacheLookups, Lookup},
+    outbox::{Outbox, PendingWrite},
+    problem::ErrorCode,
+};
 
+use std::{collections::HashMap, error::Error, fmt, str::FromStr, sync::Arc};
+
+pub mod admin;
+pub mod announcements;
+pub mod approvals;
+pub mod audit;
+pub mod avatars;
 pub mod bans;
+pub mod bot_commands;
+pub mod chat;
+pub mod chat_modes;
+pub mod combos;
+pub mod connections;
+pub mod donations;
+pub mod embeds;
+pub mod emotes;
+pub mod events;
+pub mod export;
+pub mod flairs;
+#[cfg(feature = "frontend")]
+pub mod frontend;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod health;
+pub mod history;
+pub mod ignores;
+pub mod impersonation;
+pub mod jwks;
+pub mod last_seen;
+pub mod links;
+pub mod me;
+pub mod metrics;
 pub mod mutes;
 pub mod name_resolver;
 pub mod oauth;
+pub mod oauth_state;
+pub mod pins;
+pub mod poll;
+pub mod polls;
+pub mod profiles;
+pub mod refresh_tokens;
 pub mod roles;
+pub mod sessions;
+pub mod settings;
+pub mod snapshot;
+pub mod spam;
+pub mod stats;
+pub mod stream;
+pub mod subscriptions;
+pub mod throttle;
+pub mod two_factor;
+pub mod users;
+pub mod version;
+pub mod whispers;
+
+/// The number of keys that redis is asked to examine in each step of a scan.
+const SCAN_COUNT: usize = 500;
 
 /// ProviderError represents any error emitted by a ban backend.
 #[derive(Debug)]
@@ -16,7 +77,25 @@ pub enum ProviderError {
     RedisError(RedisError),
     SerdeError(SerdeError),
     DieselError(DieselError),
-    MissingArgument { arg: &'static str },
+    ConnectionError(ConnectionError),
+    PoolError(PoolError),
+    KeyringError(KeyringError),
+    MissingArgument {
+        arg: &'static str,
+    },
+    InvalidArgument {
+        arg: &'static str,
+    },
+
+    /// The persistent layer couldn't be reached, such that no connection to
+    /// it could be obtained
+    Unavailable,
+
+    /// Both layers of a hybrid provider failed, each with the given error
+    Composite {
+        cache: Box<ProviderError>,
+        persistent: Box<ProviderError>,
+    },
 }
 
 impl fmt::Display for ProviderError {
@@ -29,9 +108,33 @@ impl fmt::Display for ProviderError {
             Self::DieselError(err) => {
                 write!(f, "the provider encountered a database error: {}", err)
             }
+            Self::ConnectionError(err) => write!(
+                f,
+                "the provider was unable to connect to the database: {}",
+                err
+            ),
+            Self::PoolError(err) => write!(
+                f,
+                "the provider was unable to check out a pooled database connection: {}",
+                err
+            ),
+            Self::KeyringError(err) => write!(
+                f,
+                "the provider was unable to encrypt or decrypt a value: {}",
+                err
+            ),
             Self::MissingArgument { arg } => {
                 write!(f, "malformed query; missing argument: {}", arg)
             }
+            Self::InvalidArgument { arg } => {
+                write!(f, "malformed query; invalid argument: {}", arg)
+            }
+            Self::Unavailable => write!(f, "the persistent layer is unavailable"),
+            Self::Composite { cache, persistent } => write!(
+                f,
+                "the caching layer failed ({}), and the persistent layer failed ({})",
+                cache, persistent
+            ),
         }
     }
 }
@@ -42,6 +145,13 @@ impl Error for ProviderError {
             Self::RedisError(e) => Some(e),
             Self::SerdeError(e) => Some(e),
             Self::DieselError(e) => Some(e),
+            Self::ConnectionError(e) => Some(e),
+            Self::PoolError(e) => Some(e),
+            Self::KeyringError(e) => Some(e),
+
+            // The persistent layer is authoritative, so its error is
+            // considered the underlying cause
+            Self::Composite { persistent, .. } => Some(persistent.as_ref()),
             _ => None,
         }
     }
@@ -80,10 +190,92 @@ impl From<DieselError> for ProviderError {
     }
 }
 
+impl From<ConnectionError> for ProviderError {
+    /// Constructs a provider error from the given diesel connection error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The connection error that should be wrapped in the
+    /// ProviderError
+    fn from(e: ConnectionError) -> Self {
+        Self::ConnectionError(e)
+    }
+}
+
+impl From<PoolError> for ProviderError {
+    /// Constructs a provider error from the given connection pool error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The pool error that should be wrapped in the ProviderError
+    fn from(e: PoolError) -> Self {
+        Self::PoolError(e)
+    }
+}
+
+impl From<KeyringError> for ProviderError {
+    /// Constructs a provider error from the given keyring error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The keyring error that should be wrapped in the ProviderError
+    fn from(e: KeyringError) -> Self {
+        Self::KeyringError(e)
+    }
+}
+
+impl ResponseError for ProviderError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::MissingArgument { .. } | Self::InvalidArgument { .. } => StatusCode::BAD_REQUEST,
+            Self::DieselError(DieselError::NotFound) => StatusCode::NOT_FOUND,
+            Self::Unavailable | Self::ConnectionError(_) | Self::PoolError(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Composite { cache, persistent } => {
+                cache.status_code().max(persistent.status_code())
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ErrorCode for ProviderError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::RedisError(_) => "cache_error",
+            Self::SerdeError(_) => "serialization_error",
+            Self::DieselError(DieselError::NotFound) => "not_found",
+            Self::DieselError(_) => "database_error",
+            Self::ConnectionError(_) | Self::PoolError(_) | Self::Unavailable => "unavailable",
+            Self::KeyringError(_) => "encryption_error",
+            Self::MissingArgument { .. } => "missing_argument",
+            Self::InvalidArgument { .. } => "invalid_argument",
+
+            // The code of the failure determining the status is given
+            Self::Composite { cache, persistent } => {
+                if cache.status_code() >= persistent.status_code() {
+                    cache.code()
+                } else {
+                    persistent.code()
+                }
+            }
+        }
+    }
+}
+
 /// Cache is a connection helper to a redis database running remotely or
 /// locally.
 pub struct Cache<'a> {
     connection: &'a mut Connection,
+
+    /// The prefix prepended to each key and channel used by the cache, such
+    /// that several deployments may share one redis instance
+    prefix: &'a str,
+
+    /// The keyring from which the keys of hashed sensitive values are
+    /// derived, if any
+    keyring: Option<&'a Keyring>,
 }
 
 impl<'a> Cache<'a> {
@@ -94,41 +286,612 @@ impl<'a> Cache<'a> {
     /// * `database_address` - The address corresponding to the remote redis
     /// session, formatted as such: 127.0.0.1:6379
     pub fn new(connection: &'a mut Connection) -> Self {
-        Self { connection }
+        Self {
+            connection,
+            prefix: "",
+            keyring: None,
+        }
+    }
+
+    /// Consumes the cache, and modifies it according to the provided key
+    /// prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix that should be prepended to each key and
+    /// channel used by the cache (e.g. "staging::")
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::Cache;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    ///
+    /// let cache = Cache::new(&mut conn).with_prefix("staging::");
+    /// assert_eq!(cache.key("muted::1"), "staging::muted::1");
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn with_prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = prefix;
+
+        self
+    }
+
+    /// Consumes the cache, and modifies it such that sensitive values are
+    /// only cached under keys hashed with a key derived from the given
+    /// keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyring` - The keyring from which the hashing key should be
+    /// derived, if any
+    pub fn with_keyring(mut self, keyring: Option<&'a Keyring>) -> Self {
+        self.keyring = keyring;
+
+        self
+    }
+
+    /// Namespaces the given key or channel according to the cache's prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key or channel that should be namespaced
+    pub fn key(&self, key: impl fmt::Display) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Notifies each node that the values concerned by the given
+    /// invalidation changed, such that they discard their local copies.
+    ///
+    /// # Arguments
+    ///
+    /// * `invalidation` - The change that was made to the values
+    pub fn publish_invalidation(
+        &mut self,
+        invalidation: &Invalidation,
+    ) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(INVALIDATION_CHANNEL))
+            .arg(serde_json::to_string(invalidation)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Sends each of the commands queued by the given closure to redis in a
+    /// single round trip, yielding the results of the commands that weren't
+    /// ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - Queues the commands that should be sent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::Cache;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    ///
+    /// let (old,): (Option<String>,) = Cache::new(&mut conn).pipeline(|pipe| {
+    ///     pipe.cmd("SET").arg("pipelined").arg("a").ignore()
+    ///         .cmd("GETSET").arg("pipelined").arg("b");
+    /// })?;
+    /// assert_eq!(old.as_deref(), Some("a"));
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn pipeline<T: FromRedisValue>(
+        &mut self,
+        queue: impl FnOnce(&mut Pipeline),
+    ) -> Result<T, ProviderError> {
+        let mut pipe = redis::pipe();
+        queue(&mut pipe);
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+
+    /// Sends each of the commands queued by the given closure to redis in a
+    /// single round trip, and runs them atomically (i.e. in a MULTI / EXEC
+    /// block), such that no other client observes only some of the commands'
+    /// effects.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - Queues the commands that should be run
+    pub fn transaction<T: FromRedisValue>(
+        &mut self,
+        queue: impl FnOnce(&mut Pipeline),
+    ) -> Result<T, ProviderError> {
+        self.pipeline(|pipe| {
+            pipe.atomic();
+            queue(pipe);
+        })
+    }
+
+    /// Runs the given Lua script in redis. Scripts run atomically, so they
+    /// may read a value and write others depending on it without another
+    /// client modifying the value in between. The script is sent by its hash
+    /// where possible, and loaded into redis otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source code of the script
+    /// * `args` - Provides the keys and arguments of the script
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::Cache;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    ///
+    /// let old: Option<String> = Cache::new(&mut conn).eval(
+    ///     "local old = redis.call('GET', KEYS[1]) redis.call('SET', KEYS[1], ARGV[1]) return old",
+    ///     |script| {
+    ///         script.key("scripted").arg("a");
+    ///     },
+    /// )?;
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn eval<T: FromRedisValue>(
+        &mut self,
+        source: &str,
+        args: impl FnOnce(&mut ScriptInvocation),
+    ) -> Result<T, ProviderError> {
+        let script = Script::new(source);
+        let mut invocation = script.prepare_invoke();
+        args(&mut invocation);
+
+        invocation.invoke(self.connection).map_err(|e| e.into())
+    }
+
+    /// Finds each of the keys matching the given glob-style pattern, which is
+    /// namespaced according to the cache's prefix. Keys are found
+    /// incrementally (i.e. with SCAN), such that redis isn't blocked while
+    /// they are found.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern that the keys should match (e.g. "muted::*")
+    pub fn scan(&mut self, pattern: &str) -> Result<Vec<String>, ProviderError> {
+        let pattern = self.key(pattern);
+        let (mut cursor, mut keys) = (0, Vec::new());
+
+        loop {
+            let (next, found): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query(self.connection)?;
+            keys.extend(found);
+
+            if next == 0 {
+                return Ok(keys);
+            }
+
+            cursor = next;
+        }
     }
 }
 
-/// Persistent is a mysql-based persistence layer for the gnomegg bans backend.
+/// Persistent is a mysql-based persistence layer for the gnomegg bans backend,
+/// or an SQLite-based one if the crate is built with the `sqlite` feature.
 pub struct Persistent<'a> {
-    connection: &'a MysqlConnection,
+    connection: &'a PersistentConnection,
+
+    /// A connection to a read replica of the MySQL database, if any, to which
+    /// frequent reads are routed
+    replica: Option<&'a PersistentConnection>,
+
+    /// The keyring with which sensitive values are encrypted at rest, if any
+    keyring: Option<&'a Keyring>,
 }
 
 impl<'a> Persistent<'a> {
     /// Creates a new connection to the mysql backend, and provides
-    pub fn new(connection: &'a MysqlConnection) -> Self {
-        Self { connection }
+    pub fn new(connection: &'a PersistentConnection) -> Self {
+        Self {
+            connection,
+            replica: None,
+            keyring: None,
+        }
+    }
+
+    /// Consumes the persistence helper, and modifies it such that frequent
+    /// reads (i.e. bans, roles and usernames) are routed to the given read
+    /// replica. Mutations, and reads made while mutating, are always sent to
+    /// the primary database.
+    ///
+    /// # Arguments
+    ///
+    /// * `replica` - A connection to a read replica of the MySQL database, if
+    /// any
+    pub fn with_replica(mut self, replica: Option<&'a PersistentConnection>) -> Self {
+        self.replica = replica;
+
+        self
+    }
+
+    /// Gets the connection to which frequent reads should be routed: the read
+    /// replica if one was provided, or the primary database otherwise.
+    fn reader(&self) -> &'a PersistentConnection {
+        self.replica.unwrap_or(self.connection)
+    }
+
+    /// Consumes the persistence helper, and modifies it such that sensitive
+    /// values are encrypted with the given keyring before being stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyring` - The keyring with which sensitive values should be
+    /// encrypted, if any
+    pub fn with_keyring(mut self, keyring: Option<&'a Keyring>) -> Self {
+        self.keyring = keyring;
+
+        self
+    }
+
+    /// Encrypts the given sensitive value if the helper holds a keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value that should be stored
+    fn seal(&self, value: &str) -> Result<String, ProviderError> {
+        self.keyring
+            .map_or(Ok(value.to_owned()), |keyring| keyring.seal(value))
+            .map_err(|e| e.into())
+    }
+
+    /// Decrypts the given stored value if it was encrypted.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The stored value
+    fn open(&self, value: &str) -> Result<String, ProviderError> {
+        match self.keyring {
+            Some(keyring) => keyring.open(value).map_err(|e| e.into()),
+            None if Keyring::is_sealed(value) => {
+                Err(ProviderError::KeyringError(KeyringError::UnknownKey))
+            }
+            None => Ok(value.to_owned()),
+        }
     }
 }
 
-/// Hybrid implements a provider utilizing both persistent and cached name
-/// resolution.
-pub struct Hybrid<'a> {
-    /// The cached name storage layer
-    cache: Cache<'a>,
+/// Memory is a backend holding its state in memory, which is lost once the
+/// backend is dropped. Memory backends don't depend on redis or MySQL, and
+/// may therefore be used in development, or to test handler logic.
+#[derive(Default, Debug)]
+pub struct Memory {
+    /// The most recent ban issued against each user, keyed by their IDs
+    bans: HashMap<u64, Ban>,
 
-    /// The persistent name storage layer
-    persistent: Persistent<'a>,
+    /// The most recent ban issued against each IP address
+    banned_addrs: HashMap<String, Ban>,
+
+    /// The most recent mute issued against each user, keyed by their IDs
+    mutes: HashMap<u64, Mute>,
+
+    /// The roles held by each user, keyed by their IDs
+    roles: HashMap<u64, Vec<Role>>,
+
+    /// The ID of the user holding each username
+    user_ids: HashMap<String, u64>,
+
+    /// The username held by each user, keyed by their IDs
+    usernames: HashMap<u64, String>,
+
+    /// The usernames previously held by each user, most recent first
+    username_history: HashMap<u64, Vec<String>>,
 }
 
-impl<'a> Hybrid<'a> {
-    /// Creates a new hybrid name resolution service with the provided
-    /// persistent and cached helper layers.
+impl Memory {
+    /// Creates a new, empty in-memory backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Hybrid implements a provider utilizing both a caching and a persistent
+/// layer. Hybrid is generic over the two layers, such that any pair of
+/// implementations of a module's provider trait (e.g. in-memory, or pooled
+/// backends) may be composed. Modules whose hybrid providers rely on helpers
+/// specific to the redis and MySQL layers only implement their providers for
+/// `Hybrid<Cache, Persistent>`.
+///
+/// Hybrid providers given an outbox degrade to the caching layer while the
+/// persistent layer is down: moderation writes that can't be persisted
+/// because the persistent layer is unreachable (or its breaker is open) are
+/// applied to the cache and queued in the outbox, to be replayed once the
+/// persistent layer returns. Writes made while older writes are still queued
+/// are queued behind them, such that writes are persisted in order. Writes
+/// rejected by a reachable persistent layer are never queued.
+pub struct Hybrid<C, P> {
+    /// The caching layer
+    cache: C,
+
+    /// The persistent storage layer
+    persistent: P,
+
+    /// (optional) The breaker keeping reads from reaching the persistent
+    /// layer while it is failing, shared by every hybrid provider
+    breaker: Option<Arc<CircuitBreaker>>,
+
+    /// (optional) The outbox holding the writes that couldn't be persisted
+    /// while the persistent layer was down
+    outbox: Option<Arc<dyn Outbox>>,
+
+    /// (optional) The counts of the hits, misses, and errors of the reads
+    /// sent to the caching layer, shared by every hybrid provider
+    lookups: Option<Arc<CacheLookups>>,
+}
+
+impl<C, P> Hybrid<C, P> {
+    /// Creates a new hybrid provider with the provided persistent and cached
+    /// helper layers.
     ///
     /// # Arguments
     ///
-    /// * `cache` - The redis caching helper to use
-    /// * `persistent` - The MySQL storage helper to use
-    pub fn new(cache: Cache<'a>, persistent: Persistent<'a>) -> Self {
-        Self { cache, persistent }
+    /// * `cache` - The caching layer to use (e.g. a redis caching helper)
+    /// * `persistent` - The persistent storage layer to use (e.g. a MySQL
+    /// storage helper)
+    pub fn new(cache: C, persistent: P) -> Self {
+        Self {
+            cache,
+            persistent,
+            breaker: None,
+            outbox: None,
+            lookups: None,
+        }
+    }
+
+    /// Consumes the hybrid provider, and modifies it according to the
+    /// provided circuit breaker. Without a breaker, reads missing the cache
+    /// always reach the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `breaker` - (optional) The breaker that should keep reads from
+    /// reaching the persistent layer while it is failing
+    pub fn with_breaker(mut self, breaker: Option<Arc<CircuitBreaker>>) -> Self {
+        self.breaker = breaker;
+
+        self
+    }
+
+    /// Consumes the hybrid provider, and modifies it according to the
+    /// provided outbox. Without an outbox, writes fail while the persistent
+    /// layer is down.
+    ///
+    /// # Arguments
+    ///
+    /// * `outbox` - (optional) The outbox that writes which can't be
+    /// persisted should be queued in
+    pub fn with_outbox(mut self, outbox: Option<Arc<dyn Outbox>>) -> Self {
+        self.outbox = outbox;
+
+        self
+    }
+
+    /// Consumes the hybrid provider, and modifies it according to the
+    /// provided lookup counts. Without lookup counts, the outcomes of reads
+    /// sent to the caching layer aren't recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookups` - (optional) The counts that the hits, misses, and errors
+    /// of reads sent to the caching layer should be recorded in
+    pub fn with_lookups(mut self, lookups: Option<Arc<CacheLookups>>) -> Self {
+        self.lookups = lookups;
+
+        self
+    }
+
+    /// Sends the given request to the persistent layer and records its
+    /// result, unless the provider's breaker is open, in which case `None`
+    /// is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request that should be sent to the persistent layer
+    fn guard<T>(
+        &mut self,
+        request: impl FnOnce(&mut Self) -> Result<T, ProviderError>,
+    ) -> Option<Result<T, ProviderError>> {
+        let breaker = self.breaker.clone();
+        if !breaker.as_ref().is_none_or(|breaker| breaker.allow()) {
+            tracing::warn!("circuit breaker is open, skipping the persistent layer");
+
+            return None;
+        }
+
+        let result = request(self);
+        if let Some(breaker) = breaker {
+            breaker.record(&result);
+        }
+
+        Some(result)
+    }
+
+    /// Records the outcome of a read sent to the caching layer by the given
+    /// module, if the provider was given lookup counts. Reads that the
+    /// caching layer failed to answer are counted as errors, rather than as
+    /// misses.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module that sent the read (e.g. "bans")
+    /// * `cached` - The result obtained from the caching layer
+    fn record_lookup<T: Lookup>(&self, module: &'static str, cached: &Result<T, ProviderError>) {
+        if let Some(lookups) = &self.lookups {
+            match cached {
+                Ok(value) => lookups.record_hit(module, value),
+                Err(_) => lookups.record_error(module),
+            }
+        }
+    }
+
+    /// Falls back to the persistent layer if the caching layer failed,
+    /// unless the provider's breaker is open, in which case the caching
+    /// layer's error is returned. If both layers fail, both errors are
+    /// reported. The outcome of the read sent to the caching layer is
+    /// recorded under the given module.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module that sent the read (e.g. "bans")
+    /// * `cached` - The result obtained from the caching layer
+    /// * `persistent` - Obtains the result from the persistent layer
+    fn fall_back<T: Lookup>(
+        &mut self,
+        module: &'static str,
+        cached: Result<T, ProviderError>,
+        persistent: impl FnOnce(&mut Self) -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        self.record_lookup(module, &cached);
+
+        let cache = match cached {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        tracing::warn!(
+            module,
+            error = %cache,
+            "caching layer failed, falling back to the persistent layer"
+        );
+
+        match self.guard(persistent) {
+            Some(result) => result.map_err(|persistent| ProviderError::Composite {
+                cache: Box::new(cache),
+                persistent: Box::new(persistent),
+            }),
+            None => Err(cache),
+        }
+    }
+
+    /// Applies the given write to the persistent layer, or queues the given
+    /// pending write in the provider's outbox if the persistent layer is
+    /// down, in which case `None` is returned. Writes are never queued
+    /// without an outbox, or without a pending write describing them (e.g.
+    /// because they weren't applied to the caching layer).
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - (optional) The write that should be queued if it can't
+    /// be persisted
+    /// * `write` - Applies the write to the persistent layer
+    pub(crate) fn persist<T>(
+        &mut self,
+        pending: Option<PendingWrite>,
+        write: impl FnOnce(&mut P) -> Result<T, ProviderError>,
+    ) -> Result<Option<T>, ProviderError> {
+        let (outbox, pending) = match (self.outbox.clone(), pending) {
+            (Some(outbox), Some(pending)) => (outbox, pending),
+            _ => return write(&mut self.persistent).map(Some),
+        };
+
+        // Writes queued earlier must be persisted first, lest they override
+        // this write once replayed
+        if outbox.pending().is_ok_and(|pending| pending > 0) {
+            return outbox.enqueue(&pending).map(|_| None);
+        }
+
+        match self.guard(|hybrid| write(&mut hybrid.persistent)) {
+            None => outbox.enqueue(&pending).map(|_| None),
+            Some(Err(e)) if breaker::indicates_outage(&e) => {
+                tracing::warn!(error = %e, "persistent layer is down, queueing the write");
+
+                outbox.enqueue(&pending).map(|_| None).map_err(|_| e)
+            }
+            Some(result) => result.map(Some),
+        }
+    }
+
+    /// Combines the result of writing to the caching layer with the result
+    /// of persisting the write, or queueing it if the persistent layer is
+    /// down, in which case the caching layer's result is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `cached` - The result of writing to the caching layer
+    /// * `pending` - The write that should be queued if it can't be
+    /// persisted
+    /// * `write` - Applies the write to the persistent layer
+    pub(crate) fn write_through<T>(
+        &mut self,
+        cached: Result<T, ProviderError>,
+        pending: PendingWrite,
+        write: impl FnOnce(&mut P) -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let pending = Some(pending).filter(|_| cached.is_ok());
+
+        match self.persist(pending, write) {
+            Ok(None) => cached,
+            Ok(Some(persisted)) => write_both(cached, Ok(persisted)),
+            Err(e) => write_both(cached, Err(e)),
+        }
+    }
+}
+
+/// Combines the results of writing to both layers of a hybrid provider,
+/// yielding the persistent layer's result if both writes succeeded. If both
+/// layers fail, both errors are reported.
+///
+/// # Arguments
+///
+/// * `cached` - The result of writing to the caching layer
+/// * `persisted` - The result of writing to the persistent layer
+pub(crate) fn write_both<T, U>(
+    cached: Result<T, ProviderError>,
+    persisted: Result<U, ProviderError>,
+) -> Result<U, ProviderError> {
+    match (cached, persisted) {
+        (Err(cache), Err(persistent)) => Err(ProviderError::Composite {
+            cache: Box::new(cache),
+            persistent: Box::new(persistent),
+        }),
+        (cached, persisted) => cached.and(persisted),
+    }
+}
+
+/// Layers represents the storage layers backing a provider selected at
+/// runtime (e.g. through the server's configuration), rather than at compile
+/// time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Layers {
+    /// Only the redis caching layer is used. State is lost once it is evicted
+    /// from the cache.
+    Cache,
+
+    /// Only the MySQL persistence layer is used
+    Persistent,
+
+    /// The caching layer is consulted before the persistence layer, and
+    /// writes are applied to both
+    #[default]
+    Hybrid,
+}
+
+impl FromStr for Layers {
+    type Err = ProviderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cache" => Ok(Self::Cache),
+            "persistent" => Ok(Self::Persistent),
+            "hybrid" => Ok(Self::Hybrid),
+            _ => Err(ProviderError::InvalidArgument { arg: "layers" }),
+        }
     }
 }
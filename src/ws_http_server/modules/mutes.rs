@@ -1,11 +1,285 @@
-use diesel::{result::Error as DieselError, QueryDsl, RunQueryDsl};
-use redis::RedisError;
+use actix_web::{
+    web::{Data, Json, Path, Query},
+    HttpResponse, Scope,
+};
+use diesel::{
+    dsl::sql, result::Error as DieselError, sql_types::Bool, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
+use serde::Deserialize;
+use tracing::instrument;
 
 use super::{
-    super::super::spec::{mute::Mute, schema::mutes},
-    Cache, Hybrid, Persistent, ProviderError,
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            event::{Event, EventKind, EventTarget, Mute as MuteEvent, Unmute},
+            mute::Mute,
+            schema::mutes,
+        },
+        auth::{capability::CanMute, Principal, RequireCapability},
+        breaker,
+        invalidation::Invalidation,
+        outbox::{PendingWrite, Unavailable},
+        pagination::{Page, Pagination, Sort},
+        server::State,
+    },
+    audit,
+    bans::{publish_moderation, username_for},
+    write_both, Cache, Hybrid, Layers, Memory, Persistent, ProviderError,
 };
 
+use std::collections::HashMap;
+
+/// The number of mutes returned by the mute list route if no limit is
+/// specified.
+const DEFAULT_PAGE_LENGTH: usize = 50;
+
+/// The maximum number of mutes that may be returned by the mute list route.
+const MAX_PAGE_LENGTH: usize = 200;
+
+/// The condition satisfied by the mutes that haven't lapsed. Durations are
+/// stored in nanoseconds, and converted to the fractional days counted by
+/// julianday.
+const UNLAPSED: &str = "julianday(initiated_at) + duration / 86400000000000.0 > julianday('now')";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the mutes module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/mutes")
+        .service(list_mutes)
+        .service(user_mute)
+        .service(create_mute)
+        .service(lift_mute)
+}
+
+/// MuteRequest represents a request to mute a user.
+#[derive(Deserialize)]
+pub struct MuteRequest {
+    /// The ID of the user who should be muted
+    user_id: u64,
+
+    /// The number of nanoseconds that the mute should be active for
+    duration: u64,
+
+    /// (optional) Why the user is being muted
+    reason: Option<String>,
+}
+
+/// MutesQuery represents the filters accepted by the mute list route,
+/// alongside its pagination parameters.
+#[derive(Deserialize)]
+pub struct MutesQuery {
+    /// (optional) Whether or not mutes that have lapsed, but haven't been
+    /// lifted, should be returned
+    #[serde(default)]
+    include_lapsed: bool,
+}
+
+/// Gets a page of mutes, ordered by the IDs of the muted users, which are
+/// used as cursors. Only users permitted to mute may view mutes.
+#[get("")]
+pub async fn list_mutes(
+    state: Data<State>,
+    _auth: RequireCapability<CanMute>,
+    pagination: Pagination,
+    query: Query<MutesQuery>,
+) -> Result<Json<Page<Mute>>, ProviderError> {
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+    let limit = pagination.limit(DEFAULT_PAGE_LENGTH, MAX_PAGE_LENGTH);
+
+    Persistent::new(&persistent_conn)
+        .with_replica(replica_conn.as_deref())
+        .mutes(
+            pagination.cursor()?,
+            pagination.sort(Sort::Asc),
+            query.include_lapsed,
+            limit,
+        )
+        .map(|mutes| Json(Page::new(mutes, limit, Mute::concerns)))
+}
+
+/// Gets the active mute issued against the specified user. Responds with 404
+/// Not Found if the user isn't muted. Only users permitted to mute may view
+/// mutes.
+#[get("/{user_id}")]
+pub async fn user_mute(
+    state: Data<State>,
+    _auth: RequireCapability<CanMute>,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let mute = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()))
+    .get_mute(*user_id)?;
+
+    // Lapsed mutes remain persisted until they are lifted
+    Ok(match mute.filter(Mute::active) {
+        Some(mute) => HttpResponse::Ok().json(mute),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+/// Mutes the specified user for the given duration, notifying every
+/// connected chatter. Muting a user who is already muted replaces their
+/// mute. Responds with 404 Not Found if the user doesn't exist. Only users
+/// permitted to mute may mute other users.
+#[post("")]
+pub async fn create_mute(
+    state: Data<State>,
+    auth: RequireCapability<CanMute>,
+    body: Json<MuteRequest>,
+) -> Result<HttpResponse, ProviderError> {
+    if body.duration == 0 {
+        return Err(ProviderError::InvalidArgument { arg: "duration" });
+    }
+
+    let username = match username_for(&state, body.user_id)? {
+        Some(username) => username,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    mute_user(
+        &state,
+        auth.principal(),
+        body.user_id,
+        &username,
+        body.duration,
+        body.reason.as_deref().unwrap_or_default(),
+    )
+    .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Lifts the mute issued against the specified user, notifying every
+/// connected chatter. Responds with 404 Not Found if the user isn't muted.
+/// Only users permitted to mute may lift mutes.
+#[delete("/{user_id}")]
+pub async fn lift_mute(
+    state: Data<State>,
+    auth: RequireCapability<CanMute>,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let username = match username_for(&state, *user_id)? {
+        Some(username) => username,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    Ok(
+        if lift_user_mute(&state, auth.principal(), *user_id, &username)? {
+            HttpResponse::NoContent().finish()
+        } else {
+            HttpResponse::NotFound().finish()
+        },
+    )
+}
+
+/// Mutes the given user for the given nonzero duration, notifying every
+/// connected chatter, and records the mute in the audit log.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `principal` - The party issuing the mute
+/// * `user_id` - The ID of the user who should be muted
+/// * `username` - The username of the user who should be muted
+/// * `duration` - The number of nanoseconds that the mute should be active
+/// for
+/// * `reason` - Why the user is being muted
+pub(crate) fn mute_user(
+    state: &State,
+    principal: &Principal,
+    user_id: u64,
+    username: &str,
+    duration: u64,
+    reason: &str,
+) -> Result<(), ProviderError> {
+    write_mutes(state, |mutes| {
+        mutes.set_muted(user_id, true, Some(duration))
+    })?;
+
+    publish_moderation(
+        state,
+        &Event::new(
+            EventTarget::All,
+            EventKind::Muted(MuteEvent::new(username, duration)),
+        ),
+    )?;
+    audit::record_moderation_action(
+        state,
+        principal,
+        "mute.create",
+        &format!("user {}: {}", user_id, reason),
+    );
+
+    Ok(())
+}
+
+/// Lifts the mute issued against the given user, notifying every connected
+/// chatter, and records the lifted mute in the audit log. Returns whether or
+/// not the user was muted.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `principal` - The party lifting the mute
+/// * `user_id` - The ID of the user whose mute should be lifted
+/// * `username` - The username of the user whose mute should be lifted
+pub(crate) fn lift_user_mute(
+    state: &State,
+    principal: &Principal,
+    user_id: u64,
+    username: &str,
+) -> Result<bool, ProviderError> {
+    if !write_mutes(state, |mutes| mutes.set_muted(user_id, false, None))? {
+        return Ok(false);
+    }
+
+    publish_moderation(
+        state,
+        &Event::new(EventTarget::All, EventKind::Unmuted(Unmute::new(username))),
+    )?;
+    audit::record_moderation_action(state, principal, "mute.lift", &format!("user {}", user_id));
+
+    Ok(true)
+}
+
+/// Applies the given write to the mutes. If the persistent layer can't be
+/// reached and the server has an outbox, the write is applied to the caching
+/// layer and queued until the persistent layer returns.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `write` - The write that should be applied
+fn write_mutes<T>(
+    state: &State,
+    write: impl FnOnce(&mut dyn Provider) -> Result<T, ProviderError>,
+) -> Result<T, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+    let outbox = state.outbox().cloned();
+
+    match state.persistent_connection() {
+        Ok(persistent_conn) => {
+            write(&mut Hybrid::new(cache, Persistent::new(&persistent_conn)).with_outbox(outbox))
+        }
+        Err(e) if outbox.is_some() && breaker::indicates_outage(&e) => {
+            tracing::warn!(error = %e, "persistent layer is down, queueing the write");
+
+            write(&mut Hybrid::new(cache, Unavailable).with_outbox(outbox))
+        }
+        Err(e) => Err(e),
+    }
+}
+
 /// Provider represents an arbitrary backend for the mutes service that may or
 /// may not present an accurate or up to date view of the entire history of
 /// mutes. Providers should be used in conjunction unless otherwise specified.
@@ -22,7 +296,7 @@ pub trait Provider {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -51,7 +325,7 @@ pub trait Provider {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::{ws_http_server::modules::mutes::{Cache, Provider}, spec::mute::Mute};
+    /// use gnomegg::{ws_http_server::modules::{Cache, mutes::Provider}, spec::mute::Mute};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -84,7 +358,7 @@ pub trait Provider {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -113,7 +387,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -125,6 +399,7 @@ impl<'a> Provider for Cache<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn set_muted(
         &mut self,
         user_id: u64,
@@ -133,14 +408,13 @@ impl<'a> Provider for Cache<'a> {
     ) -> Result<bool, ProviderError> {
         // If we're unmuting a user, we simply need to remove the redis entry
         if !muted {
-            let already_muted = self.is_muted(user_id)?;
-
-            redis::cmd("DEL")
-                .arg(format!("muted::{}", user_id))
-                .query(self.connection)
-                .map_err(<RedisError as Into<ProviderError>>::into)?;
+            let key = self.key(format_args!("muted::{}", user_id));
+            let (old,): (HashMap<String, String>,) = self.transaction(|pipe| {
+                pipe.cmd("HGETALL").arg(&key).cmd("DEL").arg(&key).ignore();
+            })?;
+            self.publish_invalidation(&Invalidation::Mute { user_id })?;
 
-            return Ok(already_muted);
+            return Ok(Mute::from_fields(&old)?.is_some_and(|mute| mute.active()));
         }
 
         // Otherwise, insert a new mute into the redis database, and return any old entries
@@ -149,7 +423,7 @@ impl<'a> Provider for Cache<'a> {
                 user_id,
                 duration.ok_or(ProviderError::MissingArgument { arg: "duration" })?,
             ))?
-            .map_or(false, |mute| mute.active()))
+            .is_some_and(|mute| mute.active()))
     }
 
     /// Registers a gnomegg mute primitive in the cache backend.
@@ -162,7 +436,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::{ws_http_server::modules::mutes::{Cache, Provider}, spec::mute::Mute};
+    /// use gnomegg::{ws_http_server::modules::{Cache, mutes::Provider}, spec::mute::Mute};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -176,16 +450,27 @@ impl<'a> Provider for Cache<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "cache"), err)]
     fn register_mute(&mut self, mute: &Mute) -> Result<Option<Mute>, ProviderError> {
-        redis::cmd("GETSET")
-            .arg(format!("muted::{}", mute.concerns()))
-            .arg(serde_json::to_string(mute)?)
-            .query::<Option<String>>(self.connection)
-            .map_err(|e| e.into())
-            .map(|raw| {
-                raw.map(|str_data| serde_json::from_str::<Mute>(&str_data).map(Some))?
-                    .unwrap_or(None)
-            })
+        let key = self.key(format_args!("muted::{}", mute.concerns()));
+        let fields = mute.to_fields();
+
+        let (old,): (HashMap<String, String>,) = self.transaction(|pipe| {
+            pipe.cmd("HGETALL")
+                .arg(&key)
+                .cmd("DEL")
+                .arg(&key)
+                .ignore()
+                .cmd("HSET")
+                .arg(&key)
+                .arg(fields.as_slice())
+                .ignore();
+        })?;
+        self.publish_invalidation(&Invalidation::Mute {
+            user_id: mute.concerns(),
+        })?;
+
+        Mute::from_fields(&old).map_err(|e| e.into())
     }
 
     /// Gets the mute primitive corresponding to the given user ID.
@@ -194,15 +479,13 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `user_id` - The user ID for which a mute primitive should be found in
     /// the caching database
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn get_mute(&mut self, user_id: u64) -> Result<Option<Mute>, ProviderError> {
-        redis::cmd("GET")
-            .arg(format!("muted::{}", user_id))
-            .query::<Option<String>>(self.connection)
-            .map_err(|e| e.into())
-            .map(|raw| {
-                raw.map(|str_data| serde_json::from_str::<Mute>(&str_data).map(Some))?
-                    .unwrap_or(None)
-            })
+        let fields = redis::cmd("HGETALL")
+            .arg(self.key(format_args!("muted::{}", user_id)))
+            .query::<HashMap<String, String>>(self.connection)?;
+
+        Mute::from_fields(&fields).map_err(|e| e.into())
     }
 
     /// Checks whether or not a user with the given username has been muted
@@ -214,7 +497,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -227,8 +510,9 @@ impl<'a> Provider for Cache<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn is_muted(&mut self, user_id: u64) -> Result<bool, ProviderError> {
-        Ok(self.get_mute(user_id)?.map_or(false, |mute| mute.active()))
+        Ok(self.get_mute(user_id)?.is_some_and(|mute| mute.active()))
     }
 }
 
@@ -245,7 +529,7 @@ impl<'a> Provider for Persistent<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -257,6 +541,7 @@ impl<'a> Provider for Persistent<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn set_muted(
         &mut self,
         user_id: u64,
@@ -268,9 +553,9 @@ impl<'a> Provider for Persistent<'a> {
         // If the user is being unmuted, we simply need to delete the row
         // corresponding to the user's mute in the database
         if !muted {
-            return diesel::delete(mutes::dsl::mutes.find(user_id))
+            return diesel::delete(mutes::dsl::mutes.find(Unsigned(user_id)))
                 .execute(self.connection)
-                .map(|_| old.map_or(false, |mute| mute.active()))
+                .map(|_| old.is_some_and(|mute| mute.active()))
                 .map_err(|e| e.into());
         }
 
@@ -280,7 +565,7 @@ impl<'a> Provider for Persistent<'a> {
                 user_id,
                 duration.ok_or(ProviderError::MissingArgument { arg: "duration" })?,
             ))?
-            .map_or(false, |mute| mute.active()))
+            .is_some_and(|mute| mute.active()))
     }
 
     /// Registers a gnomegg mute primitive in the active provider.
@@ -293,7 +578,7 @@ impl<'a> Provider for Persistent<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::{ws_http_server::modules::mutes::{Cache, Provider}, spec::mute::Mute};
+    /// use gnomegg::{ws_http_server::modules::{Cache, mutes::Provider}, spec::mute::Mute};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -307,6 +592,7 @@ impl<'a> Provider for Persistent<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "persistent"), err)]
     fn register_mute(&mut self, mute: &Mute) -> Result<Option<Mute>, ProviderError> {
         let old = self.get_mute(mute.concerns())?;
 
@@ -323,9 +609,10 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `user_id` - The user ID for which a mute primitive should be found in
     /// the caching database
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn get_mute(&mut self, user_id: u64) -> Result<Option<Mute>, ProviderError> {
         mutes::dsl::mutes
-            .find(user_id)
+            .find(Unsigned(user_id))
             .first::<Mute>(self.connection)
             .map(Some)
             .or_else(|e| {
@@ -346,7 +633,7 @@ impl<'a> Provider for Persistent<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -359,12 +646,125 @@ impl<'a> Provider for Persistent<'a> {
     /// Ok(())
     /// # }
     /// ```
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn is_muted(&mut self, user_id: u64) -> Result<bool, ProviderError> {
-        Ok(self.get_mute(user_id)?.map_or(false, |mute| mute.active()))
+        Ok(self.get_mute(user_id)?.is_some_and(|mute| mute.active()))
     }
 }
 
-impl<'a> Provider for Hybrid<'a> {
+impl<'a> Persistent<'a> {
+    /// Gets up to `limit` mutes following the given cursor, ordered by the
+    /// IDs of the muted users. Mutes are only listed by the persistent layer,
+    /// which holds every mute.
+    ///
+    /// # Arguments
+    ///
+    /// * `cursor` - (optional) The ID of the muted user that each returned
+    /// mute must follow
+    /// * `sort` - The order in which mutes should be returned
+    /// * `include_lapsed` - Whether or not mutes that have lapsed, but
+    /// haven't been lifted, should be returned
+    /// * `limit` - The maximum number of mutes that should be returned
+    pub fn mutes(
+        &mut self,
+        cursor: Option<u64>,
+        sort: Sort,
+        include_lapsed: bool,
+        limit: usize,
+    ) -> Result<Vec<Mute>, ProviderError> {
+        let mut query = mutes::dsl::mutes.limit(limit as i64).into_boxed();
+
+        query = match (sort, cursor) {
+            (Sort::Asc, Some(cursor)) => query
+                .filter(mutes::dsl::user_id.gt(Unsigned(cursor)))
+                .order(mutes::dsl::user_id.asc()),
+            (Sort::Asc, None) => query.order(mutes::dsl::user_id.asc()),
+            (Sort::Desc, Some(cursor)) => query
+                .filter(mutes::dsl::user_id.lt(Unsigned(cursor)))
+                .order(mutes::dsl::user_id.desc()),
+            (Sort::Desc, None) => query.order(mutes::dsl::user_id.desc()),
+        };
+
+        if !include_lapsed {
+            query = query.filter(sql::<Bool>(UNLAPSED));
+        }
+
+        query.load(self.reader()).map_err(|e| e.into())
+    }
+}
+
+impl Provider for Memory {
+    /// Sets a user's muted status in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the chatter who will be muted by this command
+    /// * `muted` - Whether or not this user should be muted
+    /// * `duration` - (optional) The number of nanoseconds that the mute
+    /// should be active for (this does not apply for unmuting a user)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{mutes::Provider, Memory};
+    ///
+    /// let mut mutes = Memory::new();
+    /// mutes.set_muted(1, true, Some(1_000_000_000)).unwrap();
+    /// assert!(mutes.is_muted(1).unwrap());
+    /// ```
+    fn set_muted(
+        &mut self,
+        user_id: u64,
+        muted: bool,
+        duration: Option<u64>,
+    ) -> Result<bool, ProviderError> {
+        if !muted {
+            return Ok(self
+                .mutes
+                .remove(&user_id)
+                .is_some_and(|mute| mute.active()));
+        }
+
+        Ok(self
+            .register_mute(&Mute::new(
+                user_id,
+                duration.ok_or(ProviderError::MissingArgument { arg: "duration" })?,
+            ))?
+            .is_some_and(|mute| mute.active()))
+    }
+
+    /// Registers a gnomegg mute primitive in memory, returning the mute it
+    /// replaced, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `mute` - The mute primitive that should be used to modify the mutes
+    /// state
+    fn register_mute(&mut self, mute: &Mute) -> Result<Option<Mute>, ProviderError> {
+        Ok(self.mutes.insert(mute.concerns(), mute.clone()))
+    }
+
+    /// Gets the mute primitive corresponding to the given user ID from
+    /// memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID for which a mute primitive should be found
+    fn get_mute(&mut self, user_id: u64) -> Result<Option<Mute>, ProviderError> {
+        Ok(self.mutes.get(&user_id).cloned())
+    }
+
+    /// Checks whether or not a user with the given username has been muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID for which the "muted" value should be fetched
+    fn is_muted(&mut self, user_id: u64) -> Result<bool, ProviderError> {
+        Ok(self.get_mute(user_id)?.is_some_and(|mute| mute.active()))
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
     /// Sets a user's muted status in the active provider.
     ///
     /// # Arguments
@@ -377,7 +777,7 @@ impl<'a> Provider for Hybrid<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -395,9 +795,17 @@ impl<'a> Provider for Hybrid<'a> {
         muted: bool,
         duration: Option<u64>,
     ) -> Result<bool, ProviderError> {
-        self.cache
-            .set_muted(user_id, muted, duration)
-            .and(self.persistent.set_muted(user_id, muted, duration))
+        let pending = match (muted, duration) {
+            (true, Some(duration)) => Some(PendingWrite::Mute {
+                mute: Mute::new(user_id, duration),
+            }),
+            (true, None) => None,
+            (false, _) => Some(PendingWrite::Unmute { user_id }),
+        };
+
+        self.write_mute(user_id, pending, |mutes| {
+            mutes.set_muted(user_id, muted, duration)
+        })
     }
 
     /// Registers a gnomegg mute primitive in the active provider.
@@ -410,7 +818,7 @@ impl<'a> Provider for Hybrid<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::{ws_http_server::modules::mutes::{Cache, Provider}, spec::mute::Mute};
+    /// use gnomegg::{ws_http_server::modules::{Cache, mutes::Provider}, spec::mute::Mute};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -425,9 +833,11 @@ impl<'a> Provider for Hybrid<'a> {
     /// # }
     /// ```
     fn register_mute(&mut self, mute: &Mute) -> Result<Option<Mute>, ProviderError> {
-        self.cache
-            .register_mute(&mute)
-            .and(self.persistent.register_mute(&mute))
+        let pending = PendingWrite::Mute { mute: mute.clone() };
+
+        self.write_mute(mute.concerns(), Some(pending), |mutes| {
+            mutes.register_mute(mute)
+        })
     }
 
     /// Gets the mute primitive corresponding to the given user ID.
@@ -437,9 +847,10 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The user ID for which a mute primitive should be found in
     /// the caching database
     fn get_mute(&mut self, user_id: u64) -> Result<Option<Mute>, ProviderError> {
-        self.cache
-            .get_mute(user_id)
-            .or_else(|_| self.persistent.get_mute(user_id))
+        let cached = self.cache.get_mute(user_id);
+        self.fall_back("mutes", cached, |hybrid| {
+            hybrid.persistent.get_mute(user_id)
+        })
     }
 
     /// Checks whether or not a user with the given username has been muted
@@ -451,7 +862,7 @@ impl<'a> Provider for Hybrid<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::mutes::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, mutes::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -465,22 +876,139 @@ impl<'a> Provider for Hybrid<'a> {
     /// # }
     /// ```
     fn is_muted(&mut self, user_id: u64) -> Result<bool, ProviderError> {
-        self.cache
-            .is_muted(user_id)
-            .or_else(|_| self.persistent.is_muted(user_id))
+        let cached = self.cache.is_muted(user_id);
+        self.fall_back("mutes", cached, |hybrid| {
+            hybrid.persistent.is_muted(user_id)
+        })
+    }
+}
+
+impl<C: Provider, P: Provider> Hybrid<C, P> {
+    /// Applies the given write to the caching layer, and then to the
+    /// persistent layer. If the persistent layer rejects the write, the
+    /// user's cached mute is restored to its state before the write, such
+    /// that the cache never reports a mute that wasn't persisted.
+    /// Restoration is best-effort: if the cache fails in the meantime, the
+    /// persistent layer's error is still returned. Writes that can't be
+    /// persisted because the persistent layer is down are queued in the
+    /// provider's outbox instead, if it has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose mute is being written
+    /// * `pending` - (optional) The write that should be queued if it can't
+    /// be persisted
+    /// * `write` - The write that should be applied to each layer
+    fn write_mute<T>(
+        &mut self,
+        user_id: u64,
+        pending: Option<PendingWrite>,
+        mut write: impl FnMut(&mut dyn Provider) -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let previous = self.cache.get_mute(user_id);
+
+        let written = write(&mut self.cache);
+        let pending = pending.filter(|_| written.is_ok());
+        let persisted = self.persist(pending, |persistent| write(persistent));
+
+        match (written, persisted) {
+            (written, Ok(None)) => written,
+            (Ok(_), Err(e)) => {
+                if let Ok(previous) = previous {
+                    let _ = match previous {
+                        Some(mute) => self.cache.register_mute(&mute).map(|_| ()),
+                        None => self.cache.set_muted(user_id, false, None).map(|_| ()),
+                    };
+                }
+
+                Err(e)
+            }
+            (written, Ok(Some(persisted))) => write_both(written, Ok(persisted)),
+            (written, Err(e)) => write_both(written, Err(e)),
+        }
+    }
+}
+
+impl Provider for Unavailable {
+    fn set_muted(
+        &mut self,
+        _user_id: u64,
+        _muted: bool,
+        _duration: Option<u64>,
+    ) -> Result<bool, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn register_mute(&mut self, _mute: &Mute) -> Result<Option<Mute>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn get_mute(&mut self, _user_id: u64) -> Result<Option<Mute>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn is_muted(&mut self, _user_id: u64) -> Result<bool, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+}
+
+impl<T: Provider + ?Sized> Provider for Box<T> {
+    fn set_muted(
+        &mut self,
+        user_id: u64,
+        muted: bool,
+        duration: Option<u64>,
+    ) -> Result<bool, ProviderError> {
+        (**self).set_muted(user_id, muted, duration)
+    }
+
+    fn register_mute(&mut self, mute: &Mute) -> Result<Option<Mute>, ProviderError> {
+        (**self).register_mute(mute)
+    }
+
+    fn get_mute(&mut self, user_id: u64) -> Result<Option<Mute>, ProviderError> {
+        (**self).get_mute(user_id)
+    }
+
+    fn is_muted(&mut self, user_id: u64) -> Result<bool, ProviderError> {
+        (**self).is_muted(user_id)
+    }
+}
+
+/// Selects the mutes provider backed by the given layers. Layers left unused
+/// by the selection are dropped.
+///
+/// # Arguments
+///
+/// * `layers` - The layers that should back the provider
+/// * `cache` - The redis caching layer
+/// * `persistent` - The MySQL persistence layer
+pub fn provider<'a>(
+    layers: Layers,
+    cache: Cache<'a>,
+    persistent: Persistent<'a>,
+) -> Box<dyn Provider + 'a> {
+    match layers {
+        Layers::Cache => Box::new(cache),
+        Layers::Persistent => Box::new(persistent),
+        Layers::Hybrid => Box::new(Hybrid::new(cache, persistent)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        super::super::super::spec::{schema::users, user::NewUser},
+        super::super::{
+            super::spec::{backend::PersistentConnection, schema::users, user::NewUser},
+            breaker::{BreakerState, CircuitBreaker},
+            lookups::CacheLookups,
+        },
         *,
     };
-    use diesel::{ExpressionMethods, Connection, mysql::MysqlConnection};
+    use diesel::{result::ConnectionError, Connection, ExpressionMethods};
     use dotenv;
 
-    use std::{default::Default, env, error::Error};
+    use std::{default::Default, env, error::Error, sync::Arc};
 
     #[test]
     fn test_hybrid() -> Result<(), Box<dyn Error>> {
@@ -488,7 +1016,7 @@ mod tests {
 
         let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -510,7 +1038,11 @@ mod tests {
         let mut mutes = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
         mutes.set_muted(id, true, Some(1_000_000_000))?;
 
-        assert_eq!(mutes.is_muted(id)?, true);
+        assert!(mutes.is_muted(id)?);
+        assert!(Persistent::new(&persistent_conn)
+            .mutes(Some(id - 1), Sort::Asc, false, 1)?
+            .iter()
+            .any(|mute| mute.concerns() == id));
 
         Ok(())
     }
@@ -524,7 +1056,7 @@ mod tests {
         let mut mutes = Cache::new(&mut conn);
         mutes.set_muted(42069, true, Some(1_000_000))?;
 
-        assert_eq!(mutes.is_muted(42069)?, true);
+        assert!(mutes.is_muted(42069)?);
 
         Ok(())
     }
@@ -535,7 +1067,7 @@ mod tests {
 
         // Open a connection with the MySQL server
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -557,7 +1089,123 @@ mod tests {
         let mut mutes = Persistent::new(&persistent_conn);
         mutes.set_muted(id, true, Some(1_000_000_000))?;
 
-        assert_eq!(mutes.is_muted(id)?, true);
+        assert!(mutes.is_muted(id)?);
+
+        Ok(())
+    }
+
+    /// Failing is a layer rejecting every operation, used to verify the
+    /// behavior of hybrid providers when a layer is unavailable.
+    struct Failing;
+
+    /// Gets the error returned by a layer that can't be reached.
+    fn unavailable<T>() -> Result<T, ProviderError> {
+        Err(ProviderError::ConnectionError(
+            ConnectionError::BadConnection("connection refused".to_owned()),
+        ))
+    }
+
+    impl Provider for Failing {
+        fn set_muted(
+            &mut self,
+            _user_id: u64,
+            _muted: bool,
+            _duration: Option<u64>,
+        ) -> Result<bool, ProviderError> {
+            unavailable()
+        }
+
+        fn register_mute(&mut self, _mute: &Mute) -> Result<Option<Mute>, ProviderError> {
+            unavailable()
+        }
+
+        fn get_mute(&mut self, _user_id: u64) -> Result<Option<Mute>, ProviderError> {
+            unavailable()
+        }
+
+        fn is_muted(&mut self, _user_id: u64) -> Result<bool, ProviderError> {
+            unavailable()
+        }
+    }
+
+    #[test]
+    fn test_write_through() -> Result<(), Box<dyn Error>> {
+        let mut cache = Memory::new();
+        cache.set_muted(2, true, Some(60_000_000_000))?;
+
+        let mut mutes = Hybrid::new(cache, Failing);
+
+        // Mutes rejected by the persistent layer aren't left in the cache
+        assert!(mutes.set_muted(1, true, Some(60_000_000_000)).is_err());
+        assert!(!mutes.cache.is_muted(1)?);
+
+        // Mutes that were already cached are restored
+        assert!(mutes.set_muted(2, false, None).is_err());
+        assert!(mutes.cache.is_muted(2)?);
+        assert!(mutes.register_mute(&Mute::new(2, 1)).is_err());
+        assert!(mutes.cache.is_muted(2)?);
+
+        // Writes accepted by the persistent layer are kept in the cache
+        let mut mutes = Hybrid::new(Memory::new(), Memory::new());
+        mutes.set_muted(1, true, Some(60_000_000_000))?;
+        assert!(mutes.cache.is_muted(1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_breaker() {
+        let breaker = Arc::new(CircuitBreaker::default().with_failure_threshold(1));
+        let mut mutes = Hybrid::new(Failing, Failing).with_breaker(Some(breaker.clone()));
+
+        assert!(matches!(
+            mutes.is_muted(1),
+            Err(ProviderError::Composite { .. })
+        ));
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        // Once the breaker opens, reads are no longer sent to the persistent
+        // layer
+        assert!(matches!(
+            mutes.is_muted(1),
+            Err(ProviderError::ConnectionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_lookups() -> Result<(), Box<dyn Error>> {
+        let lookups = Arc::new(CacheLookups::default());
+
+        let mut mutes =
+            Hybrid::new(Memory::new(), Memory::new()).with_lookups(Some(lookups.clone()));
+        mutes.set_muted(1, true, Some(60_000_000_000))?;
+        mutes.get_mute(1)?;
+        mutes.get_mute(2)?;
+
+        // Reads that the cache fails to answer aren't counted as misses
+        let mut failing = Hybrid::new(Failing, Memory::new()).with_lookups(Some(lookups.clone()));
+        assert!(!failing.is_muted(1)?);
+
+        let counts = lookups.metrics()["mutes"];
+        assert_eq!(counts.hits(), 1);
+        assert_eq!(counts.misses(), 1);
+        assert_eq!(counts.errors(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory() -> Result<(), Box<dyn Error>> {
+        let mut mutes = Hybrid::new(Memory::new(), Memory::new());
+
+        assert!(!mutes.set_muted(1, true, Some(60_000_000_000))?);
+        assert!(mutes.is_muted(1)?);
+
+        // Muting a chatter again reports that they were already muted
+        assert!(mutes.set_muted(1, true, Some(60_000_000_000))?);
+
+        assert!(mutes.set_muted(1, false, None)?);
+        assert!(!mutes.is_muted(1)?);
 
         Ok(())
     }
@@ -1,15 +1,21 @@
+use chrono::Utc;
 use diesel::{
-    expression_methods::ExpressionMethods, result::Error as DieselError, QueryDsl, RunQueryDsl,
+    expression_methods::{ExpressionMethods, TextExpressionMethods},
+    result::Error as DieselError,
+    Connection, OptionalExtension, QueryDsl, RunQueryDsl,
 };
 
 use super::{
     super::super::spec::{
-        schema::{ids, users},
-        user::NewIdMapping,
+        backend::Unsigned,
+        schema::{ids, username_history, users},
+        user::{NewIdMapping, NewUsernameChange},
     },
-    Cache, Persistent, ProviderError, Hybrid,
+    write_both, Cache, Hybrid, Memory, Persistent, ProviderError,
 };
 
+use std::collections::HashMap;
+
 /// Provider represents an arbitrary backend for the name resolution service.
 pub trait Provider {
     /// Retreieves the user ID matching the provided username.
@@ -28,6 +34,24 @@ pub trait Provider {
     /// obtained
     fn username_for(&mut self, user_id: u64) -> Result<Option<String>, ProviderError>;
 
+    /// Retreives the user IDs matching each of the provided usernames, in the
+    /// order that the usernames were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `usernames` - The usernames for which corresponding user IDs should
+    /// be obtained
+    fn user_ids_for(&mut self, usernames: &[&str]) -> Result<Vec<Option<u64>>, ProviderError>;
+
+    /// Retreives the usernames matching each of the provided user IDs, in the
+    /// order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The user IDs for which corresponding usernames should be
+    /// obtained
+    fn usernames_for(&mut self, user_ids: &[u64]) -> Result<Vec<Option<String>>, ProviderError>;
+
     /// Stores a username to user ID / user ID to username mapping in a
     /// provider.
     ///
@@ -36,6 +60,46 @@ pub trait Provider {
     /// * `username` - The username for which a corresponding user ID should be
     /// obtained
     fn set_combination(&mut self, username: &str, user_id: u64) -> Result<(), ProviderError>;
+
+    /// Changes the username of the user with the given ID, recording the
+    /// user's old username in their username history. Returns the user's old
+    /// username, if they had one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username should be changed
+    /// * `new_username` - The username that should be assigned to the user
+    fn rename_user(
+        &mut self,
+        user_id: u64,
+        new_username: &str,
+    ) -> Result<Option<String>, ProviderError>;
+
+    /// Retreives each of the usernames previously held by the user with the
+    /// given ID, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username history should be
+    /// obtained
+    fn previous_usernames(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError>;
+
+    /// Retreives up to `limit` usernames beginning with the given prefix,
+    /// ignoring case, in the order in which they are indexed.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The string that each of the returned usernames should
+    /// begin with
+    /// * `after` - (optional) The username that each of the returned
+    /// usernames should follow, used as a pagination cursor
+    /// * `limit` - The maximum number of usernames that should be returned
+    fn search_usernames(
+        &mut self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ProviderError>;
 }
 
 impl<'a> Provider for Cache<'a> {
@@ -49,7 +113,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::name_resolver::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -64,7 +128,7 @@ impl<'a> Provider for Cache<'a> {
     /// ```
     fn user_id_for(&mut self, username: &str) -> Result<Option<u64>, ProviderError> {
         redis::cmd("GET")
-            .arg(format!("user_id::{}", username))
+            .arg(self.key(format_args!("user_id::{}", username)))
             .query(self.connection)
             .map_err(|e| e.into())
     }
@@ -79,7 +143,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::name_resolver::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -94,7 +158,71 @@ impl<'a> Provider for Cache<'a> {
     /// ```
     fn username_for(&mut self, user_id: u64) -> Result<Option<String>, ProviderError> {
         redis::cmd("GET")
-            .arg(format!("username::{}", user_id))
+            .arg(self.key(format_args!("username::{}", user_id)))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the user IDs matching each of the provided usernames, in the
+    /// order that the usernames were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `usernames` - The usernames for which corresponding user IDs should
+    /// be obtained
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut names = Cache::new(&mut conn);
+    /// names.set_combination("MrMouton", 69420)?;
+    /// assert_eq!(names.user_ids_for(&["MrMouton"])?, vec![Some(69420)]);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn user_ids_for(&mut self, usernames: &[&str]) -> Result<Vec<Option<u64>>, ProviderError> {
+        // MGET requires at least one key
+        if usernames.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        redis::cmd("MGET")
+            .arg(
+                usernames
+                    .iter()
+                    .map(|username| self.key(format_args!("user_id::{}", username)))
+                    .collect::<Vec<String>>(),
+            )
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives the usernames matching each of the provided user IDs, in the
+    /// order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The user IDs for which corresponding usernames should be
+    /// obtained
+    fn usernames_for(&mut self, user_ids: &[u64]) -> Result<Vec<Option<String>>, ProviderError> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        redis::cmd("MGET")
+            .arg(
+                user_ids
+                    .iter()
+                    .map(|user_id| self.key(format_args!("username::{}", user_id)))
+                    .collect::<Vec<String>>(),
+            )
             .query(self.connection)
             .map_err(|e| e.into())
     }
@@ -110,7 +238,7 @@ impl<'a> Provider for Cache<'a> {
     /// # Example
     ///
     /// ```
-    /// use gnomegg::ws_http_server::modules::name_resolver::{Cache, Provider};
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
     /// # use std::error::Error;
     ///
     /// # fn main() -> Result<(), Box<dyn Error>> {
@@ -124,14 +252,159 @@ impl<'a> Provider for Cache<'a> {
     /// # }
     /// ```
     fn set_combination(&mut self, username: &str, user_id: u64) -> Result<(), ProviderError> {
-        redis::cmd("MSET")
-            .arg(format!("user_id::{}", username))
-            .arg(user_id)
-            .arg(format!("username::{}", user_id))
-            .arg(username)
+        cache_combinations(self, &[(username, user_id)])
+    }
+
+    /// Changes the username of the user with the given ID, recording the
+    /// user's old username in their username history.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username should be changed
+    /// * `new_username` - The username that should be assigned to the user
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut names = Cache::new(&mut conn);
+    /// names.set_combination("MrMouton", 69420)?;
+    /// names.rename_user(69420, "MrMoutonFan")?;
+    /// assert_eq!(names.previous_usernames(69420)?[0], "MrMouton");
+    /// Ok(())
+    /// # }
+    /// ```
+    fn rename_user(
+        &mut self,
+        user_id: u64,
+        new_username: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        let mut expected = self.username_for(user_id)?;
+
+        // Swap out the user's mappings in one script, so that no reader can
+        // observe the user under both names. Search entries can't be derived
+        // in Lua, so the script only runs if the user wasn't renamed since
+        // their old username was read, and is retried otherwise.
+        let keys = [
+            self.key(format_args!("username::{}", user_id)),
+            self.key(format_args!("user_id::{}", new_username)),
+            self.key("usernames"),
+            self.key(format_args!("username_history::{}", user_id)),
+        ];
+
+        let old = loop {
+            let old_key = self.key(format_args!(
+                "user_id::{}",
+                expected.as_deref().unwrap_or_default()
+            ));
+
+            let (swapped, current): (bool, String) = self.eval(RENAME_USER_SCRIPT, |script| {
+                script
+                    .key(&keys[..])
+                    .key(old_key)
+                    .arg(user_id)
+                    .arg(new_username)
+                    .arg(expected.as_deref().unwrap_or_default())
+                    .arg(expected.as_deref().map(search_entry).unwrap_or_default())
+                    .arg(search_entry(new_username));
+            })?;
+
+            let current = Some(current).filter(|username| !username.is_empty());
+            if swapped {
+                break current;
+            }
+
+            expected = current;
+        };
+
+        Ok(old)
+    }
+
+    /// Retreives each of the usernames previously held by the user with the
+    /// given ID, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username history should be
+    /// obtained
+    fn previous_usernames(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        redis::cmd("LRANGE")
+            .arg(self.key(format_args!("username_history::{}", user_id)))
+            .arg(0)
+            .arg(-1)
             .query(self.connection)
             .map_err(|e| e.into())
     }
+
+    /// Retreives up to `limit` usernames beginning with the given prefix,
+    /// ignoring case.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The string that each of the returned usernames should
+    /// begin with
+    /// * `after` - (optional) The username that each of the returned
+    /// usernames should follow, used as a pagination cursor
+    /// * `limit` - The maximum number of usernames that should be returned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, name_resolver::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut names = Cache::new(&mut conn);
+    /// names.set_combination("MrMouton", 69420)?;
+    /// assert!(names.search_usernames("mrmou", None, 10)?.contains(&"MrMouton".to_owned()));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn search_usernames(
+        &mut self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ProviderError> {
+        let lower = prefix.to_lowercase();
+
+        // 0xFF sorts after every byte in a UTF-8 string, and thus bounds the
+        // range of members beginning with the prefix
+        let mut max = format!("[{}", lower).into_bytes();
+        max.push(0xFF);
+
+        // Pages following a cursor start just past the cursor's own member
+        let min = match after {
+            Some(after) => format!("({}", search_entry(after)),
+            None => format!("[{}", lower),
+        };
+
+        redis::cmd("ZRANGEBYLEX")
+            .arg(self.key("usernames"))
+            .arg(min)
+            .arg(max)
+            .arg("LIMIT")
+            .arg(0)
+            .arg(limit)
+            .query::<Vec<String>>(self.connection)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| entry.split_once("::").map(|x| x.1))
+                    .map(|username| username.to_owned())
+                    .collect()
+            })
+            .map_err(|e| e.into())
+    }
 }
 
 impl<'a> Provider for Persistent<'a> {
@@ -167,12 +440,54 @@ impl<'a> Provider for Persistent<'a> {
     /// obtained
     fn username_for(&mut self, user_id: u64) -> Result<Option<String>, ProviderError> {
         users::dsl::users
-            .find(user_id)
+            .find(Unsigned(user_id))
             .select(users::dsl::username)
-            .first(self.connection)
+            .first(self.reader())
             .map_err(|e| e.into())
     }
 
+    /// Retreives the user IDs matching each of the provided usernames, in the
+    /// order that the usernames were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `usernames` - The usernames for which corresponding user IDs should
+    /// be obtained
+    fn user_ids_for(&mut self, usernames: &[&str]) -> Result<Vec<Option<u64>>, ProviderError> {
+        let found = ids::dsl::ids
+            .filter(ids::dsl::username.eq_any(usernames))
+            .select((ids::dsl::username, ids::dsl::user_id))
+            .load::<(String, u64)>(self.connection)?
+            .into_iter()
+            .collect::<HashMap<String, u64>>();
+
+        Ok(usernames
+            .iter()
+            .map(|username| found.get(*username).copied())
+            .collect())
+    }
+
+    /// Retreives the usernames matching each of the provided user IDs, in the
+    /// order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The user IDs for which corresponding usernames should be
+    /// obtained
+    fn usernames_for(&mut self, user_ids: &[u64]) -> Result<Vec<Option<String>>, ProviderError> {
+        let mut found = users::dsl::users
+            .filter(users::dsl::id.eq_any(user_ids.iter().copied().map(Unsigned)))
+            .select((users::dsl::id, users::dsl::username))
+            .load::<(u64, Option<String>)>(self.connection)?
+            .into_iter()
+            .collect::<HashMap<u64, Option<String>>>();
+
+        Ok(user_ids
+            .iter()
+            .map(|user_id| found.remove(user_id).flatten())
+            .collect())
+    }
+
     /// Stores a username to user ID / user ID to username mapping in a
     /// provider.
     ///
@@ -184,7 +499,7 @@ impl<'a> Provider for Persistent<'a> {
         // The user must exist in order to set a mapping between them. As such,
         // we want to update existing user entries before adding or updating
         // secondary mappings
-        diesel::update(users::dsl::users.find(user_id))
+        diesel::update(users::dsl::users.find(Unsigned(user_id)))
             .set(users::dsl::username.eq(username))
             .execute(self.connection)?;
 
@@ -194,9 +509,261 @@ impl<'a> Provider for Persistent<'a> {
             .map(|_| ())
             .map_err(|e| e.into())
     }
+
+    /// Changes the username of the user with the given ID, recording the
+    /// user's old username in their username history.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username should be changed
+    /// * `new_username` - The username that should be assigned to the user
+    fn rename_user(
+        &mut self,
+        user_id: u64,
+        new_username: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction::<_, ProviderError, _>(|| {
+            let old = users::dsl::users
+                .find(Unsigned(user_id))
+                .select(users::dsl::username)
+                .first::<Option<String>>(connection)
+                .optional()?
+                .flatten();
+
+            diesel::update(users::dsl::users.find(Unsigned(user_id)))
+                .set(users::dsl::username.eq(new_username))
+                .execute(connection)?;
+
+            // Mappings from the user's old name must be removed, such that
+            // the name may be claimed by another user
+            diesel::delete(ids::dsl::ids.filter(ids::dsl::user_id.eq(Unsigned(user_id))))
+                .execute(connection)?;
+            diesel::insert_into(ids::dsl::ids)
+                .values(&NewIdMapping::new(new_username, user_id))
+                .execute(connection)?;
+
+            if let Some(old_username) = &old {
+                diesel::insert_into(username_history::table)
+                    .values(&NewUsernameChange::new(user_id, old_username, Utc::now()))
+                    .execute(connection)?;
+            }
+
+            Ok(old)
+        })
+    }
+
+    /// Retreives each of the usernames previously held by the user with the
+    /// given ID, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username history should be
+    /// obtained
+    fn previous_usernames(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        username_history::dsl::username_history
+            .filter(username_history::dsl::user_id.eq(Unsigned(user_id)))
+            .order(username_history::dsl::changed_at.desc())
+            .select(username_history::dsl::username)
+            .load(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Retreives up to `limit` usernames beginning with the given prefix,
+    /// ignoring case.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The string that each of the returned usernames should
+    /// begin with
+    /// * `after` - (optional) The username that each of the returned
+    /// usernames should follow, used as a pagination cursor
+    /// * `limit` - The maximum number of usernames that should be returned
+    fn search_usernames(
+        &mut self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ProviderError> {
+        // Wildcards in the prefix must be escaped, lest they be interpreted
+        // by the LIKE clause
+        let pattern = format!(
+            "{}%",
+            prefix
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        let mut query = ids::dsl::ids
+            .filter(ids::dsl::username.like(pattern))
+            .order(ids::dsl::username.asc())
+            .limit(limit as i64)
+            .select(ids::dsl::username)
+            .into_boxed();
+
+        if let Some(after) = after {
+            query = query.filter(ids::dsl::username.gt(after));
+        }
+
+        query.load(self.connection).map_err(|e| e.into())
+    }
 }
 
-impl<'a> Provider for Hybrid<'a> {
+impl Provider for Memory {
+    /// Retreieves the user ID matching the provided username from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username for which a corresponding user ID should
+    /// be obtained
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{name_resolver::Provider, Memory};
+    ///
+    /// let mut resolver = Memory::new();
+    /// resolver.set_combination("MrMouton", 69420).unwrap();
+    /// assert_eq!(resolver.user_id_for("MrMouton").unwrap(), Some(69420));
+    /// ```
+    fn user_id_for(&mut self, username: &str) -> Result<Option<u64>, ProviderError> {
+        Ok(self.user_ids.get(username).copied())
+    }
+
+    /// Retreives the username matching the provided user ID from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID for which a corresponding username should be
+    /// obtained
+    fn username_for(&mut self, user_id: u64) -> Result<Option<String>, ProviderError> {
+        Ok(self.usernames.get(&user_id).cloned())
+    }
+
+    /// Retreives the user IDs matching each of the provided usernames from
+    /// memory, in the order that the usernames were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `usernames` - The usernames for which corresponding user IDs should
+    /// be obtained
+    fn user_ids_for(&mut self, usernames: &[&str]) -> Result<Vec<Option<u64>>, ProviderError> {
+        Ok(usernames
+            .iter()
+            .map(|username| self.user_ids.get(*username).copied())
+            .collect())
+    }
+
+    /// Retreives the usernames matching each of the provided user IDs from
+    /// memory, in the order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The user IDs for which corresponding usernames should be
+    /// obtained
+    fn usernames_for(&mut self, user_ids: &[u64]) -> Result<Vec<Option<String>>, ProviderError> {
+        Ok(user_ids
+            .iter()
+            .map(|user_id| self.usernames.get(user_id).cloned())
+            .collect())
+    }
+
+    /// Stores a username to user ID / user ID to username mapping in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username for which a corresponding user ID should be
+    /// obtained
+    /// * `user_id` - The ID of the user holding the username
+    fn set_combination(&mut self, username: &str, user_id: u64) -> Result<(), ProviderError> {
+        self.user_ids.insert(username.to_owned(), user_id);
+        self.usernames.insert(user_id, username.to_owned());
+
+        Ok(())
+    }
+
+    /// Changes the username of the user with the given ID in memory,
+    /// recording the user's old username in their username history. Returns
+    /// the user's old username, if they had one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username should be changed
+    /// * `new_username` - The username that should be assigned to the user
+    fn rename_user(
+        &mut self,
+        user_id: u64,
+        new_username: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        let old = self.usernames.insert(user_id, new_username.to_owned());
+
+        if let Some(old_username) = &old {
+            self.user_ids.remove(old_username);
+            self.username_history
+                .entry(user_id)
+                .or_default()
+                .insert(0, old_username.clone());
+        }
+
+        self.user_ids.insert(new_username.to_owned(), user_id);
+
+        Ok(old)
+    }
+
+    /// Retreives each of the usernames previously held by the user with the
+    /// given ID from memory, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username history should be
+    /// obtained
+    fn previous_usernames(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        Ok(self
+            .username_history
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Retreives up to `limit` usernames beginning with the given prefix from
+    /// memory, ignoring case.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The string that each of the returned usernames should
+    /// begin with
+    /// * `after` - (optional) The username that each of the returned
+    /// usernames should follow, used as a pagination cursor
+    /// * `limit` - The maximum number of usernames that should be returned
+    fn search_usernames(
+        &mut self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ProviderError> {
+        let lower = prefix.to_lowercase();
+
+        // Usernames are ordered as they are in the cache's search index
+        let mut entries = self
+            .user_ids
+            .keys()
+            .filter(|username| username.to_lowercase().starts_with(&lower))
+            .map(|username| (search_entry(username), username.clone()))
+            .filter(|(entry, _)| after.is_none_or(|after| *entry > search_entry(after)))
+            .collect::<Vec<(String, String)>>();
+        entries.sort();
+
+        Ok(entries
+            .into_iter()
+            .take(limit)
+            .map(|(_, username)| username)
+            .collect())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
     /// Retreieves the user ID matching the provided username.
     ///
     /// # Arguments
@@ -204,10 +771,11 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `username` - The username for which a corresponding user ID should
     /// be obtained
     fn user_id_for(&mut self, username: &str) -> Result<Option<u64>, ProviderError> {
-        self.cache.user_id_for(username).or_else(|_| {
-            self.persistent.user_id_for(username).and_then(|id| {
+        let cached = self.cache.user_id_for(username);
+        self.fall_back("name_resolver", cached, |hybrid| {
+            hybrid.persistent.user_id_for(username).and_then(|id| {
                 id.map_or(Ok(None), |id| {
-                    self.cache.set_combination(username, id).and(Ok(Some(id)))
+                    hybrid.cache.set_combination(username, id).and(Ok(Some(id)))
                 })
             })
         })
@@ -220,17 +788,111 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The user ID for which a corresponding username should be
     /// obtained
     fn username_for(&mut self, user_id: u64) -> Result<Option<String>, ProviderError> {
-        self.cache.username_for(user_id).or_else(|_| {
-            self.persistent.username_for(user_id).and_then(|username| {
-                username.map_or(Ok(None), |username| {
-                    self.cache
-                        .set_combination(&username, user_id)
-                        .and(Ok(Some(username)))
+        let cached = self.cache.username_for(user_id);
+        self.fall_back("name_resolver", cached, |hybrid| {
+            hybrid
+                .persistent
+                .username_for(user_id)
+                .and_then(|username| {
+                    username.map_or(Ok(None), |username| {
+                        hybrid
+                            .cache
+                            .set_combination(&username, user_id)
+                            .and(Ok(Some(username)))
+                    })
                 })
-            })
         })
     }
 
+    /// Retreives the user IDs matching each of the provided usernames, in the
+    /// order that the usernames were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `usernames` - The usernames for which corresponding user IDs should
+    /// be obtained
+    fn user_ids_for(&mut self, usernames: &[&str]) -> Result<Vec<Option<u64>>, ProviderError> {
+        let cached = self.cache.user_ids_for(usernames);
+        self.record_lookup("name_resolver", &cached);
+        let mut user_ids = cached.unwrap_or_else(|_| vec![None; usernames.len()]);
+
+        // Only the usernames that the cache couldn't resolve need to be
+        // looked up in the persistent layer
+        let missing = usernames
+            .iter()
+            .zip(user_ids.iter())
+            .filter(|(_, user_id)| user_id.is_none())
+            .map(|(username, _)| *username)
+            .collect::<Vec<&str>>();
+
+        if missing.is_empty() {
+            return Ok(user_ids);
+        }
+
+        // While the persistent layer is failing, only the cached usernames
+        // are resolved
+        let mut resolved = match self.guard(|hybrid| hybrid.persistent.user_ids_for(&missing)) {
+            Some(resolved) => resolved?.into_iter(),
+            None => return Ok(user_ids),
+        };
+        let mut combinations = Vec::new();
+
+        for (username, user_id) in usernames.iter().zip(user_ids.iter_mut()) {
+            if user_id.is_none() {
+                *user_id = resolved.next().flatten();
+
+                if let Some(id) = user_id {
+                    combinations.push((*username, *id));
+                }
+            }
+        }
+
+        cache_combinations(&mut self.cache, &combinations).map(|_| user_ids)
+    }
+
+    /// Retreives the usernames matching each of the provided user IDs, in the
+    /// order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The user IDs for which corresponding usernames should be
+    /// obtained
+    fn usernames_for(&mut self, user_ids: &[u64]) -> Result<Vec<Option<String>>, ProviderError> {
+        let cached = self.cache.usernames_for(user_ids);
+        self.record_lookup("name_resolver", &cached);
+        let mut usernames = cached.unwrap_or_else(|_| vec![None; user_ids.len()]);
+
+        let missing = user_ids
+            .iter()
+            .zip(usernames.iter())
+            .filter(|(_, username)| username.is_none())
+            .map(|(user_id, _)| *user_id)
+            .collect::<Vec<u64>>();
+
+        if missing.is_empty() {
+            return Ok(usernames);
+        }
+
+        let mut resolved = match self.guard(|hybrid| hybrid.persistent.usernames_for(&missing)) {
+            Some(resolved) => resolved?.into_iter(),
+            None => return Ok(usernames),
+        };
+
+        for username in usernames.iter_mut().filter(|username| username.is_none()) {
+            *username = resolved.next().flatten();
+        }
+
+        let combinations = usernames
+            .iter()
+            .zip(user_ids.iter())
+            .filter_map(|(username, user_id)| {
+                username.as_deref().map(|username| (username, *user_id))
+            })
+            .collect::<Vec<(&str, u64)>>();
+
+        cache_combinations(&mut self.cache, &combinations).map(|_| usernames)
+    }
+
     /// Stores a username to user ID / user ID to username mapping in a
     /// provider.
     ///
@@ -239,17 +901,161 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `username` - The username for which a corresponding user ID should be
     /// obtained
     fn set_combination(&mut self, username: &str, user_id: u64) -> Result<(), ProviderError> {
-        self.cache
-            .set_combination(username, user_id)
-            .and(self.persistent.set_combination(username, user_id))
+        write_both(
+            self.cache.set_combination(username, user_id),
+            self.persistent.set_combination(username, user_id),
+        )
+    }
+
+    /// Changes the username of the user with the given ID, recording the
+    /// user's old username in their username history.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username should be changed
+    /// * `new_username` - The username that should be assigned to the user
+    fn rename_user(
+        &mut self,
+        user_id: u64,
+        new_username: &str,
+    ) -> Result<Option<String>, ProviderError> {
+        // The persistent layer enforces username uniqueness, so the cache
+        // should only be updated once the rename has been committed
+        self.persistent
+            .rename_user(user_id, new_username)
+            .and_then(|old| self.cache.rename_user(user_id, new_username).map(|_| old))
+    }
+
+    /// Retreives each of the usernames previously held by the user with the
+    /// given ID, most recent first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose username history should be
+    /// obtained
+    fn previous_usernames(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        // The cache only retains renames that it has observed, so the
+        // persistent layer is authoritative here
+        self.persistent
+            .previous_usernames(user_id)
+            .or_else(|persistent| {
+                self.cache
+                    .previous_usernames(user_id)
+                    .map_err(|cache| ProviderError::Composite {
+                        cache: Box::new(cache),
+                        persistent: Box::new(persistent),
+                    })
+            })
+    }
+
+    /// Retreives up to `limit` usernames beginning with the given prefix,
+    /// ignoring case.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The string that each of the returned usernames should
+    /// begin with
+    /// * `after` - (optional) The username that each of the returned
+    /// usernames should follow, used as a pagination cursor
+    /// * `limit` - The maximum number of usernames that should be returned
+    fn search_usernames(
+        &mut self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ProviderError> {
+        // The cache's search index only contains names that have passed
+        // through it, so an empty result may simply mean a cold index
+        match self.cache.search_usernames(prefix, after, limit) {
+            Ok(usernames) if !usernames.is_empty() => Ok(usernames),
+            _ => self.persistent.search_usernames(prefix, after, limit),
+        }
     }
 }
 
+/// Swaps out a user's username mappings, provided that the user is still
+/// known by the expected username (or by none, if it is empty). Returns
+/// whether or not the mappings were swapped, and the user's username prior to
+/// running the script.
+///
+/// KEYS: the user's username, the new username's user ID, the search index,
+/// the user's username history, and the old username's user ID.
+/// ARGV: the user's ID, the new username, the expected username, and the
+/// search entries of the expected and new usernames.
+const RENAME_USER_SCRIPT: &str = r"
+local old = redis.call('GET', KEYS[1]) or ''
+if old ~= ARGV[3] then
+    return {0, old}
+end
+
+if old ~= '' then
+    redis.call('DEL', KEYS[5])
+    redis.call('ZREM', KEYS[3], ARGV[4])
+    redis.call('LPUSH', KEYS[4], old)
+end
+
+redis.call('MSET', KEYS[2], ARGV[1], KEYS[1], ARGV[2])
+redis.call('ZADD', KEYS[3], 0, ARGV[5])
+
+return {1, old}
+";
+
+/// Constructs the member under which the given username is stored in the
+/// cache's username search index. Members are ordered by their lowercase
+/// representation, such that prefix searches are case-insensitive.
+///
+/// # Arguments
+///
+/// * `username` - The username that should be indexed
+pub(crate) fn search_entry(username: &str) -> String {
+    format!("{}::{}", username.to_lowercase(), username)
+}
+
+/// Stores each of the provided username to user ID mappings in the cache in
+/// one round trip.
+///
+/// # Arguments
+///
+/// * `cache` - The cache in which the mappings should be stored
+/// * `combinations` - The username, user ID pairs that should be stored
+fn cache_combinations(
+    cache: &mut Cache,
+    combinations: &[(&str, u64)],
+) -> Result<(), ProviderError> {
+    if combinations.is_empty() {
+        return Ok(());
+    }
+
+    let mut mappings = redis::cmd("MSET");
+    let mut index = redis::cmd("ZADD");
+    index.arg(cache.key("usernames"));
+
+    for (username, user_id) in combinations {
+        mappings
+            .arg(cache.key(format_args!("user_id::{}", username)))
+            .arg(*user_id)
+            .arg(cache.key(format_args!("username::{}", user_id)))
+            .arg(*username);
+        index.arg(0).arg(search_entry(username));
+    }
+
+    redis::pipe()
+        .add_command(mappings)
+        .ignore()
+        .add_command(index)
+        .ignore()
+        .query(cache.connection)
+        .map_err(|e| e.into())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{super::super::super::spec::user::NewUser, *};
+    use super::{
+        super::super::super::spec::{backend::PersistentConnection, user::NewUser},
+        *,
+    };
+    use diesel::Connection;
     use dotenv;
-    use diesel::{Connection, mysql::MysqlConnection};
 
     use std::{default::Default, env, error::Error};
 
@@ -259,7 +1065,7 @@ mod tests {
 
         let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -286,6 +1092,74 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_rename_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        // Register Chudfitter as a user so that he can change his name
+        diesel::replace_into(users::table)
+            .values(NewUser::default().with_username("Chudfitter"))
+            .execute(&persistent_conn)?;
+
+        let id = users::dsl::users
+            .filter(users::dsl::username.eq("Chudfitter"))
+            .select(users::dsl::id)
+            .first(&persistent_conn)?;
+
+        let mut names = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        names.set_combination("Chudfitter", id)?;
+
+        assert_eq!(
+            names.rename_user(id, "Fitchudder")?,
+            Some("Chudfitter".to_owned())
+        );
+        assert_eq!(names.username_for(id)?.unwrap(), "Fitchudder");
+        assert_eq!(names.user_id_for("Fitchudder")?.unwrap(), id);
+        assert_eq!(names.previous_usernames(id)?[0], "Chudfitter");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        diesel::replace_into(users::table)
+            .values(NewUser::default().with_username("MrMouton"))
+            .execute(&persistent_conn)?;
+
+        let id = users::dsl::users
+            .filter(users::dsl::username.eq("MrMouton"))
+            .select(users::dsl::id)
+            .first(&persistent_conn)?;
+
+        let mut names = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        names.set_combination("MrMouton", id)?;
+
+        assert_eq!(
+            names.user_ids_for(&["MrMouton", "NotARealChatter"])?,
+            vec![Some(id), None]
+        );
+        assert_eq!(
+            names.usernames_for(&[id])?,
+            vec![Some("MrMouton".to_owned())]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_cache() -> Result<(), Box<dyn Error>> {
         dotenv::dotenv()?;
@@ -297,6 +1171,9 @@ mod tests {
 
         assert_eq!(names.username_for(42069)?.unwrap(), "MrMouton");
         assert_eq!(names.user_id_for("MrMouton")?.unwrap(), 42069);
+        assert!(names
+            .search_usernames("mrmou", None, 10)?
+            .contains(&"MrMouton".to_owned()));
 
         Ok(())
     }
@@ -307,7 +1184,7 @@ mod tests {
 
         // Open a connection with the MySQL server
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -334,4 +1211,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_memory() -> Result<(), Box<dyn Error>> {
+        let mut resolver = Memory::new();
+
+        resolver.set_combination("MrMouton", 1)?;
+        resolver.set_combination("mrmoutonfan", 2)?;
+        assert_eq!(
+            resolver.user_ids_for(&["MrMouton", "nobody"])?,
+            vec![Some(1), None]
+        );
+
+        assert_eq!(
+            resolver.rename_user(1, "MrMoutonV2")?,
+            Some("MrMouton".to_owned())
+        );
+        assert_eq!(resolver.user_id_for("MrMouton")?, None);
+        assert_eq!(resolver.username_for(1)?, Some("MrMoutonV2".to_owned()));
+        assert_eq!(resolver.previous_usernames(1)?, vec!["MrMouton".to_owned()]);
+
+        assert_eq!(
+            resolver.search_usernames("mrmouton", None, 10)?,
+            vec!["mrmoutonfan".to_owned(), "MrMoutonV2".to_owned()]
+        );
+        assert_eq!(
+            resolver.search_usernames("mrmouton", Some("mrmoutonfan"), 10)?,
+            vec!["MrMoutonV2".to_owned()]
+        );
+
+        Ok(())
+    }
 }
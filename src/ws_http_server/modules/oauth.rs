@@ -1 +1,1210 @@
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    http::{
+        header::{LOCATION, USER_AGENT as USER_AGENT_HEADER},
+        StatusCode,
+    },
+    web::{Data, Json, Path, Query},
+    Error as HttpError, HttpMessage, HttpRequest, HttpResponse, ResponseError, Scope,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use oauth2::{
+    basic::BasicClient, AuthUrl, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl,
+};
+use openssl::memcmp;
+use rand::{thread_rng, Rng};
+use redis::Connection as RedisConnection;
+use serde::{Deserialize, Serialize};
 
+use super::{
+    super::{
+        super::spec::{
+            backend::{PersistentConnection, Unsigned},
+            schema::{reddit_connected, twitch_connected, twitter_connected},
+            user::{is_valid_username, ConnectionId, NewUser, OauthConnection, Role, User},
+        },
+        keyring::Keyring,
+        problem::ErrorCode,
+        server::State,
+    },
+    name_resolver::Provider as NameResolver,
+    oauth_state::{PendingLogin, Provider as StateProvider},
+    refresh_tokens::{self, Tokens},
+    sessions::Provider as SessionsProvider,
+    throttle::{self, Subject},
+    two_factor::{self, ChallengeProvider},
+    users::{self, Provider as UsersProvider},
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{error::Error, fmt, iter, str::FromStr};
+
+/// The number of times that a random suffix will be appended to a taken
+/// username before registration is abandoned.
+const USERNAME_ATTEMPTS: usize = 5;
+
+/// The user agent that gnomegg identifies itself with when making requests to
+/// oauth providers. Reddit rejects requests carrying generic user agents.
+const USER_AGENT: &str = concat!("gnomegg/", env!("CARGO_PKG_VERSION"));
+
+/// The cookie in which the browser starting a login keeps its state
+/// parameter, such that only that browser may complete the login.
+const STATE_COOKIE: &str = "gnomegg_oauth_state";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the oauth module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/oauth").service(login).service(callback)
+}
+
+/// OauthProvider represents any of the services through which a user may log
+/// in to gnomegg.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OauthProvider {
+    Twitch,
+    Reddit,
+    Twitter,
+}
+
+impl OauthProvider {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Twitch => "twitch",
+            Self::Reddit => "reddit",
+            Self::Twitter => "twitter",
+        }
+    }
+
+    /// Gets the URL to which users are sent in order to authorize gnomegg.
+    fn auth_url(&self) -> &'static str {
+        match self {
+            Self::Twitch => "https://id.twitch.tv/oauth2/authorize",
+            Self::Reddit => "https://www.reddit.com/api/v1/authorize",
+            Self::Twitter => "https://twitter.com/i/oauth2/authorize",
+        }
+    }
+
+    /// Gets the URL at which authorization codes are exchanged for access
+    /// tokens.
+    fn token_url(&self) -> &'static str {
+        match self {
+            Self::Twitch => "https://id.twitch.tv/oauth2/token",
+            Self::Reddit => "https://www.reddit.com/api/v1/access_token",
+            Self::Twitter => "https://api.twitter.com/2/oauth2/token",
+        }
+    }
+
+    /// Gets the scopes that gnomegg requests from the provider.
+    fn scopes(&self) -> &'static [&'static str] {
+        match self {
+            Self::Twitch => &["user:read:email"],
+            Self::Reddit => &["identity"],
+            Self::Twitter => &["tweet.read", "users.read"],
+        }
+    }
+
+    /// Gets any additional query parameters that the provider expects in
+    /// authorization requests.
+    fn auth_params(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Twitch | Self::Twitter => &[],
+
+            // Reddit requires that gnomegg specify whether or not it wants a
+            // refresh token
+            Self::Reddit => &[("duration", "temporary")],
+        }
+    }
+
+    /// Determines whether or not the provider expects client credentials in
+    /// an Authorization header, rather than in the body of token requests.
+    fn uses_basic_auth(&self) -> bool {
+        match self {
+            Self::Twitch => false,
+            Self::Reddit | Self::Twitter => true,
+        }
+    }
+}
+
+impl fmt::Display for OauthProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+/// ParseOauthProviderError represents an error encountered while converting
+/// a string to an oauth provider.
+#[derive(Debug)]
+pub enum ParseOauthProviderError {
+    NoMatchingProvider,
+}
+
+impl fmt::Display for ParseOauthProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no oauth provider matches the provided string")
+    }
+}
+
+impl Error for ParseOauthProviderError {}
+
+impl FromStr for OauthProvider {
+    type Err = ParseOauthProviderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "twitch" => Ok(Self::Twitch),
+            "reddit" => Ok(Self::Reddit),
+            "twitter" => Ok(Self::Twitter),
+            _ => Err(ParseOauthProviderError::NoMatchingProvider),
+        }
+    }
+}
+
+/// OauthCredentials represents the credentials issued to gnomegg by an oauth
+/// provider.
+#[derive(Clone, Debug)]
+pub struct OauthCredentials {
+    /// The ID assigned to gnomegg by the provider
+    client_id: String,
+
+    /// The secret assigned to gnomegg by the provider
+    client_secret: String,
+
+    /// The URL of gnomegg's callback route for the provider
+    redirect_url: String,
+}
+
+impl OauthCredentials {
+    /// Creates a new set of oauth credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The ID assigned to gnomegg by the provider
+    /// * `client_secret` - The secret assigned to gnomegg by the provider
+    /// * `redirect_url` - The URL of gnomegg's callback route for the provider
+    /// (e.g. https://gnome.gg/oauth/twitch/callback)
+    pub fn new(client_id: String, client_secret: String, redirect_url: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            redirect_url,
+        }
+    }
+}
+
+/// OauthError represents any error encountered while logging a user in
+/// through an oauth provider.
+#[derive(Debug)]
+pub enum OauthError {
+    /// The server holds no credentials for the requested provider
+    UnconfiguredProvider,
+
+    /// The server's credentials for the provider are malformed
+    InvalidConfiguration(oauth2::url::ParseError),
+
+    /// The provider could not be reached, or rejected a request
+    RequestFailed(reqwest::Error),
+
+    /// The provider didn't describe the user's account
+    MissingIdentity,
+
+    /// The callback's state parameter doesn't match a login started by
+    /// gnomegg
+    StateMismatch,
+}
+
+impl fmt::Display for OauthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnconfiguredProvider => write!(f, "the oauth provider is not enabled"),
+            Self::InvalidConfiguration(e) => {
+                write!(f, "the oauth provider is misconfigured: {}", e)
+            }
+            Self::RequestFailed(e) => write!(f, "the oauth provider request failed: {}", e),
+            Self::MissingIdentity => write!(f, "the oauth provider did not identify the user"),
+            Self::StateMismatch => write!(f, "the oauth state parameter is invalid or expired"),
+        }
+    }
+}
+
+impl Error for OauthError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidConfiguration(e) => Some(e),
+            Self::RequestFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for OauthError {
+    /// Constructs an oauth error from the given reqwest error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The reqwest error that should be wrapped in the OauthError
+    fn from(e: reqwest::Error) -> Self {
+        Self::RequestFailed(e)
+    }
+}
+
+impl From<oauth2::url::ParseError> for OauthError {
+    /// Constructs an oauth error from the given URL parsing error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The parsing error that should be wrapped in the OauthError
+    fn from(e: oauth2::url::ParseError) -> Self {
+        Self::InvalidConfiguration(e)
+    }
+}
+
+impl ResponseError for OauthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnconfiguredProvider => StatusCode::NOT_FOUND,
+            Self::InvalidConfiguration(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::RequestFailed(_) | Self::MissingIdentity => StatusCode::BAD_GATEWAY,
+            Self::StateMismatch => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+impl ErrorCode for OauthError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnconfiguredProvider => "oauth_provider_disabled",
+            Self::InvalidConfiguration(_) => "oauth_provider_misconfigured",
+            Self::RequestFailed(_) => "oauth_request_failed",
+            Self::MissingIdentity => "oauth_identity_missing",
+            Self::StateMismatch => "oauth_state_mismatch",
+        }
+    }
+}
+
+/// Identity represents a user's account on an oauth provider.
+#[derive(Debug)]
+pub struct Identity {
+    /// The ID assigned to the user by the provider
+    id: String,
+
+    /// The username of the user on the provider
+    username: String,
+}
+
+impl Identity {
+    /// Retreives the ID assigned to the user by the provider.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Retreives the username of the user on the provider.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// TokenResponse represents the response of a provider to a successful
+/// authorization code exchange.
+#[derive(Deserialize)]
+struct TokenResponse {
+    /// The token with which gnomegg may act on behalf of the user
+    access_token: String,
+}
+
+/// TwitchUsers represents the response of the Twitch users API.
+#[derive(Deserialize)]
+struct TwitchUsers {
+    /// Each of the users described by the response
+    data: Vec<TwitchUser>,
+}
+
+/// TwitchUser represents a user described by the Twitch users API.
+#[derive(Deserialize)]
+struct TwitchUser {
+    /// The ID assigned to the user by Twitch
+    id: String,
+
+    /// The user's Twitch username
+    login: String,
+}
+
+/// RedditUser represents the response of the Reddit identity API.
+#[derive(Deserialize)]
+struct RedditUser {
+    /// The ID assigned to the user by Reddit
+    id: String,
+
+    /// The user's Reddit username
+    name: String,
+}
+
+/// TwitterUser represents the response of the Twitter users API.
+#[derive(Deserialize)]
+struct TwitterUser {
+    /// The user described by the response
+    data: TwitterAccount,
+}
+
+/// TwitterAccount represents a user described by the Twitter users API.
+#[derive(Deserialize)]
+struct TwitterAccount {
+    /// The ID assigned to the user by Twitter
+    id: String,
+
+    /// The user's Twitter handle
+    username: String,
+}
+
+/// CallbackQuery represents the query parameters with which a provider
+/// redirects a user back to gnomegg.
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    /// The authorization code issued to gnomegg by the provider
+    code: String,
+
+    /// The state parameter issued to the user by gnomegg upon login
+    state: String,
+}
+
+/// Login represents the result of a successful oauth login.
+#[derive(Serialize)]
+pub struct Login {
+    /// The tokens with which the user may authenticate themselves, unless
+    /// they must present a second factor first
+    #[serde(flatten)]
+    tokens: Option<Tokens>,
+
+    /// The challenge that must be answered with a two-factor code before
+    /// tokens are issued, if the user is required to present one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    challenge: Option<String>,
+
+    /// The profile of the user who logged in
+    user: User,
+
+    /// Whether or not the user's account was created by the login
+    created: bool,
+}
+
+/// Redirects the user to the specified provider, so that they may authorize
+/// gnomegg to access their account. The state parameter and PKCE verifier
+/// issued for the login are recorded, so that the callback may be verified.
+/// The state parameter is also kept in a cookie, so that the callback must
+/// be made by the same browser.
+#[get("/{provider}/login")]
+pub async fn login(
+    state: Data<State>,
+    provider: Path<OauthProvider>,
+) -> Result<HttpResponse, HttpError> {
+    let credentials = state
+        .oauth_credentials(*provider)
+        .ok_or(OauthError::UnconfiguredProvider)?;
+
+    let client = BasicClient::new(
+        ClientId::new(credentials.client_id.clone()),
+        Some(ClientSecret::new(credentials.client_secret.clone())),
+        AuthUrl::new(provider.auth_url().to_owned()).map_err(OauthError::from)?,
+        None,
+    )
+    .set_redirect_uri(
+        RedirectUrl::new(credentials.redirect_url.clone()).map_err(OauthError::from)?,
+    );
+
+    let req = provider
+        .scopes()
+        .iter()
+        .fold(client.authorize_url(CsrfToken::new_random), |req, scope| {
+            req.add_scope(oauth2::Scope::new((*scope).to_owned()))
+        });
+    let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+    let (url, csrf_token) = provider
+        .auth_params()
+        .iter()
+        .fold(req, |req, (name, value)| req.add_extra_param(*name, *value))
+        .set_pkce_challenge(challenge)
+        .url();
+
+    let mut conn = state.cache_connection()?;
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .register_state(
+            csrf_token.secret(),
+            &PendingLogin::new(*provider, &verifier),
+        )?;
+
+    let cookie = Cookie::build(STATE_COOKIE, csrf_token.secret().clone())
+        .path("/oauth")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .finish();
+
+    Ok(HttpResponse::Found()
+        .header(LOCATION, url.to_string())
+        .cookie(cookie)
+        .finish())
+}
+
+/// Completes a login through the specified provider, exchanging the issued
+/// authorization code for the user's identity. Users that have not logged in
+/// through the provider before are registered.
+#[get("/{provider}/callback")]
+pub async fn callback(
+    state: Data<State>,
+    req: HttpRequest,
+    provider: Path<OauthProvider>,
+    query: Query<CallbackQuery>,
+) -> Result<Json<Login>, HttpError> {
+    let provider = *provider;
+    let credentials = state
+        .oauth_credentials(provider)
+        .ok_or(OauthError::UnconfiguredProvider)?;
+
+    let mut conn = state.cache_connection()?;
+
+    // Addresses making repeated failed callbacks are locked out, so that
+    // stolen or guessed authorization codes can't be tried in bulk
+    let ip = throttle::client_ip(&req, state.trusted_proxies());
+    throttle::check(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+    // Callbacks must be made by the browser that started the login, so that
+    // a user can't be made to complete a login started by someone else
+    let issued = req.cookie(STATE_COOKIE);
+    if !state_matches(issued.as_ref().map(Cookie::value), &query.state) {
+        throttle::record_failure(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+        return Err(OauthError::StateMismatch.into());
+    }
+
+    // Callbacks must redeem a state parameter issued by the login route for
+    // the same provider
+    let pending = match Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .take_state(&query.state)?
+        .filter(|pending| pending.provider() == provider)
+    {
+        Some(pending) => pending,
+        None => {
+            throttle::record_failure(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+            return Err(OauthError::StateMismatch.into());
+        }
+    };
+
+    let access_token =
+        match exchange_code(provider, credentials, &query.code, &pending.verifier()).await {
+            Ok(access_token) => access_token,
+            Err(e) => {
+                throttle::record_failure(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+                return Err(e.into());
+            }
+        };
+    let identity = fetch_identity(provider, credentials, &access_token).await?;
+
+    let persistent_conn = state.persistent_connection()?;
+
+    let (user, created) = login_user(
+        &mut conn,
+        state.key_prefix(),
+        &persistent_conn,
+        state.keyring(),
+        state.default_roles(),
+        provider,
+        &identity,
+    )?;
+
+    // Privileged users who have enrolled in two-factor authentication must
+    // present a code before being issued a session
+    if two_factor::requires_code(
+        &mut Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        ),
+        &mut Persistent::new(&persistent_conn).with_keyring(state.keyring()),
+        user.id(),
+    )? {
+        return Ok(Json(Login {
+            tokens: None,
+            challenge: Some(
+                Cache::new(&mut conn)
+                    .with_prefix(state.key_prefix())
+                    .issue_challenge(user.id())?,
+            ),
+            user,
+            created,
+        }));
+    }
+
+    let session_id = Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .issue_session(
+            user.id(),
+            req.headers()
+                .get(USER_AGENT_HEADER)
+                .and_then(|header| header.to_str().ok()),
+            Some(&throttle::client_ip(&req, state.trusted_proxies())),
+        )?;
+    let tokens =
+        refresh_tokens::issue_tokens(&state, &mut conn, &persistent_conn, user.id(), session_id)?;
+
+    Ok(Json(Login {
+        tokens: Some(tokens),
+        challenge: None,
+        user,
+        created,
+    }))
+}
+
+/// Determines whether or not the state parameter presented to a callback
+/// matches the one kept by the browser making the callback. States are
+/// compared in constant time.
+///
+/// # Arguments
+///
+/// * `issued` - The state parameter kept by the browser, if any
+/// * `presented` - The state parameter presented to the callback
+fn state_matches(issued: Option<&str>, presented: &str) -> bool {
+    issued.is_some_and(|issued| {
+        issued.len() == presented.len() && memcmp::eq(issued.as_bytes(), presented.as_bytes())
+    })
+}
+
+/// Exchanges the given authorization code for an access token.
+///
+/// # Arguments
+///
+/// * `provider` - The provider that issued the authorization code
+/// * `credentials` - The credentials issued to gnomegg by the provider
+/// * `code` - The authorization code that should be exchanged
+/// * `verifier` - The PKCE code verifier issued for the login
+async fn exchange_code(
+    provider: OauthProvider,
+    credentials: &OauthCredentials,
+    code: &str,
+    verifier: &PkceCodeVerifier,
+) -> Result<String, OauthError> {
+    let mut params = vec![
+        ("code", code),
+        ("code_verifier", verifier.secret().as_str()),
+        ("grant_type", "authorization_code"),
+        ("redirect_uri", credentials.redirect_url.as_str()),
+    ];
+
+    // Twitch reports granted scopes as an array rather than a space-delimited
+    // string, which the oauth2 crate's standard token response rejects, so
+    // codes are exchanged by hand
+    let req = http_client()?.post(provider.token_url());
+    let req = if provider.uses_basic_auth() {
+        req.basic_auth(&credentials.client_id, Some(&credentials.client_secret))
+    } else {
+        params.push(("client_id", credentials.client_id.as_str()));
+        params.push(("client_secret", credentials.client_secret.as_str()));
+
+        req
+    };
+
+    req.form(&params)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<TokenResponse>()
+        .await
+        .map(|resp| resp.access_token)
+        .map_err(|e| e.into())
+}
+
+/// Fetches the identity of the user to whom the given access token was
+/// issued.
+///
+/// # Arguments
+///
+/// * `provider` - The provider that issued the access token
+/// * `credentials` - The credentials issued to gnomegg by the provider
+/// * `access_token` - The access token issued on behalf of the user
+async fn fetch_identity(
+    provider: OauthProvider,
+    credentials: &OauthCredentials,
+    access_token: &str,
+) -> Result<Identity, OauthError> {
+    match provider {
+        OauthProvider::Twitch => http_client()?
+            .get("https://api.twitch.tv/helix/users")
+            .bearer_auth(access_token)
+            .header("Client-Id", credentials.client_id.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TwitchUsers>()
+            .await?
+            .data
+            .into_iter()
+            .next()
+            .map(|user| Identity {
+                id: user.id,
+                username: user.login,
+            })
+            .ok_or(OauthError::MissingIdentity),
+        OauthProvider::Reddit => http_client()?
+            .get("https://oauth.reddit.com/api/v1/me")
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RedditUser>()
+            .await
+            .map(|user| Identity {
+                id: user.id,
+                username: user.name,
+            })
+            .map_err(|e| e.into()),
+        OauthProvider::Twitter => http_client()?
+            .get("https://api.twitter.com/2/users/me")
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TwitterUser>()
+            .await
+            .map(|user| Identity {
+                id: user.data.id,
+                username: user.data.username,
+            })
+            .map_err(|e| e.into()),
+    }
+}
+
+/// Builds an HTTP client suitable for making requests to oauth providers.
+fn http_client() -> Result<reqwest::Client, OauthError> {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|e| e.into())
+}
+
+/// Gets the user linked to the given identity, registering a new user if the
+/// identity has not been seen before. Returns the user, and whether or not
+/// they were registered.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `keyring` - The keyring with which linked account IDs should be
+/// encrypted, if any
+/// * `default_roles` - The roles that should be assigned to new users
+/// * `provider` - The provider through which the user logged in
+/// * `identity` - The user's account on the provider
+pub(crate) fn login_user(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    keyring: Option<&Keyring>,
+    default_roles: &[Role],
+    provider: OauthProvider,
+    identity: &Identity,
+) -> Result<(User, bool), ProviderError> {
+    let mut hybrid = Hybrid::new(
+        Cache::new(conn)
+            .with_prefix(key_prefix)
+            .with_keyring(keyring),
+        Persistent::new(persistent_conn).with_keyring(keyring),
+    );
+
+    // Cached links may outlive the accounts they point to, so the linked
+    // account must still exist
+    if let Some(user) = hybrid
+        .user_for_connection(provider, identity.id())?
+        .map_or(Ok(None), |user_id| hybrid.get_user(user_id))?
+    {
+        return Ok((user, false));
+    }
+
+    let username = available_username(&mut hybrid, identity.username())?;
+    let user = users::register_user(
+        conn,
+        key_prefix,
+        persistent_conn,
+        default_roles,
+        &NewUser::default().with_username(&username),
+    )?;
+
+    Hybrid::new(
+        Cache::new(conn)
+            .with_prefix(key_prefix)
+            .with_keyring(keyring),
+        Persistent::new(persistent_conn).with_keyring(keyring),
+    )
+    .link_connection(provider, user.id(), identity.id())?;
+
+    Ok((user, true))
+}
+
+/// Derives an unclaimed gnomegg username from the user's username on an
+/// oauth provider.
+///
+/// # Arguments
+///
+/// * `resolver` - The name resolver used to check whether or not a username
+/// has been claimed
+/// * `provider_username` - The user's username on the oauth provider
+fn available_username<T: NameResolver>(
+    resolver: &mut T,
+    provider_username: &str,
+) -> Result<String, ProviderError> {
+    // Leave room for a disambiguating suffix within the username length limit
+    let mut base = provider_username
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(15)
+        .collect::<String>();
+    if base.len() < 3 {
+        base = format!("gnome{}", base);
+    }
+
+    if is_valid_username(&base) && resolver.user_id_for(&base)?.is_none() {
+        return Ok(base);
+    }
+
+    for _ in 0..USERNAME_ATTEMPTS {
+        let candidate = format!("{}_{}", base, thread_rng().gen_range(0, 10_000));
+
+        if resolver.user_id_for(&candidate)?.is_none() {
+            return Ok(candidate);
+        }
+    }
+
+    Err(ProviderError::InvalidArgument { arg: "username" })
+}
+
+/// Evaluates the given expression with the connection table of the given
+/// oauth provider in scope under the given name.
+macro_rules! with_connection_table {
+    ($provider:expr, $table:ident => $body:expr) => {
+        match $provider {
+            OauthProvider::Twitch => {
+                use twitch_connected as $table;
+                $body
+            }
+            OauthProvider::Reddit => {
+                use reddit_connected as $table;
+                $body
+            }
+            OauthProvider::Twitter => {
+                use twitter_connected as $table;
+                $body
+            }
+        }
+    };
+}
+
+/// Gets the redis key under which the user linked to the given account on an
+/// oauth provider is cached. The account ID is hashed with a key derived
+/// from the given keyring, if any.
+///
+/// # Arguments
+///
+/// * `provider` - The oauth provider that issued the account ID
+/// * `external_id` - The ID assigned to the account by the provider
+/// * `keyring` - The keyring with which account IDs are encrypted, if any
+fn connection_key(provider: OauthProvider, external_id: &str, keyring: Option<&Keyring>) -> String {
+    let id = ConnectionId::new(external_id, keyring.map(Keyring::index_key).as_ref());
+    let hash = id
+        .id_hash()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!("oauth::{}::{}", provider, hash)
+}
+
+/// Gets each of the hashes under which the given account ID may have been
+/// stored: its hash under a key derived from each of the keyring's keys, if
+/// any, and its unkeyed hash, under which IDs stored before encryption was
+/// enabled are found.
+///
+/// # Arguments
+///
+/// * `external_id` - The ID assigned to the account by the provider
+/// * `keyring` - The keyring with which account IDs are encrypted, if any
+fn connection_hashes(external_id: &str, keyring: Option<&Keyring>) -> Vec<Vec<u8>> {
+    keyring
+        .map_or_else(Vec::new, Keyring::index_keys)
+        .iter()
+        .map(Some)
+        .chain(iter::once(None))
+        .map(|key| ConnectionId::new(external_id, key).id_hash().to_vec())
+        .collect()
+}
+
+/// Encrypts each linked account ID stored in plain text, or with a retired
+/// key, with the keyring's current key, and hashes it with a key derived
+/// from the keyring's current key. Returns the number of IDs that were
+/// re-encrypted.
+///
+/// # Arguments
+///
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `keyring` - The keyring with which the IDs should be encrypted
+pub fn reencrypt_connections(
+    persistent_conn: &PersistentConnection,
+    keyring: &Keyring,
+) -> Result<usize, ProviderError> {
+    let mut reencrypted = 0;
+
+    for provider in &[
+        OauthProvider::Twitch,
+        OauthProvider::Reddit,
+        OauthProvider::Twitter,
+    ] {
+        let stored = with_connection_table!(*provider, table => table::table
+            .select((table::dsl::user_id, table::dsl::id_hash, table::dsl::id_value))
+            .load::<(u64, Option<Vec<u8>>, Option<String>)>(persistent_conn))?;
+
+        for (user_id, hash, value) in stored {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            let external_id = keyring.open(&value)?;
+            let id = ConnectionId::new(&external_id, Some(&keyring.index_key()));
+
+            if !keyring.needs_reseal(&value) && hash.as_deref() == Some(id.id_hash()) {
+                continue;
+            }
+
+            with_connection_table!(*provider, table => diesel::update(table::table.find(Unsigned(user_id)))
+                .set((
+                    table::dsl::id_hash.eq(id.id_hash()),
+                    table::dsl::id_value.eq(keyring.seal(id.id())?),
+                ))
+                .execute(persistent_conn))?;
+
+            reencrypted += 1;
+        }
+    }
+
+    Ok(reencrypted)
+}
+
+/// Provider represents an arbitrary backend for the oauth connections
+/// service, which links accounts on oauth providers to gnomegg users.
+pub trait Provider {
+    /// Gets the ID of the user linked to the given account on an oauth
+    /// provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn user_for_connection(
+        &mut self,
+        provider: OauthProvider,
+        external_id: &str,
+    ) -> Result<Option<u64>, ProviderError>;
+
+    /// Links the given account on an oauth provider to the user with the
+    /// given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn link_connection(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+        external_id: &str,
+    ) -> Result<(), ProviderError>;
+
+    /// Gets the ID of the account on an oauth provider linked to the user
+    /// with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    fn connected_id(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+    ) -> Result<Option<String>, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the ID of the user linked to the given account on an oauth
+    /// provider from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn user_for_connection(
+        &mut self,
+        provider: OauthProvider,
+        external_id: &str,
+    ) -> Result<Option<u64>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(connection_key(provider, external_id, self.keyring)))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Links the given account on an oauth provider to the user with the
+    /// given ID in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn link_connection(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+        external_id: &str,
+    ) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(connection_key(provider, external_id, self.keyring)))
+            .arg(user_id)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the ID of the account on an oauth provider linked to the user
+    /// with the given ID from the redis caching layer. Account IDs are only
+    /// cached in hashed form, so the caching layer can't resolve the account
+    /// linked to a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    fn connected_id(
+        &mut self,
+        _provider: OauthProvider,
+        _user_id: u64,
+    ) -> Result<Option<String>, ProviderError> {
+        Ok(None)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets the ID of the user linked to the given account on an oauth
+    /// provider from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn user_for_connection(
+        &mut self,
+        provider: OauthProvider,
+        external_id: &str,
+    ) -> Result<Option<u64>, ProviderError> {
+        let hashes = connection_hashes(external_id, self.keyring);
+
+        with_connection_table!(provider, table => table::table
+            .filter(table::dsl::id_hash.eq_any(hashes))
+            .select(table::dsl::user_id)
+            .first::<u64>(self.connection)
+            .optional()
+            .map_err(|e| e.into()))
+    }
+
+    /// Links the given account on an oauth provider to the user with the
+    /// given ID in the MySQL database. The account ID is encrypted if the
+    /// helper holds a keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn link_connection(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+        external_id: &str,
+    ) -> Result<(), ProviderError> {
+        let id = ConnectionId::new(external_id, self.keyring.map(Keyring::index_key).as_ref());
+        let value = self.seal(id.id())?;
+
+        with_connection_table!(provider, table => diesel::replace_into(table::table)
+            .values((
+                table::dsl::user_id.eq(Unsigned(user_id)),
+                table::dsl::id_hash.eq(id.id_hash()),
+                table::dsl::id_value.eq(value),
+            ))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into()))
+    }
+
+    /// Gets the ID of the account on an oauth provider linked to the user
+    /// with the given ID from the MySQL database, decrypting it if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    fn connected_id(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+    ) -> Result<Option<String>, ProviderError> {
+        with_connection_table!(provider, table => table::table
+            .find(Unsigned(user_id))
+            .select(table::dsl::id_value)
+            .first::<Option<String>>(self.connection)
+            .optional())?
+        .flatten()
+        .map_or(Ok(None), |value| self.open(&value).map(Some))
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Gets the ID of the user linked to the given account on an oauth
+    /// provider, populating the cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn user_for_connection(
+        &mut self,
+        provider: OauthProvider,
+        external_id: &str,
+    ) -> Result<Option<u64>, ProviderError> {
+        match self.cache.user_for_connection(provider, external_id) {
+            Ok(Some(user_id)) => Ok(Some(user_id)),
+            _ => self
+                .persistent
+                .user_for_connection(provider, external_id)
+                .and_then(|user_id| {
+                    user_id.map_or(Ok(None), |user_id| {
+                        self.cache
+                            .link_connection(provider, user_id, external_id)
+                            .map(|_| Some(user_id))
+                    })
+                }),
+        }
+    }
+
+    /// Links the given account on an oauth provider to the user with the
+    /// given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    /// * `external_id` - The ID assigned to the account by the provider
+    fn link_connection(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+        external_id: &str,
+    ) -> Result<(), ProviderError> {
+        self.persistent
+            .link_connection(provider, user_id, external_id)
+            .and_then(|_| self.cache.link_connection(provider, user_id, external_id))
+    }
+
+    /// Gets the ID of the account on an oauth provider linked to the user
+    /// with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that issued the account ID
+    /// * `user_id` - The ID of the gnomegg user
+    fn connected_id(
+        &mut self,
+        provider: OauthProvider,
+        user_id: u64,
+    ) -> Result<Option<String>, ProviderError> {
+        self.persistent.connected_id(provider, user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::Connection;
+
+    use std::env;
+
+    #[test]
+    fn test_state_matches() {
+        assert!(state_matches(Some("abc123"), "abc123"));
+
+        assert!(!state_matches(None, "abc123"));
+        assert!(!state_matches(Some("abc123"), "abc124"));
+        assert!(!state_matches(Some("abc"), "abc123"));
+    }
+
+    #[test]
+    fn test_login() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let identity = Identity {
+            id: "8675309".to_owned(),
+            username: "Bogsworth".to_owned(),
+        };
+
+        let keyring = Keyring::new("test", vec![1; 32])?;
+
+        let (user, created) = login_user(
+            &mut conn,
+            "",
+            &persistent_conn,
+            Some(&keyring),
+            &[],
+            OauthProvider::Twitch,
+            &identity,
+        )?;
+
+        // Logging in again should resolve to the same account
+        let (returning, created_again) = login_user(
+            &mut conn,
+            "",
+            &persistent_conn,
+            Some(&keyring),
+            &[],
+            OauthProvider::Twitch,
+            &identity,
+        )?;
+        assert!(created);
+        assert!(!created_again);
+        assert_eq!(returning.id(), user.id());
+
+        // The linked account ID must be stored encrypted, but be readable
+        // through the provider
+        let stored = twitch_connected::table
+            .find(Unsigned(user.id()))
+            .select(twitch_connected::dsl::id_value)
+            .first::<Option<String>>(&persistent_conn)?;
+        assert!(stored.is_some_and(|value| Keyring::is_sealed(&value)));
+        assert_eq!(
+            Persistent::new(&persistent_conn)
+                .with_keyring(Some(&keyring))
+                .connected_id(OauthProvider::Twitch, user.id())?
+                .as_deref(),
+            Some(identity.id())
+        );
+
+        Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn))
+            .delete_user(user.id())?;
+
+        Ok(())
+    }
+}
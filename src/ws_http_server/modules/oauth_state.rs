@@ -0,0 +1,138 @@
+use oauth2::PkceCodeVerifier;
+use serde::{Deserialize, Serialize};
+
+use super::{oauth::OauthProvider, Cache, ProviderError};
+
+/// The number of seconds that a user has to complete an oauth login after
+/// being redirected to the provider.
+pub const STATE_TTL: u64 = 10 * 60;
+
+/// PendingLogin represents an oauth login that has been started, but not yet
+/// completed by the provider redirecting the user back to gnomegg.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PendingLogin {
+    /// The provider through which the user is logging in
+    provider: OauthProvider,
+
+    /// The PKCE code verifier corresponding to the challenge sent to the
+    /// provider
+    verifier: String,
+}
+
+impl PendingLogin {
+    /// Creates a new pending login.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider through which the user is logging in
+    /// * `verifier` - The PKCE code verifier corresponding to the challenge
+    /// sent to the provider
+    pub fn new(provider: OauthProvider, verifier: &PkceCodeVerifier) -> Self {
+        Self {
+            provider,
+            verifier: verifier.secret().clone(),
+        }
+    }
+
+    /// Retreives the provider through which the user is logging in.
+    pub fn provider(&self) -> OauthProvider {
+        self.provider
+    }
+
+    /// Retreives the PKCE code verifier that must be presented to the
+    /// provider in order to exchange the issued authorization code.
+    pub fn verifier(&self) -> PkceCodeVerifier {
+        PkceCodeVerifier::new(self.verifier.clone())
+    }
+}
+
+/// Provider represents an arbitrary backend for the oauth state service,
+/// which keeps track of the state parameters issued to users being redirected
+/// to oauth providers. Pending logins are ephemeral, and are therefore only
+/// stored in the caching layer.
+pub trait Provider {
+    /// Records a pending login under the given state parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state parameter sent to the provider
+    /// * `login` - The login that the state parameter was issued for
+    fn register_state(&mut self, state: &str, login: &PendingLogin) -> Result<(), ProviderError>;
+
+    /// Gets the pending login issued the given state parameter, if it exists
+    /// and has not expired. Each state parameter may only be used once.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state parameter returned by the provider
+    fn take_state(&mut self, state: &str) -> Result<Option<PendingLogin>, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records a pending login under the given state parameter in the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state parameter sent to the provider
+    /// * `login` - The login that the state parameter was issued for
+    fn register_state(&mut self, state: &str, login: &PendingLogin) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(format_args!("oauth_state::{}", state)))
+            .arg(serde_json::to_string(login)?)
+            .arg("EX")
+            .arg(STATE_TTL)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets and removes the pending login issued the given state parameter
+    /// from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state parameter returned by the provider
+    fn take_state(&mut self, state: &str) -> Result<Option<PendingLogin>, ProviderError> {
+        let key = self.key(format_args!("oauth_state::{}", state));
+
+        // The state is removed in the same transaction that reads it, so that
+        // a callback can't be replayed
+        let (raw,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .query(self.connection)?;
+
+        raw.map_or(Ok(None), |raw| {
+            serde_json::from_str(&raw).map(Some).map_err(|e| e.into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_cache() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut states = Cache::new(&mut conn);
+        let login = PendingLogin::new(
+            OauthProvider::Twitch,
+            &PkceCodeVerifier::new("verifier".to_owned()),
+        );
+        states.register_state("state", &login)?;
+
+        // States may only be redeemed once
+        assert_eq!(states.take_state("state")?, Some(login));
+        assert_eq!(states.take_state("state")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+
+use super::{
+    super::super::spec::{
+        backend::last_insert_id,
+        pin::{NewPinnedMessage, PinnedMessage},
+        schema::pins,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The redis key holding the pinned message, or null if no message is
+/// pinned.
+const PIN_KEY: &str = "pinned_message";
+
+/// The redis key holding the ID of the most recently pinned message.
+const LAST_ID_KEY: &str = "last_pin_id";
+
+/// Provider represents an arbitrary backend for the pins service, which
+/// stores the message currently pinned above the chat.
+pub trait Provider {
+    /// Gets the pinned message, if a message is pinned and hasn't expired.
+    fn pinned_message(&mut self) -> Result<Option<PinnedMessage>, ProviderError>;
+
+    /// Pins the given message, replacing any message that is already pinned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - The message that should be pinned
+    fn pin_message(&mut self, pin: &NewPinnedMessage) -> Result<PinnedMessage, ProviderError>;
+
+    /// Unpins the pinned message, if there is one.
+    fn unpin_message(&mut self) -> Result<(), ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Gets the pinned message from the redis caching layer, or None if the
+    /// cache doesn't know whether or not a message is pinned.
+    fn cached_pin(&mut self) -> Result<Option<Option<PinnedMessage>>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(PIN_KEY))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |pin| {
+                serde_json::from_str(&pin).map(Some).map_err(|e| e.into())
+            })
+    }
+
+    /// Caches the pinned message, or the absence of one. Pins are evicted
+    /// from the redis caching layer once they expire.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - (optional) The pinned message
+    fn cache_pin(&mut self, pin: Option<&PinnedMessage>) -> Result<(), ProviderError> {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(PIN_KEY)).arg(serde_json::to_string(&pin)?);
+
+        if let Some(expires_at) = pin.and_then(|pin| pin.expires_at()) {
+            cmd.arg("EX")
+                .arg((expires_at - Utc::now()).num_seconds().max(1));
+        }
+
+        cmd.query(self.connection).map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the pinned message from the redis caching layer, if a message is
+    /// pinned and hasn't expired.
+    fn pinned_message(&mut self) -> Result<Option<PinnedMessage>, ProviderError> {
+        Ok(self
+            .cached_pin()?
+            .flatten()
+            .filter(|pin| pin.is_active(Utc::now())))
+    }
+
+    /// Pins the given message in the redis caching layer, replacing any
+    /// message that is already pinned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - The message that should be pinned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::pin::NewPinnedMessage, ws_http_server::modules::{Cache, pins::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut pins = Cache::new(&mut conn);
+    /// let pin = pins.pin_message(&NewPinnedMessage::new(69420, "MrMouton", "Debate at 5", Utc::now()))?;
+    ///
+    /// assert_eq!(pins.pinned_message()?, Some(pin));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn pin_message(&mut self, pin: &NewPinnedMessage) -> Result<PinnedMessage, ProviderError> {
+        let id: u64 = redis::cmd("INCR")
+            .arg(self.key(LAST_ID_KEY))
+            .query(self.connection)?;
+        let pin = pin.with_id(id);
+
+        self.cache_pin(Some(&pin)).map(|_| pin)
+    }
+
+    /// Unpins the pinned message in the redis caching layer, if there is one.
+    fn unpin_message(&mut self) -> Result<(), ProviderError> {
+        self.cache_pin(None)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets the most recently pinned message from the MySQL database, if it
+    /// hasn't been unpinned and hasn't expired.
+    fn pinned_message(&mut self) -> Result<Option<PinnedMessage>, ProviderError> {
+        pins::table
+            .filter(pins::dsl::unpinned_at.is_null())
+            .filter(
+                pins::dsl::expires_at
+                    .is_null()
+                    .or(pins::dsl::expires_at.gt(Utc::now().naive_utc())),
+            )
+            .order(pins::dsl::id.desc())
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Records the given pin in the MySQL database, unpinning any message
+    /// that is already pinned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - The message that should be pinned
+    fn pin_message(&mut self, pin: &NewPinnedMessage) -> Result<PinnedMessage, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            self.unpin_message()?;
+
+            diesel::insert_into(pins::table)
+                .values(pin)
+                .execute(connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched separately
+            last_insert_id(connection)
+                .map(|id| pin.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Marks the pinned message as unpinned in the MySQL database, if there
+    /// is one.
+    fn unpin_message(&mut self) -> Result<(), ProviderError> {
+        diesel::update(pins::table.filter(pins::dsl::unpinned_at.is_null()))
+            .set(pins::dsl::unpinned_at.eq(Utc::now().naive_utc()))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets the pinned message, populating the cache from the persistent
+    /// layer if the cache doesn't know whether or not a message is pinned.
+    fn pinned_message(&mut self) -> Result<Option<PinnedMessage>, ProviderError> {
+        if let Some(pin) = self.cache.cached_pin()? {
+            return Ok(pin.filter(|pin| pin.is_active(Utc::now())));
+        }
+
+        // The absence of a pin is cached as well, so that chatters
+        // connecting while no message is pinned don't each query the
+        // persistent layer
+        let pin = self.persistent.pinned_message()?;
+        self.cache.cache_pin(pin.as_ref()).map(|_| pin)
+    }
+
+    /// Pins the given message in the persistent layer, and caches it under
+    /// the ID that it was assigned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - The message that should be pinned
+    fn pin_message(&mut self, pin: &NewPinnedMessage) -> Result<PinnedMessage, ProviderError> {
+        let pin = self.persistent.pin_message(pin)?;
+
+        self.cache.cache_pin(Some(&pin)).map(|_| pin)
+    }
+
+    /// Unpins the pinned message in the active provider, if there is one.
+    fn unpin_message(&mut self) -> Result<(), ProviderError> {
+        self.persistent
+            .unpin_message()
+            .and_then(|_| self.cache.unpin_message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use chrono::Duration;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut pins = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        let pin = pins.pin_message(
+            &NewPinnedMessage::new(69420, "MrMouton", "Debate at 5", Utc::now())
+                .with_ttl(Some(Duration::minutes(5))),
+        )?;
+        assert_eq!(pins.pinned_message()?, Some(pin.clone()));
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL").arg(PIN_KEY).query::<()>(&mut conn)?;
+        let mut pins = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert_eq!(pins.pinned_message()?, Some(pin));
+
+        pins.unpin_message()?;
+        assert_eq!(pins.pinned_message()?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use actix_web::{
+    web::{Data, Json, Query},
+    Error as HttpError, HttpRequest, Scope,
+};
+use serde::Deserialize;
+use tokio::time;
+
+use super::{
+    super::{auth::AuthedUser, server::State},
+    events::{self, EventBatch, EventId, Watch},
+    throttle, ProviderError,
+};
+
+use std::time::Duration;
+
+/// The number of seconds that a poll waits for an event if the client
+/// doesn't say otherwise.
+const DEFAULT_WAIT_SECS: u64 = 25;
+
+/// The maximum number of seconds that a poll may wait for an event. Polls are
+/// answered well before most proxies give up on a request.
+const MAX_WAIT_SECS: u64 = 30;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the poll module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/poll").service(poll_events)
+}
+
+/// PollQuery represents the parameters accepted by the long-polling route.
+#[derive(Deserialize)]
+pub struct PollQuery {
+    /// (optional) The cursor returned by the client's previous poll. Clients
+    /// that omit it are immediately given the state of the chat, and a cursor
+    /// from which to start.
+    since: Option<String>,
+
+    /// (optional) The number of seconds to wait for an event, should none
+    /// have been relayed since the client's previous poll
+    wait: Option<u64>,
+}
+
+/// Gets the events pushed to the polling chatter since the client's previous
+/// poll, for clients that can hold neither a WebSocket nor an event stream
+/// open. Events are filtered as they are for event streams. A client's first
+/// poll is answered with the state of the chat, and a cursor from which to
+/// start. If no events were
+/// pushed in the meantime, the request is held until one is, or until the
+/// requested wait elapses, after which the client should poll again with the
+/// cursor it was given. Each poll renews the registration of the client's
+/// connection, which lapses once the client stops polling.
+#[get("")]
+pub async fn poll_events(
+    req: HttpRequest,
+    state: Data<State>,
+    query: Query<PollQuery>,
+    user: Option<AuthedUser>,
+) -> Result<Json<EventBatch>, HttpError> {
+    // Polls are made over separate requests, so the connection is identified
+    // by the client's session, or its address if it is anonymous
+    let connection_id = format!(
+        "poll:{}",
+        match &user {
+            Some(user) => user.session_id().to_owned(),
+            None => throttle::client_ip(&req, state.trusted_proxies()),
+        }
+    );
+    let connection = events::connection_for(&state, &req, user.as_ref(), &connection_id);
+
+    let since = match &query.since {
+        Some(since) => since
+            .parse::<EventId>()
+            .map_err(|_| ProviderError::InvalidArgument { arg: "since" })?,
+        None => {
+            events::register_connection(&state, &connection)?;
+
+            return Ok(Json(
+                state
+                    .event_relay()
+                    .latest()
+                    .with_greeting(events::greeting_for(&state, user.as_ref())?),
+            ));
+        }
+    };
+    let wait = Duration::from_secs(query.wait.unwrap_or(DEFAULT_WAIT_SECS).min(MAX_WAIT_SECS));
+    let subscriber = events::subscriber_for(&state, user.as_ref(), connection)?;
+
+    let waiter = match state.event_relay().watch(&subscriber, since) {
+        Watch::Ready(batch) => return Ok(Json(batch)),
+        Watch::Pending(waiter) => waiter,
+    };
+
+    // Polls that time out are answered without any events
+    let _ = time::timeout(wait, waiter).await;
+
+    Ok(Json(match state.event_relay().watch(&subscriber, since) {
+        Watch::Ready(batch) => batch,
+        Watch::Pending(_) => EventBatch::idle(since),
+    }))
+}
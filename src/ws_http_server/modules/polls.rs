@@ -0,0 +1,487 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Scope,
+};
+use chrono::Utc;
+use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            poll::{NewPoll, Poll, PollResult, PollResultEntry},
+            schema::poll_results,
+        },
+        server::State,
+    },
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The redis key holding the active poll.
+const POLL_KEY: &str = "poll";
+
+/// The redis key holding the ID of the most recently started poll.
+const LAST_ID_KEY: &str = "last_poll_id";
+
+/// The number of seconds for which the results of ended polls are cached.
+const RESULT_TTL: u64 = 86400;
+
+/// Gets the redis key of the hash holding the number of votes cast for each
+/// of the options of the given poll, keyed by their indices.
+///
+/// # Arguments
+///
+/// * `poll_id` - The ID of the poll whose votes should be located
+fn votes_key(poll_id: u64) -> String {
+    format!("poll_votes::{}", poll_id)
+}
+
+/// Gets the redis key of the set holding the IDs of each of the users who
+/// voted in the given poll.
+///
+/// # Arguments
+///
+/// * `poll_id` - The ID of the poll whose voters should be located
+fn voters_key(poll_id: u64) -> String {
+    format!("poll_voters::{}", poll_id)
+}
+
+/// Gets the redis key of the value holding the results of the given poll.
+///
+/// # Arguments
+///
+/// * `poll_id` - The ID of the poll whose results should be located
+fn result_key(poll_id: u64) -> String {
+    format!("poll_result::{}", poll_id)
+}
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the polls module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/polls").service(poll_result)
+}
+
+/// Gets the results of the ended poll with the given ID.
+#[get("/{poll_id}")]
+pub async fn poll_result(
+    state: Data<State>,
+    poll_id: Path<u64>,
+) -> Result<Option<Json<PollResult>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .poll_result(*poll_id)
+    .map(|result| result.map(Json))
+}
+
+/// Provider represents an arbitrary backend for the polls service, which
+/// stores the active poll and the votes cast in it, alongside the results of
+/// ended polls. Only one poll may be active at a time.
+pub trait Provider {
+    /// Gets the active poll, if there is one.
+    fn active_poll(&mut self) -> Result<Option<Poll>, ProviderError>;
+
+    /// Starts the given poll, unless a poll is already active.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll that should be started
+    fn start_poll(&mut self, poll: &NewPoll) -> Result<Option<Poll>, ProviderError>;
+
+    /// Records a ballot cast by the given user, unless they already voted in
+    /// the poll, in which case false is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll in which the user is voting
+    /// * `user_id` - The ID of the user casting the ballot
+    /// * `option` - The index of the option that the user is voting for
+    /// * `weight` - The number of votes that the ballot is worth
+    fn vote(
+        &mut self,
+        poll_id: u64,
+        user_id: u64,
+        option: usize,
+        weight: u64,
+    ) -> Result<bool, ProviderError>;
+
+    /// Gets the number of votes cast for each of the given poll's options.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll whose votes should be counted
+    fn totals(&mut self, poll: &Poll) -> Result<Vec<u64>, ProviderError>;
+
+    /// Ends the active poll, if there is one, returning its results.
+    fn end_poll(&mut self) -> Result<Option<PollResult>, ProviderError>;
+
+    /// Gets the results of the ended poll with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll whose results should be obtained
+    fn poll_result(&mut self, poll_id: u64) -> Result<Option<PollResult>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Ensures that polls started from now on are assigned IDs greater than
+    /// the given ID, in case the redis caching layer was emptied.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_id` - The ID of the most recently archived poll
+    fn seed_poll_id(&mut self, last_id: u64) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(LAST_ID_KEY))
+            .arg(last_id)
+            .arg("NX")
+            .query::<Option<String>>(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Caches the results of an ended poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The results that should be cached
+    fn cache_result(&mut self, result: &PollResult) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(result_key(result.poll().id())))
+            .arg(serde_json::to_string(result)?)
+            .arg("EX")
+            .arg(RESULT_TTL)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the active poll from the redis caching layer, if there is one.
+    fn active_poll(&mut self) -> Result<Option<Poll>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(POLL_KEY))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |poll| {
+                serde_json::from_str(&poll).map_err(|e| e.into())
+            })
+    }
+
+    /// Starts the given poll in the redis caching layer, unless a poll is
+    /// already active.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll that should be started
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::poll::NewPoll, ws_http_server::modules::{Cache, polls::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut polls = Cache::new(&mut conn);
+    /// polls.end_poll()?;
+    ///
+    /// let poll = polls
+    ///     .start_poll(&NewPoll::new(69420, "Is Destiny based?", vec!["yes", "no"], false, Utc::now()))?
+    ///     .unwrap();
+    /// assert_eq!(polls.active_poll()?, Some(poll));
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn start_poll(&mut self, poll: &NewPoll) -> Result<Option<Poll>, ProviderError> {
+        let id: u64 = redis::cmd("INCR")
+            .arg(self.key(LAST_ID_KEY))
+            .query(self.connection)?;
+        let poll = poll.with_id(id);
+
+        // The poll is only set if no other poll is active
+        let started: Option<String> = redis::cmd("SET")
+            .arg(self.key(POLL_KEY))
+            .arg(serde_json::to_string(&poll)?)
+            .arg("NX")
+            .query(self.connection)?;
+
+        Ok(started.map(|_| poll))
+    }
+
+    /// Records a ballot cast by the given user in the redis caching layer,
+    /// unless they already voted in the poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll in which the user is voting
+    /// * `user_id` - The ID of the user casting the ballot
+    /// * `option` - The index of the option that the user is voting for
+    /// * `weight` - The number of votes that the ballot is worth
+    fn vote(
+        &mut self,
+        poll_id: u64,
+        user_id: u64,
+        option: usize,
+        weight: u64,
+    ) -> Result<bool, ProviderError> {
+        // Adding the voter to the set of voters is atomic, so concurrent
+        // ballots from the same user can't both be counted
+        let added: u64 = redis::cmd("SADD")
+            .arg(self.key(voters_key(poll_id)))
+            .arg(user_id)
+            .query(self.connection)?;
+        if added == 0 {
+            return Ok(false);
+        }
+
+        redis::cmd("HINCRBY")
+            .arg(self.key(votes_key(poll_id)))
+            .arg(option)
+            .arg(weight)
+            .query::<()>(self.connection)?;
+
+        Ok(true)
+    }
+
+    /// Gets the number of votes cast for each of the given poll's options
+    /// from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll whose votes should be counted
+    fn totals(&mut self, poll: &Poll) -> Result<Vec<u64>, ProviderError> {
+        if poll.options().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        redis::cmd("HMGET")
+            .arg(self.key(votes_key(poll.id())))
+            .arg((0..poll.options().len()).collect::<Vec<usize>>())
+            .query::<Vec<Option<u64>>>(self.connection)
+            .map(|totals| {
+                totals
+                    .into_iter()
+                    .map(|total| total.unwrap_or_default())
+                    .collect()
+            })
+            .map_err(|e| e.into())
+    }
+
+    /// Ends the active poll in the redis caching layer, if there is one,
+    /// caching its results for a day.
+    fn end_poll(&mut self) -> Result<Option<PollResult>, ProviderError> {
+        let poll = match self.active_poll()? {
+            Some(poll) => poll,
+            None => return Ok(None),
+        };
+
+        let result = poll.finish(self.totals(&poll)?, Utc::now());
+        self.cache_result(&result)?;
+
+        redis::cmd("DEL")
+            .arg(self.key(POLL_KEY))
+            .arg(self.key(votes_key(poll.id())))
+            .arg(self.key(voters_key(poll.id())))
+            .query::<()>(self.connection)?;
+
+        Ok(Some(result))
+    }
+
+    /// Gets the cached results of the ended poll with the given ID from the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll whose results should be obtained
+    fn poll_result(&mut self, poll_id: u64) -> Result<Option<PollResult>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(result_key(poll_id)))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |result| {
+                serde_json::from_str(&result).map_err(|e| e.into())
+            })
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Gets the ID of the most recently archived poll from the MySQL
+    /// database, or 0 if no polls have been archived.
+    fn last_poll_id(&mut self) -> Result<u64, ProviderError> {
+        poll_results::table
+            .select(diesel::dsl::max(poll_results::dsl::id))
+            .first::<Option<u64>>(self.connection)
+            .map(|id| id.unwrap_or_default())
+            .map_err(|e| e.into())
+    }
+
+    /// Archives the results of an ended poll in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The results that should be archived
+    fn archive_poll(&mut self, result: &PollResult) -> Result<(), ProviderError> {
+        diesel::replace_into(poll_results::table)
+            .values(PollResultEntry::new(result)?)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the archived results of the poll with the given ID from the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll whose results should be obtained
+    fn archived_poll(&mut self, poll_id: u64) -> Result<Option<PollResult>, ProviderError> {
+        poll_results::table
+            .find(Unsigned(poll_id))
+            .first::<PollResultEntry>(self.connection)
+            .optional()?
+            .map_or(Ok(None), |entry| {
+                entry.result().map(Some).map_err(|e| e.into())
+            })
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Gets the active poll from the caching layer, if there is one.
+    fn active_poll(&mut self) -> Result<Option<Poll>, ProviderError> {
+        self.cache.active_poll()
+    }
+
+    /// Starts the given poll in the caching layer, unless a poll is already
+    /// active. Poll IDs continue from the most recently archived poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll that should be started
+    fn start_poll(&mut self, poll: &NewPoll) -> Result<Option<Poll>, ProviderError> {
+        let last_id = self.persistent.last_poll_id()?;
+        self.cache.seed_poll_id(last_id)?;
+
+        self.cache.start_poll(poll)
+    }
+
+    /// Records a ballot cast by the given user in the caching layer, unless
+    /// they already voted in the poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll in which the user is voting
+    /// * `user_id` - The ID of the user casting the ballot
+    /// * `option` - The index of the option that the user is voting for
+    /// * `weight` - The number of votes that the ballot is worth
+    fn vote(
+        &mut self,
+        poll_id: u64,
+        user_id: u64,
+        option: usize,
+        weight: u64,
+    ) -> Result<bool, ProviderError> {
+        self.cache.vote(poll_id, user_id, option, weight)
+    }
+
+    /// Gets the number of votes cast for each of the given poll's options
+    /// from the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll` - The poll whose votes should be counted
+    fn totals(&mut self, poll: &Poll) -> Result<Vec<u64>, ProviderError> {
+        self.cache.totals(poll)
+    }
+
+    /// Ends the active poll, if there is one, archiving its results in the
+    /// persistent layer.
+    fn end_poll(&mut self) -> Result<Option<PollResult>, ProviderError> {
+        let result = self.cache.end_poll()?;
+
+        if let Some(result) = &result {
+            self.persistent.archive_poll(result)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Gets the results of the ended poll with the given ID, populating the
+    /// cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `poll_id` - The ID of the poll whose results should be obtained
+    fn poll_result(&mut self, poll_id: u64) -> Result<Option<PollResult>, ProviderError> {
+        if let Some(result) = self.cache.poll_result(poll_id)? {
+            return Ok(Some(result));
+        }
+
+        self.persistent
+            .archived_poll(poll_id)?
+            .map_or(Ok(None), |result| {
+                self.cache.cache_result(&result).map(|_| Some(result))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut polls = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        polls.end_poll()?;
+
+        let poll = polls
+            .start_poll(&NewPoll::new(
+                69420,
+                "Is Destiny based?",
+                vec!["yes", "no"],
+                true,
+                Utc::now(),
+            ))?
+            .ok_or("the poll should have started")?;
+
+        // Only one poll may be active at a time
+        assert!(polls
+            .start_poll(&NewPoll::new(69420, "a", vec!["b", "c"], false, Utc::now()))?
+            .is_none());
+
+        // Each user may only vote once
+        assert!(polls.vote(poll.id(), 1, 0, 3)?);
+        assert!(!polls.vote(poll.id(), 1, 1, 1)?);
+        assert!(polls.vote(poll.id(), 2, 1, 1)?);
+        assert_eq!(polls.totals(&poll)?, vec![3, 1]);
+
+        let result = polls.end_poll()?.ok_or("the poll should have ended")?;
+        assert_eq!(result.winner(), Some(0));
+        assert_eq!(polls.active_poll()?, None);
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(result_key(poll.id()))
+            .query::<()>(&mut conn)?;
+        let mut polls = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert_eq!(polls.poll_result(poll.id())?, Some(result));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use super::{
+    super::super::spec::profile::SenderProfile, flairs, roles::Provider as RolesProvider,
+    subscriptions::Provider as SubscriptionsProvider, Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The number of seconds for which a chatter's profile is cached once
+/// resolved. Changes to a chatter's roles, flairs or subscription are shown
+/// in their messages once their cached profile expires.
+pub const PROFILE_TTL: u64 = 30;
+
+/// Gets the redis key of the value holding the cached profile of the given
+/// user.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose profile should be located
+fn profile_key(user_id: u64) -> String {
+    format!("sender_profile::{}", user_id)
+}
+
+/// Resolves the profiles of each of the given users, in the order that the
+/// user IDs were provided. Cached profiles are used where possible, and the
+/// roles of the remaining users are looked up at once.
+///
+/// # Arguments
+///
+/// * `hybrid` - The providers from which profiles should be resolved
+/// * `user_ids` - The IDs of the users whose profiles should be resolved
+pub fn profiles_for(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    user_ids: &[u64],
+) -> Result<Vec<SenderProfile>, ProviderError> {
+    let cached = hybrid.cached_profiles(user_ids)?;
+
+    let missing = user_ids
+        .iter()
+        .zip(cached.iter())
+        .filter(|(_, profile)| profile.is_none())
+        .map(|(user_id, _)| *user_id)
+        .collect::<Vec<u64>>();
+
+    let mut resolved = Vec::with_capacity(missing.len());
+    for (user_id, roles) in missing.iter().zip(hybrid.roles_for_users(&missing)?) {
+        let flairs = flairs::flairs_for(hybrid, *user_id, &roles)?
+            .into_iter()
+            .map(|flair| flair.name().to_owned())
+            .collect();
+        let tier = hybrid.active_tier(*user_id)?;
+
+        resolved.push(
+            SenderProfile::new(*user_id, roles)
+                .with_flairs(flairs)
+                .with_tier(tier),
+        );
+    }
+    hybrid.cache_profiles(&resolved)?;
+
+    let mut resolved = resolved.into_iter();
+
+    Ok(cached
+        .into_iter()
+        .filter_map(|profile| profile.or_else(|| resolved.next()))
+        .collect())
+}
+
+/// Resolves the profile of the given user.
+///
+/// # Arguments
+///
+/// * `hybrid` - The providers from which the profile should be resolved
+/// * `user_id` - The ID of the user whose profile should be resolved
+pub fn profile_for(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    user_id: u64,
+) -> Result<SenderProfile, ProviderError> {
+    profiles_for(hybrid, &[user_id]).map(|mut profiles| {
+        profiles
+            .pop()
+            .unwrap_or_else(|| SenderProfile::new(user_id, Vec::new()))
+    })
+}
+
+/// Provider represents an arbitrary backend for the profiles service, which
+/// caches the details attached to each message sent by a chatter. Profiles
+/// are derived from other services, and are therefore only stored in the
+/// caching layer.
+pub trait Provider {
+    /// Retreives the cached profiles of each of the given users, in the order
+    /// that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose profiles should be obtained
+    fn cached_profiles(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<Option<SenderProfile>>, ProviderError>;
+
+    /// Caches each of the given profiles for PROFILE_TTL seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `profiles` - The profiles that should be cached
+    fn cache_profiles(&mut self, profiles: &[SenderProfile]) -> Result<(), ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Retreives the profiles of each of the given users from the redis
+    /// caching layer, in the order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose profiles should be obtained
+    fn cached_profiles(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<Option<SenderProfile>>, ProviderError> {
+        // MGET requires at least one key
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        redis::cmd("MGET")
+            .arg(
+                user_ids
+                    .iter()
+                    .map(|user_id| self.key(profile_key(*user_id)))
+                    .collect::<Vec<String>>(),
+            )
+            .query::<Vec<Option<String>>>(self.connection)?
+            .iter()
+            .map(|profile| {
+                profile
+                    .as_deref()
+                    .map_or(Ok(None), |profile| serde_json::from_str(profile).map(Some))
+                    .map_err(|e| e.into())
+            })
+            .collect()
+    }
+
+    /// Caches each of the given profiles in the redis caching layer for
+    /// PROFILE_TTL seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `profiles` - The profiles that should be cached
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{spec::{profile::SenderProfile, user::Role}, ws_http_server::modules::{Cache, profiles::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut profiles = Cache::new(&mut conn);
+    /// let profile = SenderProfile::new(69420, vec![Role::Subscriber]).with_tier(Some(1));
+    /// profiles.cache_profiles(&[profile.clone()])?;
+    ///
+    /// assert_eq!(profiles.cached_profiles(&[69420])?, vec![Some(profile)]);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn cache_profiles(&mut self, profiles: &[SenderProfile]) -> Result<(), ProviderError> {
+        if profiles.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for profile in profiles {
+            pipe.cmd("SET")
+                .arg(self.key(profile_key(profile.user_id())))
+                .arg(serde_json::to_string(profile)?)
+                .arg("EX")
+                .arg(PROFILE_TTL)
+                .ignore();
+        }
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Retreives the profiles of each of the given users from the caching
+    /// layer, in the order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose profiles should be obtained
+    fn cached_profiles(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<Option<SenderProfile>>, ProviderError> {
+        self.cache.cached_profiles(user_ids)
+    }
+
+    /// Caches each of the given profiles in the caching layer for
+    /// PROFILE_TTL seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `profiles` - The profiles that should be cached
+    fn cache_profiles(&mut self, profiles: &[SenderProfile]) -> Result<(), ProviderError> {
+        self.cache.cache_profiles(profiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, super::Persistent, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error, slice};
+
+    #[test]
+    fn test_profiles_for() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut hybrid = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+
+        // Cached profiles are used as-is, while the rest are resolved
+        let cached = SenderProfile::new(69420, Vec::new()).with_tier(Some(3));
+        hybrid.cache_profiles(slice::from_ref(&cached))?;
+        redis::cmd("DEL")
+            .arg(profile_key(42069))
+            .query::<()>(hybrid.cache.connection)?;
+
+        let profiles = profiles_for(&mut hybrid, &[42069, 69420])?;
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].user_id(), 42069);
+        assert_eq!(profiles[1], cached);
+
+        // Resolved profiles are cached
+        assert_eq!(
+            hybrid.cached_profiles(&[42069])?,
+            vec![Some(profiles[0].clone())]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,485 @@
+use actix_web::{
+    web::{Data, Json},
+    Error as HttpError, HttpRequest, Scope,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::Connection as RedisConnection;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{
+        super::spec::{
+            backend::PersistentConnection, refresh_token::RefreshToken, schema::refresh_tokens,
+        },
+        auth::AuthError,
+        jwt::Claims,
+        server::State,
+    },
+    roles::Provider as RolesProvider,
+    sessions::{Provider as SessionsProvider, SESSION_TTL},
+    throttle::{self, Subject},
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The number of characters in a refresh token.
+const TOKEN_LENGTH: usize = 48;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the refresh tokens module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/auth").service(refresh)
+}
+
+/// Tokens represents the credentials issued to a user upon logging in or
+/// refreshing their session.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Tokens {
+    /// The short-lived token with which the user may authenticate themselves
+    token: String,
+
+    /// The token with which the user may obtain a new session token once the
+    /// current one expires. Each refresh token may only be used once.
+    refresh_token: String,
+}
+
+/// RefreshRequest represents a request to exchange a refresh token for a new
+/// set of tokens.
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    /// The refresh token that should be exchanged
+    refresh_token: String,
+}
+
+/// Exchanges a refresh token for a new session token and refresh token.
+/// Presenting a refresh token that has already been exchanged revokes the
+/// session that it belongs to, as the token has likely been stolen.
+/// Addresses presenting too many invalid refresh tokens are locked out.
+#[post("/refresh")]
+pub async fn refresh(
+    state: Data<State>,
+    req: HttpRequest,
+    body: Json<RefreshRequest>,
+) -> Result<Json<Tokens>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let ip = throttle::client_ip(&req, state.trusted_proxies());
+    throttle::check(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+    match rotate_tokens(&state, &mut conn, &persistent_conn, &body.refresh_token) {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => {
+            if e.as_error::<AuthError>().is_some() {
+                throttle::record_failure(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+            }
+
+            Err(e)
+        }
+    }
+}
+
+/// Issues a session token and refresh token to the user with the given ID for
+/// the given session.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the session signing key
+/// * `conn` - A connection to the redis caching layer
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `user_id` - The ID of the user to whom the tokens should be issued
+/// * `session_id` - The ID of the session that the tokens belong to
+pub(crate) fn issue_tokens(
+    state: &State,
+    conn: &mut RedisConnection,
+    persistent_conn: &PersistentConnection,
+    user_id: u64,
+    session_id: String,
+) -> Result<Tokens, HttpError> {
+    let mut hybrid = Hybrid::new(
+        Cache::new(conn).with_prefix(state.key_prefix()),
+        Persistent::new(persistent_conn),
+    );
+
+    let refresh_token = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .collect::<String>();
+    hybrid.store_refresh_token(&RefreshToken::new(
+        &refresh_token,
+        user_id,
+        session_id.clone(),
+    ))?;
+
+    // Roles are looked up each time a token is issued, so that changes to a
+    // user's roles take effect once their token is refreshed
+    let roles = hybrid.roles_for_user(user_id)?;
+    let token = state
+        .signing_keys()
+        .sign(&Claims::new(user_id, session_id, roles))?;
+
+    Ok(Tokens {
+        token,
+        refresh_token,
+    })
+}
+
+/// Exchanges the given refresh token for a new set of tokens belonging to the
+/// same session.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the session signing key
+/// * `conn` - A connection to the redis caching layer
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `refresh_token` - The refresh token presented by the user
+pub(crate) fn rotate_tokens(
+    state: &State,
+    conn: &mut RedisConnection,
+    persistent_conn: &PersistentConnection,
+    refresh_token: &str,
+) -> Result<Tokens, HttpError> {
+    let token_hash = RefreshToken::hash(refresh_token);
+
+    let presented = {
+        let mut hybrid = Hybrid::new(
+            Cache::new(conn).with_prefix(state.key_prefix()),
+            Persistent::new(persistent_conn),
+        );
+
+        let presented = hybrid
+            .get_refresh_token(&token_hash)?
+            .ok_or(AuthError::InvalidCredentials)?;
+
+        // A token may only be consumed once, so a token that has already been
+        // consumed was either replayed by an attacker, or was stolen and used
+        // before its owner could use it
+        if presented.used() || !hybrid.consume_refresh_token(&token_hash)? {
+            hybrid.revoke_session_tokens(presented.session_id())?;
+            Cache::new(conn)
+                .with_prefix(state.key_prefix())
+                .revoke_session(presented.session_id())?;
+
+            return Err(AuthError::InvalidCredentials.into());
+        }
+
+        presented
+    };
+
+    // Refresh tokens outlive neither their sessions, nor the users they were
+    // issued to
+    let mut sessions = Cache::new(conn).with_prefix(state.key_prefix());
+    if sessions.session_user(presented.session_id())? != Some(presented.user_id()) {
+        return Err(AuthError::InvalidCredentials.into());
+    }
+    sessions.touch_session(presented.session_id())?;
+
+    issue_tokens(
+        state,
+        conn,
+        persistent_conn,
+        presented.user_id(),
+        presented.session_id().to_owned(),
+    )
+}
+
+/// Provider represents an arbitrary backend for the refresh tokens service,
+/// which keeps track of the refresh tokens issued for each session.
+pub trait Provider {
+    /// Records the given refresh token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The refresh token that should be recorded
+    fn store_refresh_token(&mut self, token: &RefreshToken) -> Result<(), ProviderError>;
+
+    /// Gets the refresh token with the given hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn get_refresh_token(
+        &mut self,
+        token_hash: &[u8],
+    ) -> Result<Option<RefreshToken>, ProviderError>;
+
+    /// Marks the refresh token with the given hash as used, returning whether
+    /// or not the token was unused beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn consume_refresh_token(&mut self, token_hash: &[u8]) -> Result<bool, ProviderError>;
+
+    /// Revokes each of the refresh tokens issued for the given session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the session whose tokens should be revoked
+    fn revoke_session_tokens(&mut self, session_id: &str) -> Result<(), ProviderError>;
+}
+
+/// Gets the redis key under which the refresh token with the given hash is
+/// cached.
+///
+/// # Arguments
+///
+/// * `token_hash` - The hash of the refresh token
+fn token_key(token_hash: &[u8]) -> String {
+    format!(
+        "refresh::{}",
+        token_hash
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records the given refresh token in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The refresh token that should be recorded
+    fn store_refresh_token(&mut self, token: &RefreshToken) -> Result<(), ProviderError> {
+        let family = self.key(format_args!("refresh_session::{}", token.session_id()));
+
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(self.key(token_key(token.token_hash())))
+            .arg(serde_json::to_string(token)?)
+            .arg("EX")
+            .arg(SESSION_TTL)
+            .ignore()
+            .cmd("SADD")
+            .arg(&family)
+            .arg(self.key(token_key(token.token_hash())))
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&family)
+            .arg(SESSION_TTL)
+            .ignore()
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the refresh token with the given hash from the redis caching
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn get_refresh_token(
+        &mut self,
+        token_hash: &[u8],
+    ) -> Result<Option<RefreshToken>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(token_key(token_hash)))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |raw| {
+                serde_json::from_str(&raw).map(Some).map_err(|e| e.into())
+            })
+    }
+
+    /// Evicts the refresh token with the given hash from the redis caching
+    /// layer, returning whether or not the token was cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn consume_refresh_token(&mut self, token_hash: &[u8]) -> Result<bool, ProviderError> {
+        redis::cmd("DEL")
+            .arg(self.key(token_key(token_hash)))
+            .query::<u64>(self.connection)
+            .map(|deleted| deleted > 0)
+            .map_err(|e| e.into())
+    }
+
+    /// Evicts each of the refresh tokens issued for the given session from
+    /// the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the session whose tokens should be revoked
+    fn revoke_session_tokens(&mut self, session_id: &str) -> Result<(), ProviderError> {
+        let family = self.key(format_args!("refresh_session::{}", session_id));
+        let keys: Vec<String> = redis::cmd("SMEMBERS").arg(&family).query(self.connection)?;
+
+        redis::cmd("DEL")
+            .arg(&family)
+            .arg(keys)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Records the given refresh token in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The refresh token that should be recorded
+    fn store_refresh_token(&mut self, token: &RefreshToken) -> Result<(), ProviderError> {
+        diesel::insert_into(refresh_tokens::table)
+            .values(token)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the refresh token with the given hash from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn get_refresh_token(
+        &mut self,
+        token_hash: &[u8],
+    ) -> Result<Option<RefreshToken>, ProviderError> {
+        refresh_tokens::table
+            .find(token_hash)
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Marks the refresh token with the given hash as used in the MySQL
+    /// database, returning whether or not the token was unused beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn consume_refresh_token(&mut self, token_hash: &[u8]) -> Result<bool, ProviderError> {
+        // Only unused tokens are updated, so that concurrent refreshes with
+        // the same token can't both succeed
+        diesel::update(
+            refresh_tokens::table
+                .find(token_hash)
+                .filter(refresh_tokens::dsl::used.eq(false)),
+        )
+        .set(refresh_tokens::dsl::used.eq(true))
+        .execute(self.connection)
+        .map(|updated| updated > 0)
+        .map_err(|e| e.into())
+    }
+
+    /// Deletes each of the refresh tokens issued for the given session from
+    /// the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the session whose tokens should be revoked
+    fn revoke_session_tokens(&mut self, session_id: &str) -> Result<(), ProviderError> {
+        diesel::delete(refresh_tokens::table.filter(refresh_tokens::dsl::session_id.eq(session_id)))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Records the given refresh token in both the persistent and caching
+    /// layers.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The refresh token that should be recorded
+    fn store_refresh_token(&mut self, token: &RefreshToken) -> Result<(), ProviderError> {
+        self.persistent
+            .store_refresh_token(token)
+            .and_then(|_| self.cache.store_refresh_token(token))
+    }
+
+    /// Gets the refresh token with the given hash, populating the cache from
+    /// the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn get_refresh_token(
+        &mut self,
+        token_hash: &[u8],
+    ) -> Result<Option<RefreshToken>, ProviderError> {
+        match self.cache.get_refresh_token(token_hash) {
+            Ok(Some(token)) => Ok(Some(token)),
+            _ => self
+                .persistent
+                .get_refresh_token(token_hash)
+                .and_then(|token| match token {
+                    // Used tokens are left uncached, so that the persistent
+                    // layer remains the authority on reuse
+                    Some(token) if !token.used() => {
+                        self.cache.store_refresh_token(&token).map(|_| Some(token))
+                    }
+                    token => Ok(token),
+                }),
+        }
+    }
+
+    /// Marks the refresh token with the given hash as used, returning whether
+    /// or not the token was unused beforehand according to the persistent
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_hash` - The hash of the refresh token
+    fn consume_refresh_token(&mut self, token_hash: &[u8]) -> Result<bool, ProviderError> {
+        let consumed = self.persistent.consume_refresh_token(token_hash)?;
+
+        self.cache
+            .consume_refresh_token(token_hash)
+            .map(|_| consumed)
+    }
+
+    /// Revokes each of the refresh tokens issued for the given session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The ID of the session whose tokens should be revoked
+    fn revoke_session_tokens(&mut self, session_id: &str) -> Result<(), ProviderError> {
+        self.persistent
+            .revoke_session_tokens(session_id)
+            .and_then(|_| self.cache.revoke_session_tokens(session_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut tokens = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        let token = RefreshToken::new("refresh", 42069, "session".to_owned());
+        tokens.store_refresh_token(&token)?;
+        assert_eq!(
+            tokens.get_refresh_token(token.token_hash())?,
+            Some(token.clone())
+        );
+
+        // Tokens may only be consumed once
+        assert!(tokens.consume_refresh_token(token.token_hash())?);
+        assert!(!tokens.consume_refresh_token(token.token_hash())?);
+        assert_eq!(
+            tokens.get_refresh_token(token.token_hash())?,
+            Some(token.clone().mark_used())
+        );
+
+        tokens.revoke_session_tokens("session")?;
+        assert_eq!(tokens.get_refresh_token(token.token_hash())?, None);
+
+        Ok(())
+    }
+}
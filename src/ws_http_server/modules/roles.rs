@@ -1,11 +1,111 @@
 use super::{
-    super::super::spec::{
-        schema::roles,
-        user::{Role, RoleEntry},
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            schema::roles,
+            user::{Role, RoleChanges, RoleEntry},
+        },
+        auth::{capability::CanManageRoles, RequireCapability},
+        breaker,
+        invalidation::Invalidation,
+        lookups::Lookup,
+        outbox::{PendingWrite, Unavailable},
+        server::State,
     },
-    Cache, Hybrid, Persistent, ProviderError,
+    Cache, Hybrid, Memory, Persistent, ProviderError,
+};
+use actix_web::{
+    web::{Data, Json, Path},
+    HttpResponse, Scope,
 };
-use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use tracing::instrument;
+
+use std::collections::HashMap;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the roles module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/roles")
+        .service(user_roles)
+        .service(grant_role)
+        .service(revoke_role)
+}
+
+/// Gets a list of the roles held by the specified user. Only administrators
+/// may manage roles.
+#[get("/{user_id}")]
+pub async fn user_roles(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageRoles>,
+    user_id: Path<u64>,
+) -> Result<Json<Vec<Role>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .roles_for_user(*user_id)
+    .map(Json)
+}
+
+/// Grants the specified role to the specified user.
+#[put("/{user_id}/{role}")]
+pub async fn grant_role(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageRoles>,
+    path: Path<(u64, Role)>,
+) -> Result<HttpResponse, ProviderError> {
+    let (user_id, role) = path.into_inner();
+
+    write_roles(&state, |roles| roles.give_role(user_id, &role))
+        .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Revokes the specified role from the specified user.
+#[delete("/{user_id}/{role}")]
+pub async fn revoke_role(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageRoles>,
+    path: Path<(u64, Role)>,
+) -> Result<HttpResponse, ProviderError> {
+    let (user_id, role) = path.into_inner();
+
+    write_roles(&state, |roles| roles.remove_role(user_id, &role))
+        .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Applies the given write to the roles held by a user. If the persistent
+/// layer can't be reached and the server has an outbox, the write is applied
+/// to the caching layer and queued until the persistent layer returns.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+/// * `write` - The write that should be applied
+pub(crate) fn write_roles<T>(
+    state: &State,
+    write: impl FnOnce(&mut dyn Provider) -> Result<T, ProviderError>,
+) -> Result<T, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+    let outbox = state.outbox().cloned();
+
+    match state.persistent_connection() {
+        Ok(persistent_conn) => {
+            write(&mut Hybrid::new(cache, Persistent::new(&persistent_conn)).with_outbox(outbox))
+        }
+        Err(e) if outbox.is_some() && breaker::indicates_outage(&e) => {
+            tracing::warn!(error = %e, "persistent layer is down, queueing the write");
+
+            write(&mut Hybrid::new(cache, Unavailable).with_outbox(outbox))
+        }
+        Err(e) => Err(e),
+    }
+}
 
 /// Provider represents an arbitrary provider of the roles lib API.
 /// The roles API is responsible for managing roles corresponding to certain
@@ -60,6 +160,14 @@ pub trait Provider {
     ///
     /// * `user_id` - The ID of the user whose roles should be determined
     fn roles_for_user(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError>;
+
+    /// Obtains the roles held by each of the given users at once, in the
+    /// order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose roles should be determined
+    fn roles_for_users(&mut self, user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError>;
 }
 
 impl<'a> Provider for Cache<'a> {
@@ -70,9 +178,10 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn has_role(&mut self, user_id: u64, role: &Role) -> Result<bool, ProviderError> {
         redis::cmd("SISMEMBER")
-            .arg(format!("roles::{}", user_id))
+            .arg(self.key(format_args!("roles::{}", user_id)))
             .arg(role.to_str())
             .query::<bool>(self.connection)
             .map_err(|e| e.into())
@@ -85,6 +194,7 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn give_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
         self.give_roles(user_id, &[*role])
     }
@@ -95,17 +205,19 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be set
     /// * `roles` - The roles that should be assigned to the user
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn give_roles(&mut self, user_id: u64, roles: &[Role]) -> Result<(), ProviderError> {
         redis::cmd("SADD")
-            .arg(format!("roles::{}", user_id))
+            .arg(self.key(format_args!("roles::{}", user_id)))
             .arg(
                 roles
                     .iter()
                     .map(|role| role.to_str())
                     .collect::<Vec<&str>>(),
             )
-            .query::<()>(self.connection)
-            .map_err(|e| e.into())
+            .query::<()>(self.connection)?;
+
+        self.publish_invalidation(&Invalidation::Roles { user_id })
     }
 
     /// Removes the given role from the user with the corresponding user_id.
@@ -114,12 +226,14 @@ impl<'a> Provider for Cache<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be removed
     /// * `role` - The role that should be removed from the user
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn remove_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
         redis::cmd("SREM")
-            .arg(format!("roles::{}", user_id))
+            .arg(self.key(format_args!("roles::{}", user_id)))
             .arg(role.to_str())
-            .query::<()>(self.connection)
-            .map_err(|e| e.into())
+            .query::<()>(self.connection)?;
+
+        self.publish_invalidation(&Invalidation::Roles { user_id })
     }
 
     /// Removes all of the roles corresponding to the given user, returning
@@ -128,16 +242,19 @@ impl<'a> Provider for Cache<'a> {
     /// # Arguments
     ///
     /// * `user_id` - The ID of the user whose roles should be purged
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn purge_roles(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
-        // Get a list of the roles that the user once had
-        let old = self.roles_for_user(user_id)?;
-
-        // Purge all of the user's roles
-        redis::cmd("DEL")
-            .arg(format!("roles::{}", user_id))
-            .query::<()>(self.connection)?;
-
-        Ok(old)
+        // Get a list of the roles that the user once had, and purge them
+        let key = self.key(format_args!("roles::{}", user_id));
+        let (old,): (Vec<String>,) = self.transaction(|pipe| {
+            pipe.cmd("SMEMBERS").arg(&key).cmd("DEL").arg(&key).ignore();
+        })?;
+        self.publish_invalidation(&Invalidation::Roles { user_id })?;
+
+        Ok(old
+            .iter()
+            .filter_map(|str_role| str_role.parse().ok())
+            .collect())
     }
 
     /// Obtains a list of the roles held by a certain user, indicated by the
@@ -146,9 +263,10 @@ impl<'a> Provider for Cache<'a> {
     /// # Arguments
     ///
     /// * `user_id` - The ID of the user whose roles should be determined
+    #[instrument(skip_all, fields(layer = "cache", user_id = user_id), err)]
     fn roles_for_user(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
         redis::cmd("SMEMBERS")
-            .arg(format!("roles::{}", user_id))
+            .arg(self.key(format_args!("roles::{}", user_id)))
             .query::<Vec<String>>(self.connection)
             .map(|str_roles| {
                 str_roles
@@ -158,6 +276,41 @@ impl<'a> Provider for Cache<'a> {
             })
             .map_err(|e| e.into())
     }
+
+    /// Obtains the roles held by each of the given users at once, in a
+    /// single round trip to the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose roles should be determined
+    #[instrument(skip_all, fields(layer = "cache"), err)]
+    fn roles_for_users(&mut self, user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = user_ids
+            .iter()
+            .map(|user_id| self.key(format_args!("roles::{}", user_id)))
+            .collect::<Vec<String>>();
+
+        self.pipeline(|pipe| {
+            for key in keys {
+                pipe.cmd("SMEMBERS").arg(key);
+            }
+        })
+        .map(|all_roles: Vec<Vec<String>>| {
+            all_roles
+                .iter()
+                .map(|str_roles| {
+                    str_roles
+                        .iter()
+                        .filter_map(|str_role| str_role.parse().ok())
+                        .collect()
+                })
+                .collect()
+        })
+    }
 }
 
 impl<'a> Provider for Persistent<'a> {
@@ -168,9 +321,10 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn has_role(&mut self, user_id: u64, role: &Role) -> Result<bool, ProviderError> {
         roles::dsl::roles
-            .find(user_id)
+            .find(Unsigned(user_id))
             .first::<RoleEntry>(self.connection)
             .map(|role_entry| role_entry.has_role(role))
             .map_err(|e| e.into())
@@ -183,11 +337,9 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn give_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
-        role.construct_give_role_statement(user_id, true)
-            .execute(self.connection)
-            .map(|_| ())
-            .map_err(|e| e.into())
+        self.set_roles(user_id, &RoleChanges::new(&[*role], true))
     }
 
     /// Assigns multiple roles to a suer at once.
@@ -196,52 +348,9 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be set
     /// * `roles` - The roles that should be assigned to the user
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn give_roles(&mut self, user_id: u64, roles: &[Role]) -> Result<(), ProviderError> {
-        println!(
-            "IF EXISTS (SELECT * FROM roles WHERE user_id = {}) UPDATE roles SET {} WHERE user_id = {} ELSE INSERT INTO roles(user_id, {}) VALUES({}, {}) END",
-            user_id,
-            roles
-                .iter()
-                .map(|role| format!("{} = true", role))
-                .collect::<Vec<String>>()
-                .join(", "),
-            user_id,
-            roles
-                .iter()
-                .map(|role| role.to_str())
-                .collect::<Vec<&str>>()
-                .join(", "),
-            user_id,
-            roles
-                .iter()
-                .map(|_| "true")
-                .collect::<Vec<&str>>()
-                .join(", "),
-        );
-        diesel::sql_query(format!(
-            "IF EXISTS (SELECT * FROM roles WHERE user_id = {}) UPDATE roles SET {} WHERE user_id = {} ELSE INSERT INTO roles(user_id, {}) VALUES({}, {}) END",
-            user_id,
-            roles
-                .iter()
-                .map(|role| format!("{} = true", role))
-                .collect::<Vec<String>>()
-                .join(", "),
-            user_id,
-            roles
-                .iter()
-                .map(|role| role.to_str())
-                .collect::<Vec<&str>>()
-                .join(", "),
-            user_id,
-            roles
-                .iter()
-                .map(|_| "true")
-                .collect::<Vec<&str>>()
-                .join(", "),
-        ))
-        .execute(self.connection)
-        .map(|_| ())
-        .map_err(|e| e.into())
+        self.set_roles(user_id, &RoleChanges::new(roles, true))
     }
 
     /// Removes the given role from the user with the corresponding user_id.
@@ -250,11 +359,9 @@ impl<'a> Provider for Persistent<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be removed
     /// * `role` - The role that should be removed from the user
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn remove_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
-        role.construct_give_role_statement(user_id, false)
-            .execute(self.connection)
-            .map(|_| ())
-            .map_err(|e| e.into())
+        self.set_roles(user_id, &RoleChanges::new(&[*role], false))
     }
 
     /// Removes all of the roles corresponding to the given user, returning
@@ -263,10 +370,13 @@ impl<'a> Provider for Persistent<'a> {
     /// # Arguments
     ///
     /// * `user_id` - The ID of the user whose roles should be purged
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn purge_roles(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
-        let roles = self.roles_for_user(user_id)?;
+        // The removed roles are read from the primary database, as a replica
+        // may not have caught up with recent grants yet
+        let roles = Persistent::new(self.connection).roles_for_user(user_id)?;
 
-        diesel::delete(roles::table.find(user_id))
+        diesel::delete(roles::table.find(Unsigned(user_id)))
             .execute(self.connection)
             .map(|_| roles)
             .map_err(|e| e.into())
@@ -278,18 +388,227 @@ impl<'a> Provider for Persistent<'a> {
     /// # Arguments
     ///
     /// * `user_id` - The ID of the user whose roles should be determined
+    #[instrument(skip_all, fields(layer = "persistent", user_id = user_id), err)]
     fn roles_for_user(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
         Ok(<Vec<Role> as From<&RoleEntry>>::from(
             &roles::table
-                .find(user_id)
-                .first::<RoleEntry>(self.connection)
+                .find(Unsigned(user_id))
+                .first::<RoleEntry>(self.reader())
                 .optional()?
                 .unwrap_or_default(),
         ))
     }
+
+    /// Obtains the roles held by each of the given users at once, in a
+    /// single query to the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose roles should be determined
+    #[instrument(skip_all, fields(layer = "persistent"), err)]
+    fn roles_for_users(&mut self, user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError> {
+        let mut entries = roles::table
+            .filter(roles::dsl::user_id.eq_any(user_ids.iter().copied().map(Unsigned)))
+            .load::<RoleEntry>(self.connection)?
+            .iter()
+            .map(|entry| {
+                (
+                    entry.concerns(),
+                    <Vec<Role> as From<&RoleEntry>>::from(entry),
+                )
+            })
+            .collect::<HashMap<u64, Vec<Role>>>();
+
+        Ok(user_ids
+            .iter()
+            .map(|user_id| entries.remove(user_id).unwrap_or_default())
+            .collect())
+    }
 }
 
-impl<'a> Provider for Hybrid<'a> {
+impl<'a> Persistent<'a> {
+    /// Applies the given change to the roles held by a user, creating their
+    /// role entry if they don't have one yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be changed
+    /// * `changes` - The roles that should be given or taken
+    fn set_roles(&mut self, user_id: u64, changes: &RoleChanges) -> Result<(), ProviderError> {
+        // Diesel rejects updates that don't set any column
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.connection.transaction(|| {
+            diesel::insert_or_ignore_into(roles::table)
+                .values(roles::dsl::user_id.eq(Unsigned(user_id)))
+                .execute(self.connection)?;
+
+            diesel::update(roles::table.find(Unsigned(user_id)))
+                .set(changes)
+                .execute(self.connection)
+                .map(|_| ())
+                .map_err(|e| e.into())
+        })
+    }
+}
+
+impl Provider for Memory {
+    /// Determines whether or not a user with the given user ID has the given
+    /// role in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose role should be checked
+    /// * `role` - The role that the user should have
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{spec::user::Role, ws_http_server::modules::{roles::Provider, Memory}};
+    ///
+    /// let mut roles = Memory::new();
+    /// roles.give_role(1, &Role::Moderator).unwrap();
+    /// assert!(roles.has_role(1, &Role::Moderator).unwrap());
+    /// ```
+    fn has_role(&mut self, user_id: u64, role: &Role) -> Result<bool, ProviderError> {
+        Ok(self
+            .roles
+            .get(&user_id)
+            .is_some_and(|roles| roles.contains(role)))
+    }
+
+    /// Assigns the given role to a user in memory without removing any
+    /// existing roles from the aforementioned user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose role should be checked
+    /// * `role` - The role that the user should have
+    fn give_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
+        self.give_roles(user_id, &[*role])
+    }
+
+    /// Assigns multiple roles to a user at once in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be set
+    /// * `roles` - The roles that should be assigned to the user
+    fn give_roles(&mut self, user_id: u64, roles: &[Role]) -> Result<(), ProviderError> {
+        let held = self.roles.entry(user_id).or_default();
+
+        for role in roles {
+            if !held.contains(role) {
+                held.push(*role);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the given role from the user with the corresponding user_id
+    /// in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be removed
+    /// * `role` - The role that should be removed from the user
+    fn remove_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
+        if let Some(held) = self.roles.get_mut(&user_id) {
+            held.retain(|held_role| held_role != role);
+        }
+
+        Ok(())
+    }
+
+    /// Removes all of the roles corresponding to the given user from memory,
+    /// returning all roles that were removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be purged
+    fn purge_roles(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
+        Ok(self.roles.remove(&user_id).unwrap_or_default())
+    }
+
+    /// Obtains a list of the roles held by a certain user from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose roles should be determined
+    fn roles_for_user(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
+        Ok(self.roles.get(&user_id).cloned().unwrap_or_default())
+    }
+
+    /// Obtains the roles held by each of the given users at once from
+    /// memory, in the order that the user IDs were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose roles should be determined
+    fn roles_for_users(&mut self, user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError> {
+        user_ids
+            .iter()
+            .map(|user_id| self.roles_for_user(*user_id))
+            .collect()
+    }
+}
+
+impl Provider for Unavailable {
+    fn has_role(&mut self, _user_id: u64, _role: &Role) -> Result<bool, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn give_role(&mut self, _user_id: u64, _role: &Role) -> Result<(), ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn give_roles(&mut self, _user_id: u64, _roles: &[Role]) -> Result<(), ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn remove_role(&mut self, _user_id: u64, _role: &Role) -> Result<(), ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn purge_roles(&mut self, _user_id: u64) -> Result<Vec<Role>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn roles_for_user(&mut self, _user_id: u64) -> Result<Vec<Role>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+
+    fn roles_for_users(&mut self, _user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError> {
+        Err(ProviderError::Unavailable)
+    }
+}
+
+impl Lookup for Vec<Role> {
+    /// Users without any roles aren't cached, so reading no roles from the
+    /// caching layer is a miss.
+    fn outcomes(&self) -> (u64, u64) {
+        if self.is_empty() {
+            (0, 1)
+        } else {
+            (1, 0)
+        }
+    }
+}
+
+impl Lookup for Vec<Vec<Role>> {
+    fn outcomes(&self) -> (u64, u64) {
+        self.iter()
+            .map(Lookup::outcomes)
+            .fold((0, 0), |(hits, misses), (hit, miss)| {
+                (hits + hit, misses + miss)
+            })
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
     /// Determines whether or not a user with the given user ID has the given
     /// role.
     ///
@@ -298,15 +617,17 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
     fn has_role(&mut self, user_id: u64, role: &Role) -> Result<bool, ProviderError> {
-        self.cache.has_role(user_id, role).or_else(|_| {
-            self.persistent
+        let cached = self.cache.has_role(user_id, role);
+        self.fall_back("roles", cached, |hybrid| {
+            hybrid
+                .persistent
                 .has_role(user_id, role)
                 .and_then(|has_role| {
                     {
                         if has_role {
-                            self.cache.give_role(user_id, role)
+                            hybrid.cache.give_role(user_id, role)
                         } else {
-                            self.cache.remove_role(user_id, role)
+                            hybrid.cache.remove_role(user_id, role)
                         }
                     }
                     .map(|_| has_role)
@@ -322,9 +643,15 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The ID of the user whose role should be checked
     /// * `role` - The role that the user should have
     fn give_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
-        self.cache
-            .give_role(user_id, role)
-            .and(self.persistent.give_role(user_id, role))
+        let cached = self.cache.give_role(user_id, role);
+        self.write_through(
+            cached,
+            PendingWrite::GiveRoles {
+                user_id,
+                roles: vec![*role],
+            },
+            |persistent| persistent.give_role(user_id, role),
+        )
     }
 
     /// Assigns multiple roles to a suer at once.
@@ -334,9 +661,15 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The ID of the user whose roles should be set
     /// * `roles` - The roles that should be assigned to the user
     fn give_roles(&mut self, user_id: u64, roles: &[Role]) -> Result<(), ProviderError> {
-        self.cache
-            .give_roles(user_id, roles)
-            .and(self.persistent.give_roles(user_id, roles))
+        let cached = self.cache.give_roles(user_id, roles);
+        self.write_through(
+            cached,
+            PendingWrite::GiveRoles {
+                user_id,
+                roles: roles.to_vec(),
+            },
+            |persistent| persistent.give_roles(user_id, roles),
+        )
     }
 
     /// Removes the given role from the user with the corresponding user_id.
@@ -346,9 +679,15 @@ impl<'a> Provider for Hybrid<'a> {
     /// * `user_id` - The ID of the user whose roles should be removed
     /// * `role` - The role that should be removed from the user
     fn remove_role(&mut self, user_id: u64, role: &Role) -> Result<(), ProviderError> {
-        self.cache
-            .remove_role(user_id, role)
-            .and(self.persistent.remove_role(user_id, role))
+        let cached = self.cache.remove_role(user_id, role);
+        self.write_through(
+            cached,
+            PendingWrite::RemoveRole {
+                user_id,
+                role: *role,
+            },
+            |persistent| persistent.remove_role(user_id, role),
+        )
     }
 
     /// Removes all of the roles corresponding to the given user, returning
@@ -358,9 +697,10 @@ impl<'a> Provider for Hybrid<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be purged
     fn purge_roles(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
-        self.cache
-            .purge_roles(user_id)
-            .and(self.persistent.purge_roles(user_id))
+        let cached = self.cache.purge_roles(user_id);
+        self.write_through(cached, PendingWrite::PurgeRoles { user_id }, |persistent| {
+            persistent.purge_roles(user_id)
+        })
     }
 
     /// Obtains a list of the roles held by a certain user, indicated by the
@@ -370,28 +710,56 @@ impl<'a> Provider for Hybrid<'a> {
     ///
     /// * `user_id` - The ID of the user whose roles should be determined
     fn roles_for_user(&mut self, user_id: u64) -> Result<Vec<Role>, ProviderError> {
-        self.cache.roles_for_user(user_id).or_else(|_| {
-            self.persistent.roles_for_user(user_id).and_then(|roles| {
-                self.cache
+        let cached = self.cache.roles_for_user(user_id);
+        self.fall_back("roles", cached, |hybrid| {
+            hybrid.persistent.roles_for_user(user_id).and_then(|roles| {
+                hybrid
+                    .cache
                     .give_roles(user_id, roles.as_slice())
                     .map(|_| roles)
             })
         })
     }
+
+    /// Obtains the roles held by each of the given users at once, populating
+    /// the cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose roles should be determined
+    fn roles_for_users(&mut self, user_ids: &[u64]) -> Result<Vec<Vec<Role>>, ProviderError> {
+        let cached = self.cache.roles_for_users(user_ids);
+        self.fall_back("roles", cached, |hybrid| {
+            let all_roles = hybrid.persistent.roles_for_users(user_ids)?;
+
+            // Users without any roles can't be cached as a set
+            for (user_id, roles) in user_ids.iter().zip(all_roles.iter()) {
+                if !roles.is_empty() {
+                    hybrid.cache.give_roles(*user_id, roles)?;
+                }
+            }
+
+            Ok(all_roles)
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        super::super::super::spec::{
-            schema::users,
-            user::{NewUser, Role},
+        super::super::{
+            super::spec::{
+                backend::PersistentConnection,
+                schema::users,
+                user::{NewUser, Role},
+            },
+            migrations,
         },
         *,
     };
-    use diesel::{mysql::MysqlConnection, Connection, ExpressionMethods};
+    use diesel::{Connection, ExpressionMethods};
 
-    use std::{env, error::Error};
+    use std::{env, error::Error, io};
 
     #[test]
     fn test_hybrid() -> Result<(), Box<dyn Error>> {
@@ -399,7 +767,7 @@ mod tests {
 
         let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
         let persistent_conn =
-            MysqlConnection::establish(&env::var("DATABASE_URL").expect(
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
                 "DATABASE_URL must be set in a .env file for test to complete successfully",
             ))?;
 
@@ -418,7 +786,56 @@ mod tests {
         let mut roles = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
         roles.give_role(id, &Role::Protected)?;
 
-        assert_eq!(roles.has_role(id, &Role::Protected)?, true);
+        assert!(roles.has_role(id, &Role::Protected)?);
+        assert!(roles.roles_for_users(&[id, 0])?[0].contains(&Role::Protected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_persistent() -> Result<(), Box<dyn Error>> {
+        // Role writes only go through the query builder, so an in-memory
+        // database is enough to check them against SQLite
+        let persistent_conn = PersistentConnection::establish(":memory:")?;
+        migrations::run_pending(&persistent_conn, &mut io::sink())?;
+
+        let mut roles = Persistent::new(&persistent_conn);
+        roles.give_role(1, &Role::Moderator)?;
+        roles.give_roles(1, &[Role::VIP, Role::Moderator])?;
+        assert_eq!(roles.roles_for_user(1)?, vec![Role::Moderator, Role::VIP]);
+
+        // Revoking a role leaves the others, and revoking a role that isn't
+        // held creates an empty entry
+        roles.remove_role(1, &Role::Moderator)?;
+        roles.remove_role(2, &Role::Bot)?;
+        assert!(!roles.has_role(1, &Role::Moderator)?);
+        assert_eq!(
+            roles.roles_for_users(&[1, 2])?,
+            vec![vec![Role::VIP], Vec::new()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory() -> Result<(), Box<dyn Error>> {
+        let mut roles = Hybrid::new(Memory::new(), Memory::new());
+
+        roles.give_roles(1, &[Role::Moderator, Role::Subscriber, Role::Moderator])?;
+        assert_eq!(
+            roles.roles_for_user(1)?,
+            vec![Role::Moderator, Role::Subscriber]
+        );
+
+        roles.remove_role(1, &Role::Moderator)?;
+        assert!(!roles.has_role(1, &Role::Moderator)?);
+        assert_eq!(
+            roles.roles_for_users(&[1, 2])?,
+            vec![vec![Role::Subscriber], Vec::new()]
+        );
+
+        assert_eq!(roles.purge_roles(1)?, vec![Role::Subscriber]);
+        assert!(roles.roles_for_user(1)?.is_empty());
 
         Ok(())
     }
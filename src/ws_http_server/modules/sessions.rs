@@ -0,0 +1,481 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::Connection as RedisConnection;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{super::spec::backend::PersistentConnection, auth::AuthedUser, server::State},
+    refresh_tokens::Provider as RefreshTokensProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::cmp::Reverse;
+
+/// The number of seconds that a session remains valid for after being issued.
+pub const SESSION_TTL: u64 = 30 * 24 * 60 * 60;
+
+/// The redis channel on which the ID of each revoked session is published, so
+/// that connections authenticated by the session may be dropped.
+pub const REVOCATION_CHANNEL: &str = "session_revocations";
+
+/// The number of characters in a session token.
+const TOKEN_LENGTH: usize = 48;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the sessions module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/sessions")
+        .service(list_sessions)
+        .service(revoke_all_sessions)
+        .service(revoke_session)
+}
+
+/// Gets each of the active sessions belonging to the authenticated user.
+#[get("")]
+pub async fn list_sessions(
+    state: Data<State>,
+    user: AuthedUser,
+) -> Result<Json<Vec<Session>>, ProviderError> {
+    state
+        .retry_policy()
+        .run(|| {
+            let mut conn = state.cache_connection()?;
+
+            Cache::new(&mut conn)
+                .with_prefix(state.key_prefix())
+                .user_sessions(user.id())
+        })
+        .map(Json)
+}
+
+/// Revokes each of the authenticated user's sessions, logging them out on
+/// every device.
+#[delete("")]
+pub async fn revoke_all_sessions(
+    state: Data<State>,
+    user: AuthedUser,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let revoked = Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .revoke_user_sessions(user.id())?;
+    for session_id in revoked {
+        revoke_refresh_tokens(&mut conn, state.key_prefix(), &persistent_conn, &session_id)?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Revokes the specified session belonging to the authenticated user.
+#[delete("/{session_id}")]
+pub async fn revoke_session(
+    state: Data<State>,
+    user: AuthedUser,
+    session_id: Path<String>,
+) -> Result<HttpResponse, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    // Sessions belonging to other users are indistinguishable from sessions
+    // that don't exist
+    if Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .session_user(&session_id)?
+        != Some(user.id())
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .revoke_session(&session_id)?;
+    revoke_refresh_tokens(&mut conn, state.key_prefix(), &persistent_conn, &session_id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Revokes each of the refresh tokens issued for the given session.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `session_id` - The ID of the session whose refresh tokens should be
+/// revoked
+fn revoke_refresh_tokens(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    session_id: &str,
+) -> Result<(), ProviderError> {
+    Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .revoke_session_tokens(session_id)
+}
+
+/// Session describes a session issued to a user, and the device that it was
+/// issued to.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Session {
+    /// The ID of the session
+    id: String,
+
+    /// The ID of the user to whom the session belongs
+    user_id: u64,
+
+    /// (optional) The user agent of the device that the session was issued to
+    device: Option<String>,
+
+    /// (optional) The IP address from which the session was issued
+    ip: Option<String>,
+
+    /// The time at which the session was issued
+    created_at: DateTime<Utc>,
+
+    /// The last time at which the session was used to obtain a token
+    last_seen_at: DateTime<Utc>,
+}
+
+impl Session {
+    /// Retreives the ID of the session.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Retreives the ID of the user to whom the session belongs.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Retreives the user agent of the device that the session was issued to.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Retreives the IP address from which the session was issued.
+    pub fn ip(&self) -> Option<&str> {
+        self.ip.as_deref()
+    }
+
+    /// Retreives the time at which the session was issued.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Retreives the last time at which the session was used to obtain a
+    /// token.
+    pub fn last_seen_at(&self) -> DateTime<Utc> {
+        self.last_seen_at
+    }
+}
+
+/// Provider represents an arbitrary backend for the sessions service, which
+/// keeps track of the tokens with which users authenticate themselves.
+/// Sessions are ephemeral, and are therefore only stored in the caching
+/// layer.
+pub trait Provider {
+    /// Issues a new session for the user with the given ID, returning the
+    /// token that the user should present in order to authenticate.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the session belongs
+    /// * `device` - (optional) The user agent of the device that the session
+    /// is issued to
+    /// * `ip` - (optional) The IP address from which the session was requested
+    fn issue_session(
+        &mut self,
+        user_id: u64,
+        device: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<String, ProviderError>;
+
+    /// Gets the ID of the user to whom the session with the given token
+    /// belongs, if the session exists and has not expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session
+    fn session_user(&mut self, token: &str) -> Result<Option<u64>, ProviderError>;
+
+    /// Records that the session with the given token was just used.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session
+    fn touch_session(&mut self, token: &str) -> Result<(), ProviderError>;
+
+    /// Gets each of the active sessions belonging to the user with the given
+    /// ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose sessions should be obtained
+    fn user_sessions(&mut self, user_id: u64) -> Result<Vec<Session>, ProviderError>;
+
+    /// Revokes the session with the given token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session that should be revoked
+    fn revoke_session(&mut self, token: &str) -> Result<(), ProviderError>;
+
+    /// Revokes each of the sessions belonging to the user with the given ID,
+    /// returning the tokens of the sessions that were revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose sessions should be revoked
+    fn revoke_user_sessions(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Gets the description of the session with the given token from the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session
+    fn get_session(&mut self, token: &str) -> Result<Option<Session>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(format_args!("session_info::{}", token)))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |raw| {
+                serde_json::from_str(&raw).map(Some).map_err(|e| e.into())
+            })
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Issues a new session for the user with the given ID in the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the session belongs
+    /// * `device` - (optional) The user agent of the device that the session
+    /// is issued to
+    /// * `ip` - (optional) The IP address from which the session was requested
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, sessions::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut sessions = Cache::new(&mut conn);
+    /// let token = sessions.issue_session(69420, None, None)?;
+    ///
+    /// assert_eq!(sessions.session_user(&token)?, Some(69420));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn issue_session(
+        &mut self,
+        user_id: u64,
+        device: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<String, ProviderError> {
+        let token = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .collect::<String>();
+        let now = Utc::now();
+        let session = Session {
+            id: token.clone(),
+            user_id,
+            device: device.map(|device| device.to_owned()),
+            ip: ip.map(|ip| ip.to_owned()),
+            created_at: now,
+            last_seen_at: now,
+        };
+
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(self.key(format_args!("session::{}", token)))
+            .arg(user_id)
+            .arg("EX")
+            .arg(SESSION_TTL)
+            .ignore()
+            .cmd("SET")
+            .arg(self.key(format_args!("session_info::{}", token)))
+            .arg(serde_json::to_string(&session)?)
+            .arg("EX")
+            .arg(SESSION_TTL)
+            .ignore()
+            .cmd("SADD")
+            .arg(self.key(format_args!("user_sessions::{}", user_id)))
+            .arg(&token)
+            .ignore()
+            .query::<()>(self.connection)
+            .map(|_| token)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets the ID of the user to whom the session with the given token
+    /// belongs from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session
+    fn session_user(&mut self, token: &str) -> Result<Option<u64>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(format_args!("session::{}", token)))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Records that the session with the given token was just used in the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session
+    fn touch_session(&mut self, token: &str) -> Result<(), ProviderError> {
+        let mut session = match self.get_session(token)? {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        session.last_seen_at = Utc::now();
+
+        // The description of the session must expire alongside the session
+        // itself
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(self.key(format_args!("session::{}", token)))
+            .query(self.connection)?;
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        redis::cmd("SET")
+            .arg(self.key(format_args!("session_info::{}", token)))
+            .arg(serde_json::to_string(&session)?)
+            .arg("EX")
+            .arg(ttl)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets each of the active sessions belonging to the user with the given
+    /// ID from the redis caching layer. Expired sessions are pruned from the
+    /// user's list of sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose sessions should be obtained
+    fn user_sessions(&mut self, user_id: u64) -> Result<Vec<Session>, ProviderError> {
+        let tokens: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(self.key(format_args!("user_sessions::{}", user_id)))
+            .query(self.connection)?;
+
+        let mut sessions = Vec::new();
+        for token in tokens {
+            match self.get_session(&token)? {
+                Some(session) => sessions.push(session),
+                None => redis::cmd("SREM")
+                    .arg(self.key(format_args!("user_sessions::{}", user_id)))
+                    .arg(&token)
+                    .query(self.connection)?,
+            }
+        }
+        sessions.sort_by_key(|session| Reverse(session.last_seen_at));
+
+        Ok(sessions)
+    }
+
+    /// Revokes the session with the given token in the redis caching layer,
+    /// and announces its revocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the session that should be revoked
+    fn revoke_session(&mut self, token: &str) -> Result<(), ProviderError> {
+        let user_id = self.session_user(token)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("DEL")
+            .arg(self.key(format_args!("session::{}", token)))
+            .arg(self.key(format_args!("session_info::{}", token)))
+            .ignore()
+            .cmd("PUBLISH")
+            .arg(self.key(REVOCATION_CHANNEL))
+            .arg(token)
+            .ignore();
+        if let Some(user_id) = user_id {
+            pipe.cmd("SREM")
+                .arg(self.key(format_args!("user_sessions::{}", user_id)))
+                .arg(token)
+                .ignore();
+        }
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+
+    /// Revokes each of the sessions belonging to the user with the given ID
+    /// in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose sessions should be revoked
+    fn revoke_user_sessions(&mut self, user_id: u64) -> Result<Vec<String>, ProviderError> {
+        let tokens: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(self.key(format_args!("user_sessions::{}", user_id)))
+            .query(self.connection)?;
+
+        for token in &tokens {
+            self.revoke_session(token)?;
+        }
+
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_cache() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut sessions = Cache::new(&mut conn);
+        let token = sessions.issue_session(42069, Some("gnomebrowser"), Some("127.0.0.1"))?;
+        assert_eq!(sessions.session_user(&token)?, Some(42069));
+
+        sessions.touch_session(&token)?;
+        let listed = sessions.user_sessions(42069)?;
+        assert!(listed
+            .iter()
+            .any(|session| session.id() == token && session.device() == Some("gnomebrowser")));
+
+        sessions.revoke_session(&token)?;
+        assert_eq!(sessions.session_user(&token)?, None);
+
+        // Logging out everywhere revokes every session belonging to the user
+        let first = sessions.issue_session(42069, None, None)?;
+        let second = sessions.issue_session(42069, None, None)?;
+        sessions.revoke_user_sessions(42069)?;
+        assert_eq!(sessions.session_user(&first)?, None);
+        assert_eq!(sessions.session_user(&second)?, None);
+        assert!(sessions.user_sessions(42069)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, Scope,
+};
+use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            schema::settings,
+            settings::{Settings, SettingsEntry},
+        },
+        auth::Principal,
+        server::State,
+    },
+    users, Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the settings module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/settings")
+        .service(user_settings)
+        .service(update_settings)
+}
+
+/// Gets the chat preferences of the specified user. Users that have never
+/// saved their preferences are given the default settings. Users may only
+/// view their own settings, unless they are an administrator.
+#[get("/{user_id}")]
+pub async fn user_settings(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+) -> Result<Json<Settings>, HttpError> {
+    users::authorize_edit(&principal, *user_id)?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Ok(Json(
+        Hybrid::new(
+            Cache::new(&mut conn).with_prefix(state.key_prefix()),
+            Persistent::new(&persistent_conn),
+        )
+        .get_settings(*user_id)?
+        .unwrap_or_default(),
+    ))
+}
+
+/// Replaces the chat preferences of the specified user. Users may only edit
+/// their own settings, unless they are an administrator.
+#[put("/{user_id}")]
+pub async fn update_settings(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+    settings: Json<Settings>,
+) -> Result<Json<Settings>, HttpError> {
+    users::authorize_edit(&principal, *user_id)?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    if !settings.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "settings" }.into());
+    }
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .set_settings(*user_id, &settings)?;
+
+    Ok(settings)
+}
+
+/// Provider represents an arbitrary backend for the settings service, which
+/// is responsible for storing the chat preferences of each user.
+pub trait Provider {
+    /// Retreives the settings of the user with the given ID, if they have
+    /// saved any.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be obtained
+    fn get_settings(&mut self, user_id: u64) -> Result<Option<Settings>, ProviderError>;
+
+    /// Stores the given settings for the user with the given ID, replacing
+    /// any existing settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be stored
+    /// * `settings` - The settings that should be stored
+    fn set_settings(&mut self, user_id: u64, settings: &Settings) -> Result<(), ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Retreives the settings of the user with the given ID from the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be obtained
+    fn get_settings(&mut self, user_id: u64) -> Result<Option<Settings>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(format_args!("settings::{}", user_id)))
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given settings in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be stored
+    /// * `settings` - The settings that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, settings::Provider}, spec::settings::Settings};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut settings = Cache::new(&mut conn);
+    /// let preferences = Settings::default().with_timezone(Some("America/Chicago".to_owned()));
+    ///
+    /// settings.set_settings(69420, &preferences)?;
+    /// assert_eq!(settings.get_settings(69420)?, Some(preferences));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_settings(&mut self, user_id: u64, settings: &Settings) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(format_args!("settings::{}", user_id)))
+            .arg(serde_json::to_string(settings)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Retreives the settings of the user with the given ID from the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be obtained
+    fn get_settings(&mut self, user_id: u64) -> Result<Option<Settings>, ProviderError> {
+        settings::table
+            .find(Unsigned(user_id))
+            .first::<SettingsEntry>(self.connection)
+            .optional()?
+            .map_or(Ok(None), |entry| {
+                entry.settings().map(Some).map_err(|e| e.into())
+            })
+    }
+
+    /// Stores the given settings in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be stored
+    /// * `settings` - The settings that should be stored
+    fn set_settings(&mut self, user_id: u64, settings: &Settings) -> Result<(), ProviderError> {
+        diesel::replace_into(settings::table)
+            .values(SettingsEntry::new(user_id, settings)?)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Retreives the settings of the user with the given ID, populating the
+    /// cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be obtained
+    fn get_settings(&mut self, user_id: u64) -> Result<Option<Settings>, ProviderError> {
+        match self.cache.get_settings(user_id) {
+            Ok(Some(settings)) => Ok(Some(settings)),
+            _ => self.persistent.get_settings(user_id).and_then(|settings| {
+                settings.map_or(Ok(None), |settings| {
+                    self.cache
+                        .set_settings(user_id, &settings)
+                        .map(|_| Some(settings))
+                })
+            }),
+        }
+    }
+
+    /// Stores the given settings in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose settings should be stored
+    /// * `settings` - The settings that should be stored
+    fn set_settings(&mut self, user_id: u64, settings: &Settings) -> Result<(), ProviderError> {
+        self.persistent
+            .set_settings(user_id, settings)
+            .and_then(|_| self.cache.set_settings(user_id, settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let preferences = Settings::default()
+            .with_notify_on_whisper(false)
+            .with_hidden_flairs(vec!["subscriber".to_owned()]);
+
+        let mut settings = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        settings.set_settings(42069, &preferences)?;
+
+        assert_eq!(settings.get_settings(42069)?, Some(preferences.clone()));
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg("settings::42069")
+            .query::<()>(&mut conn)?;
+        assert_eq!(
+            Persistent::new(&persistent_conn).get_settings(42069)?,
+            Some(preferences)
+        );
+
+        Ok(())
+    }
+}
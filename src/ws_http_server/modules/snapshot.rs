@@ -0,0 +1,365 @@
+use diesel::{ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::Serialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            ban::Ban,
+            mute::Mute,
+            schema::{bans, mutes, roles, users},
+            user::{Role, RoleEntry},
+        },
+        lookups::Lookup,
+    },
+    Cache, Hybrid, Layers, Memory, Persistent, ProviderError,
+};
+
+use std::collections::HashMap;
+
+/// The values held by the redis caching layer about a single user: the
+/// fields of their ban and mute, their roles, and their username.
+type CachedState = (
+    HashMap<String, String>,
+    HashMap<String, String>,
+    Vec<String>,
+    Option<String>,
+);
+
+/// UserModerationState represents everything that must be known about a
+/// chatter in order to set up their connection: their username, the ban and
+/// mute issued against them, if any, and the roles that they hold.
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct UserModerationState {
+    /// The ID of the user
+    user_id: u64,
+
+    /// The username held by the user, if they have one
+    username: Option<String>,
+
+    /// The most recent ban issued against the user, if any
+    ban: Option<Ban>,
+
+    /// The most recent mute issued against the user, if any
+    mute: Option<Mute>,
+
+    /// The roles held by the user
+    roles: Vec<Role>,
+}
+
+impl UserModerationState {
+    /// Creates a new moderation state for the user with the given ID, who
+    /// holds no username or roles, and hasn't been banned or muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user
+    pub fn new(user_id: u64) -> Self {
+        Self {
+            user_id,
+            username: None,
+            ban: None,
+            mute: None,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Retreives the ID of the user.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Retreives the username held by the user, if they have one.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Retreives the most recent ban issued against the user, if any.
+    pub fn ban(&self) -> Option<&Ban> {
+        self.ban.as_ref()
+    }
+
+    /// Retreives the most recent mute issued against the user, if any.
+    pub fn mute(&self) -> Option<&Mute> {
+        self.mute.as_ref()
+    }
+
+    /// Retreives the roles held by the user.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    /// Determines whether or not the user is currently banned.
+    pub fn is_banned(&self) -> bool {
+        self.ban.as_ref().is_some_and(|ban| ban.active())
+    }
+
+    /// Determines whether or not the user is currently muted.
+    pub fn is_muted(&self) -> bool {
+        self.mute.as_ref().is_some_and(|mute| mute.active())
+    }
+}
+
+/// Provider represents an arbitrary backend for the user snapshot service,
+/// which gathers the state held by the bans, mutes, roles and name resolver
+/// services about a set of users at once.
+pub trait Provider {
+    /// Obtains the moderation state of each of the given users, in the order
+    /// that the users were provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose state should be obtained
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Obtains the moderation state of each of the given users in a single
+    /// round trip to the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose state should be obtained
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{
+    ///     roles::Provider as RolesProvider, snapshot::Provider, Cache,
+    /// };
+    /// use gnomegg::spec::user::Role;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut cache = Cache::new(&mut conn);
+    /// cache.give_roles(69, &[Role::VIP])?;
+    ///
+    /// let states = cache.moderation_states(&[69])?;
+    /// assert!(states[0].roles().contains(&Role::VIP));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = user_ids
+            .iter()
+            .map(|user_id| {
+                (
+                    self.key(format_args!("banned::{}", user_id)),
+                    self.key(format_args!("muted::{}", user_id)),
+                    self.key(format_args!("roles::{}", user_id)),
+                    self.key(format_args!("username::{}", user_id)),
+                )
+            })
+            .collect::<Vec<(String, String, String, String)>>();
+
+        let raw: Vec<CachedState> = self.pipeline(|pipe| {
+            for (ban_key, mute_key, roles_key, username_key) in &keys {
+                pipe.cmd("HGETALL")
+                    .arg(ban_key)
+                    .cmd("HGETALL")
+                    .arg(mute_key)
+                    .cmd("SMEMBERS")
+                    .arg(roles_key)
+                    .cmd("GET")
+                    .arg(username_key);
+            }
+        })?;
+
+        user_ids
+            .iter()
+            .zip(raw)
+            .map(|(user_id, (ban, mute, roles, username))| {
+                Ok(UserModerationState {
+                    user_id: *user_id,
+                    username,
+                    ban: Ban::from_fields(&ban)?,
+                    mute: Mute::from_fields(&mute)?,
+                    roles: roles.iter().filter_map(|role| role.parse().ok()).collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Obtains the moderation state of each of the given users in a single
+    /// query joining the users, bans, mutes and roles tables. Users without
+    /// an account are reported as holding no username or roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose state should be obtained
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError> {
+        let mut states = users::table
+            .left_join(bans::table.on(bans::dsl::user_id.eq(users::dsl::id)))
+            .left_join(mutes::table.on(mutes::dsl::user_id.eq(users::dsl::id)))
+            .left_join(roles::table.on(roles::dsl::user_id.eq(users::dsl::id)))
+            .filter(users::dsl::id.eq_any(user_ids.iter().copied().map(Unsigned)))
+            .select((
+                users::dsl::id,
+                users::dsl::username,
+                bans::all_columns.nullable(),
+                mutes::all_columns.nullable(),
+                roles::all_columns.nullable(),
+            ))
+            .load::<(
+                u64,
+                Option<String>,
+                Option<Ban>,
+                Option<Mute>,
+                Option<RoleEntry>,
+            )>(self.reader())?
+            .into_iter()
+            .map(|(user_id, username, ban, mute, roles)| {
+                (
+                    user_id,
+                    UserModerationState {
+                        user_id,
+                        username,
+                        ban,
+                        mute,
+                        roles: roles
+                            .as_ref()
+                            .map(<Vec<Role> as From<&RoleEntry>>::from)
+                            .unwrap_or_default(),
+                    },
+                )
+            })
+            .collect::<HashMap<u64, UserModerationState>>();
+
+        Ok(user_ids
+            .iter()
+            .map(|user_id| {
+                states
+                    .remove(user_id)
+                    .unwrap_or_else(|| UserModerationState::new(*user_id))
+            })
+            .collect())
+    }
+}
+
+impl Provider for Memory {
+    /// Obtains the moderation state of each of the given users from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose state should be obtained
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError> {
+        Ok(user_ids
+            .iter()
+            .map(|user_id| UserModerationState {
+                user_id: *user_id,
+                username: self.usernames.get(user_id).cloned(),
+                ban: self.bans.get(user_id).cloned(),
+                mute: self.mutes.get(user_id).cloned(),
+                roles: self.roles.get(user_id).cloned().unwrap_or_default(),
+            })
+            .collect())
+    }
+}
+
+impl Lookup for Vec<UserModerationState> {
+    /// A user absent from the caching layer is neither banned, muted, nor
+    /// holding any roles, so each state read from the caching layer is
+    /// counted as a hit.
+    fn outcomes(&self) -> (u64, u64) {
+        (self.len() as u64, 0)
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Obtains the moderation state of each of the given users from the
+    /// caching layer, falling back to the persistent layer if the caching
+    /// layer fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The IDs of the users whose state should be obtained
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError> {
+        let cached = self.cache.moderation_states(user_ids);
+        self.fall_back("snapshot", cached, |hybrid| {
+            hybrid.persistent.moderation_states(user_ids)
+        })
+    }
+}
+
+impl<T: Provider + ?Sized> Provider for Box<T> {
+    fn moderation_states(
+        &mut self,
+        user_ids: &[u64],
+    ) -> Result<Vec<UserModerationState>, ProviderError> {
+        (**self).moderation_states(user_ids)
+    }
+}
+
+/// Selects the snapshot provider matching the layers backing the
+/// deployment's bans and mutes.
+///
+/// # Arguments
+///
+/// * `layers` - The layers backing the bans and mutes providers
+/// * `cache` - The redis caching layer
+/// * `persistent` - The MySQL persistence layer
+pub fn provider<'a>(
+    layers: Layers,
+    cache: Cache<'a>,
+    persistent: Persistent<'a>,
+) -> Box<dyn Provider + 'a> {
+    match layers {
+        Layers::Cache => Box::new(cache),
+        Layers::Persistent => Box::new(persistent),
+        Layers::Hybrid => Box::new(Hybrid::new(cache, persistent)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{bans::Provider as BansProvider, roles::Provider as RolesProvider},
+        *,
+    };
+
+    #[test]
+    fn test_memory() -> Result<(), ProviderError> {
+        let mut memory = Memory::new();
+        memory.give_roles(1, &[Role::Moderator])?;
+        memory.set_banned(2, true, Some(1_000_000_000_000), None)?;
+
+        let states = memory.moderation_states(&[1, 2, 3])?;
+        assert_eq!(
+            states
+                .iter()
+                .map(|state| state.user_id())
+                .collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(states[0].roles(), &[Role::Moderator]);
+        assert!(!states[0].is_banned());
+        assert!(states[1].is_banned());
+        assert_eq!(states[2], UserModerationState::new(3));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Cache, Hybrid, ProviderError};
+
+/// The number of each user's most recent messages that new messages are
+/// compared against.
+pub const RECENT_MESSAGES: usize = 5;
+
+/// The number of seconds after a user's last message for which their recent
+/// messages are remembered.
+pub const RECENT_WINDOW: u64 = 60;
+
+/// The number of seconds over which a user's spam offenses are counted.
+pub const OFFENSE_WINDOW: u64 = 10 * 60;
+
+/// The maximum number of bits by which the SimHashes of two similar messages
+/// may differ.
+pub const MAX_SIMILAR_DISTANCE: u32 = 6;
+
+/// The minimum number of characters in a message that is compared to recent
+/// messages by similarity. Shorter messages are only rejected if they are
+/// exact duplicates, as a single changed character makes up too much of them.
+pub const MIN_SIMILAR_LENGTH: usize = 24;
+
+/// The number of characters in each of the shingles hashed into a message's
+/// SimHash.
+const SHINGLE_LENGTH: usize = 3;
+
+/// Fingerprint represents the hashes by which a message is compared to a
+/// user's recent messages.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Fingerprint {
+    /// The hash of the normalized contents of the message
+    exact: u64,
+
+    /// The SimHash of the normalized contents of the message, if it is long
+    /// enough to be compared by similarity
+    simhash: Option<u64>,
+}
+
+impl Fingerprint {
+    /// Creates a new fingerprint of the given message. Messages are compared
+    /// without regard to case or whitespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The contents of the message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::spam::Fingerprint;
+    ///
+    /// let fingerprint = Fingerprint::new("check out my stream at the link in my bio");
+    /// assert!(fingerprint.is_similar_to(&Fingerprint::new("Check out my stream at the link in my bio!!")));
+    /// assert!(!fingerprint.is_similar_to(&Fingerprint::new("did anyone catch the debate last night?")));
+    /// ```
+    pub fn new(msg: &str) -> Self {
+        let normalized = msg
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        Self {
+            exact: hash(normalized.as_bytes()),
+            simhash: if normalized.chars().count() >= MIN_SIMILAR_LENGTH {
+                Some(simhash(&normalized))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Determines whether or not the message is a duplicate of, or is highly
+    /// similar to, the message with the given fingerprint.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The fingerprint of the message that should be compared
+    pub fn is_similar_to(&self, other: &Self) -> bool {
+        self.exact == other.exact
+            || match (self.simhash, other.simhash) {
+                (Some(a), Some(b)) => (a ^ b).count_ones() <= MAX_SIMILAR_DISTANCE,
+                _ => false,
+            }
+    }
+}
+
+/// Hashes the given bytes with 64-bit FNV-1a, followed by a finalizer that
+/// spreads short inputs across each of the bits. Hashes are stored between
+/// runs, so a hasher with a stable output is used rather than std's.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes that should be hashed
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h: u64, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    h ^ (h >> 31)
+}
+
+/// Computes the SimHash of the given normalized message from its character
+/// shingles, such that similar messages have hashes differing by few bits.
+///
+/// # Arguments
+///
+/// * `normalized` - The normalized contents of the message
+fn simhash(normalized: &str) -> u64 {
+    let chars = normalized.chars().collect::<Vec<char>>();
+    let mut weights = [0i32; 64];
+
+    for shingle in chars.windows(SHINGLE_LENGTH) {
+        let h = hash(shingle.iter().collect::<String>().as_bytes());
+
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if h >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |simhash, (bit, _)| simhash | 1 << bit)
+}
+
+/// Gets the redis key of the list holding the fingerprints of the given
+/// user's recent messages.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose messages should be located
+fn recent_key(user_id: u64) -> String {
+    format!("recent_messages::{}", user_id)
+}
+
+/// Gets the redis key under which the given user's spam offenses are
+/// counted.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose offenses should be located
+fn offenses_key(user_id: u64) -> String {
+    format!("spam_offenses::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the spam detector, which
+/// remembers each user's recent messages and counts their offenses. Both are
+/// ephemeral, and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Gets the fingerprints of the given user's recent messages, newest
+    /// first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be obtained
+    fn recent_fingerprints(&mut self, user_id: u64) -> Result<Vec<Fingerprint>, ProviderError>;
+
+    /// Remembers the fingerprint of a message sent by the given user,
+    /// forgetting their oldest message if their window is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `fingerprint` - The fingerprint of the message
+    fn record_fingerprint(
+        &mut self,
+        user_id: u64,
+        fingerprint: &Fingerprint,
+    ) -> Result<(), ProviderError>;
+
+    /// Records a spam offense by the given user, returning the number of
+    /// offenses they have committed within the offense window.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent spam
+    fn record_offense(&mut self, user_id: u64) -> Result<u64, ProviderError>;
+
+    /// Determines whether or not the message with the given fingerprint is a
+    /// duplicate of, or is highly similar to, one of the user's recent
+    /// messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user sending the message
+    /// * `fingerprint` - The fingerprint of the message
+    fn is_repeat(
+        &mut self,
+        user_id: u64,
+        fingerprint: &Fingerprint,
+    ) -> Result<bool, ProviderError> {
+        Ok(self
+            .recent_fingerprints(user_id)?
+            .iter()
+            .any(|recent| fingerprint.is_similar_to(recent)))
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the fingerprints of the given user's recent messages from the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be obtained
+    fn recent_fingerprints(&mut self, user_id: u64) -> Result<Vec<Fingerprint>, ProviderError> {
+        redis::cmd("LRANGE")
+            .arg(self.key(recent_key(user_id)))
+            .arg(0)
+            .arg(RECENT_MESSAGES - 1)
+            .query::<Vec<String>>(self.connection)?
+            .iter()
+            .map(|fingerprint| serde_json::from_str(fingerprint).map_err(|e| e.into()))
+            .collect()
+    }
+
+    /// Remembers the fingerprint of a message sent by the given user in the
+    /// redis caching layer. The user's window expires once they have gone
+    /// quiet for the recent window.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `fingerprint` - The fingerprint of the message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::modules::{Cache, spam::{Fingerprint, Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut spam = Cache::new(&mut conn);
+    /// spam.record_fingerprint(69420, &Fingerprint::new("OverRustle"))?;
+    ///
+    /// assert!(spam.is_repeat(69420, &Fingerprint::new("overrustle"))?);
+    /// Ok(())
+    /// # }
+    /// ```
+    fn record_fingerprint(
+        &mut self,
+        user_id: u64,
+        fingerprint: &Fingerprint,
+    ) -> Result<(), ProviderError> {
+        let key = self.key(recent_key(user_id));
+
+        redis::pipe()
+            .cmd("LPUSH")
+            .arg(&key)
+            .arg(serde_json::to_string(fingerprint)?)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&key)
+            .arg(0)
+            .arg(RECENT_MESSAGES - 1)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(RECENT_WINDOW)
+            .ignore()
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Records a spam offense by the given user in the redis caching layer.
+    /// The counter expires after the offense window.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent spam
+    fn record_offense(&mut self, user_id: u64) -> Result<u64, ProviderError> {
+        let offenses: u64 = redis::cmd("INCR")
+            .arg(self.key(offenses_key(user_id)))
+            .query(self.connection)?;
+
+        if offenses == 1 {
+            redis::cmd("EXPIRE")
+                .arg(self.key(offenses_key(user_id)))
+                .arg(OFFENSE_WINDOW)
+                .query::<()>(self.connection)?;
+        }
+
+        Ok(offenses)
+    }
+}
+
+impl<C: Provider, P> Provider for Hybrid<C, P> {
+    /// Gets the fingerprints of the given user's recent messages from the
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be obtained
+    fn recent_fingerprints(&mut self, user_id: u64) -> Result<Vec<Fingerprint>, ProviderError> {
+        self.cache.recent_fingerprints(user_id)
+    }
+
+    /// Remembers the fingerprint of a message sent by the given user in the
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `fingerprint` - The fingerprint of the message
+    fn record_fingerprint(
+        &mut self,
+        user_id: u64,
+        fingerprint: &Fingerprint,
+    ) -> Result<(), ProviderError> {
+        self.cache.record_fingerprint(user_id, fingerprint)
+    }
+
+    /// Records a spam offense by the given user in the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent spam
+    fn record_offense(&mut self, user_id: u64) -> Result<u64, ProviderError> {
+        self.cache.record_offense(user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_fingerprint() {
+        let spam = Fingerprint::new("FREE SUBS at my channel, just follow and type !claim in chat");
+
+        assert!(spam.is_similar_to(&Fingerprint::new(
+            "free subs at my channel,  just follow and type !claim in chat"
+        )));
+        assert!(spam.is_similar_to(&Fingerprint::new(
+            "FREE SUBS at my channel, just follow and type !claim in chat 2"
+        )));
+        assert!(!spam.is_similar_to(&Fingerprint::new(
+            "the stream is muted on my end, is anyone else hearing nothing"
+        )));
+
+        // Short messages are only compared exactly
+        assert!(Fingerprint::new("OMEGALUL").is_similar_to(&Fingerprint::new("omegalul")));
+        assert!(!Fingerprint::new("OMEGALUL").is_similar_to(&Fingerprint::new("OMEGALUL2")));
+    }
+
+    #[test]
+    fn test_cache() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        redis::cmd("DEL")
+            .arg(recent_key(42069))
+            .arg(offenses_key(42069))
+            .query::<()>(&mut conn)?;
+
+        let mut spam = Cache::new(&mut conn);
+        spam.record_fingerprint(42069, &Fingerprint::new("first"))?;
+
+        for i in 0..RECENT_MESSAGES {
+            spam.record_fingerprint(42069, &Fingerprint::new(&i.to_string()))?;
+        }
+
+        // The oldest message should have left the window
+        assert!(!spam.is_repeat(42069, &Fingerprint::new("first"))?);
+        assert!(spam.is_repeat(42069, &Fingerprint::new("0"))?);
+
+        assert_eq!(spam.record_offense(42069)?, 1);
+        assert_eq!(spam.record_offense(42069)?, 2);
+
+        Ok(())
+    }
+}
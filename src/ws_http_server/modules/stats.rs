@@ -0,0 +1,628 @@
+use actix_web::{
+    rt,
+    web::{self, Data, Json, Query},
+    Error as HttpError, Scope,
+};
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            schema::chat_stats,
+            stats::{
+                parse_leaderboard_period, DailyStats, TopChatter, Window, MAX_LEADERBOARD_HOURS,
+            },
+        },
+        server::State,
+    },
+    name_resolver::Provider as NameResolver,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::time::Duration;
+
+/// The interval at which cached message counts are rolled up into the
+/// persistent layer.
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The redis hash holding the number of public messages sent by each user,
+/// keyed by their IDs. Only used without a persistent layer.
+const TOTALS_KEY: &str = "stats_lines";
+
+/// The redis hash holding the number of public messages sent by each user
+/// that have not yet been rolled up into the persistent layer.
+const PENDING_KEY: &str = "stats_pending_lines";
+
+/// The redis hash holding the message counts currently being rolled up into
+/// the persistent layer.
+const ROLLING_KEY: &str = "stats_rolling_lines";
+
+/// The field of each window's hash holding the number of messages sent by
+/// every user.
+const GLOBAL_FIELD: &str = "all";
+
+/// The period over which chatters are ranked if none is specified.
+const DEFAULT_LEADERBOARD_PERIOD: &str = "24h";
+
+/// The number of chatters returned by the leaderboard route if no limit is
+/// specified.
+const DEFAULT_LEADERBOARD_LENGTH: usize = 10;
+
+/// The maximum number of chatters that may be returned by the leaderboard
+/// route.
+const MAX_LEADERBOARD_LENGTH: usize = 100;
+
+/// The number of seconds for which a computed leaderboard is reused, so that
+/// frontends polling the leaderboard don't each recompute it.
+const LEADERBOARD_TTL: u64 = 30;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the stats module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/stats").service(top_chatters)
+}
+
+/// Periodically rolls up the message counts recorded in the caching layer
+/// into the persistent layer for as long as the server is running.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_rollup_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(ROLLUP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // A failed rollup leaves its counts in the cache, so they will be
+            // retried on the next tick
+            let _ = web::block(move || -> Result<usize, ProviderError> {
+                let mut conn = state.cache_connection()?;
+                let persistent_conn = state.persistent_connection()?;
+
+                Hybrid::new(
+                    Cache::new(&mut conn).with_prefix(state.key_prefix()),
+                    Persistent::new(&persistent_conn),
+                )
+                .roll_up_stats(Utc::now())
+            })
+            .await;
+        }
+    });
+}
+
+/// LeaderboardQuery represents the query parameters accepted by the
+/// leaderboard route.
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    /// (optional) The period over which chatters should be ranked, as a
+    /// number of hours or days (e.g. "24h" or "7d")
+    window: Option<String>,
+
+    /// (optional) The maximum number of chatters that should be returned
+    limit: Option<usize>,
+}
+
+/// Gets the chatters who sent the most public messages over the given
+/// period, most active first.
+#[get("/top")]
+pub async fn top_chatters(
+    state: Data<State>,
+    query: Query<LeaderboardQuery>,
+) -> Result<Json<Vec<TopChatter>>, HttpError> {
+    let hours = parse_leaderboard_period(
+        query
+            .window
+            .as_deref()
+            .unwrap_or(DEFAULT_LEADERBOARD_PERIOD),
+    )
+    .ok_or(ProviderError::InvalidArgument { arg: "window" })?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LEADERBOARD_LENGTH)
+        .min(MAX_LEADERBOARD_LENGTH);
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let top = hybrid.top_chatters(hours, limit, Utc::now())?;
+    let usernames = hybrid.usernames_for(
+        &top.iter()
+            .map(|(user_id, _)| *user_id)
+            .collect::<Vec<u64>>(),
+    )?;
+
+    Ok(Json(
+        top.into_iter()
+            .zip(usernames)
+            .enumerate()
+            .map(|(i, ((user_id, lines), username))| {
+                TopChatter::new(i + 1, user_id, username, lines)
+            })
+            .collect(),
+    ))
+}
+
+/// Provider represents an arbitrary backend for the stats service, which
+/// counts the public messages sent by each user.
+pub trait Provider {
+    /// Records that the user with the given ID sent a public message at the
+    /// given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `at` - The time at which the message was sent
+    fn record_line(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError>;
+
+    /// Counts the public messages that the user with the given ID has ever
+    /// sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be counted
+    fn lines_typed(&mut self, user_id: u64) -> Result<u64, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Counts a public message in the window containing the given time, both
+    /// for the user who sent it and for the entire chat, and adds it to the
+    /// user's count in the given hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `at` - The time at which the message was sent
+    /// * `counter` - The hash holding the user's running count
+    fn count_line(
+        &mut self,
+        user_id: u64,
+        at: DateTime<Utc>,
+        counter: &str,
+    ) -> Result<(), ProviderError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("HINCRBY")
+            .arg(self.key(counter))
+            .arg(user_id)
+            .arg(1)
+            .ignore();
+
+        for window in Window::ALL.iter() {
+            let key = self.key(window_key(*window, at));
+
+            pipe.cmd("HINCRBY")
+                .arg(&key)
+                .arg(user_id)
+                .arg(1)
+                .ignore()
+                .cmd("HINCRBY")
+                .arg(&key)
+                .arg(GLOBAL_FIELD)
+                .arg(1)
+                .ignore()
+                .cmd("EXPIRE")
+                .arg(&key)
+                .arg(window.retention())
+                .ignore();
+        }
+
+        // Chatters are also ranked by hour, so that the top chatters over
+        // any number of hours may be found by merging the hours' rankings
+        let key = self.key(leaderboard_key(Window::Hour.bucket(at)));
+        pipe.cmd("ZINCRBY")
+            .arg(&key)
+            .arg(1)
+            .arg(user_id)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg((MAX_LEADERBOARD_HOURS + 1) as i64 * Window::Hour.seconds())
+            .ignore();
+
+        pipe.query(self.connection).map_err(|e| e.into())
+    }
+
+    /// Counts the public messages sent in the window containing the given
+    /// time, either by the user with the given ID, or by every user.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The length of the window
+    /// * `user_id` - (optional) The ID of the user whose messages should be
+    /// counted
+    /// * `at` - A time within the window
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::{spec::stats::Window, ws_http_server::modules::{Cache, stats::Provider}};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut stats = Cache::new(&mut conn);
+    /// stats.record_line(69420, Utc::now())?;
+    ///
+    /// assert!(stats.message_count(Window::Minute, Some(69420), Utc::now())? > 0);
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn message_count(
+        &mut self,
+        window: Window,
+        user_id: Option<u64>,
+        at: DateTime<Utc>,
+    ) -> Result<u64, ProviderError> {
+        let mut cmd = redis::cmd("HGET");
+        cmd.arg(self.key(window_key(window, at)));
+
+        match user_id {
+            Some(user_id) => cmd.arg(user_id),
+            None => cmd.arg(GLOBAL_FIELD),
+        };
+
+        cmd.query::<Option<u64>>(self.connection)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Cache<'a> {
+    /// Ranks the chatters who sent the most public messages over the given
+    /// number of hours, including the current hour, returning the ID of each
+    /// chatter alongside their message count, most active first. Rankings
+    /// are reused for LEADERBOARD_TTL seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `hours` - The number of hours over which chatters should be ranked
+    /// * `limit` - The maximum number of chatters that should be returned
+    /// * `now` - The current time
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use gnomegg::ws_http_server::modules::{Cache, stats::Provider};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut stats = Cache::new(&mut conn);
+    /// stats.record_line(69420, Utc::now())?;
+    ///
+    /// assert!(!stats.top_chatters(24, 10, Utc::now())?.is_empty());
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn top_chatters(
+        &mut self,
+        hours: u64,
+        limit: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(u64, u64)>, ProviderError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let current = Window::Hour.bucket(now);
+        let merged = self.key(format_args!("stats_top::{}h::{}", hours, current));
+
+        if !redis::cmd("EXISTS")
+            .arg(&merged)
+            .query::<bool>(self.connection)?
+        {
+            let hourly = (0..hours as i64)
+                .map(|i| self.key(leaderboard_key(current - i)))
+                .collect::<Vec<String>>();
+
+            redis::pipe()
+                .atomic()
+                .cmd("ZUNIONSTORE")
+                .arg(&merged)
+                .arg(hourly.len())
+                .arg(hourly)
+                .ignore()
+                .cmd("EXPIRE")
+                .arg(&merged)
+                .arg(LEADERBOARD_TTL)
+                .ignore()
+                .query::<()>(self.connection)?;
+        }
+
+        redis::cmd("ZREVRANGE")
+            .arg(&merged)
+            .arg(0)
+            .arg(limit - 1)
+            .arg("WITHSCORES")
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Records that the user with the given ID sent a public message at the
+    /// given time in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `at` - The time at which the message was sent
+    fn record_line(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.count_line(user_id, at, TOTALS_KEY)
+    }
+
+    /// Counts the public messages that the user with the given ID has sent
+    /// according to the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be counted
+    fn lines_typed(&mut self, user_id: u64) -> Result<u64, ProviderError> {
+        redis::cmd("HGET")
+            .arg(self.key(TOTALS_KEY))
+            .arg(user_id)
+            .query::<Option<u64>>(self.connection)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Adds each of the given message counts to the counts stored in the
+    /// MySQL database for the given day.
+    ///
+    /// # Arguments
+    ///
+    /// * `counts` - The IDs of each user, alongside the number of messages
+    /// that they sent
+    /// * `at` - A time within the day on which the messages were sent
+    pub fn add_lines(
+        &mut self,
+        counts: &[(u64, u64)],
+        at: DateTime<Utc>,
+    ) -> Result<(), ProviderError> {
+        let connection = self.connection;
+        let day = at.naive_utc().date();
+
+        connection.transaction(|| {
+            for (user_id, lines) in counts {
+                let existing = chat_stats::table
+                    .find((Unsigned(*user_id), day))
+                    .select(chat_stats::dsl::lines)
+                    .first::<u64>(connection)
+                    .optional()?;
+
+                diesel::replace_into(chat_stats::table)
+                    .values(&DailyStats::new(
+                        *user_id,
+                        day,
+                        existing.unwrap_or_default() + lines,
+                    ))
+                    .execute(connection)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Records that the user with the given ID sent a public message at the
+    /// given time in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `at` - The time at which the message was sent
+    fn record_line(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.add_lines(&[(user_id, 1)], at)
+    }
+
+    /// Counts the public messages that the user with the given ID has sent
+    /// according to the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be counted
+    fn lines_typed(&mut self, user_id: u64) -> Result<u64, ProviderError> {
+        chat_stats::table
+            .filter(chat_stats::dsl::user_id.eq(Unsigned(user_id)))
+            .select(chat_stats::dsl::lines)
+            .load::<u64>(self.connection)
+            .map(|lines| lines.iter().sum())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Counts the public messages sent in the window containing the given
+    /// time, either by the user with the given ID, or by every user. Windowed
+    /// counts are only kept in the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The length of the window
+    /// * `user_id` - (optional) The ID of the user whose messages should be
+    /// counted
+    /// * `at` - A time within the window
+    pub fn message_count(
+        &mut self,
+        window: Window,
+        user_id: Option<u64>,
+        at: DateTime<Utc>,
+    ) -> Result<u64, ProviderError> {
+        self.cache.message_count(window, user_id, at)
+    }
+
+    /// Ranks the chatters who sent the most public messages over the given
+    /// number of hours, most active first. Rankings are only kept in the
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `hours` - The number of hours over which chatters should be ranked
+    /// * `limit` - The maximum number of chatters that should be returned
+    /// * `now` - The current time
+    pub fn top_chatters(
+        &mut self,
+        hours: u64,
+        limit: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(u64, u64)>, ProviderError> {
+        self.cache.top_chatters(hours, limit, now)
+    }
+
+    /// Rolls up the message counts recorded in the caching layer into the
+    /// persistent layer, attributing them to the day containing the given
+    /// time. Returns the number of users whose counts were rolled up.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time
+    pub fn roll_up_stats(&mut self, now: DateTime<Utc>) -> Result<usize, ProviderError> {
+        // Counts left over from a failed rollup are retried before any new
+        // counts are taken, so that they aren't overwritten
+        let exists = |key: &str, conn: &mut redis::Connection| {
+            redis::cmd("EXISTS").arg(key).query::<bool>(conn)
+        };
+
+        if !exists(&self.cache.key(ROLLING_KEY), self.cache.connection)? {
+            if !exists(&self.cache.key(PENDING_KEY), self.cache.connection)? {
+                return Ok(0);
+            }
+
+            redis::cmd("RENAME")
+                .arg(self.cache.key(PENDING_KEY))
+                .arg(self.cache.key(ROLLING_KEY))
+                .query::<()>(self.cache.connection)?;
+        }
+
+        let counts = redis::cmd("HGETALL")
+            .arg(self.cache.key(ROLLING_KEY))
+            .query::<Vec<(u64, u64)>>(self.cache.connection)?;
+        self.persistent.add_lines(&counts, now)?;
+
+        redis::cmd("DEL")
+            .arg(self.cache.key(ROLLING_KEY))
+            .query::<()>(self.cache.connection)?;
+
+        Ok(counts.len())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Records that the user with the given ID sent a public message at the
+    /// given time. Messages are only counted in the caching layer, and are
+    /// periodically rolled up into the persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who sent the message
+    /// * `at` - The time at which the message was sent
+    fn record_line(&mut self, user_id: u64, at: DateTime<Utc>) -> Result<(), ProviderError> {
+        self.cache.count_line(user_id, at, PENDING_KEY)
+    }
+
+    /// Counts the public messages that the user with the given ID has sent,
+    /// including those that have not yet been rolled up into the persistent
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose messages should be counted
+    fn lines_typed(&mut self, user_id: u64) -> Result<u64, ProviderError> {
+        let (pending, rolling) = redis::pipe()
+            .cmd("HGET")
+            .arg(self.cache.key(PENDING_KEY))
+            .arg(user_id)
+            .cmd("HGET")
+            .arg(self.cache.key(ROLLING_KEY))
+            .arg(user_id)
+            .query::<(Option<u64>, Option<u64>)>(self.cache.connection)?;
+
+        Ok(self.persistent.lines_typed(user_id)?
+            + pending.unwrap_or_default()
+            + rolling.unwrap_or_default())
+    }
+}
+
+/// Gets the redis hash holding the message counts of the window containing
+/// the given time.
+///
+/// # Arguments
+///
+/// * `window` - The length of the window
+/// * `at` - A time within the window
+fn window_key(window: Window, at: DateTime<Utc>) -> String {
+    format!("stats::{}::{}", window.name(), window.bucket(at))
+}
+
+/// Gets the redis sorted set ranking the chatters of the given hour.
+///
+/// # Arguments
+///
+/// * `hour` - The index of the hour, as given by `Window::Hour.bucket`
+fn leaderboard_key(hour: i64) -> String {
+    format!("stats_top::{}", hour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_rollup() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let now = Utc::now();
+
+        let mut stats = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        let before = stats.lines_typed(42069)?;
+        let minute = stats.message_count(Window::Minute, None, now)?;
+
+        stats.record_line(42069, now)?;
+        stats.record_line(42069, now)?;
+        assert_eq!(stats.lines_typed(42069)?, before + 2);
+        assert!(stats.message_count(Window::Minute, None, now)? >= minute + 2);
+        assert!(stats
+            .top_chatters(1, MAX_LEADERBOARD_LENGTH, now)?
+            .iter()
+            .any(|(user_id, lines)| *user_id == 42069 && *lines >= 2));
+
+        // Rolled up counts are read from the persistent layer instead
+        assert!(stats.roll_up_stats(now)? > 0);
+        assert_eq!(stats.lines_typed(42069)?, before + 2);
+        assert_eq!(
+            Persistent::new(&persistent_conn).lines_typed(42069)?,
+            before + 2
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+use actix_web::{
+    http::StatusCode,
+    web::{Bytes, Data},
+    Error as HttpError, HttpRequest, HttpResponse, ResponseError, Scope,
+};
+use chrono::{DateTime, Duration, Utc};
+use openssl::{error::ErrorStack, hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            announcement::NewAnnouncement,
+            event::{Event, EventKind, EventTarget, StreamStatus},
+        },
+        problem::ErrorCode,
+        server::State,
+    },
+    Cache, ProviderError,
+};
+
+use std::{
+    error::Error,
+    fmt::{self, Write},
+};
+
+/// The redis channel on which each change in the status of the stream is
+/// published, so that it may be pushed to every connected chatter.
+pub const STREAM_CHANNEL: &str = "stream";
+
+/// The number of seconds after being sent for which a webhook notification
+/// is accepted. Older notifications are rejected, so that they can't be
+/// replayed.
+pub const MAX_NOTIFICATION_AGE: i64 = 10 * 60;
+
+/// The header holding the unique ID of a webhook notification.
+const MESSAGE_ID_HEADER: &str = "Twitch-Eventsub-Message-Id";
+
+/// The header holding the time at which a webhook notification was sent.
+const MESSAGE_TIMESTAMP_HEADER: &str = "Twitch-Eventsub-Message-Timestamp";
+
+/// The header holding the signature of a webhook notification.
+const MESSAGE_SIGNATURE_HEADER: &str = "Twitch-Eventsub-Message-Signature";
+
+/// The header holding the kind of a webhook notification.
+const MESSAGE_TYPE_HEADER: &str = "Twitch-Eventsub-Message-Type";
+
+/// The redis key holding whether or not the stream is live.
+const LIVE_KEY: &str = "stream_live";
+
+/// The redis key holding the current status of the stream.
+const STATUS_KEY: &str = "stream_status";
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the stream module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/hooks").service(stream_hook)
+}
+
+/// WebhookError represents any error encountered while authenticating a
+/// request to the stream status webhook.
+#[derive(Debug)]
+pub enum WebhookError {
+    /// No stream webhook secret was configured, such that the webhook is
+    /// disabled
+    Disabled,
+
+    /// The signature of the notification doesn't match its contents
+    InvalidSignature,
+
+    /// The notification was sent too long ago to be accepted
+    Expired,
+
+    /// The signature of the notification couldn't be computed
+    CryptoError(ErrorStack),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "the stream webhook is disabled"),
+            Self::InvalidSignature => write!(f, "the notification's signature is invalid"),
+            Self::Expired => write!(f, "the notification has expired"),
+            Self::CryptoError(e) => write!(f, "the notification could not be verified: {}", e),
+        }
+    }
+}
+
+impl Error for WebhookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CryptoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorStack> for WebhookError {
+    /// Constructs a webhook error from the given openssl error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The openssl error that should be wrapped in the WebhookError
+    fn from(e: ErrorStack) -> Self {
+        Self::CryptoError(e)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Disabled => StatusCode::NOT_FOUND,
+            Self::InvalidSignature => StatusCode::FORBIDDEN,
+            Self::Expired => StatusCode::BAD_REQUEST,
+            Self::CryptoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ErrorCode for WebhookError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Disabled => "webhook_disabled",
+            Self::InvalidSignature => "invalid_webhook_signature",
+            Self::Expired => "webhook_expired",
+            Self::CryptoError(_) => "webhook_error",
+        }
+    }
+}
+
+/// Notification represents the body of a request to the stream status
+/// webhook, in the format used by Twitch EventSub.
+#[derive(Deserialize)]
+pub struct Notification {
+    /// The subscription that the notification was sent for
+    subscription: Subscription,
+
+    /// (optional) The challenge that must be echoed back to confirm the
+    /// subscription, if the notification verifies the webhook
+    challenge: Option<String>,
+}
+
+/// Subscription represents the subscription that a webhook notification was
+/// sent for.
+#[derive(Deserialize)]
+pub struct Subscription {
+    /// The kind of event that the subscription concerns (e.g.
+    /// "stream.online")
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// Computes the signature of a webhook notification, as a hex-encoded
+/// HMAC-SHA256 of its ID, timestamp and body, prefixed with "sha256=".
+///
+/// # Arguments
+///
+/// * `secret` - The secret that the notification is signed with
+/// * `id` - The unique ID of the notification
+/// * `timestamp` - The time at which the notification was sent, as sent
+/// * `body` - The raw body of the notification
+pub fn sign(secret: &str, id: &str, timestamp: &str, body: &[u8]) -> Result<String, ErrorStack> {
+    let key = PKey::hmac(secret.as_bytes())?;
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(id.as_bytes())?;
+    signer.update(timestamp.as_bytes())?;
+    signer.update(body)?;
+
+    Ok(signer
+        .sign_to_vec()?
+        .iter()
+        .fold("sha256=".to_owned(), |mut signature, byte| {
+            let _ = write!(signature, "{:02x}", byte);
+
+            signature
+        }))
+}
+
+/// Gets the value of the given header of the request.
+///
+/// # Arguments
+///
+/// * `req` - The request to the webhook
+/// * `name` - The name of the header
+fn header<'a>(req: &'a HttpRequest, name: &'static str) -> Result<&'a str, ProviderError> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(ProviderError::MissingArgument { arg: name })
+}
+
+/// Marks the stream as live or offline according to a notification signed
+/// with the server's stream webhook secret. Notifications follow the format
+/// of Twitch EventSub, such that EventSub may call the webhook directly, and
+/// so that other infrastructure may sign its requests the same way. Changes
+/// in the status of the stream are pushed to every connected chatter, and
+/// the live announcement, if any, is sent once the stream goes live.
+#[post("/stream")]
+pub async fn stream_hook(
+    state: Data<State>,
+    req: HttpRequest,
+    body: Bytes,
+) -> Result<HttpResponse, HttpError> {
+    let secret = state
+        .stream_webhook_secret()
+        .ok_or(WebhookError::Disabled)?;
+
+    let id = header(&req, MESSAGE_ID_HEADER)?;
+    let timestamp = header(&req, MESSAGE_TIMESTAMP_HEADER)?;
+    let signature = header(&req, MESSAGE_SIGNATURE_HEADER)?;
+
+    let expected = sign(secret, id, timestamp, &body).map_err(WebhookError::from)?;
+    if signature.len() != expected.len() || !memcmp::eq(signature.as_bytes(), expected.as_bytes()) {
+        return Err(WebhookError::InvalidSignature.into());
+    }
+
+    let now = Utc::now();
+    let sent_at = DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|_| ProviderError::InvalidArgument {
+            arg: MESSAGE_TIMESTAMP_HEADER,
+        })?
+        .with_timezone(&Utc);
+    if now - sent_at > Duration::seconds(MAX_NOTIFICATION_AGE) {
+        return Err(WebhookError::Expired.into());
+    }
+
+    let notification: Notification = serde_json::from_slice(&body).map_err(ProviderError::from)?;
+
+    match header(&req, MESSAGE_TYPE_HEADER)? {
+        "webhook_callback_verification" => {
+            return Ok(HttpResponse::Ok()
+                .content_type("text/plain")
+                .body(notification.challenge.unwrap_or_default()))
+        }
+        "revocation" => {
+            tracing::warn!(
+                subscription = %notification.subscription.kind,
+                "stream webhook subscription was revoked"
+            );
+
+            return Ok(HttpResponse::NoContent().finish());
+        }
+        "notification" => (),
+        _ => {
+            return Err(ProviderError::InvalidArgument {
+                arg: MESSAGE_TYPE_HEADER,
+            }
+            .into())
+        }
+    }
+
+    let live = match notification.subscription.kind.as_str() {
+        "stream.online" => true,
+        "stream.offline" => false,
+        _ => {
+            return Err(ProviderError::InvalidArgument {
+                arg: "subscription.type",
+            }
+            .into())
+        }
+    };
+
+    let mut conn = state.cache_connection()?;
+    let mut cache = Cache::new(&mut conn).with_prefix(state.key_prefix());
+
+    // Notifications are retried until they are acknowledged, so each one is
+    // only acted upon once
+    if !cache.claim_notification(id)? {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    let status = StreamStatus::new(live, now);
+    if cache.set_stream_status(&status)? {
+        cache.publish_stream_status(status)?;
+
+        if let Some(message) = state.live_announcement().filter(|_| live) {
+            cache.publish_announcement(
+                EventTarget::All,
+                NewAnnouncement::new(message, now, now).with_id(0),
+            )?;
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes the given status of the stream as an event, so that it may
+    /// be pushed to every connected chatter.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The new status of the stream
+    fn publish_stream_status(&mut self, status: StreamStatus) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(STREAM_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                EventTarget::All,
+                EventKind::StreamStatus(status),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+/// Provider represents an arbitrary backend for the stream status service,
+/// which keeps track of whether or not the stream is live. The status of the
+/// stream is ephemeral, and is therefore only stored in the caching layer.
+pub trait Provider {
+    /// Gets the current status of the stream, if it was ever reported.
+    fn stream_status(&mut self) -> Result<Option<StreamStatus>, ProviderError>;
+
+    /// Records the given status of the stream, returning whether or not the
+    /// stream went live or offline as a result. The time at which the stream
+    /// last went live or offline is kept if it didn't.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The reported status of the stream
+    fn set_stream_status(&mut self, status: &StreamStatus) -> Result<bool, ProviderError>;
+
+    /// Claims the webhook notification with the given ID, returning whether
+    /// or not it hadn't already been claimed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique ID of the notification
+    fn claim_notification(&mut self, id: &str) -> Result<bool, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the current status of the stream from the redis caching layer.
+    fn stream_status(&mut self) -> Result<Option<StreamStatus>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(STATUS_KEY))
+            .query::<Option<String>>(self.connection)?
+            .map_or(Ok(None), |raw| {
+                serde_json::from_str(&raw).map(Some).map_err(|e| e.into())
+            })
+    }
+
+    /// Records the given status of the stream in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The reported status of the stream
+    fn set_stream_status(&mut self, status: &StreamStatus) -> Result<bool, ProviderError> {
+        // The flag is swapped atomically, so that concurrent reports of the
+        // same change are only acted upon once
+        let was_live: Option<bool> = redis::cmd("GETSET")
+            .arg(self.key(LIVE_KEY))
+            .arg(status.live())
+            .query(self.connection)?;
+        if was_live == Some(status.live()) {
+            return Ok(false);
+        }
+
+        redis::cmd("SET")
+            .arg(self.key(STATUS_KEY))
+            .arg(serde_json::to_string(status)?)
+            .query::<()>(self.connection)?;
+
+        Ok(true)
+    }
+
+    /// Claims the webhook notification with the given ID in the redis
+    /// caching layer. Claims lapse once the notification could no longer be
+    /// accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique ID of the notification
+    fn claim_notification(&mut self, id: &str) -> Result<bool, ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(format_args!("stream_notifications::{}", id)))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(MAX_NOTIFICATION_AGE)
+            .query::<Option<String>>(self.connection)
+            .map(|claimed| claimed.is_some())
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_sign() -> Result<(), Box<dyn Error>> {
+        let signature = sign("secret", "id", "2020-05-17T12:00:00Z", b"{}")?;
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+
+        // Any change to the notification changes its signature
+        assert_ne!(
+            signature,
+            sign("secret", "id", "2020-05-17T12:00:00Z", b"[]")?
+        );
+        assert_ne!(
+            signature,
+            sign("other", "id", "2020-05-17T12:00:00Z", b"{}")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut cache = Cache::new(&mut conn).with_prefix("test_stream::");
+        redis::cmd("DEL")
+            .arg(cache.key(LIVE_KEY))
+            .arg(cache.key(STATUS_KEY))
+            .arg(cache.key("stream_notifications::1"))
+            .query::<()>(cache.connection)?;
+
+        assert_eq!(cache.stream_status()?, None);
+
+        let live = StreamStatus::new(true, Utc::now());
+        assert!(cache.set_stream_status(&live)?);
+
+        // Repeated reports don't change when the stream went live
+        assert!(!cache.set_stream_status(&StreamStatus::new(true, Utc::now()))?);
+        assert_eq!(
+            cache.stream_status()?.map(|status| status.live()),
+            Some(true)
+        );
+
+        assert!(cache.set_stream_status(&StreamStatus::new(false, Utc::now()))?);
+        assert_eq!(
+            cache.stream_status()?.map(|status| status.live()),
+            Some(false)
+        );
+
+        // Notifications may only be claimed once
+        assert!(cache.claim_notification("1")?);
+        assert!(!cache.claim_notification("1")?);
+
+        Ok(())
+    }
+}
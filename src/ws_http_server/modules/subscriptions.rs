@@ -0,0 +1,375 @@
+use actix_web::{
+    web::{Data, Json, Path},
+    Error as HttpError, HttpResponse, Scope,
+};
+use chrono::{DateTime, Utc};
+use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned, schema::subscriptions, subscription::Subscription, user::Role,
+        },
+        auth::{capability::CanManageRoles, Principal, RequireCapability},
+        server::State,
+    },
+    roles::Provider as RolesProvider,
+    users, Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the subscriptions module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/subscriptions")
+        .service(user_subscription)
+        .service(grant_subscription)
+        .service(revoke_subscription)
+}
+
+/// SubscriptionRequest represents a request to grant a subscription to a
+/// user.
+#[derive(Deserialize)]
+pub struct SubscriptionRequest {
+    /// The tier of the subscription
+    tier: u8,
+
+    /// (optional) The time at which the subscription lapses
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Gets the subscription held by the specified user. Users may only view
+/// their own subscriptions, unless they are an administrator.
+#[get("/{user_id}")]
+pub async fn user_subscription(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+) -> Result<Option<Json<Subscription>>, HttpError> {
+    users::authorize_edit(&principal, *user_id)?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Ok(Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .get_subscription(*user_id)?
+    .map(Json))
+}
+
+/// Grants a subscription of the given tier to the specified user, replacing
+/// any subscription they already hold, and assigns them the subscriber role.
+#[put("/{user_id}")]
+pub async fn grant_subscription(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageRoles>,
+    user_id: Path<u64>,
+    body: Json<SubscriptionRequest>,
+) -> Result<Json<Subscription>, HttpError> {
+    let sub = Subscription::new(*user_id, body.tier).with_expires_at(body.expires_at);
+    if !sub.is_valid() {
+        return Err(ProviderError::InvalidArgument { arg: "tier" }.into());
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    hybrid.set_subscription(&sub)?;
+    hybrid.give_role(*user_id, &Role::Subscriber)?;
+
+    Ok(Json(sub))
+}
+
+/// Revokes the subscription held by the specified user, alongside their
+/// subscriber role.
+#[delete("/{user_id}")]
+pub async fn revoke_subscription(
+    state: Data<State>,
+    _auth: RequireCapability<CanManageRoles>,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    hybrid.remove_subscription(*user_id)?;
+    hybrid.remove_role(*user_id, &Role::Subscriber)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Gets the redis key of the value holding the given user's subscription.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose subscription should be located
+pub(crate) fn subscription_key(user_id: u64) -> String {
+    format!("subscription::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the subscriptions service,
+/// which stores the subscription tier held by each user.
+pub trait Provider {
+    /// Retreives the subscription held by the user with the given ID, if they
+    /// hold one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be obtained
+    fn get_subscription(&mut self, user_id: u64) -> Result<Option<Subscription>, ProviderError>;
+
+    /// Stores the given subscription, replacing any subscription already held
+    /// by its user.
+    ///
+    /// # Arguments
+    ///
+    /// * `sub` - The subscription that should be stored
+    fn set_subscription(&mut self, sub: &Subscription) -> Result<(), ProviderError>;
+
+    /// Removes the subscription held by the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be removed
+    fn remove_subscription(&mut self, user_id: u64) -> Result<(), ProviderError>;
+
+    /// Retreives the tier of the subscription held by the user with the given
+    /// ID, if they hold one that has not lapsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose tier should be obtained
+    fn active_tier(&mut self, user_id: u64) -> Result<Option<u8>, ProviderError> {
+        self.get_subscription(user_id)
+            .map(|sub| sub.and_then(|sub| sub.active_tier()))
+    }
+}
+
+impl<'a> Cache<'a> {
+    /// Retreives the subscription held by the given user from the redis
+    /// caching layer. Users known to hold no subscription are cached as
+    /// well, so that they needn't be looked up for each of their messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be obtained
+    fn cached_subscription(
+        &mut self,
+        user_id: u64,
+    ) -> Result<Option<Option<Subscription>>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(subscription_key(user_id)))
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given user's subscription, or the lack of one, in the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be stored
+    /// * `sub` - (optional) The subscription held by the user
+    fn cache_subscription(
+        &mut self,
+        user_id: u64,
+        sub: Option<&Subscription>,
+    ) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(subscription_key(user_id)))
+            .arg(serde_json::to_string(&sub)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Retreives the subscription held by the given user from the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be obtained
+    fn get_subscription(&mut self, user_id: u64) -> Result<Option<Subscription>, ProviderError> {
+        self.cached_subscription(user_id).map(Option::flatten)
+    }
+
+    /// Stores the given subscription in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `sub` - The subscription that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, subscriptions::Provider}, spec::subscription::Subscription};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut subs = Cache::new(&mut conn);
+    /// subs.set_subscription(&Subscription::new(69420, 3))?;
+    ///
+    /// assert_eq!(subs.active_tier(69420)?, Some(3));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_subscription(&mut self, sub: &Subscription) -> Result<(), ProviderError> {
+        self.cache_subscription(sub.user_id(), Some(sub))
+    }
+
+    /// Removes the subscription held by the given user from the redis
+    /// caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be removed
+    fn remove_subscription(&mut self, user_id: u64) -> Result<(), ProviderError> {
+        self.cache_subscription(user_id, None)
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Retreives the subscription held by the given user from the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be obtained
+    fn get_subscription(&mut self, user_id: u64) -> Result<Option<Subscription>, ProviderError> {
+        subscriptions::table
+            .find(Unsigned(user_id))
+            .first(self.connection)
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Stores the given subscription in the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `sub` - The subscription that should be stored
+    fn set_subscription(&mut self, sub: &Subscription) -> Result<(), ProviderError> {
+        diesel::replace_into(subscriptions::table)
+            .values(sub)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the subscription held by the given user from the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be removed
+    fn remove_subscription(&mut self, user_id: u64) -> Result<(), ProviderError> {
+        diesel::delete(subscriptions::table.find(Unsigned(user_id)))
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Retreives the subscription held by the given user, populating the
+    /// cache from the persistent layer if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be obtained
+    fn get_subscription(&mut self, user_id: u64) -> Result<Option<Subscription>, ProviderError> {
+        match self.cache.cached_subscription(user_id) {
+            Ok(Some(sub)) => Ok(sub),
+            _ => self.persistent.get_subscription(user_id).and_then(|sub| {
+                self.cache
+                    .cache_subscription(user_id, sub.as_ref())
+                    .map(|_| sub)
+            }),
+        }
+    }
+
+    /// Stores the given subscription in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `sub` - The subscription that should be stored
+    fn set_subscription(&mut self, sub: &Subscription) -> Result<(), ProviderError> {
+        self.persistent
+            .set_subscription(sub)
+            .and_then(|_| self.cache.set_subscription(sub))
+    }
+
+    /// Removes the subscription held by the given user from the active
+    /// provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose subscription should be removed
+    fn remove_subscription(&mut self, user_id: u64) -> Result<(), ProviderError> {
+        self.persistent
+            .remove_subscription(user_id)
+            .and_then(|_| self.cache.remove_subscription(user_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::super::spec::backend::PersistentConnection, *};
+    use chrono::Duration;
+    use diesel::Connection;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut subs = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        subs.remove_subscription(42069)?;
+        assert_eq!(subs.active_tier(42069)?, None);
+
+        subs.set_subscription(&Subscription::new(42069, 2))?;
+        assert_eq!(subs.active_tier(42069)?, Some(2));
+
+        // Lapsed subscriptions grant no tier
+        subs.set_subscription(
+            &Subscription::new(42069, 2).with_expires_at(Some(Utc::now() - Duration::days(1))),
+        )?;
+        assert_eq!(subs.active_tier(42069)?, None);
+
+        // The persistent layer should be able to repopulate the cache
+        redis::cmd("DEL")
+            .arg(subscription_key(42069))
+            .query::<()>(&mut conn)?;
+        let mut subs = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert_eq!(subs.get_subscription(42069)?.map(|sub| sub.tier()), Some(2));
+
+        Ok(())
+    }
+}
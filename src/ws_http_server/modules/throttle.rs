@@ -0,0 +1,350 @@
+use actix_web::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    Error as HttpError, HttpRequest, HttpResponse, ResponseError,
+};
+use redis::Connection as RedisConnection;
+
+use super::{
+    super::problem::{ErrorCode, Problem},
+    Cache, ProviderError,
+};
+
+use std::{error::Error, fmt, net::IpAddr};
+
+/// The number of seconds over which failed attempts are counted.
+pub const FAILURE_WINDOW: u64 = 15 * 60;
+
+/// The number of seconds for which a subject is locked out after exceeding
+/// its limit.
+pub const LOCKOUT_DURATION: u64 = 15 * 60;
+
+/// The number of failed attempts from a single IP address permitted within
+/// the failure window. IP addresses are given more leeway than accounts, as
+/// many users may share an address.
+const MAX_IP_FAILURES: u64 = 20;
+
+/// The number of failed attempts against a single account permitted within
+/// the failure window.
+const MAX_ACCOUNT_FAILURES: u64 = 5;
+
+/// The header in which reverse proxies name the addresses that a request was
+/// forwarded for.
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Counts a failed attempt by the subject whose counter is stored at
+/// KEYS[1], returning the number of failures and the seconds until the
+/// counter expires. The counter expires after the failure window (ARGV[1]),
+/// or after the lockout duration (ARGV[2]) once the subject's limit (ARGV[3])
+/// is reached. The counter is incremented and given its expiry at once, such
+/// that it can't outlive its window.
+const RECORD_FAILURE_SCRIPT: &str = r"
+local failures = redis.call('INCR', KEYS[1])
+local limit = tonumber(ARGV[3])
+
+if failures == limit then
+    redis.call('EXPIRE', KEYS[1], ARGV[2])
+elseif failures == 1 or redis.call('TTL', KEYS[1]) < 0 then
+    redis.call('EXPIRE', KEYS[1], failures >= limit and ARGV[2] or ARGV[1])
+end
+
+return {failures, redis.call('TTL', KEYS[1])}
+";
+
+/// Subject represents a party whose failed authentication attempts are
+/// counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subject<'a> {
+    /// Attempts made from the given IP address
+    Ip(&'a str),
+
+    /// Attempts made against the account of the user with the given ID
+    Account(u64),
+}
+
+impl<'a> Subject<'a> {
+    /// Gets the redis key under which the subject's failures are counted.
+    fn key(&self) -> String {
+        match self {
+            Self::Ip(ip) => format!("auth_failures::ip::{}", ip),
+            Self::Account(user_id) => format!("auth_failures::account::{}", user_id),
+        }
+    }
+
+    /// Gets the number of failed attempts after which the subject is locked
+    /// out.
+    fn limit(&self) -> u64 {
+        match self {
+            Self::Ip(_) => MAX_IP_FAILURES,
+            Self::Account(_) => MAX_ACCOUNT_FAILURES,
+        }
+    }
+}
+
+/// ThrottleError represents a request rejected because its subject has made
+/// too many failed authentication attempts.
+#[derive(Debug)]
+pub struct ThrottleError {
+    /// The number of seconds until the subject may try again
+    retry_after: u64,
+}
+
+impl fmt::Display for ThrottleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "too many failed attempts; try again in {} seconds",
+            self.retry_after
+        )
+    }
+}
+
+impl Error for ThrottleError {}
+
+impl ResponseError for ThrottleError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = Problem::from_error(self).error_response();
+        res.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(self.retry_after));
+
+        res
+    }
+}
+
+impl ErrorCode for ThrottleError {
+    fn code(&self) -> &'static str {
+        "too_many_failed_attempts"
+    }
+}
+
+/// Gets the IP address from which the given request was made, for use as a
+/// throttling subject. Forwarded headers are only consulted if the request
+/// was made by a trusted proxy, as any other client could forge them.
+///
+/// # Arguments
+///
+/// * `req` - The request whose origin should be determined
+/// * `trusted_proxies` - The addresses of the proxies whose forwarded
+/// headers may be trusted
+pub(crate) fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> String {
+    let peer = match req.peer_addr() {
+        Some(peer) => peer.ip(),
+        None => return "unknown".to_owned(),
+    };
+
+    let forwarded_for = req
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|header| header.to_str().ok())
+        .collect::<Vec<&str>>()
+        .join(",");
+
+    resolve_ip(peer, &forwarded_for, trusted_proxies).to_string()
+}
+
+/// Resolves the address of the client that a request was made on behalf of.
+/// Proxies append the address that they received the request from to the
+/// forwarded addresses, so the addresses are walked back from the peer for
+/// as long as they were appended by a trusted proxy.
+///
+/// # Arguments
+///
+/// * `peer` - The address of the peer that made the request
+/// * `forwarded_for` - The comma-separated addresses that the request was
+/// forwarded for, oldest first
+/// * `trusted_proxies` - The addresses of the proxies whose forwarded
+/// headers may be trusted
+fn resolve_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+
+    for hop in forwarded_for.rsplit(',').map(str::trim) {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+
+        match hop.parse() {
+            Ok(hop) => client = hop,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+/// Ensures that none of the given subjects are locked out.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `subjects` - The parties attempting to authenticate
+pub(crate) fn check(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    subjects: &[Subject],
+) -> Result<(), HttpError> {
+    let mut throttle = Cache::new(conn).with_prefix(key_prefix);
+
+    for subject in subjects {
+        if let Some(retry_after) = throttle.lockout(subject)? {
+            return Err(ThrottleError { retry_after }.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Records a failed authentication attempt by each of the given subjects.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `subjects` - The parties whose attempt failed
+pub(crate) fn record_failure(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    subjects: &[Subject],
+) -> Result<(), ProviderError> {
+    let mut throttle = Cache::new(conn).with_prefix(key_prefix);
+
+    subjects
+        .iter()
+        .try_for_each(|subject| throttle.record_failure(subject).map(|_| ()))
+}
+
+/// Provider represents an arbitrary backend for the authentication throttle,
+/// which counts the failed attempts made by each subject. Counters are
+/// ephemeral, and are therefore only stored in the caching layer.
+pub trait Provider {
+    /// Gets the number of seconds for which the given subject remains locked
+    /// out, if it is locked out.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party attempting to authenticate
+    fn lockout(&mut self, subject: &Subject) -> Result<Option<u64>, ProviderError>;
+
+    /// Records a failed attempt by the given subject, returning the number of
+    /// seconds for which the subject is locked out as a result, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party whose attempt failed
+    fn record_failure(&mut self, subject: &Subject) -> Result<Option<u64>, ProviderError>;
+
+    /// Forgets each of the failed attempts made by the given subject.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party who authenticated successfully
+    fn clear_failures(&mut self, subject: &Subject) -> Result<(), ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Gets the number of seconds for which the given subject remains locked
+    /// out in the redis caching layer, if it is locked out.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party attempting to authenticate
+    fn lockout(&mut self, subject: &Subject) -> Result<Option<u64>, ProviderError> {
+        let (failures, ttl): (Option<u64>, i64) = redis::pipe()
+            .cmd("GET")
+            .arg(self.key(subject.key()))
+            .cmd("TTL")
+            .arg(self.key(subject.key()))
+            .query(self.connection)?;
+
+        Ok(match failures {
+            Some(failures) if failures >= subject.limit() => Some(ttl.max(1) as u64),
+            _ => None,
+        })
+    }
+
+    /// Records a failed attempt by the given subject in the redis caching
+    /// layer. The counter expires after the failure window, or after the
+    /// lockout duration once the subject's limit has been reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party whose attempt failed
+    fn record_failure(&mut self, subject: &Subject) -> Result<Option<u64>, ProviderError> {
+        let key = self.key(subject.key());
+        let (failures, ttl): (u64, i64) = self.eval(RECORD_FAILURE_SCRIPT, |script| {
+            script
+                .key(key)
+                .arg(FAILURE_WINDOW)
+                .arg(LOCKOUT_DURATION)
+                .arg(subject.limit());
+        })?;
+
+        Ok(if failures >= subject.limit() {
+            Some(ttl.max(1) as u64)
+        } else {
+            None
+        })
+    }
+
+    /// Forgets each of the failed attempts made by the given subject in the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `subject` - The party who authenticated successfully
+    fn clear_failures(&mut self, subject: &Subject) -> Result<(), ProviderError> {
+        redis::cmd("DEL")
+            .arg(self.key(subject.key()))
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        // Untrusted peers can't name another address
+        assert_eq!(resolve_ip(client, "198.51.100.1", &[proxy]), client);
+        assert_eq!(resolve_ip(proxy, "203.0.113.7", &[]), proxy);
+
+        // Addresses prepended by the client are ignored
+        assert_eq!(
+            resolve_ip(proxy, "198.51.100.1, 203.0.113.7", &[proxy]),
+            client
+        );
+        assert_eq!(resolve_ip(proxy, "", &[proxy]), proxy);
+        assert_eq!(resolve_ip(proxy, "garbage", &[proxy]), proxy);
+    }
+
+    #[test]
+    fn test_lockout() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut throttle = Cache::new(&mut conn);
+        let subject = Subject::Account(69420);
+        throttle.clear_failures(&subject)?;
+
+        for _ in 1..MAX_ACCOUNT_FAILURES {
+            assert_eq!(throttle.record_failure(&subject)?, None);
+        }
+
+        // Reaching the limit locks the subject out for the lockout duration
+        let retry_after = throttle.record_failure(&subject)?;
+        assert!(retry_after.is_some_and(|secs| secs <= LOCKOUT_DURATION));
+        assert_eq!(throttle.lockout(&Subject::Ip("127.0.0.1"))?, None);
+
+        throttle.clear_failures(&subject)?;
+        assert_eq!(throttle.lockout(&subject)?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,709 @@
+use actix_web::{
+    http::{header::USER_AGENT, StatusCode},
+    web::{Data, Json},
+    Error as HttpError, HttpRequest, HttpResponse, ResponseError, Scope,
+};
+use chrono::Utc;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use openssl::error::ErrorStack;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{
+        super::spec::{
+            backend::Unsigned,
+            schema::{recovery_codes, two_factor},
+            two_factor::TwoFactor,
+            user::Role,
+        },
+        auth::{AuthError, AuthedUser},
+        problem::ErrorCode,
+        server::State,
+        totp::TotpSecret,
+    },
+    refresh_tokens::{self, Tokens},
+    roles::Provider as RolesProvider,
+    sessions::Provider as SessionsProvider,
+    throttle::{self, Provider as ThrottleProvider, Subject},
+    users::Provider as UsersProvider,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::{error::Error, fmt};
+
+/// The number of seconds that a user has to present a code after logging in.
+pub const CHALLENGE_TTL: u64 = 5 * 60;
+
+/// The number of characters in a login challenge.
+const CHALLENGE_LENGTH: usize = 48;
+
+/// The number of recovery codes issued upon enrollment.
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// The number of characters in each recovery code.
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// The roles whose holders must present a code when logging in, if they have
+/// enrolled in two-factor authentication.
+const PRIVILEGED_ROLES: &[Role] = &[Role::Administrator, Role::Moderator];
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the two-factor module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/2fa")
+        .service(enroll)
+        .service(confirm)
+        .service(disable)
+        .service(verify)
+}
+
+/// TwoFactorError represents any error encountered while enrolling in or
+/// verifying two-factor authentication.
+#[derive(Debug)]
+pub enum TwoFactorError {
+    /// The user has already confirmed their enrollment
+    AlreadyEnrolled,
+
+    /// The user hasn't begun enrolling
+    NotEnrolled,
+
+    /// The presented code is invalid, or has already been used
+    InvalidCode,
+
+    /// The code couldn't be computed
+    CryptoError(ErrorStack),
+}
+
+impl fmt::Display for TwoFactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyEnrolled => write!(f, "two-factor authentication is already enabled"),
+            Self::NotEnrolled => write!(f, "two-factor authentication is not enabled"),
+            Self::InvalidCode => write!(f, "the code is invalid"),
+            Self::CryptoError(e) => write!(f, "the code could not be verified: {}", e),
+        }
+    }
+}
+
+impl Error for TwoFactorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CryptoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorStack> for TwoFactorError {
+    /// Constructs a two-factor error from the given openssl error.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The openssl error that should be wrapped in the TwoFactorError
+    fn from(e: ErrorStack) -> Self {
+        Self::CryptoError(e)
+    }
+}
+
+impl ResponseError for TwoFactorError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::AlreadyEnrolled => StatusCode::CONFLICT,
+            Self::NotEnrolled => StatusCode::NOT_FOUND,
+            Self::InvalidCode => StatusCode::FORBIDDEN,
+            Self::CryptoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ErrorCode for TwoFactorError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::AlreadyEnrolled => "two_factor_enabled",
+            Self::NotEnrolled => "two_factor_disabled",
+            Self::InvalidCode => "invalid_two_factor_code",
+            Self::CryptoError(_) => "two_factor_error",
+        }
+    }
+}
+
+/// Enrollment represents the secret issued to a user beginning enrollment.
+#[derive(Serialize)]
+pub struct Enrollment {
+    /// The base32-encoded secret, for users entering it by hand
+    secret: String,
+
+    /// The otpauth URI with which the secret may be added to an authenticator
+    /// app, typically rendered as a QR code
+    uri: String,
+}
+
+/// RecoveryCodes represents the single-use codes issued to a user upon
+/// confirming their enrollment. The codes are only ever shown once.
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+/// CodeRequest represents a request carrying a code from the user's
+/// authenticator app, or a recovery code.
+#[derive(Deserialize)]
+pub struct CodeRequest {
+    code: String,
+}
+
+/// VerifyRequest represents a response to a login challenge.
+#[derive(Deserialize)]
+pub struct VerifyRequest {
+    /// The challenge issued upon logging in
+    challenge: String,
+
+    /// A code from the user's authenticator app, or a recovery code
+    code: String,
+}
+
+/// Begins enrolling the authenticated user in two-factor authentication,
+/// issuing a new secret. Enrollment must be confirmed with a code before it
+/// takes effect.
+#[post("/enroll")]
+pub async fn enroll(state: Data<State>, user: AuthedUser) -> Result<Json<Enrollment>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut persistent = Persistent::new(&persistent_conn).with_keyring(state.keyring());
+    if persistent
+        .get_two_factor(user.id())?
+        .is_some_and(|enrollment| enrollment.confirmed())
+    {
+        return Err(TwoFactorError::AlreadyEnrolled.into());
+    }
+
+    let secret = TotpSecret::random();
+    persistent.set_two_factor(&TwoFactor::new(user.id(), secret.to_base32()))?;
+
+    let account = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .get_user(user.id())?
+    .and_then(|profile| profile.username().map(|username| username.to_owned()))
+    .unwrap_or_else(|| user.id().to_string());
+
+    Ok(Json(Enrollment {
+        secret: secret.to_base32(),
+        uri: secret.provisioning_uri(&account),
+    }))
+}
+
+/// Confirms the authenticated user's enrollment with a code from their
+/// authenticator app, and issues their recovery codes.
+#[post("/confirm")]
+pub async fn confirm(
+    state: Data<State>,
+    user: AuthedUser,
+    req: Json<CodeRequest>,
+) -> Result<Json<RecoveryCodes>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let mut persistent = Persistent::new(&persistent_conn).with_keyring(state.keyring());
+
+    let subjects = [Subject::Account(user.id())];
+    throttle::check(&mut conn, state.key_prefix(), &subjects)?;
+
+    let enrollment = persistent
+        .get_two_factor(user.id())?
+        .ok_or(TwoFactorError::NotEnrolled)?;
+    if enrollment.confirmed() {
+        return Err(TwoFactorError::AlreadyEnrolled.into());
+    }
+
+    let step = match accepted_step(&enrollment, &req.code)? {
+        Some(step) => step,
+        None => {
+            throttle::record_failure(&mut conn, state.key_prefix(), &subjects)?;
+
+            return Err(TwoFactorError::InvalidCode.into());
+        }
+    };
+
+    let recovery_codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(RECOVERY_CODE_LENGTH)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect::<Vec<_>>();
+
+    persistent.set_two_factor(&enrollment.confirm(step))?;
+    persistent.set_recovery_codes(
+        user.id(),
+        &recovery_codes
+            .iter()
+            .map(|code| recovery_code_hash(code))
+            .collect::<Vec<_>>(),
+    )?;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+/// Disables two-factor authentication for the authenticated user. A valid
+/// code must be presented, so that a stolen session can't be used to disable
+/// the second factor.
+#[delete("")]
+pub async fn disable(
+    state: Data<State>,
+    user: AuthedUser,
+    req: Json<CodeRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let mut persistent = Persistent::new(&persistent_conn).with_keyring(state.keyring());
+
+    let subjects = [Subject::Account(user.id())];
+    throttle::check(&mut conn, state.key_prefix(), &subjects)?;
+
+    if !verify_code(&mut persistent, user.id(), &req.code)? {
+        throttle::record_failure(&mut conn, state.key_prefix(), &subjects)?;
+
+        return Err(TwoFactorError::InvalidCode.into());
+    }
+    persistent.delete_two_factor(user.id())?;
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .clear_failures(&subjects[0])?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Completes a login that was challenged for a second factor, issuing a
+/// session to the user if they present a valid code. A challenge may only be
+/// answered once, so an incorrect code requires the user to log in again, and
+/// repeated incorrect codes lock the account out.
+#[post("/verify")]
+pub async fn verify(
+    state: Data<State>,
+    req: HttpRequest,
+    body: Json<VerifyRequest>,
+) -> Result<Json<Tokens>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let ip = throttle::client_ip(&req, state.trusted_proxies());
+    throttle::check(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+    let user_id = match Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .take_challenge(&body.challenge)?
+    {
+        Some(user_id) => user_id,
+        None => {
+            throttle::record_failure(&mut conn, state.key_prefix(), &[Subject::Ip(&ip)])?;
+
+            return Err(AuthError::InvalidCredentials.into());
+        }
+    };
+
+    // Accounts are throttled alongside addresses, so that codes can't be
+    // guessed by logging in from many addresses at once
+    let subjects = [Subject::Ip(&ip), Subject::Account(user_id)];
+    throttle::check(&mut conn, state.key_prefix(), &subjects)?;
+
+    let mut persistent = Persistent::new(&persistent_conn).with_keyring(state.keyring());
+    if !verify_code(&mut persistent, user_id, &body.code)? {
+        throttle::record_failure(&mut conn, state.key_prefix(), &subjects)?;
+
+        return Err(TwoFactorError::InvalidCode.into());
+    }
+    Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .clear_failures(&Subject::Account(user_id))?;
+
+    let session_id = Cache::new(&mut conn)
+        .with_prefix(state.key_prefix())
+        .issue_session(
+            user_id,
+            req.headers()
+                .get(USER_AGENT)
+                .and_then(|header| header.to_str().ok()),
+            Some(&throttle::client_ip(&req, state.trusted_proxies())),
+        )?;
+
+    refresh_tokens::issue_tokens(&state, &mut conn, &persistent_conn, user_id, session_id).map(Json)
+}
+
+/// Determines whether or not the user with the given ID must present a code
+/// upon logging in. Codes are required of users holding privileged roles who
+/// have confirmed their enrollment.
+///
+/// # Arguments
+///
+/// * `hybrid` - The hybrid provider used to look up the user's roles
+/// * `persistent` - The persistent provider holding two-factor enrollments
+/// * `user_id` - The ID of the user who is logging in
+pub(crate) fn requires_code(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    persistent: &mut Persistent,
+    user_id: u64,
+) -> Result<bool, ProviderError> {
+    if !hybrid
+        .roles_for_user(user_id)?
+        .iter()
+        .any(|role| PRIVILEGED_ROLES.contains(role))
+    {
+        return Ok(false);
+    }
+
+    persistent
+        .get_two_factor(user_id)
+        .map(|enrollment| enrollment.is_some_and(|enrollment| enrollment.confirmed()))
+}
+
+/// Checks the given code against the user's authenticator secret, and then
+/// against their recovery codes. Accepted codes may not be used again.
+///
+/// # Arguments
+///
+/// * `persistent` - The persistent provider holding two-factor enrollments
+/// * `user_id` - The ID of the user presenting the code
+/// * `code` - The presented code
+pub(crate) fn verify_code(
+    persistent: &mut Persistent,
+    user_id: u64,
+    code: &str,
+) -> Result<bool, HttpError> {
+    let enrollment = match persistent.get_two_factor(user_id)? {
+        Some(enrollment) => enrollment,
+        None => return Err(TwoFactorError::NotEnrolled.into()),
+    };
+
+    if let Some(step) = accepted_step(&enrollment, code)? {
+        persistent.set_two_factor(&enrollment.confirm(step))?;
+
+        return Ok(true);
+    }
+
+    persistent
+        .consume_recovery_code(user_id, &recovery_code_hash(code))
+        .map_err(|e| e.into())
+}
+
+/// Gets the time step of the given code if it is valid for the given
+/// enrollment, and newer than the most recently accepted code.
+///
+/// # Arguments
+///
+/// * `enrollment` - The enrollment whose secret the code should be derived
+/// from
+/// * `code` - The presented code
+fn accepted_step(enrollment: &TwoFactor, code: &str) -> Result<Option<i64>, TwoFactorError> {
+    let secret = match TotpSecret::from_base32(enrollment.secret()) {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    Ok(secret
+        .verify(code.trim(), Utc::now().timestamp())?
+        .filter(|step| enrollment.last_step().is_none_or(|last| *step > last)))
+}
+
+/// Computes the hash under which the given recovery code is stored.
+///
+/// # Arguments
+///
+/// * `code` - The recovery code
+fn recovery_code_hash(code: &str) -> [u8; 32] {
+    *blake3::hash(code.trim().to_lowercase().as_bytes()).as_bytes()
+}
+
+/// Provider represents an arbitrary backend for two-factor enrollments.
+/// Secrets are never cached, and are therefore only stored in the persistent
+/// layer.
+pub trait Provider {
+    /// Gets the two-factor enrollment of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose enrollment should be obtained
+    fn get_two_factor(&mut self, user_id: u64) -> Result<Option<TwoFactor>, ProviderError>;
+
+    /// Stores the given enrollment, replacing any existing enrollment for the
+    /// same user.
+    ///
+    /// # Arguments
+    ///
+    /// * `enrollment` - The enrollment that should be stored
+    fn set_two_factor(&mut self, enrollment: &TwoFactor) -> Result<(), ProviderError>;
+
+    /// Removes the two-factor enrollment and recovery codes of the user with
+    /// the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose enrollment should be removed
+    fn delete_two_factor(&mut self, user_id: u64) -> Result<(), ProviderError>;
+
+    /// Replaces the recovery codes of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the codes were issued
+    /// * `code_hashes` - The hashes of the issued recovery codes
+    fn set_recovery_codes(
+        &mut self,
+        user_id: u64,
+        code_hashes: &[[u8; 32]],
+    ) -> Result<(), ProviderError>;
+
+    /// Removes the recovery code with the given hash, returning whether or
+    /// not it was issued to the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user presenting the code
+    /// * `code_hash` - The hash of the presented recovery code
+    fn consume_recovery_code(
+        &mut self,
+        user_id: u64,
+        code_hash: &[u8],
+    ) -> Result<bool, ProviderError>;
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Gets the two-factor enrollment of the user with the given ID from the
+    /// MySQL database, decrypting its secret if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose enrollment should be obtained
+    fn get_two_factor(&mut self, user_id: u64) -> Result<Option<TwoFactor>, ProviderError> {
+        two_factor::table
+            .find(Unsigned(user_id))
+            .first::<TwoFactor>(self.connection)
+            .optional()?
+            .map_or(Ok(None), |enrollment| {
+                let secret = self.open(enrollment.secret())?;
+
+                Ok(Some(enrollment.with_secret(secret)))
+            })
+    }
+
+    /// Stores the given enrollment in the MySQL database, encrypting its
+    /// secret if the helper holds a keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `enrollment` - The enrollment that should be stored
+    fn set_two_factor(&mut self, enrollment: &TwoFactor) -> Result<(), ProviderError> {
+        let sealed = enrollment
+            .clone()
+            .with_secret(self.seal(enrollment.secret())?);
+
+        diesel::replace_into(two_factor::table)
+            .values(&sealed)
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Removes the two-factor enrollment and recovery codes of the user with
+    /// the given ID from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose enrollment should be removed
+    fn delete_two_factor(&mut self, user_id: u64) -> Result<(), ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction::<_, ProviderError, _>(|| {
+            diesel::delete(
+                recovery_codes::table.filter(recovery_codes::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+
+            diesel::delete(two_factor::table.find(Unsigned(user_id)))
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Replaces the recovery codes of the user with the given ID in the MySQL
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the codes were issued
+    /// * `code_hashes` - The hashes of the issued recovery codes
+    fn set_recovery_codes(
+        &mut self,
+        user_id: u64,
+        code_hashes: &[[u8; 32]],
+    ) -> Result<(), ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction::<_, ProviderError, _>(|| {
+            diesel::delete(
+                recovery_codes::table.filter(recovery_codes::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+
+            diesel::insert_into(recovery_codes::table)
+                .values(
+                    code_hashes
+                        .iter()
+                        .map(|hash| {
+                            (
+                                recovery_codes::dsl::code_hash.eq(hash.to_vec()),
+                                recovery_codes::dsl::user_id.eq(Unsigned(user_id)),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Removes the recovery code with the given hash from the MySQL database,
+    /// returning whether or not it was issued to the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user presenting the code
+    /// * `code_hash` - The hash of the presented recovery code
+    fn consume_recovery_code(
+        &mut self,
+        user_id: u64,
+        code_hash: &[u8],
+    ) -> Result<bool, ProviderError> {
+        // Codes are deleted in the same statement that matches them, so that
+        // concurrent logins can't both use the same code
+        diesel::delete(
+            recovery_codes::table
+                .find(code_hash)
+                .filter(recovery_codes::dsl::user_id.eq(Unsigned(user_id))),
+        )
+        .execute(self.connection)
+        .map(|deleted| deleted > 0)
+        .map_err(|e| e.into())
+    }
+}
+
+/// ChallengeProvider represents an arbitrary backend for the login challenges
+/// issued to users who must present a second factor. Challenges are
+/// ephemeral, and are therefore only stored in the caching layer.
+pub trait ChallengeProvider {
+    /// Issues a new login challenge for the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is logging in
+    fn issue_challenge(&mut self, user_id: u64) -> Result<String, ProviderError>;
+
+    /// Gets the ID of the user for whom the given challenge was issued, if
+    /// it exists and has not expired. Each challenge may only be answered
+    /// once.
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - The challenge presented by the user
+    fn take_challenge(&mut self, challenge: &str) -> Result<Option<u64>, ProviderError>;
+}
+
+impl<'a> ChallengeProvider for Cache<'a> {
+    /// Issues a new login challenge for the user with the given ID in the
+    /// redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who is logging in
+    fn issue_challenge(&mut self, user_id: u64) -> Result<String, ProviderError> {
+        let challenge = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CHALLENGE_LENGTH)
+            .collect::<String>();
+
+        redis::cmd("SET")
+            .arg(self.key(format_args!("two_factor_challenge::{}", challenge)))
+            .arg(user_id)
+            .arg("EX")
+            .arg(CHALLENGE_TTL)
+            .query::<()>(self.connection)
+            .map(|_| challenge)
+            .map_err(|e| e.into())
+    }
+
+    /// Gets and removes the given challenge from the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - The challenge presented by the user
+    fn take_challenge(&mut self, challenge: &str) -> Result<Option<u64>, ProviderError> {
+        let key = self.key(format_args!("two_factor_challenge::{}", challenge));
+
+        let (user_id,): (Option<u64>,) = redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .query(self.connection)?;
+
+        Ok(user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::super::super::spec::backend::PersistentConnection, super::super::totp::STEP, *,
+    };
+
+    use std::env;
+
+    #[test]
+    fn test_challenge() -> Result<(), Box<dyn Error>> {
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+
+        let mut challenges = Cache::new(&mut conn);
+        let challenge = challenges.issue_challenge(69420)?;
+
+        // Challenges may only be answered once
+        assert_eq!(challenges.take_challenge(&challenge)?, Some(69420));
+        assert_eq!(challenges.take_challenge(&challenge)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_code() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+        let mut persistent = Persistent::new(&persistent_conn);
+
+        let secret = TotpSecret::random();
+        persistent.set_two_factor(&TwoFactor::new(69420, secret.to_base32()))?;
+        persistent.set_recovery_codes(69420, &[recovery_code_hash("recovery")])?;
+
+        // Each code may only be used once
+        let code = secret.code_at(Utc::now().timestamp() / STEP)?;
+        assert!(verify_code(&mut persistent, 69420, &code)?);
+        assert!(!verify_code(&mut persistent, 69420, &code)?);
+
+        assert!(verify_code(&mut persistent, 69420, "recovery")?);
+        assert!(!verify_code(&mut persistent, 69420, "recovery")?);
+
+        persistent.delete_two_factor(69420)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,954 @@
+use actix_web::{
+    web::{Data, Json, Path, Query},
+    Error as HttpError, HttpRequest, HttpResponse, Scope,
+};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use redis::Connection as RedisConnection;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{PersistentConnection, Unsigned},
+            last_seen::LastSeen,
+            schema::{
+                bans, chat_history, chat_stats, discord_connected, donations, flair_grants,
+                gifted_subscriptions, google_connected, ids, ignores, last_seen, mutes,
+                recovery_codes, reddit_connected, refresh_tokens, roles, settings, subscriptions,
+                twitch_connected, twitter_connected, two_factor, username_history, users,
+                whisper_reads, whispers,
+            },
+            user::{is_valid_minecraft_name, is_valid_username, NewUser, Role, User},
+        },
+        auth::{capability::CanViewActivity, AdminToken, AuthError, Principal, RequireCapability},
+        etag,
+        pagination::{Page, Pagination, Sort},
+        server::State,
+    },
+    avatars,
+    flairs::grants_key,
+    ignores::ignores_key,
+    last_seen::Provider as LastSeenProvider,
+    name_resolver::{self, Provider as NameResolver},
+    roles::Provider as RolesProvider,
+    sessions::Provider as SessionsProvider,
+    subscriptions::subscription_key,
+    whispers::inbox_key,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+/// The name displayed in place of a deleted user's name on the donations and
+/// gifted subscriptions that they made.
+pub const DELETED_USER_NAME: &str = "deleted";
+
+/// The number of results returned by a username search if no limit is
+/// specified.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// The maximum number of results that may be returned by a username search.
+const MAX_SEARCH_LIMIT: usize = 50;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the users module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/users")
+        .service(search)
+        .service(register)
+        .service(profile_by_name)
+        .service(profile)
+        .service(user_last_seen)
+        .service(update_profile)
+        .service(delete_account)
+}
+
+/// SearchQuery represents the filters accepted by the username search route,
+/// alongside its pagination parameters.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// The prefix that each returned username should begin with
+    q: String,
+}
+
+/// Gets a page of usernames beginning with the provided prefix, for use in
+/// @-mention autocompletion. Usernames are paged alphabetically, ignoring
+/// case, and may only be sorted in ascending order.
+#[get("/search")]
+pub async fn search(
+    state: Data<State>,
+    pagination: Pagination,
+    query: Query<SearchQuery>,
+) -> Result<Json<Page<String>>, ProviderError> {
+    if pagination.sort(Sort::Asc) != Sort::Asc {
+        return Err(ProviderError::InvalidArgument { arg: "sort" });
+    }
+
+    if query.q.is_empty() {
+        return Ok(Json(Page::empty()));
+    }
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let limit = pagination.limit(DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT);
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .search_usernames(&query.q, pagination.cursor::<String>()?.as_deref(), limit)
+    .map(|usernames| Json(Page::new(usernames, limit, String::clone)))
+}
+
+/// Registration represents a request to register a new user.
+#[derive(Deserialize)]
+pub struct Registration {
+    /// The username that should be assigned to the user
+    username: String,
+
+    /// (optional) The country that the user most identifies with
+    nationality: Option<String>,
+
+    /// (optional) Whether or not the user accepts gifts
+    accepts_gifts: Option<bool>,
+
+    /// (optional) The user's minecraft username
+    minecraft_name: Option<String>,
+}
+
+impl Registration {
+    /// Validates the registration request, and converts it to a request to
+    /// insert a new user.
+    pub fn to_new_user(&self) -> Result<NewUser<'_>, ProviderError> {
+        if !is_valid_username(&self.username) {
+            return Err(ProviderError::InvalidArgument { arg: "username" });
+        }
+
+        let mut user = NewUser::default()
+            .with_username(&self.username)
+            .with_accepts_gifts(self.accepts_gifts.unwrap_or(false));
+
+        if let Some(nationality) = &self.nationality {
+            user = user.with_nationality(nationality);
+        }
+
+        if let Some(minecraft_name) = &self.minecraft_name {
+            if !is_valid_minecraft_name(minecraft_name) {
+                return Err(ProviderError::InvalidArgument {
+                    arg: "minecraft_name",
+                });
+            }
+
+            user = user.with_minecraft_name(minecraft_name);
+        }
+
+        Ok(user)
+    }
+}
+
+/// Registers a new user, and responds with the created profile. Until users
+/// are able to authenticate themselves, registration is restricted to
+/// administrators.
+#[post("")]
+pub async fn register(
+    state: Data<State>,
+    _auth: AdminToken,
+    registration: Json<Registration>,
+) -> Result<Json<User>, ProviderError> {
+    let new_user = registration.to_new_user()?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    register_user(
+        &mut conn,
+        state.key_prefix(),
+        &persistent_conn,
+        state.default_roles(),
+        &new_user,
+    )
+    .map(Json)
+}
+
+/// UserDetails represents the profile of a user alongside the roles that
+/// they hold, as shown in hovercards and moderation tooling.
+#[derive(Serialize)]
+pub struct UserDetails {
+    /// The profile of the user
+    #[serde(flatten)]
+    user: User,
+
+    /// The roles held by the user
+    roles: Vec<Role>,
+}
+
+/// Gets the profile and roles of the user with the given ID. Responds with
+/// 304 Not Modified if the client's copy of the profile is current.
+#[get("/{user_id}")]
+pub async fn profile(
+    state: Data<State>,
+    req: HttpRequest,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()));
+
+    match user_details(&mut hybrid, *user_id)? {
+        Some(details) => etag::respond(&req, &details),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Gets the profile and roles of the user with the given username. Responds
+/// with 304 Not Modified if the client's copy of the profile is current.
+#[get("/by-name/{username}")]
+pub async fn profile_by_name(
+    state: Data<State>,
+    req: HttpRequest,
+    username: Path<String>,
+) -> Result<HttpResponse, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+    let replica_conn = state.replica_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn).with_replica(replica_conn.as_deref()),
+    )
+    .with_breaker(Some(state.breaker().clone()))
+    .with_lookups(Some(state.lookups().clone()));
+
+    match hybrid.user_id_for(&username)? {
+        Some(user_id) => match user_details(&mut hybrid, user_id)? {
+            Some(details) => etag::respond(&req, &details),
+            None => Ok(HttpResponse::NotFound().finish()),
+        },
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Gets the profile of the user with the given ID alongside the roles that
+/// they hold, if the user exists.
+///
+/// # Arguments
+///
+/// * `hybrid` - The providers from which the user's details should be
+/// obtained
+/// * `user_id` - The ID of the user whose details should be obtained
+fn user_details(
+    hybrid: &mut Hybrid<Cache, Persistent>,
+    user_id: u64,
+) -> Result<Option<UserDetails>, ProviderError> {
+    let user = match hybrid.get_user(user_id)? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+
+    Ok(Some(UserDetails {
+        user,
+        roles: hybrid.roles_for_user(user_id)?,
+    }))
+}
+
+/// Gets the most recent activity of the specified user, so that moderators
+/// can tell whether or not an account is dormant. Only moderators and
+/// administrators may view a user's activity.
+#[get("/{user_id}/last_seen")]
+pub async fn user_last_seen(
+    state: Data<State>,
+    _auth: RequireCapability<CanViewActivity>,
+    user_id: Path<u64>,
+) -> Result<Option<Json<LastSeen>>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Ok(Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .get_last_seen(*user_id)?
+    .map(Json))
+}
+
+/// ProfileUpdate represents a request to modify the editable fields of a
+/// user's profile. Fields that are omitted are left unchanged.
+#[derive(Deserialize)]
+pub struct ProfileUpdate {
+    /// (optional) The country that the user most identifies with
+    nationality: Option<String>,
+
+    /// (optional) Whether or not the user accepts gifts
+    accepts_gifts: Option<bool>,
+
+    /// (optional) The user's minecraft username
+    minecraft_name: Option<String>,
+}
+
+impl ProfileUpdate {
+    /// Validates the update, and applies it to the given user profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The profile that the update should be applied to
+    pub fn apply(&self, mut user: User) -> Result<User, ProviderError> {
+        if let Some(nationality) = &self.nationality {
+            user = user.with_nationality(Some(nationality.clone()).filter(|n| !n.is_empty()));
+        }
+
+        if let Some(accepts_gifts) = self.accepts_gifts {
+            user = user.with_accepts_gifts(accepts_gifts);
+        }
+
+        if let Some(minecraft_name) = &self.minecraft_name {
+            // An empty minecraft username unlinks the user's minecraft
+            // account
+            if !minecraft_name.is_empty() && !is_valid_minecraft_name(minecraft_name) {
+                return Err(ProviderError::InvalidArgument {
+                    arg: "minecraft_name",
+                });
+            }
+
+            user = user.with_minecraft_name(Some(minecraft_name.clone()).filter(|n| !n.is_empty()));
+        }
+
+        Ok(user)
+    }
+}
+
+/// Updates the profile of the user with the given ID. Users may only edit
+/// their own profiles, unless they are an administrator.
+#[patch("/{user_id}")]
+pub async fn update_profile(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+    update: Json<ProfileUpdate>,
+) -> Result<Option<Json<User>>, HttpError> {
+    authorize_edit(&principal, *user_id)?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut users = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let user = match users.get_user(*user_id)? {
+        Some(user) => update.apply(user)?,
+        None => return Ok(None),
+    };
+    users.set_user(&user)?;
+
+    Ok(Some(Json(user)))
+}
+
+/// Deletes the account of the user with the given ID, alongside all data
+/// attributed to the user. Users may only delete their own accounts, unless
+/// they are an administrator.
+#[delete("/{user_id}")]
+pub async fn delete_account(
+    state: Data<State>,
+    principal: Principal,
+    user_id: Path<u64>,
+) -> Result<HttpResponse, HttpError> {
+    authorize_edit(&principal, *user_id)?;
+
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut users = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+    let avatar = users
+        .get_user(*user_id)?
+        .and_then(|user| user.avatar().map(|avatar| avatar.to_owned()));
+
+    if users.delete_user(*user_id)? {
+        if let Some(avatar) = avatar {
+            avatars::remove_avatar(&state, avatar).await?;
+        }
+
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+/// Ensures that the given principal is permitted to modify the account of the
+/// user with the given ID. Accounts may be modified by their owners and by
+/// administrators.
+///
+/// # Arguments
+///
+/// * `principal` - The party attempting to modify the account
+/// * `user_id` - The ID of the user whose account would be modified
+pub(crate) fn authorize_edit(principal: &Principal, user_id: u64) -> Result<(), AuthError> {
+    let permitted = match principal {
+        Principal::User(user) if user.id() == user_id => true,
+        principal => principal.is_administrator(),
+    };
+
+    if permitted {
+        Ok(())
+    } else {
+        Err(AuthError::InsufficientPermissions)
+    }
+}
+
+/// Registers a new user, storing a mapping between their username and ID in
+/// the name resolver, and assigning them the provided default roles.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `default_roles` - The roles that should be assigned to the user
+/// * `user` - The user that should be registered
+pub(crate) fn register_user(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    default_roles: &[Role],
+    user: &NewUser,
+) -> Result<User, ProviderError> {
+    let registered = Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .register_user(user)?;
+
+    Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .set_combination(user.username(), registered.id())?;
+
+    if !default_roles.is_empty() {
+        Hybrid::new(
+            Cache::new(conn).with_prefix(key_prefix),
+            Persistent::new(persistent_conn),
+        )
+        .give_roles(registered.id(), default_roles)?;
+    }
+
+    Ok(registered)
+}
+
+/// Provider represents an arbitrary backend for the users service, which
+/// stores the profiles of registered users.
+pub trait Provider {
+    /// Retreives the profile of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose profile should be obtained
+    fn get_user(&mut self, user_id: u64) -> Result<Option<User>, ProviderError>;
+
+    /// Stores the given user profile in the provider, replacing any existing
+    /// profile for the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The profile that should be stored
+    fn set_user(&mut self, user: &User) -> Result<(), ProviderError>;
+
+    /// Deletes the user with the given ID, alongside their username mappings,
+    /// roles, oauth connections, sanctions, sessions, messages, whispers and
+    /// ignores. Donations and gifts made by the user are kept, but no longer
+    /// attributed to them. Returns whether or not the user existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be deleted
+    fn delete_user(&mut self, user_id: u64) -> Result<bool, ProviderError>;
+}
+
+impl<'a> Provider for Cache<'a> {
+    /// Retreives the profile of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose profile should be obtained
+    fn get_user(&mut self, user_id: u64) -> Result<Option<User>, ProviderError> {
+        redis::cmd("GET")
+            .arg(self.key(format_args!("user::{}", user_id)))
+            .query::<Option<String>>(self.connection)
+            .map_err(|e| e.into())
+            .and_then(|raw| {
+                raw.map_or(Ok(None), |str_data| {
+                    serde_json::from_str(&str_data)
+                        .map(Some)
+                        .map_err(|e| e.into())
+                })
+            })
+    }
+
+    /// Stores the given user profile in the redis caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The profile that should be stored
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::{ws_http_server::modules::{Cache, users::Provider}, spec::user::User};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let client = redis::Client::open("redis://127.0.0.1/")?;
+    /// let mut conn = client.get_connection()?;
+    ///
+    /// let mut users = Cache::new(&mut conn);
+    /// let user: User = serde_json::from_str(r#"{"id":69420,"username":"MrMouton","verified":true,"nationality":null,"accepts_gifts":null,"minecraft_name":null,"created_at":"2020-05-04T17:19:02","updated_at":"2020-05-04T17:19:02","avatar":null}"#)?;
+    ///
+    /// users.set_user(&user)?;
+    /// assert_eq!(users.get_user(69420)?, Some(user));
+    /// Ok(())
+    /// # }
+    /// ```
+    fn set_user(&mut self, user: &User) -> Result<(), ProviderError> {
+        redis::cmd("SET")
+            .arg(self.key(format_args!("user::{}", user.id())))
+            .arg(serde_json::to_string(user)?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Deletes each of the keys pertaining to the user with the given ID from
+    /// the redis caching layer, and revokes each of the user's sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be deleted
+    fn delete_user(&mut self, user_id: u64) -> Result<bool, ProviderError> {
+        // Deleted accounts mustn't remain logged in on any device
+        self.revoke_user_sessions(user_id)?;
+
+        // The user's username is necessary to remove mappings keyed by it
+        let username = match self.username_for(user_id)? {
+            Some(username) => Some(username),
+            None => self
+                .get_user(user_id)?
+                .and_then(|user| user.username().map(|username| username.to_owned())),
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("DEL")
+            .arg(self.key(format_args!("user::{}", user_id)))
+            .arg(self.key(format_args!("username::{}", user_id)))
+            .arg(self.key(format_args!("username_history::{}", user_id)))
+            .arg(self.key(format_args!("roles::{}", user_id)))
+            .arg(self.key(format_args!("settings::{}", user_id)))
+            .arg(self.key(format_args!("last_seen::{}", user_id)))
+            .arg(self.key(format_args!("muted::{}", user_id)))
+            .arg(self.key(format_args!("banned::{}", user_id)))
+            .arg(self.key(ignores_key(user_id)))
+            .arg(self.key(inbox_key(user_id)))
+            .arg(self.key(grants_key(user_id)))
+            .arg(self.key(subscription_key(user_id)));
+
+        // A pending flush would otherwise resurrect the user's activity
+        pipe.cmd("SREM")
+            .arg(self.key("last_seen_dirty"))
+            .arg(user_id)
+            .ignore();
+
+        if let Some(username) = &username {
+            pipe.cmd("DEL")
+                .arg(self.key(format_args!("user_id::{}", username)))
+                .ignore()
+                .cmd("ZREM")
+                .arg(self.key("usernames"))
+                .arg(name_resolver::search_entry(username))
+                .ignore();
+        }
+
+        pipe.query::<(u64,)>(self.connection)
+            .map(|(deleted,)| deleted > 0)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Inserts the given user into the database, returning the profile of the
+    /// newly created user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user that should be registered
+    pub fn register_user(&mut self, user: &NewUser) -> Result<User, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            diesel::insert_into(users::table)
+                .values(user)
+                .execute(connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched by the user's (unique) username
+            users::dsl::users
+                .filter(users::dsl::username.eq(user.username()))
+                .first::<User>(connection)
+                .map_err(|e| e.into())
+        })
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Retreives the profile of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose profile should be obtained
+    fn get_user(&mut self, user_id: u64) -> Result<Option<User>, ProviderError> {
+        users::dsl::users
+            .find(Unsigned(user_id))
+            .first::<User>(self.connection)
+            .map(Some)
+            .or_else(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    Ok(None)
+                } else {
+                    Err(e.into())
+                }
+            })
+    }
+
+    /// Stores the given user profile in the MySQL database, marking it as
+    /// having been modified at the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The profile that should be stored
+    fn set_user(&mut self, user: &User) -> Result<(), ProviderError> {
+        diesel::replace_into(users::table)
+            .values(&user.clone().touch())
+            .execute(self.connection)
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Deletes the user with the given ID, alongside each row attributed to
+    /// the user, in one transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be deleted
+    fn delete_user(&mut self, user_id: u64) -> Result<bool, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            diesel::delete(ids::table.filter(ids::dsl::user_id.eq(Unsigned(user_id))))
+                .execute(connection)?;
+            diesel::delete(roles::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(last_seen::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(settings::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(
+                username_history::table
+                    .filter(username_history::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(mutes::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(bans::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(
+                refresh_tokens::table.filter(refresh_tokens::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(two_factor::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(
+                recovery_codes::table.filter(recovery_codes::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(reddit_connected::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(twitch_connected::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(twitter_connected::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(google_connected::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(discord_connected::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(
+                chat_history::table.filter(chat_history::dsl::sender_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                chat_stats::table.filter(chat_stats::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                whispers::table.filter(
+                    whispers::dsl::sender_id
+                        .eq(Unsigned(user_id))
+                        .or(whispers::dsl::recipient_id.eq(Unsigned(user_id))),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                whisper_reads::table.filter(
+                    whisper_reads::dsl::user_id
+                        .eq(Unsigned(user_id))
+                        .or(whisper_reads::dsl::peer_id.eq(Unsigned(user_id))),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                ignores::table.filter(
+                    ignores::dsl::user_id
+                        .eq(Unsigned(user_id))
+                        .or(ignores::dsl::ignored_id.eq(Unsigned(user_id))),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                flair_grants::table.filter(flair_grants::dsl::user_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+            diesel::delete(subscriptions::table.find(Unsigned(user_id))).execute(connection)?;
+            diesel::delete(
+                gifted_subscriptions::table
+                    .filter(gifted_subscriptions::dsl::recipient_id.eq(Unsigned(user_id))),
+            )
+            .execute(connection)?;
+
+            // Donations and gifts count towards the streamer's totals, so
+            // they are kept, but anonymized
+            diesel::update(donations::table.filter(donations::dsl::donor_id.eq(Unsigned(user_id))))
+                .set((
+                    donations::dsl::donor_id.eq(None::<Unsigned<u64>>),
+                    donations::dsl::donor.eq(DELETED_USER_NAME),
+                ))
+                .execute(connection)?;
+            diesel::update(
+                gifted_subscriptions::table
+                    .filter(gifted_subscriptions::dsl::gifter_id.eq(Unsigned(user_id))),
+            )
+            .set((
+                gifted_subscriptions::dsl::gifter_id.eq(None::<Unsigned<u64>>),
+                gifted_subscriptions::dsl::gifter.eq(DELETED_USER_NAME),
+            ))
+            .execute(connection)?;
+
+            diesel::delete(users::table.find(Unsigned(user_id)))
+                .execute(connection)
+                .map(|deleted| deleted > 0)
+                .map_err(|e| e.into())
+        })
+    }
+}
+
+impl<'a, 'b> Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Inserts the given user into the database, and caches the profile of the
+    /// newly created user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user that should be registered
+    pub fn register_user(&mut self, user: &NewUser) -> Result<User, ProviderError> {
+        let registered = self.persistent.register_user(user)?;
+
+        self.cache.set_user(&registered).map(|_| registered)
+    }
+}
+
+impl<C: Provider, P: Provider> Provider for Hybrid<C, P> {
+    /// Retreives the profile of the user with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose profile should be obtained
+    fn get_user(&mut self, user_id: u64) -> Result<Option<User>, ProviderError> {
+        match self.cache.get_user(user_id) {
+            Ok(Some(user)) => Ok(Some(user)),
+            _ => self.persistent.get_user(user_id).and_then(|user| {
+                user.map_or(Ok(None), |user| {
+                    self.cache.set_user(&user).map(|_| Some(user))
+                })
+            }),
+        }
+    }
+
+    /// Stores the given user profile in the active provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The profile that should be stored
+    fn set_user(&mut self, user: &User) -> Result<(), ProviderError> {
+        // The cached profile should reflect the modification time assigned
+        // by the persistent layer
+        let user = user.clone().touch();
+
+        self.persistent
+            .set_user(&user)
+            .and_then(|_| self.cache.set_user(&user))
+    }
+
+    /// Deletes the user with the given ID from the persistent layer, and
+    /// evicts each of the user's cached keys once the deletion has been
+    /// committed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who should be deleted
+    fn delete_user(&mut self, user_id: u64) -> Result<bool, ProviderError> {
+        let existed = self.persistent.delete_user(user_id)?;
+
+        self.cache.delete_user(user_id).map(|_| existed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::migrations, *};
+    use diesel::connection::SimpleConnection;
+
+    use std::{env, error::Error, io};
+
+    #[test]
+    fn test_register() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        // Clear out any previous registrations, so that the username is free
+        diesel::delete(users::dsl::users.filter(users::dsl::username.eq("Bogsworth")))
+            .execute(&persistent_conn)?;
+
+        let user = register_user(
+            &mut conn,
+            "",
+            &persistent_conn,
+            &[Role::Subscriber],
+            &NewUser::default().with_username("Bogsworth"),
+        )?;
+
+        let mut users = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        assert_eq!(users.get_user(user.id())?, Some(user.clone()));
+        assert_eq!(users.user_id_for("Bogsworth")?, Some(user.id()));
+        assert!(users.has_role(user.id(), &Role::Subscriber)?);
+
+        assert!(users.delete_user(user.id())?);
+        assert_eq!(users.get_user(user.id())?, None);
+        assert_eq!(users.user_id_for("Bogsworth")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_cascade() -> Result<(), Box<dyn Error>> {
+        let persistent_conn = PersistentConnection::establish(":memory:")?;
+        migrations::run_pending(&persistent_conn, &mut io::sink())?;
+
+        // MrMouton (1) chats, whispers, ignores, and donates, alongside
+        // Destiny (2)
+        persistent_conn.batch_execute(
+            "INSERT INTO users (id, username, verified) VALUES (1, 'MrMouton', 1), (2, 'Destiny', 1);
+             INSERT INTO chat_history (sender_id, sender, contents, sent_at)
+                 VALUES (1, 'MrMouton', 'Hi', '2020-05-09 11:03:42'),
+                        (2, 'Destiny', 'Hi', '2020-05-09 11:03:42');
+             INSERT INTO chat_stats (user_id, day, lines) VALUES (1, '2020-05-09', 1);
+             INSERT INTO whispers (sender_id, sender, recipient_id, contents, sent_at)
+                 VALUES (1, 'MrMouton', 2, 'Hi', '2020-05-10 09:30:27'),
+                        (2, 'Destiny', 1, 'Hi', '2020-05-10 09:30:27');
+             INSERT INTO whisper_reads (user_id, peer_id, last_read_id) VALUES (1, 2, 2), (2, 1, 1);
+             INSERT INTO ignores (user_id, ignored_id) VALUES (1, 2), (2, 1);
+             INSERT INTO flair_grants (user_id, flair) VALUES (1, 'gnome');
+             INSERT INTO subscriptions (user_id, tier) VALUES (1, 1);
+             INSERT INTO donations (donor_id, donor, amount, currency) VALUES (1, 'MrMouton', 500, 'USD');
+             INSERT INTO gifted_subscriptions (gifter_id, gifter, recipient_id, recipient, tier, amount, currency)
+                 VALUES (1, 'MrMouton', 2, 'Destiny', 1, 500, 'USD'),
+                        (2, 'Destiny', 1, 'MrMouton', 1, 500, 'USD');",
+        )?;
+
+        assert!(Persistent::new(&persistent_conn).delete_user(1)?);
+
+        let id = Unsigned(1u64);
+        let remaining: [i64; 8] = [
+            chat_history::table
+                .filter(chat_history::dsl::sender_id.eq(id))
+                .count()
+                .get_result(&persistent_conn)?,
+            chat_stats::table
+                .filter(chat_stats::dsl::user_id.eq(id))
+                .count()
+                .get_result(&persistent_conn)?,
+            whispers::table
+                .filter(
+                    whispers::dsl::sender_id
+                        .eq(id)
+                        .or(whispers::dsl::recipient_id.eq(id)),
+                )
+                .count()
+                .get_result(&persistent_conn)?,
+            whisper_reads::table
+                .filter(
+                    whisper_reads::dsl::user_id
+                        .eq(id)
+                        .or(whisper_reads::dsl::peer_id.eq(id)),
+                )
+                .count()
+                .get_result(&persistent_conn)?,
+            ignores::table
+                .filter(
+                    ignores::dsl::user_id
+                        .eq(id)
+                        .or(ignores::dsl::ignored_id.eq(id)),
+                )
+                .count()
+                .get_result(&persistent_conn)?,
+            flair_grants::table
+                .filter(flair_grants::dsl::user_id.eq(id))
+                .count()
+                .get_result(&persistent_conn)?,
+            donations::table
+                .filter(donations::dsl::donor_id.eq(id))
+                .count()
+                .get_result(&persistent_conn)?,
+            gifted_subscriptions::table
+                .filter(
+                    gifted_subscriptions::dsl::gifter_id
+                        .eq(id)
+                        .or(gifted_subscriptions::dsl::recipient_id.eq(id)),
+                )
+                .count()
+                .get_result(&persistent_conn)?,
+        ];
+        assert_eq!(remaining, [0; 8]);
+        assert_eq!(
+            subscriptions::table
+                .find(id)
+                .count()
+                .get_result::<i64>(&persistent_conn)?,
+            0
+        );
+
+        // Donations are kept for the streamer's totals, but anonymized
+        assert_eq!(
+            donations::table
+                .select(donations::dsl::donor)
+                .load::<String>(&persistent_conn)?,
+            vec![DELETED_USER_NAME.to_owned()]
+        );
+
+        // Destiny's own messages remain
+        assert_eq!(
+            chat_history::table
+                .count()
+                .get_result::<i64>(&persistent_conn)?,
+            1
+        );
+
+        Ok(())
+    }
+}
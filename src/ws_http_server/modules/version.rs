@@ -0,0 +1,98 @@
+use actix_web::{web::Json, Scope};
+use serde::Serialize;
+
+use super::connections::PROTOCOL_VERSIONS;
+
+/// The optional features that the server may have been built with.
+const FEATURES: [(&str, bool); 3] = [
+    ("frontend", cfg!(feature = "frontend")),
+    ("graphql", cfg!(feature = "graphql")),
+    ("otlp", cfg!(feature = "otlp")),
+];
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the version module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/version").service(version)
+}
+
+/// VersionInfo describes the build of gnomegg answering a request.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct VersionInfo {
+    /// The version of the gnomegg crate
+    version: &'static str,
+
+    /// The git commit that the server was built from, or "unknown" if it
+    /// wasn't built from a git checkout
+    commit: &'static str,
+
+    /// The versions of the chat protocol that clients may negotiate, oldest
+    /// first
+    protocol_versions: &'static [u32],
+
+    /// The optional features that the server was built with
+    features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    /// Describes the running build of gnomegg.
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: env!("GNOMEGG_GIT_COMMIT"),
+            protocol_versions: &PROTOCOL_VERSIONS,
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| *feature)
+                .collect(),
+        }
+    }
+
+    /// Retreives the version of the gnomegg crate.
+    pub fn version(&self) -> &str {
+        self.version
+    }
+
+    /// Retreives the git commit that the server was built from.
+    pub fn commit(&self) -> &str {
+        self.commit
+    }
+
+    /// Retreives the versions of the chat protocol that clients may
+    /// negotiate.
+    pub fn protocol_versions(&self) -> &[u32] {
+        self.protocol_versions
+    }
+
+    /// Retreives the optional features that the server was built with.
+    pub fn features(&self) -> &[&'static str] {
+        &self.features
+    }
+}
+
+/// Gets the version of gnomegg answering the request, the commit it was
+/// built from, the chat protocol versions it supports, and the optional
+/// features it was built with. The route is public, so that operators and
+/// bots may check what they are talking to.
+#[get("")]
+pub async fn version() -> Json<VersionInfo> {
+    Json(VersionInfo::current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current() {
+        let info = VersionInfo::current();
+        assert_eq!(info.version(), env!("CARGO_PKG_VERSION"));
+        assert!(!info.commit().is_empty());
+        assert_eq!(info.protocol_versions(), &PROTOCOL_VERSIONS);
+        assert_eq!(
+            info.features().contains(&"graphql"),
+            cfg!(feature = "graphql")
+        );
+    }
+}
@@ -0,0 +1,697 @@
+use actix_web::{
+    web::{Data, Json, Path, Query},
+    Error as HttpError, Scope,
+};
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+use redis::Connection as RedisConnection;
+use serde::Deserialize;
+
+use super::{
+    super::{
+        super::spec::{
+            backend::{last_insert_id, PersistentConnection, Unsigned, UnsignedBigint},
+            event::{Event, EventKind, EventTarget},
+            schema::{whisper_reads, whispers},
+            whisper::{NewWhisper, ReadMarker, UnreadCount, Whisper},
+        },
+        auth::AuthedUser,
+        server::State,
+    },
+    name_resolver::Provider as NameResolver,
+    Cache, Hybrid, Persistent, ProviderError,
+};
+
+use std::slice;
+
+/// The maximum number of undelivered whispers held in a user's cached inbox.
+/// Inboxes holding more whispers than this are read from the persistent
+/// layer.
+pub const INBOX_LENGTH: usize = 100;
+
+/// The redis channel on which each change to a user's unread whisper counts
+/// is published as an event targeting the user, so that each of the user's
+/// sessions may be updated.
+pub const UNREAD_CHANNEL: &str = "whisper_unread";
+
+/// The number of whispers returned by the conversation route if no limit is
+/// specified.
+const DEFAULT_PAGE_LENGTH: usize = 50;
+
+/// The maximum number of whispers that may be returned by the conversation
+/// route.
+const MAX_PAGE_LENGTH: usize = 200;
+
+/// Builds an actix service group encompassing each of the HTTP routes
+/// designated by the whispers module.
+pub(crate) fn build_service_group() -> Scope {
+    Scope::new("/whispers")
+        .service(unread_counts)
+        .service(conversation)
+        .service(mark_read)
+}
+
+/// MarkRead represents a request to mark the whispers in a conversation as
+/// read.
+#[derive(Deserialize)]
+pub struct MarkRead {
+    /// (optional) The ID of the most recent whisper read by the user. If
+    /// omitted, each whisper in the conversation is marked as read.
+    last_read_id: Option<u64>,
+}
+
+/// Gets the number of unread whispers in each of the authenticated user's
+/// conversations holding any.
+#[get("")]
+pub async fn unread_counts(
+    state: Data<State>,
+    user: AuthedUser,
+) -> Result<Json<Vec<UnreadCount>>, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    )
+    .unread_counts(user.id())
+    .map(Json)
+}
+
+/// ConversationQuery represents the query parameters accepted by the
+/// conversation route.
+#[derive(Deserialize)]
+pub struct ConversationQuery {
+    /// (optional) The ID of the whisper before which each returned whisper
+    /// must have been sent
+    before: Option<u64>,
+
+    /// (optional) The maximum number of whispers to return
+    limit: Option<usize>,
+}
+
+/// Gets the whispers exchanged between the authenticated user and the user
+/// with the given username, oldest first.
+#[get("/{peer}")]
+pub async fn conversation(
+    state: Data<State>,
+    user: AuthedUser,
+    peer: Path<String>,
+    query: Query<ConversationQuery>,
+) -> Result<Option<Json<Vec<Whisper>>>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let peer_id = match hybrid.user_id_for(&peer)? {
+        Some(peer_id) => peer_id,
+        None => return Ok(None),
+    };
+
+    Ok(Some(Json(
+        hybrid.conversation(
+            user.id(),
+            peer_id,
+            query.before,
+            query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_LENGTH)
+                .min(MAX_PAGE_LENGTH),
+        )?,
+    )))
+}
+
+/// Marks the whispers sent to the authenticated user by the user with the
+/// given username as read, returning the number of whispers left unread. Read
+/// markers only move forward, so that a device lagging behind can't mark
+/// read whispers as unread.
+#[post("/{peer}/read")]
+pub async fn mark_read(
+    state: Data<State>,
+    user: AuthedUser,
+    peer: Path<String>,
+    body: Json<MarkRead>,
+) -> Result<Option<Json<UnreadCount>>, HttpError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let mut hybrid = Hybrid::new(
+        Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        Persistent::new(&persistent_conn),
+    );
+
+    let peer_id = match hybrid.user_id_for(&peer)? {
+        Some(peer_id) => peer_id,
+        None => return Ok(None),
+    };
+
+    Ok(Some(Json(hybrid.mark_read(
+        user.id(),
+        peer_id,
+        body.last_read_id,
+    )?)))
+}
+
+/// Gets each of the whispers queued for the given user while they were
+/// offline, oldest first, marking them as delivered. Called once the user
+/// connects to the chat.
+///
+/// # Arguments
+///
+/// * `conn` - A connection to the redis caching layer
+/// * `key_prefix` - The prefix of each of the deployment's redis keys
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `user_id` - The ID of the user who connected
+pub fn deliver_inbox(
+    conn: &mut RedisConnection,
+    key_prefix: &str,
+    persistent_conn: &PersistentConnection,
+    user_id: u64,
+) -> Result<Vec<Whisper>, ProviderError> {
+    Hybrid::new(
+        Cache::new(conn).with_prefix(key_prefix),
+        Persistent::new(persistent_conn),
+    )
+    .take_inbox(user_id)
+}
+
+/// Gets the redis key of the list holding the given user's undelivered
+/// whispers.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user whose inbox should be located
+pub(crate) fn inbox_key(user_id: u64) -> String {
+    format!("whisper_inbox::{}", user_id)
+}
+
+/// Provider represents an arbitrary backend for the whispers service, which
+/// archives private messages and queues them for their recipients. Whispers
+/// must outlive the caching layer, so only the persistent and hybrid layers
+/// implement the provider; the caching layer merely holds each user's inbox.
+pub trait Provider {
+    /// Records the given whisper, queueing it in its recipient's inbox, and
+    /// returns the whisper alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `whisper` - The whisper that should be recorded
+    fn record_whisper(&mut self, whisper: &NewWhisper) -> Result<Whisper, ProviderError>;
+
+    /// Gets each of the undelivered whispers sent to the given user, oldest
+    /// first, and marks them as delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose inbox should be emptied
+    fn take_inbox(&mut self, user_id: u64) -> Result<Vec<Whisper>, ProviderError>;
+
+    /// Gets up to `limit` of the most recent whispers exchanged between the
+    /// given users, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of one of the participants
+    /// * `peer_id` - The ID of the other participant
+    /// * `before` - (optional) The ID of the whisper before which each
+    /// whisper must have been sent
+    /// * `limit` - The maximum number of whispers that should be returned
+    fn conversation(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        before: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Whisper>, ProviderError>;
+
+    /// Gets the number of unread whispers in each of the given user's
+    /// conversations holding any.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose unread whispers should be
+    /// counted
+    fn unread_counts(&mut self, user_id: u64) -> Result<Vec<UnreadCount>, ProviderError>;
+
+    /// Moves the given user's read marker in their conversation with the
+    /// given peer forward, returning the number of whispers left unread.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who read the whispers
+    /// * `peer_id` - The ID of the user who sent the whispers
+    /// * `through` - (optional) The ID of the most recent whisper read by the
+    /// user, or None if each whisper has been read
+    fn mark_read(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        through: Option<u64>,
+    ) -> Result<UnreadCount, ProviderError>;
+}
+
+impl<'a> Cache<'a> {
+    /// Queues the given whispers in their recipient's redis inbox, discarding
+    /// the oldest whispers once the inbox is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the recipient
+    /// * `whispers` - The whispers that should be queued, oldest first
+    fn queue_whispers(&mut self, user_id: u64, whispers: &[Whisper]) -> Result<(), ProviderError> {
+        if whispers.is_empty() {
+            return Ok(());
+        }
+
+        let serialized = whispers
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, _>>()?;
+
+        redis::pipe()
+            .atomic()
+            .cmd("RPUSH")
+            .arg(self.key(inbox_key(user_id)))
+            .arg(serialized)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(self.key(inbox_key(user_id)))
+            .arg(-(INBOX_LENGTH as isize))
+            .arg(-1)
+            .ignore()
+            .query::<()>(self.connection)
+            .map_err(|e| e.into())
+    }
+
+    /// Empties the given user's redis inbox, returning the whispers that it
+    /// held, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose inbox should be emptied
+    fn take_cached_inbox(&mut self, user_id: u64) -> Result<Vec<Whisper>, ProviderError> {
+        let (serialized,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .cmd("LRANGE")
+            .arg(self.key(inbox_key(user_id)))
+            .arg(0)
+            .arg(-1)
+            .cmd("DEL")
+            .arg(self.key(inbox_key(user_id)))
+            .ignore()
+            .query(self.connection)?;
+
+        serialized
+            .iter()
+            .map(|whisper| serde_json::from_str(whisper).map_err(|e| e.into()))
+            .collect()
+    }
+}
+
+impl<'a> Cache<'a> {
+    /// Publishes the given unread count, so that it may be pushed to each of
+    /// the user's sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username of the user whose count changed
+    /// * `count` - The changed unread count
+    fn publish_unread(&mut self, username: &str, count: &UnreadCount) -> Result<(), ProviderError> {
+        redis::cmd("PUBLISH")
+            .arg(self.key(UNREAD_CHANNEL))
+            .arg(serde_json::to_string(&Event::new(
+                EventTarget::User(username),
+                EventKind::WhisperUnread(count.clone()),
+            ))?)
+            .query(self.connection)
+            .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Persistent<'a> {
+    /// Counts the whispers sent to the given user by the given peer after the
+    /// user's read marker.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to whom the whispers were sent
+    /// * `peer_id` - The ID of the user who sent the whispers
+    fn unread_count(&mut self, user_id: u64, peer_id: u64) -> Result<UnreadCount, ProviderError> {
+        let last_read_id = whisper_reads::dsl::whisper_reads
+            .find((Unsigned(user_id), Unsigned(peer_id)))
+            .select(whisper_reads::dsl::last_read_id)
+            .first::<u64>(self.connection)
+            .optional()?
+            .unwrap_or(0);
+
+        let unread = whispers::dsl::whispers
+            .filter(whispers::dsl::recipient_id.eq(Unsigned(user_id)))
+            .filter(whispers::dsl::sender_id.eq(Unsigned(peer_id)))
+            .filter(whispers::dsl::id.gt(Unsigned(last_read_id)))
+            .count()
+            .get_result::<i64>(self.connection)?;
+
+        Ok(UnreadCount::new(
+            &ReadMarker::new(user_id, peer_id, last_read_id),
+            unread as u64,
+        ))
+    }
+
+    /// Marks each of the whispers with the given IDs as delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The IDs of the delivered whispers
+    fn mark_delivered(&mut self, ids: &[u64]) -> Result<(), ProviderError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        diesel::update(
+            whispers::dsl::whispers
+                .filter(whispers::dsl::id.eq_any(ids.iter().copied().map(Unsigned))),
+        )
+        .set(whispers::dsl::delivered_at.eq(Utc::now().naive_utc()))
+        .execute(self.connection)
+        .map(|_| ())
+        .map_err(|e| e.into())
+    }
+}
+
+impl<'a> Provider for Persistent<'a> {
+    /// Archives the given whisper in the MySQL database, returning the
+    /// whisper alongside its assigned ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `whisper` - The whisper that should be recorded
+    fn record_whisper(&mut self, whisper: &NewWhisper) -> Result<Whisper, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            diesel::insert_into(whispers::table)
+                .values(whisper)
+                .execute(connection)?;
+
+            // RETURNING clauses aren't supported, so the newly assigned ID
+            // must be fetched separately
+            last_insert_id(connection)
+                .map(|id| whisper.with_id(id))
+                .map_err(|e| e.into())
+        })
+    }
+
+    /// Gets each of the undelivered whispers sent to the given user from the
+    /// MySQL database, oldest first, and marks them as delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose inbox should be emptied
+    fn take_inbox(&mut self, user_id: u64) -> Result<Vec<Whisper>, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            let inbox = whispers::dsl::whispers
+                .filter(whispers::dsl::recipient_id.eq(Unsigned(user_id)))
+                .filter(whispers::dsl::delivered_at.is_null())
+                .order(whispers::dsl::id.asc())
+                .load::<Whisper>(connection)?;
+
+            self.mark_delivered(&inbox.iter().map(Whisper::id).collect::<Vec<u64>>())
+                .map(|_| inbox)
+        })
+    }
+
+    /// Gets up to `limit` of the most recent whispers exchanged between the
+    /// given users from the MySQL database, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of one of the participants
+    /// * `peer_id` - The ID of the other participant
+    /// * `before` - (optional) The ID of the whisper before which each
+    /// whisper must have been sent
+    /// * `limit` - The maximum number of whispers that should be returned
+    fn conversation(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        before: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Whisper>, ProviderError> {
+        let mut query = whispers::dsl::whispers
+            .filter(
+                (whispers::dsl::sender_id
+                    .eq(Unsigned(user_id))
+                    .and(whispers::dsl::recipient_id.eq(Unsigned(peer_id))))
+                .or(whispers::dsl::sender_id
+                    .eq(Unsigned(peer_id))
+                    .and(whispers::dsl::recipient_id.eq(Unsigned(user_id)))),
+            )
+            .order(whispers::dsl::id.desc())
+            .limit(limit as i64)
+            .into_boxed();
+
+        if let Some(before) = before {
+            query = query.filter(whispers::dsl::id.lt(Unsigned(before)));
+        }
+
+        let mut exchanged = query.load::<Whisper>(self.connection)?;
+        exchanged.reverse();
+
+        Ok(exchanged)
+    }
+
+    /// Gets the number of unread whispers in each of the given user's
+    /// conversations holding any from the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose unread whispers should be
+    /// counted
+    fn unread_counts(&mut self, user_id: u64) -> Result<Vec<UnreadCount>, ProviderError> {
+        diesel::sql_query(
+            "SELECT w.recipient_id AS user_id, w.sender_id AS peer_id, \
+             COALESCE(MAX(r.last_read_id), 0) AS last_read_id, COUNT(*) AS unread \
+             FROM whispers w LEFT JOIN whisper_reads r \
+             ON r.user_id = w.recipient_id AND r.peer_id = w.sender_id \
+             WHERE w.recipient_id = ? AND w.id > COALESCE(r.last_read_id, 0) \
+             GROUP BY w.recipient_id, w.sender_id",
+        )
+        .bind::<UnsignedBigint, _>(user_id)
+        .load(self.connection)
+        .map_err(|e| e.into())
+    }
+
+    /// Moves the given user's read marker in their conversation with the
+    /// given peer forward in the MySQL database, returning the number of
+    /// whispers left unread.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who read the whispers
+    /// * `peer_id` - The ID of the user who sent the whispers
+    /// * `through` - (optional) The ID of the most recent whisper read by the
+    /// user, or None if each whisper has been read
+    fn mark_read(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        through: Option<u64>,
+    ) -> Result<UnreadCount, ProviderError> {
+        let connection = self.connection;
+
+        connection.transaction(|| {
+            let current = self.unread_count(user_id, peer_id)?;
+            let latest = whispers::dsl::whispers
+                .filter(whispers::dsl::recipient_id.eq(Unsigned(user_id)))
+                .filter(whispers::dsl::sender_id.eq(Unsigned(peer_id)))
+                .select(diesel::dsl::max(whispers::dsl::id))
+                .first::<Option<u64>>(connection)?
+                .unwrap_or(0);
+
+            // Markers may neither move backwards, nor past the most recent
+            // whisper, lest future whispers be considered read
+            let last_read_id = through
+                .map_or(latest, |through| through.min(latest))
+                .max(current.last_read_id());
+            if last_read_id == current.last_read_id() {
+                return Ok(current);
+            }
+
+            diesel::replace_into(whisper_reads::table)
+                .values(&ReadMarker::new(user_id, peer_id, last_read_id))
+                .execute(connection)?;
+
+            self.unread_count(user_id, peer_id)
+        })
+    }
+}
+
+impl<'a, 'b> Provider for Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Archives the given whisper in the persistent layer, and queues it in
+    /// its recipient's cached inbox.
+    ///
+    /// # Arguments
+    ///
+    /// * `whisper` - The whisper that should be recorded
+    fn record_whisper(&mut self, whisper: &NewWhisper) -> Result<Whisper, ProviderError> {
+        let whisper = self.persistent.record_whisper(whisper)?;
+        self.cache
+            .queue_whispers(whisper.recipient_id(), slice::from_ref(&whisper))?;
+
+        let count = self
+            .persistent
+            .unread_count(whisper.recipient_id(), whisper.sender_id())?;
+        self.publish_unread(&count).map(|_| whisper)
+    }
+
+    /// Gets each of the undelivered whispers sent to the given user, oldest
+    /// first, and marks them as delivered. The cached inbox is used unless it
+    /// is empty or full, in which case it may have lost whispers, and the
+    /// persistent layer is consulted instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose inbox should be emptied
+    fn take_inbox(&mut self, user_id: u64) -> Result<Vec<Whisper>, ProviderError> {
+        let cached = self.cache.take_cached_inbox(user_id)?;
+
+        if cached.is_empty() || cached.len() >= INBOX_LENGTH {
+            return self.persistent.take_inbox(user_id);
+        }
+
+        self.persistent
+            .mark_delivered(&cached.iter().map(Whisper::id).collect::<Vec<u64>>())
+            .map(|_| cached)
+    }
+
+    /// Gets up to `limit` of the most recent whispers exchanged between the
+    /// given users, oldest first. Conversations are only held by the
+    /// persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of one of the participants
+    /// * `peer_id` - The ID of the other participant
+    /// * `before` - (optional) The ID of the whisper before which each
+    /// whisper must have been sent
+    /// * `limit` - The maximum number of whispers that should be returned
+    fn conversation(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        before: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Whisper>, ProviderError> {
+        self.persistent
+            .conversation(user_id, peer_id, before, limit)
+    }
+
+    /// Gets the number of unread whispers in each of the given user's
+    /// conversations holding any. Counts are only held by the persistent
+    /// layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user whose unread whispers should be
+    /// counted
+    fn unread_counts(&mut self, user_id: u64) -> Result<Vec<UnreadCount>, ProviderError> {
+        self.persistent.unread_counts(user_id)
+    }
+
+    /// Moves the given user's read marker in their conversation with the
+    /// given peer forward, and publishes the resulting unread count to each
+    /// of the user's sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user who read the whispers
+    /// * `peer_id` - The ID of the user who sent the whispers
+    /// * `through` - (optional) The ID of the most recent whisper read by the
+    /// user, or None if each whisper has been read
+    fn mark_read(
+        &mut self,
+        user_id: u64,
+        peer_id: u64,
+        through: Option<u64>,
+    ) -> Result<UnreadCount, ProviderError> {
+        let count = self.persistent.mark_read(user_id, peer_id, through)?;
+
+        self.publish_unread(&count).map(|_| count)
+    }
+}
+
+impl<'a, 'b> Hybrid<Cache<'a>, Persistent<'b>> {
+    /// Publishes the given unread count to each of its user's sessions.
+    /// Counts held by users that have since deleted their accounts have no
+    /// sessions to be pushed to, and aren't published.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The changed unread count
+    fn publish_unread(&mut self, count: &UnreadCount) -> Result<(), ProviderError> {
+        match self.username_for(count.user_id())? {
+            Some(username) => self.cache.publish_unread(&username, count),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_hybrid() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        let mut whispers = Hybrid::new(Cache::new(&mut conn), Persistent::new(&persistent_conn));
+        whispers.take_inbox(42069)?;
+
+        let sent = whispers.record_whisper(&NewWhisper::new(
+            69420,
+            "MrMouton",
+            42069,
+            "are you there?",
+            Utc::now(),
+        ))?;
+
+        // Whispers are delivered once, but remain in the conversation
+        assert_eq!(whispers.take_inbox(42069)?, vec![sent.clone()]);
+        assert!(whispers.take_inbox(42069)?.is_empty());
+        assert_eq!(
+            whispers.conversation(42069, 69420, None, 1)?[0].id(),
+            sent.id()
+        );
+
+        // Delivered whispers remain unread until the recipient reads them
+        assert_eq!(
+            whispers
+                .mark_read(42069, 69420, Some(sent.id() - 1))?
+                .unread(),
+            1
+        );
+        assert_eq!(whispers.mark_read(42069, 69420, None)?.unread(), 0);
+        assert_eq!(
+            whispers.mark_read(42069, 69420, Some(0))?.last_read_id(),
+            sent.id()
+        );
+
+        Ok(())
+    }
+}
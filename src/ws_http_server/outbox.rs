@@ -0,0 +1,463 @@
+use actix_web::{
+    rt,
+    web::{self, Data},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    super::spec::{ban::NewBan, mute::Mute, user::Role},
+    breaker,
+    modules::{bans, mutes, roles, Cache, Persistent, ProviderError},
+    pool::RedisPool,
+    server::State,
+};
+
+use std::{collections::VecDeque, fmt, sync::Mutex, time::Duration};
+
+/// The key of the redis list holding the writes waiting to be persisted,
+/// oldest first.
+const OUTBOX_KEY: &str = "outbox";
+
+/// The key of the redis list holding the writes that the persistent layer
+/// rejected upon being replayed.
+const DEAD_LETTER_KEY: &str = "outbox::dead";
+
+/// The key of the lock held by the node replaying the outbox.
+const REPLAY_LOCK_KEY: &str = "outbox::replaying";
+
+/// The number of seconds after which a node's claim to replay the outbox
+/// lapses, in case it crashed while replaying.
+const REPLAY_LOCK_TTL_SECONDS: u64 = 60;
+
+/// The interval at which each node attempts to replay the outbox.
+const REPLAY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// PendingWrite represents a moderation write that was applied to the caching
+/// layer, but couldn't be persisted because the persistent layer was down.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingWrite {
+    /// A user (and, optionally, their address) was banned
+    Ban {
+        user_id: u64,
+        duration: Option<u64>,
+        initiated_at: DateTime<Utc>,
+        ip: Option<String>,
+    },
+
+    /// A user (and, optionally, their address) was unbanned
+    Unban { user_id: u64, ip: Option<String> },
+
+    /// A user was muted
+    Mute { mute: Mute },
+
+    /// A user was unmuted
+    Unmute { user_id: u64 },
+
+    /// A user was given a number of roles
+    GiveRoles { user_id: u64, roles: Vec<Role> },
+
+    /// A user was stripped of a role
+    RemoveRole { user_id: u64, role: Role },
+
+    /// A user was stripped of each of their roles
+    PurgeRoles { user_id: u64 },
+}
+
+impl PendingWrite {
+    /// Applies the write to the given persistent layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `persistent` - The persistent layer that the write should be applied
+    /// to
+    pub fn apply<P>(&self, persistent: &mut P) -> Result<(), ProviderError>
+    where
+        P: bans::Provider + mutes::Provider + roles::Provider,
+    {
+        match self {
+            Self::Ban {
+                user_id,
+                duration,
+                initiated_at,
+                ip,
+            } => persistent
+                .register_ban(&NewBan::new(
+                    *user_id,
+                    *duration,
+                    *initiated_at,
+                    ip.as_deref(),
+                ))
+                .map(|_| ()),
+            Self::Unban { user_id, ip } => persistent
+                .set_banned(*user_id, false, None, ip.as_deref())
+                .map(|_| ()),
+            Self::Mute { mute } => persistent.register_mute(mute).map(|_| ()),
+            Self::Unmute { user_id } => persistent.set_muted(*user_id, false, None).map(|_| ()),
+            Self::GiveRoles { user_id, roles } => persistent.give_roles(*user_id, roles),
+            Self::RemoveRole { user_id, role } => persistent.remove_role(*user_id, role),
+            Self::PurgeRoles { user_id } => persistent.purge_roles(*user_id).map(|_| ()),
+        }
+    }
+}
+
+/// Outbox represents an arbitrary queue of the writes waiting to be
+/// persisted, shared by each hybrid provider (and, for shared backends, by
+/// each node).
+pub trait Outbox: Send + Sync + fmt::Debug {
+    /// Queues the given write behind every write already waiting to be
+    /// persisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `write` - The write that should be queued
+    fn enqueue(&self, write: &PendingWrite) -> Result<(), ProviderError>;
+
+    /// Counts the writes waiting to be persisted.
+    fn pending(&self) -> Result<usize, ProviderError>;
+
+    /// Retreives the oldest write waiting to be persisted, without removing
+    /// it from the queue.
+    fn peek(&self) -> Result<Option<PendingWrite>, ProviderError>;
+
+    /// Removes the oldest write waiting to be persisted from the queue, once
+    /// it has been replayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead` - Whether or not the persistent layer rejected the write, in
+    /// which case it is kept aside for an operator to inspect
+    fn acknowledge(&self, dead: bool) -> Result<(), ProviderError>;
+
+    /// Claims the right to replay the queue, such that only one node replays
+    /// it at a time. Returns whether or not the claim succeeded.
+    fn claim(&self) -> Result<bool, ProviderError>;
+
+    /// Relinquishes a claim to replay the queue.
+    fn release(&self) -> Result<(), ProviderError>;
+}
+
+/// RedisOutbox is an outbox stored in the redis caching layer, which keeps
+/// serving chat while the persistent layer is down.
+#[derive(Debug)]
+pub struct RedisOutbox {
+    /// The pool that connections to the caching layer are obtained from
+    pool: RedisPool,
+
+    /// The prefix applied to each of the outbox's keys
+    prefix: String,
+}
+
+impl RedisOutbox {
+    /// Creates a new redis-backed outbox.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The pool that connections to the caching layer should be
+    /// obtained from
+    /// * `prefix` - The prefix applied to each of the outbox's keys
+    pub fn new(pool: RedisPool, prefix: String) -> Self {
+        Self { pool, prefix }
+    }
+
+    /// Runs the given closure against the caching layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The closure that should be run
+    fn with_cache<T>(
+        &self,
+        f: impl FnOnce(&mut Cache, &str) -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let mut conn = self.pool.get()?;
+        let mut cache = Cache::new(&mut conn).with_prefix(&self.prefix);
+        let key = cache.key(OUTBOX_KEY);
+
+        f(&mut cache, &key)
+    }
+}
+
+impl Outbox for RedisOutbox {
+    fn enqueue(&self, write: &PendingWrite) -> Result<(), ProviderError> {
+        let write = serde_json::to_string(write)?;
+
+        self.with_cache(|cache, key| {
+            cache.pipeline(|pipe| {
+                pipe.cmd("RPUSH").arg(key).arg(&write).ignore();
+            })
+        })
+    }
+
+    fn pending(&self) -> Result<usize, ProviderError> {
+        self.with_cache(|cache, key| {
+            cache
+                .pipeline(|pipe| {
+                    pipe.cmd("LLEN").arg(key);
+                })
+                .map(|(pending,)| pending)
+        })
+    }
+
+    fn peek(&self) -> Result<Option<PendingWrite>, ProviderError> {
+        let (write,): (Option<String>,) = self.with_cache(|cache, key| {
+            cache.pipeline(|pipe| {
+                pipe.cmd("LINDEX").arg(key).arg(0);
+            })
+        })?;
+
+        write
+            .map(|write| serde_json::from_str(&write))
+            .transpose()
+            .map_err(|e| e.into())
+    }
+
+    fn acknowledge(&self, dead: bool) -> Result<(), ProviderError> {
+        self.with_cache(|cache, key| {
+            let dead_letters = cache.key(DEAD_LETTER_KEY);
+
+            // The write is moved atomically, such that it is never lost nor
+            // replayed twice
+            cache
+                .eval(
+                    "local write = redis.call('LPOP', KEYS[1]) \
+                 if write and ARGV[1] == '1' then redis.call('RPUSH', KEYS[2], write) end \
+                 return 0",
+                    |script| {
+                        script
+                            .key(key)
+                            .key(&dead_letters)
+                            .arg(if dead { "1" } else { "0" });
+                    },
+                )
+                .map(|_: i64| ())
+        })
+    }
+
+    fn claim(&self) -> Result<bool, ProviderError> {
+        self.with_cache(|cache, _| {
+            let lock = cache.key(REPLAY_LOCK_KEY);
+
+            cache
+                .pipeline(|pipe| {
+                    pipe.cmd("SET")
+                        .arg(&lock)
+                        .arg(1)
+                        .arg("NX")
+                        .arg("EX")
+                        .arg(REPLAY_LOCK_TTL_SECONDS);
+                })
+                .map(|(claimed,): (Option<String>,)| claimed.is_some())
+        })
+    }
+
+    fn release(&self) -> Result<(), ProviderError> {
+        self.with_cache(|cache, _| {
+            let lock = cache.key(REPLAY_LOCK_KEY);
+
+            cache.pipeline(|pipe| {
+                pipe.cmd("DEL").arg(&lock).ignore();
+            })
+        })
+    }
+}
+
+/// MemoryOutbox is an outbox held in the memory of a single node.
+#[derive(Default, Debug)]
+pub struct MemoryOutbox {
+    /// The writes waiting to be persisted, oldest first
+    writes: Mutex<VecDeque<PendingWrite>>,
+
+    /// The writes that the persistent layer rejected upon being replayed
+    dead_letters: Mutex<Vec<PendingWrite>>,
+}
+
+impl MemoryOutbox {
+    /// Retreives the writes that the persistent layer rejected upon being
+    /// replayed.
+    pub fn dead_letters(&self) -> Vec<PendingWrite> {
+        self.dead_letters.lock().unwrap().clone()
+    }
+}
+
+impl Outbox for MemoryOutbox {
+    fn enqueue(&self, write: &PendingWrite) -> Result<(), ProviderError> {
+        self.writes.lock().unwrap().push_back(write.clone());
+
+        Ok(())
+    }
+
+    fn pending(&self) -> Result<usize, ProviderError> {
+        Ok(self.writes.lock().unwrap().len())
+    }
+
+    fn peek(&self) -> Result<Option<PendingWrite>, ProviderError> {
+        Ok(self.writes.lock().unwrap().front().cloned())
+    }
+
+    fn acknowledge(&self, dead: bool) -> Result<(), ProviderError> {
+        let write = self.writes.lock().unwrap().pop_front();
+
+        if let Some(write) = write.filter(|_| dead) {
+            self.dead_letters.lock().unwrap().push(write);
+        }
+
+        Ok(())
+    }
+
+    fn claim(&self) -> Result<bool, ProviderError> {
+        Ok(true)
+    }
+
+    fn release(&self) -> Result<(), ProviderError> {
+        Ok(())
+    }
+}
+
+/// Unavailable is a persistent layer standing in for a persistent layer that
+/// couldn't be reached, such that hybrid providers given an outbox may still
+/// serve writes from the caching layer.
+pub struct Unavailable;
+
+/// Replays the writes queued in the given outbox against the given
+/// persistent layer, oldest first, returning the number of writes that were
+/// replayed. Replaying stops at the first write that fails because the
+/// persistent layer is (still) down, leaving it at the head of the queue.
+/// Writes rejected by the persistent layer are set aside, lest they block the
+/// queue forever.
+///
+/// # Arguments
+///
+/// * `outbox` - The outbox whose writes should be replayed
+/// * `persistent` - The persistent layer that the writes should be applied to
+pub fn replay<P>(outbox: &dyn Outbox, persistent: &mut P) -> Result<usize, ProviderError>
+where
+    P: bans::Provider + mutes::Provider + roles::Provider,
+{
+    // Another node is already replaying the queue
+    if !outbox.claim()? {
+        return Ok(0);
+    }
+
+    let replayed = replay_claimed(outbox, persistent);
+    let released = outbox.release();
+
+    replayed.and_then(|replayed| released.map(|_| replayed))
+}
+
+/// Replays the writes queued in the given outbox against the given
+/// persistent layer, once the right to replay it has been claimed.
+///
+/// # Arguments
+///
+/// * `outbox` - The outbox whose writes should be replayed
+/// * `persistent` - The persistent layer that the writes should be applied to
+fn replay_claimed<P>(outbox: &dyn Outbox, persistent: &mut P) -> Result<usize, ProviderError>
+where
+    P: bans::Provider + mutes::Provider + roles::Provider,
+{
+    let mut replayed = 0;
+
+    while let Some(write) = outbox.peek()? {
+        match write.apply(persistent) {
+            Err(e) if breaker::indicates_outage(&e) => return Err(e),
+            Err(e) => {
+                tracing::warn!(error = %e, write = ?write, "the persistent layer rejected a queued write");
+
+                outbox.acknowledge(true)?;
+            }
+            Ok(_) => outbox.acknowledge(false)?,
+        }
+
+        replayed += 1;
+    }
+
+    Ok(replayed)
+}
+
+/// Periodically replays the writes queued while the persistent layer was
+/// down, for as long as the server is running.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub(crate) fn spawn_replay_task(state: Data<State>) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(REPLAY_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let state = state.clone();
+
+            // A failed replay leaves the remaining writes queued, so they will
+            // be retried on the next tick
+            let _ = web::block(move || -> Result<usize, ProviderError> {
+                let outbox = match state.outbox() {
+                    Some(outbox) if outbox.pending()? > 0 => outbox,
+                    _ => return Ok(0),
+                };
+                let persistent_conn = state.persistent_connection()?;
+
+                let replayed = replay(outbox.as_ref(), &mut Persistent::new(&persistent_conn))?;
+                tracing::info!(
+                    replayed,
+                    "replayed the writes queued while the persistent layer was down"
+                );
+
+                Ok(replayed)
+            })
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_http_server::modules::{
+        mutes::Provider as MutesProvider, roles::Provider as RolesProvider, Hybrid, Memory,
+    };
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_queue_and_replay() {
+        let outbox = Arc::new(MemoryOutbox::default());
+
+        // Writes succeed against the cache while the persistent layer is down
+        let mut hybrid = Hybrid::new(Memory::new(), Unavailable).with_outbox(Some(outbox.clone()));
+        hybrid.give_role(1, &Role::Moderator).unwrap();
+        hybrid.give_role(1, &Role::Subscriber).unwrap();
+        hybrid.remove_role(1, &Role::Moderator).unwrap();
+        assert_eq!(hybrid.roles_for_user(1).unwrap(), vec![Role::Subscriber]);
+        assert_eq!(outbox.pending().unwrap(), 3);
+
+        // Without an outbox, the same writes fail
+        assert!(Hybrid::new(Memory::new(), Unavailable)
+            .give_role(1, &Role::Moderator)
+            .is_err());
+
+        // Queued writes are persisted in order once the persistent layer returns
+        let mut persistent = Memory::new();
+        assert_eq!(replay(outbox.as_ref(), &mut persistent).unwrap(), 3);
+        assert_eq!(
+            persistent.roles_for_user(1).unwrap(),
+            vec![Role::Subscriber]
+        );
+        assert_eq!(outbox.pending().unwrap(), 0);
+
+        // Replaying a queue that can't be persisted yet keeps it intact
+        outbox
+            .enqueue(&PendingWrite::Mute {
+                mute: Mute::new(2, 1_000_000_000),
+            })
+            .unwrap();
+        assert!(replay(outbox.as_ref(), &mut Unavailable).is_err());
+        assert_eq!(outbox.pending().unwrap(), 1);
+
+        assert_eq!(replay(outbox.as_ref(), &mut persistent).unwrap(), 1);
+        assert!(persistent.is_muted(2).unwrap());
+        assert!(outbox.dead_letters().is_empty());
+    }
+}
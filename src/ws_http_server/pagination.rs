@@ -0,0 +1,208 @@
+use actix_web::{dev::Payload, web::Query, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+
+use super::modules::ProviderError;
+
+use std::{collections::HashMap, str::FromStr};
+
+/// Sort represents the order in which the items of a list are returned, as
+/// given by the `sort` query parameter accepted by each list route.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// Oldest (or lowest) first
+    Asc,
+
+    /// Most recent (or highest) first
+    Desc,
+}
+
+impl FromStr for Sort {
+    type Err = ProviderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(Self::Asc),
+            "desc" => Ok(Self::Desc),
+            _ => Err(ProviderError::InvalidArgument { arg: "sort" }),
+        }
+    }
+}
+
+/// Pagination is an extractor describing the page of a list requested by a
+/// client. Every list route accepts the same three query parameters:
+///
+/// * `cursor` - (optional) The cursor returned alongside the previous page
+/// * `limit` - (optional) The maximum number of items to return
+/// * `sort` - (optional) Either `asc` or `desc`
+///
+/// Filters specific to a route are extracted from the remaining query
+/// parameters by the route itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pagination {
+    /// The cursor returned alongside the previous page, if any
+    cursor: Option<String>,
+
+    /// The maximum number of items requested, if specified
+    limit: Option<usize>,
+
+    /// The order in which items were requested, if specified
+    sort: Option<Sort>,
+}
+
+impl Pagination {
+    /// Parses the pagination parameters from the given query string, ignoring
+    /// any other parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query string of the request
+    pub fn from_query(query: &str) -> Result<Self, ProviderError> {
+        // Each parameter is parsed individually, so that the client may be
+        // told which of them was malformed
+        let params = Query::<HashMap<String, String>>::from_query(query)
+            .map_err(|_| ProviderError::InvalidArgument { arg: "query" })?;
+
+        Ok(Self {
+            cursor: params.get("cursor").filter(|c| !c.is_empty()).cloned(),
+            limit: params
+                .get("limit")
+                .map(|limit| limit.parse())
+                .transpose()
+                .map_err(|_| ProviderError::InvalidArgument { arg: "limit" })?,
+            sort: params.get("sort").map(|sort| sort.parse()).transpose()?,
+        })
+    }
+
+    /// Parses the cursor given by the client, if any. Cursors are opaque to
+    /// clients, but each route knows what its own cursors hold.
+    pub fn cursor<T: FromStr>(&self) -> Result<Option<T>, ProviderError> {
+        self.cursor
+            .as_deref()
+            .map(|cursor| {
+                cursor
+                    .parse()
+                    .map_err(|_| ProviderError::InvalidArgument { arg: "cursor" })
+            })
+            .transpose()
+    }
+
+    /// Gets the number of items that should be returned, given the route's
+    /// defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - The number of items returned if no limit is specified
+    /// * `max` - The maximum number of items that the route returns
+    pub fn limit(&self, default: usize, max: usize) -> usize {
+        self.limit.unwrap_or(default).min(max).max(1)
+    }
+
+    /// Gets the order in which items should be returned, given the route's
+    /// default.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - The order in which items are returned if none is
+    /// specified
+    pub fn sort(&self, default: Sort) -> Sort {
+        self.sort.unwrap_or(default)
+    }
+}
+
+impl FromRequest for Pagination {
+    type Error = ProviderError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::from_query(req.query_string()))
+    }
+}
+
+/// Page represents a page of items returned by a list route.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    /// The items on the page, in the requested order
+    items: Vec<T>,
+
+    /// The cursor with which the next page may be obtained, if there may be
+    /// one
+    next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Creates a new page from the given items. A short page must be the last
+    /// one, so a cursor is only given for full pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items on the page, in the requested order
+    /// * `limit` - The number of items that were requested
+    /// * `cursor_for` - A function producing the cursor that follows the given
+    /// item
+    pub fn new<C: ToString>(items: Vec<T>, limit: usize, cursor_for: impl Fn(&T) -> C) -> Self {
+        let next_cursor = items
+            .last()
+            .filter(|_| items.len() >= limit)
+            .map(|last| cursor_for(last).to_string());
+
+        Self { items, next_cursor }
+    }
+
+    /// Creates a new page holding no items.
+    pub fn empty() -> Self {
+        Self {
+            items: Vec::new(),
+            next_cursor: None,
+        }
+    }
+
+    /// Retreives the items on the page.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Retreives the cursor with which the next page may be obtained, if
+    /// there may be one.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_query() -> Result<(), ProviderError> {
+        let pagination = Pagination::from_query("cursor=42&limit=500&sort=asc&active=true")?;
+        assert_eq!(pagination.cursor::<u64>()?, Some(42));
+        assert_eq!(pagination.limit(50, 200), 200);
+        assert_eq!(pagination.sort(Sort::Desc), Sort::Asc);
+
+        let pagination = Pagination::from_query("")?;
+        assert_eq!(pagination.cursor::<u64>()?, None);
+        assert_eq!(pagination.limit(50, 200), 50);
+        assert_eq!(pagination.sort(Sort::Desc), Sort::Desc);
+
+        assert!(Pagination::from_query("sort=sideways").is_err());
+        assert!(Pagination::from_query("limit=-1").is_err());
+        assert!(Pagination::from_query("cursor=abc")?
+            .cursor::<u64>()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_page() {
+        let page = Page::new(vec![3, 2, 1], 3, |n| *n);
+        assert_eq!(page.next_cursor(), Some("1"));
+
+        let page = Page::new(vec![3, 2], 3, |n| *n);
+        assert_eq!(page.next_cursor(), None);
+        assert_eq!(page.items(), &[3, 2]);
+    }
+}
@@ -0,0 +1,308 @@
+use diesel::r2d2::{ConnectionManager, ManageConnection, Pool, PoolError, PooledConnection};
+use redis::{Client, Connection, ConnectionLike, RedisError};
+use serde::Serialize;
+
+use super::super::spec::backend::PersistentConnection;
+
+use std::time::Duration;
+
+/// The maximum number of connections held by a pool if none is specified.
+pub const DEFAULT_MAX_SIZE: u32 = 10;
+
+/// The number of seconds that a handler waits for a pooled connection to
+/// become available if no timeout is specified.
+pub const DEFAULT_CONNECTION_TIMEOUT_SECONDS: u64 = 30;
+
+/// PersistentPool is a pool of connections to the persistence layer, shared
+/// by every actix worker.
+pub type PersistentPool = Pool<ConnectionManager<PersistentConnection>>;
+
+/// PooledPersistentConnection is a connection to the persistence layer
+/// checked out of a pool, which is returned to the pool once dropped.
+pub type PooledPersistentConnection = PooledConnection<ConnectionManager<PersistentConnection>>;
+
+/// RedisPool is a pool of connections to the redis caching layer, shared by
+/// every actix worker.
+pub type RedisPool = Pool<RedisConnectionManager>;
+
+/// PooledRedisConnection is a connection to the redis caching layer checked
+/// out of a pool, which is returned to the pool once dropped.
+pub type PooledRedisConnection = PooledConnection<RedisConnectionManager>;
+
+/// RedisConnectionManager opens and checks the health of the connections
+/// held by a pool of connections to the redis caching layer.
+#[derive(Clone, Debug)]
+pub struct RedisConnectionManager {
+    /// The client used to open connections to the redis caching layer
+    client: Client,
+}
+
+impl RedisConnectionManager {
+    /// Creates a new connection manager opening connections with the given
+    /// client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to open connections to the redis caching
+    /// layer
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl ManageConnection for RedisConnectionManager {
+    type Connection = Connection;
+    type Error = RedisError;
+
+    fn connect(&self) -> Result<Connection, RedisError> {
+        self.client.get_connection()
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<(), RedisError> {
+        redis::cmd("PING").query(conn)
+    }
+
+    fn has_broken(&self, conn: &mut Connection) -> bool {
+        !conn.is_open()
+    }
+}
+
+/// PoolConfig represents the sizing of a pool of connections to either the
+/// redis caching layer or the MySQL persistence layer.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolConfig {
+    /// The maximum number of connections held by the pool
+    max_size: u32,
+
+    /// The number of idle connections that the pool tries to keep open, if
+    /// not every connection
+    min_idle: Option<u32>,
+
+    /// The time that a handler waits for a connection to become available
+    /// before giving up
+    connection_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(DEFAULT_CONNECTION_TIMEOUT_SECONDS),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Consumes the configuration, and modifies it according to the provided
+    /// maximum number of connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The maximum number of connections held by the pool
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+
+        self
+    }
+
+    /// Consumes the configuration, and modifies it according to the provided
+    /// number of idle connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_idle` - (optional) The number of idle connections that the
+    /// pool should try to keep open. By default, the pool keeps every
+    /// connection open.
+    pub fn with_min_idle(mut self, min_idle: Option<u32>) -> Self {
+        self.min_idle = min_idle;
+
+        self
+    }
+
+    /// Consumes the configuration, and modifies it according to the provided
+    /// timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_timeout` - The time that a handler should wait for a
+    /// connection to become available before giving up
+    pub fn with_connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection_timeout = connection_timeout;
+
+        self
+    }
+
+    /// Opens a pool of connections to the MySQL database at the given
+    /// address, failing if the pool's idle connections can't be opened
+    /// before the connection timeout elapses.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_url` - The address of the MySQL database
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::pool::PoolConfig;
+    /// # use std::{env, error::Error};
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// dotenv::dotenv()?;
+    ///
+    /// let pool = PoolConfig::default()
+    ///     .with_max_size(4)
+    ///     .build(&env::var("DATABASE_URL")?)?;
+    /// assert_eq!(pool.max_size(), 4);
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn build(&self, database_url: &str) -> Result<PersistentPool, PoolError> {
+        self.build_with(ConnectionManager::new(database_url))
+    }
+
+    /// Opens a pool of connections to the redis caching layer with the given
+    /// client, failing if the pool's idle connections can't be opened before
+    /// the connection timeout elapses.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to open connections to the redis caching
+    /// layer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::pool::PoolConfig;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let pool = PoolConfig::default()
+    ///     .with_max_size(4)
+    ///     .build_redis(redis::Client::open("redis://127.0.0.1/")?)?;
+    /// assert_eq!(pool.max_size(), 4);
+    /// Ok(())
+    /// # }
+    /// ```
+    pub fn build_redis(&self, client: Client) -> Result<RedisPool, PoolError> {
+        self.build_with(RedisConnectionManager::new(client))
+    }
+
+    /// Opens a pool of connections with the given connection manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager` - The manager used to open connections
+    fn build_with<M: ManageConnection>(&self, manager: M) -> Result<Pool<M>, PoolError> {
+        Pool::builder()
+            .max_size(self.max_size)
+            .min_idle(self.min_idle)
+            .connection_timeout(self.connection_timeout)
+            .build(manager)
+    }
+}
+
+/// PoolMetrics represents a snapshot of the usage of a connection pool.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct PoolMetrics {
+    /// The maximum number of connections held by the pool
+    max_size: u32,
+
+    /// The number of connections currently open
+    connections: u32,
+
+    /// The number of open connections that aren't checked out
+    idle_connections: u32,
+}
+
+impl PoolMetrics {
+    /// Takes a snapshot of the usage of the given pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The pool whose usage should be measured
+    pub fn of<M: ManageConnection>(pool: &Pool<M>) -> Self {
+        let state = pool.state();
+
+        Self {
+            max_size: pool.max_size(),
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+        }
+    }
+
+    /// Retreives the maximum number of connections held by the pool.
+    pub fn max_size(&self) -> u32 {
+        self.max_size
+    }
+
+    /// Retreives the number of connections currently open.
+    pub fn connections(&self) -> u32 {
+        self.connections
+    }
+
+    /// Retreives the number of open connections that aren't checked out.
+    pub fn idle_connections(&self) -> u32 {
+        self.idle_connections
+    }
+
+    /// Gets the number of connections currently checked out by handlers.
+    pub fn in_use(&self) -> u32 {
+        self.connections - self.idle_connections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_pool() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let pool =
+            PoolConfig::default()
+                .with_max_size(2)
+                .build(&env::var("DATABASE_URL").expect(
+                    "DATABASE_URL must be set in a .env file for test to complete successfully",
+                ))?;
+
+        let conn = pool.get()?;
+        let metrics = PoolMetrics::of(&pool);
+        assert_eq!(metrics.max_size(), 2);
+        assert_eq!(metrics.in_use(), 1);
+
+        // Connections are returned to the pool once dropped
+        drop(conn);
+        assert_eq!(PoolMetrics::of(&pool).in_use(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_redis_pool() -> Result<(), Box<dyn Error>> {
+        let pool = PoolConfig::default()
+            .with_max_size(2)
+            .build_redis(Client::open("redis://127.0.0.1/")?)?;
+
+        let mut first = pool.get()?;
+        let mut second = pool.get()?;
+        assert_eq!(PoolMetrics::of(&pool).in_use(), 2);
+
+        // Pooled connections may be used concurrently
+        redis::cmd("SET")
+            .arg("test_redis_pool")
+            .arg(1)
+            .query::<()>(&mut *first)?;
+        assert_eq!(
+            redis::cmd("GET")
+                .arg("test_redis_pool")
+                .query::<u64>(&mut *second)?,
+            1
+        );
+
+        Ok(())
+    }
+}
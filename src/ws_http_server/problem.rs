@@ -0,0 +1,227 @@
+use actix_web::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    Error as HttpError, HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    auth::AuthError,
+    jwt::JwtError,
+    modules::{
+        oauth::OauthError, stream::WebhookError, throttle::ThrottleError,
+        two_factor::TwoFactorError, ProviderError,
+    },
+};
+
+use std::{error::Error, fmt};
+
+/// The media type of each response describing an error.
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// The prefix of the URI identifying each type of problem, which is followed
+/// by the problem's code.
+const PROBLEM_TYPE_PREFIX: &str = "urn:gnomegg:problem:";
+
+/// ErrorCode is implemented by each error that may be returned by an HTTP
+/// handler, such that clients may tell errors apart without parsing their
+/// descriptions.
+pub trait ErrorCode {
+    /// Gets the machine-readable code identifying the kind of error, in
+    /// snake_case. Codes are stable, whereas descriptions may change.
+    fn code(&self) -> &'static str;
+}
+
+/// Problem represents the body of a response to a request that failed, as
+/// an RFC 7807 problem detail object.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// A URI identifying the type of problem
+    #[serde(rename = "type")]
+    problem_type: String,
+
+    /// A short summary of the type of problem, which is the reason phrase of
+    /// the status
+    title: String,
+
+    /// The HTTP status with which the request was answered
+    status: u16,
+
+    /// A description of this occurrence of the problem
+    detail: String,
+
+    /// The machine-readable code identifying the type of problem
+    code: String,
+
+    /// (optional) The ID of the request, which may be quoted in bug reports
+    /// so that they may be matched with the server's logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl Problem {
+    /// Creates a new problem.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status with which the request is answered
+    /// * `code` - The machine-readable code identifying the type of problem
+    /// * `detail` - A description of this occurrence of the problem
+    pub fn new(status: StatusCode, code: &str, detail: &str) -> Self {
+        Self {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: status.canonical_reason().unwrap_or("Error").to_owned(),
+            status: status.as_u16(),
+            detail: detail.to_owned(),
+            code: code.to_owned(),
+            request_id: None,
+        }
+    }
+
+    /// Describes the given error returned by a handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The error that should be described
+    pub fn from_error<E: ResponseError + ErrorCode>(e: &E) -> Self {
+        Self::new(e.status_code(), e.code(), &e.to_string())
+    }
+
+    /// Describes a response with the given status, which carried no
+    /// description of its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status with which the request was answered
+    pub fn from_status(status: StatusCode) -> Self {
+        let reason = status.canonical_reason().unwrap_or("Error");
+
+        Self::new(
+            status,
+            &reason
+                .to_lowercase()
+                .replace(|c: char| !c.is_alphanumeric(), "_"),
+            reason,
+        )
+    }
+
+    /// Describes the given error, of any type, returned by a handler or
+    /// extractor. Errors that gnomegg doesn't define (e.g. malformed JSON
+    /// bodies) are identified by their status.
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The error that should be described
+    pub fn of(e: &HttpError) -> Self {
+        if let Some(problem) = e.as_error::<Self>() {
+            return problem.clone();
+        }
+
+        let status = e.as_response_error().status_code();
+        let code = code_of::<ProviderError>(e)
+            .or_else(|| code_of::<AuthError>(e))
+            .or_else(|| code_of::<JwtError>(e))
+            .or_else(|| code_of::<ThrottleError>(e))
+            .or_else(|| code_of::<TwoFactorError>(e))
+            .or_else(|| code_of::<OauthError>(e))
+            .or_else(|| code_of::<WebhookError>(e));
+
+        match code {
+            Some(code) => Self::new(status, code, &e.to_string()),
+            None => Self {
+                detail: e.to_string(),
+                ..Self::from_status(status)
+            },
+        }
+    }
+
+    /// Consumes the problem, and modifies it according to the ID of the
+    /// request that encountered it.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_id` - The ID of the request
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_owned());
+
+        self
+    }
+
+    /// Retreives the HTTP status with which the request was answered.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Retreives the machine-readable code identifying the type of problem.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Retreives the description of this occurrence of the problem.
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.detail)
+    }
+}
+
+impl Error for Problem {}
+
+impl ResponseError for Problem {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .header(CONTENT_TYPE, PROBLEM_CONTENT_TYPE)
+            .body(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// Gets the code of the given error, if it is of the given type.
+///
+/// # Arguments
+///
+/// * `e` - The error whose code should be obtained
+fn code_of<E: ResponseError + ErrorCode + 'static>(e: &HttpError) -> Option<&'static str> {
+    e.as_error::<E>().map(ErrorCode::code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of() {
+        let problem = Problem::of(&ProviderError::InvalidArgument { arg: "limit" }.into());
+        assert_eq!(problem.status(), 400);
+        assert_eq!(problem.code(), "invalid_argument");
+        assert_eq!(problem.problem_type, "urn:gnomegg:problem:invalid_argument");
+        assert_eq!(problem.title, "Bad Request");
+
+        let problem = Problem::of(&AuthError::InsufficientPermissions.into());
+        assert_eq!(problem.status(), 403);
+        assert_eq!(problem.code(), "insufficient_permissions");
+
+        // Problems returned by handlers are described as they are
+        let conflict = Problem::new(StatusCode::CONFLICT, "username_taken", "taken");
+        assert_eq!(Problem::of(&conflict.clone().into()), conflict);
+    }
+
+    #[test]
+    fn test_from_status() {
+        let problem = Problem::from_status(StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(problem.code(), "too_many_requests");
+        assert_eq!(problem.detail(), "Too Many Requests");
+
+        let problem = Problem::from_status(StatusCode::NOT_FOUND).with_request_id("abc");
+        let body = serde_json::to_value(&problem).unwrap();
+        assert_eq!(body["type"], "urn:gnomegg:problem:not_found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["request_id"], "abc");
+    }
+}
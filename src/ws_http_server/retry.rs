@@ -0,0 +1,214 @@
+use rand::{thread_rng, Rng};
+
+use super::modules::ProviderError;
+
+use std::{thread, time::Duration};
+
+/// The number of times that an operation is attempted if no maximum is
+/// specified.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// The number of milliseconds waited before the first retry if no base delay
+/// is specified.
+pub const DEFAULT_BASE_DELAY_MS: u64 = 10;
+
+/// The maximum number of milliseconds waited between two attempts if no cap
+/// is specified.
+pub const DEFAULT_MAX_DELAY_MS: u64 = 200;
+
+/// RetryPolicy represents the way in which idempotent operations are retried
+/// when the redis caching layer can't be reached. The delay before each
+/// retry is chosen at random between zero and an exponentially growing cap
+/// (i.e. "full jitter"), such that workers retrying at once don't hit redis
+/// in lockstep.
+///
+/// Delays are waited out by blocking the calling thread, so they should be
+/// kept short.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// The number of times that an operation is attempted before giving up
+    max_attempts: u32,
+
+    /// The cap of the delay before the first retry, doubled for each
+    /// subsequent retry
+    base_delay: Duration,
+
+    /// The maximum delay between two attempts
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Consumes the policy, and modifies it according to the provided
+    /// maximum number of attempts.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - The number of times that an operation should be
+    /// attempted before giving up. Operations are always attempted at least
+    /// once.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+
+        self
+    }
+
+    /// Consumes the policy, and modifies it according to the provided base
+    /// delay.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_delay` - The cap of the delay before the first retry
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+
+        self
+    }
+
+    /// Consumes the policy, and modifies it according to the provided
+    /// maximum delay.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_delay` - The maximum delay between two attempts
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+
+        self
+    }
+
+    /// Retreives the number of times that an operation is attempted before
+    /// giving up.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Gets the cap of the delay before the given retry (starting at 1).
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - The number of the retry
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.base_delay
+            .checked_mul(1 << retry.saturating_sub(1).min(16))
+            .map_or(self.max_delay, |backoff| backoff.min(self.max_delay))
+    }
+
+    /// Runs the given idempotent operation, running it again after a delay
+    /// if it fails because the redis caching layer couldn't be reached. The
+    /// operation should check out a new connection on each attempt, as
+    /// connections are discarded once they are found to be broken.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation that should be run
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::{modules::ProviderError, retry::RetryPolicy};
+    /// use std::io;
+    ///
+    /// let mut attempts = 0;
+    /// let res = RetryPolicy::default().with_max_attempts(2).run(|| {
+    ///     attempts += 1;
+    ///
+    ///     Err::<(), _>(ProviderError::RedisError(
+    ///         io::Error::from(io::ErrorKind::ConnectionRefused).into(),
+    ///     ))
+    /// });
+    /// assert!(res.is_err());
+    /// assert_eq!(attempts, 2);
+    /// ```
+    pub fn run<T>(
+        &self,
+        mut op: impl FnMut() -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let mut retry = 0;
+
+        loop {
+            match op() {
+                Err(e) if is_transient(&e) && retry + 1 < self.max_attempts => {
+                    retry += 1;
+
+                    let backoff = self.backoff(retry);
+                    if backoff > Duration::from_secs(0) {
+                        thread::sleep(thread_rng().gen_range(Duration::from_secs(0), backoff));
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Determines whether or not the given error was caused by a connection to
+/// the redis caching layer failing, such that the operation may succeed if
+/// it is attempted again.
+///
+/// # Arguments
+///
+/// * `e` - The error returned by the operation
+pub fn is_transient(e: &ProviderError) -> bool {
+    match e {
+        ProviderError::RedisError(e) => {
+            e.is_io_error()
+                || e.is_timeout()
+                || e.is_connection_dropped()
+                || e.is_connection_refusal()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_base_delay(Duration::from_millis(1))
+            .with_max_delay(Duration::from_millis(3));
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(3), Duration::from_millis(3));
+
+        // Transient errors are retried until the operation succeeds
+        let mut attempts = 0;
+        let res = policy.run(|| {
+            attempts += 1;
+
+            if attempts < 3 {
+                Err(ProviderError::RedisError(
+                    io::Error::from(io::ErrorKind::ConnectionReset).into(),
+                ))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res.ok(), Some(3));
+
+        // Other errors aren't retried
+        let mut attempts = 0;
+        let res = policy.run(|| {
+            attempts += 1;
+
+            Err::<(), _>(ProviderError::InvalidArgument { arg: "user_id" })
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,393 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use std::{borrow::Cow, error, fmt};
+
+/// The maximum number of characters in a message, unless configured
+/// otherwise.
+pub const DEFAULT_MAX_MESSAGE_CHARS: usize = 512;
+
+/// The maximum number of bytes in a message, unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 2048;
+
+/// The maximum number of combining marks that may follow a single character,
+/// unless configured otherwise. Marks beyond this limit (e.g. zalgo text) are
+/// removed.
+pub const DEFAULT_MAX_COMBINING_MARKS: usize = 3;
+
+/// SanitizeError represents any reason for which a message could not be
+/// sanitized.
+#[derive(Debug, PartialEq)]
+pub enum SanitizeError {
+    /// The message has no visible contents
+    EmptyMessage,
+
+    /// The message exceeds the maximum length
+    MessageTooLong,
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyMessage => write!(f, "the message has no visible contents"),
+            Self::MessageTooLong => write!(f, "the message exceeds the maximum length"),
+        }
+    }
+}
+
+impl error::Error for SanitizeError {}
+
+/// Sanitizer represents the stage through which each message passes before
+/// it is broadcasted or written to the chat history. By default, messages
+/// are normalized to NFC, control characters are stripped, combining marks
+/// are limited to DEFAULT_MAX_COMBINING_MARKS per character, runs of
+/// whitespace are collapsed, and messages are limited to
+/// DEFAULT_MAX_MESSAGE_CHARS characters and DEFAULT_MAX_MESSAGE_BYTES bytes.
+/// Confusable characters are only folded if configured to be.
+#[derive(Clone, Debug)]
+pub struct Sanitizer {
+    /// (optional) The maximum number of characters in a message
+    max_chars: Option<usize>,
+
+    /// (optional) The maximum number of bytes in a message
+    max_bytes: Option<usize>,
+
+    /// Whether or not control characters should be removed from messages
+    strip_control: bool,
+
+    /// Whether or not runs of whitespace should be collapsed into a single
+    /// space, and leading and trailing whitespace removed
+    collapse_whitespace: bool,
+
+    /// Whether or not messages should be normalized to NFC
+    normalize: bool,
+
+    /// (optional) The maximum number of combining marks that may follow a
+    /// single character
+    max_combining_marks: Option<usize>,
+
+    /// Whether or not characters resembling ASCII characters (e.g. Cyrillic
+    /// or fullwidth letters) should be replaced with them
+    fold_confusables: bool,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self {
+            max_chars: Some(DEFAULT_MAX_MESSAGE_CHARS),
+            max_bytes: Some(DEFAULT_MAX_MESSAGE_BYTES),
+            strip_control: true,
+            collapse_whitespace: true,
+            normalize: true,
+            max_combining_marks: Some(DEFAULT_MAX_COMBINING_MARKS),
+            fold_confusables: false,
+        }
+    }
+}
+
+impl Sanitizer {
+    /// Consumes the sanitizer, and modifies it according to the provided
+    /// maximum number of characters.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_chars` - (optional) The maximum number of characters in a
+    /// message
+    pub fn with_max_chars(mut self, max_chars: Option<usize>) -> Self {
+        self.max_chars = max_chars;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to the provided
+    /// maximum number of bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_bytes` - (optional) The maximum number of bytes in a message
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to whether or not
+    /// control characters should be stripped.
+    ///
+    /// # Arguments
+    ///
+    /// * `strip_control` - Whether or not control characters should be
+    /// removed from messages
+    pub fn with_strip_control(mut self, strip_control: bool) -> Self {
+        self.strip_control = strip_control;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to whether or not
+    /// whitespace should be collapsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `collapse_whitespace` - Whether or not runs of whitespace should be
+    /// collapsed into a single space
+    pub fn with_collapse_whitespace(mut self, collapse_whitespace: bool) -> Self {
+        self.collapse_whitespace = collapse_whitespace;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to whether or not
+    /// messages should be normalized to NFC.
+    ///
+    /// # Arguments
+    ///
+    /// * `normalize` - Whether or not messages should be normalized
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to the provided
+    /// maximum number of combining marks per character.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_combining_marks` - (optional) The maximum number of combining
+    /// marks that may follow a single character
+    pub fn with_max_combining_marks(mut self, max_combining_marks: Option<usize>) -> Self {
+        self.max_combining_marks = max_combining_marks;
+
+        self
+    }
+
+    /// Consumes the sanitizer, and modifies it according to whether or not
+    /// confusable characters should be folded.
+    ///
+    /// # Arguments
+    ///
+    /// * `fold_confusables` - Whether or not characters resembling ASCII
+    /// characters should be replaced with them
+    pub fn with_fold_confusables(mut self, fold_confusables: bool) -> Self {
+        self.fold_confusables = fold_confusables;
+
+        self
+    }
+
+    /// Sanitizes the given message, borrowing it if it needn't be modified.
+    /// Lengths are checked once the message has been cleaned, so that
+    /// stripped characters don't count against the sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The contents of the message that should be sanitized
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::sanitizer::Sanitizer;
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let sanitizer = Sanitizer::default();
+    ///
+    /// assert_eq!(sanitizer.sanitize("  hi \u{7}   dadd ")?, "hi dadd");
+    /// assert!(sanitizer.sanitize(" \t ").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sanitize<'a>(&self, msg: &'a str) -> Result<Cow<'a, str>, SanitizeError> {
+        let mut sanitized = String::with_capacity(msg.len());
+        let mut pending_space = false;
+        let mut marks = 0;
+
+        // Normalizing first composes accents onto their base characters,
+        // so that they don't count as combining marks
+        let chars: Box<dyn Iterator<Item = char>> = if self.normalize {
+            Box::new(msg.nfc())
+        } else {
+            Box::new(msg.chars())
+        };
+
+        for c in chars {
+            if is_combining_mark(c) {
+                marks += 1;
+
+                if self
+                    .max_combining_marks
+                    .is_some_and(|max_marks| marks > max_marks)
+                {
+                    continue;
+                }
+            } else {
+                marks = 0;
+            }
+
+            if self.collapse_whitespace && c.is_whitespace() {
+                pending_space = !sanitized.is_empty();
+
+                continue;
+            }
+
+            // Whitespace control characters (e.g. newlines) are left for
+            // collapsing, if enabled
+            if self.strip_control && c.is_control() && !c.is_whitespace() {
+                continue;
+            }
+
+            if pending_space {
+                sanitized.push(' ');
+                pending_space = false;
+            }
+
+            sanitized.push(if self.fold_confusables {
+                fold_confusable(c)
+            } else {
+                c
+            });
+        }
+
+        if sanitized.trim().is_empty() {
+            return Err(SanitizeError::EmptyMessage);
+        }
+
+        if self
+            .max_bytes
+            .is_some_and(|max_bytes| sanitized.len() > max_bytes)
+            || self
+                .max_chars
+                .is_some_and(|max_chars| sanitized.chars().count() > max_chars)
+        {
+            return Err(SanitizeError::MessageTooLong);
+        }
+
+        Ok(if sanitized == msg {
+            Cow::Borrowed(msg)
+        } else {
+            Cow::Owned(sanitized)
+        })
+    }
+}
+
+/// Replaces the given character with the ASCII character that it resembles,
+/// if it is a fullwidth form or a commonly confused Cyrillic or Greek letter.
+///
+/// # Arguments
+///
+/// * `c` - The character that should be folded
+///
+/// # Example
+///
+/// ```
+/// use gnomegg::ws_http_server::sanitizer::fold_confusable;
+///
+/// assert_eq!(fold_confusable('\u{0430}'), 'a'); // Cyrillic a
+/// assert_eq!(fold_confusable('\u{ff24}'), 'D'); // Fullwidth D
+/// assert_eq!(fold_confusable('\u{00e9}'), '\u{00e9}');
+/// ```
+pub fn fold_confusable(c: char) -> char {
+    match c {
+        // Fullwidth forms are offset from printable ASCII
+        '\u{ff01}'..='\u{ff5e}' => std::char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        '\u{0430}' => 'a',
+        '\u{0441}' => 'c',
+        '\u{0435}' => 'e',
+        '\u{04bb}' => 'h',
+        '\u{0456}' => 'i',
+        '\u{0458}' => 'j',
+        '\u{043e}' | '\u{03bf}' => 'o',
+        '\u{0440}' => 'p',
+        '\u{0455}' => 's',
+        '\u{03bd}' => 'v',
+        '\u{0445}' => 'x',
+        '\u{0443}' => 'y',
+        '\u{0410}' | '\u{0391}' => 'A',
+        '\u{0412}' | '\u{0392}' => 'B',
+        '\u{0421}' => 'C',
+        '\u{0415}' | '\u{0395}' => 'E',
+        '\u{041d}' | '\u{0397}' => 'H',
+        '\u{0406}' | '\u{0399}' => 'I',
+        '\u{0408}' => 'J',
+        '\u{041a}' | '\u{039a}' => 'K',
+        '\u{041c}' | '\u{039c}' => 'M',
+        '\u{039d}' => 'N',
+        '\u{041e}' | '\u{039f}' => 'O',
+        '\u{0420}' | '\u{03a1}' => 'P',
+        '\u{0405}' => 'S',
+        '\u{0422}' | '\u{03a4}' => 'T',
+        '\u{0425}' | '\u{03a7}' => 'X',
+        '\u{04ae}' | '\u{03a5}' => 'Y',
+        '\u{0396}' => 'Z',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let sanitizer = Sanitizer::default();
+
+        assert!(matches!(
+            sanitizer.sanitize("hi dadd"),
+            Ok(Cow::Borrowed(_))
+        ));
+        assert_eq!(
+            sanitizer.sanitize("\u{1b}[31mhi\n\n dadd\u{0}").unwrap(),
+            "[31mhi dadd"
+        );
+        assert_eq!(
+            sanitizer.sanitize("\u{0}\u{7f}"),
+            Err(SanitizeError::EmptyMessage)
+        );
+
+        // Lengths are measured in characters and in bytes
+        let sanitizer = sanitizer.with_max_chars(Some(4)).with_max_bytes(Some(6));
+        assert!(sanitizer.sanitize("abcd").is_ok());
+        assert_eq!(
+            sanitizer.sanitize("abcde"),
+            Err(SanitizeError::MessageTooLong)
+        );
+        assert_eq!(
+            sanitizer.sanitize("éééé"),
+            Err(SanitizeError::MessageTooLong)
+        );
+        assert!(sanitizer.sanitize("   abcd   ").is_ok());
+
+        // Whitespace is left intact unless it should be collapsed
+        let sanitizer = Sanitizer::default().with_collapse_whitespace(false);
+        assert_eq!(sanitizer.sanitize("a\u{7}  b").unwrap(), "a  b");
+    }
+
+    #[test]
+    fn test_sanitize_unicode() {
+        let sanitizer = Sanitizer::default();
+
+        // Decomposed accents are composed, rather than counted as marks
+        assert_eq!(sanitizer.sanitize("cafe\u{301}").unwrap(), "caf\u{e9}");
+        assert_eq!(
+            sanitizer
+                .sanitize("z\u{336}\u{335}\u{334}\u{337}\u{338}a")
+                .unwrap(),
+            "z\u{336}\u{335}\u{334}a"
+        );
+
+        // Zalgo text consisting only of marks is empty once limited
+        assert_eq!(
+            sanitizer
+                .with_max_combining_marks(Some(0))
+                .sanitize("\u{336}\u{335}"),
+            Err(SanitizeError::EmptyMessage)
+        );
+
+        let sanitizer = Sanitizer::default().with_fold_confusables(true);
+        assert_eq!(
+            sanitizer
+                .sanitize("\u{0414}\u{0435}stiny \u{ff2f}\u{ff2d}\u{ff27}")
+                .unwrap(),
+            "\u{0414}estiny OMG"
+        );
+    }
+}
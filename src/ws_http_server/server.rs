@@ -1 +1,897 @@
+use actix_web::{
+    dev::Service,
+    http::{header::RETRY_AFTER, HeaderName, HeaderValue, StatusCode},
+    middleware::Compress,
+    web::{self, Data, ServiceConfig},
+    App, Error as HttpError, HttpRequest, HttpResponse, HttpServer,
+};
+use futures::future::{ready, Either};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rustls::{
+    internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys},
+    NoClientAuth, ServerConfig,
+};
+use tracing::{field, info_span, Instrument};
 
+use super::{
+    super::spec::user::Role,
+    auth,
+    breaker::{BreakerMetrics, CircuitBreaker},
+    compression::CompressionPolicy,
+    dispatcher::Dispatcher,
+    invalidation::{self, LocalCache},
+    jwt::KeySet,
+    keyring::Keyring,
+    lookups::{CacheLookups, LookupMetrics},
+    modules::{
+        admin, announcements, approvals, audit, avatars, bans, bot_commands, chat, donations,
+        embeds, emotes,
+        events::{self, EventRelay},
+        export, flairs, health, history, ignores, impersonation, jwks, last_seen, links, me,
+        metrics, mutes,
+        oauth::{self, OauthCredentials, OauthProvider},
+        poll, polls, refresh_tokens, roles, sessions, settings, stats, stream, subscriptions,
+        two_factor, users, version, whispers, Layers, ProviderError,
+    },
+    outbox::{self, Outbox},
+    pool::{
+        PersistentPool, PoolMetrics, PooledPersistentConnection, PooledRedisConnection, RedisPool,
+    },
+    problem::Problem,
+    retry::RetryPolicy,
+    shedding::{self, LoadShedder, SheddingMetrics, SHED_RETRY_AFTER_SECONDS},
+    telemetry::{self, REQUEST_ID_HEADER},
+};
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    mem,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+/// State represents the shared resources available to each of the HTTP
+/// handlers registered by the gnomegg server.
+pub struct State {
+    /// The pool of connections to the redis caching layer, shared by every
+    /// worker
+    cache: RedisPool,
+
+    /// The pool of connections to the MySQL database backing the persistent
+    /// layer, shared by every worker
+    persistent: PersistentPool,
+
+    /// The pool of connections to a read replica of the MySQL database, if
+    /// any, to which frequent reads are routed
+    replica: Option<PersistentPool>,
+
+    /// The bearer token that must be presented in order to access
+    /// administrative routes
+    admin_token: String,
+
+    /// The addresses of the reverse proxies whose forwarded headers are
+    /// trusted to name the client making a request
+    trusted_proxies: Vec<IpAddr>,
+
+    /// The roles assigned to each newly registered user
+    default_roles: Vec<Role>,
+
+    /// The dispatcher used to handle commands issued by chatters
+    dispatcher: Dispatcher,
+
+    /// The directory in which uploaded avatars are stored
+    avatar_dir: PathBuf,
+
+    /// The credentials issued to gnomegg by each enabled oauth provider
+    oauth: HashMap<OauthProvider, OauthCredentials>,
+
+    /// The keys with which session tokens are signed and verified
+    signing_keys: KeySet,
+
+    /// The keyring with which linked account IDs are encrypted at rest, if
+    /// any
+    keyring: Option<Keyring>,
+
+    /// The layers backing the bans and mutes providers
+    moderation_layers: Layers,
+
+    /// The breaker keeping reads from reaching the persistent layer while it
+    /// is failing, shared by every worker
+    breaker: Arc<CircuitBreaker>,
+
+    /// The way in which idempotent operations are retried when redis can't
+    /// be reached
+    retry_policy: RetryPolicy,
+
+    /// The prefix prepended to each redis key and channel used by the
+    /// deployment
+    key_prefix: String,
+
+    /// The copies of frequently read values kept by this node, shared by
+    /// every worker
+    local_cache: Arc<LocalCache>,
+
+    /// (optional) The outbox holding the moderation writes that couldn't be
+    /// persisted while the persistent layer was down
+    outbox: Option<Arc<dyn Outbox>>,
+
+    /// The counts of the hits, misses, and errors of the reads sent to the
+    /// caching layer, shared by every worker
+    lookups: Arc<CacheLookups>,
+
+    /// (optional) The secret that requests to the stream status webhook must
+    /// be signed with. The webhook is disabled until one is provided.
+    stream_webhook_secret: Option<String>,
+
+    /// (optional) The announcement sent to the chat when the stream goes live
+    live_announcement: Option<String>,
+
+    /// The relay feeding the event streams held open by this node, shared by
+    /// every worker
+    event_relay: Arc<EventRelay>,
+
+    /// The shedder rejecting low-priority requests while this node is
+    /// overloaded, shared by every worker
+    load_shedder: Arc<LoadShedder>,
+
+    /// The policy determining which responses are compressed
+    compression: CompressionPolicy,
+
+    /// (optional) The directory holding the chat frontend served alongside
+    /// the API, if any
+    frontend_dir: Option<PathBuf>,
+
+    /// The name under which this node registers the connections that it
+    /// holds open, chosen when the node started
+    node: String,
+}
+
+impl State {
+    /// Creates a new server state with the given backends. Administrative
+    /// routes are disabled until an administrative token is provided, and
+    /// session tokens are signed with a random key until one is provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The pool of connections to the redis caching layer
+    /// * `persistent` - The pool of connections to the MySQL persistence layer
+    pub fn new(cache: RedisPool, persistent: PersistentPool) -> Self {
+        let breaker = Arc::new(CircuitBreaker::default());
+        let local_cache = Arc::new(LocalCache::default());
+        let lookups = Arc::new(CacheLookups::default());
+
+        Self {
+            cache,
+            persistent,
+            replica: None,
+            admin_token: String::new(),
+            trusted_proxies: Vec::new(),
+            default_roles: Vec::new(),
+            dispatcher: Dispatcher::default()
+                .with_breaker(Some(breaker.clone()))
+                .with_local_cache(Some(local_cache.clone()))
+                .with_lookups(Some(lookups.clone())),
+            avatar_dir: PathBuf::from("avatars"),
+            oauth: HashMap::new(),
+            signing_keys: KeySet::random().expect("unable to generate a session signing key"),
+            keyring: None,
+            moderation_layers: Layers::default(),
+            breaker,
+            retry_policy: RetryPolicy::default(),
+            key_prefix: String::new(),
+            local_cache,
+            outbox: None,
+            lookups,
+            stream_webhook_secret: None,
+            live_announcement: None,
+            event_relay: Arc::new(EventRelay::default()),
+            load_shedder: Arc::new(LoadShedder::default()),
+            compression: CompressionPolicy::default(),
+            frontend_dir: None,
+            node: thread_rng().sample_iter(&Alphanumeric).take(16).collect(),
+        }
+    }
+
+    /// Consumes the state, and modifies it according to the provided
+    /// administrative token.
+    ///
+    /// # Arguments
+    ///
+    /// * `admin_token` - The token that administrators must present in order
+    /// to use administrative routes
+    pub fn with_admin_token(mut self, admin_token: String) -> Self {
+        self.admin_token = admin_token;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided trusted
+    /// proxies.
+    ///
+    /// # Arguments
+    ///
+    /// * `trusted_proxies` - The addresses of the reverse proxies whose
+    /// forwarded headers should be trusted to name the client making a
+    /// request
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided stream
+    /// webhook secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_webhook_secret` - (optional) The secret that requests to the
+    /// stream status webhook must be signed with
+    pub fn with_stream_webhook_secret(mut self, stream_webhook_secret: Option<String>) -> Self {
+        self.stream_webhook_secret = stream_webhook_secret;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided live
+    /// announcement.
+    ///
+    /// # Arguments
+    ///
+    /// * `live_announcement` - (optional) The announcement that should be
+    /// sent to the chat when the stream goes live
+    pub fn with_live_announcement(mut self, live_announcement: Option<String>) -> Self {
+        self.live_announcement = live_announcement;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided default
+    /// roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `default_roles` - The roles that should be assigned to each newly
+    /// registered user
+    pub fn with_default_roles(mut self, default_roles: Vec<Role>) -> Self {
+        self.default_roles = default_roles;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided command
+    /// dispatcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `dispatcher` - The dispatcher that should be used to handle commands
+    /// issued by chatters
+    pub fn with_dispatcher(mut self, dispatcher: Dispatcher) -> Self {
+        self.dispatcher = dispatcher
+            .with_breaker(Some(self.breaker.clone()))
+            .with_key_prefix(self.key_prefix.clone())
+            .with_local_cache(Some(self.local_cache.clone()))
+            .with_outbox(self.outbox.clone())
+            .with_lookups(Some(self.lookups.clone()));
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided avatar
+    /// directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `avatar_dir` - The directory in which uploaded avatars should be
+    /// stored
+    pub fn with_avatar_dir(mut self, avatar_dir: PathBuf) -> Self {
+        self.avatar_dir = avatar_dir;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided
+    /// frontend directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `frontend_dir` - The directory holding the chat frontend that should
+    /// be served alongside the API, if any
+    pub fn with_frontend_dir(mut self, frontend_dir: Option<PathBuf>) -> Self {
+        self.frontend_dir = frontend_dir;
+
+        self
+    }
+
+    /// Consumes the state, and enables logging in through the given oauth
+    /// provider with the provided credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The oauth provider that should be enabled
+    /// * `credentials` - The credentials issued to gnomegg by the provider
+    pub fn with_oauth_provider(
+        mut self,
+        provider: OauthProvider,
+        credentials: OauthCredentials,
+    ) -> Self {
+        self.oauth.insert(provider, credentials);
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided session
+    /// token signing keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `signing_keys` - The keys with which session tokens should be signed
+    /// and verified
+    pub fn with_signing_keys(mut self, signing_keys: KeySet) -> Self {
+        self.signing_keys = signing_keys;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided keyring.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyring` - The keyring with which linked account IDs should be
+    /// encrypted at rest
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided
+    /// moderation layers.
+    ///
+    /// # Arguments
+    ///
+    /// * `moderation_layers` - The layers that should back the bans and mutes
+    /// providers
+    pub fn with_moderation_layers(mut self, moderation_layers: Layers) -> Self {
+        self.moderation_layers = moderation_layers;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided circuit
+    /// breaker, which is shared with the state's dispatcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `breaker` - The breaker that should keep reads from reaching the
+    /// persistent layer while it is failing
+    pub fn with_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Arc::new(breaker);
+        self.dispatcher = mem::take(&mut self.dispatcher).with_breaker(Some(self.breaker.clone()));
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided load
+    /// shedder.
+    ///
+    /// # Arguments
+    ///
+    /// * `load_shedder` - The shedder that should reject low-priority
+    /// requests while the node is overloaded
+    pub fn with_load_shedder(mut self, load_shedder: LoadShedder) -> Self {
+        self.load_shedder = Arc::new(load_shedder);
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided
+    /// compression policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - The policy determining which responses should be
+    /// compressed
+    pub fn with_compression(mut self, compression: CompressionPolicy) -> Self {
+        self.compression = compression;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided retry
+    /// policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_policy` - The way in which idempotent operations should be
+    /// retried when redis can't be reached
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided redis
+    /// key prefix, which is shared with the state's dispatcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_prefix` - The prefix that should be prepended to each redis key
+    /// and channel used by the deployment, such that several deployments
+    /// may share one redis instance
+    pub fn with_key_prefix(mut self, key_prefix: String) -> Self {
+        self.dispatcher = mem::take(&mut self.dispatcher).with_key_prefix(key_prefix.clone());
+        self.key_prefix = key_prefix;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it such that bans, roles and
+    /// usernames are read from the given replica of the MySQL database.
+    ///
+    /// # Arguments
+    ///
+    /// * `replica` - The pool of connections to a read replica of the MySQL
+    /// database, if any
+    pub fn with_replica(mut self, replica: Option<PersistentPool>) -> Self {
+        self.replica = replica;
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided local
+    /// cache, which is shared with the state's dispatcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `local_cache` - The cache that should hold this node's copies of
+    /// frequently read values
+    pub fn with_local_cache(mut self, local_cache: LocalCache) -> Self {
+        self.local_cache = Arc::new(local_cache);
+        self.dispatcher =
+            mem::take(&mut self.dispatcher).with_local_cache(Some(self.local_cache.clone()));
+
+        self
+    }
+
+    /// Consumes the state, and modifies it according to the provided outbox,
+    /// which is shared with the state's dispatcher. Without an outbox,
+    /// moderation writes fail while the persistent layer is down.
+    ///
+    /// # Arguments
+    ///
+    /// * `outbox` - (optional) The outbox that moderation writes which can't
+    /// be persisted should be queued in
+    pub fn with_outbox(mut self, outbox: Option<Arc<dyn Outbox>>) -> Self {
+        self.dispatcher = mem::take(&mut self.dispatcher).with_outbox(outbox.clone());
+        self.outbox = outbox;
+
+        self
+    }
+
+    /// Checks out a connection to the redis caching layer from the pool,
+    /// waiting for one to become available if every connection is in use.
+    pub fn cache_connection(&self) -> Result<PooledRedisConnection, ProviderError> {
+        let started = Instant::now();
+        let conn = self.cache.get();
+        self.load_shedder.record_provider_latency(started.elapsed());
+
+        conn.map_err(|e| e.into())
+    }
+
+    /// Checks out a connection to the MySQL persistence layer from the pool,
+    /// waiting for one to become available if every connection is in use.
+    pub fn persistent_connection(&self) -> Result<PooledPersistentConnection, ProviderError> {
+        let started = Instant::now();
+        let conn = self.persistent.get();
+        self.load_shedder.record_provider_latency(started.elapsed());
+
+        conn.map_err(|e| e.into())
+    }
+
+    /// Checks out a connection to the MySQL read replica from its pool, if the
+    /// deployment has a replica, waiting for one to become available if
+    /// every connection is in use.
+    pub fn replica_connection(&self) -> Result<Option<PooledPersistentConnection>, ProviderError> {
+        let started = Instant::now();
+        let conn = self
+            .replica
+            .as_ref()
+            .map(|replica| replica.get())
+            .transpose();
+        self.load_shedder.record_provider_latency(started.elapsed());
+
+        conn.map_err(|e| e.into())
+    }
+
+    /// Takes a snapshot of the usage of the redis connection pool.
+    pub fn cache_pool_metrics(&self) -> PoolMetrics {
+        PoolMetrics::of(&self.cache)
+    }
+
+    /// Takes a snapshot of the usage of the MySQL connection pool.
+    pub fn persistent_pool_metrics(&self) -> PoolMetrics {
+        PoolMetrics::of(&self.persistent)
+    }
+
+    /// Takes a snapshot of the usage of the MySQL read replica's connection
+    /// pool, if the deployment has a replica.
+    pub fn replica_pool_metrics(&self) -> Option<PoolMetrics> {
+        self.replica.as_ref().map(PoolMetrics::of)
+    }
+
+    /// Takes a snapshot of the state of the persistent layer's circuit
+    /// breaker.
+    pub fn breaker_metrics(&self) -> BreakerMetrics {
+        self.breaker.metrics()
+    }
+
+    /// Takes a snapshot of the state of the node's load shedder.
+    pub fn shedding_metrics(&self) -> SheddingMetrics {
+        self.load_shedder.metrics()
+    }
+
+    /// Takes a snapshot of the hits, misses, and errors of the reads sent to
+    /// the caching layer by each module.
+    pub fn lookup_metrics(&self) -> LookupMetrics {
+        self.lookups.metrics()
+    }
+
+    /// Gets the token that must be presented to access administrative routes.
+    pub fn admin_token(&self) -> &str {
+        &self.admin_token
+    }
+
+    /// Gets the addresses of the reverse proxies whose forwarded headers are
+    /// trusted to name the client making a request.
+    pub fn trusted_proxies(&self) -> &[IpAddr] {
+        &self.trusted_proxies
+    }
+
+    /// Gets the secret that requests to the stream status webhook must be
+    /// signed with, if the webhook is enabled.
+    pub fn stream_webhook_secret(&self) -> Option<&str> {
+        self.stream_webhook_secret.as_deref()
+    }
+
+    /// Gets the announcement sent to the chat when the stream goes live, if
+    /// any.
+    pub fn live_announcement(&self) -> Option<&str> {
+        self.live_announcement.as_deref()
+    }
+
+    /// Gets the roles assigned to each newly registered user.
+    pub fn default_roles(&self) -> &[Role] {
+        &self.default_roles
+    }
+
+    /// Gets the dispatcher used to handle commands issued by chatters.
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
+    /// Gets the credentials issued to gnomegg by the given oauth provider, if
+    /// the provider is enabled.
+    pub fn oauth_credentials(&self, provider: OauthProvider) -> Option<&OauthCredentials> {
+        self.oauth.get(&provider)
+    }
+
+    /// Gets the keys with which session tokens are signed and verified.
+    pub fn signing_keys(&self) -> &KeySet {
+        &self.signing_keys
+    }
+
+    /// Gets the keyring with which linked account IDs are encrypted at rest,
+    /// if encryption is enabled.
+    pub fn keyring(&self) -> Option<&Keyring> {
+        self.keyring.as_ref()
+    }
+
+    /// Gets the directory in which uploaded avatars are stored.
+    pub fn avatar_dir(&self) -> &PathBuf {
+        &self.avatar_dir
+    }
+
+    /// Gets the directory holding the chat frontend served alongside the API,
+    /// if any.
+    pub fn frontend_dir(&self) -> Option<&Path> {
+        self.frontend_dir.as_deref()
+    }
+
+    /// Gets the breaker keeping reads from reaching the persistent layer
+    /// while it is failing.
+    pub fn breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.breaker
+    }
+
+    /// Gets the counts of the hits, misses, and errors of the reads sent to
+    /// the caching layer.
+    pub fn lookups(&self) -> &Arc<CacheLookups> {
+        &self.lookups
+    }
+
+    /// Gets the copies of frequently read values kept by this node.
+    pub fn local_cache(&self) -> &LocalCache {
+        &self.local_cache
+    }
+
+    /// Gets the way in which idempotent operations are retried when redis
+    /// can't be reached.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Gets the prefix prepended to each redis key and channel used by the
+    /// deployment.
+    pub fn key_prefix(&self) -> &str {
+        &self.key_prefix
+    }
+
+    /// Gets the outbox holding the moderation writes that couldn't be
+    /// persisted while the persistent layer was down, if any.
+    pub fn outbox(&self) -> Option<&Arc<dyn Outbox>> {
+        self.outbox.as_ref()
+    }
+
+    /// Gets the shedder rejecting low-priority requests while this node is
+    /// overloaded.
+    pub fn load_shedder(&self) -> &Arc<LoadShedder> {
+        &self.load_shedder
+    }
+
+    /// Gets the policy determining which responses are compressed.
+    pub fn compression(&self) -> CompressionPolicy {
+        self.compression
+    }
+
+    /// Gets the relay feeding the event streams held open by this node.
+    pub fn event_relay(&self) -> &EventRelay {
+        &self.event_relay
+    }
+
+    /// Gets the layers backing the bans and mutes providers.
+    pub fn moderation_layers(&self) -> Layers {
+        self.moderation_layers
+    }
+
+    /// Gets the name under which this node registers the connections that it
+    /// holds open.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+}
+
+/// Starts the gnomegg HTTP server on the given address, registering each of
+/// the service groups designated by the server's modules.
+///
+/// # Arguments
+///
+/// * `addr` - The address that the server should listen on (e.g.
+/// 127.0.0.1:8080)
+/// * `tls` - (optional) The paths of the PEM-encoded certificate chain and
+/// private key with which requests should be served over TLS
+/// * `state` - The shared state that should be made available to each
+/// handler
+pub async fn serve(addr: &str, tls: Option<(&Path, &Path)>, state: State) -> io::Result<()> {
+    let state = Data::new(state);
+    last_seen::spawn_flush_task(state.clone());
+    announcements::spawn_announcement_task(state.clone());
+    stats::spawn_rollup_task(state.clone());
+    embeds::spawn_embed_task(state.clone());
+    bot_commands::spawn_webhook_task(state.clone());
+    invalidation::spawn_invalidation_task(state.clone());
+    outbox::spawn_replay_task(state.clone());
+    events::spawn_relay_task(state.clone());
+    events::spawn_heartbeat_task(state.clone());
+
+    let server = HttpServer::new(move || {
+        let load_shedder = state.load_shedder().clone();
+        let compression = state.compression();
+
+        App::new()
+            .app_data(state.clone())
+            .wrap_fn(move |req, srv| {
+                // Each worker's event loop is probed for lag from within the
+                // worker itself
+                shedding::ensure_probe(&load_shedder);
+
+                // Low-priority requests are rejected while the node is
+                // overloaded, leaving room for chat fanout and moderation
+                if load_shedder.should_shed(req.path()) {
+                    let mut res = req.error_response(Problem::new(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "overloaded",
+                        "the server is overloaded; try again later",
+                    ));
+                    res.headers_mut()
+                        .insert(RETRY_AFTER, HeaderValue::from(SHED_RETRY_AFTER_SECONDS));
+
+                    return Either::Left(ready(Ok(res)));
+                }
+
+                Either::Right(srv.call(req))
+            })
+            .wrap_fn(|req, srv| {
+                let res = srv.call(req);
+
+                async move {
+                    let res = res.await?;
+
+                    // Requests made while impersonating a user are recorded
+                    // once answered, so that their outcome is known. A request
+                    // that can't be recorded is reported as failed.
+                    audit::record_impersonated_request(res.request(), res.status())?;
+
+                    Ok(res)
+                }
+            })
+            .wrap_fn(|req, srv| {
+                // Each request is answered within its own span, such that the
+                // spans of the provider calls made on its behalf, and the
+                // events logged meanwhile, carry its ID
+                let request_id = telemetry::request_id(&req);
+                let span = info_span!(
+                    "request",
+                    request_id = %request_id,
+                    method = %req.method(),
+                    path = %req.path(),
+                    status = field::Empty,
+                    elapsed_ms = field::Empty,
+                );
+                let started = Instant::now();
+                let res = span.in_scope(|| srv.call(req));
+
+                async move {
+                    // Errors are answered as problem details carrying the ID
+                    // of the request, so that it may be quoted in bug reports
+                    let mut res = telemetry::tag_error(res.await?, &request_id);
+
+                    let status = res.status().as_u16();
+                    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+                    let span = tracing::Span::current();
+                    span.record("status", status);
+                    span.record("elapsed_ms", elapsed_ms);
+
+                    // Each request is logged once answered, alongside the
+                    // route that answered it and the user who made it, if any
+                    tracing::info!(
+                        target: telemetry::ACCESS_LOG_TARGET,
+                        request_id = %request_id,
+                        method = %res.request().method(),
+                        route = res.request().match_pattern().as_deref(),
+                        path = %res.request().path(),
+                        status,
+                        elapsed_ms,
+                        user_id = auth::claimed_user_id(res.request()),
+                        "answered request"
+                    );
+
+                    if let Ok(id) = HeaderValue::from_str(&request_id) {
+                        res.headers_mut()
+                            .insert(HeaderName::from_static(REQUEST_ID_HEADER), id);
+                    }
+
+                    Ok(res)
+                }
+                .instrument(span)
+            })
+            .wrap_fn(move |req, srv| {
+                let res = srv.call(req);
+
+                async move {
+                    let mut res = res.await?;
+
+                    // Responses are compressed once their final body is
+                    // known, including the bodies of errors
+                    compression.apply(&mut res);
+
+                    Ok(res)
+                }
+            })
+            .wrap(Compress::default())
+            .service(bans::build_service_group())
+            .service(mutes::build_service_group())
+            .service(roles::build_service_group())
+            .service(users::build_service_group())
+            .service(me::build_service_group())
+            .service(settings::build_service_group())
+            .service(export::build_service_group())
+            .service(avatars::build_service_group())
+            .service(oauth::build_service_group())
+            .service(refresh_tokens::build_service_group())
+            .service(sessions::build_service_group())
+            .service(two_factor::build_service_group())
+            .service(jwks::build_service_group())
+            .service(audit::build_service_group())
+            .service(impersonation::build_service_group())
+            .service(history::build_service_group())
+            .service(whispers::build_service_group())
+            .service(ignores::build_service_group())
+            .service(emotes::build_service_group())
+            .service(subscriptions::build_service_group())
+            .service(flairs::build_service_group())
+            .service(links::build_service_group())
+            .service(polls::build_service_group())
+            .service(announcements::build_service_group())
+            .service(stats::build_service_group())
+            .service(approvals::build_service_group())
+            .service(embeds::build_service_group())
+            .service(bot_commands::build_service_group())
+            .service(donations::build_service_group())
+            .service(stream::build_service_group())
+            .service(chat::build_service_group())
+            .service(events::build_service_group())
+            .service(poll::build_service_group())
+            .service(admin::build_service_group())
+            .service(metrics::build_service_group())
+            .service(version::build_service_group())
+            .service(health::build_service_group())
+            .configure(configure_graphql)
+            .default_service(web::route().to(serve_default))
+    });
+
+    match tls {
+        Some((cert_path, key_path)) => {
+            server.bind_rustls(addr, tls_config(cert_path, key_path)?)?
+        }
+        None => server.bind(addr)?,
+    }
+    .run()
+    .await
+}
+
+/// Loads the TLS configuration with which requests should be served from the
+/// given PEM-encoded certificate chain and private key. Both PKCS#8 and RSA
+/// private keys are accepted.
+///
+/// # Arguments
+///
+/// * `cert_path` - The path of the PEM-encoded certificate chain
+/// * `key_path` - The path of the PEM-encoded private key
+fn tls_config(cert_path: &Path, key_path: &Path) -> io::Result<ServerConfig> {
+    let invalid =
+        |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}", what));
+
+    let chain = certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| invalid("certificate chain"))?;
+
+    let mut keys = pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))
+        .map_err(|_| invalid("private key"))?;
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut BufReader::new(File::open(key_path)?))
+            .map_err(|_| invalid("private key"))?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid("private key"))?;
+
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(chain, key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(config)
+}
+
+/// Registers the GraphQL endpoint, if the server was built with the graphql
+/// feature.
+fn configure_graphql(_cfg: &mut ServiceConfig) {
+    #[cfg(feature = "graphql")]
+    _cfg.service(super::modules::graphql::build_service_group());
+}
+
+/// Answers requests that no route matched with the chat frontend, if the
+/// server was built with the frontend feature, or with 404 Not Found
+/// otherwise.
+async fn serve_default(_state: Data<State>, _req: HttpRequest) -> Result<HttpResponse, HttpError> {
+    #[cfg(feature = "frontend")]
+    return super::modules::frontend::serve_asset(_state, _req).await;
+
+    #[cfg(not(feature = "frontend"))]
+    Ok(HttpResponse::NotFound().finish())
+}
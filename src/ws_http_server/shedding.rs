@@ -0,0 +1,328 @@
+use serde::Serialize;
+use tokio::{task, time};
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of milliseconds by which a worker's event loop may fall behind
+/// before the server is considered overloaded, if no limit is specified.
+pub const DEFAULT_MAX_LOOP_LAG_MS: u64 = 200;
+
+/// The number of milliseconds that checking out a connection to a backend may
+/// take before the server is considered overloaded, if no limit is specified.
+pub const DEFAULT_MAX_PROVIDER_LATENCY_MS: u64 = 500;
+
+/// The number of seconds after which clients are asked to retry shed
+/// requests.
+pub const SHED_RETRY_AFTER_SECONDS: u64 = 5;
+
+/// The paths under which the routes that may be shed are mounted. Reads that
+/// are expensive, and that clients can do without for a while, are shed;
+/// chat fanout and moderation checks never are.
+pub const SHEDDABLE_PATHS: [&str; 3] = ["/chat/history", "/stats", "/exports"];
+
+/// The interval at which each worker's event loop is probed for lag.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The reciprocal of the weight given to each new sample by the moving
+/// averages, such that a single slow sample doesn't trip the shedder.
+const SMOOTHING: u64 = 8;
+
+thread_local! {
+    /// Whether or not the event loop of the worker running on this thread is
+    /// already being probed
+    static PROBING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// LoadShedder keeps the server responsive under overload by rejecting
+/// low-priority requests while the server is overloaded. The server is
+/// considered overloaded while the moving average of either its workers'
+/// event loop lag, or the time taken to check out a connection to a backend,
+/// exceeds its limit.
+#[derive(Debug)]
+pub struct LoadShedder {
+    /// The lag beyond which the server is overloaded, or zero if lag is
+    /// ignored
+    max_loop_lag: Duration,
+
+    /// The checkout latency beyond which the server is overloaded, or zero if
+    /// latency is ignored
+    max_provider_latency: Duration,
+
+    /// The moving average of the event loop lag, in microseconds
+    loop_lag_us: AtomicU64,
+
+    /// The moving average of the checkout latency, in microseconds
+    provider_latency_us: AtomicU64,
+
+    /// Whether or not the server was overloaded when last checked
+    overloaded: AtomicBool,
+
+    /// The number of requests that have been shed
+    shed: AtomicU64,
+}
+
+impl Default for LoadShedder {
+    fn default() -> Self {
+        Self {
+            max_loop_lag: Duration::from_millis(DEFAULT_MAX_LOOP_LAG_MS),
+            max_provider_latency: Duration::from_millis(DEFAULT_MAX_PROVIDER_LATENCY_MS),
+            loop_lag_us: AtomicU64::new(0),
+            provider_latency_us: AtomicU64::new(0),
+            overloaded: AtomicBool::new(false),
+            shed: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LoadShedder {
+    /// Consumes the shedder, and modifies it according to the provided
+    /// event loop lag limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_loop_lag` - The lag beyond which the server should be
+    /// considered overloaded, or zero if lag should be ignored
+    pub fn with_max_loop_lag(mut self, max_loop_lag: Duration) -> Self {
+        self.max_loop_lag = max_loop_lag;
+
+        self
+    }
+
+    /// Consumes the shedder, and modifies it according to the provided
+    /// checkout latency limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_provider_latency` - The checkout latency beyond which the
+    /// server should be considered overloaded, or zero if latency should be
+    /// ignored
+    pub fn with_max_provider_latency(mut self, max_provider_latency: Duration) -> Self {
+        self.max_provider_latency = max_provider_latency;
+
+        self
+    }
+
+    /// Records the lag of a worker's event loop, which is the time by which a
+    /// timer fired late.
+    ///
+    /// # Arguments
+    ///
+    /// * `lag` - The lag observed by the worker
+    pub fn record_loop_lag(&self, lag: Duration) {
+        record(&self.loop_lag_us, lag);
+    }
+
+    /// Records the time taken to check out a connection to a backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `latency` - The time taken to check out the connection
+    pub fn record_provider_latency(&self, latency: Duration) {
+        record(&self.provider_latency_us, latency);
+    }
+
+    /// Determines whether or not the server is currently overloaded.
+    pub fn overloaded(&self) -> bool {
+        let exceeds = |average: &AtomicU64, max: Duration| {
+            max > Duration::from_secs(0)
+                && Duration::from_micros(average.load(Ordering::Relaxed)) > max
+        };
+
+        let overloaded = exceeds(&self.loop_lag_us, self.max_loop_lag)
+            || exceeds(&self.provider_latency_us, self.max_provider_latency);
+
+        // Shedding is only logged as it starts and stops, rather than for
+        // every request shed
+        if self.overloaded.swap(overloaded, Ordering::Relaxed) != overloaded {
+            if overloaded {
+                tracing::warn!(
+                    loop_lag_us = self.loop_lag_us.load(Ordering::Relaxed),
+                    provider_latency_us = self.provider_latency_us.load(Ordering::Relaxed),
+                    "overloaded; shedding low-priority requests"
+                );
+            } else {
+                tracing::info!("no longer overloaded; serving every request");
+            }
+        }
+
+        overloaded
+    }
+
+    /// Determines whether or not a request to the given path should be
+    /// rejected, counting it as shed if it should be.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the request
+    pub fn should_shed(&self, path: &str) -> bool {
+        let shed = is_sheddable(path) && self.overloaded();
+        if shed {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        shed
+    }
+
+    /// Takes a snapshot of the state of the shedder.
+    pub fn metrics(&self) -> SheddingMetrics {
+        SheddingMetrics {
+            overloaded: self.overloaded(),
+            loop_lag_ms: self.loop_lag_us.load(Ordering::Relaxed) / 1000,
+            provider_latency_ms: self.provider_latency_us.load(Ordering::Relaxed) / 1000,
+            shed: self.shed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// SheddingMetrics represents a snapshot of the state of a load shedder.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct SheddingMetrics {
+    /// Whether or not low-priority requests are being shed
+    overloaded: bool,
+
+    /// The moving average of the workers' event loop lag, in milliseconds
+    loop_lag_ms: u64,
+
+    /// The moving average of the time taken to check out a connection to a
+    /// backend, in milliseconds
+    provider_latency_ms: u64,
+
+    /// The number of requests that have been shed
+    shed: u64,
+}
+
+impl SheddingMetrics {
+    /// Retreives whether or not low-priority requests are being shed.
+    pub fn overloaded(&self) -> bool {
+        self.overloaded
+    }
+
+    /// Retreives the moving average of the workers' event loop lag, in
+    /// milliseconds.
+    pub fn loop_lag_ms(&self) -> u64 {
+        self.loop_lag_ms
+    }
+
+    /// Retreives the moving average of the time taken to check out a
+    /// connection to a backend, in milliseconds.
+    pub fn provider_latency_ms(&self) -> u64 {
+        self.provider_latency_ms
+    }
+
+    /// Retreives the number of requests that have been shed.
+    pub fn shed(&self) -> u64 {
+        self.shed
+    }
+}
+
+/// Determines whether or not a request to the given path may be shed.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request
+pub fn is_sheddable(path: &str) -> bool {
+    SHEDDABLE_PATHS.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Starts probing the event loop of the worker running on the current thread
+/// for lag, unless it is already being probed. Must be called from within a
+/// worker, such that the probe runs on the worker's event loop.
+///
+/// # Arguments
+///
+/// * `shedder` - The shedder that the observed lag should be recorded by
+pub(crate) fn ensure_probe(shedder: &Arc<LoadShedder>) {
+    if PROBING.with(|probing| probing.replace(true)) {
+        return;
+    }
+
+    let shedder = shedder.clone();
+    task::spawn_local(async move {
+        loop {
+            let started = Instant::now();
+            time::delay_for(PROBE_INTERVAL).await;
+
+            // The timer fires late by however long the loop was kept busy
+            shedder.record_loop_lag(started.elapsed().saturating_sub(PROBE_INTERVAL));
+        }
+    });
+}
+
+/// Folds the given sample into the given moving average.
+///
+/// # Arguments
+///
+/// * `average` - The moving average, in microseconds
+/// * `sample` - The new sample
+fn record(average: &AtomicU64, sample: Duration) {
+    let sample = sample.as_micros().min(u64::MAX as u128) as u64;
+
+    // Samples are folded in one at a time, so none are lost to races between
+    // workers
+    let _ = average.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        Some(current - current / SMOOTHING + sample / SMOOTHING)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sheddable() {
+        assert!(is_sheddable("/chat/history"));
+        assert!(is_sheddable("/stats/top"));
+        assert!(is_sheddable("/exports/abc"));
+
+        assert!(!is_sheddable("/chat"));
+        assert!(!is_sheddable("/statsd"));
+        assert!(!is_sheddable("/bans"));
+        assert!(!is_sheddable("/events"));
+    }
+
+    #[test]
+    fn test_should_shed() {
+        let shedder = LoadShedder::default().with_max_provider_latency(Duration::from_millis(10));
+        assert!(!shedder.should_shed("/chat/history"));
+
+        // A single slow checkout doesn't trip the shedder
+        shedder.record_provider_latency(Duration::from_millis(50));
+        assert!(!shedder.overloaded());
+
+        for _ in 0..32 {
+            shedder.record_provider_latency(Duration::from_millis(50));
+        }
+        assert!(shedder.should_shed("/chat/history"));
+        assert!(!shedder.should_shed("/bans/1"));
+        assert_eq!(shedder.metrics().shed(), 1);
+
+        // The shedder recovers once the backends do
+        for _ in 0..64 {
+            shedder.record_provider_latency(Duration::from_millis(0));
+        }
+        assert!(!shedder.should_shed("/chat/history"));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let shedder = LoadShedder::default()
+            .with_max_loop_lag(Duration::from_secs(0))
+            .with_max_provider_latency(Duration::from_secs(0));
+
+        for _ in 0..32 {
+            shedder.record_loop_lag(Duration::from_secs(1));
+            shedder.record_provider_latency(Duration::from_secs(1));
+        }
+        assert!(!shedder.overloaded());
+    }
+}
@@ -0,0 +1,183 @@
+use actix_web::{
+    dev::{Body, MessageBody, ServiceRequest, ServiceResponse},
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE},
+    ResponseError,
+};
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use super::problem::Problem;
+
+use std::{io, str::FromStr};
+
+/// The filter applied to spans and events if none is specified through the
+/// RUST_LOG environment variable.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// The header carrying the ID of a request, which is propagated into each of
+/// the events logged while answering the request, and echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The target of the event logged once each request is answered, such that
+/// access logs may be filtered apart from other events (e.g.
+/// RUST_LOG=access=info).
+pub const ACCESS_LOG_TARGET: &str = "access";
+
+/// LogFormat represents the format in which spans and events are written to
+/// stdout.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+
+    /// One JSON object per line, for consumption by a log aggregator
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("expected either text or json, got {:?}", s)),
+        }
+    }
+}
+
+/// The name under which the server's spans are exported.
+#[cfg(feature = "otlp")]
+const SERVICE_NAME: &str = "gnomegg";
+
+/// Gets the ID of the given request: the ID provided by the client or a
+/// proxy in front of the server, if any, or a random ID otherwise.
+///
+/// # Arguments
+///
+/// * `req` - The request whose ID should be obtained
+pub fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64)
+        .map(|id| id.to_owned())
+        .unwrap_or_else(|| format!("{:016x}", thread_rng().gen::<u64>()))
+}
+
+/// Replaces the body of the given response with a problem detail object
+/// describing its error alongside the given request ID, if the response was
+/// produced by an error. Error responses that carry no body of their own
+/// (e.g. a bare 404 Not Found) are described by their status. Other responses
+/// are returned untouched.
+///
+/// # Arguments
+///
+/// * `res` - The response to the request
+/// * `request_id` - The ID of the request
+pub fn tag_error(res: ServiceResponse<Body>, request_id: &str) -> ServiceResponse<Body> {
+    let status = res.status();
+    let problem = match res.response().error() {
+        Some(error) => Problem::of(error),
+        None if (status.is_client_error() || status.is_server_error())
+            && res.response().body().size().is_eof() =>
+        {
+            Problem::from_status(status)
+        }
+        None => return res,
+    };
+
+    let mut tagged = problem.with_request_id(request_id).error_response();
+
+    // Headers set alongside the error (e.g. Retry-After) are kept
+    for (name, value) in res.headers().iter() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            tagged.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+
+    res.into_response(tagged)
+}
+
+/// Installs the global subscriber, which logs each span and event to stdout
+/// in the given format according to the RUST_LOG filter, and exports spans
+/// to the given OTLP collector, if any.
+///
+/// # Arguments
+///
+/// * `format` - The format in which spans and events should be logged
+/// * `otlp_endpoint` - (optional) The URL of the OTLP/HTTP collector to
+/// which spans should be exported. Exporting spans requires the `otlp`
+/// feature.
+pub fn init(format: LogFormat, otlp_endpoint: Option<&str>) -> io::Result<()> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+
+    #[cfg(feature = "otlp")]
+    let otlp = otlp_endpoint.map(otlp_layer).transpose()?;
+
+    #[cfg(not(feature = "otlp"))]
+    let otlp: Option<tracing_subscriber::layer::Identity> = match otlp_endpoint {
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "gnomegg must be built with the otlp feature to export spans",
+            ))
+        }
+        None => None,
+    };
+
+    // Only one of the formatting layers is installed
+    let (text, json) = match format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(otlp)
+        .try_init()
+        .map_err(io::Error::other)
+}
+
+/// Builds the layer exporting spans to the given OTLP/HTTP collector. Spans
+/// are exported from a dedicated thread as they end, such that handlers
+/// aren't held up by the collector.
+///
+/// # Arguments
+///
+/// * `endpoint` - The URL of the collector
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(
+    endpoint: &str,
+) -> io::Result<tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{trace, Resource};
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                SERVICE_NAME,
+            )])),
+        )
+        .install_simple()
+        .map_err(io::Error::other)?;
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
@@ -0,0 +1,225 @@
+use openssl::{error::ErrorStack, hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use rand::{thread_rng, RngCore};
+
+use std::fmt;
+
+/// The number of seconds for which each code remains valid.
+pub const STEP: i64 = 30;
+
+/// The number of digits in each code.
+const DIGITS: usize = 6;
+
+/// The number of bytes in a randomly generated secret.
+const SECRET_LENGTH: usize = 20;
+
+/// The number of steps before or after the current step whose codes are
+/// accepted, so as to tolerate clock drift.
+const WINDOW: i64 = 1;
+
+/// The alphabet used to encode secrets, as defined in RFC 4648.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// TotpSecret represents the secret shared between gnomegg and a user's
+/// authenticator app, from which time-based one-time codes are derived as
+/// defined in RFC 6238.
+#[derive(Clone, PartialEq)]
+pub struct TotpSecret {
+    secret: Vec<u8>,
+}
+
+impl fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TotpSecret(..)")
+    }
+}
+
+impl TotpSecret {
+    /// Creates a new TOTP secret from the given bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The raw secret
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret }
+    }
+
+    /// Generates a new random TOTP secret.
+    pub fn random() -> Self {
+        let mut secret = vec![0; SECRET_LENGTH];
+        thread_rng().fill_bytes(&mut secret);
+
+        Self::new(secret)
+    }
+
+    /// Decodes a secret from its unpadded base32 representation, as displayed
+    /// to users and embedded in provisioning URIs.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded` - The base32-encoded secret
+    pub fn from_base32(encoded: &str) -> Option<Self> {
+        let mut secret = Vec::with_capacity(encoded.len() * 5 / 8);
+        let (mut buffer, mut bits) = (0u64, 0);
+
+        for c in encoded.trim_end_matches('=').bytes() {
+            let value = BASE32_ALPHABET
+                .iter()
+                .position(|a| *a == c.to_ascii_uppercase())?;
+
+            buffer = (buffer << 5) | value as u64;
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                secret.push((buffer >> bits) as u8);
+            }
+        }
+
+        Some(Self::new(secret))
+    }
+
+    /// Encodes the secret in unpadded base32.
+    pub fn to_base32(&self) -> String {
+        let mut encoded = String::with_capacity((self.secret.len() * 8).div_ceil(5));
+        let (mut buffer, mut bits) = (0u64, 0);
+
+        for byte in &self.secret {
+            buffer = (buffer << 8) | *byte as u64;
+            bits += 8;
+
+            while bits >= 5 {
+                bits -= 5;
+                encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+            }
+        }
+
+        if bits > 0 {
+            encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+        }
+
+        encoded
+    }
+
+    /// Builds an otpauth URI with which the secret may be added to an
+    /// authenticator app, typically by rendering it as a QR code.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The name of the account that the secret belongs to
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        let encode = |s: &str| {
+            s.bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    b => format!("%{:02X}", b),
+                })
+                .collect::<String>()
+        };
+
+        format!(
+            "otpauth://totp/gnomegg:{}?secret={}&issuer=gnomegg&algorithm=SHA1&digits={}&period={}",
+            encode(account),
+            self.to_base32(),
+            DIGITS,
+            STEP
+        )
+    }
+
+    /// Computes the code for the given time step.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The number of steps elapsed since the UNIX epoch
+    pub fn code_at(&self, step: i64) -> Result<String, ErrorStack> {
+        let key = PKey::hmac(&self.secret)?;
+        let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+        signer.update(&step.to_be_bytes())?;
+        let mac = signer.sign_to_vec()?;
+
+        // Dynamic truncation, as defined in RFC 4226
+        let offset = (mac[mac.len() - 1] & 0xf) as usize;
+        let binary = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+
+        Ok(format!(
+            "{:0width$}",
+            binary % 10u32.pow(DIGITS as u32),
+            width = DIGITS
+        ))
+    }
+
+    /// Checks the given code against the codes valid at the given time,
+    /// returning the step that the code belongs to if it is valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code presented by the user
+    /// * `timestamp` - The current UNIX timestamp
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gnomegg::ws_http_server::totp::{TotpSecret, STEP};
+    /// # use std::error::Error;
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let secret = TotpSecret::random();
+    /// let code = secret.code_at(1_000_000 / STEP)?;
+    ///
+    /// assert_eq!(secret.verify(&code, 1_000_000)?, Some(1_000_000 / STEP));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify(&self, code: &str, timestamp: i64) -> Result<Option<i64>, ErrorStack> {
+        let current = timestamp / STEP;
+
+        for step in current - WINDOW..=current + WINDOW {
+            let expected = self.code_at(step)?;
+
+            if code.len() == expected.len() && memcmp::eq(code.as_bytes(), expected.as_bytes()) {
+                return Ok(Some(step));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    #[test]
+    fn test_rfc6238() -> Result<(), Box<dyn Error>> {
+        // The SHA1 test vectors from RFC 6238, truncated to six digits
+        let secret = TotpSecret::new(b"12345678901234567890".to_vec());
+
+        assert_eq!(secret.code_at(59 / STEP)?, "287082");
+        assert_eq!(secret.code_at(1_111_111_109 / STEP)?, "081804");
+        assert_eq!(secret.code_at(2_000_000_000 / STEP)?, "279037");
+
+        assert_eq!(secret.verify("287082", 59)?, Some(1));
+        assert_eq!(secret.verify("287082", 59 + STEP)?, Some(1));
+        assert_eq!(secret.verify("287082", 59 + 2 * STEP)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_base32() {
+        let secret = TotpSecret::new(b"12345678901234567890".to_vec());
+        let encoded = secret.to_base32();
+
+        assert_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(TotpSecret::from_base32(&encoded), Some(secret));
+        assert_eq!(TotpSecret::from_base32("not base32!"), None);
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Utc};
+use diesel::RunQueryDsl;
+
+use super::{
+    super::spec::{
+        backend::PersistentConnection,
+        ban::Ban,
+        mute::Mute,
+        schema::{bans, mutes, roles},
+        user::{Role, RoleEntry},
+    },
+    modules::{Cache, Layers, ProviderError},
+    server::State,
+};
+
+/// The number of entries written to redis in each round trip while warming
+/// the cache.
+const WARMUP_BATCH_SIZE: usize = 500;
+
+/// SanctionEntry represents a sanction as it is cached: its key, its fields,
+/// and the (optional) number of milliseconds until it expires.
+type SanctionEntry = (String, Vec<(&'static str, String)>, Option<u64>);
+
+/// Warmed represents the number of entries preloaded into the caching layer
+/// by a warm-up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Warmed {
+    /// The number of active bans preloaded
+    pub bans: usize,
+
+    /// The number of active mutes preloaded
+    pub mutes: usize,
+
+    /// The number of users whose roles were preloaded
+    pub roles: usize,
+}
+
+/// Preloads the active bans and mutes, and each user's roles, from the
+/// persistent layer into the caching layer, such that the first wave of
+/// connections after redis restarts doesn't miss the cache all at once.
+/// Bans and mutes are cached as hashes, which expire once they lapse. Entries already present in
+/// the cache are left untouched, as they may be newer than their persisted
+/// counterparts. Bans and mutes are only preloaded if they are backed by
+/// both layers.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+/// * `moderation_layers` - The layers backing the bans and mutes providers
+pub fn warm(
+    cache: &mut Cache,
+    persistent_conn: &PersistentConnection,
+    moderation_layers: Layers,
+) -> Result<Warmed, ProviderError> {
+    let mut warmed = Warmed::default();
+
+    if moderation_layers == Layers::Hybrid {
+        warmed.bans = warm_bans(cache, persistent_conn)?;
+        warmed.mutes = warm_mutes(cache, persistent_conn)?;
+    }
+
+    warmed.roles = warm_roles(cache, persistent_conn)?;
+
+    Ok(warmed)
+}
+
+/// Preloads the caching layer of the given server state from its persistent
+/// layer, logging the number of entries that were preloaded.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state used to open connections to each layer
+pub fn warm_state(state: &State) -> Result<Warmed, ProviderError> {
+    let mut conn = state.cache_connection()?;
+    let persistent_conn = state.persistent_connection()?;
+
+    let warmed = warm(
+        &mut Cache::new(&mut conn).with_prefix(state.key_prefix()),
+        &persistent_conn,
+        state.moderation_layers(),
+    )?;
+    tracing::info!(
+        bans = warmed.bans,
+        mutes = warmed.mutes,
+        roles = warmed.roles,
+        "warmed the caching layer"
+    );
+
+    Ok(warmed)
+}
+
+/// Determines the number of milliseconds remaining until a sanction expiring
+/// at the given time lapses, if it hasn't lapsed yet.
+///
+/// # Arguments
+///
+/// * `expires_at` - The time at which the sanction lapses
+fn remaining_millis(expires_at: DateTime<Utc>) -> Option<u64> {
+    Some((expires_at - Utc::now()).num_milliseconds())
+        .filter(|ms| *ms > 0)
+        .map(|ms| ms as u64)
+}
+
+/// Preloads each active ban into the cache, under the banned user's ID and
+/// address. Returns the number of bans preloaded.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+fn warm_bans(
+    cache: &mut Cache,
+    persistent_conn: &PersistentConnection,
+) -> Result<usize, ProviderError> {
+    let (mut entries, mut warmed) = (Vec::new(), 0);
+
+    for ban in bans::table.load::<Ban>(persistent_conn)? {
+        // Permanent bans are cached without an expiry
+        let ttl = match ban.expires_at() {
+            Some(expires_at) => match remaining_millis(expires_at) {
+                Some(ms) => Some(ms),
+                None => continue,
+            },
+            None => None,
+        };
+        let fields = ban.to_fields();
+
+        if let Some(addr) = ban.address() {
+            entries.push((
+                cache.key(format_args!("banned_addr::{}", addr)),
+                fields.clone(),
+                ttl,
+            ));
+        }
+
+        entries.push((
+            cache.key(format_args!("banned::{}", ban.concerns())),
+            fields,
+            ttl,
+        ));
+        warmed += 1;
+    }
+
+    set_missing(cache, &entries)?;
+
+    Ok(warmed)
+}
+
+/// Preloads each active mute into the cache. Returns the number of mutes
+/// preloaded.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+fn warm_mutes(
+    cache: &mut Cache,
+    persistent_conn: &PersistentConnection,
+) -> Result<usize, ProviderError> {
+    let mut entries = Vec::new();
+
+    for mute in mutes::table.load::<Mute>(persistent_conn)? {
+        let ttl = match remaining_millis(mute.expires_at()) {
+            Some(ms) => ms,
+            None => continue,
+        };
+
+        entries.push((
+            cache.key(format_args!("muted::{}", mute.concerns())),
+            mute.to_fields(),
+            Some(ttl),
+        ));
+    }
+
+    set_missing(cache, &entries)?;
+
+    Ok(entries.len())
+}
+
+/// Determines which of the given keys aren't cached yet, in a single round
+/// trip to the cache.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `keys` - The keys that should be checked
+fn uncached_keys<'a>(
+    cache: &mut Cache,
+    keys: impl Iterator<Item = &'a String>,
+) -> Result<Vec<bool>, ProviderError> {
+    let cached: Vec<bool> = cache.pipeline(|pipe| {
+        for key in keys {
+            pipe.cmd("EXISTS").arg(key);
+        }
+    })?;
+
+    Ok(cached.into_iter().map(|cached| !cached).collect())
+}
+
+/// Writes each of the given sanctions to the cache as a hash in batches,
+/// unless a sanction is already cached under its key.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `entries` - The sanctions that should be cached
+fn set_missing(cache: &mut Cache, entries: &[SanctionEntry]) -> Result<(), ProviderError> {
+    for batch in entries.chunks(WARMUP_BATCH_SIZE) {
+        let uncached = uncached_keys(cache, batch.iter().map(|(key, _, _)| key))?;
+
+        cache.pipeline::<()>(|pipe| {
+            for ((key, fields, ttl), _) in
+                batch.iter().zip(uncached).filter(|(_, uncached)| *uncached)
+            {
+                pipe.cmd("HSET").arg(key).arg(fields.as_slice()).ignore();
+
+                if let Some(ttl) = ttl {
+                    pipe.cmd("PEXPIRE").arg(key).arg(*ttl).ignore();
+                }
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Preloads the roles of each user holding at least one role into the cache,
+/// unless their roles are already cached. Returns the number of users whose
+/// roles were preloaded.
+///
+/// # Arguments
+///
+/// * `cache` - The caching layer that should be warmed
+/// * `persistent_conn` - A connection to the MySQL persistence layer
+fn warm_roles(
+    cache: &mut Cache,
+    persistent_conn: &PersistentConnection,
+) -> Result<usize, ProviderError> {
+    let entries = roles::table
+        .load::<RoleEntry>(persistent_conn)?
+        .iter()
+        .map(|entry| {
+            (
+                cache.key(format_args!("roles::{}", entry.concerns())),
+                Vec::<Role>::from(entry),
+            )
+        })
+        .filter(|(_, roles)| !roles.is_empty())
+        .collect::<Vec<(String, Vec<Role>)>>();
+    let mut warmed = 0;
+
+    for batch in entries.chunks(WARMUP_BATCH_SIZE) {
+        let uncached = uncached_keys(cache, batch.iter().map(|(key, _)| key))?;
+        let missing = batch
+            .iter()
+            .zip(uncached)
+            .filter(|(_, uncached)| *uncached)
+            .map(|(entry, _)| entry)
+            .collect::<Vec<&(String, Vec<Role>)>>();
+
+        if missing.is_empty() {
+            continue;
+        }
+
+        cache.pipeline::<()>(|pipe| {
+            for (key, roles) in &missing {
+                pipe.cmd("SADD")
+                    .arg(key)
+                    .arg(roles.iter().map(Role::to_str).collect::<Vec<&str>>())
+                    .ignore();
+            }
+        })?;
+        warmed += missing.len();
+    }
+
+    Ok(warmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            super::spec::{schema::users, user::NewUser},
+            modules::{
+                bans::{BanQuery, Provider as BansProvider},
+                mutes::Provider as MutesProvider,
+                Persistent,
+            },
+        },
+        *,
+    };
+    use diesel::{Connection, ExpressionMethods, QueryDsl};
+
+    use std::{env, error::Error};
+
+    #[test]
+    fn test_warm() -> Result<(), Box<dyn Error>> {
+        dotenv::dotenv()?;
+
+        let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+        let persistent_conn =
+            PersistentConnection::establish(&env::var("DATABASE_URL").expect(
+                "DATABASE_URL must be set in a .env file for test to complete successfully",
+            ))?;
+
+        diesel::replace_into(users::table)
+            .values(NewUser::default().with_username("MrMouton"))
+            .execute(&persistent_conn)?;
+        let id = users::dsl::users
+            .filter(users::dsl::username.eq("MrMouton"))
+            .select(users::dsl::id)
+            .first(&persistent_conn)?;
+
+        // Sanctions persisted while the cache was down are preloaded once it
+        // returns
+        let mut persistent = Persistent::new(&persistent_conn);
+        persistent.set_banned(id, true, Some(60_000_000_000), None)?;
+        persistent.set_muted(id, true, Some(60_000_000_000))?;
+
+        let mut cache = Cache::new(&mut conn).with_prefix("warmup::");
+        let warmed = warm(&mut cache, &persistent_conn, Layers::Hybrid)?;
+        assert!(warmed.bans >= 1);
+        assert!(warmed.mutes >= 1);
+        assert!(cache.is_banned(&BanQuery::Id(id))?);
+        assert!(cache.is_muted(id)?);
+
+        // Entries that are already cached aren't replaced
+        cache.set_muted(id, false, None)?;
+        cache.set_muted(id, true, Some(1_000_000_000_000))?;
+        warm(&mut cache, &persistent_conn, Layers::Hybrid)?;
+        assert_eq!(
+            cache.get_mute(id)?.map(|mute| mute.active_for()),
+            Some(chrono::Duration::seconds(1_000))
+        );
+
+        Ok(())
+    }
+}